@@ -0,0 +1,258 @@
+//! Extra outputs written in the same run as the main one (`also_output`), each in the
+//! format its extension names, from the files as they are read once.
+
+use crate::formatter::{FileGroup, Formatter, RunContext};
+use crate::{
+    BudgetSelection, Config, Error, FileEntry, Format, RunSummary, SkipReason, output_template,
+    write_framing_text,
+};
+use anyhow::{Context, Result, bail};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// An extra output file and the formatter that writes it.
+pub(crate) struct AlsoOutput {
+    path: PathBuf,
+    format: Format,
+    formatter: Box<dyn Formatter>,
+    writer: BufWriter<File>,
+}
+
+impl AlsoOutput {
+    /// Resolves the names of the extra outputs and their formats.
+    ///
+    /// Each name gets the `append_date`, `append_git_hash`, or `output_template` suffixes of
+    /// its own stem and extension.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<Vec<(PathBuf, Format)>>` - The file and format of each output, or an error
+    ///   if an extension names no format.
+    pub(crate) fn resolve_all(config: &Config) -> Result<Vec<(PathBuf, Format)>> {
+        config
+            .also_output
+            .iter()
+            .map(|path| {
+                let Some(format) = Format::from_extension(path) else {
+                    bail!(
+                        "Cannot tell the format of --also-output {} from its extension \
+                         (expected .md, .txt, .xml, .json, .adoc, or .tar)",
+                        path.display()
+                    );
+                };
+                let path = output_template::output_path(Some(path), config)?
+                    .unwrap_or_else(|| path.clone());
+                Ok((path, format))
+            })
+            .collect()
+    }
+
+    /// Creates the extra outputs, so a path that cannot be written fails the run before any
+    /// file is read.
+    ///
+    /// # Arguments
+    /// * `outputs` - The file and format of each output, from [`AlsoOutput::resolve_all`].
+    ///
+    /// # Returns
+    /// * `Result<Vec<AlsoOutput>>` - The created outputs, or an error if a file cannot be
+    ///   created.
+    pub(crate) fn create_all(outputs: Vec<(PathBuf, Format)>) -> Result<Vec<AlsoOutput>> {
+        outputs
+            .into_iter()
+            .map(|(path, format)| {
+                let file = File::create(&path).map_err(|source| Error::OutputCreate {
+                    path: path.clone(),
+                    source,
+                })?;
+                Ok(AlsoOutput {
+                    path,
+                    format,
+                    formatter: format.formatter(),
+                    writer: BufWriter::new(file),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the file written, after suffixes.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Flushes what is left of the output to its file and waits for it to reach the disk.
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        self.writer
+            .flush()
+            .and_then(|()| crate::sync_file(self.writer.get_ref()))
+            .with_context(|| format!("Failed to write output file: {}", self.path.display()))
+    }
+}
+
+/// A formatter that writes the main output with its own formatter, and every extra output
+/// with the built-in formatter of its format.
+///
+/// The front matter, preamble, and postamble are written around the main output by the
+/// run, so they are added to each extra output here.
+pub(crate) struct FanOut<'a> {
+    primary: &'a mut dyn Formatter,
+    outputs: &'a mut [AlsoOutput],
+    postamble: Option<&'a str>,
+}
+
+impl<'a> FanOut<'a> {
+    /// Pairs the main formatter with the extra outputs.
+    pub(crate) fn new(
+        primary: &'a mut dyn Formatter,
+        outputs: &'a mut [AlsoOutput],
+        config: &'a Config,
+    ) -> Self {
+        FanOut {
+            primary,
+            outputs,
+            postamble: config.postamble.as_deref(),
+        }
+    }
+
+    /// Writes to each extra output, naming the file in errors.
+    fn each(
+        &mut self,
+        mut write: impl FnMut(&mut dyn Formatter, &mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        for output in self.outputs.iter_mut() {
+            write(output.formatter.as_mut(), &mut output.writer)
+                .with_context(|| format!("Failed to write {}", output.path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Formatter for FanOut<'_> {
+    fn embeds_framing_text(&self) -> bool {
+        self.primary.embeds_framing_text()
+    }
+
+    fn begin(&mut self, writer: &mut dyn Write, context: &RunContext) -> Result<()> {
+        self.primary.begin(writer, context)?;
+        // Extra outputs are files, which never get color escapes.
+        let context = RunContext {
+            color: false,
+            ..*context
+        };
+        // Front matter only means something at the top of a Markdown document.
+        if let Some(frontmatter) = context.frontmatter {
+            for output in self.outputs.iter_mut() {
+                if matches!(output.format, Format::Markdown) {
+                    output
+                        .writer
+                        .write_all(frontmatter.as_bytes())
+                        .with_context(|| format!("Failed to write {}", output.path.display()))?;
+                }
+            }
+        }
+        self.each(|formatter, writer| {
+            if let Some(preamble) = context
+                .config
+                .preamble
+                .as_ref()
+                .filter(|_| !formatter.embeds_framing_text())
+            {
+                write_framing_text(writer, preamble)?;
+                writeln!(writer)?;
+            }
+            formatter.begin(writer, &context)
+        })
+    }
+
+    fn file(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        self.primary.file(writer, entry, config)?;
+        self.each(|formatter, writer| formatter.file(writer, entry, config))
+    }
+
+    fn group(&mut self, writer: &mut dyn Write, group: &FileGroup, config: &Config) -> Result<()> {
+        self.primary.group(writer, group, config)?;
+        self.each(|formatter, writer| formatter.group(writer, group, config))
+    }
+
+    fn header(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        self.primary.header(writer, entry, config)?;
+        self.each(|formatter, writer| formatter.header(writer, entry, config))
+    }
+
+    fn duplicate(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        original: &Path,
+        config: &Config,
+    ) -> Result<()> {
+        self.primary.duplicate(writer, entry, original, config)?;
+        self.each(|formatter, writer| formatter.duplicate(writer, entry, original, config))
+    }
+
+    fn placeholder(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        reason: SkipReason,
+        config: &Config,
+    ) -> Result<()> {
+        self.primary.placeholder(writer, entry, reason, config)?;
+        self.each(|formatter, writer| formatter.placeholder(writer, entry, reason, config))
+    }
+
+    fn deleted(
+        &mut self,
+        writer: &mut dyn Write,
+        paths: &[PathBuf],
+        config: &Config,
+    ) -> Result<()> {
+        self.primary.deleted(writer, paths, config)?;
+        self.each(|formatter, writer| formatter.deleted(writer, paths, config))
+    }
+
+    fn omitted(
+        &mut self,
+        writer: &mut dyn Write,
+        selection: &BudgetSelection,
+        config: &Config,
+    ) -> Result<()> {
+        self.primary.omitted(writer, selection, config)?;
+        self.each(|formatter, writer| formatter.omitted(writer, selection, config))
+    }
+
+    fn limited(
+        &mut self,
+        writer: &mut dyn Write,
+        limit: usize,
+        left_out: usize,
+        config: &Config,
+    ) -> Result<()> {
+        self.primary.limited(writer, limit, left_out, config)?;
+        self.each(|formatter, writer| formatter.limited(writer, limit, left_out, config))
+    }
+
+    fn digest(&mut self, writer: &mut dyn Write, digest: &str, config: &Config) -> Result<()> {
+        self.primary.digest(writer, digest, config)?;
+        self.each(|formatter, writer| formatter.digest(writer, digest, config))
+    }
+
+    fn question(&mut self, writer: &mut dyn Write, question: &str, config: &Config) -> Result<()> {
+        self.primary.question(writer, question, config)?;
+        self.each(|formatter, writer| formatter.question(writer, question, config))
+    }
+
+    fn finish(&mut self, writer: &mut dyn Write, summary: &RunSummary) -> Result<()> {
+        self.primary.finish(writer, summary)?;
+        let postamble = self.postamble;
+        self.each(|formatter, writer| {
+            formatter.finish(writer, summary)?;
+            if let Some(postamble) = postamble.filter(|_| !formatter.embeds_framing_text()) {
+                write_framing_text(writer, postamble)?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,1046 @@
+//! Archives as input: the entries of a `.zip`, `.tar`, `.tar.gz`, or `.tgz` file, read in memory.
+
+use crate::compress::{self, Decoder, Framing};
+use crate::extensions;
+use crate::file_filter::FileFilter;
+use crate::limit::ByteSize;
+use crate::path_overrides::PathRule;
+use crate::source::{is_ignore_file, list_tree};
+use crate::{Config, FileSource, SkipReason, SourceFile};
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// The size of a tar header and the unit tar content is padded to.
+const TAR_BLOCK: usize = 512;
+
+/// The signature of a zip end of central directory record.
+const ZIP_END: u32 = 0x0605_4b50;
+
+/// The signature of a zip central directory file header.
+const ZIP_CENTRAL: u32 = 0x0201_4b50;
+
+/// The signature of a zip local file header.
+const ZIP_LOCAL: u32 = 0x0403_4b50;
+
+/// The most bytes an archive may decompress to: the whole stream of a tar archive, or the
+/// entries of a zip archive that are decompressed. A small compressed archive can expand to
+/// far more than memory holds.
+const MAX_UNPACKED_SIZE: u64 = 2 << 30;
+
+/// The archive formats, recognized by their file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl Kind {
+    fn of(path: &Path) -> Option<Kind> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Kind::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Kind::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Kind::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Checks whether an input path names an archive rather than a directory.
+///
+/// # Arguments
+/// * `path` - The input path.
+///
+/// # Returns
+/// * `bool` - `true` if the path ends in `.zip`, `.tar`, `.tar.gz`, or `.tgz` and is not a
+///   directory.
+pub fn is_archive(path: &Path) -> bool {
+    Kind::of(path).is_some() && !path.is_dir()
+}
+
+/// A source backed by the regular files of an archive, read into memory.
+///
+/// Paths are relative to the root of the archive. Hidden files are filtered like on disk, and
+/// `.gitignore` entries inside the archive are respected. Links, and entries whose path is
+/// absolute or leaves the archive root (`../`), are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveSource {
+    files: BTreeMap<PathBuf, Entry>,
+}
+
+/// A regular file of an archive.
+#[derive(Debug, Clone)]
+enum Entry {
+    /// A file whose content was read.
+    Read(Vec<u8>),
+    /// A file left out by its path or by `max_file_size` while the archive was read, so it
+    /// was never decompressed, with its size.
+    Unread(u64),
+}
+
+/// Decides which entries of an archive are decompressed, and counts what they expand to.
+struct Unpacker {
+    /// The path filters of the run, or `None` to read every entry.
+    filter: Option<FileFilter>,
+    max_file_size: Option<u64>,
+    /// The decompressed bytes still allowed, out of [`MAX_UNPACKED_SIZE`].
+    remaining: u64,
+}
+
+impl Unpacker {
+    /// Reads every entry.
+    #[cfg(any(test, feature = "http"))]
+    fn all() -> Self {
+        Unpacker {
+            filter: None,
+            max_file_size: None,
+            remaining: MAX_UNPACKED_SIZE,
+        }
+    }
+
+    /// Reads the entries a run can bundle.
+    ///
+    /// # Returns
+    /// * `Result<Unpacker>` - The unpacker, or an error if a filter pattern is invalid.
+    fn for_run(config: &Config) -> Result<Self> {
+        Ok(Unpacker {
+            filter: Some(FileFilter::new(config)?),
+            max_file_size: config.max_file_size.map(|size| size.0),
+            remaining: MAX_UNPACKED_SIZE,
+        })
+    }
+
+    /// Checks whether an entry is decompressed: ignore files always are, as they filter the
+    /// others, and so are files in `include_paths`; other files are unless hidden, in an
+    /// excluded directory or `exclude_paths`, or larger than `max_file_size`.
+    fn reads(&self, path: &Path, size: u64) -> bool {
+        if is_ignore_file(path) {
+            return true;
+        }
+        let rule = self
+            .filter
+            .as_ref()
+            .map_or(PathRule::Filters, |filter| filter.overrides().rule(path));
+        match rule {
+            PathRule::Include => true,
+            PathRule::Exclude => false,
+            PathRule::Filters => {
+                self.max_file_size.is_none_or(|max| size <= max)
+                    && self
+                        .filter
+                        .as_ref()
+                        .is_none_or(|filter| filter.path_exclusion(path, false).is_none())
+            }
+        }
+    }
+
+    /// Counts bytes about to be decompressed.
+    ///
+    /// # Returns
+    /// * `Result<()>` - An error if the archive decompresses to more than
+    ///   [`MAX_UNPACKED_SIZE`].
+    fn take(&mut self, size: u64) -> Result<()> {
+        self.remaining = self
+            .remaining
+            .checked_sub(size)
+            .ok_or_else(|| anyhow!(too_large()))?;
+        Ok(())
+    }
+}
+
+/// The error of an archive that decompresses to more than [`MAX_UNPACKED_SIZE`].
+fn too_large() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "The archive decompresses to more than {}",
+            ByteSize(MAX_UNPACKED_SIZE)
+        ),
+    )
+}
+
+/// A reader of a decompressed archive that fails once it has read [`MAX_UNPACKED_SIZE`].
+struct Capped<R: Read> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for Capped<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.remaining = self
+            .remaining
+            .checked_sub(read as u64)
+            .ok_or_else(too_large)?;
+        Ok(read)
+    }
+}
+
+impl ArchiveSource {
+    /// Reads an archive, choosing the format by the file name.
+    ///
+    /// Entries are decompressed one at a time, and only those the run can bundle: entries
+    /// that are hidden, in an excluded directory, in `exclude_paths`, or larger than
+    /// `max_file_size` are listed without their content, so the run skips them.
+    ///
+    /// # Arguments
+    /// * `path` - The path of a `.zip`, `.tar`, `.tar.gz`, or `.tgz` file.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<ArchiveSource>` - The files of the archive, or an error if it cannot be read
+    ///   or decompresses to more than 2 GiB.
+    pub fn open(path: &Path, config: &Config) -> Result<Self> {
+        let Some(kind) = Kind::of(path) else {
+            bail!(
+                "{} is not a .zip, .tar, .tar.gz, or .tgz archive",
+                path.display()
+            );
+        };
+        let mut unpacker = Unpacker::for_run(config)?;
+        let file = || {
+            File::open(path)
+                .map(BufReader::new)
+                .with_context(|| format!("Failed to read {}", path.display()))
+        };
+        let source = match kind {
+            Kind::Zip => {
+                let data =
+                    fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+                ArchiveSource::read_zip(&data, &mut unpacker)
+            }
+            Kind::Tar => ArchiveSource::read_tar(file()?, &unpacker),
+            Kind::TarGz => Decoder::new(file()?, Framing::Gzip)
+                .and_then(|tar| ArchiveSource::read_tar(tar, &unpacker)),
+        };
+        source.with_context(|| format!("Failed to read archive {}", path.display()))
+    }
+
+    /// Reads every file of a gzip-compressed tar archive held in memory.
+    #[cfg(feature = "http")]
+    pub(crate) fn from_tar_gz(data: &[u8]) -> Result<Self> {
+        ArchiveSource::read_tar(Decoder::new(data, Framing::Gzip)?, &Unpacker::all())
+    }
+
+    /// Reads every file of an uncompressed tar archive held in memory.
+    #[cfg(test)]
+    pub(crate) fn from_tar(data: &[u8]) -> Result<Self> {
+        ArchiveSource::read_tar(data, &Unpacker::all())
+    }
+
+    /// Reads every file of a zip archive held in memory.
+    #[cfg(test)]
+    pub(crate) fn from_zip(data: &[u8]) -> Result<Self> {
+        ArchiveSource::read_zip(data, &mut Unpacker::all())
+    }
+
+    /// Reads the files of an uncompressed tar archive as a stream.
+    ///
+    /// ustar prefixes, GNU long names, and pax `path` records are supported.
+    fn read_tar(reader: impl Read, unpacker: &Unpacker) -> Result<Self> {
+        let mut reader = Capped {
+            inner: reader,
+            remaining: unpacker.remaining,
+        };
+        let mut source = ArchiveSource::default();
+        let mut offset: u64 = 0;
+        // A name given by a GNU long name or pax header for the entry that follows it.
+        let mut next_name = None;
+        let mut header = [0; TAR_BLOCK];
+        // An archive ends with zero blocks, or, like a truncated one, with no full header.
+        while read_block(&mut reader, &mut header)? {
+            if header.iter().all(|&byte| byte == 0) {
+                break;
+            }
+            if tar_number(&header[148..156]) != Some(tar_checksum(&header)) {
+                bail!("Invalid tar header at byte {}", offset);
+            }
+            let size = tar_number(&header[124..136]).with_context(|| {
+                format!("Invalid entry size in the tar header at byte {}", offset)
+            })?;
+            let padded = size
+                .checked_next_multiple_of(TAR_BLOCK as u64)
+                .context("The tar archive is truncated")?;
+            offset += TAR_BLOCK as u64 + padded;
+
+            let name = next_name.take().unwrap_or_else(|| {
+                let name = nul_terminated(&header[0..100]);
+                let prefix = nul_terminated(&header[345..500]);
+                if header[257..262] == *b"ustar" && !prefix.is_empty() {
+                    format!("{}/{}", prefix, name)
+                } else {
+                    name
+                }
+            });
+            let content = match header[156] {
+                b'0' | b'\0' | b'7' => match entry_path(&name) {
+                    Some(path) if unpacker.reads(&path, size) => {
+                        let content = read_content(&mut reader, size)?;
+                        source.files.insert(path, Entry::Read(content));
+                        size
+                    }
+                    Some(path) => {
+                        source.files.insert(path, Entry::Unread(size));
+                        0
+                    }
+                    None => {
+                        warn_unsafe(&name);
+                        0
+                    }
+                },
+                b'L' => {
+                    next_name = Some(nul_terminated(&read_content(&mut reader, size)?));
+                    size
+                }
+                b'x' => {
+                    next_name = pax_path(&read_content(&mut reader, size)?);
+                    size
+                }
+                b'1' | b'2' => {
+                    warn!("Skipping link {} in the archive", name);
+                    0
+                }
+                // Directories, global pax headers, and devices.
+                kind => {
+                    debug!("Skipping {} (tar entry type {:?})", name, kind as char);
+                    0
+                }
+            };
+            let skipped = io::copy(&mut (&mut reader).take(padded - content), &mut io::sink())?;
+            if skipped != padded - content {
+                bail!("The tar archive is truncated");
+            }
+        }
+        Ok(source)
+    }
+
+    /// Reads the files of a zip archive, stored or deflated.
+    ///
+    /// Each entry is decompressed to at most the size it declares.
+    fn read_zip(data: &[u8], unpacker: &mut Unpacker) -> Result<Self> {
+        // The end record is at least 22 bytes and followed by a comment of up to 64 KiB.
+        let last = data.len().checked_sub(22).context("Not a zip archive")?;
+        let end = (last.saturating_sub(u16::MAX as usize)..=last)
+            .rev()
+            .find(|&offset| le(data, offset, 4) == Some(ZIP_END as u64))
+            .context("Not a zip archive: no end of central directory record")?;
+        let field = |offset: usize, len: usize| {
+            le(data, offset, len)
+                .map(|value| value as usize)
+                .context("The zip archive is truncated")
+        };
+        let entries = field(end + 10, 2)?;
+        let mut offset = field(end + 16, 4)?;
+        if entries == 0xffff || offset == 0xffff_ffff {
+            bail!("ZIP64 archives are not supported");
+        }
+
+        let mut source = ArchiveSource::default();
+        for _ in 0..entries {
+            if field(offset, 4)? != ZIP_CENTRAL as usize {
+                bail!("The zip central directory is corrupt at byte {}", offset);
+            }
+            let made_by_unix = field(offset + 4, 2)? >> 8 == 3;
+            let flags = field(offset + 8, 2)?;
+            let method = field(offset + 10, 2)?;
+            let compressed_size = field(offset + 20, 4)?;
+            let size = field(offset + 24, 4)?;
+            let name_len = field(offset + 28, 2)?;
+            let extra_len = field(offset + 30, 2)?;
+            let comment_len = field(offset + 32, 2)?;
+            let mode = field(offset + 38, 4)? >> 16;
+            let local = field(offset + 42, 4)?;
+            let name = data
+                .get(offset + 46..offset + 46 + name_len)
+                .context("The zip archive is truncated")?;
+            let name = String::from_utf8_lossy(name).into_owned();
+            offset += 46 + name_len + extra_len + comment_len;
+
+            if name.ends_with('/') {
+                continue;
+            }
+            if made_by_unix && mode & 0o170000 == 0o120000 {
+                warn!("Skipping link {} in the archive", name);
+                continue;
+            }
+            if flags & 1 != 0 {
+                warn!("Skipping encrypted entry {} in the archive", name);
+                continue;
+            }
+            if compressed_size == 0xffff_ffff || size == 0xffff_ffff {
+                warn!("Skipping ZIP64 entry {} in the archive", name);
+                continue;
+            }
+            if !matches!(method, 0 | 8) {
+                warn!(
+                    "Skipping {} in the archive: unsupported compression method {}",
+                    name, method
+                );
+                continue;
+            }
+            let Some(path) = entry_path(&name) else {
+                warn_unsafe(&name);
+                continue;
+            };
+            if !unpacker.reads(&path, size as u64) {
+                source.files.insert(path, Entry::Unread(size as u64));
+                continue;
+            }
+            if field(local, 4)? != ZIP_LOCAL as usize {
+                bail!("The zip entry {} is corrupt", name);
+            }
+            let start = local + 30 + field(local + 26, 2)? + field(local + 28, 2)?;
+            let raw = data
+                .get(start..start + compressed_size)
+                .context("The zip archive is truncated")?;
+            unpacker.take(size as u64)?;
+            let content = match method {
+                0 if raw.len() == size => raw.to_vec(),
+                0 => bail!("The zip entry {} is corrupt", name),
+                _ => compress::inflate(raw, Framing::Raw, size)
+                    .with_context(|| format!("Failed to decompress {}", name))?,
+            };
+            source.files.insert(path, Entry::Read(content));
+        }
+        Ok(source)
+    }
+
+    /// Wraps files read from elsewhere, e.g. a git tree, keyed by relative path.
+    pub(crate) fn from_files(files: BTreeMap<PathBuf, Vec<u8>>) -> Self {
+        let files = files
+            .into_iter()
+            .map(|(path, content)| (path, Entry::Read(content)))
+            .collect();
+        ArchiveSource { files }
+    }
+
+    /// Returns the files of the archive that were read, with their paths.
+    #[cfg(feature = "http")]
+    pub(crate) fn files(&self) -> impl Iterator<Item = (&Path, &[u8])> {
+        self.files.iter().filter_map(|(path, entry)| match entry {
+            Entry::Read(content) => Some((path.as_path(), content.as_slice())),
+            Entry::Unread(_) => None,
+        })
+    }
+
+    /// Returns the content of a file, if it was read.
+    fn content(&self, path: &Path) -> Option<&[u8]> {
+        match self.files.get(path)? {
+            Entry::Read(content) => Some(content),
+            Entry::Unread(_) => None,
+        }
+    }
+
+    /// Checks whether a listed file looks binary, like [`extensions::looks_binary`] on disk.
+    pub(crate) fn looks_binary(&self, file: &SourceFile) -> bool {
+        self.content(&file.path).is_some_and(extensions::is_binary)
+    }
+}
+
+impl FileSource for ArchiveSource {
+    fn list(
+        &self,
+        config: &Config,
+        on_skipped: &mut dyn FnMut(&Path, SkipReason),
+    ) -> Result<Vec<SourceFile>> {
+        let gitignores = self
+            .files
+            .keys()
+            .filter(|path| is_ignore_file(path))
+            .filter_map(|path| {
+                Some((
+                    path.as_path(),
+                    std::str::from_utf8(self.content(path)?).ok()?,
+                ))
+            });
+        list_tree(self.files.keys(), gitignores, config, on_skipped)
+    }
+
+    fn find(&self, _config: &Config, relative_path: &Path) -> Option<SourceFile> {
+        self.files
+            .contains_key(relative_path)
+            .then(|| SourceFile::new(relative_path, relative_path))
+    }
+
+    fn read(&self, file: &SourceFile) -> io::Result<String> {
+        String::from_utf8(self.read_bytes(file)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn read_bytes(&self, file: &SourceFile) -> io::Result<Vec<u8>> {
+        match self.files.get(&file.path) {
+            Some(Entry::Read(content)) => Ok(content.clone()),
+            Some(Entry::Unread(_)) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} was left out when the archive was read",
+                    file.path.display()
+                ),
+            )),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no such file: {}", file.path.display()),
+            )),
+        }
+    }
+
+    fn len(&self, file: &SourceFile) -> Option<u64> {
+        self.files.get(&file.path).map(|entry| match entry {
+            Entry::Read(content) => content.len() as u64,
+            Entry::Unread(size) => *size,
+        })
+    }
+}
+
+/// Warns about an entry whose name is unsafe to use as a path.
+fn warn_unsafe(name: &str) {
+    warn!(
+        "Skipping {} in the archive: the path is absolute or leaves the archive",
+        name
+    );
+}
+
+/// Reads a tar header block.
+///
+/// # Returns
+/// * `io::Result<bool>` - `false` if the stream ends before a full block.
+fn read_block(reader: &mut impl Read, block: &mut [u8; TAR_BLOCK]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < TAR_BLOCK {
+        match reader.read(&mut block[filled..])? {
+            0 => return Ok(false),
+            read => filled += read,
+        }
+    }
+    Ok(true)
+}
+
+/// Reads the content of a tar entry, growing the buffer only as the bytes arrive.
+fn read_content(reader: &mut impl Read, size: u64) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    reader.take(size).read_to_end(&mut content)?;
+    if (content.len() as u64) < size {
+        bail!("The tar archive is truncated");
+    }
+    Ok(content)
+}
+
+/// Turns an entry name into a relative path.
+///
+/// # Arguments
+/// * `name` - The name stored in the archive.
+///
+/// # Returns
+/// * `Option<PathBuf>` - The path, or `None` if the name is absolute, has a drive prefix,
+///   contains a `..` component, or is empty.
+fn entry_path(name: &str) -> Option<PathBuf> {
+    if name.starts_with(['/', '\\']) {
+        return None;
+    }
+    let mut path = PathBuf::new();
+    for (i, component) in name.split(['/', '\\']).enumerate() {
+        match component {
+            "" | "." => {}
+            ".." => return None,
+            drive if i == 0 && drive.ends_with(':') => return None,
+            component => path.push(component),
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+/// Reads a little-endian integer of `len` bytes at `offset`.
+fn le(data: &[u8], offset: usize, len: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(len)?)?;
+    Some(
+        bytes
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| value << 8 | byte as u64),
+    )
+}
+
+/// Reads a string field of a tar header, which ends at the first NUL byte.
+fn nul_terminated(field: &[u8]) -> String {
+    let end = field
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// Reads a numeric tar header field: octal digits, or big-endian base-256 for large values.
+fn tar_number(field: &[u8]) -> Option<u64> {
+    if field.first().is_some_and(|&byte| byte & 0x80 != 0) {
+        let value = field[1..]
+            .iter()
+            .try_fold(u64::from(field[0] & 0x7f), |value, &byte| {
+                value.checked_mul(256).map(|value| value | byte as u64)
+            });
+        return value;
+    }
+    let digits = nul_terminated(field);
+    let digits = digits.trim_matches(' ');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+/// Computes the checksum of a tar header, with the checksum field counted as spaces.
+fn tar_checksum(header: &[u8]) -> u64 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, &byte)| u64::from(if (148..156).contains(&i) { b' ' } else { byte }))
+        .sum()
+}
+
+/// The content of an entry written by [`write_tar_entry`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum TarContent<'a> {
+    /// A regular file with these bytes.
+    File(&'a [u8]),
+    /// A hard link to an earlier entry of the archive, by its path.
+    HardLink(&'a str),
+}
+
+/// Writes one entry of a ustar archive: its header, then its content padded to whole blocks.
+///
+/// A path too long for the 100-byte name field is split between the ustar prefix and name
+/// fields where it can be, and is otherwise written as a GNU long name record first, as GNU
+/// tar does; link targets longer than 100 bytes get a GNU long link record. Entries are
+/// owned by user 0 with mode `0644`.
+///
+/// # Arguments
+/// * `writer` - The writer of the archive.
+/// * `path` - The path of the entry, with `/` separators.
+/// * `content` - The file content, or the target of a hard link.
+/// * `mtime` - The modification time of the entry, in seconds since the Unix epoch.
+///
+/// # Returns
+/// * `io::Result<()>` - Returns `Ok(())` if successful, or an error if the write fails.
+pub(crate) fn write_tar_entry(
+    writer: &mut dyn io::Write,
+    path: &str,
+    content: TarContent,
+    mtime: u64,
+) -> io::Result<()> {
+    let (kind, data, link) = match content {
+        TarContent::File(data) => (b'0', data, ""),
+        TarContent::HardLink(target) => (b'1', &[][..], target),
+    };
+    if link.len() > 100 {
+        write_gnu_long_record(writer, b'K', link, mtime)?;
+    }
+    let (prefix, name) = match split_tar_path(path) {
+        Some(split) => split,
+        None => {
+            write_gnu_long_record(writer, b'L', path, mtime)?;
+            ("", path)
+        }
+    };
+    let header = tar_header(name, prefix, kind, data.len() as u64, link, mtime);
+    writer.write_all(&header)?;
+    write_tar_data(writer, data)
+}
+
+/// Writes the two zero blocks that end a tar archive.
+pub(crate) fn finish_tar(writer: &mut dyn io::Write) -> io::Result<()> {
+    writer.write_all(&[0; 2 * TAR_BLOCK])
+}
+
+/// Writes a GNU long name (`L`) or long link (`K`) record holding a NUL-terminated path.
+fn write_gnu_long_record(
+    writer: &mut dyn io::Write,
+    kind: u8,
+    path: &str,
+    mtime: u64,
+) -> io::Result<()> {
+    let mut data = path.as_bytes().to_vec();
+    data.push(0);
+    let header = tar_header("././@LongLink", "", kind, data.len() as u64, "", mtime);
+    writer.write_all(&header)?;
+    write_tar_data(writer, &data)
+}
+
+/// Writes entry content followed by the zeros that pad it to a whole block.
+fn write_tar_data(writer: &mut dyn io::Write, data: &[u8]) -> io::Result<()> {
+    writer.write_all(data)?;
+    let padding = data.len().div_ceil(TAR_BLOCK) * TAR_BLOCK - data.len();
+    writer.write_all(&[0; TAR_BLOCK][..padding])
+}
+
+/// Splits a path into the ustar prefix and name fields, at the first `/` that makes both
+/// fit; `None` if the path cannot fit.
+fn split_tar_path(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= 100 {
+        return Some(("", path));
+    }
+    path.match_indices('/')
+        .map(|(i, _)| (&path[..i], &path[i + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && !name.is_empty() && name.len() <= 100)
+}
+
+/// Builds a ustar header block; fields too long for the header are cut short.
+fn tar_header(
+    name: &str,
+    prefix: &str,
+    kind: u8,
+    size: u64,
+    link: &str,
+    mtime: u64,
+) -> [u8; TAR_BLOCK] {
+    let mut header = [0; TAR_BLOCK];
+    let mut put = |offset: usize, len: usize, value: &[u8]| {
+        let len = value.len().min(len);
+        header[offset..offset + len].copy_from_slice(&value[..len]);
+    };
+    put(0, 100, name.as_bytes());
+    put(100, 8, b"0000644");
+    put(108, 8, b"0000000");
+    put(116, 8, b"0000000");
+    put(124, 12, format!("{:011o}", size).as_bytes());
+    put(136, 12, format!("{:011o}", mtime).as_bytes());
+    put(156, 1, &[kind]);
+    put(157, 100, link.as_bytes());
+    put(257, 8, b"ustar\x0000");
+    put(345, 155, prefix.as_bytes());
+    let checksum = format!("{:06o}\0 ", tar_checksum(&header));
+    header[148..156].copy_from_slice(checksum.as_bytes());
+    header
+}
+
+/// Finds the `path` record of a pax extended header.
+fn pax_path(records: &[u8]) -> Option<String> {
+    let mut rest = records;
+    while !rest.is_empty() {
+        // Each record is "<length> <key>=<value>\n", the length counting the whole record.
+        let space = rest.iter().position(|&byte| byte == b' ')?;
+        let len: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        let record = rest.get(space + 1..len)?;
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+        if let Some(path) = record.strip_prefix(b"path=") {
+            return Some(String::from_utf8_lossy(path).into_owned());
+        }
+        rest = &rest[len..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_source_to_string;
+
+    /// Builds a tar header and content block for an entry of the given type.
+    fn tar_entry(name: &str, kind: u8, content: &[u8]) -> Vec<u8> {
+        let mut header = [0u8; TAR_BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{:011o}", content.len()).as_bytes());
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        let checksum = tar_checksum(&header);
+        header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+        let mut entry = header.to_vec();
+        entry.extend_from_slice(content);
+        entry.resize(entry.len().div_ceil(TAR_BLOCK) * TAR_BLOCK, 0);
+        entry
+    }
+
+    /// Builds a tar archive of regular files.
+    fn tar(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut data: Vec<u8> = files
+            .iter()
+            .flat_map(|(name, content)| tar_entry(name, b'0', content.as_bytes()))
+            .collect();
+        data.extend_from_slice(&[0; 2 * TAR_BLOCK]);
+        data
+    }
+
+    /// Builds a zip archive of stored (method 0) or pre-compressed entries.
+    fn zip(entries: &[(&str, u16, &[u8], usize)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut central = Vec::new();
+        for (name, method, content, size) in entries {
+            let local = data.len() as u32;
+            data.extend_from_slice(&ZIP_LOCAL.to_le_bytes());
+            data.extend_from_slice(&[20, 0, 0, 0]);
+            data.extend_from_slice(&method.to_le_bytes());
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(*size as u32).to_le_bytes());
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(&[0, 0]);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(content);
+
+            central.extend_from_slice(&ZIP_CENTRAL.to_le_bytes());
+            central.extend_from_slice(&[20, 3, 20, 0, 0, 0]);
+            central.extend_from_slice(&method.to_le_bytes());
+            central.extend_from_slice(&[0; 8]);
+            central.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(*size as u32).to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 8]);
+            let mode: u32 = if name.starts_with("link") {
+                0o120777
+            } else {
+                0o100644
+            };
+            central.extend_from_slice(&(mode << 16).to_le_bytes());
+            central.extend_from_slice(&local.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let central_offset = data.len() as u32;
+        data.extend_from_slice(&central);
+        data.extend_from_slice(&ZIP_END.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        data.extend_from_slice(&central_offset.to_le_bytes());
+        data.extend_from_slice(&[0, 0]);
+        data
+    }
+
+    fn paths(source: &ArchiveSource) -> Vec<&str> {
+        source
+            .files
+            .keys()
+            .map(|path| path.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn unsafe_entry_names_are_rejected() {
+        assert_eq!(
+            entry_path("./src//main.rs"),
+            Some(PathBuf::from("src/main.rs"))
+        );
+        for name in [
+            "../etc/passwd",
+            "src/../../x",
+            "/etc/passwd",
+            "\\x",
+            "C:\\x",
+            "",
+            "./",
+        ] {
+            assert_eq!(entry_path(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn tar_entries_are_read() {
+        let mut data = tar_entry("project/src/", b'5', b"");
+        data.extend(tar_entry("project/src/main.rs", b'0', b"fn main() {}\n"));
+        data.extend(tar_entry("project/link.rs", b'2', b""));
+        data.extend(tar_entry("../evil.rs", b'0', b"evil"));
+        data.extend(tar_entry(
+            "././@LongLink",
+            b'L',
+            b"project/a-very-long-name.rs\0",
+        ));
+        data.extend(tar_entry("project/a-very-lo", b'0', b"long"));
+        data.extend(tar_entry(
+            "PaxHeader",
+            b'x',
+            b"28 path=project/pax-name.rs\n",
+        ));
+        data.extend(tar_entry("project/pax-na", b'0', b"pax"));
+        data.extend_from_slice(&[0; 2 * TAR_BLOCK]);
+
+        let source = ArchiveSource::from_tar(&data).unwrap();
+        assert_eq!(
+            paths(&source),
+            [
+                "project/a-very-long-name.rs",
+                "project/pax-name.rs",
+                "project/src/main.rs"
+            ]
+        );
+
+        data[0] ^= 1;
+        assert!(ArchiveSource::from_tar(&data).is_err());
+    }
+
+    #[test]
+    fn written_tar_entries_read_back() {
+        let split = format!("{}/{}.rs", "d".repeat(120), "f".repeat(90));
+        let long = format!("{}.rs", "f".repeat(120));
+        let mut data = Vec::new();
+        for path in ["src/main.rs", &split, &long] {
+            write_tar_entry(&mut data, path, TarContent::File(path.as_bytes()), 0).unwrap();
+        }
+        write_tar_entry(&mut data, "copy.rs", TarContent::HardLink("src/main.rs"), 0).unwrap();
+        finish_tar(&mut data).unwrap();
+        assert_eq!(data.len() % TAR_BLOCK, 0);
+
+        // Links are skipped when reading.
+        let source = ArchiveSource::from_tar(&data).unwrap();
+        let mut expected = vec![split.as_str(), long.as_str(), "src/main.rs"];
+        expected.sort();
+        assert_eq!(paths(&source), expected);
+        for path in expected {
+            assert_eq!(source.content(Path::new(path)).unwrap(), path.as_bytes());
+        }
+    }
+
+    #[test]
+    fn zip_entries_are_read() {
+        let data = zip(&[
+            ("src/", 0, b"", 0),
+            ("src/main.rs", 0, b"fn main() {}\n", 13),
+            ("README.md", 0, b"# Hi\n", 5),
+            ("link.rs", 0, b"src/main.rs", 11),
+            ("../../evil.rs", 0, b"evil", 4),
+        ]);
+        let source = ArchiveSource::from_zip(&data).unwrap();
+        assert_eq!(paths(&source), ["README.md", "src/main.rs"]);
+        assert_eq!(source.content(Path::new("README.md")).unwrap(), b"# Hi\n");
+        assert!(ArchiveSource::from_zip(b"not a zip").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn deflated_zip_entries_are_read() {
+        use std::io::Write;
+
+        let content = "fn main() {}\n".repeat(20);
+        let mut encoder = compress::Encoder::new(compress::Compression::Gzip, Vec::new()).unwrap();
+        encoder.write_all(content.as_bytes()).unwrap();
+        let (gzip, _, _) = encoder.finish().unwrap();
+        // Raw deflate data is the gzip member without its 10-byte header and 8-byte trailer.
+        let deflated = &gzip[10..gzip.len() - 8];
+
+        let source =
+            ArchiveSource::from_zip(&zip(&[("main.rs", 8, deflated, content.len())])).unwrap();
+        assert_eq!(
+            source.content(Path::new("main.rs")).unwrap(),
+            content.as_bytes()
+        );
+        let truncated = zip(&[("main.rs", 8, &deflated[..10], content.len())]);
+        assert!(ArchiveSource::from_zip(&truncated).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn zip_entries_stop_at_their_declared_size() {
+        use std::io::Write;
+
+        let content = "0".repeat(100_000);
+        let mut encoder = compress::Encoder::new(compress::Compression::Gzip, Vec::new()).unwrap();
+        encoder.write_all(content.as_bytes()).unwrap();
+        let (gzip, _, _) = encoder.finish().unwrap();
+        let deflated = &gzip[10..gzip.len() - 8];
+
+        let err = ArchiveSource::from_zip(&zip(&[("bomb.txt", 8, deflated, 1_000)])).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("decompresses to more than 1000 bytes"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn entries_are_left_unread_by_path_and_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("project.tar");
+        let large = "x".repeat(2_000);
+        fs::write(
+            &path,
+            tar(&[
+                ("src/lib.rs", "pub fn f() {}\n"),
+                ("src/large.rs", &large),
+                (".secret/key.rs", "const KEY: u8 = 1;\n"),
+            ]),
+        )
+        .unwrap();
+        let config = Config::builder()
+            .ignore_hidden(true)
+            .max_file_size(ByteSize(1_000))
+            .build();
+        let source = ArchiveSource::open(&path, &config).unwrap();
+        assert!(matches!(
+            source.files[Path::new("src/lib.rs")],
+            Entry::Read(_)
+        ));
+        assert!(matches!(
+            source.files[Path::new("src/large.rs")],
+            Entry::Unread(2_000)
+        ));
+        assert!(matches!(
+            source.files[Path::new(".secret/key.rs")],
+            Entry::Unread(_)
+        ));
+
+        let output = run_source_to_string(&config, &source).unwrap();
+        assert!(output.contains("pub fn f() {}"));
+        assert!(!output.contains(&large) && !output.contains("KEY"));
+    }
+
+    #[test]
+    fn archives_stop_at_the_decompressed_size_cap() {
+        let data = tar(&[("a.txt", "a"), ("b.txt", "b")]);
+        let unpacker = Unpacker {
+            remaining: 3 * TAR_BLOCK as u64,
+            ..Unpacker::all()
+        };
+        let err = ArchiveSource::read_tar(data.as_slice(), &unpacker).unwrap_err();
+        assert!(format!("{:#}", err).contains("The archive decompresses to more than"));
+
+        let mut unpacker = Unpacker {
+            remaining: 10,
+            ..Unpacker::all()
+        };
+        let data = zip(&[("a.txt", 0, b"123456", 6), ("b.txt", 0, b"123456", 6)]);
+        assert!(ArchiveSource::read_zip(&data, &mut unpacker).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn tar_gz_files_are_opened_by_name() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("release.TGZ");
+        let mut encoder = compress::Encoder::new(compress::Compression::Gzip, Vec::new()).unwrap();
+        encoder
+            .write_all(&tar(&[("src/main.rs", "fn main() {}\n")]))
+            .unwrap();
+        fs::write(&path, encoder.finish().unwrap().0).unwrap();
+
+        assert!(is_archive(&path));
+        let source = ArchiveSource::open(&path, &Config::default()).unwrap();
+        assert_eq!(paths(&source), ["src/main.rs"]);
+
+        fs::write(&path, b"not gzip").unwrap();
+        let err = ArchiveSource::open(&path, &Config::default()).unwrap_err();
+        assert!(format!("{:#}", err).contains("release.TGZ"));
+    }
+
+    #[test]
+    fn archive_files_are_bundled_with_their_gitignore() {
+        let data = tar(&[
+            ("project/.gitignore", "*.log\n"),
+            ("project/src/lib.rs", "pub fn f() {}\n"),
+            ("project/debug.log", "noise\n"),
+        ]);
+        let source = ArchiveSource::from_tar(&data).unwrap();
+        let config = Config::builder().ignore_hidden(true).build();
+        let output = run_source_to_string(&config, &source).unwrap();
+        assert!(output.contains("project/src/lib.rs"));
+        assert!(output.contains("pub fn f() {}"));
+        assert!(!output.contains("noise"));
+    }
+}
@@ -0,0 +1,70 @@
+//! Base64 (RFC 4648) for binary files embedded with `embed_binary`.
+
+/// The standard alphabet.
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The most characters per line, as in MIME.
+const LINE_WIDTH: usize = 76;
+
+/// Encodes bytes as padded base64 in lines of 76 characters, separated by `\n`.
+///
+/// # Arguments
+/// * `bytes` - The bytes to encode.
+///
+/// # Returns
+/// * `String` - The encoded lines, without a final line ending.
+pub(crate) fn encode_wrapped(bytes: &[u8]) -> String {
+    let mut encoded = Vec::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            encoded.push(if i <= chunk.len() {
+                ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize]
+            } else {
+                b'='
+            });
+        }
+    }
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / LINE_WIDTH);
+    for (i, line) in encoded.chunks(LINE_WIDTH).enumerate() {
+        if i > 0 {
+            wrapped.push('\n');
+        }
+        // The alphabet and padding are ASCII.
+        wrapped.extend(line.iter().map(|&c| char::from(c)));
+    }
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_rfc_4648_vectors() {
+        let cases = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(encode_wrapped(input.as_bytes()), expected);
+        }
+        assert_eq!(encode_wrapped(&[0xff, 0xfe, 0x00]), "//4A");
+    }
+
+    #[test]
+    fn wraps_lines_at_76_characters() {
+        let encoded = encode_wrapped(&[0; 60]);
+        let lines: Vec<&str> = encoded.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 76);
+        assert_eq!(lines[1], "AAAA");
+    }
+}
@@ -0,0 +1,160 @@
+//! Bundling the files that differ between two git refs (`between`), as they are at the
+//! second ref.
+
+use crate::git::GitRepo;
+use crate::{ArchiveSource, Config, FileSource, SkipReason, SourceFile};
+use anyhow::{Context, Result, bail};
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Splits a `base..head` range into its refs.
+///
+/// # Arguments
+/// * `range` - The range, e.g. `main..release/2.4`.
+///
+/// # Returns
+/// * `Result<(&str, &str)>` - The base and head refs, or an error if the range is not two
+///   refs joined by `..`.
+pub(crate) fn parse_range(range: &str) -> Result<(&str, &str)> {
+    match range.split_once("..") {
+        Some((base, head)) if !base.is_empty() && !head.is_empty() && !head.starts_with('.') => {
+            Ok((base, head))
+        }
+        _ => bail!(
+            "Invalid range `{}`: expected BASE..HEAD, e.g. main..release/2.4",
+            range
+        ),
+    }
+}
+
+/// The files that differ between the refs of `between`, read from the git tree of the head
+/// ref rather than the working tree, and the files the head ref deleted.
+///
+/// Paths are relative to the input directory, which may be a subdirectory of the working
+/// tree; changes outside it are left out.
+#[derive(Debug)]
+pub(crate) struct TreeSource {
+    files: ArchiveSource,
+    deleted: Vec<PathBuf>,
+}
+
+impl TreeSource {
+    /// Diffs the refs of `between` in the repository of the input directory.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    /// * `range` - The `base..head` range.
+    ///
+    /// # Returns
+    /// * `Result<TreeSource>` - The changed files, or an error if the range or a ref is
+    ///   invalid, the input is not in a git working tree, or there is more than one input.
+    pub(crate) fn open(config: &Config, range: &str) -> Result<TreeSource> {
+        let (base, head) = parse_range(range)?;
+        let [directory] = config.directories.as_slice() else {
+            bail!("--between takes a single directory");
+        };
+        if config.files_from.is_some() {
+            bail!("--files-from cannot be combined with --between");
+        }
+        let repo = GitRepo::discover(std::slice::from_ref(directory))?.with_context(|| {
+            format!(
+                "--between needs a git repository, and {} is not in one",
+                directory.display()
+            )
+        })?;
+        let workdir = repo
+            .workdir()
+            .context("--between needs a working tree, and the repository is bare")?;
+        let prefix = prefix(workdir, directory)?;
+
+        let changes = repo.tree_changes(base, head, &prefix)?;
+        if changes.changed.is_empty() && changes.deleted.is_empty() {
+            warn!("No files differ between {} and {}", base, head);
+        } else {
+            info!(
+                "{} files differ between {} and {}, {} of them deleted",
+                changes.changed.len() + changes.deleted.len(),
+                base,
+                head,
+                changes.deleted.len()
+            );
+        }
+        Ok(TreeSource {
+            files: ArchiveSource::from_files(changes.changed),
+            deleted: changes.deleted,
+        })
+    }
+
+    /// Checks whether a listed file looks binary, like [`ArchiveSource::looks_binary`].
+    pub(crate) fn looks_binary(&self, file: &SourceFile) -> bool {
+        self.files.looks_binary(file)
+    }
+}
+
+/// Returns the path of the input directory inside the working tree.
+fn prefix(workdir: &Path, directory: &Path) -> Result<PathBuf> {
+    let resolve = |path: &Path| {
+        path.canonicalize()
+            .with_context(|| format!("Failed to resolve {}", path.display()))
+    };
+    let (workdir, directory) = (resolve(workdir)?, resolve(directory)?);
+    directory
+        .strip_prefix(&workdir)
+        .map(Path::to_path_buf)
+        .with_context(|| {
+            format!(
+                "{} is not inside the working tree {}",
+                directory.display(),
+                workdir.display()
+            )
+        })
+}
+
+impl FileSource for TreeSource {
+    /// Lists the changed files; deleted ones are returned by [`deleted`](FileSource::deleted).
+    fn list(
+        &self,
+        config: &Config,
+        on_skipped: &mut dyn FnMut(&Path, SkipReason),
+    ) -> Result<Vec<SourceFile>> {
+        self.files.list(config, on_skipped)
+    }
+
+    fn find(&self, config: &Config, relative_path: &Path) -> Option<SourceFile> {
+        self.files.find(config, relative_path)
+    }
+
+    fn read(&self, file: &SourceFile) -> io::Result<String> {
+        self.files.read(file)
+    }
+
+    fn read_bytes(&self, file: &SourceFile) -> io::Result<Vec<u8>> {
+        self.files.read_bytes(file)
+    }
+
+    fn len(&self, file: &SourceFile) -> Option<u64> {
+        self.files.len(file)
+    }
+
+    fn deleted(&self) -> Vec<PathBuf> {
+        self.deleted.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_need_two_refs() {
+        assert_eq!(
+            parse_range("main..release/2.4").unwrap(),
+            ("main", "release/2.4")
+        );
+        assert_eq!(parse_range("v1.0..HEAD~2").unwrap(), ("v1.0", "HEAD~2"));
+        for range in ["main", "main..", "..main", "main...dev", ""] {
+            assert!(parse_range(range).is_err(), "{}", range);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Fitting a bundle into a token budget (`fit_budget`): choosing which files to write so as
+//! many as possible fit, and reporting the ones left out.
+
+use crate::churn::Churn;
+use crate::source::SourceFile;
+use crate::{Config, DEFAULT_PRIORITY_PATTERNS, FileEntry, build_glob_set, parallel};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+/// The order in which files are considered for the budget after the priority patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FitStrategy {
+    /// Fewest tokens first, fitting the most files (default).
+    #[default]
+    Smallest,
+    /// Most tokens first, keeping the largest files.
+    Largest,
+    /// In output order.
+    Order,
+    /// Most commits within the churn window first, keeping the most active files.
+    Churn,
+}
+
+/// A file considered for the budget, with its estimated tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetedFile {
+    /// The path shown in the output.
+    pub path: PathBuf,
+    /// The estimated number of tokens of the file's content.
+    pub tokens: usize,
+}
+
+/// The outcome of fitting a run into `fit_budget`, in [`RunSummary::budget`].
+///
+/// [`RunSummary::budget`]: crate::RunSummary::budget
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BudgetSelection {
+    /// The budget, in tokens.
+    pub budget: usize,
+    /// The files written, in output order.
+    pub chosen: Vec<BudgetedFile>,
+    /// The files left out to stay within the budget, in output order.
+    pub omitted: Vec<BudgetedFile>,
+}
+
+impl BudgetSelection {
+    /// Returns the estimated tokens of the omitted files.
+    pub fn omitted_tokens(&self) -> usize {
+        self.omitted.iter().map(|file| file.tokens).sum()
+    }
+}
+
+/// The files of a run that were read ahead of writing, by path, each with what
+/// `prepare_file` returned, so it is written from that one read.
+pub(crate) type Prepared = HashMap<PathBuf, Result<Option<FileEntry>>>;
+
+/// What the budget needs to know about a file that could be bundled.
+struct Candidate {
+    relative_path: PathBuf,
+    display_path: PathBuf,
+    tokens: usize,
+    sha256: Option<String>,
+    /// The commits touching the file, with [`FitStrategy::Churn`].
+    commits: usize,
+}
+
+/// Leaves out files so the bundle fits in `fit_budget`.
+///
+/// Every file is prepared once to count its tokens, and the run writes the chosen files from
+/// that read; files that fail to prepare are kept, so the run skips them with their own reason. The others are considered in the order of the
+/// `priority` patterns, then of [`DEFAULT_PRIORITY_PATTERNS`], then of `fit_strategy`, with
+/// the output order breaking ties, and each is taken if it still fits, so later, smaller
+/// files can fill the space a large one left. With `dedup`, a file identical to one already
+/// taken costs nothing, since only a stub is written for it. The output order is unchanged.
+///
+/// # Arguments
+/// * `entries` - The files of the run, in output order; the omitted ones are removed.
+/// * `config` - The configuration options for the bundling process.
+/// * `budget` - The budget, in tokens.
+/// * `churn` - The commit counts for [`FitStrategy::Churn`], if the run loaded them.
+/// * `prepare` - Reads and transforms a file as the run would.
+/// * `on_omitted` - Called with the path of each omitted file.
+///
+/// # Returns
+/// * `Result<(BudgetSelection, Prepared)>` - The chosen and omitted files, and what preparing
+///   each remaining entry returned; or an error if a priority pattern is invalid.
+pub(crate) fn fit(
+    entries: &mut Vec<SourceFile>,
+    config: &Config,
+    budget: usize,
+    churn: Option<&Churn>,
+    prepare: impl Fn(&SourceFile) -> Result<Option<FileEntry>> + Sync,
+    on_omitted: &mut dyn FnMut(&Path),
+) -> Result<(BudgetSelection, Prepared)> {
+    let mut candidates = Vec::with_capacity(entries.len());
+    let mut results = Vec::with_capacity(entries.len());
+    parallel::for_each_ordered(
+        entries,
+        parallel::job_count(config.jobs),
+        |file| {
+            let result = prepare(file);
+            let candidate = match &result {
+                Ok(Some(entry)) => Some(Candidate {
+                    relative_path: entry.relative_path.clone(),
+                    display_path: entry.display_path.clone(),
+                    tokens: entry.tokens,
+                    sha256: entry.sha256.clone(),
+                    commits: churn.map_or(0, |churn| churn.commits(file)),
+                }),
+                _ => None,
+            };
+            (candidate, result)
+        },
+        |_, (candidate, result)| {
+            candidates.push(candidate);
+            results.push(result);
+            ControlFlow::Continue(())
+        },
+    );
+    let reserved = [&config.preamble, &config.postamble, &config.question]
+        .into_iter()
+        .flatten()
+        .map(|text| config.tokenizer.count(text))
+        .sum();
+    let chosen = select(&candidates, config, budget.saturating_sub(reserved))?;
+
+    let mut selection = BudgetSelection {
+        budget,
+        ..BudgetSelection::default()
+    };
+    let mut prepared = Prepared::with_capacity(entries.len());
+    let mut chosen = chosen.into_iter();
+    let mut candidates = candidates.into_iter().zip(results);
+    entries.retain(|file| {
+        let Some((candidate, result)) = candidates.next() else {
+            return true;
+        };
+        let Some(candidate) = candidate else {
+            prepared.insert(file.path.clone(), result);
+            return true;
+        };
+        let chosen = chosen.next().unwrap_or(true);
+        let budgeted = BudgetedFile {
+            path: candidate.display_path,
+            tokens: candidate.tokens,
+        };
+        if chosen {
+            selection.chosen.push(budgeted);
+            prepared.insert(file.path.clone(), result);
+        } else {
+            on_omitted(&file.path);
+            selection.omitted.push(budgeted);
+        }
+        chosen
+    });
+    Ok((selection, prepared))
+}
+
+/// Chooses the candidates that fit in the remaining budget; see [`fit`] for the order.
+///
+/// # Returns
+/// * `Result<Vec<bool>>` - Whether each prepared candidate is chosen, in order, skipping
+///   the files that failed to prepare; or an error if a priority pattern is invalid.
+fn select(
+    candidates: &[Option<Candidate>],
+    config: &Config,
+    mut remaining: usize,
+) -> Result<Vec<bool>> {
+    let mut patterns: Vec<&str> = config.priority.iter().map(String::as_str).collect();
+    patterns.extend(DEFAULT_PRIORITY_PATTERNS);
+    let priorities = build_glob_set(&patterns).context("Invalid --priority pattern")?;
+
+    let prepared: Vec<&Candidate> = candidates.iter().flatten().collect();
+    let mut order: Vec<usize> = (0..prepared.len()).collect();
+    order.sort_by_cached_key(|&index| {
+        let candidate = prepared[index];
+        let rank = priorities
+            .matches(&candidate.relative_path)
+            .into_iter()
+            .min()
+            .unwrap_or(patterns.len());
+        let size = match config.fit_strategy {
+            FitStrategy::Smallest => candidate.tokens,
+            FitStrategy::Largest => usize::MAX - candidate.tokens,
+            FitStrategy::Order => 0,
+            FitStrategy::Churn => usize::MAX - candidate.commits,
+        };
+        (rank, size, index)
+    });
+
+    let mut chosen = vec![false; prepared.len()];
+    let mut hashes: HashSet<&str> = HashSet::new();
+    for index in order {
+        let candidate = prepared[index];
+        let hash = candidate.sha256.as_deref().filter(|_| config.dedup);
+        let cost = match hash {
+            Some(hash) if hashes.contains(hash) => 0,
+            _ => candidate.tokens,
+        };
+        if cost <= remaining {
+            remaining -= cost;
+            chosen[index] = true;
+            hashes.extend(hash);
+        }
+    }
+    Ok(chosen)
+}
@@ -0,0 +1,131 @@
+//! The metadata header at the top of a bundle: what produced it and from what state.
+
+use crate::git::GitRepo;
+use crate::meta::format_time;
+use crate::{Config, LanguageStats};
+use crate::{between, languages, reproducible};
+
+/// What produced a bundle, written before the first file with `metadata_header`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BundleMetadata {
+    /// The name and version of the tool, e.g. `codebase-to-prompt 1.0.0`.
+    pub tool: String,
+    /// When the bundle was generated, as an RFC 3339 time; `None` for a `reproducible` run
+    /// without `SOURCE_DATE_EPOCH`.
+    pub generated_at: Option<String>,
+    /// The base names of the input directories.
+    pub directories: Vec<String>,
+    /// The checked-out branch, if the input is in a git repository with one.
+    pub git_branch: Option<String>,
+    /// The abbreviated hash of the HEAD commit, if the input is in a git repository.
+    pub git_commit: Option<String>,
+    /// The refs of a `between` run with the commits they resolve to, e.g.
+    /// `main @ 1a2b3c4..release/2.4 @ 5d6e7f8`.
+    pub git_between: Option<String>,
+    /// The number of files that pass the path and extension filters, before the filters
+    /// that read the files, such as `skip_empty` or binary detection.
+    pub files: usize,
+    /// The extensions and globs included, with those of `languages` first, or empty if every
+    /// file is.
+    pub include: Vec<String>,
+    /// The extensions and directories excluded.
+    pub exclude: Vec<String>,
+    /// The line counts of each language among the included files, with `lang_stats`;
+    /// empty otherwise.
+    pub languages: Vec<LanguageStats>,
+}
+
+impl BundleMetadata {
+    /// Collects the metadata of a run; a missing or unreadable repository leaves the git
+    /// fields empty.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    /// * `files` - The number of files that pass the path and extension filters.
+    ///
+    /// # Returns
+    /// * `BundleMetadata` - The metadata.
+    pub(crate) fn collect(config: &Config, files: usize) -> BundleMetadata {
+        // Committing a reproducible bundle would move HEAD and change the next one.
+        let repo = match config.reproducible {
+            true => None,
+            false => GitRepo::discover(&config.directories).ok().flatten(),
+        };
+        let directories = config
+            .directories
+            .iter()
+            .map(|directory| {
+                let absolute = std::path::absolute(directory).unwrap_or_else(|_| directory.clone());
+                absolute
+                    .file_name()
+                    .unwrap_or(absolute.as_os_str())
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        BundleMetadata {
+            tool: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            generated_at: reproducible::run_time(config)
+                .map(|time| format_time(time, reproducible::utc(config))),
+            directories,
+            git_branch: repo.as_ref().and_then(|repo| repo.branch().ok().flatten()),
+            git_commit: repo.as_ref().and_then(|repo| repo.short_hash(false).ok()),
+            git_between: config.between.as_deref().map(|range| {
+                let Ok((base, head)) = between::parse_range(range) else {
+                    return range.to_string();
+                };
+                let resolved = |rev: &str| match repo.as_ref().and_then(|repo| repo.abbreviate(rev))
+                {
+                    Some(id) => format!("{} @ {}", rev, id),
+                    None => rev.to_string(),
+                };
+                format!("{}..{}", resolved(base), resolved(head))
+            }),
+            files,
+            include: languages::include_patterns(&config.languages)
+                .unwrap_or_default()
+                .into_iter()
+                .chain(config.include.iter().cloned())
+                .filter(|extension| !extension.is_empty())
+                .collect(),
+            exclude: config
+                .exclude
+                .iter()
+                .cloned()
+                .chain(config.exclude_dirs.iter().map(|dir| format!("{}/", dir)))
+                .collect(),
+            languages: Vec::new(),
+        }
+    }
+
+    /// Returns the metadata as labelled fields, in the order they are written; the
+    /// [`languages`](BundleMetadata::languages) table is written after them.
+    ///
+    /// # Returns
+    /// * `Vec<(&'static str, String)>` - e.g. `("git", "main @ 1a2b3c4")`; the `git` field is
+    ///   left out when the input is not in a repository.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let list = |items: &[String], empty: &str| match items {
+            [] => empty.to_string(),
+            items => items.join(", "),
+        };
+        let mut fields = vec![("tool", self.tool.clone())];
+        fields.extend(self.generated_at.clone().map(|time| ("generated", time)));
+        fields.push(("directory", list(&self.directories, "(none)")));
+        let git = match (&self.git_branch, &self.git_commit) {
+            (Some(branch), Some(commit)) => Some(format!("{} @ {}", branch, commit)),
+            (Some(branch), None) => Some(branch.clone()),
+            (None, Some(commit)) => Some(commit.clone()),
+            (None, None) => None,
+        };
+        fields.extend(git.map(|git| ("git", git)));
+        fields.extend(self.git_between.clone().map(|range| ("between", range)));
+        fields.extend([
+            ("files", self.files.to_string()),
+            ("include", list(&self.include, "(all)")),
+            ("exclude", list(&self.exclude, "(none)")),
+        ]);
+        fields
+    }
+}
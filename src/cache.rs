@@ -0,0 +1,337 @@
+//! The incremental cache: transformed file content kept between runs, so files that have not
+//! changed are not read or transformed again.
+//!
+//! Entries are keyed by relative path and reused while the file's size and modification time
+//! match. The options that change the transformed content are hashed into a fingerprint, and a
+//! cache written with a different fingerprint is discarded as a whole. A missing, unreadable,
+//! or corrupt cache file is treated as empty; the cache never fails a run.
+
+use crate::sha256::sha256_hex;
+use crate::source::{FileSource, SourceFile};
+use crate::{Config, FileEntry, needs_sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// The name of the cache file the CLI uses by default, next to the output file.
+pub const DEFAULT_CACHE_FILE: &str = ".c2p-cache";
+
+/// The first line of a cache file, followed by a space and the SHA-256 of the rest of the file.
+const MAGIC: &str = "c2p-cache 2";
+
+/// The transformed content of a file as of its last run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CachedFile {
+    /// The size of the file in bytes when it was read.
+    pub(crate) size: u64,
+    /// The last-modified time of the file when it was read.
+    pub(crate) modified: SystemTime,
+    /// The SHA-256 of the content before transformations, if the run computed it.
+    pub(crate) sha256: Option<String>,
+    /// The content after transformations, before lines are selected.
+    pub(crate) content: String,
+    /// The number of lines of license headers removed from the content.
+    pub(crate) license_lines: usize,
+}
+
+/// The cached files of a run, read from or written to one cache file.
+#[derive(Debug)]
+pub(crate) struct Cache {
+    path: PathBuf,
+    fingerprint: String,
+    files: HashMap<PathBuf, CachedFile>,
+}
+
+impl Cache {
+    /// Reads a cache file, starting cold if it is missing, corrupt, or was written with
+    /// different options.
+    ///
+    /// # Arguments
+    /// * `path` - The cache file.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Cache` - The cached files that can be reused by this run.
+    pub(crate) fn load(path: &Path, config: &Config) -> Cache {
+        let mut cache = Cache {
+            path: path.to_path_buf(),
+            fingerprint: fingerprint(config),
+            files: HashMap::new(),
+        };
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return cache,
+            Err(err) => {
+                warn!("Ignoring unreadable cache {}: {}", path.display(), err);
+                return cache;
+            }
+        };
+        match parse(&data) {
+            Some((fingerprint, _)) if fingerprint != cache.fingerprint => {
+                debug!("Options changed since the cache was written; starting cold");
+            }
+            Some((_, files)) => cache.files = files,
+            None => warn!("Ignoring corrupt cache {}", path.display()),
+        }
+        cache
+    }
+
+    /// Creates an empty cache written to the same file with the same options.
+    pub(crate) fn successor(&self) -> Cache {
+        Cache {
+            path: self.path.clone(),
+            fingerprint: self.fingerprint.clone(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Looks up a file whose size and modification time still match its cached entry.
+    ///
+    /// # Arguments
+    /// * `file` - The file to look up.
+    /// * `source` - The source the file is read from.
+    ///
+    /// # Returns
+    /// * `Option<&CachedFile>` - The cached entry, or `None` if there is none or it is stale.
+    pub(crate) fn get(&self, file: &SourceFile, source: &dyn FileSource) -> Option<&CachedFile> {
+        let cached = self.files.get(&file.relative_path)?;
+        (source.len(file)? == cached.size && source.modified(file)? == cached.modified)
+            .then_some(cached)
+    }
+
+    /// Adds a prepared file, unless its source does not report a modification time or it is
+    /// an embedded binary file, which is encoded again on each run.
+    pub(crate) fn insert(&mut self, entry: &FileEntry) {
+        if let Some(modified) = entry.modified.filter(|_| !entry.is_base64()) {
+            self.files.insert(
+                entry.relative_path.clone(),
+                CachedFile {
+                    size: entry.size,
+                    modified,
+                    sha256: entry.sha256.clone(),
+                    content: entry.content.clone(),
+                    license_lines: entry.license_lines,
+                },
+            );
+        }
+    }
+
+    /// Writes the cache file, replacing it atomically; a failure is logged, not returned.
+    pub(crate) fn save(&self) {
+        if let Err(err) = self.write() {
+            warn!("Failed to write cache {}: {}", self.path.display(), err);
+        }
+    }
+
+    /// Serializes the cache to a temporary file next to the cache file and moves it in place.
+    fn write(&self) -> io::Result<()> {
+        let mut body = Vec::new();
+        writeln!(body, "{}", self.fingerprint)?;
+        let mut paths: Vec<&PathBuf> = self.files.keys().collect();
+        paths.sort();
+        for path in paths {
+            let file = &self.files[path];
+            let (Some(path), Ok(modified)) =
+                (path.to_str(), file.modified.duration_since(UNIX_EPOCH))
+            else {
+                continue;
+            };
+            writeln!(
+                body,
+                "{} {} {} {} {} {} {}",
+                path.len(),
+                file.size,
+                modified.as_secs(),
+                modified.subsec_nanos(),
+                file.sha256.as_deref().unwrap_or("-"),
+                file.license_lines,
+                file.content.len()
+            )?;
+            body.extend_from_slice(path.as_bytes());
+            body.extend_from_slice(file.content.as_bytes());
+            body.push(b'\n');
+        }
+
+        let directory = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut temp = tempfile::NamedTempFile::new_in(directory)?;
+        writeln!(temp, "{} {}", MAGIC, sha256_hex(&body))?;
+        temp.write_all(&body)?;
+        temp.persist(&self.path).map_err(|err| err.error)?;
+        Ok(())
+    }
+}
+
+/// Hashes the options that change the transformed content of a file.
+///
+/// Options applied before a file is read (filters) or after the cached content (line
+/// selection and formatting) are left out, so changing them keeps the cache.
+fn fingerprint(config: &Config) -> String {
+    let options = format!(
+        "{:?}",
+        (
+            env!("CARGO_PKG_VERSION"),
+            config.skip_generated,
+            config.normalize_eol,
+            (config.strip_frontmatter, config.notebook_outputs),
+            (config.strip_license_headers, &config.license_pattern),
+            (config.strip_comments, config.keep_doc_comments),
+            config.signatures_only,
+            config.expand_tabs,
+            (config.trim_trailing_whitespace, config.strip_ansi),
+            (config.compact, config.max_line_length),
+            (&config.filter_cmd, &config.filter_cmd_ext),
+            needs_sha256(config),
+        )
+    );
+    sha256_hex(options.as_bytes())
+}
+
+/// Parses a cache file into its fingerprint and entries.
+///
+/// # Arguments
+/// * `data` - The content of the cache file.
+///
+/// # Returns
+/// * `Option<(&str, HashMap<PathBuf, CachedFile>)>` - The fingerprint and entries, or `None`
+///   if the file is truncated, fails its checksum, or is otherwise malformed.
+fn parse(data: &[u8]) -> Option<(&str, HashMap<PathBuf, CachedFile>)> {
+    let (header, body) = split_line(data)?;
+    let checksum = std::str::from_utf8(header)
+        .ok()?
+        .strip_prefix(MAGIC)?
+        .strip_prefix(' ')?;
+    if sha256_hex(body) != checksum {
+        return None;
+    }
+    let (fingerprint, mut rest) = split_line(body)?;
+    let fingerprint = std::str::from_utf8(fingerprint).ok()?;
+
+    let mut files = HashMap::new();
+    while !rest.is_empty() {
+        let (line, data) = split_line(rest)?;
+        let line = std::str::from_utf8(line).ok()?;
+        let mut fields = line.split(' ');
+        let mut number = || fields.next()?.parse::<u64>().ok();
+        let path_len = usize::try_from(number()?).ok()?;
+        let size = number()?;
+        let modified =
+            UNIX_EPOCH.checked_add(Duration::new(number()?, u32::try_from(number()?).ok()?))?;
+        let sha256 = match fields.next()? {
+            "-" => None,
+            hash => Some(hash.to_string()),
+        };
+        let license_lines = fields.next()?.parse::<usize>().ok()?;
+        let content_len = fields.next()?.parse::<usize>().ok()?;
+        if fields.next().is_some() {
+            return None;
+        }
+
+        let content_end = path_len.checked_add(content_len)?;
+        let path = std::str::from_utf8(data.get(..path_len)?).ok()?;
+        let content = std::str::from_utf8(data.get(path_len..content_end)?).ok()?;
+        if data.get(content_end) != Some(&b'\n') {
+            return None;
+        }
+        files.insert(
+            PathBuf::from(path),
+            CachedFile {
+                size,
+                modified,
+                sha256,
+                content: content.to_string(),
+                license_lines,
+            },
+        );
+        rest = &data[content_end + 1..];
+    }
+    Some((fingerprint, files))
+}
+
+/// Splits the first line off a byte slice, without its newline.
+fn split_line(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = data.iter().position(|&byte| byte == b'\n')?;
+    Some((&data[..end], &data[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemorySource;
+
+    fn cached(content: &str) -> CachedFile {
+        CachedFile {
+            size: content.len() as u64,
+            modified: UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789),
+            sha256: None,
+            content: content.to_string(),
+            license_lines: 0,
+        }
+    }
+
+    #[test]
+    fn round_trips_through_the_cache_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(".c2p-cache");
+        let config = Config::default();
+        let mut cache = Cache::load(&path, &config);
+        cache.files.insert(
+            PathBuf::from("src/a b.rs"),
+            CachedFile {
+                license_lines: 18,
+                ..cached("fn a() {}\n\nline two\n")
+            },
+        );
+        cache.files.insert(
+            PathBuf::from("empty.txt"),
+            CachedFile {
+                sha256: Some(sha256_hex(b"")),
+                ..cached("")
+            },
+        );
+        cache.save();
+
+        let loaded = Cache::load(&path, &config);
+        assert_eq!(loaded.files, cache.files);
+    }
+
+    #[test]
+    fn corrupt_or_outdated_caches_start_cold() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(".c2p-cache");
+        let mut cache = Cache::load(&path, &Config::default());
+        cache
+            .files
+            .insert(PathBuf::from("a.rs"), cached("fn a() {}\n"));
+        cache.save();
+
+        let changed = Config::builder().strip_comments(true).build();
+        assert!(Cache::load(&path, &changed).files.is_empty());
+
+        let data = fs::read(&path).unwrap();
+        for corrupt in [&data[..data.len() - 3], &data[1..], b"garbage".as_slice()] {
+            fs::write(&path, corrupt).unwrap();
+            assert!(Cache::load(&path, &Config::default()).files.is_empty());
+        }
+        let mut flipped = data.clone();
+        *flipped.last_mut().unwrap() = b'x';
+        fs::write(&path, flipped).unwrap();
+        assert!(Cache::load(&path, &Config::default()).files.is_empty());
+    }
+
+    #[test]
+    fn sources_without_modification_times_are_never_hits() {
+        let source: MemorySource = [("a.rs", "fn a() {}\n")].into_iter().collect();
+        let mut cache = Cache::load(Path::new("unused"), &Config::default());
+        cache
+            .files
+            .insert(PathBuf::from("a.rs"), cached("fn a() {}\n"));
+        let file = SourceFile::new(PathBuf::from("a.rs"), PathBuf::from("a.rs"));
+        assert!(cache.get(&file, &source).is_none());
+    }
+}
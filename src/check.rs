@@ -0,0 +1,263 @@
+//! The `--check` mode: regenerates the bundle in memory and compares it with the committed
+//! output file, for CI jobs that fail when the bundle is stale.
+
+use crate::formatter::{FileGroup, Formatter, RunContext};
+use crate::hooks::NoHooks;
+use crate::{
+    BudgetSelection, Config, FileEntry, Input, RunSummary, SkipReason, output_template,
+    process_directory, reproducible,
+};
+use anyhow::{Context, Result, bail};
+use regex_automata::meta::Regex;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// An RFC 3339 time as written by the metadata header, e.g. `2024-05-02T14:03:11Z`.
+const TIME: &str = r"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(?:Z|[+-]\d{2}:\d{2})";
+
+/// How the committed output compares with a fresh bundle, from [`check_output`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CheckOutcome {
+    /// The output file matches the bundle.
+    UpToDate {
+        /// The output file.
+        path: PathBuf,
+    },
+    /// The output file differs from the bundle.
+    Stale {
+        /// The output file.
+        path: PathBuf,
+        /// The files whose sections differ, in bundle order; empty when only the rest of
+        /// the bundle does, such as the header, the tree, or a file that was removed.
+        changed: Vec<PathBuf>,
+    },
+    /// The output file does not exist.
+    Missing {
+        /// The output file.
+        path: PathBuf,
+    },
+}
+
+/// Checks that the output file holds the bundle a run would write now, without writing
+/// anything.
+///
+/// The bundle is generated in memory with `reproducible` set. The time in the metadata
+/// header, front matter, and AsciiDoc `:revdate:` is ignored on both sides unless
+/// `SOURCE_DATE_EPOCH` pins it, so a bundle committed without `--reproducible` can still be
+/// checked; everything else must match byte for byte.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process; `output` names the file
+///   to check.
+///
+/// # Returns
+/// * `Result<CheckOutcome>` - How the output file compares, or an error if the options
+///   cannot be checked or the files cannot be read.
+pub fn check_output(config: &Config) -> Result<CheckOutcome> {
+    let config = Config {
+        reproducible: true,
+        dry_run: false,
+        ..config.clone()
+    };
+    let conflicts = [
+        ("--output-dir", config.output_dir.is_some()),
+        ("--compress", config.compress.is_some()),
+        ("--append", config.append),
+        ("--clipboard", config.clipboard),
+    ];
+    if let Some((flag, _)) = conflicts.iter().find(|(_, set)| *set) {
+        bail!("--check cannot be combined with {}", flag);
+    }
+    reproducible::check(&config)?;
+    let Some(path) = output_template::output_path(config.output.as_deref(), &config)? else {
+        bail!("--check needs --output naming the bundle to compare");
+    };
+    if !path.exists() {
+        return Ok(CheckOutcome::Missing { path });
+    }
+    let existing = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let mut formatter = config.format.formatter();
+    let mut sections = Sections {
+        inner: formatter.as_mut(),
+        sections: Vec::new(),
+    };
+    let mut bundle = Vec::new();
+    let input = Input::open(&config, vec![path.as_path()])?;
+    process_directory(
+        &config,
+        input.source(),
+        &mut bundle,
+        false,
+        &mut NoHooks,
+        &mut sections,
+    )?;
+
+    let pinned = reproducible::run_time(&config).is_some();
+    let normalize = |bytes: &[u8]| {
+        let text = String::from_utf8_lossy(bytes).into_owned();
+        if pinned { text } else { without_times(&text) }
+    };
+    let (existing, bundle) = (normalize(&existing), normalize(&bundle));
+    if existing == bundle {
+        return Ok(CheckOutcome::UpToDate { path });
+    }
+    let changed = sections
+        .sections
+        .into_iter()
+        .filter(|(_, section)| !existing.contains(&normalize(section)))
+        .map(|(path, _)| path)
+        .collect();
+    Ok(CheckOutcome::Stale { path, changed })
+}
+
+/// Removes the lines that carry the time of a run: the `generated` field of the metadata
+/// header in each format, the `date` of the front matter, and the AsciiDoc `:revdate:`.
+fn without_times(text: &str) -> String {
+    let pattern = format!(
+        r"(?m)^(?:> |<)?(?:generated|date)(?:>|::?) ?{TIME}(?:</generated>)? *\r?\n|^:revdate: \d{{4}}-\d{{2}}-\d{{2}}\r?\n|generated: {TIME}(?:\\r)?\\n"
+    );
+    // The pattern is fixed, so it always compiles.
+    let regex = Regex::new(&pattern).expect("valid time pattern");
+    let mut kept = String::with_capacity(text.len());
+    let mut at = 0;
+    for found in regex.find_iter(text) {
+        kept.push_str(&text[at..found.start()]);
+        at = found.end();
+    }
+    kept.push_str(&text[at..]);
+    kept
+}
+
+/// A formatter that keeps what the inner formatter writes for each file, by path.
+struct Sections<'a> {
+    inner: &'a mut dyn Formatter,
+    sections: Vec<(PathBuf, Vec<u8>)>,
+}
+
+impl Sections<'_> {
+    /// Writes one file's section through a buffer, keeping a copy.
+    fn record(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        write: impl FnOnce(&mut dyn Formatter, &mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        let mut section = Vec::new();
+        write(self.inner, &mut section)?;
+        writer.write_all(&section)?;
+        self.sections.push((entry.relative_path.clone(), section));
+        Ok(())
+    }
+}
+
+impl Formatter for Sections<'_> {
+    fn embeds_framing_text(&self) -> bool {
+        self.inner.embeds_framing_text()
+    }
+
+    fn begin(&mut self, writer: &mut dyn Write, context: &RunContext) -> Result<()> {
+        self.inner.begin(writer, context)
+    }
+
+    fn file(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        self.record(writer, entry, |inner, writer| {
+            inner.file(writer, entry, config)
+        })
+    }
+
+    fn group(&mut self, writer: &mut dyn Write, group: &FileGroup, config: &Config) -> Result<()> {
+        self.inner.group(writer, group, config)
+    }
+
+    fn header(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        self.record(writer, entry, |inner, writer| {
+            inner.header(writer, entry, config)
+        })
+    }
+
+    fn duplicate(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        original: &Path,
+        config: &Config,
+    ) -> Result<()> {
+        self.record(writer, entry, |inner, writer| {
+            inner.duplicate(writer, entry, original, config)
+        })
+    }
+
+    fn placeholder(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        reason: SkipReason,
+        config: &Config,
+    ) -> Result<()> {
+        self.record(writer, entry, |inner, writer| {
+            inner.placeholder(writer, entry, reason, config)
+        })
+    }
+
+    fn deleted(
+        &mut self,
+        writer: &mut dyn Write,
+        paths: &[PathBuf],
+        config: &Config,
+    ) -> Result<()> {
+        self.inner.deleted(writer, paths, config)
+    }
+
+    fn omitted(
+        &mut self,
+        writer: &mut dyn Write,
+        selection: &BudgetSelection,
+        config: &Config,
+    ) -> Result<()> {
+        self.inner.omitted(writer, selection, config)
+    }
+
+    fn limited(
+        &mut self,
+        writer: &mut dyn Write,
+        limit: usize,
+        left_out: usize,
+        config: &Config,
+    ) -> Result<()> {
+        self.inner.limited(writer, limit, left_out, config)
+    }
+
+    fn digest(&mut self, writer: &mut dyn Write, digest: &str, config: &Config) -> Result<()> {
+        self.inner.digest(writer, digest, config)
+    }
+
+    fn question(&mut self, writer: &mut dyn Write, question: &str, config: &Config) -> Result<()> {
+        self.inner.question(writer, question, config)
+    }
+
+    fn finish(&mut self, writer: &mut dyn Write, summary: &RunSummary) -> Result<()> {
+        self.inner.finish(writer, summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_times_are_removed_in_each_format() {
+        let text = "> tool: c2p  \n> generated: 2024-05-02T14:03:11+02:00  \n> files: 2  \n";
+        assert_eq!(without_times(text), "> tool: c2p  \n> files: 2  \n");
+        let text = "<tool>c2p</tool>\n<generated>2024-05-02T14:03:11Z</generated>\n";
+        assert_eq!(without_times(text), "<tool>c2p</tool>\n");
+        let text = "= Codebase bundle\n:revdate: 2024-05-02\n\ngenerated:: 2024-05-02T14:03:11Z\n";
+        assert_eq!(without_times(text), "= Codebase bundle\n\n");
+        let text = r#"{"content": "tool: c2p\ngenerated: 2024-05-02T14:03:11Z\nfiles: 1"}"#;
+        assert_eq!(without_times(text), r#"{"content": "tool: c2p\nfiles: 1"}"#);
+        let text = "let created = \"2024-05-02T14:03:11Z\";\n";
+        assert_eq!(without_times(text), text);
+    }
+}
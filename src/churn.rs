@@ -0,0 +1,110 @@
+//! Git activity per file: the commit counts behind `--sort churn` and
+//! `--fit-strategy churn`.
+
+use crate::git::GitRepo;
+use crate::source::SourceFile;
+use crate::{Config, FitStrategy, SortOrder};
+use anyhow::{Result, anyhow, bail};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// How many of the most changed files the summary lists.
+const TOP_FILES: usize = 10;
+
+/// A file and the number of commits that touched it, in [`RunSummary::churn`].
+///
+/// [`RunSummary::churn`]: crate::RunSummary::churn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChurnFile {
+    /// The path shown in the output.
+    pub path: PathBuf,
+    /// The number of commits within `churn_window` that touched the file.
+    pub commits: usize,
+}
+
+/// The commit counts of the files of a repository within `churn_window`.
+pub(crate) struct Churn {
+    /// The canonical working tree, which the counted paths are relative to.
+    workdir: PathBuf,
+    counts: HashMap<PathBuf, usize>,
+}
+
+impl Churn {
+    /// Counts the commits per file when the run ranks files by churn.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<Option<Churn>>` - The counts, `None` if neither `sort` nor `fit_strategy`
+    ///   asks for them, or an error if the input is not in a git repository with a working
+    ///   tree.
+    pub(crate) fn for_run(config: &Config) -> Result<Option<Churn>> {
+        let by_budget = config.fit_budget.is_some() && config.fit_strategy == FitStrategy::Churn;
+        if !matches!(config.sort, SortOrder::Churn) && !by_budget {
+            return Ok(None);
+        }
+        let directories: Vec<String> = config
+            .directories
+            .iter()
+            .map(|directory| directory.display().to_string())
+            .collect();
+        let Some(repo) = GitRepo::discover(&config.directories)? else {
+            bail!(
+                "Ranking files by churn needs a git repository, but {} is not inside one",
+                directories.join(", ")
+            );
+        };
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| {
+                anyhow!("Ranking files by churn needs a working tree, not a bare repository")
+            })?
+            .canonicalize()?;
+        let since = config.churn_window.resolve(SystemTime::now());
+        Ok(Some(Churn {
+            counts: repo.commit_counts(since)?,
+            workdir,
+        }))
+    }
+
+    /// Returns the number of commits that touched a file; zero for files outside the
+    /// working tree or not in the history.
+    pub(crate) fn commits(&self, file: &SourceFile) -> usize {
+        file.path
+            .canonicalize()
+            .ok()
+            .and_then(|path| {
+                let relative = path.strip_prefix(&self.workdir).ok()?;
+                self.counts.get(relative).copied()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Returns the most changed of the run's files, most commits first, then by path.
+    ///
+    /// # Arguments
+    /// * `entries` - The files of the run.
+    ///
+    /// # Returns
+    /// * `Vec<ChurnFile>` - Up to ten files touched by at least one commit.
+    pub(crate) fn top(&self, entries: &[SourceFile]) -> Vec<ChurnFile> {
+        let mut files: Vec<ChurnFile> = entries
+            .iter()
+            .map(|file| ChurnFile {
+                path: file.display_path.clone(),
+                commits: self.commits(file),
+            })
+            .filter(|file| file.commits > 0)
+            .collect();
+        files.sort_by(|a, b| {
+            Reverse(a.commits)
+                .cmp(&Reverse(b.commits))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        files.truncate(TOP_FILES);
+        files
+    }
+}
@@ -0,0 +1,68 @@
+//! Copying the generated bundle to the system clipboard via the platform's clipboard tool.
+
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use tracing::info;
+
+/// Returns the clipboard command for the current platform.
+///
+/// On Linux this requires a running Wayland or X11 session; headless environments get a
+/// descriptive error instead of a failed spawn later on.
+///
+/// # Returns
+/// * `Result<(&'static str, &'static [&'static str])>` - The program and its arguments.
+pub fn clipboard_command() -> Result<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        Ok(("pbcopy", &[]))
+    } else if cfg!(windows) {
+        Ok(("clip", &[]))
+    } else if env::var_os("WAYLAND_DISPLAY").is_some() {
+        Ok(("wl-copy", &[]))
+    } else if env::var_os("DISPLAY").is_some() {
+        if Command::new("xclip").arg("-version").output().is_ok() {
+            Ok(("xclip", &["-selection", "clipboard"]))
+        } else {
+            Ok(("xsel", &["--clipboard", "--input"]))
+        }
+    } else {
+        bail!(
+            "Cannot access the clipboard: no display server found (neither WAYLAND_DISPLAY nor DISPLAY is set)"
+        )
+    }
+}
+
+/// Copies the given bytes to the system clipboard.
+///
+/// # Arguments
+/// * `contents` - The text to place on the clipboard.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error naming the clipboard tool.
+pub fn copy_to_clipboard(contents: &[u8]) -> Result<()> {
+    let (program, args) = clipboard_command()?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run `{}` to access the clipboard", program))?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(contents)
+        .with_context(|| format!("Failed to write to `{}`", program))?;
+
+    let status = child
+        .wait()
+        .with_context(|| format!("Failed to wait for `{}`", program))?;
+    if !status.success() {
+        bail!("`{}` exited with {}", program, status);
+    }
+
+    info!("Copied {} bytes to the clipboard.", contents.len());
+    Ok(())
+}
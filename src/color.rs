@@ -0,0 +1,107 @@
+//! Terminal colors for the console format: when to use them and how to highlight code.
+
+use crate::comments::{Syntax, Token, tokens};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Bold text, used for file paths.
+pub(crate) const BOLD: &str = "\x1b[1m";
+/// Dimmed text, used for separators, labels, and the line-number gutter.
+pub(crate) const DIM: &str = "\x1b[2m";
+/// Resets all attributes.
+pub(crate) const RESET: &str = "\x1b[0m";
+/// Dimmed italic text, used for comments.
+const COMMENT: &str = "\x1b[2;3m";
+/// Green text, used for string literals.
+const LITERAL: &str = "\x1b[32m";
+
+/// When the console format uses colors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Color output written to a terminal, unless `NO_COLOR` is set.
+    #[default]
+    Auto,
+    /// Always color output that is not written to a file or the clipboard.
+    Always,
+    /// Never color output.
+    Never,
+}
+
+impl ColorChoice {
+    /// Decides whether to color output.
+    ///
+    /// # Arguments
+    /// * `terminal` - Whether the output goes to a terminal.
+    ///
+    /// # Returns
+    /// * `bool` - `true` if the output should contain color escapes.
+    pub(crate) fn enabled(self, terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                terminal && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        }
+    }
+}
+
+/// Highlights the comments and string literals of a file.
+///
+/// Escapes are closed at the end of each line, so the result can be split into lines.
+///
+/// # Arguments
+/// * `content` - The text to highlight.
+/// * `extension` - The file extension, used to select the syntax.
+///
+/// # Returns
+/// * `String` - The highlighted text, or the text unchanged for unknown extensions.
+pub(crate) fn highlight(content: &str, extension: &str) -> String {
+    let Some(syntax) = Syntax::from_extension(extension) else {
+        return content.to_string();
+    };
+    let mut result = String::with_capacity(content.len());
+    let mut end = 0;
+    for (range, token) in tokens(content, syntax) {
+        let style = match token {
+            Token::Comment => COMMENT,
+            Token::Literal => LITERAL,
+        };
+        result.push_str(&content[end..range.start]);
+        for (i, line) in content[range.clone()].split('\n').enumerate() {
+            if i > 0 {
+                result.push('\n');
+            }
+            if !line.is_empty() {
+                result.push_str(style);
+                result.push_str(line);
+                result.push_str(RESET);
+            }
+        }
+        end = range.end;
+    }
+    result.push_str(&content[end..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_comments_and_literals_per_line() {
+        assert_eq!(
+            highlight("x = \"a\" /* b\nc */\n", "c"),
+            "x = \x1b[32m\"a\"\x1b[0m \x1b[2;3m/* b\x1b[0m\n\x1b[2;3mc */\x1b[0m\n"
+        );
+        assert_eq!(highlight("x = \"a\"\n", "txt"), "x = \"a\"\n");
+    }
+
+    #[test]
+    fn never_and_always_ignore_the_terminal() {
+        assert!(ColorChoice::Always.enabled(false));
+        assert!(!ColorChoice::Never.enabled(true));
+        assert!(!ColorChoice::Auto.enabled(false));
+    }
+}
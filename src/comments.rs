@@ -0,0 +1,633 @@
+//! Language-aware removal of comments from source files.
+//!
+//! Each supported extension maps to a [`Syntax`] family with a small tokenizer that
+//! understands the family's string literals, so comment-like sequences inside strings are
+//! left alone. Lines that become blank because a comment was removed are dropped entirely;
+//! all other lines keep their original layout (minus trailing whitespace left behind by a
+//! removed end-of-line comment).
+//!
+//! The same tokenizer finds the comments and string literals that the console format
+//! highlights.
+
+use std::ops::Range;
+
+/// The comment syntax family of a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// Rust: `//` and nested `/* */` comments, raw strings, char literals vs lifetimes.
+    Rust,
+    /// C, C++, Java, Go, JavaScript, TypeScript and friends: `//` and `/* */` comments.
+    CLike,
+    /// CSS: `/* */` comments only.
+    Css,
+    /// Python, Ruby, Perl, R, TOML: `#` comments anywhere on a line.
+    Hash,
+    /// Shell scripts and YAML: `#` comments only at line start or after whitespace.
+    HashSpaced,
+    /// HTML, XML, SVG: `<!-- -->` comments.
+    Markup,
+    /// SQL: `--` and `/* */` comments, `''`-escaped strings.
+    Sql,
+    /// Lua: `--` and `--[[ ]]` comments, `[[ ]]` long strings.
+    Lua,
+}
+
+impl Syntax {
+    /// Returns the comment syntax for a file extension, if it is supported.
+    ///
+    /// # Arguments
+    /// * `extension` - The file extension, without the leading dot.
+    ///
+    /// # Returns
+    /// * `Option<Syntax>` - The syntax family, or `None` for unknown extensions.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let syntax = match extension.to_ascii_lowercase().as_str() {
+            "rs" => Syntax::Rust,
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hxx" | "hh" | "cs" | "java" | "kt"
+            | "kts" | "scala" | "go" | "swift" | "dart" | "js" | "jsx" | "mjs" | "cjs" | "ts"
+            | "tsx" | "mts" | "cts" | "proto" | "scss" | "less" | "groovy" | "zig" => Syntax::CLike,
+            "css" => Syntax::Css,
+            "py" | "pyi" | "rb" | "pl" | "pm" | "r" | "toml" | "cmake" | "tf" => Syntax::Hash,
+            "sh" | "bash" | "zsh" | "fish" | "yaml" | "yml" | "dockerfile" | "mk" => {
+                Syntax::HashSpaced
+            }
+            "html" | "htm" | "xml" | "svg" | "xhtml" | "vue" | "xaml" | "csproj" => Syntax::Markup,
+            "sql" => Syntax::Sql,
+            "lua" => Syntax::Lua,
+            _ => return None,
+        };
+        Some(syntax)
+    }
+}
+
+/// Removes comments from `content` according to the syntax of `extension`.
+///
+/// Content with an unknown extension is returned unchanged.
+///
+/// # Arguments
+/// * `content` - The file content.
+/// * `extension` - The file extension, used to select the comment syntax.
+/// * `keep_doc_comments` - Whether documentation comments (`///`, `//!`, `/** */`) are kept.
+///
+/// # Returns
+/// * `String` - The content without comments.
+pub fn strip_comments(content: &str, extension: &str, keep_doc_comments: bool) -> String {
+    match Syntax::from_extension(extension) {
+        Some(syntax) => strip_with_syntax(content, syntax, keep_doc_comments),
+        None => content.to_string(),
+    }
+}
+
+/// Removes comments from `content` using an explicit syntax family.
+///
+/// # Arguments
+/// * `content` - The file content.
+/// * `syntax` - The comment syntax family.
+/// * `keep_doc_comments` - Whether documentation comments are kept.
+///
+/// # Returns
+/// * `String` - The content without comments.
+pub fn strip_with_syntax(content: &str, syntax: Syntax, keep_doc_comments: bool) -> String {
+    let mut scanner = Scanner::new(content);
+    match syntax {
+        Syntax::Rust | Syntax::CLike | Syntax::Css => scanner.c_like(syntax, keep_doc_comments),
+        Syntax::Hash | Syntax::HashSpaced => scanner.hash(syntax == Syntax::HashSpaced),
+        Syntax::Markup => scanner.markup(),
+        Syntax::Sql => scanner.sql(),
+        Syntax::Lua => scanner.lua(),
+    }
+    scanner.finish()
+}
+
+/// The kind of a span found by [`tokens`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Token {
+    /// A comment, including its delimiters.
+    Comment,
+    /// A string or character literal, including its quotes.
+    Literal,
+}
+
+/// Finds the comments and string literals in `content`.
+///
+/// # Arguments
+/// * `content` - The file content.
+/// * `syntax` - The comment syntax family.
+///
+/// # Returns
+/// * `Vec<(Range<usize>, Token)>` - The byte ranges of the spans, in order and non-overlapping.
+pub(crate) fn tokens(content: &str, syntax: Syntax) -> Vec<(Range<usize>, Token)> {
+    let mut scanner = Scanner::new(content);
+    match syntax {
+        Syntax::Rust | Syntax::CLike | Syntax::Css => scanner.c_like(syntax, false),
+        Syntax::Hash | Syntax::HashSpaced => scanner.hash(syntax == Syntax::HashSpaced),
+        Syntax::Markup => scanner.markup(),
+        Syntax::Sql => scanner.sql(),
+        Syntax::Lua => scanner.lua(),
+    }
+    scanner.tokens
+}
+
+/// Copies source text to an output buffer, tracking which output lines lost a comment.
+struct Scanner<'a> {
+    src: &'a str,
+    pos: usize,
+    out: String,
+    line: usize,
+    dirty: Vec<bool>,
+    tokens: Vec<(Range<usize>, Token)>,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            pos: 0,
+            out: String::with_capacity(src.len()),
+            line: 0,
+            dirty: vec![false],
+            tokens: Vec::new(),
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.pos >= self.src.len()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn at(&self, pattern: &str) -> bool {
+        self.rest().starts_with(pattern)
+    }
+
+    fn byte(&self, offset: usize) -> Option<u8> {
+        self.src.as_bytes().get(self.pos + offset).copied()
+    }
+
+    fn prev_byte(&self) -> Option<u8> {
+        self.pos
+            .checked_sub(1)
+            .and_then(|i| self.src.as_bytes().get(i).copied())
+    }
+
+    fn at_line_start_or_space(&self) -> bool {
+        matches!(self.prev_byte(), None | Some(b' ' | b'\t' | b'\n' | b'\r'))
+    }
+
+    /// Copies `len` bytes of source to the output.
+    fn copy(&mut self, len: usize) {
+        let end = (self.pos + len).min(self.src.len());
+        let chunk = &self.src[self.pos..end];
+        for _ in chunk.matches('\n') {
+            self.line += 1;
+            self.dirty.push(false);
+        }
+        self.out.push_str(chunk);
+        self.pos = end;
+    }
+
+    /// Copies the next character to the output.
+    fn copy_char(&mut self) {
+        let len = self.rest().chars().next().map_or(1, char::len_utf8);
+        self.copy(len);
+    }
+
+    /// Copies `len` bytes of source to the output, recording them as a literal.
+    fn copy_literal(&mut self, len: usize) {
+        let start = self.pos;
+        self.copy(len);
+        self.tokens.push((start..self.pos, Token::Literal));
+    }
+
+    /// Drops `len` bytes of source, marking the current output line as having lost a comment.
+    fn skip(&mut self, len: usize) {
+        let end = (self.pos + len).min(self.src.len());
+        self.tokens.push((self.pos..end, Token::Comment));
+        self.pos = end;
+        self.dirty[self.line] = true;
+    }
+
+    /// Drops a comment running to the end of the line, keeping the newline itself.
+    fn skip_line_comment(&mut self) {
+        let len = self.rest().find('\n').unwrap_or(self.rest().len());
+        let len = if self.rest()[..len].ends_with('\r') {
+            len - 1
+        } else {
+            len
+        };
+        self.skip(len);
+    }
+
+    /// Copies a comment running to the end of the line, excluding the newline.
+    fn copy_line_comment(&mut self) {
+        let start = self.pos;
+        let len = self.rest().find('\n').unwrap_or(self.rest().len());
+        self.copy(len);
+        self.tokens.push((start..self.pos, Token::Comment));
+    }
+
+    /// Returns the length of a block comment starting at the current position.
+    fn block_len(&self, open: &str, close: &str, nested: bool) -> usize {
+        let rest = self.rest().as_bytes();
+        let mut depth = 0;
+        let mut i = 0;
+        while i < rest.len() {
+            if rest[i..].starts_with(open.as_bytes()) && (nested || depth == 0) {
+                depth += 1;
+                i += open.len();
+            } else if rest[i..].starts_with(close.as_bytes()) {
+                depth -= 1;
+                i += close.len();
+                if depth == 0 {
+                    return i;
+                }
+            } else {
+                i += 1;
+            }
+        }
+        rest.len()
+    }
+
+    /// Copies a quoted string with backslash escapes. Single-line strings that are not
+    /// closed before the end of the line are treated as a lone quote character.
+    fn copy_string(&mut self, quote: u8, multiline: bool) {
+        let bytes = self.rest().as_bytes();
+        let mut i = 1;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\\' => i += 2,
+                b'\n' if !multiline => {
+                    self.copy(1);
+                    return;
+                }
+                b if b == quote => {
+                    self.copy_literal(i + 1);
+                    return;
+                }
+                _ => i += 1,
+            }
+        }
+        if multiline {
+            self.copy_literal(bytes.len());
+        } else {
+            self.copy(1);
+        }
+    }
+
+    /// Copies a string delimited by `delimiter` with no escape sequences.
+    fn copy_until(&mut self, open_len: usize, delimiter: &str) {
+        let len = self.rest()[open_len..]
+            .find(delimiter)
+            .map_or(self.rest().len(), |i| open_len + i + delimiter.len());
+        self.copy_literal(len);
+    }
+
+    fn c_like(&mut self, syntax: Syntax, keep_doc_comments: bool) {
+        let rust = syntax == Syntax::Rust;
+        let line_comments = syntax != Syntax::Css;
+        while !self.done() {
+            if line_comments && self.at("//") {
+                let doc = (self.at("///") && !self.at("////")) || self.at("//!");
+                if keep_doc_comments && doc {
+                    self.copy_line_comment();
+                } else {
+                    self.skip_line_comment();
+                }
+            } else if self.at("/*") {
+                let doc = ((self.at("/**") && !self.at("/***") && !self.at("/**/"))
+                    || self.at("/*!"))
+                    && syntax != Syntax::Css;
+                let len = self.block_len("/*", "*/", rust);
+                if keep_doc_comments && doc {
+                    self.copy(len);
+                } else {
+                    self.skip(len);
+                }
+            } else if rust && self.rust_raw_string() {
+                continue;
+            } else {
+                match self.byte(0) {
+                    Some(b'"') => self.copy_string(b'"', rust),
+                    Some(b'\'') if rust => self.rust_quote(),
+                    Some(b'\'') => self.copy_string(b'\'', false),
+                    Some(b'`') if syntax == Syntax::CLike => self.copy_string(b'`', true),
+                    _ => self.copy_char(),
+                }
+            }
+        }
+    }
+
+    /// Copies a Rust raw string (`r"…"`, `r#"…"#`, `br"…"`) if one starts here.
+    fn rust_raw_string(&mut self) -> bool {
+        if matches!(self.prev_byte(), Some(b) if b.is_ascii_alphanumeric() || b == b'_') {
+            return false;
+        }
+        let prefix = if self.at("br") {
+            2
+        } else if self.at("r") {
+            1
+        } else {
+            return false;
+        };
+        let hashes = self.rest()[prefix..]
+            .bytes()
+            .take_while(|b| *b == b'#')
+            .count();
+        if self.byte(prefix + hashes) != Some(b'"') {
+            return false;
+        }
+        let closing = format!("\"{}", "#".repeat(hashes));
+        self.copy_until(prefix + hashes + 1, &closing);
+        true
+    }
+
+    /// Copies a Rust char literal, or a lone `'` when it introduces a lifetime or label.
+    fn rust_quote(&mut self) {
+        if self.byte(1) == Some(b'\\') {
+            self.copy_string(b'\'', false);
+            return;
+        }
+        let next_len = self.rest()[1..].chars().next().map_or(0, char::len_utf8);
+        if next_len > 0 && self.byte(1 + next_len) == Some(b'\'') {
+            self.copy_literal(next_len + 2);
+        } else {
+            self.copy(1);
+        }
+    }
+
+    fn hash(&mut self, needs_space: bool) {
+        if self.at("#!") {
+            self.copy_line_comment();
+        }
+        while !self.done() {
+            if self.at("\"\"\"") || self.at("'''") {
+                let delimiter = &self.rest()[..3];
+                self.copy_until(3, delimiter);
+            } else if self.byte(0) == Some(b'#') && (!needs_space || self.at_line_start_or_space())
+            {
+                self.skip_line_comment();
+            } else {
+                match self.byte(0) {
+                    Some(q @ (b'"' | b'\'')) => self.copy_string(q, false),
+                    _ => self.copy_char(),
+                }
+            }
+        }
+    }
+
+    fn markup(&mut self) {
+        while !self.done() {
+            if self.at("<!--") {
+                let len = self.block_len("<!--", "-->", false);
+                self.skip(len);
+            } else {
+                self.copy_char();
+            }
+        }
+    }
+
+    fn sql(&mut self) {
+        while !self.done() {
+            if self.at("--") {
+                self.skip_line_comment();
+            } else if self.at("/*") {
+                let len = self.block_len("/*", "*/", false);
+                self.skip(len);
+            } else {
+                match self.byte(0) {
+                    // Quotes are escaped by doubling, so `'it''s'` reads as two adjacent strings.
+                    Some(q @ (b'\'' | b'"')) => {
+                        let delimiter = (q as char).to_string();
+                        self.copy_until(1, &delimiter);
+                    }
+                    _ => self.copy_char(),
+                }
+            }
+        }
+    }
+
+    fn lua(&mut self) {
+        while !self.done() {
+            if self.at("--") {
+                match self.long_bracket(2) {
+                    Some(len) => self.skip(len),
+                    None => self.skip_line_comment(),
+                }
+            } else if let Some(len) = self.long_bracket(0) {
+                self.copy_literal(len);
+            } else {
+                match self.byte(0) {
+                    Some(q @ (b'"' | b'\'')) => self.copy_string(q, false),
+                    _ => self.copy_char(),
+                }
+            }
+        }
+    }
+
+    /// Returns the length (from the current position) of a Lua long bracket `[==[ … ]==]`
+    /// starting `offset` bytes ahead, if one starts there.
+    fn long_bracket(&self, offset: usize) -> Option<usize> {
+        let rest = &self.rest()[offset..];
+        let level = rest
+            .strip_prefix('[')?
+            .bytes()
+            .take_while(|b| *b == b'=')
+            .count();
+        if rest.as_bytes().get(1 + level) != Some(&b'[') {
+            return None;
+        }
+        let closing = format!("]{}]", "=".repeat(level));
+        let body = 2 + level;
+        let len = rest[body..]
+            .find(&closing)
+            .map_or(rest.len(), |i| body + i + closing.len());
+        Some(offset + len)
+    }
+
+    /// Assembles the output, trimming lines that lost a comment and dropping them if blank.
+    fn finish(self) -> String {
+        let mut result = String::with_capacity(self.out.len());
+        for (index, line) in self.out.split_inclusive('\n').enumerate() {
+            if !self.dirty.get(index).copied().unwrap_or(false) {
+                result.push_str(line);
+                continue;
+            }
+            let (body, ending) = match line.strip_suffix("\r\n") {
+                Some(body) => (body, "\r\n"),
+                None => match line.strip_suffix('\n') {
+                    Some(body) => (body, "\n"),
+                    None => (line, ""),
+                },
+            };
+            let body = body.trim_end_matches([' ', '\t']);
+            if !body.trim().is_empty() {
+                result.push_str(body);
+                result.push_str(ending);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(content: &str, extension: &str) -> String {
+        strip_comments(content, extension, false)
+    }
+
+    #[test]
+    fn rust_strips_line_and_nested_block_comments() {
+        let source = "// header\nfn main() { /* a /* nested */ b */ let x = 1; // trailing\n}\n";
+        assert_eq!(strip(source, "rs"), "fn main() {  let x = 1;\n}\n");
+    }
+
+    #[test]
+    fn rust_keeps_comment_markers_inside_strings() {
+        let source = "let a = \"// not a comment\";\nlet b = r#\"/* \"raw\" */\"#;\nlet c = '/';\n";
+        assert_eq!(strip(source, "rs"), source);
+    }
+
+    #[test]
+    fn rust_distinguishes_lifetimes_from_char_literals() {
+        let source = "fn f<'a>(x: &'a str) -> char { '\\'' } // done\n";
+        assert_eq!(
+            strip(source, "rs"),
+            "fn f<'a>(x: &'a str) -> char { '\\'' }\n"
+        );
+    }
+
+    #[test]
+    fn rust_keeps_doc_comments_when_requested() {
+        let source = "//! Crate docs\n/// Item docs\n// plain\n/** block docs */\nfn f() {}\n";
+        assert_eq!(
+            strip_comments(source, "rs", true),
+            "//! Crate docs\n/// Item docs\n/** block docs */\nfn f() {}\n"
+        );
+        assert_eq!(strip_comments(source, "rs", false), "fn f() {}\n");
+    }
+
+    #[test]
+    fn c_like_handles_template_literals_and_urls_in_strings() {
+        let source = "const url = \"http://example.com\"; // site\nconst t = `/* ${x} */`;\n/*\n * block\n */\nrun();\n";
+        assert_eq!(
+            strip(source, "ts"),
+            "const url = \"http://example.com\";\nconst t = `/* ${x} */`;\nrun();\n"
+        );
+    }
+
+    #[test]
+    fn css_does_not_treat_double_slash_as_comment() {
+        let source = "a { background: url(http://x/y.png); } /* note */\n";
+        assert_eq!(
+            strip(source, "css"),
+            "a { background: url(http://x/y.png); }\n"
+        );
+    }
+
+    #[test]
+    fn python_keeps_shebang_strings_and_docstrings() {
+        let source = "#!/usr/bin/env python3\n# comment\ndef f():\n    \"\"\"Doc # not comment\"\"\"\n    return '#' # trailing\n";
+        assert_eq!(
+            strip(source, "py"),
+            "#!/usr/bin/env python3\ndef f():\n    \"\"\"Doc # not comment\"\"\"\n    return '#'\n"
+        );
+    }
+
+    #[test]
+    fn shell_and_yaml_require_whitespace_before_hash() {
+        assert_eq!(
+            strip("echo ${#arr[@]} # count\n", "sh"),
+            "echo ${#arr[@]}\n"
+        );
+        assert_eq!(
+            strip(
+                "key: value#notcomment # comment\nother: \"a # b\"\n",
+                "yaml"
+            ),
+            "key: value#notcomment\nother: \"a # b\"\n"
+        );
+    }
+
+    #[test]
+    fn hash_treats_unclosed_quotes_as_plain_text() {
+        assert_eq!(
+            strip("note: don't panic # really\n", "yml"),
+            "note: don't panic\n"
+        );
+    }
+
+    #[test]
+    fn toml_strips_comments() {
+        assert_eq!(
+            strip("# top\nname = \"a#b\" # inline\n", "toml"),
+            "name = \"a#b\"\n"
+        );
+    }
+
+    #[test]
+    fn markup_strips_html_comments() {
+        let source = "<div>\n  <!-- hidden\n  block -->\n  <p>text -- with dashes</p>\n</div>\n";
+        assert_eq!(
+            strip(source, "html"),
+            "<div>\n  <p>text -- with dashes</p>\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn sql_strips_comments_but_not_strings() {
+        let source = "SELECT '--not' AS a, 'it''s' -- comment\n/* block */ FROM t;\n";
+        assert_eq!(
+            strip(source, "sql"),
+            "SELECT '--not' AS a, 'it''s'\n FROM t;\n"
+        );
+    }
+
+    #[test]
+    fn lua_strips_long_comments_and_keeps_long_strings() {
+        let source = "--[[ block\ncomment ]]\nlocal s = [[-- kept]] -- line\nprint(\"--\")\n";
+        assert_eq!(
+            strip(source, "lua"),
+            "local s = [[-- kept]]\nprint(\"--\")\n"
+        );
+    }
+
+    #[test]
+    fn unknown_extensions_pass_through() {
+        let source = "# not touched // at all\n";
+        assert_eq!(strip(source, "txt"), source);
+    }
+
+    #[test]
+    fn handles_multibyte_characters_inside_comments() {
+        assert_eq!(
+            strip("/* żółw → 🐢 */\nlet s = \"ą\"; // ć\n", "rs"),
+            "let s = \"ą\";\n"
+        );
+    }
+
+    #[test]
+    fn tokens_find_comments_and_literals() {
+        let source = "let s = \"// x\"; // note\nlet c = 'a';\n";
+        let spans: Vec<_> = tokens(source, Syntax::Rust)
+            .into_iter()
+            .map(|(range, token)| (&source[range], token))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("\"// x\"", Token::Literal),
+                ("// note", Token::Comment),
+                ("'a'", Token::Literal)
+            ]
+        );
+    }
+
+    #[test]
+    fn preserves_crlf_line_endings() {
+        assert_eq!(strip("a = 1 # c\r\nb = 2\r\n", "py"), "a = 1\r\nb = 2\r\n");
+    }
+}
@@ -0,0 +1,462 @@
+//! Shell completion scripts, generated from the command-line definition so they follow every
+//! new flag.
+
+use clap::{Arg, ArgAction, Command, ValueEnum, ValueHint};
+use std::io::{self, Write};
+
+/// A shell to generate a completion script for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// What the value of an option completes to.
+enum Values {
+    /// Nothing in particular, e.g. a number or a glob.
+    Any,
+    /// One of a fixed set of names.
+    OneOf(Vec<String>),
+    /// A path to a file or directory.
+    Path,
+    /// A path to a directory.
+    Directory,
+}
+
+/// An option of the command, as seen by the completion scripts.
+struct Opt {
+    /// The long names, without `--`, e.g. `output` and its visible aliases.
+    longs: Vec<String>,
+    /// The short name, if any.
+    short: Option<char>,
+    /// The first line of the help text.
+    help: String,
+    /// What the value completes to, or `None` for a flag without a value.
+    values: Option<Values>,
+    /// Whether the option can be given more than once.
+    repeatable: bool,
+}
+
+impl Opt {
+    /// Describes a non-positional argument, or returns `None` if it is hidden or positional.
+    fn new(arg: &Arg) -> Option<Opt> {
+        if arg.is_positional() || arg.is_hide_set() {
+            return None;
+        }
+        let mut longs: Vec<String> = arg.get_long().map(str::to_string).into_iter().collect();
+        longs.extend(
+            arg.get_visible_aliases()
+                .unwrap_or_default()
+                .into_iter()
+                .map(str::to_string),
+        );
+        let takes_value = arg.get_num_args().is_some_and(|range| range.takes_values());
+        let values = takes_value.then(|| value_completion(arg));
+        Some(Opt {
+            longs,
+            short: arg.get_short(),
+            help: arg
+                .get_help()
+                .map(|help| help.to_string())
+                .unwrap_or_default()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            values,
+            repeatable: matches!(arg.get_action(), ArgAction::Append | ArgAction::Count),
+        })
+    }
+
+    /// Returns every spelling of the option, e.g. `-o` and `--output`.
+    fn names(&self) -> Vec<String> {
+        let short = self.short.map(|short| format!("-{}", short));
+        short
+            .into_iter()
+            .chain(self.longs.iter().map(|long| format!("--{}", long)))
+            .collect()
+    }
+}
+
+/// A subcommand, as seen by the completion scripts.
+struct Sub {
+    /// The name of the subcommand.
+    name: String,
+    /// The first line of its help text.
+    about: String,
+    /// The names its first argument completes to.
+    values: Vec<String>,
+}
+
+/// Returns what the value of an argument completes to.
+fn value_completion(arg: &Arg) -> Values {
+    let names: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    if !names.is_empty() {
+        return Values::OneOf(names);
+    }
+    match arg.get_value_hint() {
+        ValueHint::DirPath => Values::Directory,
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::ExecutablePath => Values::Path,
+        _ => Values::Any,
+    }
+}
+
+/// Writes the completion script for a shell.
+///
+/// The script completes every visible option, the values of options with a fixed set of
+/// names, paths for path-valued options and the input directories, and the subcommands.
+///
+/// # Arguments
+/// * `shell` - The shell the script is for.
+/// * `command` - The command-line definition.
+/// * `writer` - Where to write the script.
+///
+/// # Returns
+/// * `io::Result<()>` - Returns `Ok(())` if successful, or an error if writing fails.
+pub fn generate(shell: Shell, command: &mut Command, writer: &mut dyn Write) -> io::Result<()> {
+    command.build();
+    let name = command.get_name().to_string();
+    let options: Vec<Opt> = command.get_arguments().filter_map(Opt::new).collect();
+    let subcommands: Vec<Sub> = command
+        .get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set() && subcommand.get_name() != "help")
+        .map(|subcommand| {
+            let about = subcommand
+                .get_about()
+                .map(|about| about.to_string())
+                .unwrap_or_default()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            let values = subcommand
+                .get_positionals()
+                .next()
+                .and_then(|arg| match value_completion(arg) {
+                    Values::OneOf(names) => Some(names),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            Sub {
+                name: subcommand.get_name().to_string(),
+                about,
+                values,
+            }
+        })
+        .collect();
+    match shell {
+        Shell::Bash => bash(&name, &options, &subcommands, writer),
+        Shell::Zsh => zsh(&name, &options, &subcommands, writer),
+        Shell::Fish => fish(&name, &options, &subcommands, writer),
+        Shell::Powershell => powershell(&name, &options, &subcommands, writer),
+    }
+}
+
+/// Writes the bash script, registered with `complete -F`.
+fn bash(
+    name: &str,
+    options: &[Opt],
+    subcommands: &[Sub],
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let function = format!("_{}", name.replace('-', "_"));
+    writeln!(writer, "{}() {{", function)?;
+    writeln!(writer, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"")?;
+    writeln!(writer, "    local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"")?;
+    for Sub {
+        name: subcommand,
+        values,
+        ..
+    } in subcommands
+    {
+        writeln!(
+            writer,
+            "    if [[ $COMP_CWORD -eq 2 && ${{COMP_WORDS[1]}} == {} ]]; then",
+            subcommand
+        )?;
+        writeln!(
+            writer,
+            "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+            values.join(" ")
+        )?;
+        writeln!(writer, "        return")?;
+        writeln!(writer, "    fi")?;
+    }
+    writeln!(writer, "    case \"$prev\" in")?;
+    for option in options {
+        let Some(values) = &option.values else {
+            continue;
+        };
+        let reply = match values {
+            Values::Any => "COMPREPLY=()".to_string(),
+            Values::OneOf(names) => {
+                format!(
+                    "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                    names.join(" ")
+                )
+            }
+            Values::Path => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+            Values::Directory => "COMPREPLY=($(compgen -d -- \"$cur\"))".to_string(),
+        };
+        writeln!(writer, "        {})", option.names().join("|"))?;
+        writeln!(writer, "            {}", reply)?;
+        writeln!(writer, "            return")?;
+        writeln!(writer, "            ;;")?;
+    }
+    writeln!(writer, "    esac")?;
+    let flags: Vec<String> = options.iter().flat_map(Opt::names).collect();
+    writeln!(writer, "    if [[ $cur == -* ]]; then")?;
+    writeln!(
+        writer,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        flags.join(" ")
+    )?;
+    writeln!(writer, "        return")?;
+    writeln!(writer, "    fi")?;
+    writeln!(writer, "    COMPREPLY=($(compgen -d -- \"$cur\"))")?;
+    let names: Vec<&str> = subcommands.iter().map(|sub| sub.name.as_str()).collect();
+    writeln!(writer, "    if [[ $COMP_CWORD -eq 1 ]]; then")?;
+    writeln!(
+        writer,
+        "        COMPREPLY+=($(compgen -W \"{}\" -- \"$cur\"))",
+        names.join(" ")
+    )?;
+    writeln!(writer, "    fi")?;
+    writeln!(writer, "}}")?;
+    writeln!(writer, "complete -F {} -o filenames {}", function, name)
+}
+
+/// Quotes a string for a single-quoted shell word.
+fn single_quoted(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Writes the zsh script, an `#compdef` function built on `_arguments`.
+fn zsh(name: &str, options: &[Opt], subcommands: &[Sub], writer: &mut dyn Write) -> io::Result<()> {
+    let function = format!("_{}", name.replace('-', "_"));
+    writeln!(writer, "#compdef {}", name)?;
+    writeln!(writer)?;
+    writeln!(writer, "{}() {{", function)?;
+    for Sub {
+        name: subcommand,
+        values,
+        ..
+    } in subcommands
+    {
+        writeln!(writer, "    if [[ $words[2] == {} ]]; then", subcommand)?;
+        writeln!(
+            writer,
+            "        _arguments {}",
+            single_quoted(&format!("2:value:({})", values.join(" ")))
+        )?;
+        writeln!(writer, "        return")?;
+        writeln!(writer, "    fi")?;
+    }
+    let choices: Vec<String> = subcommands
+        .iter()
+        .map(|Sub { name, about, .. }| format!("{}\\:\"{}\"", name, about.replace('"', "")))
+        .collect();
+    writeln!(
+        writer,
+        "    if (( CURRENT == 2 )) && [[ $PREFIX != -* ]]; then"
+    )?;
+    writeln!(
+        writer,
+        "        _alternative {} {}",
+        single_quoted(&format!("subcommands:subcommand:(({}))", choices.join(" "))),
+        single_quoted("directories:directory:_files -/")
+    )?;
+    writeln!(writer, "        return")?;
+    writeln!(writer, "    fi")?;
+    writeln!(writer, "    _arguments -s -S \\")?;
+    for option in options {
+        let help = option
+            .help
+            .replace('\\', "\\\\")
+            .replace('[', "\\[")
+            .replace(']', "\\]")
+            .replace(':', "\\:");
+        let action = match &option.values {
+            None => String::new(),
+            Some(Values::Any) => ":value: ".to_string(),
+            Some(Values::OneOf(names)) => format!(":value:({})", names.join(" ")),
+            Some(Values::Path) => ":path:_files".to_string(),
+            Some(Values::Directory) => ":directory:_files -/".to_string(),
+        };
+        let repeat = if option.repeatable { "*" } else { "" };
+        for spelling in option.names() {
+            let suffix = match (&option.values, spelling.starts_with("--")) {
+                (None, _) => "",
+                (Some(_), true) => "=",
+                (Some(_), false) => "+",
+            };
+            let spec = format!("{}{}{}[{}]{}", repeat, spelling, suffix, help, action);
+            writeln!(writer, "        {} \\", single_quoted(&spec))?;
+        }
+    }
+    writeln!(writer, "        '*:directory:_files -/'")?;
+    writeln!(writer, "}}")?;
+    writeln!(writer)?;
+    writeln!(writer, "{} \"$@\"", function)
+}
+
+/// Writes the fish script, a list of `complete` commands.
+fn fish(
+    name: &str,
+    options: &[Opt],
+    subcommands: &[Sub],
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let names: Vec<&str> = subcommands.iter().map(|sub| sub.name.as_str()).collect();
+    let bundling = format!("not __fish_seen_subcommand_from {}", names.join(" "));
+    writeln!(writer, "complete -c {} -f", name)?;
+    for option in options {
+        let mut line = format!("complete -c {} -n {}", name, single_quoted(&bundling));
+        if let Some(short) = option.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        for long in &option.longs {
+            line.push_str(&format!(" -l {}", long));
+        }
+        match &option.values {
+            None => {}
+            Some(Values::Any) => line.push_str(" -x"),
+            Some(Values::OneOf(names)) => {
+                line.push_str(&format!(" -x -a {}", single_quoted(&names.join(" "))))
+            }
+            Some(Values::Path) => line.push_str(" -r -F"),
+            Some(Values::Directory) => line.push_str(" -x -a '(__fish_complete_directories)'"),
+        }
+        if !option.help.is_empty() {
+            line.push_str(&format!(" -d {}", single_quoted(&option.help)));
+        }
+        writeln!(writer, "{}", line)?;
+    }
+    for Sub {
+        name: subcommand,
+        about,
+        values,
+    } in subcommands
+    {
+        writeln!(
+            writer,
+            "complete -c {} -n '__fish_use_subcommand' -a {} -d {}",
+            name,
+            subcommand,
+            single_quoted(about)
+        )?;
+        writeln!(
+            writer,
+            "complete -c {} -n '__fish_seen_subcommand_from {}' -x -a {}",
+            name,
+            subcommand,
+            single_quoted(&values.join(" "))
+        )?;
+    }
+    writeln!(
+        writer,
+        "complete -c {} -n {} -a '(__fish_complete_directories)'",
+        name,
+        single_quoted(&bundling)
+    )
+}
+
+/// Writes the PowerShell script, a native `Register-ArgumentCompleter` script block.
+///
+/// Returning nothing lets PowerShell fall back to completing paths.
+fn powershell(
+    name: &str,
+    options: &[Opt],
+    subcommands: &[Sub],
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let list = |items: &[String]| {
+        let quoted: Vec<String> = items.iter().map(|item| single_quoted(item)).collect();
+        format!("@({})", quoted.join(", "))
+    };
+    writeln!(
+        writer,
+        "Register-ArgumentCompleter -Native -CommandName {} -ScriptBlock {{",
+        single_quoted(name)
+    )?;
+    writeln!(
+        writer,
+        "    param($wordToComplete, $commandAst, $cursorPosition)"
+    )?;
+    writeln!(
+        writer,
+        "    $words = @($commandAst.CommandElements | Where-Object {{ $_.Extent.EndOffset -lt $cursorPosition }} | ForEach-Object {{ $_.ToString() }})"
+    )?;
+    writeln!(writer, "    $previous = $words[-1]")?;
+    writeln!(writer, "    $values = $null")?;
+    let mut keyword = "if";
+    for Sub {
+        name: subcommand,
+        values,
+        ..
+    } in subcommands
+    {
+        writeln!(
+            writer,
+            "    {} ($words.Count -eq 2 -and $previous -eq {}) {{ $values = {} }}",
+            keyword,
+            single_quoted(subcommand),
+            list(values)
+        )?;
+        keyword = "elseif";
+    }
+    writeln!(writer, "    {} ($wordToComplete -notlike '-*') {{", keyword)?;
+    writeln!(writer, "        switch -CaseSensitive ($previous) {{")?;
+    for option in options {
+        let Some(values) = &option.values else {
+            continue;
+        };
+        let body = match values {
+            Values::OneOf(names) => format!("$values = {}; break", list(names)),
+            Values::Any => "$values = @(); break".to_string(),
+            Values::Path | Values::Directory => "return".to_string(),
+        };
+        for spelling in option.names() {
+            writeln!(
+                writer,
+                "            {} {{ {} }}",
+                single_quoted(&spelling),
+                body
+            )?;
+        }
+    }
+    writeln!(writer, "        }}")?;
+    writeln!(writer, "    }}")?;
+    writeln!(writer, "    if ($null -eq $values) {{")?;
+    let flags: Vec<String> = options.iter().flat_map(Opt::names).collect();
+    writeln!(
+        writer,
+        "        if ($wordToComplete -like '-*') {{ $values = {} }}",
+        list(&flags)
+    )?;
+    let names: Vec<String> = subcommands.iter().map(|sub| sub.name.clone()).collect();
+    writeln!(
+        writer,
+        "        elseif ($words.Count -eq 1) {{ $values = {} }}",
+        list(&names)
+    )?;
+    writeln!(writer, "        else {{ return }}")?;
+    writeln!(writer, "    }}")?;
+    writeln!(
+        writer,
+        "    $values | Where-Object {{ $_ -like \"$wordToComplete*\" }} | ForEach-Object {{"
+    )?;
+    writeln!(
+        writer,
+        "        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)"
+    )?;
+    writeln!(writer, "    }}")?;
+    writeln!(writer, "}}")
+}
@@ -0,0 +1,483 @@
+//! Compression: a gzip encoder wrapped around the output writer, and the decoder compressed
+//! archives are read with.
+
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// A compression format for the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// gzip (RFC 1952), appended as `.gz`.
+    Gzip,
+}
+
+impl Compression {
+    /// Returns the file name extension of the format, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+        }
+    }
+
+    /// Appends the format's extension to a file name unless it already ends with it.
+    ///
+    /// # Arguments
+    /// * `path` - The output file path.
+    ///
+    /// # Returns
+    /// * `PathBuf` - The path with the extension, e.g. `bundle.md.gz`.
+    pub(crate) fn output_path(&self, path: &Path) -> std::path::PathBuf {
+        if path.extension().and_then(|s| s.to_str()) == Some(self.extension()) {
+            return path.to_path_buf();
+        }
+        let mut name = path.as_os_str().to_os_string();
+        name.push(".");
+        name.push(self.extension());
+        name.into()
+    }
+}
+
+/// Compresses everything written to it into the wrapped writer.
+///
+/// [`finish`](Encoder::finish) must be called to write the end of the stream; dropping an
+/// unfinished encoder releases its resources without writing a truncated archive trailer.
+pub(crate) struct Encoder<W: Write> {
+    inner: W,
+    stream: zlib::Deflate,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Creates an encoder for the given format.
+    ///
+    /// # Arguments
+    /// * `compression` - The compression format.
+    /// * `inner` - The writer that receives the compressed bytes.
+    ///
+    /// # Returns
+    /// * `Result<Encoder<W>>` - The encoder, or an error if the format is unavailable in this build.
+    pub(crate) fn new(compression: Compression, inner: W) -> Result<Self> {
+        match compression {
+            Compression::Gzip => Ok(Encoder {
+                inner,
+                stream: zlib::Deflate::gzip()?,
+            }),
+        }
+    }
+
+    /// Writes the end of the compressed stream and flushes the wrapped writer.
+    ///
+    /// # Returns
+    /// * `io::Result<(W, usize, usize)>` - The wrapped writer and the uncompressed and
+    ///   compressed sizes in bytes.
+    pub(crate) fn finish(mut self) -> io::Result<(W, usize, usize)> {
+        self.stream.run(&[], &mut self.inner, true)?;
+        self.inner.flush()?;
+        let (total_in, total_out) = self.stream.totals();
+        Ok((self.inner, total_in, total_out))
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let input = &buf[..buf.len().min(u32::MAX as usize)];
+        self.stream.run(input, &mut self.inner, false)?;
+        Ok(input.len())
+    }
+
+    /// Flushes the wrapped writer; buffered input stays in the encoder until [`Encoder::finish`].
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The framing of a compressed stream read by [`inflate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Framing {
+    /// A gzip member (RFC 1952), as in `.tar.gz` files.
+    Gzip,
+    /// Raw deflate data (RFC 1951), as in zip entries.
+    Raw,
+}
+
+/// Decompresses a complete stream held in memory.
+///
+/// # Arguments
+/// * `data` - The compressed bytes.
+/// * `framing` - How the deflate data is wrapped.
+/// * `limit` - The most bytes the data may decompress to, e.g. the size a zip entry declares.
+///
+/// # Returns
+/// * `Result<Vec<u8>>` - The decompressed bytes, or an error if the data is corrupt, would
+///   decompress to more than `limit` bytes, or the crate was built without the `gzip`
+///   feature.
+pub(crate) fn inflate(data: &[u8], framing: Framing, limit: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(limit.min(64 * 1024 * 1024));
+    Decoder::new(data, framing)?
+        .take(limit as u64 + 1)
+        .read_to_end(&mut output)?;
+    if output.len() > limit {
+        bail!(
+            "The compressed data decompresses to more than {} bytes",
+            limit
+        );
+    }
+    Ok(output)
+}
+
+/// Decompresses a stream as it is read, so only what the reader asks for is held in memory.
+pub(crate) struct Decoder<R: Read> {
+    stream: zlib::Inflate<R>,
+}
+
+impl<R: Read> Decoder<R> {
+    /// Creates a decoder reading compressed bytes from `input`.
+    ///
+    /// # Arguments
+    /// * `input` - The reader of the compressed bytes.
+    /// * `framing` - How the deflate data is wrapped.
+    ///
+    /// # Returns
+    /// * `Result<Decoder<R>>` - The decoder, or an error if the crate was built without the
+    ///   `gzip` feature.
+    pub(crate) fn new(input: R, framing: Framing) -> Result<Self> {
+        Ok(Decoder {
+            stream: zlib::Inflate::new(input, framing)?,
+        })
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    /// Reads decompressed bytes; the end of the first gzip member or deflate stream is the
+    /// end of the data, and input that ends before it is an error.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+/// Fails if the compression format is unavailable in this build.
+///
+/// # Arguments
+/// * `compression` - The requested compression format.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if the format can be used, or an error otherwise.
+pub(crate) fn check_support(compression: Compression) -> Result<()> {
+    match compression {
+        Compression::Gzip if cfg!(not(feature = "gzip")) => {
+            bail!("Cannot compress with gzip: built without gzip support")
+        }
+        Compression::Gzip => Ok(()),
+    }
+}
+
+#[cfg(feature = "gzip")]
+mod zlib {
+    use anyhow::{Result, bail};
+    use libz_sys as z;
+    use std::alloc::{self, Layout};
+    use std::io::{self, Read, Write};
+    use std::os::raw::{c_int, c_uint, c_void};
+
+    /// Window bits selecting the gzip wrapper (15 for a 32 KiB window, plus 16).
+    const GZIP_WINDOW_BITS: c_int = 15 + 16;
+
+    /// The size of the output buffer handed to zlib per call.
+    const CHUNK: usize = 32 * 1024;
+
+    /// The alignment and size of the header that records each allocation's size.
+    const ALIGN: usize = 16;
+
+    /// Window bits selecting raw deflate data without a header (a 32 KiB window).
+    const RAW_WINDOW_BITS: c_int = -15;
+
+    /// Creates a zero-initialized stream that allocates through [`zalloc`] and [`zfree`].
+    fn new_stream() -> Box<z::z_stream> {
+        Box::new(z::z_stream {
+            next_in: std::ptr::null_mut(),
+            avail_in: 0,
+            total_in: 0,
+            next_out: std::ptr::null_mut(),
+            avail_out: 0,
+            total_out: 0,
+            msg: std::ptr::null_mut(),
+            state: std::ptr::null_mut(),
+            zalloc,
+            zfree,
+            opaque: std::ptr::null_mut(),
+            data_type: 0,
+            adler: 0,
+            reserved: 0,
+        })
+    }
+
+    /// A zlib deflate stream.
+    pub(super) struct Deflate {
+        // Boxed because zlib keeps a pointer back to the stream in its internal state.
+        stream: Box<z::z_stream>,
+        buffer: Vec<u8>,
+    }
+
+    impl Deflate {
+        /// Starts a gzip stream at the default compression level.
+        pub(super) fn gzip() -> Result<Self> {
+            let mut stream = new_stream();
+            // SAFETY: the stream is fully initialized, and the version and size describe the
+            // zlib the bindings were built against.
+            let status = unsafe {
+                z::deflateInit2_(
+                    stream.as_mut(),
+                    z::Z_DEFAULT_COMPRESSION,
+                    z::Z_DEFLATED,
+                    GZIP_WINDOW_BITS,
+                    8,
+                    z::Z_DEFAULT_STRATEGY,
+                    z::zlibVersion(),
+                    std::mem::size_of::<z::z_stream>() as c_int,
+                )
+            };
+            if status != z::Z_OK {
+                bail!(
+                    "Failed to initialize gzip compression (zlib error {})",
+                    status
+                );
+            }
+            Ok(Deflate {
+                stream,
+                buffer: vec![0; CHUNK],
+            })
+        }
+
+        /// Compresses `input` into `output`, ending the stream when `finish` is set.
+        ///
+        /// `input` must be shorter than 4 GiB.
+        pub(super) fn run(
+            &mut self,
+            input: &[u8],
+            output: &mut dyn Write,
+            finish: bool,
+        ) -> io::Result<()> {
+            let flush = if finish { z::Z_FINISH } else { z::Z_NO_FLUSH };
+            self.stream.next_in = input.as_ptr() as *mut u8;
+            self.stream.avail_in = input.len() as c_uint;
+            loop {
+                self.stream.next_out = self.buffer.as_mut_ptr();
+                self.stream.avail_out = CHUNK as c_uint;
+                // SAFETY: the input and output pointers are valid for the given lengths, and
+                // zlib does not write through `next_in`.
+                let status = unsafe { z::deflate(self.stream.as_mut(), flush) };
+                let produced = CHUNK - self.stream.avail_out as usize;
+                output.write_all(&self.buffer[..produced])?;
+                match status {
+                    z::Z_STREAM_END => return Ok(()),
+                    z::Z_OK | z::Z_BUF_ERROR => {}
+                    status => {
+                        return Err(io::Error::other(format!(
+                            "gzip compression failed (zlib error {})",
+                            status
+                        )));
+                    }
+                }
+                // Without `finish`, stop once the input is consumed and zlib has room to spare.
+                if !finish && self.stream.avail_in == 0 && self.stream.avail_out != 0 {
+                    return Ok(());
+                }
+            }
+        }
+
+        /// Returns the number of bytes consumed and produced so far.
+        pub(super) fn totals(&self) -> (usize, usize) {
+            (
+                self.stream.total_in as usize,
+                self.stream.total_out as usize,
+            )
+        }
+    }
+
+    impl Drop for Deflate {
+        fn drop(&mut self) {
+            // SAFETY: the stream was initialized by `deflateInit2_` and is ended once.
+            unsafe {
+                z::deflateEnd(self.stream.as_mut());
+            }
+        }
+    }
+
+    /// A zlib inflate stream reading its input from a reader.
+    pub(super) struct Inflate<R: Read> {
+        // Boxed because zlib keeps a pointer back to the stream in its internal state.
+        stream: Box<z::z_stream>,
+        input: R,
+        // Boxed so `next_in` stays valid when the stream moves.
+        buffer: Box<[u8]>,
+        /// Whether `input` has no more bytes.
+        exhausted: bool,
+        /// Whether the end of the compressed stream was reached.
+        finished: bool,
+    }
+
+    impl<R: Read> Inflate<R> {
+        /// Starts decompressing a gzip member or raw deflate stream.
+        pub(super) fn new(input: R, framing: super::Framing) -> Result<Self> {
+            let window_bits = match framing {
+                super::Framing::Gzip => GZIP_WINDOW_BITS,
+                super::Framing::Raw => RAW_WINDOW_BITS,
+            };
+            let mut stream = new_stream();
+            // SAFETY: the stream is fully initialized, and the version and size describe the
+            // zlib the bindings were built against.
+            let status = unsafe {
+                z::inflateInit2_(
+                    stream.as_mut(),
+                    window_bits,
+                    z::zlibVersion(),
+                    std::mem::size_of::<z::z_stream>() as c_int,
+                )
+            };
+            if status != z::Z_OK {
+                bail!("Failed to initialize decompression (zlib error {})", status);
+            }
+            Ok(Inflate {
+                stream,
+                input,
+                buffer: vec![0; CHUNK].into_boxed_slice(),
+                exhausted: false,
+                finished: false,
+            })
+        }
+    }
+
+    impl<R: Read> Read for Inflate<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let corrupt = |what: String| io::Error::new(io::ErrorKind::InvalidData, what);
+            if self.finished || buf.is_empty() {
+                return Ok(0);
+            }
+            loop {
+                if self.stream.avail_in == 0 && !self.exhausted {
+                    let read = self.input.read(&mut self.buffer)?;
+                    self.exhausted = read == 0;
+                    self.stream.next_in = self.buffer.as_mut_ptr();
+                    self.stream.avail_in = read as c_uint;
+                }
+                let room = buf.len().min(c_uint::MAX as usize);
+                // SAFETY: the output pointer is valid for `room` bytes, the input pointer for
+                // `avail_in` bytes of the buffer, and zlib does not write through `next_in`.
+                let status = unsafe {
+                    self.stream.next_out = buf.as_mut_ptr();
+                    self.stream.avail_out = room as c_uint;
+                    z::inflate(self.stream.as_mut(), z::Z_NO_FLUSH)
+                };
+                let produced = room - self.stream.avail_out as usize;
+                match status {
+                    z::Z_STREAM_END => {
+                        self.finished = true;
+                        return Ok(produced);
+                    }
+                    z::Z_OK | z::Z_BUF_ERROR if produced > 0 => return Ok(produced),
+                    z::Z_OK => {}
+                    // Out of input before the end of the stream.
+                    z::Z_BUF_ERROR if self.exhausted => {
+                        return Err(corrupt("The compressed data is truncated".to_string()));
+                    }
+                    z::Z_BUF_ERROR => {}
+                    status => {
+                        return Err(corrupt(format!(
+                            "The compressed data is corrupt (zlib error {})",
+                            status
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    impl<R: Read> Drop for Inflate<R> {
+        fn drop(&mut self) {
+            // SAFETY: the stream was initialized by `inflateInit2_` and is ended once.
+            unsafe {
+                z::inflateEnd(self.stream.as_mut());
+            }
+        }
+    }
+
+    /// Allocates memory for zlib through the Rust allocator, recording the size in a header.
+    unsafe extern "C" fn zalloc(_: *mut c_void, items: c_uint, size: c_uint) -> *mut c_void {
+        let Some(size) = (items as usize)
+            .checked_mul(size as usize)
+            .and_then(|size| size.checked_add(ALIGN))
+        else {
+            return std::ptr::null_mut();
+        };
+        let Ok(layout) = Layout::from_size_align(size, ALIGN) else {
+            return std::ptr::null_mut();
+        };
+        // SAFETY: the layout has a non-zero size, and the header fits before the returned block.
+        unsafe {
+            let ptr = alloc::alloc(layout);
+            if ptr.is_null() {
+                return std::ptr::null_mut();
+            }
+            (ptr as *mut usize).write(size);
+            ptr.add(ALIGN) as *mut c_void
+        }
+    }
+
+    /// Frees memory allocated by [`zalloc`].
+    unsafe extern "C" fn zfree(_: *mut c_void, address: *mut c_void) {
+        if address.is_null() {
+            return;
+        }
+        // SAFETY: `address` came from `zalloc`, so the header with its size precedes it.
+        unsafe {
+            let ptr = (address as *mut u8).sub(ALIGN);
+            let size = (ptr as *const usize).read();
+            alloc::dealloc(ptr, Layout::from_size_align_unchecked(size, ALIGN));
+        }
+    }
+}
+
+#[cfg(not(feature = "gzip"))]
+mod zlib {
+    use anyhow::{Result, bail};
+    use std::io::{self, Read, Write};
+    use std::marker::PhantomData;
+
+    /// A placeholder for builds without the `gzip` feature; it cannot be created.
+    pub(super) struct Inflate<R> {
+        never: std::convert::Infallible,
+        input: PhantomData<R>,
+    }
+
+    impl<R> Inflate<R> {
+        pub(super) fn new(_: R, _: super::Framing) -> Result<Self> {
+            bail!("Cannot decompress: built without gzip support")
+        }
+    }
+
+    impl<R> Read for Inflate<R> {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            match self.never {}
+        }
+    }
+
+    /// A placeholder for builds without the `gzip` feature; it cannot be created.
+    pub(super) enum Deflate {}
+
+    impl Deflate {
+        pub(super) fn gzip() -> Result<Self> {
+            bail!("Cannot compress with gzip: built without gzip support")
+        }
+
+        pub(super) fn run(&mut self, _: &[u8], _: &mut dyn Write, _: bool) -> io::Result<()> {
+            match *self {}
+        }
+
+        pub(super) fn totals(&self) -> (usize, usize) {
+            match *self {}
+        }
+    }
+}
@@ -0,0 +1,991 @@
+//! A builder for [`Config`], for library users who only need to set a few options.
+
+use crate::lang::LangMapping;
+use crate::rename::PathRename;
+use crate::transform::{LineEnding, LineRange, Truncation};
+use crate::{
+    ByteSize, ColorChoice, Compression, Config, FileMeta, FilterCommand, FitStrategy, Format,
+    GroupBy, GroupOrder, HashAlgorithm, LineNumberFormat, Model, RelativeTo, SortOrder, TimeBound,
+    Tokenizer,
+};
+use std::path::PathBuf;
+
+/// Builds a [`Config`], starting from the same defaults as the CLI.
+///
+/// ```
+/// use codebase_to_prompt::{Config, Format};
+///
+/// let config = Config::builder()
+///     .include(["rs"])
+///     .format(Format::Markdown)
+///     .output("out.md")
+///     .build();
+/// assert_eq!(config.include, ["rs"]);
+/// ```
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl Config {
+    /// Returns a builder starting from the default configuration.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+}
+
+impl ConfigBuilder {
+    /// Sets a single directory to process.
+    pub fn directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.config.directories = vec![directory.into()];
+        self
+    }
+
+    /// Sets the directories to process, walked in turn.
+    pub fn directories<I, P>(mut self, directories: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.config.directories = directories.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the output file path.
+    pub fn output(mut self, output: impl Into<PathBuf>) -> Self {
+        self.config.output = Some(output.into());
+        self
+    }
+
+    /// Sets more files to write the same bundle to, each in the format of its extension.
+    pub fn also_output<I, P>(mut self, also_output: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.config.also_output = also_output.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the CSV file that receives per-file statistics after the run.
+    pub fn stats_output(mut self, stats_output: impl Into<PathBuf>) -> Self {
+        self.config.stats_output = Some(stats_output.into());
+        self
+    }
+
+    /// Sets how many of the largest included files are listed after the run.
+    pub fn top(mut self, top: usize) -> Self {
+        self.config.top = Some(top);
+        self
+    }
+
+    /// Sets the JSON file that receives the manifest of the bundle after the run.
+    pub fn manifest(mut self, manifest: impl Into<PathBuf>) -> Self {
+        self.config.manifest = Some(manifest.into());
+        self
+    }
+
+    /// Sets the file extensions to include in the output.
+    pub fn include<I, S>(mut self, include: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.include = include.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the well-known languages, such as `rust` or `docker`, whose files to include.
+    pub fn languages<I, S>(mut self, languages: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.languages = languages.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the file extensions to exclude from the output.
+    pub fn exclude<I, S>(mut self, exclude: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.exclude = exclude.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets a file of extensions or globs to include, one per line.
+    pub fn include_from(mut self, include_from: impl Into<PathBuf>) -> Self {
+        self.config.include_from = Some(include_from.into());
+        self
+    }
+
+    /// Sets a file of extensions or globs to exclude, one per line.
+    pub fn exclude_from(mut self, exclude_from: impl Into<PathBuf>) -> Self {
+        self.config.exclude_from = Some(exclude_from.into());
+        self
+    }
+
+    /// Sets whether configuration files in subdirectories apply to the files under them.
+    pub fn nested_configs(mut self, nested_configs: bool) -> Self {
+        self.config.nested_configs = nested_configs;
+        self
+    }
+
+    /// Sets the format of the output (Markdown, Text, or Console).
+    pub fn format(mut self, format: Format) -> Self {
+        self.config.format = format;
+        self
+    }
+
+    /// Sets whether to append the current date to the output file name.
+    pub fn append_date(mut self, append_date: bool) -> Self {
+        self.config.append_date = append_date;
+        self
+    }
+
+    /// Sets whether to append the current Git hash to the output file name.
+    pub fn append_git_hash(mut self, append_git_hash: bool) -> Self {
+        self.config.append_git_hash = append_git_hash;
+        self
+    }
+
+    /// Sets whether to add `-dirty` to the Git hash when the working tree has uncommitted changes.
+    pub fn dirty_suffix(mut self, dirty_suffix: bool) -> Self {
+        self.config.dirty_suffix = dirty_suffix;
+        self
+    }
+
+    /// Sets the template for the output file name, e.g. `{dir}_{date}_{hash}.{ext}`.
+    pub fn output_template(mut self, output_template: impl Into<String>) -> Self {
+        self.config.output_template = Some(output_template.into());
+        self
+    }
+
+    /// Sets the template appended to the output file name, e.g. `-{branch}-{label}`.
+    pub fn name_suffix(mut self, name_suffix: impl Into<String>) -> Self {
+        self.config.name_suffix = Some(name_suffix.into());
+        self
+    }
+
+    /// Sets the value of `{label}` in the output template and name suffix.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.config.label = Some(label.into());
+        self
+    }
+
+    /// Sets whether placeholders without a value are left empty instead of failing the run.
+    pub fn lenient_suffix(mut self, lenient_suffix: bool) -> Self {
+        self.config.lenient_suffix = lenient_suffix;
+        self
+    }
+
+    /// Sets whether to bundle the outputs of earlier runs named by the same template.
+    pub fn include_previous_outputs(mut self, include_previous_outputs: bool) -> Self {
+        self.config.include_previous_outputs = include_previous_outputs;
+        self
+    }
+
+    /// Sets the strftime format of `{date}` and of the appended date.
+    pub fn date_format(mut self, date_format: impl Into<String>) -> Self {
+        self.config.date_format = date_format.into();
+        self
+    }
+
+    /// Sets whether to include line numbers in the output.
+    pub fn line_numbers(mut self, line_numbers: bool) -> Self {
+        self.config.line_numbers = line_numbers;
+        self
+    }
+
+    /// Sets the heading level (1–6) of each file in the Markdown format.
+    pub fn heading_level(mut self, heading_level: u8) -> Self {
+        self.config.heading_level = heading_level;
+        self
+    }
+
+    /// Sets a template that replaces each file's Markdown heading.
+    pub fn file_header_template(mut self, file_header_template: impl Into<String>) -> Self {
+        self.config.file_header_template = Some(file_header_template.into());
+        self
+    }
+
+    /// Sets how tokens are counted.
+    pub fn tokenizer(mut self, tokenizer: Tokenizer) -> Self {
+        self.config.tokenizer = tokenizer;
+        self
+    }
+
+    /// Sets the most tokens to bundle, choosing the files that fit.
+    pub fn fit_budget(mut self, fit_budget: usize) -> Self {
+        self.config.fit_budget = Some(fit_budget);
+        self
+    }
+
+    /// Sets the model the bundle is meant for.
+    pub fn model(mut self, model: Model) -> Self {
+        self.config.model = Some(model);
+        self
+    }
+
+    /// Sets whether a bundle over 90% of the model's context window fails the run.
+    pub fn strict_fit(mut self, strict_fit: bool) -> Self {
+        self.config.strict_fit = strict_fit;
+        self
+    }
+
+    /// Sets whether the chat-json format writes each file as its own user message.
+    pub fn messages_per_file(mut self, messages_per_file: bool) -> Self {
+        self.config.messages_per_file = messages_per_file;
+        self
+    }
+
+    /// Sets the order in which files are considered for the budget after the priority patterns.
+    pub fn fit_strategy(mut self, fit_strategy: FitStrategy) -> Self {
+        self.config.fit_strategy = fit_strategy;
+        self
+    }
+
+    /// Sets whether each file of a Markdown bundle is wrapped in a collapsed `<details>` block.
+    pub fn collapsible(mut self, collapsible: bool) -> Self {
+        self.config.collapsible = collapsible;
+        self
+    }
+
+    /// Sets how line numbers are written.
+    pub fn line_number_format(mut self, line_number_format: LineNumberFormat) -> Self {
+        self.config.line_number_format = line_number_format;
+        self
+    }
+
+    /// Sets whether to ignore hidden files and directories.
+    pub fn ignore_hidden(mut self, ignore_hidden: bool) -> Self {
+        self.config.ignore_hidden = ignore_hidden;
+        self
+    }
+
+    /// Sets the names or globs of hidden files and directories that `ignore_hidden` keeps.
+    pub fn keep_hidden<I, S>(mut self, keep_hidden: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.keep_hidden = keep_hidden.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether to append [`DEFAULT_KEEP_HIDDEN`](crate::DEFAULT_KEEP_HIDDEN) to the
+    /// `keep_hidden` patterns.
+    pub fn keep_hidden_defaults(mut self, keep_hidden_defaults: bool) -> Self {
+        self.config.keep_hidden_defaults = keep_hidden_defaults;
+        self
+    }
+
+    /// Sets whether to respect `.gitignore` rules.
+    pub fn respect_gitignore(mut self, respect_gitignore: bool) -> Self {
+        self.config.respect_gitignore = respect_gitignore;
+        self
+    }
+
+    /// Sets whether to respect `.ignore` and `.rgignore` files like `.gitignore`.
+    pub fn respect_ignore_files(mut self, respect_ignore_files: bool) -> Self {
+        self.config.respect_ignore_files = respect_ignore_files;
+        self
+    }
+
+    /// Sets whether to leave out files and directories with the `export-ignore` attribute.
+    pub fn respect_export_ignore(mut self, respect_export_ignore: bool) -> Self {
+        self.config.respect_export_ignore = respect_export_ignore;
+        self
+    }
+
+    /// Sets the directories to leave out without entering them, by name or relative path glob.
+    pub fn exclude_dirs<I, S>(mut self, exclude_dirs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.exclude_dirs = exclude_dirs.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the exact relative paths of files to bundle whatever the other filters say.
+    pub fn include_paths<I, P>(mut self, include_paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.config.include_paths = include_paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the exact relative paths of files never to bundle, even if in `include_paths`.
+    pub fn exclude_paths<I, P>(mut self, exclude_paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.config.exclude_paths = exclude_paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether to walk `.git`, `.hg`, and `.svn` directories, which are skipped by default.
+    pub fn include_vcs_dir(mut self, include_vcs_dir: bool) -> Self {
+        self.config.include_vcs_dir = include_vcs_dir;
+        self
+    }
+
+    /// Sets a file of gitignore-syntax rules applied in addition to each directory's `.c2pignore`.
+    pub fn ignore_file(mut self, ignore_file: impl Into<PathBuf>) -> Self {
+        self.config.ignore_file = Some(ignore_file.into());
+        self
+    }
+
+    /// Sets whether to traverse initialized git submodules.
+    pub fn include_submodules(mut self, include_submodules: bool) -> Self {
+        self.config.include_submodules = include_submodules;
+        self
+    }
+
+    /// Sets whether to copy the output to the system clipboard.
+    pub fn clipboard(mut self, clipboard: bool) -> Self {
+        self.config.clipboard = clipboard;
+        self
+    }
+
+    /// Sets whether to only list the files that would be included.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.config.dry_run = dry_run;
+        self
+    }
+
+    /// Sets whether dry-run listings include each file's size and token estimate.
+    pub fn dry_run_sizes(mut self, dry_run_sizes: bool) -> Self {
+        self.config.dry_run_sizes = dry_run_sizes;
+        self
+    }
+
+    /// Sets whether each file is written as its header alone, without its content.
+    pub fn headers_only(mut self, headers_only: bool) -> Self {
+        self.config.headers_only = headers_only;
+        self
+    }
+
+    /// Sets whether to show a progress spinner on stderr.
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.config.progress = progress;
+        self
+    }
+
+    /// Sets whether console output to a terminal is shown in a pager.
+    pub fn pager(mut self, pager: bool) -> Self {
+        self.config.pager = pager;
+        self
+    }
+
+    /// Sets whether to remove comments from source files with a known comment syntax.
+    pub fn strip_comments(mut self, strip_comments: bool) -> Self {
+        self.config.strip_comments = strip_comments;
+        self
+    }
+
+    /// Sets whether documentation comments survive `strip_comments`.
+    pub fn keep_doc_comments(mut self, keep_doc_comments: bool) -> Self {
+        self.config.keep_doc_comments = keep_doc_comments;
+        self
+    }
+
+    /// Sets whether to collapse blank-line runs and strip trailing whitespace.
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.config.compact = compact;
+        self
+    }
+
+    /// Sets whether function bodies are elided, leaving declarations.
+    pub fn signatures_only(mut self, signatures_only: bool) -> Self {
+        self.config.signatures_only = signatures_only;
+        self
+    }
+
+    /// Sets whether to strip trailing spaces and tabs from each line and blank lines at the end.
+    pub fn trim_trailing_whitespace(mut self, trim_trailing_whitespace: bool) -> Self {
+        self.config.trim_trailing_whitespace = trim_trailing_whitespace;
+        self
+    }
+
+    /// Sets whether to remove ANSI escape sequences, such as terminal colors, from content.
+    pub fn strip_ansi(mut self, strip_ansi: bool) -> Self {
+        self.config.strip_ansi = strip_ansi;
+        self
+    }
+
+    /// Sets whether to remove a leading front matter block from Markdown files.
+    pub fn strip_frontmatter(mut self, strip_frontmatter: bool) -> Self {
+        self.config.strip_frontmatter = strip_frontmatter;
+        self
+    }
+
+    /// Sets whether to remove license headers from source files.
+    pub fn strip_license_headers(mut self, strip_license_headers: bool) -> Self {
+        self.config.strip_license_headers = strip_license_headers;
+        self
+    }
+
+    /// Sets the regular expression that marks a comment block as a license header.
+    pub fn license_pattern(mut self, license_pattern: impl Into<String>) -> Self {
+        self.config.license_pattern = Some(license_pattern.into());
+        self
+    }
+
+    /// Sets whether the text outputs of notebook cells are written.
+    pub fn notebook_outputs(mut self, notebook_outputs: bool) -> Self {
+        self.config.notebook_outputs = notebook_outputs;
+        self
+    }
+
+    /// Sets the most characters a line keeps before it is cut.
+    pub fn max_line_length(mut self, max_line_length: usize) -> Self {
+        self.config.max_line_length = Some(max_line_length);
+        self
+    }
+
+    /// Sets the line endings file content is converted to.
+    pub fn normalize_eol(mut self, normalize_eol: LineEnding) -> Self {
+        self.config.normalize_eol = normalize_eol;
+        self
+    }
+
+    /// Sets the distance between tab stops when replacing tabs with spaces.
+    pub fn expand_tabs(mut self, expand_tabs: usize) -> Self {
+        self.config.expand_tabs = Some(expand_tabs);
+        self
+    }
+
+    /// Sets the shell command each included file is piped through.
+    pub fn filter_cmd(mut self, filter_cmd: impl Into<String>) -> Self {
+        self.config.filter_cmd = Some(filter_cmd.into());
+        self
+    }
+
+    /// Sets the rules that pipe the files with an extension through their own command.
+    pub fn filter_cmd_ext(
+        mut self,
+        filter_cmd_ext: impl IntoIterator<Item = FilterCommand>,
+    ) -> Self {
+        self.config.filter_cmd_ext = filter_cmd_ext.into_iter().collect();
+        self
+    }
+
+    /// Sets the seconds a filter command may run on one file; `0` for no limit.
+    pub fn filter_timeout(mut self, filter_timeout: u64) -> Self {
+        self.config.filter_timeout = filter_timeout;
+        self
+    }
+
+    /// Sets the most filter commands running at once.
+    pub fn filter_jobs(mut self, filter_jobs: usize) -> Self {
+        self.config.filter_jobs = Some(filter_jobs);
+        self
+    }
+
+    /// Sets the maximum number of leading lines to keep from each file.
+    pub fn head(mut self, head: usize) -> Self {
+        self.config.head = Some(head);
+        self
+    }
+
+    /// Sets the maximum number of trailing lines to keep from each file.
+    pub fn tail(mut self, tail: usize) -> Self {
+        self.config.tail = Some(tail);
+        self
+    }
+
+    /// Sets the most lines to keep from each file.
+    pub fn max_lines_per_file(mut self, max_lines_per_file: usize) -> Self {
+        self.config.max_lines_per_file = Some(max_lines_per_file);
+        self
+    }
+
+    /// Sets the most tokens to keep from each file.
+    pub fn max_tokens_per_file(mut self, max_tokens_per_file: usize) -> Self {
+        self.config.max_tokens_per_file = Some(max_tokens_per_file);
+        self
+    }
+
+    /// Sets which lines of a file over a per-file cap are kept.
+    pub fn truncate(mut self, truncate: Truncation) -> Self {
+        self.config.truncate = truncate;
+        self
+    }
+
+    /// Sets the line ranges to restrict the bundle to; only the listed files are included.
+    pub fn only(mut self, only: impl IntoIterator<Item = LineRange>) -> Self {
+        self.config.only = only.into_iter().collect();
+        self
+    }
+
+    /// Sets the rules that rewrite the paths shown in the output.
+    pub fn rename_paths(mut self, rename_paths: impl IntoIterator<Item = PathRename>) -> Self {
+        self.config.rename_paths = rename_paths.into_iter().collect();
+        self
+    }
+
+    /// Sets the directory displayed paths are relative to, e.g. [`RelativeTo::GitRoot`].
+    pub fn relative_to(mut self, relative_to: RelativeTo) -> Self {
+        self.config.relative_to = Some(relative_to);
+        self
+    }
+
+    /// Sets whether displayed paths keep the platform separator instead of always using `/`.
+    pub fn native_paths(mut self, native_paths: bool) -> Self {
+        self.config.native_paths = native_paths;
+        self
+    }
+
+    /// Sets the rules that set the code-fence language of files in the Markdown format.
+    pub fn lang_map(mut self, lang_map: impl IntoIterator<Item = LangMapping>) -> Self {
+        self.config.lang_map = lang_map.into_iter().collect();
+        self
+    }
+
+    /// Sets whether extensionless scripts get the language of their `#!` interpreter.
+    pub fn shebang_detect(mut self, shebang_detect: bool) -> Self {
+        self.config.shebang_detect = shebang_detect;
+        self
+    }
+
+    /// Sets whether lockfiles are bundled.
+    pub fn include_lockfiles(mut self, include_lockfiles: bool) -> Self {
+        self.config.include_lockfiles = include_lockfiles;
+        self
+    }
+
+    /// Sets whether files that look like they hold secrets, such as `.env`, are bundled.
+    pub fn include_sensitive(mut self, include_sensitive: bool) -> Self {
+        self.config.include_sensitive = include_sensitive;
+        self
+    }
+
+    /// Sets whether files marked vendored or generated by linguist attributes are left out.
+    pub fn respect_linguist(mut self, respect_linguist: bool) -> Self {
+        self.config.respect_linguist = respect_linguist;
+        self
+    }
+
+    /// Sets whether test files are left out.
+    pub fn no_tests(mut self, no_tests: bool) -> Self {
+        self.config.no_tests = no_tests;
+        self
+    }
+
+    /// Sets whether only test files are bundled.
+    pub fn only_tests(mut self, only_tests: bool) -> Self {
+        self.config.only_tests = only_tests;
+        self
+    }
+
+    /// Sets whether files that look minified or generated are skipped.
+    pub fn skip_generated(mut self, skip_generated: bool) -> Self {
+        self.config.skip_generated = skip_generated;
+        self
+    }
+
+    /// Sets how files are split into sections with subtotals: by directory or by language.
+    pub fn group_by(mut self, group_by: GroupBy) -> Self {
+        self.config.group_by = Some(group_by);
+        self
+    }
+
+    /// Sets how many leading directories make up a section with [`GroupBy::Dir`].
+    pub fn group_depth(mut self, group_depth: usize) -> Self {
+        self.config.group_depth = group_depth;
+        self
+    }
+
+    /// Sets the order of the sections with [`GroupBy::Lang`].
+    pub fn group_order(mut self, group_order: GroupOrder) -> Self {
+        self.config.group_order = group_order;
+        self
+    }
+
+    /// Sets the largest bundle written before the run is aborted; zero means no limit.
+    pub fn max_total_size(mut self, max_total_size: ByteSize) -> Self {
+        self.config.max_total_size = max_total_size;
+        self
+    }
+
+    /// Sets the file names treated as lockfiles.
+    pub fn lockfiles<I, S>(mut self, lockfiles: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.lockfiles = lockfiles.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the globs that identify test files by relative path.
+    pub fn tests_patterns<I, S>(mut self, tests_patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.tests_patterns = tests_patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the time files must be modified at or after to be bundled.
+    pub fn newer_than(mut self, newer_than: TimeBound) -> Self {
+        self.config.newer_than = Some(newer_than);
+        self
+    }
+
+    /// Sets the time files must be modified before to be bundled.
+    pub fn older_than(mut self, older_than: TimeBound) -> Self {
+        self.config.older_than = Some(older_than);
+        self
+    }
+
+    /// Sets whether files that are empty or whitespace-only after transformations are skipped.
+    pub fn skip_empty(mut self, skip_empty: bool) -> Self {
+        self.config.skip_empty = skip_empty;
+        self
+    }
+
+    /// Sets the size on disk below which files are skipped.
+    pub fn min_file_size(mut self, min_file_size: ByteSize) -> Self {
+        self.config.min_file_size = Some(min_file_size);
+        self
+    }
+
+    /// Sets the size on disk above which files are skipped without being read.
+    pub fn max_file_size(mut self, max_file_size: ByteSize) -> Self {
+        self.config.max_file_size = Some(max_file_size);
+        self
+    }
+
+    /// Sets whether binary and too large files are listed as placeholders.
+    pub fn binary_placeholders(mut self, binary_placeholders: bool) -> Self {
+        self.config.binary_placeholders = binary_placeholders;
+        self
+    }
+
+    /// Sets the regular expressions of which a file's content must match one to be bundled.
+    pub fn grep<I, S>(mut self, grep: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.grep = grep.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether a file's content must match every `grep` pattern rather than any.
+    pub fn grep_and(mut self, grep_and: bool) -> Self {
+        self.config.grep_and = grep_and;
+        self
+    }
+
+    /// Sets whether the `grep` patterns ignore case.
+    pub fn grep_ignore_case(mut self, grep_ignore_case: bool) -> Self {
+        self.config.grep_ignore_case = grep_ignore_case;
+        self
+    }
+
+    /// Sets how many lines around each `grep` match are written, writing only the matching
+    /// regions of each file.
+    pub fn grep_context(mut self, grep_context: usize) -> Self {
+        self.config.grep_context = Some(grep_context);
+        self
+    }
+
+    /// Sets the most files to include.
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        self.config.max_files = Some(max_files);
+        self
+    }
+
+    /// Sets the largest binary file that is embedded as base64.
+    pub fn embed_binary(mut self, embed_binary: ByteSize) -> Self {
+        self.config.embed_binary = Some(embed_binary);
+        self
+    }
+
+    /// Sets the JSON file, or `-` for stdout, that receives the report of the run.
+    pub fn report_json(mut self, report_json: impl Into<PathBuf>) -> Self {
+        self.config.report_json = Some(report_json.into());
+        self
+    }
+
+    /// Sets the display name of extra content read from stdin and bundled after the other
+    /// files.
+    pub fn stdin_file(mut self, stdin_file: impl Into<PathBuf>) -> Self {
+        self.config.stdin_file = Some(stdin_file.into());
+        self
+    }
+
+    /// Sets whether a header describing what produced the bundle is written before the first
+    /// file.
+    pub fn metadata_header(mut self, metadata_header: bool) -> Self {
+        self.config.metadata_header = metadata_header;
+        self
+    }
+
+    /// Sets whether the metadata header has a table of the lines of each language.
+    pub fn lang_stats(mut self, lang_stats: bool) -> Self {
+        self.config.lang_stats = lang_stats;
+        self
+    }
+
+    /// Sets whether a Markdown bundle starts with YAML front matter.
+    pub fn frontmatter(mut self, frontmatter: bool) -> Self {
+        self.config.frontmatter = frontmatter;
+        self
+    }
+
+    /// Adds a front matter field, replacing a built-in one with the same name.
+    pub fn frontmatter_field(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config
+            .frontmatter_fields
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the text written verbatim before the bundle.
+    pub fn preamble(mut self, preamble: impl Into<String>) -> Self {
+        self.config.preamble = Some(preamble.into());
+        self
+    }
+
+    /// Sets the text written verbatim after the bundle.
+    pub fn postamble(mut self, postamble: impl Into<String>) -> Self {
+        self.config.postamble = Some(postamble.into());
+        self
+    }
+
+    /// Sets the question written after the last file.
+    pub fn question(mut self, question: impl Into<String>) -> Self {
+        self.config.question = Some(question.into());
+        self
+    }
+
+    /// Sets the order in which files are written.
+    pub fn sort(mut self, sort: SortOrder) -> Self {
+        self.config.sort = sort;
+        self
+    }
+
+    /// Sets whether to reverse the sort order.
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.config.reverse = reverse;
+        self
+    }
+
+    /// Sets how far back commits count when ranking files by churn.
+    pub fn churn_window(mut self, churn_window: TimeBound) -> Self {
+        self.config.churn_window = churn_window;
+        self
+    }
+
+    /// Sets the glob patterns of relative paths to emit first, in the order given.
+    pub fn priority<I, S>(mut self, priority: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.priority = priority.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether to append [`DEFAULT_PRIORITY_PATTERNS`](crate::DEFAULT_PRIORITY_PATTERNS) to the
+    /// priority patterns.
+    pub fn priority_defaults(mut self, priority_defaults: bool) -> Self {
+        self.config.priority_defaults = priority_defaults;
+        self
+    }
+
+    /// Sets a file listing relative paths or globs, one per line, in the order to emit them.
+    pub fn order_file(mut self, order_file: impl Into<PathBuf>) -> Self {
+        self.config.order_file = Some(order_file.into());
+        self
+    }
+
+    /// Sets whether to exclude files not listed in the order file.
+    pub fn order_strict(mut self, order_strict: bool) -> Self {
+        self.config.order_strict = order_strict;
+        self
+    }
+
+    /// Sets a file listing the paths to bundle instead of walking the directories.
+    pub fn files_from(mut self, files_from: impl Into<PathBuf>) -> Self {
+        self.config.files_from = Some(files_from.into());
+        self
+    }
+
+    /// Sets whether the paths in `files_from` are NUL-delimited instead of newline-delimited.
+    pub fn files_from_nul(mut self, files_from_nul: bool) -> Self {
+        self.config.files_from_nul = files_from_nul;
+        self
+    }
+
+    /// Sets the relative paths of the entry files, which with `follow_imports` are bundled
+    /// with the files they import.
+    pub fn entry<I, P>(mut self, entry: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.config.entry = entry.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether to also bundle the files the entry files import, transitively.
+    pub fn follow_imports(mut self, follow_imports: bool) -> Self {
+        self.config.follow_imports = follow_imports;
+        self
+    }
+
+    /// Sets how many imports away from the entry files to follow.
+    pub fn follow_depth(mut self, follow_depth: usize) -> Self {
+        self.config.follow_depth = Some(follow_depth);
+        self
+    }
+
+    /// Sets the Cargo workspace members to bundle, by package name or directory.
+    pub fn workspace_members<I, S>(mut self, members: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.workspace_members = members.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether workspace members are read from `cargo metadata`.
+    pub fn use_cargo_metadata(mut self, use_cargo_metadata: bool) -> Self {
+        self.config.use_cargo_metadata = use_cargo_metadata;
+        self
+    }
+
+    /// Sets a `base..head` range of git refs to bundle only the files that differ between them.
+    pub fn between(mut self, range: impl Into<String>) -> Self {
+        self.config.between = Some(range.into());
+        self
+    }
+
+    /// Sets whether to follow symbolic links while walking.
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.config.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Sets the number of threads reading and transforming files; `0` uses one per CPU.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.config.jobs = Some(jobs);
+        self
+    }
+
+    /// Sets the file caching transformed content between runs.
+    pub fn cache(mut self, cache: impl Into<PathBuf>) -> Self {
+        self.config.cache = Some(cache.into());
+        self
+    }
+
+    /// Sets the metadata fields shown in each file header.
+    pub fn file_meta(mut self, file_meta: FileMeta) -> Self {
+        self.config.file_meta = file_meta;
+        self
+    }
+
+    /// Sets the algorithm of the content hash in each file header and of the bundle digest.
+    pub fn hash(mut self, hash: HashAlgorithm) -> Self {
+        self.config.hash = Some(hash);
+        self
+    }
+
+    /// Sets whether dates and times (file headers, output file name, run headings) are in UTC.
+    pub fn utc(mut self, utc: bool) -> Self {
+        self.config.utc = utc;
+        self
+    }
+
+    /// Sets whether the bundle depends only on the sources, with timestamps pinned by
+    /// `SOURCE_DATE_EPOCH` or left out.
+    pub fn reproducible(mut self, reproducible: bool) -> Self {
+        self.config.reproducible = reproducible;
+        self
+    }
+
+    /// Sets whether files whose content duplicates an earlier file are written as a stub.
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.config.dedup = dedup;
+        self
+    }
+
+    /// Sets the compression applied to the output.
+    pub fn compress(mut self, compress: Compression) -> Self {
+        self.config.compress = Some(compress);
+        self
+    }
+
+    /// Sets when the console format uses colors.
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.config.color = color;
+        self
+    }
+
+    /// Sets a directory to write one output file per source file to, with an `index.md`.
+    pub fn output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.config.output_dir = Some(output_dir.into());
+        self
+    }
+
+    /// Sets whether output files that already exist may be overwritten.
+    pub fn force(mut self, force: bool) -> Self {
+        self.config.force = force;
+        self
+    }
+
+    /// Sets whether output files that already exist are renamed to `<name>.bak` first.
+    pub fn backup(mut self, backup: bool) -> Self {
+        self.config.backup = backup;
+        self
+    }
+
+    /// Sets whether the missing parent directories of the output files are created.
+    pub fn create_dirs(mut self, create_dirs: bool) -> Self {
+        self.config.create_dirs = create_dirs;
+        self
+    }
+
+    /// Sets whether to append to the output file instead of overwriting it.
+    pub fn append(mut self, append: bool) -> Self {
+        self.config.append = append;
+        self
+    }
+
+    /// Sets whether output written to a file is also echoed to stdout.
+    pub fn tee(mut self, tee: bool) -> Self {
+        self.config.tee = tee;
+        self
+    }
+
+    /// Sets whether the first file that cannot be read aborts the run.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.config.strict = strict;
+        self
+    }
+
+    /// Sets whether a run that matches no files fails instead of warning.
+    pub fn fail_if_empty(mut self, fail_if_empty: bool) -> Self {
+        self.config.fail_if_empty = fail_if_empty;
+        self
+    }
+
+    /// Returns the built configuration.
+    pub fn build(self) -> Config {
+        self.config
+    }
+}
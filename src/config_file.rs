@@ -0,0 +1,373 @@
+//! Discovery and loading of `codebase-to-prompt.toml` configuration files.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{Config, toml};
+
+/// File names probed, in order, when discovering a configuration file in a directory.
+pub const CONFIG_FILE_NAMES: [&str; 2] = ["codebase-to-prompt.toml", ".c2p.toml"];
+
+/// The options a configuration file found in a bundled directory may not set, since it comes
+/// with the code: those that run commands, write or overwrite files, or read files other than
+/// the bundled ones. A file passed with `--config` may set them.
+const TRUSTED_OPTIONS: &[&str] = &[
+    "filter_cmd",
+    "filter_cmd_ext",
+    "directory",
+    "directories",
+    "output",
+    "also_output",
+    "output_dir",
+    "stats_output",
+    "manifest",
+    "report_json",
+    "cache",
+    "force",
+    "backup",
+    "append",
+    "files_from",
+    "include_from",
+    "exclude_from",
+    "include_paths",
+    "ignore_file",
+    "order_file",
+    "stdin_file",
+    "entry",
+];
+
+/// Looks for a configuration file in the given directory.
+///
+/// # Arguments
+/// * `directory` - The directory to search.
+///
+/// # Returns
+/// * `Option<PathBuf>` - The path of the first configuration file found, if any.
+pub fn find_config_file(directory: &Path) -> Option<PathBuf> {
+    CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| directory.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Loads a configuration file into a `Config`.
+///
+/// Options missing from the file keep their default values. When a profile is selected,
+/// its `[profile.<name>]` table is overlaid on the top-level options. Relative `directories`,
+/// `output`, `stats_output`, `manifest`, `report_json` (unless `-`), `cache`, `order_file`,
+/// `files_from`, `ignore_file`, `include_from`, and `exclude_from` paths are resolved against
+/// the directory containing the configuration file, as are `@file` references in `preamble`,
+/// `postamble`, and `question`.
+///
+/// # Arguments
+/// * `path` - The path of the TOML configuration file.
+/// * `profile` - The optional name of a profile defined in the file.
+///
+/// # Returns
+/// * `Result<Config>` - The loaded configuration, or an error naming the file.
+pub fn load_config_file(path: &Path, profile: Option<&str>) -> Result<Config> {
+    load_config_file_with_fields(path, profile).map(|(config, _)| config)
+}
+
+/// Loads a configuration file like [`load_config_file`], and also returns the options the
+/// file sets, so `--print-config=verbose` can tell them from defaults.
+///
+/// # Arguments
+/// * `path` - The path of the TOML configuration file.
+/// * `profile` - The optional name of a profile defined in the file.
+///
+/// # Returns
+/// * `Result<(Config, BTreeSet<String>)>` - The loaded configuration and the names of the
+///   `Config` fields set by the file or the profile, or an error naming the file.
+pub fn load_config_file_with_fields(
+    path: &Path,
+    profile: Option<&str>,
+) -> Result<(Config, BTreeSet<String>)> {
+    load(path, profile, true)
+}
+
+/// Loads a configuration file found in a bundled directory rather than chosen by the user,
+/// like [`load_config_file_with_fields`], refusing the options it may not set: filter
+/// commands, output files and `force`, `backup`, and `append`, the files that list or order
+/// the bundled files, the directories themselves, and `@file` references in `preamble`,
+/// `postamble`, and `question` to files outside the config file's directory.
+///
+/// # Arguments
+/// * `path` - The path of the TOML configuration file.
+/// * `profile` - The optional name of a profile defined in the file.
+///
+/// # Returns
+/// * `Result<(Config, BTreeSet<String>)>` - The loaded configuration and the fields it sets,
+///   or an error naming the file and the first option it may not set.
+pub fn load_discovered_config_file(
+    path: &Path,
+    profile: Option<&str>,
+) -> Result<(Config, BTreeSet<String>)> {
+    load(path, profile, false)
+}
+
+/// Loads a configuration file, refusing the options of [`TRUSTED_OPTIONS`] unless it is
+/// `trusted`.
+fn load(path: &Path, profile: Option<&str>, trusted: bool) -> Result<(Config, BTreeSet<String>)> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    let mut table =
+        toml::parse(&source).with_context(|| format!("Invalid config file: {}", path.display()))?;
+
+    let profiles = match table.remove("profile") {
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(_) => bail!(
+            "Invalid config file: {}: `profile` must be a table",
+            path.display()
+        ),
+        None => toml::Table::new(),
+    };
+    let mut table = snake_case_keys(table);
+
+    if let Some(name) = profile {
+        match profiles.get(name) {
+            Some(toml::Value::Table(overrides)) => {
+                table.extend(snake_case_keys(overrides.clone()));
+                info!("Using profile: {}", name);
+            }
+            Some(_) => bail!(
+                "Invalid config file: {}: profile `{}` must be a table",
+                path.display(),
+                name
+            ),
+            None => {
+                let available: Vec<&str> = profiles.keys().map(String::as_str).collect();
+                bail!(
+                    "Unknown profile `{}` in {} (available: {})",
+                    name,
+                    path.display(),
+                    if available.is_empty() {
+                        "none".to_string()
+                    } else {
+                        available.join(", ")
+                    }
+                );
+            }
+        }
+    }
+
+    // A `[frontmatter]` table adds front matter fields and turns the front matter on.
+    if let Some(toml::Value::Table(fields)) = table.get("frontmatter") {
+        let fields = toml::Value::Table(fields.clone());
+        table.insert("frontmatter_fields".to_string(), fields);
+        table.insert("frontmatter".to_string(), toml::Value::Boolean(true));
+    }
+    let base = path.parent().unwrap_or(Path::new(""));
+    if !trusted {
+        let refused = table.iter().find(|(key, value)| {
+            TRUSTED_OPTIONS.contains(&key.as_str())
+                || matches!(key.as_str(), "preamble" | "postamble" | "question")
+                    && matches!(value, toml::Value::String(text)
+                        if text.strip_prefix('@').is_some_and(|file| !is_inside(base, file)))
+        });
+        if let Some((key, _)) = refused {
+            bail!(
+                "{} sets `{}`, which only a config file passed with --config may set: a config \
+                 file found with the bundled code may not run commands, write files, or read \
+                 files other than the bundled ones",
+                path.display(),
+                key
+            );
+        }
+    }
+    let fields = table
+        .keys()
+        .map(|key| match key.as_str() {
+            "directory" => "directories".to_string(),
+            key => key.to_string(),
+        })
+        .collect();
+    let mut config = Config::deserialize(toml::Value::Table(table))
+        .with_context(|| format!("Invalid config file: {}", path.display()))?;
+
+    for directory in &mut config.directories {
+        *directory = base.join(&*directory);
+    }
+    if let Some(output) = &config.output {
+        config.output = Some(base.join(output));
+    }
+    if let Some(manifest) = &config.manifest {
+        config.manifest = Some(base.join(manifest));
+    }
+    if let Some(report_json) = &config.report_json
+        && report_json != Path::new("-")
+    {
+        config.report_json = Some(base.join(report_json));
+    }
+    if let Some(cache) = &config.cache {
+        config.cache = Some(base.join(cache));
+    }
+    if let Some(stats_output) = &config.stats_output {
+        config.stats_output = Some(base.join(stats_output));
+    }
+    if let Some(order_file) = &config.order_file {
+        config.order_file = Some(base.join(order_file));
+    }
+    if let Some(ignore_file) = &config.ignore_file {
+        config.ignore_file = Some(base.join(ignore_file));
+    }
+    for path in [&mut config.include_from, &mut config.exclude_from]
+        .into_iter()
+        .flatten()
+    {
+        *path = base.join(&*path);
+    }
+    if let Some(files_from) = &config.files_from
+        && files_from != Path::new("-")
+    {
+        config.files_from = Some(base.join(files_from));
+    }
+    for text in [
+        &mut config.preamble,
+        &mut config.postamble,
+        &mut config.question,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        *text = resolve_text(text, base)?;
+    }
+
+    info!("Loaded config file: {}", path.display());
+    Ok((config, fields))
+}
+
+/// Returns whether a relative path names a file inside a directory, once symbolic links are
+/// resolved.
+fn is_inside(base: &Path, relative: &str) -> bool {
+    let relative = Path::new(relative);
+    if relative.is_absolute() {
+        return false;
+    }
+    match (base.join(relative).canonicalize(), base.canonicalize()) {
+        (Ok(file), Ok(base)) => file.starts_with(base),
+        // A missing file is reported when it is read.
+        _ => !relative
+            .components()
+            .any(|component| matches!(component, std::path::Component::ParentDir)),
+    }
+}
+
+/// Spells option names with underscores, so they can also be written as the CLI flags are,
+/// e.g. `line-numbers`.
+fn snake_case_keys(table: toml::Table) -> toml::Table {
+    table
+        .into_iter()
+        .map(|(key, value)| (key.replace('-', "_"), value))
+        .collect()
+}
+
+/// Resolves a text option that can name a file: `@path` reads the file, any other value is
+/// the text itself.
+///
+/// # Arguments
+/// * `value` - The option value.
+/// * `base` - The directory that relative paths are resolved against.
+///
+/// # Returns
+/// * `Result<String>` - The text, or an error if the file cannot be read.
+pub fn resolve_text(value: &str, base: &Path) -> Result<String> {
+    match value.strip_prefix('@') {
+        Some(path) => {
+            let path = base.join(path);
+            fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read text file: {}", path.display()))
+        }
+        None => Ok(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Format, json};
+    use std::str::FromStr;
+
+    #[test]
+    fn config_round_trips_through_serde() {
+        let config = Config::builder()
+            .directories(["src", "crates/core"])
+            .output("bundle.md")
+            .include(["rs", "toml"])
+            .format(Format::ClaudeXml)
+            .line_numbers(true)
+            .build();
+        let value = json::to_value(&config).unwrap();
+        let again: Config = json::from_value(value.clone()).unwrap();
+        assert_eq!(json::to_value(&again).unwrap(), value);
+        assert_eq!(
+            again.directories,
+            [Path::new("src"), Path::new("crates/core")]
+        );
+        assert_eq!(again.format, Format::ClaudeXml);
+    }
+
+    #[test]
+    fn a_minimal_document_takes_the_defaults() {
+        let config: Config = json::from_str(r#"{ "directory": "." }"#).unwrap();
+        assert_eq!(config.directories, [Path::new(".")]);
+        assert_eq!(config.format, Config::default().format);
+        assert!(json::from_str::<Config>(r#"{ "directry": "." }"#).is_err());
+    }
+
+    #[test]
+    fn format_round_trips_as_a_lowercase_string() {
+        for format in [
+            Format::Markdown,
+            Format::Text,
+            Format::Console,
+            Format::ClaudeXml,
+            Format::ChatJson,
+            Format::Asciidoc,
+        ] {
+            assert_eq!(Format::from_str(&format.to_string()), Ok(format));
+            assert_eq!(
+                json::to_value(&format).unwrap(),
+                json::Value::String(format.to_string())
+            );
+        }
+        assert_eq!("Claude-XML".parse(), Ok(Format::ClaudeXml));
+        assert!("json".parse::<Format>().unwrap_err().contains("`json`"));
+    }
+
+    #[test]
+    fn options_can_be_spelled_as_the_cli_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("codebase-to-prompt.toml");
+        fs::write(
+            &path,
+            "directory = \"src\"\nline-numbers = true\n\
+             [profile.review]\nexclude-dirs = [\"target\"]\n",
+        )
+        .unwrap();
+        let config = load_config_file(&path, Some("review")).unwrap();
+        assert_eq!(config.directories, [dir.path().join("src")]);
+        assert!(config.line_numbers);
+        assert_eq!(config.exclude_dirs, ["target"]);
+    }
+
+    #[test]
+    fn a_frontmatter_table_adds_fields_and_turns_it_on() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("codebase-to-prompt.toml");
+        fs::write(&path, "[frontmatter]\nproject = \"billing\"\n").unwrap();
+        let config = load_config_file(&path, None).unwrap();
+        assert!(config.frontmatter);
+        assert_eq!(config.frontmatter_fields["project"], "billing");
+
+        fs::write(&path, "frontmatter = true\n").unwrap();
+        let config = load_config_file(&path, None).unwrap();
+        assert!(config.frontmatter);
+        assert!(config.frontmatter_fields.is_empty());
+    }
+}
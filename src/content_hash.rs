@@ -0,0 +1,179 @@
+//! Content hashes of the files as written (`hash`), and the bundle digest combining them.
+
+use crate::blake3::Blake3;
+use crate::sha256::Sha256;
+use crate::transform::Segment;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The number of hex digits of a content hash shown in file headers.
+const SHORT_HASH_LEN: usize = 12;
+
+/// The algorithm of the per-file content hashes and the bundle digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// SHA-256.
+    Sha256,
+    /// BLAKE3, with a 256-bit output.
+    Blake3,
+}
+
+/// An incremental hasher of either algorithm.
+enum Hasher {
+    Sha256(Sha256),
+    Blake3(Blake3),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish_hex(self) -> String {
+        match self {
+            Hasher::Sha256(hasher) => hasher.finish_hex(),
+            Hasher::Blake3(hasher) => hasher.finish_hex(),
+        }
+    }
+}
+
+impl HashAlgorithm {
+    /// Returns the name that prefixes each hash, e.g. `blake3`.
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Blake3::new()),
+        }
+    }
+
+    /// Hashes the content of a file as written: the text of its selected lines, after every
+    /// transformation, without the omission markers.
+    ///
+    /// # Arguments
+    /// * `segments` - The selected lines of the file.
+    ///
+    /// # Returns
+    /// * `String` - The hash with its algorithm, e.g. `blake3:1f2a…`.
+    pub(crate) fn content_hash(self, segments: &[Segment]) -> String {
+        let mut hasher = self.hasher();
+        for segment in segments {
+            hasher.update(segment.text().as_bytes());
+        }
+        format!("{}:{}", self.name(), hasher.finish_hex())
+    }
+
+    /// Combines the content hashes of a bundle into one digest.
+    ///
+    /// The digest covers a `<hex>  <path>` line per file, in output order, with `/`
+    /// separators, so it changes with the content and the set and order of files, but not
+    /// with the format or the layout of the bundle.
+    ///
+    /// # Arguments
+    /// * `hashes` - The path and content hash of each file, from
+    ///   [`RunSummary::content_hashes`](crate::RunSummary::content_hashes).
+    ///
+    /// # Returns
+    /// * `String` - The digest with its algorithm, e.g. `blake3:9c0d…`.
+    pub(crate) fn bundle_digest(self, hashes: &[(PathBuf, String)]) -> String {
+        let mut hasher = self.hasher();
+        for (path, hash) in hashes {
+            let hex = hash.split_once(':').map_or(hash.as_str(), |(_, hex)| hex);
+            let path = path.to_string_lossy().replace('\\', "/");
+            hasher.update(format!("{}  {}\n", hex, path).as_bytes());
+        }
+        format!("{}:{}", self.name(), hasher.finish_hex())
+    }
+}
+
+/// Shortens a content hash for a file header, e.g. `blake3:1f2a3b4c5d6e`.
+pub(crate) fn short_hash(hash: &str) -> &str {
+    let len = hash.find(':').map_or(0, |colon| colon + 1) + SHORT_HASH_LEN;
+    hash.get(..len).unwrap_or(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Segment {
+        Segment::Lines {
+            first_line: 1,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn content_hashes_cover_the_written_lines() {
+        let whole = HashAlgorithm::Sha256.content_hash(&[lines("abc")]);
+        assert_eq!(
+            whole,
+            "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let omitted = Segment::Omitted {
+            lines: 3,
+            marker: "...".to_string(),
+        };
+        assert_eq!(
+            HashAlgorithm::Sha256.content_hash(&[lines("a"), omitted, lines("bc")]),
+            whole
+        );
+        assert!(
+            HashAlgorithm::Blake3
+                .content_hash(&[])
+                .starts_with("blake3:af1349b9")
+        );
+    }
+
+    #[test]
+    fn bundle_digests_depend_on_files_not_layout() {
+        let hashes = |entries: &[(&str, &str)]| -> Vec<(PathBuf, String)> {
+            entries
+                .iter()
+                .map(|(path, text)| {
+                    (
+                        PathBuf::from(path),
+                        HashAlgorithm::Blake3.content_hash(&[lines(text)]),
+                    )
+                })
+                .collect()
+        };
+        let digest =
+            |entries: &[(&str, &str)]| HashAlgorithm::Blake3.bundle_digest(&hashes(entries));
+        let base = digest(&[("a.rs", "fn a() {}"), ("b.rs", "fn b() {}")]);
+        assert!(base.starts_with("blake3:"));
+        assert_eq!(
+            base,
+            digest(&[("a.rs", "fn a() {}"), ("b.rs", "fn b() {}")])
+        );
+        assert_ne!(
+            base,
+            digest(&[("a.rs", "fn a() {}"), ("b.rs", "fn c() {}")])
+        );
+        assert_ne!(
+            base,
+            digest(&[("a.rs", "fn a() {}"), ("c.rs", "fn b() {}")])
+        );
+        assert_ne!(base, digest(&[("a.rs", "fn a() {}")]));
+    }
+
+    #[test]
+    fn short_hashes_keep_the_algorithm() {
+        assert_eq!(
+            short_hash("blake3:1f2a3b4c5d6e7f8091a2"),
+            "blake3:1f2a3b4c5d6e"
+        );
+        assert_eq!(short_hash("sha256:abc"), "sha256:abc");
+    }
+}
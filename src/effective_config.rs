@@ -0,0 +1,203 @@
+//! Printing the effective configuration of a run (`--print-config`) as TOML that can be
+//! used as a config file as is.
+
+use crate::{Config, json};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Where the value of an option came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// The built-in default.
+    Default,
+    /// The config file, or the profile selected in it.
+    ConfigFile,
+    /// A `C2P_` environment variable.
+    Env,
+    /// A command-line flag.
+    Cli,
+    /// Derived from other options, e.g. the format from the extension of `--output`.
+    Inferred,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConfigSource::Default => "default",
+            ConfigSource::ConfigFile => "config",
+            ConfigSource::Env => "env",
+            ConfigSource::Cli => "cli",
+            ConfigSource::Inferred => "inferred",
+        })
+    }
+}
+
+/// The last word of the option names whose values are never printed.
+const SECRET_WORDS: &[&str] = &["token", "secret", "password", "credentials"];
+
+/// Checks whether an option holds a secret, by its name, e.g. `github_token`.
+fn is_secret(key: &str) -> bool {
+    let last = key.rsplit('_').next().unwrap_or(key);
+    SECRET_WORDS.contains(&last)
+}
+
+/// Renders a configuration as a TOML config file.
+///
+/// Options are written in the order of the `Config` fields, with tables such as
+/// `frontmatter_fields` at the end. Options that are not set have no TOML spelling and are
+/// left out, and options holding secrets are written as a comment without their value.
+///
+/// # Arguments
+/// * `config` - The configuration to print.
+/// * `sources` - Where each option came from, by field name, to note after each option;
+///   options missing from it came from the defaults. `None` writes no notes.
+///
+/// # Returns
+/// * `String` - The TOML document.
+pub fn config_to_toml(config: &Config, sources: Option<&BTreeMap<String, ConfigSource>>) -> String {
+    let json::Value::Object(fields) = json::to_value(config).expect("a Config always serializes")
+    else {
+        unreachable!("a Config serializes as an object");
+    };
+    let note = |key: &str| match sources {
+        Some(sources) => format!(
+            "  # {}",
+            sources.get(key).copied().unwrap_or(ConfigSource::Default)
+        ),
+        None => String::new(),
+    };
+
+    let mut toml = String::new();
+    let mut tables = Vec::new();
+    for (key, value) in &fields {
+        match value {
+            json::Value::Null => {
+                if sources.is_some() {
+                    toml.push_str(&format!("# {} is not set{}\n", key, note(key)));
+                }
+            }
+            _ if is_secret(key) => {
+                toml.push_str(&format!("# {} = (hidden){}\n", key, note(key)));
+            }
+            json::Value::Object(entries) => {
+                if !entries.is_empty() || sources.is_some() {
+                    tables.push((key, entries));
+                }
+            }
+            value => {
+                toml.push_str(&format!("{} = {}{}\n", key, inline(value), note(key)));
+            }
+        }
+    }
+    for (key, entries) in tables {
+        toml.push_str(&format!("\n[{}]{}\n", key, note(key)));
+        for (name, value) in entries {
+            toml.push_str(&format!("{} = {}\n", toml_key(name), inline(value)));
+        }
+    }
+    toml
+}
+
+/// Writes a value on one line: arrays and tables inline.
+fn inline(value: &json::Value) -> String {
+    match value {
+        json::Value::Null => "\"\"".to_string(),
+        json::Value::Bool(value) => value.to_string(),
+        json::Value::Number(number) => number.clone(),
+        json::Value::String(text) => toml_string(text),
+        json::Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(inline).collect();
+            format!("[{}]", items.join(", "))
+        }
+        json::Value::Object(entries) => {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(name, value)| format!("{} = {}", toml_key(name), inline(value)))
+                .collect();
+            if entries.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", entries.join(", "))
+            }
+        }
+    }
+}
+
+/// Writes a key bare when TOML allows it, and quoted otherwise.
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        toml_string(key)
+    }
+}
+
+/// Writes a TOML basic string, escaping quotes, backslashes, and control characters.
+fn toml_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Format, toml};
+    use serde::Deserialize;
+
+    #[test]
+    fn printed_configs_load_back() {
+        let config = Config::builder()
+            .directories(["src", "crates/core"])
+            .include(["rs", "toml"])
+            .format(Format::ClaudeXml)
+            .preamble("Review \"this\":\n\tcarefully \\ now")
+            .frontmatter_field("project", "billing")
+            .frontmatter_field("has space", "x")
+            .build();
+        let printed = config_to_toml(&config, None);
+        assert!(!printed.contains('#'));
+        let again =
+            Config::deserialize(toml::Value::Table(toml::parse(&printed).unwrap())).unwrap();
+        assert_eq!(
+            json::to_value(&again).unwrap(),
+            json::to_value(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn verbose_configs_note_each_source() {
+        let config = Config::builder().line_numbers(true).build();
+        let sources = BTreeMap::from([("line_numbers".to_string(), ConfigSource::Cli)]);
+        let printed = config_to_toml(&config, Some(&sources));
+        assert!(printed.contains("line_numbers = true  # cli\n"));
+        assert!(printed.contains("format = \"console\"  # default\n"));
+        assert!(printed.contains("# output is not set  # default\n"));
+        assert!(printed.contains("\n[frontmatter_fields]  # default\n"));
+        assert!(toml::parse(&printed).is_ok());
+    }
+
+    #[test]
+    fn secrets_are_never_printed() {
+        assert!(is_secret("github_token"));
+        assert!(is_secret("password"));
+        assert!(!is_secret("tokenizer"));
+        assert!(!is_secret("fit_budget"));
+    }
+}
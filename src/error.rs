@@ -0,0 +1,195 @@
+//! The error type of the library API, whose variants name the failures a caller may want to
+//! handle; everything else keeps the message chain of the `anyhow` error it came from.
+
+use crate::{NoFilesMatched, OutputLimitExceeded};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An error from [`run`](crate::run), [`run_to_string`](crate::run_to_string),
+/// [`collect_files`](crate::collect_files), and the other entry points of the library.
+///
+/// `Display` prints the outermost message, and the alternate form (`{:#}`) the whole chain
+/// of causes, as `anyhow` does.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// An input directory or file does not exist.
+    DirectoryNotFound(PathBuf),
+    /// An output file cannot be created.
+    OutputCreate {
+        /// The output file.
+        path: PathBuf,
+        /// The error from creating it.
+        source: io::Error,
+    },
+    /// A file cannot be read.
+    Io {
+        /// The file.
+        path: PathBuf,
+        /// The error from reading it.
+        source: io::Error,
+    },
+    /// No file matched the filters and `fail_if_empty` is set.
+    NoFilesMatched(NoFilesMatched),
+    /// The bundle reached `max_total_size`.
+    OutputLimitExceeded(OutputLimitExceeded),
+    /// A git operation failed.
+    #[cfg(feature = "git")]
+    Git {
+        /// What was being done, e.g. `Failed to resolve HEAD`; empty if nothing was said.
+        context: String,
+        /// The error from git.
+        source: git2::Error,
+    },
+    /// Any other failure.
+    Other(anyhow::Error),
+}
+
+impl Error {
+    /// Converts the error of reading one file, naming the file if it failed with an I/O error.
+    ///
+    /// # Arguments
+    /// * `err` - The error.
+    /// * `path` - The file that was read.
+    ///
+    /// # Returns
+    /// * `Error` - [`Error::Io`] for a plain I/O error, or the conversion of `err` otherwise.
+    pub(crate) fn reading(err: anyhow::Error, path: &Path) -> Self {
+        match err.downcast::<io::Error>() {
+            Ok(source) => Error::Io {
+                path: path.to_path_buf(),
+                source,
+            },
+            Err(err) => Error::from(err),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (message, source): (String, Option<&dyn std::error::Error>) = match self {
+            Error::DirectoryNotFound(path) => (format!("{} does not exist", path.display()), None),
+            Error::OutputCreate { path, source } => (
+                format!("Failed to create output file: {}", path.display()),
+                Some(source),
+            ),
+            Error::Io { path, source } => {
+                (format!("Failed to read {}", path.display()), Some(source))
+            }
+            Error::NoFilesMatched(err) => return err.fmt(f),
+            Error::OutputLimitExceeded(err) => return err.fmt(f),
+            #[cfg(feature = "git")]
+            Error::Git { context, source } if context.is_empty() => return source.fmt(f),
+            #[cfg(feature = "git")]
+            Error::Git { context, source } => (context.clone(), Some(source)),
+            Error::Other(err) if f.alternate() => return write!(f, "{:#}", err),
+            Error::Other(err) => return err.fmt(f),
+        };
+        f.write_str(&message)?;
+        if let Some(source) = source.filter(|_| f.alternate()) {
+            write!(f, ": {}", source)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::DirectoryNotFound(_)
+            | Error::NoFilesMatched(_)
+            | Error::OutputLimitExceeded(_) => None,
+            Error::OutputCreate { source, .. } | Error::Io { source, .. } => Some(source),
+            #[cfg(feature = "git")]
+            Error::Git { context, source } if !context.is_empty() => Some(source),
+            #[cfg(feature = "git")]
+            Error::Git { source, .. } => source.source(),
+            Error::Other(err) => err.source(),
+        }
+    }
+}
+
+/// Finds the failure behind an `anyhow` error.
+///
+/// An [`Error`], [`NoFilesMatched`], or [`OutputLimitExceeded`] is taken as it is only when
+/// no context was added to it, so no message is lost; a git error keeps the messages around
+/// it as its context.
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        // The outermost error: the last context added, or the error itself if there is none.
+        let outer = err
+            .chain()
+            .next()
+            .expect("an error is the first of its chain");
+        if outer.is::<Error>() {
+            return err.downcast().expect("checked above");
+        }
+        if outer.is::<NoFilesMatched>() {
+            return Error::NoFilesMatched(err.downcast().expect("checked above"));
+        }
+        if outer.is::<OutputLimitExceeded>() {
+            return Error::OutputLimitExceeded(err.downcast().expect("checked above"));
+        }
+        #[cfg(feature = "git")]
+        if err.is::<git2::Error>() {
+            let context = err
+                .chain()
+                .take_while(|cause| !cause.is::<git2::Error>())
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(": ");
+            let source = err.downcast().expect("checked above");
+            return Error::Git { context, source };
+        }
+        Error::Other(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_errors_are_found_unless_wrapped() {
+        let missing = || anyhow::Error::new(Error::DirectoryNotFound(PathBuf::from("nope")));
+        assert!(matches!(
+            Error::from(missing()),
+            Error::DirectoryNotFound(_)
+        ));
+        let wrapped = Error::from(missing().context("Failed to walk"));
+        assert!(matches!(wrapped, Error::Other(_)));
+        assert_eq!(
+            format!("{:#}", wrapped),
+            "Failed to walk: nope does not exist"
+        );
+    }
+
+    #[test]
+    fn the_alternate_form_shows_the_source() {
+        let err = Error::OutputCreate {
+            path: PathBuf::from("out/bundle.md"),
+            source: io::Error::new(io::ErrorKind::PermissionDenied, "permission denied"),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Failed to create output file: out/bundle.md"
+        );
+        assert_eq!(
+            format!("{:#}", err),
+            "Failed to create output file: out/bundle.md: permission denied"
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn git_errors_keep_their_context() {
+        let err = anyhow::Error::new(git2::Error::from_str("reference not found"))
+            .context("Failed to resolve HEAD");
+        let Error::Git { context, source } = Error::from(err) else {
+            panic!("not a git error");
+        };
+        assert_eq!(context, "Failed to resolve HEAD");
+        assert_eq!(source.message(), "reference not found");
+    }
+}
@@ -0,0 +1,170 @@
+//! The `--estimate` report: token counts of the files a run would bundle, before writing it.
+
+use crate::formatter;
+use crate::hooks::{FileAction, Hooks};
+use crate::{Config, FileEntry, Tokenizer};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Well-known context window sizes, in tokens, checked by [`Estimate::exceeded_windows`].
+pub const CONTEXT_WINDOWS: &[(usize, &str)] =
+    &[(128_000, "128k"), (200_000, "200k"), (1_000_000, "1M")];
+
+/// How many of the largest files the report lists.
+const LARGEST_FILES: usize = 10;
+
+/// The estimated size of one file in the bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEstimate {
+    /// The path shown in the output.
+    pub path: PathBuf,
+    /// The path relative to the bundled directory, as listed in a `files_from` file.
+    pub relative_path: PathBuf,
+    /// The number of bytes written for the file, after transformations.
+    pub bytes: usize,
+    /// The estimated number of tokens written for the file.
+    pub tokens: usize,
+}
+
+/// The estimated size of a bundle, returned by [`estimate`](crate::estimate).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Estimate {
+    /// The files that pass the filters, in output order.
+    pub files: Vec<FileEstimate>,
+    /// The total number of bytes across the files, the preamble, and the postamble.
+    pub total_bytes: usize,
+    /// The estimated number of tokens across the files, the preamble, and the postamble.
+    pub total_tokens: usize,
+    /// The tokenizer that counted the tokens.
+    pub tokenizer: Tokenizer,
+}
+
+impl Estimate {
+    /// Returns the context windows from [`CONTEXT_WINDOWS`] that the bundle would not fit in.
+    pub fn exceeded_windows(&self) -> Vec<&'static str> {
+        CONTEXT_WINDOWS
+            .iter()
+            .filter(|(tokens, _)| self.total_tokens > *tokens)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    /// Counts the bytes and tokens of a preamble or postamble.
+    pub(crate) fn record_framing_text(&mut self, text: &str) {
+        self.total_bytes += text.len();
+        self.total_tokens += self.tokenizer.count(text);
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut largest: Vec<&FileEstimate> = self.files.iter().collect();
+        largest.sort_by(|a, b| b.tokens.cmp(&a.tokens).then_with(|| a.path.cmp(&b.path)));
+        let shown = largest.len().min(LARGEST_FILES);
+
+        writeln!(f, "{:<48}{:>12}{:>12}", "Largest files", "Tokens", "Bytes")?;
+        for file in &largest[..shown] {
+            writeln!(
+                f,
+                "  {:<46}{:>12}{:>12}",
+                file.path.display().to_string(),
+                file.tokens,
+                file.bytes
+            )?;
+        }
+        if largest.len() > shown {
+            let rest = &largest[shown..];
+            writeln!(
+                f,
+                "  {:<46}{:>12}{:>12}",
+                format!("({} more files)", rest.len()),
+                rest.iter().map(|file| file.tokens).sum::<usize>(),
+                rest.iter().map(|file| file.bytes).sum::<usize>()
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<48}{:>12}{:>12}",
+            format!("Total ({} files)", self.files.len()),
+            self.total_tokens,
+            self.total_bytes
+        )
+    }
+}
+
+/// Hooks that record every file a run would write, used to build an [`Estimate`].
+#[derive(Default)]
+pub(crate) struct EstimateHooks {
+    pub(crate) estimate: Estimate,
+    /// The configuration of a `headers_only` run, whose files are counted by their headers.
+    pub(crate) headers: Option<Config>,
+}
+
+impl Hooks for EstimateHooks {
+    fn on_file(&mut self, entry: &FileEntry) -> FileAction {
+        let (bytes, tokens) = match &self.headers {
+            Some(config) => {
+                let header = formatter::header_text(entry, config);
+                (header.len(), self.estimate.tokenizer.count(&header))
+            }
+            None => (
+                entry.segments().iter().map(|s| s.text().len()).sum(),
+                entry.tokens,
+            ),
+        };
+        self.estimate.total_bytes += bytes;
+        self.estimate.total_tokens += tokens;
+        self.estimate.files.push(FileEstimate {
+            path: entry.display_path().to_path_buf(),
+            relative_path: entry.relative_path.clone(),
+            bytes,
+            tokens,
+        });
+        FileAction::Include
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, tokens: usize) -> FileEstimate {
+        FileEstimate {
+            path: PathBuf::from(path),
+            relative_path: PathBuf::from(path),
+            bytes: tokens * 4,
+            tokens,
+        }
+    }
+
+    #[test]
+    fn exceeded_windows_grow_with_the_total() {
+        let mut estimate = Estimate::default();
+        assert!(estimate.exceeded_windows().is_empty());
+        estimate.total_tokens = 150_000;
+        assert_eq!(estimate.exceeded_windows(), ["128k"]);
+        estimate.total_tokens = 1_500_000;
+        assert_eq!(estimate.exceeded_windows(), ["128k", "200k", "1M"]);
+    }
+
+    #[test]
+    fn table_lists_the_largest_files_first() {
+        let mut files: Vec<FileEstimate> = (1..=12).map(|i| file(&format!("f{}", i), i)).collect();
+        files.push(file("big.rs", 100));
+        let estimate = Estimate {
+            total_tokens: files.iter().map(|f| f.tokens).sum(),
+            total_bytes: files.iter().map(|f| f.bytes).sum(),
+            files,
+            ..Estimate::default()
+        };
+        let table = estimate.to_string();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 13);
+        assert!(lines[1].starts_with("  big.rs ") && lines[1].ends_with("100         400"));
+        assert!(lines[2].starts_with("  f12 "));
+        assert!(lines[11].starts_with("  (3 more files) ") && lines[11].ends_with("6          24"));
+        assert!(
+            lines[12].starts_with("Total (13 files) ") && lines[12].ends_with("178         712")
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Expansion of `~`, `~user`, `$VAR`, `${VAR}`, and `%VAR%` in path options, for paths that
+//! reach the tool without a shell to expand them: config files, aliases, or Windows.
+
+use crate::{Config, RelativeTo};
+use std::env;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Expands a leading `~` or `~user` and the environment variables in a path.
+///
+/// Absolute paths are returned as they are, as are paths that are not valid UTF-8. A
+/// variable that is not set expands to nothing, with a warning; a `~user` whose home
+/// cannot be found is kept.
+///
+/// # Arguments
+/// * `path` - The path as given.
+///
+/// # Returns
+/// * `PathBuf` - The expanded path.
+pub fn expand_path(path: &Path) -> PathBuf {
+    expand_path_with(path, &|name| env::var(name).ok())
+}
+
+/// Expands every path option of a configuration with [`expand_path`]: the directories,
+/// outputs, path lists, and the files read, as well as the `output_template`.
+///
+/// # Arguments
+/// * `config` - The configuration to expand in place.
+pub fn expand_config_paths(config: &mut Config) {
+    expand_config_paths_with(config, &|name| env::var(name).ok());
+}
+
+/// Expands the path options of a configuration, looking variables up with `lookup`.
+fn expand_config_paths_with(config: &mut Config, lookup: &dyn Fn(&str) -> Option<String>) {
+    let expand = |path: &mut PathBuf| *path = expand_path_with(path, lookup);
+    config.directories.iter_mut().for_each(expand);
+    config.also_output.iter_mut().for_each(expand);
+    config.include_paths.iter_mut().for_each(expand);
+    config.entry.iter_mut().for_each(expand);
+    config.exclude_paths.iter_mut().for_each(expand);
+    for path in [
+        &mut config.output,
+        &mut config.output_dir,
+        &mut config.stats_output,
+        &mut config.manifest,
+        &mut config.report_json,
+        &mut config.ignore_file,
+        &mut config.include_from,
+        &mut config.exclude_from,
+        &mut config.order_file,
+        &mut config.files_from,
+        &mut config.cache,
+    ] {
+        path.iter_mut().for_each(expand);
+    }
+    if let Some(RelativeTo::Path(path)) = &mut config.relative_to {
+        expand(path);
+    }
+    if let Some(template) = &mut config.output_template {
+        *template = expand_path_with(Path::new(template.as_str()), lookup)
+            .to_string_lossy()
+            .into_owned();
+    }
+}
+
+/// Expands a path, looking variables up with `lookup`.
+fn expand_path_with(path: &Path, lookup: &dyn Fn(&str) -> Option<String>) -> PathBuf {
+    let Some(text) = path.to_str().filter(|_| !path.is_absolute()) else {
+        return path.to_path_buf();
+    };
+    let (home, rest) = match text.strip_prefix('~') {
+        Some(rest) => {
+            let end = rest.find(['/', '\\']).unwrap_or(rest.len());
+            let (user, rest) = rest.split_at(end);
+            let home = if user.is_empty() {
+                lookup("HOME").or_else(|| lookup("USERPROFILE"))
+            } else {
+                user_home(user)
+            };
+            match home {
+                Some(home) => (home, rest),
+                None => {
+                    warn!("Cannot find the home directory of ~{}; keeping it", user);
+                    (String::new(), text)
+                }
+            }
+        }
+        None => (String::new(), text),
+    };
+    PathBuf::from(home + &expand_vars(rest, lookup))
+}
+
+/// Replaces `$VAR`, `${VAR}`, and `%VAR%` with the values of the variables. A `$` or `%`
+/// that starts no reference is kept.
+fn expand_vars(text: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    let is_name = |name: &str| {
+        name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find(['$', '%']) {
+        expanded.push_str(&rest[..at]);
+        let sigil = &rest[at..=at];
+        let after = &rest[at + 1..];
+        // The name and the length of the reference after the sigil.
+        let reference = match (sigil, after.strip_prefix('{')) {
+            ("$", Some(braced)) => braced.find('}').map(|end| (&braced[..end], end + 2)),
+            ("$", None) => {
+                let end = after
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(after.len());
+                Some((&after[..end], end))
+            }
+            _ => after.find('%').map(|end| (&after[..end], end + 1)),
+        };
+        match reference.filter(|(name, _)| is_name(name)) {
+            Some((name, length)) => {
+                match lookup(name) {
+                    Some(value) => expanded.push_str(&value),
+                    None => warn!("{} is not set; expanding it to nothing", name),
+                }
+                rest = &after[length..];
+            }
+            None => {
+                expanded.push_str(sigil);
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Finds the home directory of a user from the password database.
+#[cfg(unix)]
+fn user_home(user: &str) -> Option<String> {
+    use std::ffi::{CStr, CString};
+
+    let name = CString::new(user).ok()?;
+    let mut buffer = vec![0 as libc::c_char; 16 * 1024];
+    // SAFETY: `passwd` is plain data, for which all zeroes is a valid value.
+    let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut found = std::ptr::null_mut();
+    // SAFETY: every pointer is valid for the call, and the buffer length is its own.
+    let status = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut entry,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut found,
+        )
+    };
+    if status != 0 || found.is_null() || entry.pw_dir.is_null() {
+        return None;
+    }
+    // SAFETY: a found entry's `pw_dir` is a C string inside `buffer`.
+    let home = unsafe { CStr::from_ptr(entry.pw_dir) };
+    home.to_str().ok().map(str::to_string)
+}
+
+/// Home directories of other users are only known on Unix.
+#[cfg(not(unix))]
+fn user_home(_user: &str) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/ada".to_string()),
+            "PROJECT" => Some("api".to_string()),
+            _ => None,
+        }
+    }
+
+    fn expand(path: &str) -> PathBuf {
+        expand_path_with(Path::new(path), &lookup)
+    }
+
+    #[test]
+    fn tildes_expand_to_home_directories() {
+        assert_eq!(expand("~"), Path::new("/home/ada"));
+        assert_eq!(
+            expand("~/projects/api"),
+            Path::new("/home/ada/projects/api")
+        );
+        assert_eq!(
+            expand("~/prompts/api.md"),
+            Path::new("/home/ada/prompts/api.md")
+        );
+        assert_eq!(expand("a/~/b"), Path::new("a/~/b"));
+        assert_eq!(
+            expand("~no-such-user-c2p/x"),
+            Path::new("~no-such-user-c2p/x")
+        );
+        #[cfg(target_os = "linux")]
+        assert_eq!(expand("~root/src"), Path::new("/root/src"));
+    }
+
+    #[test]
+    fn variables_expand_in_every_spelling() {
+        assert_eq!(
+            expand("$HOME/projects/$PROJECT"),
+            Path::new("/home/ada/projects/api")
+        );
+        assert_eq!(expand("${PROJECT}_v2/src"), Path::new("api_v2/src"));
+        assert_eq!(expand("out/%PROJECT%.md"), Path::new("out/api.md"));
+        assert_eq!(
+            expand("~/prompts/$PROJECT.md"),
+            Path::new("/home/ada/prompts/api.md")
+        );
+        // Unset variables expand to nothing, and sigils that start no reference are kept.
+        assert_eq!(expand("out/$UNSET/a.md"), Path::new("out//a.md"));
+        assert_eq!(expand("100%/$/${/a%b"), Path::new("100%/$/${/a%b"));
+        assert_eq!(expand("$1/x"), Path::new("$1/x"));
+    }
+
+    #[test]
+    fn input_and_output_paths_are_expanded() {
+        let mut config = Config {
+            directories: vec![PathBuf::from("~/projects/$PROJECT")],
+            output: Some(PathBuf::from("~/prompts/%PROJECT%.md")),
+            output_template: Some("~/prompts/{name}-{n}.{ext}".to_string()),
+            relative_to: Some(RelativeTo::Path(PathBuf::from("$HOME/projects"))),
+            ..Config::default()
+        };
+        expand_config_paths_with(&mut config, &lookup);
+        assert_eq!(config.directories, [Path::new("/home/ada/projects/api")]);
+        assert_eq!(
+            config.output.unwrap(),
+            Path::new("/home/ada/prompts/api.md")
+        );
+        assert_eq!(
+            config.output_template.unwrap(),
+            "/home/ada/prompts/{name}-{n}.{ext}"
+        );
+        assert_eq!(
+            config.relative_to,
+            Some(RelativeTo::Path(PathBuf::from("/home/ada/projects")))
+        );
+    }
+
+    #[test]
+    fn absolute_paths_pass_through() {
+        assert_eq!(expand("/srv/$PROJECT/~"), Path::new("/srv/$PROJECT/~"));
+    }
+}
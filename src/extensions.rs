@@ -0,0 +1,234 @@
+//! The `--list-extensions` report: which extensions a directory holds, before choosing filters.
+
+use crate::languages::Language;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// How many leading bytes of a file are inspected to decide whether it looks binary.
+const SNIFF_LEN: usize = 8192;
+
+/// The files of one extension, in an [`ExtensionListing`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionCount {
+    /// The lowercase extension, without the dot.
+    pub extension: String,
+    /// Number of files with the extension.
+    pub files: usize,
+    /// Total size of the files in bytes.
+    pub bytes: u64,
+}
+
+/// The extensions of the files that pass the walk filters, returned by
+/// [`list_extensions`](crate::list_extensions).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtensionListing {
+    /// The extensions, the largest total size first.
+    pub extensions: Vec<ExtensionCount>,
+    /// Number of files without an extension, which are not listed.
+    pub extensionless: usize,
+    /// Number of files, with or without an extension, whose content looks binary.
+    pub binary: usize,
+}
+
+impl ExtensionListing {
+    /// Builds the listing from each file's path, size, and whether it looks binary.
+    pub(crate) fn new(files: impl IntoIterator<Item = (impl AsRef<Path>, u64, bool)>) -> Self {
+        let mut listing = ExtensionListing::default();
+        let mut counts: HashMap<String, ExtensionCount> = HashMap::new();
+        for (path, bytes, binary) in files {
+            listing.binary += usize::from(binary);
+            let Some(extension) = path.as_ref().extension().and_then(|s| s.to_str()) else {
+                listing.extensionless += 1;
+                continue;
+            };
+            let extension = extension.to_lowercase();
+            let count = counts
+                .entry(extension.clone())
+                .or_insert_with(|| ExtensionCount {
+                    extension,
+                    files: 0,
+                    bytes: 0,
+                });
+            count.files += 1;
+            count.bytes += bytes;
+        }
+        listing.extensions = counts.into_values().collect();
+        listing.extensions.sort_by(|a, b| {
+            b.bytes
+                .cmp(&a.bytes)
+                .then_with(|| a.extension.cmp(&b.extension))
+        });
+        listing
+    }
+}
+
+/// A language found in an [`ExtensionListing`], with the extensions of its files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguageCount {
+    /// The language.
+    pub language: &'static Language,
+    /// The extensions of its files found, the largest total size first.
+    pub extensions: Vec<String>,
+    /// Number of files in the language.
+    pub files: usize,
+    /// Total size of the files in bytes.
+    pub bytes: u64,
+}
+
+impl ExtensionListing {
+    /// Groups the listed extensions by their language, leaving out extensions of no known
+    /// language.
+    ///
+    /// # Returns
+    /// * `Vec<LanguageCount>` - The languages, the largest total size first.
+    pub fn languages(&self) -> Vec<LanguageCount> {
+        let mut languages: Vec<LanguageCount> = Vec::new();
+        for count in &self.extensions {
+            let Some(language) = Language::by_extension(&count.extension) else {
+                continue;
+            };
+            match languages
+                .iter_mut()
+                .find(|found| found.language == language)
+            {
+                Some(found) => {
+                    found.extensions.push(count.extension.clone());
+                    found.files += count.files;
+                    found.bytes += count.bytes;
+                }
+                None => languages.push(LanguageCount {
+                    language,
+                    extensions: vec![count.extension.clone()],
+                    files: count.files,
+                    bytes: count.bytes,
+                }),
+            }
+        }
+        languages.sort_by(|a, b| {
+            b.bytes
+                .cmp(&a.bytes)
+                .then_with(|| a.language.name.cmp(b.language.name))
+        });
+        languages
+    }
+}
+
+impl fmt::Display for ExtensionListing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<20}{:>10}{:>14}", "Extension", "Files", "Bytes")?;
+        for count in &self.extensions {
+            writeln!(
+                f,
+                "  {:<18}{:>10}{:>14}",
+                format!(".{}", count.extension),
+                count.files,
+                count.bytes
+            )?;
+        }
+        writeln!(f, "{} files without an extension", self.extensionless)?;
+        writeln!(f, "{} files that look binary", self.binary)
+    }
+}
+
+/// Checks whether a file looks binary: its first bytes contain a NUL byte or are not UTF-8.
+///
+/// # Arguments
+/// * `path` - The path of the file.
+///
+/// # Returns
+/// * `io::Result<bool>` - `true` if the file looks binary, or an error if it cannot be read.
+pub(crate) fn looks_binary(path: &Path) -> io::Result<bool> {
+    let mut buffer = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut buffer)?;
+    Ok(is_binary(&buffer))
+}
+
+/// Checks whether content looks binary, judging by its first bytes as [`looks_binary`] does.
+///
+/// # Arguments
+/// * `content` - The content of a file.
+///
+/// # Returns
+/// * `bool` - `true` if the content looks binary.
+pub(crate) fn is_binary(content: &[u8]) -> bool {
+    let buffer = &content[..content.len().min(SNIFF_LEN)];
+    // A multi-byte character cut off at the end of the buffer is not a sign of binary content.
+    let invalid = std::str::from_utf8(buffer).is_err_and(|err| err.error_len().is_some());
+    invalid || buffer.contains(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_are_sorted_by_size() {
+        let listing = ExtensionListing::new([
+            ("src/main.rs", 100, false),
+            ("src/lib.RS", 300, false),
+            ("logo.png", 350, true),
+            ("Makefile", 10, false),
+            ("notes.txt", 350, false),
+        ]);
+        let rows: Vec<(&str, usize, u64)> = listing
+            .extensions
+            .iter()
+            .map(|count| (count.extension.as_str(), count.files, count.bytes))
+            .collect();
+        assert_eq!(rows, [("rs", 2, 400), ("png", 1, 350), ("txt", 1, 350)]);
+        assert_eq!((listing.extensionless, listing.binary), (1, 1));
+    }
+
+    #[test]
+    fn languages_group_their_extensions() {
+        let listing = ExtensionListing::new([
+            ("a.ts", 100, false),
+            ("b.tsx", 50, false),
+            ("c.py", 120, false),
+            ("logo.png", 500, true),
+        ]);
+        let languages: Vec<(&str, Vec<String>, usize, u64)> = listing
+            .languages()
+            .into_iter()
+            .map(|count| {
+                (
+                    count.language.name,
+                    count.extensions,
+                    count.files,
+                    count.bytes,
+                )
+            })
+            .collect();
+        assert_eq!(
+            languages,
+            [
+                (
+                    "typescript",
+                    vec!["ts".to_string(), "tsx".to_string()],
+                    2,
+                    150
+                ),
+                ("python", vec!["py".to_string()], 1, 120),
+            ]
+        );
+    }
+
+    #[test]
+    fn binary_sniffing() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content, binary) in [
+            ("text.txt", "héllo\n".as_bytes(), false),
+            ("nul.bin", b"ab\0cd".as_slice(), true),
+            ("latin1.txt", b"caf\xe9 au lait".as_slice(), true),
+        ] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            assert_eq!(looks_binary(&path).unwrap(), binary, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! The path-based filters of a run in one type, so the walk, the other sources, and library
+//! users decide paths the same way.
+
+use crate::filter_rules::FilterRules;
+use crate::path_overrides::{PathOverrides, PathRule};
+use crate::{
+    Config, SkipReason, VCS_DIRECTORIES, exclude_dir_set, is_hidden_name, keep_hidden_set,
+    lockfiles, sensitive, test_files,
+};
+use anyhow::{Result, bail};
+use globset::GlobSet;
+use std::path::Path;
+
+/// What the filters say about a file, from its path alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FilterDecision {
+    /// The path passes every filter; the file is bundled unless its content rules it out,
+    /// e.g. as binary, too large, or minified.
+    Include,
+    /// The path is in `include_paths`: the file is bundled whatever the other filters say,
+    /// unless it is binary.
+    Forced,
+    /// The path is left out, for this reason.
+    Skip(SkipReason),
+}
+
+impl FilterDecision {
+    /// Checks whether the file is bundled as far as its path is concerned.
+    pub fn is_included(self) -> bool {
+        !matches!(self, FilterDecision::Skip(_))
+    }
+}
+
+/// The filters of a configuration that look at paths: hidden entries, version control and
+/// excluded directories, `include_paths` and `exclude_paths`, lockfiles, test files, and the
+/// `include` and `exclude` lists.
+///
+/// Paths are relative to the input directory. Nothing is read from disk, so the filters
+/// that need files are not applied: ignore files, `.gitattributes`, modification times, and
+/// the content checks such as binary detection, size limits, and the opt-in marker, which
+/// can still bring back a file the `include` list leaves out.
+///
+/// # Examples
+/// ```
+/// use codebase_to_prompt::{Config, FileFilter, FilterDecision, SkipReason};
+/// use std::path::Path;
+///
+/// let config = Config::builder().include(["rs"]).ignore_hidden(true).build();
+/// let filter = FileFilter::new(&config).unwrap();
+/// assert!(!filter.matches_dir(Path::new(".git")));
+/// assert_eq!(filter.matches_file(Path::new("src/main.rs")), FilterDecision::Include);
+/// assert_eq!(
+///     filter.matches_file(Path::new("README.md")),
+///     FilterDecision::Skip(SkipReason::Filtered)
+/// );
+/// ```
+#[derive(Debug)]
+pub struct FileFilter {
+    keep_hidden: GlobSet,
+    exclude_dirs: GlobSet,
+    ignore_hidden: bool,
+    include_vcs_dir: bool,
+    overrides: PathOverrides,
+    include_sensitive: bool,
+    /// The lockfile names, or `None` with `include_lockfiles`.
+    lockfiles: Option<Vec<String>>,
+    /// The test patterns and whether only tests are kept, or `None` without `no_tests` and
+    /// `only_tests`.
+    tests: Option<(GlobSet, bool)>,
+    rules: FilterRules,
+}
+
+impl FileFilter {
+    /// Compiles the path filters of a configuration.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<FileFilter>` - The filters, or an error if a pattern is invalid, a language
+    ///   is unknown, or `no_tests` and `only_tests` are both set.
+    pub fn new(config: &Config) -> Result<FileFilter> {
+        let tests = if config.no_tests || config.only_tests {
+            if config.no_tests && config.only_tests {
+                bail!("`no_tests` and `only_tests` cannot both be set");
+            }
+            Some((
+                test_files::test_set(&config.tests_patterns)?,
+                config.only_tests,
+            ))
+        } else {
+            None
+        };
+        Ok(FileFilter {
+            keep_hidden: keep_hidden_set(config)?,
+            exclude_dirs: exclude_dir_set(config)?,
+            ignore_hidden: config.ignore_hidden,
+            include_vcs_dir: config.include_vcs_dir,
+            overrides: PathOverrides::new(config),
+            include_sensitive: config.include_sensitive,
+            lockfiles: (!config.include_lockfiles).then(|| config.lockfiles.clone()),
+            tests,
+            rules: FilterRules::new(config)?,
+        })
+    }
+
+    /// Checks whether a directory is walked into.
+    ///
+    /// # Arguments
+    /// * `relative_path` - The path of the directory relative to the input directory.
+    ///
+    /// # Returns
+    /// * `bool` - `false` if the directory or one above it is hidden, version control
+    ///   metadata, or in `exclude_dirs`.
+    pub fn matches_dir(&self, relative_path: &Path) -> bool {
+        self.path_exclusion(relative_path, true).is_none()
+    }
+
+    /// Decides a file by its path, with the precedence of a run: `exclude_paths` and
+    /// `include_paths` first, then the directories above it and its name, files that may hold
+    /// secrets, lockfiles, test files, and the `include` and `exclude` lists. A file is only
+    /// skipped as one that may hold secrets if `include_paths` or the `include` and `exclude`
+    /// lists would keep it; otherwise the lists decide it.
+    ///
+    /// # Arguments
+    /// * `relative_path` - The path of the file relative to the input directory.
+    ///
+    /// # Returns
+    /// * `FilterDecision` - Whether the file is bundled, forced in, or skipped and why.
+    pub fn matches_file(&self, relative_path: &Path) -> FilterDecision {
+        let sensitive = !self.include_sensitive && sensitive::is_sensitive(relative_path);
+        match self.overrides.rule(relative_path) {
+            PathRule::Exclude => return FilterDecision::Skip(SkipReason::Filtered),
+            PathRule::Include if sensitive => return FilterDecision::Skip(SkipReason::Sensitive),
+            PathRule::Include => return FilterDecision::Forced,
+            PathRule::Filters => {}
+        }
+        let reason = self
+            .path_exclusion(relative_path, false)
+            .or_else(|| {
+                (sensitive && self.rules_allow(relative_path)).then_some(SkipReason::Sensitive)
+            })
+            .or_else(|| {
+                self.is_lockfile(relative_path)
+                    .then_some(SkipReason::Lockfile)
+            })
+            .or_else(|| {
+                self.is_test_excluded(relative_path)
+                    .then_some(SkipReason::Tests)
+            })
+            .or_else(|| (!self.rules_allow(relative_path)).then_some(SkipReason::Filtered));
+        match reason {
+            Some(reason) => FilterDecision::Skip(reason),
+            None => FilterDecision::Include,
+        }
+    }
+
+    /// Decides one entry of a walk by its own name and path, its parents having passed.
+    ///
+    /// # Arguments
+    /// * `relative_path` - The path of the entry relative to the input directory.
+    /// * `is_dir` - Whether the entry is a directory.
+    ///
+    /// # Returns
+    /// * `Option<SkipReason>` - Why the entry is skipped, or `None` if it is walked.
+    pub(crate) fn entry_exclusion(&self, relative_path: &Path, is_dir: bool) -> Option<SkipReason> {
+        let name = relative_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        // `.git` and the like are pruned whatever the other filters say; their contents are
+        // never source, and there are a lot of them.
+        let left_out = !self.include_vcs_dir && VCS_DIRECTORIES.contains(&name)
+            || self.ignore_hidden && is_hidden_name(name, &self.keep_hidden)
+            || is_dir && self.exclude_dirs.is_match(relative_path);
+        left_out.then_some(SkipReason::Filtered)
+    }
+
+    /// Decides a path listed without a walk, checking each directory above it as well.
+    ///
+    /// # Arguments
+    /// * `relative_path` - The path relative to the input directory.
+    /// * `is_dir` - Whether the path is a directory.
+    ///
+    /// # Returns
+    /// * `Option<SkipReason>` - Why the path is skipped, or `None` if it is listed.
+    pub(crate) fn path_exclusion(&self, relative_path: &Path, is_dir: bool) -> Option<SkipReason> {
+        let mut ancestors: Vec<&Path> = relative_path
+            .ancestors()
+            .skip(1)
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .collect();
+        ancestors.reverse();
+        ancestors
+            .into_iter()
+            .find_map(|directory| self.entry_exclusion(directory, true))
+            .or_else(|| self.entry_exclusion(relative_path, is_dir))
+    }
+
+    /// Returns the `include_paths` and `exclude_paths` overrides.
+    pub(crate) fn overrides(&self) -> &PathOverrides {
+        &self.overrides
+    }
+
+    /// Checks whether a file is a lockfile left out without `include_lockfiles`.
+    pub(crate) fn is_lockfile(&self, relative_path: &Path) -> bool {
+        self.lockfiles
+            .as_ref()
+            .is_some_and(|names| lockfiles::is_lockfile(relative_path, names))
+    }
+
+    /// Checks whether a file is left out by `no_tests` or `only_tests`.
+    pub(crate) fn is_test_excluded(&self, relative_path: &Path) -> bool {
+        self.tests
+            .as_ref()
+            .is_some_and(|(tests, only_tests)| tests.is_match(relative_path) != *only_tests)
+    }
+
+    /// Checks whether a file passes the `include` and `exclude` lists.
+    pub(crate) fn rules_allow(&self, relative_path: &Path) -> bool {
+        self.rules.allows(relative_path)
+    }
+
+    /// Explains why the `include` and `exclude` lists leave a file out, naming the entry.
+    pub(crate) fn rules_exclusion(&self, relative_path: &Path) -> Option<String> {
+        self.rules.exclusion(relative_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConfigBuilder;
+
+    fn filter(builder: ConfigBuilder) -> FileFilter {
+        FileFilter::new(&builder.build()).unwrap()
+    }
+
+    #[test]
+    fn directories_are_pruned_by_name_and_path() {
+        let filter = filter(
+            Config::builder()
+                .ignore_hidden(true)
+                .keep_hidden([".github"])
+                .exclude_dirs(["target", "/src/generated"]),
+        );
+        assert!(filter.matches_dir(Path::new("src")));
+        assert!(filter.matches_dir(Path::new(".github/workflows")));
+        assert!(!filter.matches_dir(Path::new(".git")));
+        assert!(!filter.matches_dir(Path::new(".cache/sub")));
+        assert!(!filter.matches_dir(Path::new("crates/a/target")));
+        assert!(!filter.matches_dir(Path::new("src/generated/deep")));
+        assert!(filter.matches_dir(Path::new("lib/src/generated")));
+    }
+
+    #[test]
+    fn files_are_decided_with_the_run_precedence() {
+        let filter = filter(
+            Config::builder()
+                .include(["rs", "lock", "pem"])
+                .exclude(["*_bench.rs"])
+                .no_tests(true)
+                .exclude_dirs(["vendor"])
+                .include_paths(["vendor/patched.rs", "tests/keep.rs", "deploy/.env"])
+                .exclude_paths(["src/secret.rs"]),
+        );
+        let cases = [
+            ("src/main.rs", FilterDecision::Include),
+            ("README.md", FilterDecision::Skip(SkipReason::Filtered)),
+            ("src/a_bench.rs", FilterDecision::Skip(SkipReason::Filtered)),
+            ("Cargo.lock", FilterDecision::Skip(SkipReason::Lockfile)),
+            ("tests/it.rs", FilterDecision::Skip(SkipReason::Tests)),
+            ("vendor/lib.rs", FilterDecision::Skip(SkipReason::Filtered)),
+            (".git/config", FilterDecision::Skip(SkipReason::Filtered)),
+            ("src/secret.rs", FilterDecision::Skip(SkipReason::Filtered)),
+            ("vendor/patched.rs", FilterDecision::Forced),
+            ("tests/keep.rs", FilterDecision::Forced),
+            ("keys/a.pem", FilterDecision::Skip(SkipReason::Sensitive)),
+            ("deploy/.env", FilterDecision::Skip(SkipReason::Sensitive)),
+            (".env", FilterDecision::Skip(SkipReason::Filtered)),
+        ];
+        for (path, decision) in cases {
+            assert_eq!(filter.matches_file(Path::new(path)), decision, "{}", path);
+        }
+        assert!(FilterDecision::Forced.is_included());
+    }
+
+    #[test]
+    fn conflicting_test_options_are_rejected() {
+        let config = Config::builder().no_tests(true).only_tests(true).build();
+        assert!(FileFilter::new(&config).is_err());
+    }
+}
@@ -0,0 +1,389 @@
+//! External filter commands (`filter_cmd`, `filter_cmd_ext`): a shell command that gets a
+//! file's content on stdin and writes the content to bundle on stdout.
+
+use crate::Config;
+use crate::rename::slash_path;
+use crate::source::SourceFile;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::str::FromStr;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// The seconds a filter command may run on one file before it is killed, by default.
+pub const DEFAULT_FILTER_TIMEOUT: u64 = 30;
+
+/// The longest pause between two checks of whether a filter command has exited.
+const MAX_POLL: Duration = Duration::from_millis(50);
+
+/// An `<ext>=<command>` rule that runs a filter command on the files with an extension,
+/// instead of `filter_cmd`.
+///
+/// For files without an extension, `ext` is matched against the file name instead, e.g.
+/// `Dockerfile=./scrub.sh`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct FilterCommand {
+    extension: String,
+    command: String,
+}
+
+impl FromStr for FilterCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (extension, command) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid filter command `{}` (expected <ext>=<command>)", s))?;
+        let extension = extension.trim().trim_start_matches('.');
+        let command = command.trim();
+        if extension.is_empty() || command.is_empty() {
+            return Err(format!(
+                "invalid filter command `{}` (both sides must be non-empty)",
+                s
+            ));
+        }
+        Ok(FilterCommand {
+            extension: extension.to_string(),
+            command: command.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for FilterCommand {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for FilterCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.extension, self.command)
+    }
+}
+
+impl Serialize for FilterCommand {
+    /// Serializes as the string form read from configuration files.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// The error returned for a file whose filter command failed, so callers can count it
+/// separately and skip the file or abort in strict mode.
+#[derive(Debug)]
+pub(crate) struct FilterFailed {
+    pub(crate) path: PathBuf,
+    pub(crate) command: String,
+    pub(crate) reason: String,
+}
+
+impl fmt::Display for FilterFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: filter command `{}` {}",
+            self.path.display(),
+            self.command,
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for FilterFailed {}
+
+/// The filter commands of a run, with the slots that cap how many run at once.
+#[derive(Debug)]
+pub(crate) struct Filters {
+    command: Option<String>,
+    by_extension: Vec<FilterCommand>,
+    /// How long a command may run, or `None` without a limit.
+    timeout: Option<Duration>,
+    /// The number of commands that may still be started.
+    free: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl Filters {
+    /// Collects the filter commands of a run.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Option<Filters>` - The commands, or `None` without `filter_cmd` and
+    ///   `filter_cmd_ext`.
+    pub(crate) fn for_run(config: &Config) -> Option<Filters> {
+        if config.filter_cmd.is_none() && config.filter_cmd_ext.is_empty() {
+            return None;
+        }
+        let jobs = config
+            .filter_jobs
+            .filter(|&jobs| jobs > 0)
+            .unwrap_or_else(|| crate::parallel::job_count(config.jobs));
+        Some(Filters {
+            command: config.filter_cmd.clone(),
+            by_extension: config.filter_cmd_ext.clone(),
+            timeout: (config.filter_timeout > 0)
+                .then(|| Duration::from_secs(config.filter_timeout)),
+            free: Mutex::new(jobs),
+            freed: Condvar::new(),
+        })
+    }
+
+    /// Returns the command for a file: the last `filter_cmd_ext` rule for its extension, or
+    /// its name without one, and otherwise `filter_cmd`.
+    fn command_for(&self, path: &Path) -> Option<&str> {
+        let key = path
+            .extension()
+            .or_else(|| path.file_name())
+            .and_then(|key| key.to_str())
+            .unwrap_or("");
+        self.by_extension
+            .iter()
+            .rev()
+            .find(|rule| rule.extension.eq_ignore_ascii_case(key))
+            .map(|rule| rule.command.as_str())
+            .or(self.command.as_deref())
+    }
+
+    /// Runs the filter command of a file on its content.
+    ///
+    /// The command runs through the shell with the content on stdin, and `C2P_PATH` (the
+    /// relative path) and `C2P_EXT` (the extension, without the dot) set. Its stdout is the
+    /// new content, and what it writes to stderr is logged.
+    ///
+    /// # Arguments
+    /// * `file` - The file.
+    /// * `content` - The content of the file.
+    ///
+    /// # Returns
+    /// * `Result<String, FilterFailed>` - The filtered content, `content` itself if no
+    ///   command applies to the file, or an error if the command cannot be started, exits
+    ///   with a failure, runs past the timeout, or writes output that is not UTF-8.
+    pub(crate) fn apply(&self, file: &SourceFile, content: String) -> Result<String, FilterFailed> {
+        let Some(command) = self.command_for(&file.relative_path) else {
+            return Ok(content);
+        };
+        let failed = |reason: String| FilterFailed {
+            path: file.relative_path.clone(),
+            command: command.to_string(),
+            reason,
+        };
+        let extension = file.relative_path.extension().unwrap_or_default();
+        let _slot = self.slot();
+        debug!(
+            "Filtering {} with `{}`",
+            file.relative_path.display(),
+            command
+        );
+        let mut child = shell(command)
+            .env("C2P_PATH", slash_path(&file.relative_path))
+            .env("C2P_EXT", extension)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| failed(format!("cannot be started: {}", err)))?;
+        let (mut stdin, mut stdout, mut stderr) = (
+            child.stdin.take().expect("stdin is piped"),
+            child.stdout.take().expect("stdout is piped"),
+            child.stderr.take().expect("stderr is piped"),
+        );
+        let (status, output, errors) = thread::scope(|scope| {
+            // A command may exit without reading its input, which closes the pipe; only its
+            // exit status tells whether it failed.
+            scope.spawn(move || stdin.write_all(content.as_bytes()));
+            let output = scope.spawn(move || {
+                let mut output = Vec::new();
+                stdout.read_to_end(&mut output).map(|_| output)
+            });
+            let errors = scope.spawn(move || {
+                let mut errors = Vec::new();
+                let _ = stderr.read_to_end(&mut errors);
+                errors
+            });
+            let status = wait(&mut child, self.timeout);
+            (
+                status,
+                output.join().expect("the stdout reader does not panic"),
+                errors.join().expect("the stderr reader does not panic"),
+            )
+        });
+        let errors = String::from_utf8_lossy(&errors);
+        let errors = errors.trim_end();
+        let status = match status {
+            Ok(Some(status)) => status,
+            Ok(None) => {
+                let seconds = self.timeout.map_or(0, |timeout| timeout.as_secs());
+                return Err(failed(format!("timed out after {}s", seconds)));
+            }
+            Err(err) => return Err(failed(format!("cannot be waited for: {}", err))),
+        };
+        if !status.success() {
+            return Err(failed(match errors {
+                "" => format!("failed with {}", status),
+                errors => format!("failed with {}: {}", status, errors),
+            }));
+        }
+        if !errors.is_empty() {
+            info!(
+                "Filter command `{}` on {}: {}",
+                command,
+                file.relative_path.display(),
+                errors
+            );
+        }
+        let output = output.map_err(|err| failed(format!("output cannot be read: {}", err)))?;
+        String::from_utf8(output).map_err(|_| failed("wrote output that is not UTF-8".to_string()))
+    }
+
+    /// Waits for a free slot to start a command, holding it until the guard is dropped.
+    fn slot(&self) -> Slot<'_> {
+        let mut free = self.free.lock().unwrap_or_else(|err| err.into_inner());
+        while *free == 0 {
+            free = self.freed.wait(free).unwrap_or_else(|err| err.into_inner());
+        }
+        *free -= 1;
+        Slot(self)
+    }
+}
+
+/// A running filter command's claim on one of the slots of [`Filters`].
+struct Slot<'a>(&'a Filters);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        *self.0.free.lock().unwrap_or_else(|err| err.into_inner()) += 1;
+        self.0.freed.notify_one();
+    }
+}
+
+/// Builds the command that runs `command` through the shell, in its own process group on
+/// Unix so that a timeout also kills the processes it started.
+fn shell(command: &str) -> Command {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command).process_group(0);
+        shell
+    }
+    #[cfg(not(unix))]
+    {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    }
+}
+
+/// Waits for a command to exit, killing it once `timeout` has passed.
+///
+/// # Arguments
+/// * `child` - The running command.
+/// * `timeout` - How long it may run, or `None` to wait as long as it takes.
+///
+/// # Returns
+/// * `io::Result<Option<ExitStatus>>` - The exit status, or `None` if it was killed.
+fn wait(child: &mut Child, timeout: Option<Duration>) -> io::Result<Option<ExitStatus>> {
+    let Some(timeout) = timeout else {
+        return child.wait().map(Some);
+    };
+    let deadline = Instant::now() + timeout;
+    let mut pause = Duration::from_millis(1);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            kill(child);
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(pause.min(deadline - now));
+        pause = (pause * 2).min(MAX_POLL);
+    }
+}
+
+/// Kills a command and, on Unix, the rest of its process group.
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    if let Ok(group) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: `kill` takes no pointers; the group is the one the command leads.
+        unsafe { libc::kill(-group, libc::SIGKILL) };
+        return;
+    }
+    let _ = child.kill();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn filters(config: Config) -> Filters {
+        Filters::for_run(&config).unwrap()
+    }
+
+    fn file(path: &str) -> SourceFile {
+        SourceFile::new(PathBuf::from(path), PathBuf::from(path))
+    }
+
+    #[test]
+    fn the_last_rule_for_an_extension_wins_over_the_default_command() {
+        let filters = filters(
+            Config::builder()
+                .filter_cmd("cat")
+                .filter_cmd_ext(["rs=a", ".RS=b", "Dockerfile=c"].map(|rule| rule.parse().unwrap()))
+                .build(),
+        );
+        assert_eq!(filters.command_for(Path::new("src/main.rs")), Some("b"));
+        assert_eq!(filters.command_for(Path::new("Dockerfile")), Some("c"));
+        assert_eq!(filters.command_for(Path::new("README.md")), Some("cat"));
+        assert!("rs".parse::<FilterCommand>().is_err());
+        assert!("rs= ".parse::<FilterCommand>().is_err());
+    }
+
+    #[test]
+    fn commands_see_the_content_path_and_extension() {
+        let filters = filters(
+            Config::builder()
+                .filter_cmd(r#"printf '%s %s\n' "$C2P_PATH" "$C2P_EXT"; tr a-z A-Z"#)
+                .build(),
+        );
+        let filtered = filters.apply(&file("src/lib.rs"), "fn main() {}\n".to_string());
+        assert_eq!(filtered.unwrap(), "src/lib.rs rs\nFN MAIN() {}\n");
+    }
+
+    #[test]
+    fn failures_carry_the_status_and_stderr() {
+        let failing = filters(
+            Config::builder()
+                .filter_cmd("echo bad input >&2; exit 3")
+                .build(),
+        );
+        let err = failing.apply(&file("a.rs"), String::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a.rs: filter command `echo bad input >&2; exit 3` failed with exit status: 3: bad input"
+        );
+
+        let slow = filters(
+            Config::builder()
+                .filter_cmd("sleep 5")
+                .filter_timeout(1)
+                .build(),
+        );
+        let started = Instant::now();
+        let err = slow.apply(&file("a.rs"), String::new()).unwrap_err();
+        assert_eq!(err.reason, "timed out after 1s");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
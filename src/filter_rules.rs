@@ -0,0 +1,400 @@
+//! The `include` and `exclude` lists as ordered rules: extensions, file names, globs, and
+//! `!` negations, where the last matching rule of a list wins, as in `.gitignore`.
+
+use crate::{Config, languages};
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a rule matches.
+#[derive(Debug)]
+enum Matcher {
+    /// A lowercase file extension without the dot, e.g. `rs`.
+    Extension(String),
+    /// A lowercase dotted file name or end of one, e.g. `min.js` or `cargo.lock`.
+    Suffix(String),
+    /// A glob matched against the file name, e.g. `*.test.rs`.
+    Name(GlobMatcher),
+    /// A glob matched against the relative path, e.g. `**/fixtures/**`.
+    Path(GlobMatcher),
+}
+
+/// One entry of an `include` or `exclude` list.
+#[derive(Debug)]
+struct Rule {
+    /// Whether the entry starts with `!`, taking matching files back out of the list.
+    negated: bool,
+    matcher: Matcher,
+    /// The entry as written.
+    entry: String,
+    /// The pattern file and line number the entry was read from, if any.
+    from: Option<(PathBuf, usize)>,
+}
+
+impl Rule {
+    /// Parses a list entry; see [`FilterRules`] for the syntax.
+    fn parse(entry: &str) -> Result<Option<Rule>> {
+        let entry = entry.trim();
+        if entry.starts_with('#') {
+            return Ok(None);
+        }
+        let (negated, pattern) = match entry.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, entry),
+        };
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+        let rule = |matcher| Rule {
+            negated,
+            matcher,
+            entry: entry.to_string(),
+            from: None,
+        };
+        if !pattern.contains(['/', '*', '?', '[', '{']) {
+            let extension = pattern.trim_start_matches('.');
+            if extension.is_empty() {
+                return Ok(None);
+            }
+            let extension = extension.to_lowercase();
+            let matcher = if extension.contains('.') {
+                Matcher::Suffix(extension)
+            } else {
+                Matcher::Extension(extension)
+            };
+            return Ok(Some(rule(matcher)));
+        }
+        let anchored = pattern.trim_start_matches("./").trim_start_matches('/');
+        let glob = GlobBuilder::new(anchored)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid filter pattern `{}`", entry))?
+            .compile_matcher();
+        let matcher = if pattern.contains('/') {
+            Matcher::Path(glob)
+        } else {
+            Matcher::Name(glob)
+        };
+        Ok(Some(rule(matcher)))
+    }
+
+    /// Checks whether the rule matches a file, negated or not.
+    fn matches(&self, relative_path: &Path) -> bool {
+        match &self.matcher {
+            Matcher::Extension(extension) => relative_path
+                .extension()
+                .and_then(|s| s.to_str())
+                .is_some_and(|s| s.to_lowercase() == *extension),
+            Matcher::Suffix(suffix) => relative_path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .map(str::to_lowercase)
+                .is_some_and(|name| {
+                    name == *suffix
+                        || name
+                            .strip_suffix(suffix.as_str())
+                            .is_some_and(|stem| stem.ends_with('.'))
+                }),
+            Matcher::Name(glob) => relative_path
+                .file_name()
+                .is_some_and(|name| glob.is_match(name)),
+            Matcher::Path(glob) => glob.is_match(relative_path),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    /// Names the entry and where it comes from, e.g. `` `*.snap` from filters.txt:3 ``.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`", self.entry)?;
+        if let Some((path, number)) = &self.from {
+            write!(f, " from {}:{}", path.display(), number)?;
+        }
+        Ok(())
+    }
+}
+
+/// An ordered list of rules.
+#[derive(Debug, Default)]
+struct RuleList {
+    rules: Vec<Rule>,
+}
+
+impl RuleList {
+    /// Parses the entries of a pattern file, then the entries given inline, so the inline
+    /// ones win.
+    ///
+    /// # Arguments
+    /// * `from` - The pattern file, if any.
+    /// * `entries` - The entries given inline.
+    ///
+    /// # Returns
+    /// * `Result<RuleList>` - The rules, or an error if the file cannot be read or a glob is
+    ///   invalid.
+    fn parse(from: Option<&Path>, entries: &[String]) -> Result<RuleList> {
+        let mut rules = Vec::new();
+        if let Some(path) = from {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read pattern file: {}", path.display()))?;
+            for (index, line) in text.lines().enumerate() {
+                let rule = Rule::parse(line)
+                    .with_context(|| format!("{}:{}", path.display(), index + 1))?;
+                rules.extend(rule.map(|rule| Rule {
+                    from: Some((path.to_path_buf(), index + 1)),
+                    ..rule
+                }));
+            }
+        }
+        for entry in entries {
+            rules.extend(Rule::parse(entry)?);
+        }
+        Ok(RuleList { rules })
+    }
+
+    /// Returns the last rule matching a file, which decides it.
+    fn last_match(&self, relative_path: &Path) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(relative_path))
+    }
+
+    /// Returns whether the list takes the file in, by its last matching rule; `None` if no
+    /// rule matches.
+    fn verdict(&self, relative_path: &Path) -> Option<bool> {
+        self.last_match(relative_path).map(|rule| !rule.negated)
+    }
+
+    /// Checks whether the list has a rule that is not negated.
+    fn has_positive(&self) -> bool {
+        self.rules.iter().any(|rule| !rule.negated)
+    }
+}
+
+/// The compiled `include` and `exclude` lists.
+///
+/// Each entry is a file extension such as `rs` or `.rs`, matched without regard to case; a
+/// dotted name such as `min.js` or `Cargo.lock`, matching file names that equal it or end
+/// with a dot and it, also without regard to case; or a glob when it contains `/`, `*`, `?`,
+/// `[`, or `{`: a glob without `/` such as
+/// `*.test.rs` matches file names at any depth, one with `/` such as `**/fixtures/**`
+/// matches paths relative to the input directory. A leading `!` negates an entry. Empty
+/// entries and entries starting with `#` are ignored, so a file without an extension is
+/// only matched by a glob.
+///
+/// The entries of `include_from` and `exclude_from`, one per line, come before those of
+/// `include` and `exclude`, and are parsed the same way.
+///
+/// Within each list, the last matching entry wins. A file is included when the last
+/// `include` entry matching it is positive, or when the list has no positive entries and no
+/// negation matches it; it is then excluded when the last `exclude` entry matching it is
+/// positive.
+#[derive(Debug, Default)]
+pub(crate) struct FilterRules {
+    include: RuleList,
+    exclude: RuleList,
+}
+
+impl FilterRules {
+    /// Compiles the `include` and `exclude` lists of a configuration, with the files of its
+    /// `languages` included before the `include` entries.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<FilterRules>` - The rules, or an error if a glob is invalid or a language is
+    ///   unknown.
+    pub(crate) fn new(config: &Config) -> Result<FilterRules> {
+        let mut include = RuleList::parse(None, &languages::include_patterns(&config.languages)?)?;
+        include
+            .rules
+            .extend(RuleList::parse(config.include_from.as_deref(), &config.include)?.rules);
+        Ok(FilterRules {
+            include,
+            exclude: RuleList::parse(config.exclude_from.as_deref(), &config.exclude)?,
+        })
+    }
+
+    /// Checks whether a file passes the `include` and `exclude` lists.
+    ///
+    /// # Arguments
+    /// * `relative_path` - The path of the file relative to the input directory.
+    ///
+    /// # Returns
+    /// * `bool` - Returns `true` if the file is included and not excluded.
+    pub(crate) fn allows(&self, relative_path: &Path) -> bool {
+        let included = match self.include.verdict(relative_path) {
+            Some(verdict) => verdict,
+            None => !self.include.has_positive(),
+        };
+        included && self.exclude.verdict(relative_path) != Some(true)
+    }
+
+    /// Explains why the lists leave a file out, naming the deciding entry.
+    ///
+    /// # Arguments
+    /// * `relative_path` - The path of the file relative to the input directory.
+    ///
+    /// # Returns
+    /// * `Option<String>` - The reason, e.g. ``excluded by `*.snap` from filters.txt:3``, or
+    ///   `None` if the lists allow the file.
+    pub(crate) fn exclusion(&self, relative_path: &Path) -> Option<String> {
+        match self.include.last_match(relative_path) {
+            Some(rule) if rule.negated => return Some(format!("excluded by {}", rule)),
+            None if self.include.has_positive() => {
+                return Some("matched by no include entry".to_string());
+            }
+            _ => {}
+        }
+        self.exclude
+            .last_match(relative_path)
+            .filter(|rule| !rule.negated)
+            .map(|rule| format!("excluded by {}", rule))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_matching_rule_wins_within_each_list() {
+        // (include, exclude, path, allowed)
+        let cases: &[(&[&str], &[&str], &str, bool)] = &[
+            (&[], &[], "src/main.rs", true),
+            (&[""], &[], "README", true),
+            // Empty entries and bare dots match nothing, not extensionless files.
+            (&[], &[""], "README", true),
+            (&[], &["", "."], "Makefile", true),
+            (&["rs", ""], &[], "README", false),
+            // Extensions may start with a dot and match regardless of case.
+            (&[".rs"], &[], "src/main.rs", true),
+            (&[], &[".md"], "README.md", false),
+            (&["RS"], &[], "src/main.rs", true),
+            (&["rs"], &[], "src/Main.RS", true),
+            (&[], &["Md"], "docs/NOTES.mD", false),
+            (&["rs"], &[], "src/main.rs", true),
+            (&["rs"], &[], "README.md", false),
+            (&[], &["md"], "README.md", false),
+            (&["rs", "md"], &["md"], "README.md", false),
+            // Exclude negations take files back.
+            (
+                &[],
+                &["**/fixtures/**", "!**/fixtures/README.md"],
+                "a/fixtures/x.rs",
+                false,
+            ),
+            (
+                &[],
+                &["**/fixtures/**", "!**/fixtures/README.md"],
+                "a/fixtures/README.md",
+                true,
+            ),
+            (
+                &[],
+                &["!**/fixtures/README.md", "**/fixtures/**"],
+                "a/fixtures/README.md",
+                false,
+            ),
+            // Include negations drop files the include list would take.
+            (&["rs", "!*_test.rs"], &[], "src/a_test.rs", false),
+            (&["rs", "!*_test.rs"], &[], "src/a.rs", true),
+            (&["!*_test.rs", "rs"], &[], "src/a_test.rs", true),
+            (&["!*_test.rs"], &[], "src/a_test.rs", false),
+            (&["!*_test.rs"], &[], "README.md", true),
+            (&["!rs"], &[], "src/a.rs", false),
+            // Exclusion applies after inclusion, so an include cannot undo it.
+            (&["src/**"], &["*.md"], "src/notes.md", false),
+            (
+                &["src/**"],
+                &["*.md", "!src/notes.md"],
+                "src/notes.md",
+                true,
+            ),
+            (&["src/**"], &[], "docs/a.md", false),
+            // Globs without `/` match names at any depth; `*` does not cross `/`.
+            (&["Makefile"], &[], "sub/Makefile", false),
+            (&["Make*"], &[], "sub/Makefile", true),
+            // Dotted entries match whole names or their dotted ends.
+            (&[], &["min.js"], "static/app.min.js", false),
+            (&[], &["min.js"], "static/app.js", true),
+            (&[], &["min.js"], "static/admin.js", true),
+            (&["nums.txt"], &[], "data/nums.txt", true),
+            (&["nums.txt"], &[], "data/other.txt", false),
+            (&["Cargo.lock"], &[], "cargo.LOCK", true),
+            (&[], &["lock", "min.js"], "Cargo.lock", false),
+            (&["rs", "!.test.rs"], &[], "src/a.test.rs", false),
+            (&["src/*.rs"], &[], "src/a/b.rs", false),
+            (&["./src/*.rs"], &[], "src/b.rs", true),
+            (&["/src/*.rs"], &[], "src/b.rs", true),
+        ];
+        for (include, exclude, path, allowed) in cases {
+            let config = Config::builder()
+                .include(include.iter().copied())
+                .exclude(exclude.iter().copied())
+                .build();
+            let rules = FilterRules::new(&config).unwrap();
+            assert_eq!(
+                rules.allows(Path::new(path)),
+                *allowed,
+                "include {:?}, exclude {:?}, path {}",
+                include,
+                exclude,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn pattern_files_come_before_inline_entries_and_are_attributed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let patterns = temp_dir.path().join("exclude.txt");
+        fs::write(
+            &patterns,
+            "# Generated code\n\n**/generated/**\n!**/generated/keep.rs\n  # snapshots\nsnap\n",
+        )
+        .unwrap();
+        let config = Config::builder()
+            .exclude_from(&patterns)
+            .exclude(["!*.snap"])
+            .include(["rs", "snap"])
+            .build();
+        let rules = FilterRules::new(&config).unwrap();
+        assert!(!rules.allows(Path::new("src/generated/api.rs")));
+        assert!(rules.allows(Path::new("src/generated/keep.rs")));
+        assert!(rules.allows(Path::new("tests/a.snap")));
+        assert_eq!(
+            rules.exclusion(Path::new("src/generated/api.rs")).unwrap(),
+            format!(
+                "excluded by `**/generated/**` from {}:3",
+                patterns.display()
+            )
+        );
+        assert_eq!(
+            rules.exclusion(Path::new("README.md")).unwrap(),
+            "matched by no include entry"
+        );
+        assert_eq!(rules.exclusion(Path::new("src/generated/keep.rs")), None);
+
+        let config = Config::builder()
+            .include_from(temp_dir.path().join("missing.txt"))
+            .build();
+        let err = FilterRules::new(&config).unwrap_err();
+        assert!(err.to_string().contains("missing.txt"), "{}", err);
+
+        fs::write(&patterns, "rs\nsrc/[a\n").unwrap();
+        let config = Config::builder().exclude_from(&patterns).build();
+        let err = FilterRules::new(&config).unwrap_err();
+        assert!(format!("{:#}", err).contains("exclude.txt:2"), "{:#}", err);
+    }
+
+    #[test]
+    fn invalid_globs_are_errors() {
+        let config = Config::builder().exclude(["src/[a"]).build();
+        let err = FilterRules::new(&config).unwrap_err();
+        assert!(err.to_string().contains("`src/[a`"));
+    }
+}
@@ -0,0 +1,555 @@
+//! Import following (`entry` with `follow_imports`): the entry files and every file they
+//! reach through their imports, found with per-language heuristics rather than a compiler.
+//!
+//! - Rust: `mod` declarations, as in `--sort rust-modules`, and `crate::`, `self::`, and
+//!   `super::` paths, resolved to the deepest module file that exists.
+//! - Python: `import a.b` and `from a.b import c`, as `a/b.py`, `a/b/__init__.py`, or the
+//!   submodule `a/b/c.py`, looked up from the importing file's directory upwards; relative
+//!   imports (`from .m import x`) from the importing file's package.
+//! - JavaScript and TypeScript: relative specifiers of `import`, `export ... from`,
+//!   `import()`, and `require()`, with or without an extension or as a directory `index`.
+//!
+//! Everything else, such as packages from a registry, is not followed.
+
+use crate::comments::Syntax;
+use crate::rust_modules::{self, Tok, lex, normalize, unquote};
+use crate::source::{FileSource, SourceFile};
+use crate::{Config, SkipReason};
+use anyhow::{Result, bail};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// The extensions tried for an extensionless JavaScript or TypeScript specifier, in order.
+const SCRIPT_EXTENSIONS: [&str; 8] = ["ts", "tsx", "js", "jsx", "mts", "cts", "mjs", "cjs"];
+
+/// The entry files of a run and how far their imports are followed.
+#[derive(Debug)]
+pub(crate) struct Reach {
+    entries: Vec<PathBuf>,
+    follow: bool,
+    depth: Option<usize>,
+    /// The number of files reached and listed, once [`retain`](Reach::retain) has run.
+    counts: (usize, usize),
+}
+
+impl Reach {
+    /// Reads the entry files of a run.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<Option<Reach>>` - The entry files, `None` without `entry`, or an error if
+    ///   `follow_imports` is set without them.
+    pub(crate) fn for_run(config: &Config) -> Result<Option<Reach>> {
+        if config.entry.is_empty() {
+            if config.follow_imports {
+                bail!("--follow-imports needs --entry");
+            }
+            return Ok(None);
+        }
+        Ok(Some(Reach {
+            entries: config.entry.iter().map(|path| normalize(path)).collect(),
+            follow: config.follow_imports,
+            depth: config.follow_depth,
+            counts: (0, 0),
+        }))
+    }
+
+    /// Keeps the entry files and the files reached from them, in a breadth-first walk of
+    /// the imports up to `follow_depth` steps away, and reports the others as
+    /// [`SkipReason::Unreachable`].
+    ///
+    /// # Arguments
+    /// * `files` - The files listed by the source.
+    /// * `source` - The source the files are read from.
+    /// * `on_skipped` - Called for each file left out.
+    ///
+    /// # Returns
+    /// * `Result<()>` - `Ok(())`, or an error if an entry file is not among `files`.
+    pub(crate) fn retain(
+        &mut self,
+        files: &mut Vec<SourceFile>,
+        source: &dyn FileSource,
+        on_skipped: &mut dyn FnMut(&Path, SkipReason),
+    ) -> Result<()> {
+        let index: HashMap<PathBuf, usize> = files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (normalize(&file.relative_path), i))
+            .collect();
+        let mut reached = HashSet::new();
+        let mut queue = VecDeque::new();
+        for entry in &self.entries {
+            if !index.contains_key(entry) {
+                bail!(
+                    "--entry {}: no such file in the input, or it is hidden or ignored",
+                    entry.display()
+                );
+            }
+            if reached.insert(entry.clone()) {
+                queue.push_back((entry.clone(), 0));
+            }
+        }
+        while let Some((path, depth)) = queue.pop_front() {
+            if !self.follow || self.depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+            let content = match source.read(&files[index[&path]]) {
+                Ok(content) => content,
+                Err(err) => {
+                    debug!("Cannot read {} for its imports: {}", path.display(), err);
+                    continue;
+                }
+            };
+            let exists = |candidate: &Path| index.contains_key(candidate);
+            for import in imports(&path, &content, &exists) {
+                if reached.insert(import.clone()) {
+                    queue.push_back((import, depth + 1));
+                }
+            }
+        }
+
+        self.counts = (reached.len(), files.len());
+        info!(
+            "Reached {} of {} files from {}",
+            reached.len(),
+            files.len(),
+            self.entries
+                .iter()
+                .map(|entry| entry.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        files.retain(|file| {
+            let keep = reached.contains(&normalize(&file.relative_path));
+            if !keep {
+                on_skipped(&file.path, SkipReason::Unreachable);
+            }
+            keep
+        });
+        Ok(())
+    }
+
+    /// Returns the number of files reached and the number listed.
+    pub(crate) fn counts(&self) -> (usize, usize) {
+        self.counts
+    }
+}
+
+/// Finds the files a file imports, by the heuristics of its language.
+///
+/// # Arguments
+/// * `path` - The relative path of the file.
+/// * `content` - The content of the file.
+/// * `exists` - Checks whether a relative path is a listed file.
+///
+/// # Returns
+/// * `Vec<PathBuf>` - The relative paths of the imported files that exist, each once.
+fn imports(path: &Path, content: &str, exists: &dyn Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let found = match extension.unwrap_or_default() {
+        "rs" => rust_imports(path, content, exists),
+        "py" | "pyi" => python_imports(path, content, exists),
+        extension if SCRIPT_EXTENSIONS.contains(&extension) => {
+            script_imports(path, content, exists)
+        }
+        _ => Vec::new(),
+    };
+    let mut seen = HashSet::new();
+    found
+        .into_iter()
+        .filter_map(|(import, resolved)| {
+            if resolved.is_none() {
+                debug!(
+                    "Not following `{}` in {}: no such file in the input",
+                    import,
+                    path.display()
+                );
+            }
+            resolved
+        })
+        .filter(|resolved| seen.insert(resolved.clone()))
+        .collect()
+}
+
+/// Finds the modules a Rust file declares or refers to through `crate::`, `self::`, or
+/// `super::` paths, each with the file it resolves to.
+fn rust_imports(
+    path: &Path,
+    content: &str,
+    exists: &dyn Fn(&Path) -> bool,
+) -> Vec<(String, Option<PathBuf>)> {
+    // Binaries, tests, examples, and benchmarks are crate roots beside their modules.
+    let is_root = rust_modules::is_crate_root(path)
+        || path
+            .parent()
+            .and_then(Path::file_name)
+            .is_some_and(|parent| {
+                ["bin", "tests", "examples", "benches"].contains(&&*parent.to_string_lossy())
+            });
+    let mut found: Vec<_> = rust_modules::declarations(content)
+        .into_iter()
+        .map(|declaration| {
+            let resolved = rust_modules::candidates(path, is_root, &declaration)
+                .into_iter()
+                .find(|candidate| exists(candidate));
+            (format!("mod {}", declaration.name), resolved)
+        })
+        .collect();
+
+    // The crate's `src` directory, or the top of the input for a crate bundled from `src`.
+    let src = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.file_name().is_some_and(|name| name == "src"))
+        .unwrap_or(Path::new(""));
+    let mut module: Vec<String> = path
+        .strip_prefix(src)
+        .unwrap_or(path)
+        .with_extension("")
+        .iter()
+        .map(|component| component.to_string_lossy().into_owned())
+        .collect();
+    if is_root || module.last().is_some_and(|last| last == "mod") {
+        module.pop();
+    }
+
+    let tokens = lex(content, Syntax::Rust);
+    let mut paths = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let after_path = i > 0 && tokens[i - 1] == Tok::Punct(':');
+        if after_path || tokens.get(i + 1..i + 3) != Some(&[Tok::Punct(':'), Tok::Punct(':')]) {
+            continue;
+        }
+        let mut prefix = match token {
+            Tok::Ident("crate") => Vec::new(),
+            Tok::Ident("self") => module.clone(),
+            Tok::Ident("super") => module[..module.len().saturating_sub(1)].to_vec(),
+            _ => continue,
+        };
+        let mut at = i + 3;
+        while let [Tok::Ident("super"), Tok::Punct(':'), Tok::Punct(':'), ..] = tokens[at..] {
+            prefix.pop();
+            at += 3;
+        }
+        use_tree(&tokens, &mut at, prefix, &mut paths);
+    }
+    for segments in paths.into_iter().filter(|segments| !segments.is_empty()) {
+        // The deepest module that exists: `crate::a::b::Item` is in `a/b.rs` or `a.rs`.
+        let resolved = (1..=segments.len()).rev().find_map(|len| {
+            let module = src.join(segments[..len].join("/"));
+            [module.with_extension("rs"), module.join("mod.rs")]
+                .into_iter()
+                .find(|candidate| exists(candidate))
+        });
+        found.push((segments.join("::"), resolved));
+    }
+    found
+}
+
+/// Collects the paths of a `use` tree, or of a plain path, that starts at `tokens[*at]`,
+/// each as its segments after `prefix`.
+fn use_tree(tokens: &[Tok], at: &mut usize, prefix: Vec<String>, paths: &mut Vec<Vec<String>>) {
+    let mut path = prefix;
+    loop {
+        match tokens.get(*at) {
+            Some(Tok::Ident("self")) => {
+                *at += 1;
+                break;
+            }
+            Some(Tok::Ident(name)) => {
+                path.push(name.to_string());
+                *at += 1;
+                if tokens.get(*at..*at + 2) == Some(&[Tok::Punct(':'), Tok::Punct(':')]) {
+                    *at += 2;
+                } else {
+                    break;
+                }
+            }
+            Some(Tok::Punct('{')) => {
+                *at += 1;
+                while !matches!(tokens.get(*at), None | Some(Tok::Punct('}'))) {
+                    use_tree(tokens, at, path.clone(), paths);
+                    if tokens.get(*at) == Some(&Tok::Punct(',')) {
+                        *at += 1;
+                    } else if tokens.get(*at) != Some(&Tok::Punct('}')) {
+                        // Not a use tree after all, e.g. a struct literal.
+                        return;
+                    }
+                }
+                *at += 1;
+                return;
+            }
+            _ => break,
+        }
+    }
+    paths.push(path);
+}
+
+/// Finds the modules a Python file imports, each with the file it resolves to.
+fn python_imports(
+    path: &Path,
+    content: &str,
+    exists: &dyn Fn(&Path) -> bool,
+) -> Vec<(String, Option<PathBuf>)> {
+    let tokens = lex(content, Syntax::Hash);
+    let mut modules = Vec::new();
+    let mut at = 0;
+    while at < tokens.len() {
+        match tokens[at] {
+            Tok::Ident("import") => {
+                at += 1;
+                while let Some(module) = dotted_name(&tokens, &mut at) {
+                    modules.push((module, None));
+                    skip_alias(&tokens, &mut at);
+                    if tokens.get(at) != Some(&Tok::Punct(',')) {
+                        break;
+                    }
+                    at += 1;
+                }
+            }
+            Tok::Ident("from") => {
+                at += 1;
+                let Some(module) = dotted_name(&tokens, &mut at) else {
+                    continue;
+                };
+                if tokens.get(at) != Some(&Tok::Ident("import")) {
+                    continue;
+                }
+                at += 1;
+                if tokens.get(at) == Some(&Tok::Punct('(')) {
+                    at += 1;
+                }
+                let mut names = Vec::new();
+                while let Some(Tok::Ident(name)) = tokens.get(at) {
+                    names.push(name.to_string());
+                    at += 1;
+                    skip_alias(&tokens, &mut at);
+                    if tokens.get(at) != Some(&Tok::Punct(',')) {
+                        break;
+                    }
+                    at += 1;
+                }
+                modules.push((module, Some(names)));
+            }
+            _ => at += 1,
+        }
+    }
+
+    let mut found = Vec::new();
+    for (module, names) in modules {
+        let level = module.chars().take_while(|c| *c == '.').count();
+        let parts: Vec<&str> = module[level..]
+            .split('.')
+            .filter(|p| !p.is_empty())
+            .collect();
+        let directory = path.parent().unwrap_or(Path::new(""));
+        // Relative imports start from the file's package; absolute ones from any directory
+        // above the file, as with a `src` layout or scripts run from their own directory.
+        let bases: Vec<&Path> = if level > 0 {
+            directory.ancestors().nth(level - 1).into_iter().collect()
+        } else {
+            directory.ancestors().collect()
+        };
+        let resolve = |parts: &[&str]| {
+            bases.iter().find_map(|base| {
+                let module = base.join(parts.join("/"));
+                [module.with_extension("py"), module.join("__init__.py")]
+                    .into_iter()
+                    .filter(|_| !parts.is_empty())
+                    .find(|candidate| exists(candidate))
+            })
+        };
+        // `from a import b` may import the submodule `a/b.py` as well as a name of `a`.
+        for name in names.iter().flatten() {
+            let submodule = [parts.as_slice(), &[name.as_str()]].concat();
+            if let Some(resolved) = resolve(&submodule) {
+                found.push((format!("{}.{}", module, name), Some(resolved)));
+            }
+        }
+        if !parts.is_empty() {
+            found.push((module.clone(), resolve(&parts)));
+        }
+    }
+    found
+}
+
+/// Reads a dotted module name at `tokens[*at]`, with its leading dots, e.g. `..a.b`.
+fn dotted_name(tokens: &[Tok], at: &mut usize) -> Option<String> {
+    let mut name = String::new();
+    while tokens.get(*at) == Some(&Tok::Punct('.')) {
+        name.push('.');
+        *at += 1;
+    }
+    while let Some(Tok::Ident(part)) = tokens.get(*at) {
+        if *part == "import" {
+            break;
+        }
+        name.push_str(part);
+        *at += 1;
+        if tokens.get(*at) == Some(&Tok::Punct('.')) {
+            name.push('.');
+            *at += 1;
+        } else {
+            break;
+        }
+    }
+    (!name.is_empty()).then_some(name)
+}
+
+/// Skips an `as alias` at `tokens[*at]`.
+fn skip_alias(tokens: &[Tok], at: &mut usize) {
+    if tokens.get(*at) == Some(&Tok::Ident("as")) {
+        *at += 2;
+    }
+}
+
+/// Finds the relative modules a JavaScript or TypeScript file imports, each with the file
+/// it resolves to.
+fn script_imports(
+    path: &Path,
+    content: &str,
+    exists: &dyn Fn(&Path) -> bool,
+) -> Vec<(String, Option<PathBuf>)> {
+    let tokens = lex(content, Syntax::CLike);
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut found = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let specifier = match (token, &tokens[i + 1..]) {
+            (Tok::Ident("from" | "import"), [Tok::Literal(literal), ..])
+            | (Tok::Ident("import" | "require"), [Tok::Punct('('), Tok::Literal(literal), ..]) => {
+                unquote(literal)
+            }
+            _ => continue,
+        };
+        if !specifier.starts_with('.') {
+            debug!(
+                "Not following `{}` in {}: not a relative import",
+                specifier,
+                path.display()
+            );
+            continue;
+        }
+        let target = normalize(&directory.join(&specifier));
+        // TypeScript imports name the compiled `.js` file of a `.ts` source.
+        let compiled = match target.extension().and_then(|extension| extension.to_str()) {
+            Some("js") => vec![target.with_extension("ts"), target.with_extension("tsx")],
+            Some("jsx") => vec![target.with_extension("tsx")],
+            Some("mjs") => vec![target.with_extension("mts")],
+            Some("cjs") => vec![target.with_extension("cts")],
+            _ => Vec::new(),
+        };
+        let with_extension = SCRIPT_EXTENSIONS.iter().map(|extension| {
+            let mut name = target.clone().into_os_string();
+            name.push(".");
+            name.push(extension);
+            PathBuf::from(name)
+        });
+        let index = SCRIPT_EXTENSIONS
+            .iter()
+            .map(|extension| target.join("index").with_extension(extension));
+        let resolved = std::iter::once(target.clone())
+            .chain(compiled)
+            .chain(with_extension)
+            .chain(index)
+            .find(|candidate| exists(candidate));
+        found.push((specifier, resolved));
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(path: &str, content: &str, files: &[&str]) -> Vec<PathBuf> {
+        let exists = |candidate: &Path| files.iter().any(|file| Path::new(file) == candidate);
+        imports(Path::new(path), content, &exists)
+    }
+
+    #[test]
+    fn rust_paths_resolve_to_the_deepest_module() {
+        let files = [
+            "src/config.rs",
+            "src/net/mod.rs",
+            "src/net/tcp.rs",
+            "src/util.rs",
+            "src/net/udp.rs",
+        ];
+        let content = "\
+use crate::config::Config;
+use crate::{net::tcp::{self, Stream}, missing::Thing};
+use super::util;
+use self::udp::Socket;
+pub(crate) fn f() { crate::util::helper(); }
+";
+        assert_eq!(
+            resolve("src/net/mod.rs", content, &files),
+            [
+                "src/config.rs",
+                "src/net/tcp.rs",
+                "src/util.rs",
+                "src/net/udp.rs",
+            ]
+            .map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn python_imports_resolve_absolute_relative_and_submodules() {
+        let files = [
+            "app/__init__.py",
+            "app/models.py",
+            "app/services/__init__.py",
+            "app/services/billing.py",
+            "app/cli/helpers.py",
+        ];
+        let content = "\
+import os, app.models as m
+from app.services import (billing, Plan)
+from .helpers import run  # from nowhere import this
+from .. import models
+";
+        assert_eq!(
+            resolve("app/cli/main.py", content, &files),
+            [
+                "app/models.py",
+                "app/services/billing.py",
+                "app/services/__init__.py",
+                "app/cli/helpers.py",
+            ]
+            .map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn script_imports_resolve_relative_specifiers() {
+        let files = [
+            "src/api.ts",
+            "src/components/index.tsx",
+            "src/util/format.ts",
+            "src/legacy.js",
+        ];
+        let content = "\
+import React from 'react';
+import { get } from \"./api\";
+export * from './components';
+import './util/format.js';
+const legacy = require('./legacy.js');
+// import { x } from './commented';
+const lazy = import('../outside');
+";
+        assert_eq!(
+            resolve("src/main.ts", content, &files),
+            [
+                "src/api.ts",
+                "src/components/index.tsx",
+                "src/util/format.ts",
+                "src/legacy.js",
+            ]
+            .map(PathBuf::from)
+        );
+    }
+}
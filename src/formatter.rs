@@ -0,0 +1,2142 @@
+//! Output formatters: how the bundle as a whole and each file in it are written.
+
+use crate::archive::{self, TarContent};
+use crate::color::{BOLD, DIM, RESET, highlight};
+use crate::lang::fence_language;
+use crate::lang_stats::{self, TABLE_HEADER};
+use crate::meta::{format_size, format_time};
+use crate::transform::{LineEnding, Segment};
+use crate::{
+    BudgetSelection, BundleMetadata, Config, FileEntry, LineNumberFormat, PendingFile, RunSummary,
+    SkipReason, Tokenizer,
+};
+use crate::{content_hash, group_by, json, manifest, reproducible, signatures, stream};
+use anyhow::{Context, Result};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The longest first line shown with `headers_only`, in characters.
+const FIRST_LINE_CHARS: usize = 100;
+
+/// Information about a run, available before any file is written.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct RunContext<'a> {
+    /// The configuration options for the bundling process.
+    pub config: &'a Config,
+    /// The displayed paths of the files selected for the bundle, in output order.
+    ///
+    /// Files can still be skipped later, e.g. when they turn out not to be UTF-8 text.
+    pub files: &'a [PathBuf],
+    /// Whether the output should be colored, resolved from `color` and where the output goes.
+    pub color: bool,
+    /// What produced the bundle, to write before the first file; `None` unless
+    /// `metadata_header` is set.
+    pub metadata: Option<&'a BundleMetadata>,
+    /// The YAML front matter written before everything else; `None` unless `frontmatter` is
+    /// set.
+    pub frontmatter: Option<&'a str>,
+}
+
+/// A section of the bundle, written before its files with `group_by`: a directory, or a
+/// language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FileGroup {
+    /// The name of the section: a directory relative to the bundled directory with a
+    /// trailing `/`, such as `src/` or `./` for top-level files, or a language such as `Rust`.
+    pub name: String,
+    /// The number of files in the section.
+    pub files: usize,
+    /// The number of lines written for the section's files.
+    pub lines: usize,
+    /// The estimated number of tokens written for the section's files.
+    pub tokens: usize,
+}
+
+impl FileGroup {
+    /// Computes the subtotals of a section.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the section.
+    /// * `entries` - The files of the section; duplicates (with an original path) add no
+    ///   lines or tokens, since only a stub is written for them.
+    ///
+    /// # Returns
+    /// * `FileGroup` - The section with its subtotals.
+    pub(crate) fn new(name: String, entries: &[PendingFile]) -> Self {
+        let written = entries
+            .iter()
+            .filter(|(_, original)| original.is_none())
+            .map(|(entry, _)| entry);
+        FileGroup {
+            name,
+            files: entries.len(),
+            lines: written
+                .clone()
+                .flat_map(|entry| entry.segments())
+                .map(|segment| segment.text().lines().count())
+                .sum(),
+            tokens: written.map(|entry| entry.tokens).sum(),
+        }
+    }
+
+    /// Returns the section label, e.g. `src/ (3 files, 120 lines, ~900 tokens)`.
+    fn label(&self) -> String {
+        format!(
+            "{} ({} files, {} lines, ~{} tokens)",
+            self.name, self.files, self.lines, self.tokens
+        )
+    }
+}
+
+/// Writes the bundle: an optional preamble, each file, and an optional epilogue.
+///
+/// The built-in formats are [`MarkdownFormatter`], [`TextFormatter`], [`ConsoleFormatter`],
+/// [`ClaudeFormatter`], [`ChatJsonFormatter`], [`AsciidocFormatter`], and [`TarFormatter`];
+/// [`Format::formatter`](crate::Format::formatter) maps a [`Format`](crate::Format) to one
+/// of them.
+pub trait Formatter {
+    /// Whether the formatter writes `preamble` and `postamble` itself, inside its own
+    /// structure; otherwise the run writes them verbatim around the bundle.
+    fn embeds_framing_text(&self) -> bool {
+        false
+    }
+
+    /// Writes anything that comes before the first file.
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the bundled content.
+    /// * `context` - Information about the run.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn begin(&mut self, writer: &mut dyn Write, context: &RunContext) -> Result<()> {
+        let _ = (writer, context);
+        Ok(())
+    }
+
+    /// Writes a single file.
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the file content.
+    /// * `entry` - The file to write.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn file(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()>;
+
+    /// Writes the header of a single file without its content (`--headers-only`): the path
+    /// with its size, line count, and token estimate, then its first line.
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the header.
+    /// * `entry` - The file whose header to write.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn header(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        writeln!(
+            writer,
+            "./{}{}{}{}",
+            entry.display_path().display(),
+            range_label(entry, config),
+            meta_label(entry, config),
+            header_label(entry)
+        )?;
+        if let Some(line) = first_line(entry) {
+            writeln!(writer, "    {}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the header of a directory or language section, before its files (`--group-by`).
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the header.
+    /// * `group` - The section and its subtotals.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn group(&mut self, writer: &mut dyn Write, group: &FileGroup, config: &Config) -> Result<()> {
+        let _ = (writer, group, config);
+        Ok(())
+    }
+
+    /// Writes the stub of a file whose content duplicates an earlier file (`--dedup`).
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the stub.
+    /// * `entry` - The duplicate file.
+    /// * `original` - The displayed path of the first file with the same content.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn duplicate(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        original: &Path,
+        config: &Config,
+    ) -> Result<()> {
+        let _ = config;
+        writeln!(
+            writer,
+            "{} (identical to {})",
+            entry.display_path().display(),
+            original.display()
+        )?;
+        Ok(())
+    }
+
+    /// Writes the one-line stub of a file left out as binary or too large
+    /// (`--binary-placeholders`).
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the stub.
+    /// * `entry` - The placeholder, without content.
+    /// * `reason` - Why the content was left out.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn placeholder(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        reason: SkipReason,
+        config: &Config,
+    ) -> Result<()> {
+        let _ = config;
+        writeln!(
+            writer,
+            "./{} [{}]",
+            entry.display_path().display(),
+            placeholder_label(entry, reason)
+        )?;
+        Ok(())
+    }
+
+    /// Writes the list of files deleted between the refs of `between`, after the last file.
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the list.
+    /// * `paths` - The deleted files, relative to the input directory.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn deleted(
+        &mut self,
+        writer: &mut dyn Write,
+        paths: &[PathBuf],
+        config: &Config,
+    ) -> Result<()> {
+        writeln!(writer, "==== {} ====", deleted_label(paths, config))?;
+        for path in paths {
+            writeln!(writer, "./{}", path.display())?;
+        }
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    /// Writes the list of files left out to fit `fit_budget`, after the last file.
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the list.
+    /// * `selection` - The chosen and omitted files.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn omitted(
+        &mut self,
+        writer: &mut dyn Write,
+        selection: &BudgetSelection,
+        config: &Config,
+    ) -> Result<()> {
+        let _ = config;
+        writeln!(writer, "==== {} ====", omitted_label(selection))?;
+        for file in &selection.omitted {
+            writeln!(
+                writer,
+                "./{} (~{} tokens)",
+                file.path.display(),
+                file.tokens
+            )?;
+        }
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    /// Writes the note that files were left out by `max_files`, after the last file.
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the note.
+    /// * `limit` - The most files included.
+    /// * `left_out` - The number of files left out by the limit.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn limited(
+        &mut self,
+        writer: &mut dyn Write,
+        limit: usize,
+        left_out: usize,
+        config: &Config,
+    ) -> Result<()> {
+        let _ = config;
+        writeln!(writer, "==== {} ====\n", limited_label(limit, left_out))?;
+        Ok(())
+    }
+
+    /// Writes the bundle digest of `hash`, after the last file and the lists of files left
+    /// out.
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the digest.
+    /// * `digest` - The digest with its algorithm, e.g. `blake3:9c0d…`.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn digest(&mut self, writer: &mut dyn Write, digest: &str, config: &Config) -> Result<()> {
+        let _ = config;
+        writeln!(writer, "bundle digest: {}", digest)?;
+        Ok(())
+    }
+
+    /// Writes the question of `question`, after the last file, the lists of files left out,
+    /// and the digest, and before [`finish`](Self::finish).
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the question.
+    /// * `question` - The question.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn question(&mut self, writer: &mut dyn Write, question: &str, config: &Config) -> Result<()> {
+        let _ = config;
+        writeln!(writer, "=== QUESTION ===\n{}\n", question.trim_end())?;
+        Ok(())
+    }
+
+    /// Writes anything that comes after the last file.
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the bundled content.
+    /// * `summary` - Statistics about the files written.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+    fn finish(&mut self, writer: &mut dyn Write, summary: &RunSummary) -> Result<()> {
+        let _ = (writer, summary);
+        Ok(())
+    }
+}
+
+/// Writes each file as a Markdown heading followed by a fenced code block.
+///
+/// The heading level is `heading_level`, and `file_header_template` replaces the heading.
+/// With `collapsible`, each file is a `<details>` block whose `<summary>` replaces the heading.
+/// Directory sections are headed one level above the files, e.g. `## src/ (…)`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownFormatter;
+
+impl Formatter for MarkdownFormatter {
+    /// Writes the metadata header as a blockquote.
+    fn begin(&mut self, writer: &mut dyn Write, context: &RunContext) -> Result<()> {
+        if let Some(metadata) = context.metadata {
+            for (label, value) in metadata.fields() {
+                writeln!(writer, "> {}: {}  ", label, value)?;
+            }
+            writeln!(writer)?;
+            if !metadata.languages.is_empty() {
+                writeln!(writer, "| {} |", TABLE_HEADER.join(" | "))?;
+                writeln!(writer, "|:---|---:|---:|---:|---:|")?;
+                for row in lang_stats::table_rows(&metadata.languages) {
+                    writeln!(writer, "| {} |", row.join(" | "))?;
+                }
+                writeln!(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn group(&mut self, writer: &mut dyn Write, group: &FileGroup, config: &Config) -> Result<()> {
+        let level = config.heading_level.clamp(2, 6) - 1;
+        writeln!(writer, "{} {}\n", "#".repeat(level.into()), group.label())?;
+        Ok(())
+    }
+
+    fn file(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        if config.collapsible {
+            // GitHub only renders the code block with a blank line after `</summary>`.
+            writeln!(
+                writer,
+                "<details>\n<summary>{}{}{}</summary>\n",
+                collapsible_summary(entry, config),
+                escape_xml(&range_label(entry, config)),
+                escape_xml(&meta_label(entry, config))
+            )?;
+        } else {
+            writeln!(
+                writer,
+                "{}{}{}\n",
+                markdown_header(entry, config),
+                range_label(entry, config),
+                meta_label(entry, config)
+            )?;
+        }
+        let fence = match &entry.streamed {
+            Some(streamed) => fence_around(streamed.longest_backtick_run),
+            None => code_fence(entry.segments()),
+        };
+        let path = entry.language_path();
+        let language = if entry.is_base64() {
+            "base64"
+        } else {
+            fence_language(&path, &config.lang_map)
+        };
+        writeln!(writer, "{}{}", fence, language)?;
+        write_content(writer, entry, config)?;
+        writeln!(writer, "{}\n", fence)?;
+        if config.collapsible {
+            writeln!(writer, "</details>\n")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the heading with the file's counts, and its first line as a blockquote.
+    fn header(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        writeln!(
+            writer,
+            "{}{}{}{}\n",
+            markdown_header(entry, config),
+            range_label(entry, config),
+            meta_label(entry, config),
+            header_label(entry)
+        )?;
+        if let Some(line) = first_line(entry) {
+            writeln!(writer, "> {}\n", line)?;
+        }
+        Ok(())
+    }
+
+    fn duplicate(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        original: &Path,
+        config: &Config,
+    ) -> Result<()> {
+        writeln!(
+            writer,
+            "{} (identical to `{}`)\n",
+            markdown_header(entry, config),
+            original.display()
+        )?;
+        Ok(())
+    }
+
+    fn placeholder(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        reason: SkipReason,
+        config: &Config,
+    ) -> Result<()> {
+        let note = match reason {
+            SkipReason::TooLarge => "File too large",
+            _ => "Binary file",
+        };
+        writeln!(
+            writer,
+            "{}\n\n*{} ({}); contents omitted.*\n",
+            markdown_header(entry, config),
+            note,
+            format_size(entry.size)
+        )?;
+        Ok(())
+    }
+
+    /// Writes the deleted files as a list under a heading at the level of section headings.
+    fn deleted(
+        &mut self,
+        writer: &mut dyn Write,
+        paths: &[PathBuf],
+        config: &Config,
+    ) -> Result<()> {
+        let level = config.heading_level.clamp(2, 6) - 1;
+        writeln!(
+            writer,
+            "{} {}\n",
+            "#".repeat(level.into()),
+            deleted_label(paths, config)
+        )?;
+        for path in paths {
+            writeln!(writer, "- `{}`", path.display())?;
+        }
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    /// Writes the omitted files as a list under a heading at the level of section headings.
+    fn omitted(
+        &mut self,
+        writer: &mut dyn Write,
+        selection: &BudgetSelection,
+        config: &Config,
+    ) -> Result<()> {
+        let level = config.heading_level.clamp(2, 6) - 1;
+        writeln!(
+            writer,
+            "{} {}\n",
+            "#".repeat(level.into()),
+            omitted_label(selection)
+        )?;
+        for file in &selection.omitted {
+            writeln!(
+                writer,
+                "- `{}` (~{} tokens)",
+                file.path.display(),
+                file.tokens
+            )?;
+        }
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    /// Writes the note as an italic paragraph.
+    fn limited(
+        &mut self,
+        writer: &mut dyn Write,
+        limit: usize,
+        left_out: usize,
+        _config: &Config,
+    ) -> Result<()> {
+        writeln!(writer, "*{}*\n", limited_label(limit, left_out))?;
+        Ok(())
+    }
+
+    /// Writes the question under a `## Question` heading.
+    fn question(&mut self, writer: &mut dyn Write, question: &str, _config: &Config) -> Result<()> {
+        writeln!(writer, "## Question\n\n{}\n", question.trim_end())?;
+        Ok(())
+    }
+}
+
+/// Writes each file as its `./`-prefixed path followed by its content between `---` lines.
+///
+/// Directory sections start with a `==== src/ (…) ====` banner.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextFormatter;
+
+impl Formatter for TextFormatter {
+    /// Writes the metadata header between `====` lines.
+    fn begin(&mut self, writer: &mut dyn Write, context: &RunContext) -> Result<()> {
+        if let Some(metadata) = context.metadata {
+            writeln!(writer, "====")?;
+            for (label, value) in metadata.fields() {
+                writeln!(writer, "{}: {}", label, value)?;
+            }
+            if !metadata.languages.is_empty() {
+                writeln!(writer, "languages:")?;
+                write_language_table(writer, metadata, "  ")?;
+            }
+            writeln!(writer, "====\n")?;
+        }
+        Ok(())
+    }
+
+    fn group(&mut self, writer: &mut dyn Write, group: &FileGroup, _config: &Config) -> Result<()> {
+        writeln!(writer, "==== {} ====\n", group.label())?;
+        Ok(())
+    }
+
+    fn file(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        writeln!(
+            writer,
+            "./{}{}{}\n---",
+            entry.display_path().display(),
+            range_label(entry, config),
+            meta_label(entry, config)
+        )?;
+        write_content(writer, entry, config)?;
+        writeln!(writer, "---")?;
+        Ok(())
+    }
+
+    fn duplicate(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        original: &Path,
+        _config: &Config,
+    ) -> Result<()> {
+        writeln!(
+            writer,
+            "./{} (identical to ./{})",
+            entry.display_path().display(),
+            original.display()
+        )?;
+        Ok(())
+    }
+}
+
+/// Writes the bundle as numbered documents, the long-context layout recommended for Claude:
+/// `<document index="N"><source>path</source><document_contents>…</document_contents></document>`
+/// blocks inside a `<documents>` element.
+///
+/// Indexes start at 1 and follow the output order. Paths are XML-escaped; in contents, only
+/// `</document` is escaped (as `&lt;/document`), so no file can close its block early.
+/// Lines left out of a file are an `<omitted lines="N"/>` element in its contents, and a
+/// file cut at a per-file cap has a `<truncated_at>` element, e.g. `4000 tokens`. The
+/// question of `question` follows `</documents>` in a `<question>` element.
+#[derive(Debug, Clone, Default)]
+pub struct ClaudeFormatter {
+    index: usize,
+    question: Option<String>,
+}
+
+impl ClaudeFormatter {
+    /// Writes one document block and advances the index; `base64` adds an
+    /// `<encoding>base64</encoding>` element for an embedded binary file, and the content
+    /// hash of `hash` goes in a `<content_hash>` element.
+    fn document(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        base64: bool,
+        write_contents: impl FnOnce(&mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        self.index += 1;
+        writeln!(
+            writer,
+            "<document index=\"{}\">\n<source>{}</source>",
+            self.index,
+            escape_xml(&entry.display_path().display().to_string())
+        )?;
+        if let Some(hash) = &entry.content_hash {
+            writeln!(writer, "<content_hash>{}</content_hash>", hash)?;
+        }
+        if base64 {
+            writeln!(writer, "<encoding>base64</encoding>")?;
+        }
+        if let Some(cap) = entry.truncated_at {
+            writeln!(writer, "<truncated_at>{}</truncated_at>", cap)?;
+        }
+        writeln!(writer, "<document_contents>")?;
+        write_contents(writer)?;
+        writeln!(writer, "</document_contents>\n</document>")?;
+        Ok(())
+    }
+}
+
+impl Formatter for ClaudeFormatter {
+    /// Writes the metadata header as a `<meta>` element, then opens `<documents>`.
+    fn begin(&mut self, writer: &mut dyn Write, context: &RunContext) -> Result<()> {
+        *self = ClaudeFormatter::default();
+        if let Some(metadata) = context.metadata {
+            writeln!(writer, "<meta>")?;
+            for (label, value) in metadata.fields() {
+                writeln!(writer, "<{0}>{1}</{0}>", label, escape_xml(&value))?;
+            }
+            if !metadata.languages.is_empty() {
+                writeln!(writer, "<languages>")?;
+                for stats in &metadata.languages {
+                    writeln!(
+                        writer,
+                        "<language name=\"{}\" files=\"{}\" blank=\"{}\" comment=\"{}\" code=\"{}\"/>",
+                        escape_xml(&stats.language),
+                        stats.files,
+                        stats.blank,
+                        stats.comment,
+                        stats.code
+                    )?;
+                }
+                writeln!(writer, "</languages>")?;
+            }
+            writeln!(writer, "</meta>")?;
+        }
+        writeln!(writer, "<documents>")?;
+        Ok(())
+    }
+
+    fn file(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        let segments: Vec<Segment> = entry
+            .segments()
+            .iter()
+            .map(|segment| match segment {
+                Segment::Lines { first_line, text } => Segment::Lines {
+                    first_line: *first_line,
+                    text: escape_closing_tags(text),
+                },
+                // An explicit element, so consumers need not parse the prose marker.
+                Segment::Omitted { lines, .. } => Segment::Omitted {
+                    lines: *lines,
+                    marker: format!("<omitted lines=\"{}\"/>", lines),
+                },
+            })
+            .collect();
+        self.document(writer, entry, entry.is_base64(), |writer| {
+            write_segments(writer, &segments, config, false)
+        })
+    }
+
+    /// Writes a document block with the file's counts and first line instead of its
+    /// contents.
+    fn header(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        _config: &Config,
+    ) -> Result<()> {
+        self.index += 1;
+        writeln!(
+            writer,
+            "<document index=\"{}\">\n<source>{}</source>",
+            self.index,
+            escape_xml(&entry.display_path().display().to_string())
+        )?;
+        writeln!(
+            writer,
+            "<size>{}</size>\n<lines>{}</lines>\n<tokens>{}</tokens>",
+            entry.size,
+            entry.line_count(),
+            entry.tokens
+        )?;
+        if let Some(line) = first_line(entry) {
+            writeln!(writer, "<first_line>{}</first_line>", escape_xml(&line))?;
+        }
+        writeln!(writer, "</document>")?;
+        Ok(())
+    }
+
+    fn duplicate(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        original: &Path,
+        _config: &Config,
+    ) -> Result<()> {
+        self.document(writer, entry, false, |writer| {
+            writeln!(
+                writer,
+                "(identical to {})",
+                escape_closing_tags(&original.display().to_string())
+            )?;
+            Ok(())
+        })
+    }
+
+    fn placeholder(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        reason: SkipReason,
+        _config: &Config,
+    ) -> Result<()> {
+        self.document(writer, entry, false, |writer| {
+            writeln!(
+                writer,
+                "({}; contents omitted)",
+                placeholder_label(entry, reason)
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Writes the deleted files as a `<deleted>` element before `</documents>`.
+    fn deleted(
+        &mut self,
+        writer: &mut dyn Write,
+        paths: &[PathBuf],
+        config: &Config,
+    ) -> Result<()> {
+        writeln!(
+            writer,
+            "<deleted between=\"{}\">",
+            escape_xml(config.between.as_deref().unwrap_or_default())
+        )?;
+        for path in paths {
+            writeln!(
+                writer,
+                "<file>{}</file>",
+                escape_xml(&path.display().to_string())
+            )?;
+        }
+        writeln!(writer, "</deleted>")?;
+        Ok(())
+    }
+
+    /// Writes the omitted files as an `<omitted>` element before `</documents>`.
+    fn omitted(
+        &mut self,
+        writer: &mut dyn Write,
+        selection: &BudgetSelection,
+        _config: &Config,
+    ) -> Result<()> {
+        writeln!(
+            writer,
+            "<omitted reason=\"budget\" budget=\"{}\">",
+            selection.budget
+        )?;
+        for file in &selection.omitted {
+            writeln!(
+                writer,
+                "<file tokens=\"{}\">{}</file>",
+                file.tokens,
+                escape_xml(&file.path.display().to_string())
+            )?;
+        }
+        writeln!(writer, "</omitted>")?;
+        Ok(())
+    }
+
+    /// Writes the note as an empty `<omitted>` element before `</documents>`.
+    fn limited(
+        &mut self,
+        writer: &mut dyn Write,
+        limit: usize,
+        left_out: usize,
+        _config: &Config,
+    ) -> Result<()> {
+        writeln!(
+            writer,
+            "<omitted reason=\"max_files\" limit=\"{}\" files=\"{}\"/>",
+            limit, left_out
+        )?;
+        Ok(())
+    }
+
+    /// Writes the digest as a `<bundle_digest>` element before `</documents>`.
+    fn digest(&mut self, writer: &mut dyn Write, digest: &str, _config: &Config) -> Result<()> {
+        writeln!(writer, "<bundle_digest>{}</bundle_digest>", digest)?;
+        Ok(())
+    }
+
+    /// Keeps the question for after `</documents>`, so it comes after the documents.
+    fn question(
+        &mut self,
+        _writer: &mut dyn Write,
+        question: &str,
+        _config: &Config,
+    ) -> Result<()> {
+        self.question = Some(question.trim_end().to_string());
+        Ok(())
+    }
+
+    fn finish(&mut self, writer: &mut dyn Write, _summary: &RunSummary) -> Result<()> {
+        writeln!(writer, "</documents>")?;
+        if let Some(question) = self.question.take() {
+            writeln!(writer, "<question>\n{}\n</question>", question)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the bundle as the `messages` of an OpenAI Chat Completions request, in a
+/// `{"messages": [...], "message_tokens": [...]}` object.
+///
+/// The preamble is the system message, the files are one user message in the
+/// [`TextFormatter`] layout (or one per file with `messages_per_file`), and the postamble and
+/// the question are the last user messages. `message_tokens` has the estimated tokens of each message, in the
+/// same order. The metadata header is left out.
+#[derive(Debug, Clone, Default)]
+pub struct ChatJsonFormatter {
+    /// The role and content of each message so far.
+    messages: Vec<(&'static str, String)>,
+    /// The text of the user message being written.
+    pending: Vec<u8>,
+    per_file: bool,
+    postamble: Option<String>,
+    question: Option<String>,
+    tokenizer: Tokenizer,
+}
+
+impl ChatJsonFormatter {
+    /// Ends the current user message, if it has any text.
+    fn push_pending(&mut self) {
+        if !self.pending.is_empty() {
+            let text = String::from_utf8_lossy(&std::mem::take(&mut self.pending)).into_owned();
+            self.messages.push(("user", text));
+        }
+    }
+
+    /// Ends the message of a file with `messages_per_file`.
+    fn end_file(&mut self) {
+        if self.per_file {
+            self.push_pending();
+        }
+    }
+}
+
+impl Formatter for ChatJsonFormatter {
+    fn embeds_framing_text(&self) -> bool {
+        true
+    }
+
+    /// Starts with the preamble as the system message.
+    fn begin(&mut self, _writer: &mut dyn Write, context: &RunContext) -> Result<()> {
+        let config = context.config;
+        *self = ChatJsonFormatter {
+            per_file: config.messages_per_file,
+            postamble: config.postamble.clone(),
+            tokenizer: config.tokenizer,
+            ..ChatJsonFormatter::default()
+        };
+        if let Some(preamble) = &config.preamble {
+            self.messages.push(("system", preamble.clone()));
+        }
+        Ok(())
+    }
+
+    fn group(&mut self, _writer: &mut dyn Write, group: &FileGroup, config: &Config) -> Result<()> {
+        TextFormatter.group(&mut self.pending, group, config)
+    }
+
+    fn file(&mut self, _writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        TextFormatter.file(&mut self.pending, entry, config)?;
+        self.end_file();
+        Ok(())
+    }
+
+    fn header(
+        &mut self,
+        _writer: &mut dyn Write,
+        entry: &FileEntry,
+        config: &Config,
+    ) -> Result<()> {
+        TextFormatter.header(&mut self.pending, entry, config)?;
+        self.end_file();
+        Ok(())
+    }
+
+    fn duplicate(
+        &mut self,
+        _writer: &mut dyn Write,
+        entry: &FileEntry,
+        original: &Path,
+        config: &Config,
+    ) -> Result<()> {
+        TextFormatter.duplicate(&mut self.pending, entry, original, config)?;
+        self.end_file();
+        Ok(())
+    }
+
+    fn placeholder(
+        &mut self,
+        _writer: &mut dyn Write,
+        entry: &FileEntry,
+        reason: SkipReason,
+        config: &Config,
+    ) -> Result<()> {
+        TextFormatter.placeholder(&mut self.pending, entry, reason, config)?;
+        self.end_file();
+        Ok(())
+    }
+
+    fn deleted(
+        &mut self,
+        _writer: &mut dyn Write,
+        paths: &[PathBuf],
+        config: &Config,
+    ) -> Result<()> {
+        TextFormatter.deleted(&mut self.pending, paths, config)
+    }
+
+    fn omitted(
+        &mut self,
+        _writer: &mut dyn Write,
+        selection: &BudgetSelection,
+        config: &Config,
+    ) -> Result<()> {
+        TextFormatter.omitted(&mut self.pending, selection, config)
+    }
+
+    fn limited(
+        &mut self,
+        _writer: &mut dyn Write,
+        limit: usize,
+        left_out: usize,
+        config: &Config,
+    ) -> Result<()> {
+        TextFormatter.limited(&mut self.pending, limit, left_out, config)
+    }
+
+    /// Leaves the digest to the `bundle_digest` field written by [`finish`](Self::finish).
+    fn digest(&mut self, _writer: &mut dyn Write, _digest: &str, _config: &Config) -> Result<()> {
+        Ok(())
+    }
+
+    /// Keeps the question for the last message.
+    fn question(
+        &mut self,
+        _writer: &mut dyn Write,
+        question: &str,
+        _config: &Config,
+    ) -> Result<()> {
+        self.question = Some(question.to_string());
+        Ok(())
+    }
+
+    /// Writes the messages, ending with the postamble and the question, and their token
+    /// counts; with `hash`, also the content hash of each file and the bundle digest.
+    fn finish(&mut self, writer: &mut dyn Write, summary: &RunSummary) -> Result<()> {
+        self.push_pending();
+        for text in [self.postamble.take(), self.question.take()]
+            .into_iter()
+            .flatten()
+        {
+            self.messages.push(("user", text));
+        }
+        let messages = self.messages.iter().map(|(role, content)| {
+            json::Value::object([
+                ("role", json::Value::String(role.to_string())),
+                ("content", json::Value::String(content.clone())),
+            ])
+        });
+        let tokens = self
+            .messages
+            .iter()
+            .map(|(_, content)| json::Value::Number(self.tokenizer.count(content).to_string()));
+        let mut fields = vec![
+            ("messages", json::Value::Array(messages.collect())),
+            ("message_tokens", json::Value::Array(tokens.collect())),
+        ];
+        if let Some(digest) = &summary.bundle_digest {
+            let hashes = summary.content_hashes.iter().map(|(path, hash)| {
+                json::Value::object([
+                    (
+                        "path",
+                        json::Value::from(path.to_string_lossy().into_owned()),
+                    ),
+                    ("hash", json::Value::from(hash.as_str())),
+                ])
+            });
+            fields.push(("content_hashes", json::Value::Array(hashes.collect())));
+            fields.push(("bundle_digest", json::Value::from(digest.as_str())));
+        }
+        let document = json::Value::object(fields);
+        writeln!(writer, "{}", document.to_pretty_string())?;
+        Ok(())
+    }
+}
+
+/// Returns the heading of the omitted-files list, e.g.
+/// `Omitted due to budget: 3 files, ~1200 tokens (budget: 1000 tokens)`.
+fn omitted_label(selection: &BudgetSelection) -> String {
+    format!(
+        "Omitted due to budget: {} files, ~{} tokens (budget: {} tokens)",
+        selection.omitted.len(),
+        selection.omitted_tokens(),
+        selection.budget
+    )
+}
+
+/// Describes the files deleted between the refs of `between`.
+fn deleted_label(paths: &[PathBuf], config: &Config) -> String {
+    format!(
+        "Deleted between {}: {}",
+        config.between.as_deref().unwrap_or_default(),
+        plural(paths.len(), "file")
+    )
+}
+
+/// Describes the files left out by `max_files`.
+fn limited_label(limit: usize, left_out: usize) -> String {
+    format!(
+        "Stopped after {} (--max-files): {} left out",
+        plural(limit, "file"),
+        plural(left_out, "more file")
+    )
+}
+
+/// Formats a count with a noun, adding `s` unless the count is one, e.g. `3 regions`.
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+/// Escapes the characters that are special in XML text.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Escapes `</document` so content cannot close a document block.
+fn escape_closing_tags(text: &str) -> String {
+    text.replace("</document", "&lt;/document")
+}
+
+/// Writes the bundle as an AsciiDoc document: a `= Codebase bundle` header with the run date
+/// as `revdate`, then each file as a section holding a `[source,<lang>]` listing block.
+///
+/// File sections are `==` sections, or `===` ones below `==` directory sections with
+/// `group_by`. Languages are those of Markdown fences, and a file containing a line of
+/// dashes gets a longer delimiter, so it cannot end the block early. With `line_numbers`, a
+/// file written whole or as one region gets the `linenums` option instead of a gutter.
+/// The preamble and postamble are paragraphs after the header and after the last file, and
+/// the question of `question` is a `== Question` section.
+#[derive(Debug, Clone, Default)]
+pub struct AsciidocFormatter {
+    postamble: Option<String>,
+}
+
+impl Formatter for AsciidocFormatter {
+    fn embeds_framing_text(&self) -> bool {
+        true
+    }
+
+    /// Writes the document header, then the metadata header as a description list and the
+    /// preamble.
+    fn begin(&mut self, writer: &mut dyn Write, context: &RunContext) -> Result<()> {
+        let config = context.config;
+        self.postamble = config.postamble.clone();
+        writeln!(writer, "= Codebase bundle")?;
+        if let Some(time) = reproducible::run_time(config) {
+            let time = format_time(time, reproducible::utc(config));
+            let date = time.split_once('T').map_or(time.as_str(), |(date, _)| date);
+            writeln!(writer, ":revdate: {}", date)?;
+        }
+        writeln!(writer)?;
+        if let Some(metadata) = context.metadata {
+            for (label, value) in metadata.fields() {
+                writeln!(writer, "{}:: {}", label, value)?;
+            }
+            writeln!(writer)?;
+            if !metadata.languages.is_empty() {
+                writeln!(writer, "[cols=\"<,>,>,>,>\",options=\"header\"]\n|===")?;
+                writeln!(writer, "|{}", TABLE_HEADER.join(" |"))?;
+                for row in lang_stats::table_rows(&metadata.languages) {
+                    writeln!(writer, "|{}", row.join(" |"))?;
+                }
+                writeln!(writer, "|===\n")?;
+            }
+        }
+        if let Some(preamble) = &config.preamble {
+            writeln!(writer, "{}\n", preamble.trim_end())?;
+        }
+        Ok(())
+    }
+
+    fn group(&mut self, writer: &mut dyn Write, group: &FileGroup, _config: &Config) -> Result<()> {
+        writeln!(writer, "== {}\n", group.label())?;
+        Ok(())
+    }
+
+    fn file(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        writeln!(
+            writer,
+            "{} {}{}{}\n",
+            asciidoc_section(config),
+            entry.display_path().display(),
+            range_label(entry, config),
+            meta_label(entry, config)
+        )?;
+        let path = entry.language_path();
+        let language = if entry.is_base64() {
+            "base64"
+        } else {
+            fence_language(&path, &config.lang_map)
+        };
+        let mut attributes = String::from("source");
+        if !language.is_empty() {
+            attributes.push(',');
+            attributes.push_str(language);
+        }
+        // Numbering restarts in each block, so only a single region can use `linenums`;
+        // several regions keep the gutter to show where each one starts.
+        let numbered_region = match entry.segments() {
+            [Segment::Lines { first_line, text }] if line_numbers(config).is_some() => {
+                attributes.push_str(",linenums");
+                if *first_line > 1 {
+                    attributes.push_str(&format!(",start={}", first_line));
+                }
+                Some(text)
+            }
+            _ => None,
+        };
+        let delimiter = listing_delimiter(entry.segments());
+        writeln!(writer, "[{}]\n{}", attributes, delimiter)?;
+        match numbered_region {
+            Some(text) => write_content_lines(writer, text, 1, None)?,
+            None => write_segments(writer, entry.segments(), config, false)?,
+        }
+        writeln!(writer, "{}\n", delimiter)?;
+        Ok(())
+    }
+
+    /// Writes the section heading with the file's counts, and its first line as a literal
+    /// paragraph.
+    fn header(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        writeln!(
+            writer,
+            "{} {}{}{}{}\n",
+            asciidoc_section(config),
+            entry.display_path().display(),
+            range_label(entry, config),
+            meta_label(entry, config),
+            header_label(entry)
+        )?;
+        if let Some(line) = first_line(entry) {
+            writeln!(writer, " {}\n", line)?;
+        }
+        Ok(())
+    }
+
+    fn duplicate(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        original: &Path,
+        config: &Config,
+    ) -> Result<()> {
+        writeln!(
+            writer,
+            "{} {}\n\nIdentical to `{}`.\n",
+            asciidoc_section(config),
+            entry.display_path().display(),
+            original.display()
+        )?;
+        Ok(())
+    }
+
+    fn placeholder(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        reason: SkipReason,
+        config: &Config,
+    ) -> Result<()> {
+        let note = match reason {
+            SkipReason::TooLarge => "File too large",
+            _ => "Binary file",
+        };
+        writeln!(
+            writer,
+            "{} {}\n\n_{} ({}); contents omitted._\n",
+            asciidoc_section(config),
+            entry.display_path().display(),
+            note,
+            format_size(entry.size)
+        )?;
+        Ok(())
+    }
+
+    /// Writes the deleted files as a list in a `==` section.
+    fn deleted(
+        &mut self,
+        writer: &mut dyn Write,
+        paths: &[PathBuf],
+        config: &Config,
+    ) -> Result<()> {
+        writeln!(writer, "== {}\n", deleted_label(paths, config))?;
+        for path in paths {
+            writeln!(writer, "* `{}`", path.display())?;
+        }
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    /// Writes the omitted files as a list in a `==` section.
+    fn omitted(
+        &mut self,
+        writer: &mut dyn Write,
+        selection: &BudgetSelection,
+        _config: &Config,
+    ) -> Result<()> {
+        writeln!(writer, "== {}\n", omitted_label(selection))?;
+        for file in &selection.omitted {
+            writeln!(
+                writer,
+                "* `{}` (~{} tokens)",
+                file.path.display(),
+                file.tokens
+            )?;
+        }
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    /// Writes the note as an italic paragraph.
+    fn limited(
+        &mut self,
+        writer: &mut dyn Write,
+        limit: usize,
+        left_out: usize,
+        _config: &Config,
+    ) -> Result<()> {
+        writeln!(writer, "_{}_\n", limited_label(limit, left_out))?;
+        Ok(())
+    }
+
+    fn question(&mut self, writer: &mut dyn Write, question: &str, _config: &Config) -> Result<()> {
+        writeln!(writer, "== Question\n\n{}\n", question.trim_end())?;
+        Ok(())
+    }
+
+    /// Writes the postamble as the last paragraph.
+    fn finish(&mut self, writer: &mut dyn Write, _summary: &RunSummary) -> Result<()> {
+        if let Some(postamble) = self.postamble.take() {
+            writeln!(writer, "{}", postamble.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the section marker of a file: `==`, or `===` below directory sections.
+fn asciidoc_section(config: &Config) -> &'static str {
+    if group_by(config).is_some() {
+        "==="
+    } else {
+        "=="
+    }
+}
+
+/// Returns a listing-block delimiter longer than any line of dashes in the content, so the
+/// content cannot close the block early (AsciiDoc closes a block at a line matching its
+/// opening delimiter).
+///
+/// # Arguments
+/// * `segments` - The selected lines of a file and any omission markers.
+///
+/// # Returns
+/// * `String` - At least four dashes.
+fn listing_delimiter(segments: &[Segment]) -> String {
+    let longest_run = segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Lines { text, .. } => text,
+            Segment::Omitted { marker, .. } => marker,
+        })
+        .flat_map(|text| text.lines())
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty() && line.bytes().all(|byte| byte == b'-'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "-".repeat((longest_run + 1).max(4))
+}
+
+/// Writes the bundle as a tar archive, for pipelines that want one file per document.
+///
+/// Each file is an entry at its displayed path holding its content after transformations,
+/// in the layout of [`TextFormatter`] without the header. Duplicates are hard links to their
+/// original, and placeholders, section headers, and framing text have no entry; the question
+/// of `question` is a `QUESTION.md` entry. A `MANIFEST.json` entry at the root, written last,
+/// describes the run like the `manifest` sidecar, with the files deleted between the refs of
+/// `between` under `deleted`. Entries are dated at the time of the run.
+#[derive(Debug, Clone, Default)]
+pub struct TarFormatter {
+    config: Option<Config>,
+    mtime: u64,
+    deleted: Vec<PathBuf>,
+}
+
+impl TarFormatter {
+    /// Writes one entry, dated at the time of the run.
+    fn entry(&self, writer: &mut dyn Write, path: &Path, content: TarContent) -> Result<()> {
+        let path = path.to_string_lossy().replace('\\', "/");
+        archive::write_tar_entry(writer, &path, content, self.mtime)?;
+        Ok(())
+    }
+}
+
+impl Formatter for TarFormatter {
+    fn embeds_framing_text(&self) -> bool {
+        true
+    }
+
+    fn begin(&mut self, _writer: &mut dyn Write, context: &RunContext) -> Result<()> {
+        let config = context.config;
+        *self = TarFormatter {
+            config: Some(config.clone()),
+            mtime: reproducible::run_time(config)
+                .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map_or(0, |age| age.as_secs()),
+            deleted: Vec::new(),
+        };
+        Ok(())
+    }
+
+    fn group(
+        &mut self,
+        _writer: &mut dyn Write,
+        _group: &FileGroup,
+        _config: &Config,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn file(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        // A file written whole keeps its content byte for byte.
+        let mut content = Vec::new();
+        match entry.segments() {
+            [Segment::Lines { text, .. }] if line_numbers(config).is_none() => {
+                content.extend_from_slice(text.as_bytes());
+            }
+            segments => write_segments(&mut content, segments, config, false)?,
+        }
+        self.entry(writer, entry.display_path(), TarContent::File(&content))
+    }
+
+    /// Leaves the files to `MANIFEST.json`, which lists each with its counts.
+    fn header(
+        &mut self,
+        _writer: &mut dyn Write,
+        _entry: &FileEntry,
+        _config: &Config,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn duplicate(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        original: &Path,
+        _config: &Config,
+    ) -> Result<()> {
+        let original = original.to_string_lossy().replace('\\', "/");
+        self.entry(
+            writer,
+            entry.display_path(),
+            TarContent::HardLink(&original),
+        )
+    }
+
+    fn placeholder(
+        &mut self,
+        _writer: &mut dyn Write,
+        _entry: &FileEntry,
+        _reason: SkipReason,
+        _config: &Config,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Keeps the deleted files for the manifest.
+    fn deleted(
+        &mut self,
+        _writer: &mut dyn Write,
+        paths: &[PathBuf],
+        _config: &Config,
+    ) -> Result<()> {
+        self.deleted = paths.to_vec();
+        Ok(())
+    }
+
+    fn omitted(
+        &mut self,
+        _writer: &mut dyn Write,
+        _selection: &BudgetSelection,
+        _config: &Config,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn limited(
+        &mut self,
+        _writer: &mut dyn Write,
+        _limit: usize,
+        _left_out: usize,
+        _config: &Config,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Leaves the digest to `MANIFEST.json`, which also has the content hash of each file.
+    fn digest(&mut self, _writer: &mut dyn Write, _digest: &str, _config: &Config) -> Result<()> {
+        Ok(())
+    }
+
+    /// Writes the question as a `QUESTION.md` entry.
+    fn question(&mut self, writer: &mut dyn Write, question: &str, _config: &Config) -> Result<()> {
+        let question = format!("{}\n", question.trim_end());
+        self.entry(
+            writer,
+            Path::new("QUESTION.md"),
+            TarContent::File(question.as_bytes()),
+        )
+    }
+
+    /// Writes the `MANIFEST.json` entry and ends the archive.
+    fn finish(&mut self, writer: &mut dyn Write, summary: &RunSummary) -> Result<()> {
+        let config = self
+            .config
+            .take()
+            .expect("`begin` is called before `finish`");
+        let mut manifest = manifest::manifest(&config, summary, None)?;
+        if let json::Value::Object(fields) = &mut manifest
+            && !self.deleted.is_empty()
+        {
+            let deleted = self
+                .deleted
+                .iter()
+                .map(|path| json::Value::from(path.to_string_lossy().replace('\\', "/")))
+                .collect();
+            fields.push(("deleted".to_string(), json::Value::Array(deleted)));
+        }
+        let manifest = manifest.to_pretty_string() + "\n";
+        self.entry(
+            writer,
+            Path::new("MANIFEST.json"),
+            TarContent::File(manifest.as_bytes()),
+        )?;
+        archive::finish_tar(writer)?;
+        Ok(())
+    }
+}
+
+/// Writes files for display in a terminal, in the [`TextFormatter`] layout.
+///
+/// When the run context allows colors, paths are bold, separators and the line-number gutter
+/// are dimmed, and comments and string literals are highlighted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConsoleFormatter {
+    color: bool,
+}
+
+impl Formatter for ConsoleFormatter {
+    /// Writes only the language table of the metadata header, with `lang_stats`.
+    fn begin(&mut self, writer: &mut dyn Write, context: &RunContext) -> Result<()> {
+        self.color = context.color;
+        if let Some(metadata) = context.metadata.filter(|m| !m.languages.is_empty()) {
+            write_language_table(writer, metadata, "")?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    fn group(&mut self, writer: &mut dyn Write, group: &FileGroup, config: &Config) -> Result<()> {
+        if !self.color {
+            return TextFormatter.group(writer, group, config);
+        }
+        writeln!(writer, "{BOLD}==== {} ===={RESET}\n", group.label())?;
+        Ok(())
+    }
+
+    fn file(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        if !self.color {
+            return TextFormatter.file(writer, entry, config);
+        }
+        writeln!(
+            writer,
+            "{BOLD}./{}{RESET}{DIM}{}{}{RESET}\n{DIM}---{RESET}",
+            entry.display_path().display(),
+            range_label(entry, config),
+            meta_label(entry, config)
+        )?;
+        let segments: Vec<Segment> = entry
+            .segments()
+            .iter()
+            .map(|segment| match segment {
+                Segment::Lines { first_line, text } => Segment::Lines {
+                    first_line: *first_line,
+                    text: highlight(text, &entry.extension),
+                },
+                Segment::Omitted { lines, marker } => Segment::Omitted {
+                    lines: *lines,
+                    marker: format!("{DIM}{}{RESET}", marker),
+                },
+            })
+            .collect();
+        write_segments(writer, &segments, config, true)?;
+        writeln!(writer, "{DIM}---{RESET}")?;
+        Ok(())
+    }
+
+    fn header(&mut self, writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        if !self.color {
+            return TextFormatter.header(writer, entry, config);
+        }
+        writeln!(
+            writer,
+            "{BOLD}./{}{RESET}{DIM}{}{}{}{RESET}",
+            entry.display_path().display(),
+            range_label(entry, config),
+            meta_label(entry, config),
+            header_label(entry)
+        )?;
+        if let Some(line) = first_line(entry) {
+            writeln!(writer, "    {}", highlight(&line, &entry.extension))?;
+        }
+        Ok(())
+    }
+
+    fn duplicate(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        original: &Path,
+        config: &Config,
+    ) -> Result<()> {
+        if !self.color {
+            return TextFormatter.duplicate(writer, entry, original, config);
+        }
+        writeln!(
+            writer,
+            "{BOLD}./{}{RESET}{DIM} (identical to ./{}){RESET}",
+            entry.display_path().display(),
+            original.display()
+        )?;
+        Ok(())
+    }
+
+    fn placeholder(
+        &mut self,
+        writer: &mut dyn Write,
+        entry: &FileEntry,
+        reason: SkipReason,
+        config: &Config,
+    ) -> Result<()> {
+        if !self.color {
+            return TextFormatter.placeholder(writer, entry, reason, config);
+        }
+        writeln!(
+            writer,
+            "{BOLD}./{}{RESET}{DIM} [{}]{RESET}",
+            entry.display_path().display(),
+            placeholder_label(entry, reason)
+        )?;
+        Ok(())
+    }
+}
+
+/// Writes the language table of the metadata header as aligned plain text, each line
+/// starting with `indent`.
+fn write_language_table(
+    writer: &mut dyn Write,
+    metadata: &BundleMetadata,
+    indent: &str,
+) -> Result<()> {
+    let header = TABLE_HEADER.map(String::from);
+    let rows = lang_stats::table_rows(&metadata.languages);
+    let mut widths = [0; 5];
+    for row in [&header].into_iter().chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in [&header].into_iter().chain(&rows) {
+        let mut line = format!("{}{:<width$}", indent, row[0], width = widths[0]);
+        for (cell, width) in row.iter().zip(widths).skip(1) {
+            line.push_str(&format!("  {:>width$}", cell));
+        }
+        writeln!(writer, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// Describes a placeholder, e.g. `binary, 48.0 KiB`.
+fn placeholder_label(entry: &FileEntry, reason: SkipReason) -> String {
+    let kind = match reason {
+        SkipReason::TooLarge => "too large",
+        _ => "binary",
+    };
+    format!("{}, {}", kind, format_size(entry.size))
+}
+
+/// Returns a code fence longer than any run of backticks in the content, so the content
+/// cannot close it early (CommonMark allows fences of three or more backticks).
+///
+/// # Arguments
+/// * `segments` - The selected lines of a file and any omission markers.
+///
+/// # Returns
+/// * `String` - At least three backticks.
+fn code_fence(segments: &[Segment]) -> String {
+    let longest_run = segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Lines { text, .. } => text,
+            Segment::Omitted { marker, .. } => marker,
+        })
+        .flat_map(|text| text.split(|c| c != '`'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    fence_around(longest_run)
+}
+
+/// Returns a code fence longer than a run of backticks.
+fn fence_around(longest_run: usize) -> String {
+    "`".repeat((longest_run + 1).max(3))
+}
+
+/// Returns the Markdown header of a file, without the range and metadata labels.
+///
+/// # Arguments
+/// * `entry` - The file being written.
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `String` - `file_header_template` with its `{path}`, `{ext}`, `{lines}`, and `{tokens}`
+///   placeholders filled in, or a heading of `heading_level` with the path in backticks.
+fn markdown_header(entry: &FileEntry, config: &Config) -> String {
+    let path = entry.display_path().display().to_string();
+    match &config.file_header_template {
+        Some(template) => template
+            .replace("{path}", &path)
+            .replace("{ext}", &entry.extension)
+            .replace("{lines}", &entry.line_count().to_string())
+            .replace(
+                "{tokens}",
+                &match entry.streamed {
+                    Some(_) => entry.tokens,
+                    None => config.tokenizer.count(&entry.content),
+                }
+                .to_string(),
+            ),
+        None => format!(
+            "{} `{}`",
+            "#".repeat(config.heading_level.clamp(1, 6).into()),
+            path
+        ),
+    }
+}
+
+/// Returns the `<summary>` text of a file with `collapsible`, without the range and metadata
+/// labels.
+///
+/// # Arguments
+/// * `entry` - The file being written.
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `String` - `file_header_template` with its placeholders filled in, or the path as
+///   `<code>` followed by the line count, e.g. `<code>src/main.rs</code> (12 lines)`.
+fn collapsible_summary(entry: &FileEntry, config: &Config) -> String {
+    match &config.file_header_template {
+        Some(_) => markdown_header(entry, config),
+        None => format!(
+            "<code>{}</code> ({} lines)",
+            escape_xml(&entry.display_path().display().to_string()),
+            entry.line_count()
+        ),
+    }
+}
+
+/// Returns the ` (lines a–b, c–d)` header suffix when `--only` selected line ranges, the
+/// ` (signatures only)` suffix when function bodies were elided, the
+/// ` (truncated at 4000 tokens)` suffix for a file cut at `max_lines_per_file` or
+/// `max_tokens_per_file`, the
+/// ` (encoding: base64)` suffix for an embedded binary file, the
+/// ` (3 matching lines in 2 regions of 120 lines)` suffix with `grep_context`, and the
+/// ` (blake3:1f2a3b4c5d6e)` suffix with `hash`.
+///
+/// # Arguments
+/// * `entry` - The file being written.
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `String` - The suffixes, or an empty string when whole files are bundled.
+pub(crate) fn range_label(entry: &FileEntry, config: &Config) -> String {
+    let mut label = String::new();
+    if !config.only.is_empty() {
+        label.push_str(&format!(
+            " (lines {})",
+            format_line_ranges(entry.segments())
+        ));
+    }
+    if config.signatures_only && signatures::is_supported(&entry.extension) {
+        label.push_str(" (signatures only)");
+    }
+    if let Some(cap) = entry.truncated_at {
+        label.push_str(&format!(" (truncated at {})", cap));
+    }
+    if entry.is_base64() {
+        label.push_str(" (encoding: base64)");
+    }
+    if let Some(matches) = entry.grep_matches {
+        let regions = entry
+            .segments()
+            .iter()
+            .filter(|segment| matches!(segment, Segment::Lines { .. }))
+            .count();
+        label.push_str(&format!(
+            " ({} in {} of {})",
+            plural(matches, "matching line"),
+            plural(regions, "region"),
+            plural(entry.line_count(), "line")
+        ));
+    }
+    if let Some(hash) = &entry.content_hash {
+        label.push_str(&format!(" ({})", content_hash::short_hash(hash)));
+    }
+    label
+}
+
+/// Returns the ` — 8.1 KiB, <mtime>, sha256:<hex>` header suffix for the selected `file_meta` fields.
+///
+/// # Arguments
+/// * `entry` - The file being written.
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `String` - The suffix, or an empty string when no metadata is selected or available.
+pub(crate) fn meta_label(entry: &FileEntry, config: &Config) -> String {
+    let mut fields = Vec::new();
+    if config.file_meta.size {
+        fields.push(format_size(entry.size));
+    }
+    if config.file_meta.mtime
+        && !config.reproducible
+        && let Some(modified) = entry.modified
+    {
+        fields.push(format_time(modified, config.utc));
+    }
+    if let Some(hash) = entry.sha256.as_ref().filter(|_| config.file_meta.hash) {
+        fields.push(format!("sha256:{}", hash));
+    }
+    if fields.is_empty() {
+        String::new()
+    } else {
+        format!(" — {}", fields.join(", "))
+    }
+}
+
+/// Renders the header of a file with `headers_only` in the [`TextFormatter`] layout, by
+/// which the file is counted in the totals whatever the format.
+pub(crate) fn header_text(entry: &FileEntry, config: &Config) -> String {
+    let mut text = Vec::new();
+    // Writing to a `Vec` cannot fail.
+    let _ = TextFormatter.header(&mut text, entry, config);
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+/// Returns the ` (12 lines, 3.1 KiB, ~800 tokens)` header suffix of a file with
+/// `headers_only`: its line count and token estimate after transformations, and its size
+/// on disk.
+fn header_label(entry: &FileEntry) -> String {
+    format!(
+        " ({}, {}, ~{} tokens)",
+        plural(entry.line_count(), "line"),
+        format_size(entry.size),
+        entry.tokens
+    )
+}
+
+/// Returns the first line of a file shown with `headers_only`, such as a doc comment: its
+/// first non-blank line after any `#!` line, trimmed and cut at [`FIRST_LINE_CHARS`]
+/// characters.
+///
+/// # Arguments
+/// * `entry` - The file being written.
+///
+/// # Returns
+/// * `Option<String>` - The line, or `None` for an empty or embedded binary file.
+fn first_line(entry: &FileEntry) -> Option<String> {
+    if entry.is_base64() {
+        return None;
+    }
+    let line =
+        entry.content.lines().map(str::trim).find(|line| {
+            !line.is_empty() && (!line.starts_with("#!") || line.starts_with("#!["))
+        })?;
+    Some(match line.char_indices().nth(FIRST_LINE_CHARS) {
+        Some((at, _)) => format!("{}…", &line[..at]),
+        None => line.to_string(),
+    })
+}
+
+/// Formats the line ranges covered by the segments, e.g. `120–240, 300–310`.
+///
+/// # Arguments
+/// * `segments` - The selected lines of a file and any omission markers.
+///
+/// # Returns
+/// * `String` - The comma-separated ranges.
+fn format_line_ranges(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Lines { first_line, text } => Some(format!(
+                "{}–{}",
+                first_line,
+                first_line + text.lines().count().max(1) - 1
+            )),
+            Segment::Omitted { .. } => None,
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns the line-number format to use, or `None` without `--line-numbers`; regions
+/// selected by `--grep-context` are always numbered.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Option<&LineNumberFormat>` - The gutter format when line numbers are enabled.
+pub(crate) fn line_numbers(config: &Config) -> Option<&LineNumberFormat> {
+    (config.line_numbers || config.grep_context.is_some()).then_some(&config.line_number_format)
+}
+
+/// Writes the selected segments of a file, with omission markers on their own lines.
+///
+/// Line numbers continue from the original file, and every segment of a file shares one
+/// gutter width.
+///
+/// # Arguments
+/// * `writer` - The writer to output the content.
+/// * `segments` - The selected lines of the file and any omission markers.
+/// * `config` - The configuration options; `line_numbers` turns on the gutter.
+/// * `dim_gutter` - Whether to dim the line numbers with terminal escapes.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+pub(crate) fn write_segments(
+    writer: &mut dyn Write,
+    segments: &[Segment],
+    config: &Config,
+    dim_gutter: bool,
+) -> Result<()> {
+    let last_line = segments
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Lines { first_line, text } => {
+                Some(first_line + text.lines().count().max(1) - 1)
+            }
+            Segment::Omitted { .. } => None,
+        })
+        .max()
+        .unwrap_or(1);
+    let gutter = Gutter::new(config, last_line, dim_gutter);
+    if let [Segment::Lines { first_line, text }] = segments {
+        return write_content_lines(writer, text, *first_line, gutter.as_ref());
+    }
+
+    for segment in segments {
+        match segment {
+            Segment::Lines { first_line, text } if gutter.is_some() => {
+                write_content_lines(writer, text, *first_line, gutter.as_ref())?;
+            }
+            Segment::Lines { text, .. } => {
+                write!(writer, "{}", text)?;
+                if !text.ends_with('\n') {
+                    writeln!(writer)?;
+                }
+            }
+            Segment::Omitted { marker, .. } => writeln!(writer, "{}", marker)?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes the content of a file: its selected segments, or its lines streamed from disk.
+///
+/// # Arguments
+/// * `writer` - The writer to output the content.
+/// * `entry` - The file being written.
+/// * `config` - The configuration options; `line_numbers` turns on the gutter.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if reading the streamed file
+///   or writing fails.
+fn write_content(writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+    let Some(streamed) = &entry.streamed else {
+        return write_segments(writer, entry.segments(), config, false);
+    };
+    // The lines come out as `write_segments` writes a whole file held in memory.
+    let gutter = Gutter::new(config, streamed.lines.max(1), false);
+    let mut number = 1;
+    stream::for_each_line(&streamed.path, |line| -> Result<()> {
+        match &gutter {
+            Some(gutter) => gutter.write_line(writer, number, stream::line_body(line))?,
+            None => writer.write_all(line.as_bytes())?,
+        }
+        number += 1;
+        Ok(())
+    })
+    .with_context(|| format!("Failed to stream {}", streamed.path.display()))?;
+    if gutter.is_none() {
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// The line-number gutter of one file.
+struct Gutter<'a> {
+    format: &'a LineNumberFormat,
+    width: usize,
+    dim: bool,
+    /// The ending of each numbered line, which replaces the line's own.
+    line_ending: &'static str,
+}
+
+impl Gutter<'_> {
+    /// Returns the gutter of a file, or `None` without line numbers.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options; `line_numbers` turns on the gutter.
+    /// * `last_line` - The largest line number written, which sets the width.
+    /// * `dim` - Whether to dim the line numbers with terminal escapes.
+    fn new(config: &Config, last_line: usize, dim: bool) -> Option<Gutter<'_>> {
+        line_numbers(config).map(|format| Gutter {
+            format,
+            width: format.width(last_line),
+            dim,
+            line_ending: match config.normalize_eol {
+                LineEnding::Crlf => "\r\n",
+                LineEnding::Lf | LineEnding::Keep => "\n",
+            },
+        })
+    }
+
+    /// Writes a line, without its line ending, after its number.
+    fn write_line(&self, writer: &mut dyn Write, number: usize, line: &str) -> io::Result<()> {
+        let number = self.format.gutter(number, self.width);
+        if self.dim {
+            write!(writer, "{DIM}{}{RESET}{}", number, line)?;
+        } else {
+            write!(writer, "{}{}", number, line)?;
+        }
+        writer.write_all(self.line_ending.as_bytes())
+    }
+}
+
+/// Writes content line by line to the writer, optionally including line numbers.
+///
+/// # Arguments
+/// * `writer` - The writer to output the content.
+/// * `content` - The content to write.
+/// * `first_line` - The line number of the first line of `content`.
+/// * `gutter` - The line-number gutter, or `None` to write the content as is.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+fn write_content_lines(
+    writer: &mut dyn Write,
+    content: &str,
+    first_line: usize,
+    gutter: Option<&Gutter>,
+) -> Result<()> {
+    if let Some(gutter) = gutter {
+        for (i, line) in content.lines().enumerate() {
+            gutter.write_line(writer, first_line + i, line)?;
+        }
+    } else {
+        writeln!(writer, "{}", content)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::Value;
+    use crate::{Format, MemorySource, run_source_to_string};
+
+    fn message(role: &str, content: &str) -> Value {
+        Value::object([
+            ("role", Value::String(role.to_string())),
+            ("content", Value::String(content.to_string())),
+        ])
+    }
+
+    fn numbered(segments: &[Segment]) -> String {
+        let config = Config::builder().line_numbers(true).build();
+        let mut written = Vec::new();
+        write_segments(&mut written, segments, &config, false).unwrap();
+        String::from_utf8(written).unwrap()
+    }
+
+    #[test]
+    fn gutters_widen_past_line_9999() {
+        let lines = |first_line, text: &str| Segment::Lines {
+            first_line,
+            text: text.to_string(),
+        };
+        assert_eq!(numbered(&[lines(9_998, "a\nb\n")]), "9998 | a\n9999 | b\n");
+        assert_eq!(
+            numbered(&[lines(9_998, "a\nb\nc\n")]),
+            " 9998 | a\n 9999 | b\n10000 | c\n"
+        );
+        // The width follows the largest number written, across omitted lines.
+        assert_eq!(
+            numbered(&[
+                lines(1, "a\n"),
+                Segment::Omitted {
+                    lines: 99_996,
+                    marker: "...".to_string()
+                },
+                lines(99_998, "b\nc\nd\n")
+            ]),
+            "     1 | a\n...\n 99998 | b\n 99999 | c\n100000 | d\n"
+        );
+    }
+
+    #[test]
+    fn chat_json_escapes_contents_into_valid_messages() {
+        let source: MemorySource = [
+            ("a.rs", "let s = \"quoted\\n\";\n\ttab\r\n"),
+            ("b.md", "Zoë says «héllo» 🦀\n"),
+        ]
+        .into_iter()
+        .collect();
+        let config = |per_file: bool| {
+            Config::builder()
+                .format(Format::ChatJson)
+                .metadata_header(false)
+                .preamble("Review \"this\":")
+                .postamble("Any bugs?")
+                .messages_per_file(per_file)
+                .build()
+        };
+
+        let bundle = run_source_to_string(&config(false), &source).unwrap();
+        let a = "./a.rs\n---\nlet s = \"quoted\\n\";\n\ttab\r\n\n---\n";
+        let b = "./b.md\n---\nZoë says «héllo» 🦀\n\n---\n";
+        let files = format!("{}{}", a, b);
+        let token_counts = |contents: &[&str]| {
+            Value::Array(
+                contents
+                    .iter()
+                    .map(|text| Value::Number(Tokenizer::Approx.count(text).to_string()))
+                    .collect(),
+            )
+        };
+        assert_eq!(
+            json::parse(&bundle).unwrap(),
+            Value::object([
+                (
+                    "messages",
+                    Value::Array(vec![
+                        message("system", "Review \"this\":"),
+                        message("user", &files),
+                        message("user", "Any bugs?"),
+                    ])
+                ),
+                (
+                    "message_tokens",
+                    token_counts(&["Review \"this\":", &files, "Any bugs?"])
+                ),
+            ])
+        );
+
+        let bundle = run_source_to_string(&config(true), &source).unwrap();
+        let Value::Object(fields) = json::parse(&bundle).unwrap() else {
+            panic!("not an object: {}", bundle);
+        };
+        assert_eq!(
+            fields[0].1,
+            Value::Array(vec![
+                message("system", "Review \"this\":"),
+                message("user", a),
+                message("user", b),
+                message("user", "Any bugs?"),
+            ])
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! The YAML front matter at the top of a Markdown bundle (`frontmatter`), for notes apps and
+//! static-site generators that key off it.
+
+use crate::{BundleMetadata, Config};
+
+/// Renders the front matter of a run, between `---` lines.
+///
+/// The fields are `title` (the name of the first input directory), `date` (left out for a
+/// `reproducible` run without `SOURCE_DATE_EPOCH`), `branch` and `commit` (when the input is
+/// in a git repository), `files`, `tokens`, `include`, and `exclude`, followed by the
+/// `frontmatter_fields`. A field there with the name of a built-in one replaces it.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+/// * `metadata` - What produced the bundle.
+/// * `tokens` - The estimated tokens of the files, the preamble, and the postamble.
+///
+/// # Returns
+/// * `String` - The front matter, ending with a blank line.
+pub(crate) fn render(config: &Config, metadata: &BundleMetadata, tokens: usize) -> String {
+    let list = |items: &[String]| {
+        let items: Vec<String> = items.iter().map(|item| quote(item)).collect();
+        format!("[{}]", items.join(", "))
+    };
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut field = |key: &str, value: String| fields.push((key.to_string(), value));
+    field(
+        "title",
+        quote(metadata.directories.first().map_or("", String::as_str)),
+    );
+    if let Some(date) = &metadata.generated_at {
+        field("date", date.clone());
+    }
+    if let Some(branch) = &metadata.git_branch {
+        field("branch", quote(branch));
+    }
+    if let Some(commit) = &metadata.git_commit {
+        field("commit", quote(commit));
+    }
+    field("files", metadata.files.to_string());
+    field("tokens", tokens.to_string());
+    field("include", list(&metadata.include));
+    field("exclude", list(&metadata.exclude));
+    for (key, value) in &config.frontmatter_fields {
+        match fields.iter_mut().find(|(name, _)| name == key) {
+            Some((_, existing)) => *existing = quote(value),
+            None => fields.push((key.clone(), quote(value))),
+        }
+    }
+
+    let mut yaml = String::from("---\n");
+    for (key, value) in fields {
+        yaml.push_str(&format!("{}: {}\n", key_name(&key), value));
+    }
+    yaml.push_str("---\n\n");
+    yaml
+}
+
+/// Writes a key plainly when it is a simple identifier, and quoted otherwise.
+fn key_name(key: &str) -> String {
+    let plain = key
+        .chars()
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if plain { key.to_string() } else { quote(key) }
+}
+
+/// Writes a string as a double-quoted YAML scalar, so no value can be read as a number, a
+/// boolean, or YAML syntax.
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_are_quoted_and_escaped() {
+        assert_eq!(quote("main"), "\"main\"");
+        assert_eq!(quote("yes"), "\"yes\"");
+        assert_eq!(
+            quote("a \"b\"\\c\nd\u{7}"),
+            "\"a \\\"b\\\"\\\\c\\nd\\u0007\""
+        );
+        assert_eq!(key_name("project"), "project");
+        assert_eq!(key_name("my-key_2"), "my-key_2");
+        assert_eq!(key_name("a: b"), "\"a: b\"");
+        assert_eq!(key_name("1st"), "\"1st\"");
+    }
+}
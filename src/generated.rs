@@ -0,0 +1,224 @@
+//! Heuristics that recognize minified and generated files, skipped with `skip_generated`.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// File name patterns of generated files. A leading `*` matches any prefix and a trailing
+/// `*` any suffix.
+const NAME_PATTERNS: &[&str] = &[
+    "*.min.*",
+    "*.map",
+    "*_pb2.py",
+    "*_pb2_grpc.py",
+    "*.pb.go",
+    "*.generated.*",
+];
+
+/// Markers that code generators put in a file's header.
+const MARKERS: &[&str] = &["@generated", "DO NOT EDIT"];
+
+/// How many lines from the start of a file are searched for a marker.
+const MARKER_LINES: usize = 5;
+
+/// Files whose lines are longer than this on average are treated as minified.
+const MAX_AVERAGE_LINE_LENGTH: usize = 500;
+
+/// Files with a single line longer than this are treated as minified.
+const MAX_LINE_LENGTH: usize = 5_000;
+
+/// Why a file was recognized as generated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Generated {
+    /// The file name matches one of the [`NAME_PATTERNS`].
+    Name(&'static str),
+    /// The average line length exceeds [`MAX_AVERAGE_LINE_LENGTH`].
+    AverageLineLength(usize),
+    /// A line is longer than [`MAX_LINE_LENGTH`].
+    LongLine { line: usize, length: usize },
+    /// One of the [`MARKERS`] appears near the start of the file.
+    Marker(&'static str),
+}
+
+impl fmt::Display for Generated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Generated::Name(pattern) => write!(f, "name matches `{}`", pattern),
+            Generated::AverageLineLength(length) => {
+                write!(f, "average line length is {} characters", length)
+            }
+            Generated::LongLine { line, length } => {
+                write!(f, "line {} is {} characters long", line, length)
+            }
+            Generated::Marker(marker) => write!(f, "contains a `{}` marker", marker),
+        }
+    }
+}
+
+/// The error returned for a file skipped as generated, so callers can count it separately.
+#[derive(Debug)]
+pub(crate) struct GeneratedFile {
+    pub(crate) path: PathBuf,
+    pub(crate) reason: Generated,
+}
+
+impl fmt::Display for GeneratedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "generated file {} ({})",
+            self.path.display(),
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for GeneratedFile {}
+
+/// Checks whether a file name matches one of the generated-file patterns.
+///
+/// # Arguments
+/// * `path` - The path of the file.
+///
+/// # Returns
+/// * `Option<Generated>` - The matching pattern, or `None`.
+pub(crate) fn check_name(path: &Path) -> Option<Generated> {
+    let name = path.file_name()?.to_str()?;
+    NAME_PATTERNS
+        .iter()
+        .find(|pattern| {
+            let inner = pattern.strip_prefix('*').unwrap_or(pattern);
+            match inner.strip_suffix('*') {
+                Some(infix) => name.contains(infix),
+                None => name.ends_with(inner),
+            }
+        })
+        .map(|pattern| Generated::Name(pattern))
+}
+
+/// Checks whether a file's content looks minified or carries a generator marker.
+///
+/// # Arguments
+/// * `content` - The content of the file.
+///
+/// # Returns
+/// * `Option<Generated>` - The first rule that matched, or `None`.
+pub(crate) fn check_content(content: &str) -> Option<Generated> {
+    if let Some(marker) = check_markers(content) {
+        return Some(marker);
+    }
+    let mut lines = LineCheck::default();
+    content
+        .lines()
+        .find_map(|line| lines.check(line))
+        .or_else(|| lines.finish(content.len()))
+}
+
+/// Checks whether the first lines of a file carry a generator marker.
+///
+/// # Arguments
+/// * `head` - The content of the file, or its first lines.
+///
+/// # Returns
+/// * `Option<Generated>` - The marker found, or `None`.
+pub(crate) fn check_markers(head: &str) -> Option<Generated> {
+    head.lines()
+        .take(MARKER_LINES)
+        .find_map(|line| MARKERS.iter().find(|marker| line.contains(*marker)))
+        .map(|marker| Generated::Marker(marker))
+}
+
+/// The line-length rules of [`check_content`], applied one line at a time, so a file
+/// streamed into the output is checked as it goes by.
+#[derive(Debug, Default)]
+pub(crate) struct LineCheck {
+    lines: usize,
+}
+
+impl LineCheck {
+    /// Checks the next line of the file, given without its line ending.
+    pub(crate) fn check(&mut self, line: &str) -> Option<Generated> {
+        self.lines += 1;
+        let length = line.chars().count();
+        (length > MAX_LINE_LENGTH).then_some(Generated::LongLine {
+            line: self.lines,
+            length,
+        })
+    }
+
+    /// Checks the average line length once every line has been checked.
+    ///
+    /// # Arguments
+    /// * `len` - The length of the file in bytes.
+    pub(crate) fn finish(&self, len: usize) -> Option<Generated> {
+        let average = len.checked_div(self.lines).unwrap_or(0);
+        (average > MAX_AVERAGE_LINE_LENGTH).then_some(Generated::AverageLineLength(average))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_patterns() {
+        for (path, pattern) in [
+            ("dist/app.min.js", "*.min.*"),
+            ("styles.min.css", "*.min.*"),
+            ("dist/app.js.map", "*.map"),
+            ("api/service_pb2.py", "*_pb2.py"),
+            ("api/service_pb2_grpc.py", "*_pb2_grpc.py"),
+            ("api/service.pb.go", "*.pb.go"),
+            ("client/Api.generated.ts", "*.generated.*"),
+        ] {
+            assert_eq!(
+                check_name(Path::new(path)),
+                Some(Generated::Name(pattern)),
+                "{}",
+                path
+            );
+        }
+        for path in ["src/main.rs", "admin.js", "sitemap.xml", "minify.py"] {
+            assert_eq!(check_name(Path::new(path)), None, "{}", path);
+        }
+    }
+
+    #[test]
+    fn generator_markers_in_the_first_lines() {
+        assert_eq!(
+            check_content("// Code generated by protoc-gen-go. DO NOT EDIT.\npackage api\n"),
+            Some(Generated::Marker("DO NOT EDIT"))
+        );
+        assert_eq!(
+            check_content("#!/bin/sh\n# @generated by build.sh\n"),
+            Some(Generated::Marker("@generated"))
+        );
+        let late_marker = format!("{}// DO NOT EDIT\n", "x = 1\n".repeat(MARKER_LINES));
+        assert_eq!(check_content(&late_marker), None);
+    }
+
+    #[test]
+    fn one_very_long_line() {
+        let content = format!("header\n{}\n", "a".repeat(MAX_LINE_LENGTH + 1));
+        assert_eq!(
+            check_content(&content),
+            Some(Generated::LongLine {
+                line: 2,
+                length: MAX_LINE_LENGTH + 1
+            })
+        );
+        let content = format!("{}{}\n", "short\n".repeat(20), "a".repeat(MAX_LINE_LENGTH));
+        assert_eq!(check_content(&content), None);
+    }
+
+    #[test]
+    fn long_average_line_length() {
+        let content = format!("{}\n", "a".repeat(600)).repeat(3);
+        assert_eq!(
+            check_content(&content),
+            Some(Generated::AverageLineLength(601))
+        );
+        let content = format!("{}\n", "a".repeat(80)).repeat(100);
+        assert_eq!(check_content(&content), None);
+        assert_eq!(check_content(""), None);
+    }
+}
@@ -0,0 +1,372 @@
+//! The git repository enclosing the input, shared by the git-dependent options.
+
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The files that differ between two commits, from [`GitRepo::tree_changes`].
+#[derive(Debug, Default)]
+pub(crate) struct TreeChanges {
+    /// The content at the head commit of each added, modified, or renamed file.
+    pub(crate) changed: BTreeMap<PathBuf, Vec<u8>>,
+    /// The files the head commit no longer has.
+    pub(crate) deleted: Vec<PathBuf>,
+}
+
+/// The repository found once per run and reused for every git lookup.
+#[cfg(feature = "git")]
+pub(crate) struct GitRepo(git2::Repository);
+
+/// A placeholder for builds without the `git` feature; it cannot be created.
+#[cfg(not(feature = "git"))]
+pub(crate) enum GitRepo {}
+
+#[cfg(feature = "git")]
+impl GitRepo {
+    /// Finds the repository that contains the first of the directories inside one.
+    ///
+    /// Like git itself, this walks up from each directory, so a subdirectory of a working
+    /// tree finds the repository at its root.
+    ///
+    /// # Arguments
+    /// * `directories` - The input directories.
+    ///
+    /// # Returns
+    /// * `Result<Option<GitRepo>>` - The repository, or `None` if no directory is in one.
+    pub(crate) fn discover(directories: &[PathBuf]) -> Result<Option<GitRepo>> {
+        Ok(directories
+            .iter()
+            .find_map(|directory| git2::Repository::discover(directory).ok())
+            .map(GitRepo))
+    }
+
+    /// Returns the abbreviated hash of the commit HEAD points at, detached or not.
+    ///
+    /// The hash is as long as git's own abbreviation: seven characters, or more where that
+    /// would be ambiguous.
+    ///
+    /// # Arguments
+    /// * `dirty_suffix` - Whether to append `-dirty` when tracked files have uncommitted
+    ///   changes.
+    ///
+    /// # Returns
+    /// * `Result<String>` - The hash, or an error explaining why there is none, e.g. a
+    ///   repository without commits.
+    pub(crate) fn short_hash(&self, dirty_suffix: bool) -> Result<String> {
+        use anyhow::{Context, anyhow};
+
+        let head = match self.0.head() {
+            Ok(head) => head,
+            Err(err) if err.code() == git2::ErrorCode::UnbornBranch => {
+                return Err(anyhow!("HEAD has no commits yet"));
+            }
+            Err(err) => return Err(err).context("Failed to resolve HEAD"),
+        };
+        let commit = head
+            .peel(git2::ObjectType::Commit)
+            .context("HEAD does not point at a commit")?;
+        let id = commit
+            .short_id()
+            .context("Failed to abbreviate the HEAD commit id")?;
+        let mut hash = id
+            .as_str()
+            .context("The HEAD commit id is not UTF-8")?
+            .to_string();
+        if dirty_suffix && self.is_dirty() {
+            hash.push_str("-dirty");
+        }
+        Ok(hash)
+    }
+
+    /// Returns the full hash of the commit HEAD points at, or `None` if there is no commit.
+    pub(crate) fn head_id(&self) -> Option<String> {
+        let commit = self.0.head().ok()?.peel_to_commit().ok()?;
+        Some(commit.id().to_string())
+    }
+
+    /// Returns the abbreviated hash of the commit a ref such as `main` or `v1.0` resolves to.
+    pub(crate) fn abbreviate(&self, rev: &str) -> Option<String> {
+        let commit = self.0.revparse_single(rev).ok()?.peel_to_commit().ok()?;
+        let id = commit.as_object().short_id().ok()?;
+        id.as_str().map(str::to_string)
+    }
+
+    /// Diffs the trees of two commits, reading each file that differs as it is at `head`.
+    ///
+    /// Renames are detected, so a renamed file is listed under its new path and not as
+    /// deleted. Links and submodules are left out of the changed files.
+    ///
+    /// # Arguments
+    /// * `base` - The ref to compare against, e.g. `main`.
+    /// * `head` - The ref whose files are read, e.g. `release/2.4`.
+    /// * `prefix` - The directory of the working tree to look in; paths are relative to it.
+    ///
+    /// # Returns
+    /// * `Result<TreeChanges>` - The changes, or an error if a ref does not name a commit or
+    ///   both name the same one.
+    pub(crate) fn tree_changes(
+        &self,
+        base: &str,
+        head: &str,
+        prefix: &Path,
+    ) -> Result<TreeChanges> {
+        use anyhow::{Context, bail};
+        use git2::{Delta, FileMode};
+        use tracing::debug;
+
+        let resolve = |rev: &str| {
+            self.0
+                .revparse_single(rev)
+                .and_then(|object| object.peel_to_commit())
+                .with_context(|| {
+                    format!(
+                        "Unknown ref `{}`: not a branch, tag, or commit of the repository",
+                        rev
+                    )
+                })
+        };
+        let (base_commit, head_commit) = (resolve(base)?, resolve(head)?);
+        if base_commit.id() == head_commit.id() {
+            bail!(
+                "{} and {} are the same commit ({}); nothing differs between them",
+                base,
+                head,
+                self.abbreviate(base).unwrap_or_default()
+            );
+        }
+        let mut diff = self
+            .0
+            .diff_tree_to_tree(Some(&base_commit.tree()?), Some(&head_commit.tree()?), None)
+            .with_context(|| format!("Failed to diff {} and {}", base, head))?;
+        diff.find_similar(Some(git2::DiffFindOptions::new().renames(true)))
+            .context("Failed to detect renames")?;
+
+        let mut changes = TreeChanges::default();
+        for delta in diff.deltas() {
+            let (file, deleted) = match delta.status() {
+                Delta::Deleted => (delta.old_file(), true),
+                Delta::Added
+                | Delta::Modified
+                | Delta::Renamed
+                | Delta::Copied
+                | Delta::Typechange => (delta.new_file(), false),
+                _ => continue,
+            };
+            let Some(path) = file.path().and_then(|path| path.strip_prefix(prefix).ok()) else {
+                continue;
+            };
+            if deleted {
+                changes.deleted.push(path.to_path_buf());
+            } else if matches!(file.mode(), FileMode::Blob | FileMode::BlobExecutable) {
+                let blob = self
+                    .0
+                    .find_blob(file.id())
+                    .with_context(|| format!("Failed to read {} at {}", path.display(), head))?;
+                changes
+                    .changed
+                    .insert(path.to_path_buf(), blob.content().to_vec());
+            } else {
+                debug!(
+                    "Skipping {}: not a regular file at {}",
+                    path.display(),
+                    head
+                );
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Checks whether tracked files have staged or unstaged changes; bare repositories never do.
+    fn is_dirty(&self) -> bool {
+        let mut options = git2::StatusOptions::new();
+        options.include_untracked(false).include_ignored(false);
+        self.0
+            .statuses(Some(&mut options))
+            .is_ok_and(|statuses| !statuses.is_empty())
+    }
+
+    /// Returns the name of the checked-out branch, with `/` replaced by `-` so it fits in a
+    /// file name.
+    ///
+    /// # Returns
+    /// * `Result<Option<String>>` - The branch name (`HEAD` when detached), or an error if
+    ///   HEAD cannot be read.
+    pub(crate) fn branch(&self) -> Result<Option<String>> {
+        use anyhow::Context;
+
+        let head = match self.0.head() {
+            Ok(head) => head,
+            // An unborn branch has a name but no commit yet.
+            Err(err) if err.code() == git2::ErrorCode::UnbornBranch => {
+                let head = self
+                    .0
+                    .find_reference("HEAD")
+                    .context("Failed to get repository HEAD")?;
+                let target = head.symbolic_target().unwrap_or("HEAD");
+                let name = target.strip_prefix("refs/heads/").unwrap_or(target);
+                return Ok(Some(name.replace('/', "-")));
+            }
+            Err(err) => return Err(err).context("Failed to get repository HEAD"),
+        };
+        Ok(head.shorthand().map(|name| name.replace('/', "-")))
+    }
+
+    /// Returns the working tree of the repository, or `None` if it is bare.
+    pub(crate) fn workdir(&self) -> Option<&Path> {
+        self.0.workdir()
+    }
+
+    /// Checks whether a path carries the `export-ignore` attribute, as `git archive` would.
+    ///
+    /// The lookup is git's own: the `.gitattributes` of the working tree and the index, the
+    /// nested ones below them, `.git/info/attributes`, and macros all apply, and a pattern
+    /// ending in `/` matches the directory itself.
+    ///
+    /// # Arguments
+    /// * `relative_path` - The path relative to the working tree.
+    ///
+    /// # Returns
+    /// * `bool` - Whether the attribute is set; `false` if it is unset or cannot be read.
+    pub(crate) fn is_export_ignored(&self, relative_path: &Path) -> bool {
+        self.0
+            .get_attr(
+                relative_path,
+                "export-ignore",
+                git2::AttrCheckFlags::FILE_THEN_INDEX,
+            )
+            .is_ok_and(|value| matches!(git2::AttrValue::from_string(value), git2::AttrValue::True))
+    }
+
+    /// Counts the commits reachable from HEAD that touch each path since a point in time.
+    ///
+    /// This is one walk over the history, newest first, diffing each commit against its
+    /// parent and counting every path the diff touches. Merge commits are skipped, as their
+    /// changes are counted on the branches they merge, and the walk stops at the first
+    /// commit older than `since`.
+    ///
+    /// # Arguments
+    /// * `since` - The oldest commit time to count.
+    ///
+    /// # Returns
+    /// * `Result<HashMap<PathBuf, usize>>` - The commit count of each path relative to the
+    ///   working tree, empty without commits; or an error if the history cannot be read.
+    pub(crate) fn commit_counts(&self, since: SystemTime) -> Result<HashMap<PathBuf, usize>> {
+        use anyhow::Context;
+
+        let mut counts = HashMap::new();
+        if self.0.head().is_err() {
+            return Ok(counts);
+        }
+        let since = since
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |age| i64::try_from(age.as_secs()).unwrap_or(i64::MAX));
+        let mut walk = self.0.revwalk().context("Failed to walk the history")?;
+        walk.set_sorting(git2::Sort::TIME)?;
+        walk.push_head()
+            .context("Failed to walk the history from HEAD")?;
+        for id in walk {
+            let commit = self.0.find_commit(id?)?;
+            if commit.time().seconds() < since {
+                break;
+            }
+            if commit.parent_count() > 1 {
+                continue;
+            }
+            let parent = match commit.parents().next() {
+                Some(parent) => Some(parent.tree()?),
+                None => None,
+            };
+            let diff = self
+                .0
+                .diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), None)
+                .with_context(|| format!("Failed to diff commit {}", commit.id()))?;
+            for delta in diff.deltas() {
+                if let Some(path) = delta.new_file().path().or(delta.old_file().path()) {
+                    *counts.entry(path.to_path_buf()).or_insert(0) += 1;
+                }
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Lists the submodules below a directory of the working tree, with whether each is
+    /// initialized (checked out).
+    ///
+    /// # Arguments
+    /// * `directory` - A directory of the working tree, at its root or below it.
+    ///
+    /// # Returns
+    /// * `Result<Vec<(PathBuf, bool)>>` - The directory of each submodule, joined to
+    ///   `directory` as given, and whether it is initialized; or an error if the submodules
+    ///   cannot be read.
+    pub(crate) fn submodules(&self, directory: &Path) -> Result<Vec<(PathBuf, bool)>> {
+        use anyhow::Context;
+
+        let workdir = self
+            .0
+            .workdir()
+            .context("The repository has no working tree")?
+            .canonicalize()?;
+        let directory_path = directory.canonicalize()?;
+        let Ok(within) = directory_path.strip_prefix(&workdir) else {
+            return Ok(Vec::new());
+        };
+        let submodules = self.0.submodules().context("Failed to list submodules")?;
+        Ok(submodules
+            .iter()
+            .filter_map(|submodule| {
+                let path = submodule.path().strip_prefix(within).ok()?;
+                Some((directory.join(path), submodule.open().is_ok()))
+            })
+            .collect())
+    }
+}
+
+#[cfg(not(feature = "git"))]
+impl GitRepo {
+    pub(crate) fn discover(_directories: &[PathBuf]) -> Result<Option<GitRepo>> {
+        anyhow::bail!("Cannot read the git repository: built without git support")
+    }
+
+    pub(crate) fn short_hash(&self, _dirty_suffix: bool) -> Result<String> {
+        match *self {}
+    }
+
+    pub(crate) fn head_id(&self) -> Option<String> {
+        match *self {}
+    }
+
+    pub(crate) fn branch(&self) -> Result<Option<String>> {
+        match *self {}
+    }
+
+    pub(crate) fn abbreviate(&self, _rev: &str) -> Option<String> {
+        match *self {}
+    }
+
+    pub(crate) fn tree_changes(
+        &self,
+        _base: &str,
+        _head: &str,
+        _prefix: &Path,
+    ) -> Result<TreeChanges> {
+        match *self {}
+    }
+
+    pub(crate) fn workdir(&self) -> Option<&Path> {
+        match *self {}
+    }
+
+    pub(crate) fn is_export_ignored(&self, _relative_path: &Path) -> bool {
+        match *self {}
+    }
+
+    pub(crate) fn commit_counts(&self, _since: SystemTime) -> Result<HashMap<PathBuf, usize>> {
+        match *self {}
+    }
+
+    pub(crate) fn submodules(&self, _directory: &Path) -> Result<Vec<(PathBuf, bool)>> {
+        match *self {}
+    }
+}
@@ -0,0 +1,294 @@
+//! `.gitattributes` files: the attributes git assigns to paths, such as GitHub linguist's
+//! `linguist-vendored` and `linguist-generated`.
+//!
+//! Patterns follow gitattributes rules, which differ from `.gitignore` ones: there are no
+//! negated patterns, and a pattern that matches a directory does not match the paths inside
+//! it (`vendor/**` does, `vendor/` matches nothing). A pattern without a `/` matches a file
+//! name at any depth below its `.gitattributes`; any other pattern is relative to it. Later
+//! lines take precedence over earlier ones, and deeper files over shallower ones.
+
+use crate::Config;
+use crate::source::FileSource;
+use globset::{GlobBuilder, GlobMatcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// The name of an attributes file.
+const FILE_NAME: &str = ".gitattributes";
+
+/// The linguist attributes that mark a path as not the project's own source.
+const LINGUIST_ATTRIBUTES: [&str; 2] = ["linguist-vendored", "linguist-generated"];
+
+/// The state of an attribute for a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum State {
+    /// `attr`: set.
+    Set,
+    /// `-attr`: unset.
+    Unset,
+    /// `attr=value`.
+    Value(String),
+}
+
+impl State {
+    /// Checks whether the state is set, or set to `true` as linguist also accepts.
+    fn is_true(&self) -> bool {
+        match self {
+            State::Set => true,
+            State::Unset => false,
+            State::Value(value) => value.eq_ignore_ascii_case("true"),
+        }
+    }
+}
+
+/// One line of a `.gitattributes` file.
+#[derive(Debug)]
+pub(crate) struct Rule {
+    /// The directory of the file, relative to the root of the source.
+    dir: PathBuf,
+    matcher: GlobMatcher,
+    /// Whether the pattern is matched against the path below `dir` rather than the name.
+    anchored: bool,
+    /// The attributes the line assigns; `None` for `!attr`, which makes one unspecified.
+    attributes: Vec<(String, Option<State>)>,
+    /// The line as written, for attributions.
+    pub(crate) line: String,
+    /// The file the line is in, relative to the root of the source.
+    pub(crate) from: PathBuf,
+}
+
+impl Rule {
+    /// Checks whether the rule applies to a path relative to the root of the source.
+    fn matches(&self, relative_path: &Path) -> bool {
+        let Ok(below) = relative_path.strip_prefix(&self.dir) else {
+            return false;
+        };
+        if self.anchored {
+            self.matcher.is_match(below)
+        } else {
+            below
+                .file_name()
+                .is_some_and(|name| self.matcher.is_match(name))
+        }
+    }
+}
+
+/// Parses the lines of a `.gitattributes` file; macro definitions and invalid patterns are
+/// ignored, the latter with a warning.
+///
+/// # Arguments
+/// * `dir` - The directory of the file, relative to the root of the source.
+/// * `text` - The content of the file.
+///
+/// # Returns
+/// * `Vec<Rule>` - The lines that assign attributes, in order.
+pub(crate) fn parse(dir: &Path, text: &str) -> Vec<Rule> {
+    let from = dir.join(FILE_NAME);
+    let mut rules = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("[attr]") {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let Some(pattern) = fields.next() else {
+            continue;
+        };
+        // Negated patterns are forbidden, and patterns ending in `/` only match directories,
+        // which attributes never apply to.
+        if pattern.starts_with('!') || pattern.ends_with('/') {
+            continue;
+        }
+        let attributes: Vec<(String, Option<State>)> = fields
+            .map(|field| {
+                if let Some(name) = field.strip_prefix('-') {
+                    (name.to_string(), Some(State::Unset))
+                } else if let Some(name) = field.strip_prefix('!') {
+                    (name.to_string(), None)
+                } else if let Some((name, value)) = field.split_once('=') {
+                    (name.to_string(), Some(State::Value(value.to_string())))
+                } else {
+                    (field.to_string(), Some(State::Set))
+                }
+            })
+            .collect();
+        let anchored = pattern.contains('/');
+        let glob = pattern.strip_prefix('/').unwrap_or(pattern);
+        let matcher = match GlobBuilder::new(glob).literal_separator(true).build() {
+            Ok(glob) => glob.compile_matcher(),
+            Err(err) => {
+                warn!("Ignoring `{}` in {}: {}", line, from.display(), err);
+                continue;
+            }
+        };
+        rules.push(Rule {
+            dir: dir.to_path_buf(),
+            matcher,
+            anchored,
+            attributes,
+            line: line.to_string(),
+            from: from.clone(),
+        });
+    }
+    rules
+}
+
+/// The `.gitattributes` files of a source, loaded as paths below their directories are
+/// looked up.
+pub(crate) struct Attributes<'a> {
+    source: &'a dyn FileSource,
+    config: &'a Config,
+    /// The rules of each directory, relative to the root of the source; empty without a file.
+    files: HashMap<PathBuf, Vec<Rule>>,
+}
+
+impl<'a> Attributes<'a> {
+    /// Creates the attributes of a source, reading no file yet.
+    pub(crate) fn new(source: &'a dyn FileSource, config: &'a Config) -> Self {
+        Attributes {
+            source,
+            config,
+            files: HashMap::new(),
+        }
+    }
+
+    /// Looks up an attribute of a path.
+    ///
+    /// # Arguments
+    /// * `relative_path` - The path relative to the root of the source.
+    /// * `name` - The attribute, e.g. `linguist-vendored`.
+    ///
+    /// # Returns
+    /// * `Option<(State, &Rule)>` - The state of the attribute and the line that set it, or
+    ///   `None` if it is unspecified.
+    pub(crate) fn lookup(&mut self, relative_path: &Path, name: &str) -> Option<(State, &Rule)> {
+        let dirs: Vec<PathBuf> = relative_path
+            .ancestors()
+            .skip(1)
+            .map(Path::to_path_buf)
+            .collect();
+        for dir in &dirs {
+            self.load(dir);
+        }
+        let mut found = None;
+        for dir in dirs.iter().rev() {
+            for rule in &self.files[dir] {
+                if !rule.matches(relative_path) {
+                    continue;
+                }
+                for (attribute, state) in &rule.attributes {
+                    if attribute == name {
+                        found = state.clone().map(|state| (state, rule));
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// Finds the linguist attribute that marks a path as vendored or generated.
+    ///
+    /// # Arguments
+    /// * `relative_path` - The path relative to the root of the source.
+    ///
+    /// # Returns
+    /// * `Option<(&'static str, &Rule)>` - The attribute and the line that set it, or `None`
+    ///   if the path is the project's own source.
+    pub(crate) fn linguist(&mut self, relative_path: &Path) -> Option<(&'static str, &Rule)> {
+        let name = LINGUIST_ATTRIBUTES.into_iter().find(|name| {
+            self.lookup(relative_path, name)
+                .is_some_and(|(state, _)| state.is_true())
+        })?;
+        let (_, rule) = self.lookup(relative_path, name)?;
+        Some((name, rule))
+    }
+
+    /// Reads the `.gitattributes` file of a directory, once.
+    fn load(&mut self, dir: &Path) {
+        if self.files.contains_key(dir) {
+            return;
+        }
+        let rules = self
+            .source
+            .find(self.config, &dir.join(FILE_NAME))
+            .and_then(|file| match self.source.read(&file) {
+                Ok(text) => Some(text),
+                Err(err) => {
+                    warn!("Ignoring unreadable {}: {}", file.path.display(), err);
+                    None
+                }
+            })
+            .map(|text| parse(dir, &text))
+            .unwrap_or_default();
+        self.files.insert(dir.to_path_buf(), rules);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemorySource;
+
+    fn linguist(source: &MemorySource, path: &str) -> Option<(&'static str, String)> {
+        let config = Config::default();
+        let mut attributes = Attributes::new(source, &config);
+        attributes
+            .linguist(Path::new(path))
+            .map(|(name, rule)| (name, rule.line.clone()))
+    }
+
+    #[test]
+    fn patterns_follow_gitattributes_rules() {
+        let source: MemorySource = [(
+            ".gitattributes",
+            "# vendored code\n\
+             third_party/** linguist-vendored\n\
+             *.pb.go linguist-generated\n\
+             /gen.rs linguist-generated=true\n\
+             vendor/ linguist-vendored\n\
+             docs linguist-vendored\n",
+        )]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            linguist(&source, "third_party/lib/a.c"),
+            Some((
+                "linguist-vendored",
+                "third_party/** linguist-vendored".into()
+            ))
+        );
+        assert!(linguist(&source, "api/v1/user.pb.go").is_some());
+        assert!(linguist(&source, "gen.rs").is_some());
+        assert!(linguist(&source, "src/gen.rs").is_none());
+        // Directory patterns do not reach the files inside.
+        assert!(linguist(&source, "vendor/a.js").is_none());
+        assert!(linguist(&source, "docs/guide.md").is_none());
+        assert!(linguist(&source, "src/main.rs").is_none());
+    }
+
+    #[test]
+    fn later_lines_and_deeper_files_take_precedence() {
+        let source: MemorySource = [
+            (
+                ".gitattributes",
+                "*.js linguist-generated\nsrc/app.js -linguist-generated\n",
+            ),
+            (
+                "web/.gitattributes",
+                "*.js linguist-generated=false\nbundle.js linguist-generated\n",
+            ),
+            ("lib/.gitattributes", "*.js !linguist-generated\n"),
+        ]
+        .into_iter()
+        .collect();
+        assert!(linguist(&source, "dist/out.js").is_some());
+        assert!(linguist(&source, "src/app.js").is_none());
+        assert!(linguist(&source, "web/index.js").is_none());
+        assert_eq!(
+            linguist(&source, "web/bundle.js").map(|(_, line)| line),
+            Some("bundle.js linguist-generated".to_string())
+        );
+        assert!(linguist(&source, "lib/util.js").is_none());
+    }
+}
@@ -0,0 +1,393 @@
+//! GitHub repositories as input (`gh:owner/repo@ref`): the tarball of a ref, downloaded and
+//! unpacked into a temporary directory without git.
+//!
+//! Downloads run the `curl` program, so the `http` feature adds no dependencies.
+
+use crate::RemoteCheckout;
+use anyhow::Result;
+
+/// The prefix of a GitHub input.
+const SCHEME: &str = "gh:";
+
+/// Checks whether an input names a GitHub repository, e.g. `gh:owner/repo@main`.
+///
+/// # Arguments
+/// * `input` - The input given on the command line.
+///
+/// # Returns
+/// * `bool` - `true` if the input should be downloaded from GitHub.
+pub fn is_github_input(input: &str) -> bool {
+    input.starts_with(SCHEME)
+}
+
+/// Downloads the tarball of a GitHub repository and unpacks it into a temporary directory.
+///
+/// A `GITHUB_TOKEN` environment variable is sent with the requests, for private
+/// repositories and a higher rate limit.
+///
+/// # Arguments
+/// * `input` - The repository, as `gh:owner/repo` or `gh:owner/repo@ref`.
+/// * `reference` - The branch, tag, or commit given with `--ref`, or `None` for the one in
+///   the input or else the default branch.
+///
+/// # Returns
+/// * `Result<RemoteCheckout>` - The unpacked tree, or an error telling a missing repository
+///   from a missing ref.
+#[cfg(feature = "http")]
+pub fn fetch_github(input: &str, reference: Option<&str>) -> Result<RemoteCheckout> {
+    use anyhow::{Context, bail};
+    use std::{env, fs};
+    use tracing::info;
+
+    let repo = GithubRepo::parse(input)?;
+    let reference = match (repo.reference.as_deref(), reference) {
+        (Some(named), Some(flag)) if named != flag => {
+            bail!(
+                "{} names the ref {}, but --ref names {}",
+                input,
+                named,
+                flag
+            )
+        }
+        (named, flag) => named.or(flag),
+    };
+    let token = env::var("GITHUB_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty());
+
+    let dir = tempfile::Builder::new()
+        .prefix("codebase-to-prompt-")
+        .tempdir()
+        .context("Failed to create a directory for the download")?;
+    let tarball = dir.path().join("tarball.tar.gz");
+    info!(
+        "Downloading {}/{}@{}",
+        repo.owner,
+        repo.name,
+        reference.unwrap_or("HEAD")
+    );
+    let status = download(&repo.tarball_url(reference), &tarball, token.as_deref())?;
+    if status != 200 {
+        // A 404 for the tarball does not say whether the repository or the ref is missing.
+        let repo_exists = status == 404
+            && reference.is_some()
+            && download(
+                &repo.api_url(),
+                &dir.path().join("repository.json"),
+                token.as_deref(),
+            )? == 200;
+        bail!(
+            "{}",
+            download_error(&repo, reference, status, repo_exists, token.is_some())
+        );
+    }
+
+    let data = fs::read(&tarball).context("Failed to read the downloaded tarball")?;
+    fs::remove_file(&tarball).context("Failed to remove the downloaded tarball")?;
+    // A named subdirectory keeps the walk root from looking hidden and reads well in headers.
+    let path = dir.path().join(&repo.name);
+    unpack(&data, &path).with_context(|| format!("Failed to unpack the tarball of {}", input))?;
+    Ok(RemoteCheckout::new(dir, path))
+}
+
+#[cfg(not(feature = "http"))]
+pub fn fetch_github(input: &str, _reference: Option<&str>) -> Result<RemoteCheckout> {
+    anyhow::bail!("Cannot download {}: built without http support", input)
+}
+
+/// A repository named by a `gh:` input.
+#[cfg(feature = "http")]
+#[derive(Debug, PartialEq, Eq)]
+struct GithubRepo {
+    owner: String,
+    name: String,
+    /// The ref after `@`, if any.
+    reference: Option<String>,
+}
+
+#[cfg(feature = "http")]
+impl GithubRepo {
+    /// Parses `gh:owner/repo` or `gh:owner/repo@ref`; the ref may contain slashes.
+    fn parse(input: &str) -> Result<GithubRepo> {
+        let invalid = || {
+            anyhow::anyhow!(
+                "Invalid GitHub repository `{}`: expected gh:owner/repo or gh:owner/repo@ref",
+                input
+            )
+        };
+        let rest = input.strip_prefix(SCHEME).ok_or_else(invalid)?;
+        let (path, reference) = match rest.split_once('@') {
+            Some((path, reference)) => (path, Some(reference)),
+            None => (rest, None),
+        };
+        let (owner, name) = path.split_once('/').ok_or_else(invalid)?;
+        let name = name.strip_suffix(".git").unwrap_or(name);
+        let valid_name = |part: &str| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+        let valid_ref = |reference: &str| {
+            !reference.is_empty()
+                && !reference
+                    .chars()
+                    .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '?' | '#' | '%'))
+        };
+        if !valid_name(owner) || !valid_name(name) || !reference.is_none_or(valid_ref) {
+            return Err(invalid());
+        }
+        Ok(GithubRepo {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            reference: reference.map(str::to_string),
+        })
+    }
+
+    /// Returns the API URL of the repository.
+    fn api_url(&self) -> String {
+        format!("https://api.github.com/repos/{}/{}", self.owner, self.name)
+    }
+
+    /// Returns the API URL of the tarball of a ref, which redirects to codeload.
+    fn tarball_url(&self, reference: Option<&str>) -> String {
+        match reference {
+            Some(reference) => format!("{}/tarball/{}", self.api_url(), reference),
+            None => format!("{}/tarball", self.api_url()),
+        }
+    }
+}
+
+/// Downloads a URL to a file with `curl`, following redirects.
+///
+/// # Arguments
+/// * `url` - The URL to download.
+/// * `output` - The file the response body is written to.
+/// * `token` - A GitHub token to authenticate with.
+///
+/// # Returns
+/// * `Result<u16>` - The HTTP status of the final response, or an error if `curl` cannot
+///   be run or the connection fails.
+#[cfg(feature = "http")]
+fn download(url: &str, output: &std::path::Path, token: Option<&str>) -> Result<u16> {
+    use anyhow::{Context, bail};
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--location",
+            "--proto",
+            "=https",
+        ])
+        .args(["--header", "@-", "--write-out", "%{http_code}", "--output"])
+        .arg(output)
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run curl, which is needed to download gh: inputs")?;
+    // Headers go through stdin so the token does not show in the process list.
+    let mut headers = format!(
+        "Accept: application/vnd.github+json\nUser-Agent: codebase-to-prompt/{}\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    if let Some(token) = token {
+        headers.push_str(&format!("Authorization: Bearer {}\n", token));
+    }
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(headers.as_bytes())
+            .context("Failed to pass the request headers to curl")?;
+    }
+    let result = child.wait_with_output().context("Failed to run curl")?;
+    if !result.status.success() {
+        bail!(
+            "Failed to download {}: {}",
+            url,
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+    String::from_utf8_lossy(&result.stdout)
+        .trim()
+        .parse()
+        .with_context(|| format!("curl reported no HTTP status for {}", url))
+}
+
+/// Explains a failed tarball download.
+///
+/// # Arguments
+/// * `repo` - The repository.
+/// * `reference` - The requested ref, if any.
+/// * `status` - The HTTP status of the tarball request.
+/// * `repo_exists` - Whether the repository itself was found.
+/// * `has_token` - Whether `GITHUB_TOKEN` was sent.
+///
+/// # Returns
+/// * `String` - The error message.
+#[cfg(feature = "http")]
+fn download_error(
+    repo: &GithubRepo,
+    reference: Option<&str>,
+    status: u16,
+    repo_exists: bool,
+    has_token: bool,
+) -> String {
+    let slug = format!("{}/{}", repo.owner, repo.name);
+    let token_hint = if has_token {
+        ""
+    } else {
+        "; set GITHUB_TOKEN to authenticate"
+    };
+    match (status, reference) {
+        (404, Some(reference)) if repo_exists => {
+            format!("{} has no branch, tag, or commit named {}", slug, reference)
+        }
+        (404, _) => format!(
+            "Repository {} was not found on GitHub (private repositories need a token{})",
+            slug, token_hint
+        ),
+        (401, _) => format!("GitHub rejected GITHUB_TOKEN for {}", slug),
+        (403 | 429, _) => format!(
+            "GitHub refused to send {} (HTTP {}), likely a rate limit{}",
+            slug, status, token_hint
+        ),
+        _ => format!("Failed to download {} from GitHub: HTTP {}", slug, status),
+    }
+}
+
+/// Unpacks a GitHub tarball into a directory, dropping the `owner-repo-sha/` directory
+/// every entry is under.
+///
+/// # Arguments
+/// * `tarball` - The gzip-compressed tar archive.
+/// * `path` - The directory to unpack into.
+///
+/// # Returns
+/// * `Result<()>` - An error if the tarball is corrupt or a file cannot be written.
+#[cfg(feature = "http")]
+fn unpack(tarball: &[u8], path: &std::path::Path) -> Result<()> {
+    use crate::ArchiveSource;
+    use anyhow::Context;
+    use std::fs;
+    use std::path::PathBuf;
+
+    let archive = ArchiveSource::from_tar_gz(tarball)?;
+    fs::create_dir_all(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    for (name, content) in archive.files() {
+        let relative: PathBuf = name.components().skip(1).collect();
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        let target = path.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(&target, content)
+            .with_context(|| format!("Failed to write {}", target.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn github_inputs_are_told_apart_from_paths_and_urls() {
+        assert!(is_github_input("gh:owner/repo"));
+        assert!(is_github_input("gh:owner/repo@v1.0"));
+        assert!(!is_github_input("https://github.com/owner/repo"));
+        assert!(!is_github_input("ghost/repo"));
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn inputs_are_parsed_with_an_optional_ref() {
+        let repo = GithubRepo::parse("gh:rust-lang/rust.git@release/1.80").unwrap();
+        assert_eq!(repo.owner, "rust-lang");
+        assert_eq!(repo.name, "rust");
+        assert_eq!(repo.reference.as_deref(), Some("release/1.80"));
+        assert_eq!(
+            repo.tarball_url(repo.reference.as_deref()),
+            "https://api.github.com/repos/rust-lang/rust/tarball/release/1.80"
+        );
+        assert_eq!(
+            GithubRepo::parse("gh:a/b").unwrap().tarball_url(None),
+            "https://api.github.com/repos/a/b/tarball"
+        );
+        for input in [
+            "gh:owner",
+            "gh:/repo",
+            "gh:a/b/c",
+            "gh:a/b@",
+            "gh:a/b@x?y",
+            "a/b",
+        ] {
+            assert!(GithubRepo::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn missing_repositories_are_told_apart_from_missing_refs() {
+        let repo = GithubRepo::parse("gh:owner/repo").unwrap();
+        assert_eq!(
+            download_error(&repo, Some("v9"), 404, true, false),
+            "owner/repo has no branch, tag, or commit named v9"
+        );
+        assert_eq!(
+            download_error(&repo, Some("v9"), 404, false, false),
+            "Repository owner/repo was not found on GitHub (private repositories need a \
+             token; set GITHUB_TOKEN to authenticate)"
+        );
+        assert!(download_error(&repo, None, 403, false, true).ends_with("likely a rate limit"));
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn tarballs_are_unpacked_without_their_top_directory() {
+        use crate::compress::{Compression, Encoder};
+        use std::io::Write;
+
+        fn tar_entry(name: &str, kind: u8, content: &[u8]) -> Vec<u8> {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[124..135].copy_from_slice(format!("{:011o}", content.len()).as_bytes());
+            header[156] = kind;
+            header[148..156].fill(b' ');
+            let sum: u64 = header.iter().map(|&byte| u64::from(byte)).sum();
+            header[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+            let mut entry = header.to_vec();
+            entry.extend_from_slice(content);
+            entry.resize(entry.len().div_ceil(512) * 512, 0);
+            entry
+        }
+
+        let mut tar = tar_entry("owner-repo-abc123/", b'5', b"");
+        tar.extend(tar_entry(
+            "owner-repo-abc123/src/main.rs",
+            b'0',
+            b"fn main() {}\n",
+        ));
+        tar.extend(tar_entry("owner-repo-abc123/README.md", b'0', b"# Repo\n"));
+        tar.extend_from_slice(&[0; 1024]);
+        let mut encoder = Encoder::new(Compression::Gzip, Vec::new()).unwrap();
+        encoder.write_all(&tar).unwrap();
+        let (tarball, _, _) = encoder.finish().unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("repo");
+        unpack(&tarball, &path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(path.join("src/main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        assert!(path.join("README.md").is_file());
+        assert!(!path.join("owner-repo-abc123").exists());
+    }
+}
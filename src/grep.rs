@@ -0,0 +1,204 @@
+//! Content filters: `grep` keeps only the files whose content matches a pattern.
+
+use crate::Config;
+use crate::transform::{self, Segment};
+use anyhow::{Context, Result};
+use regex_automata::meta::Regex;
+use regex_automata::util::syntax;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::PathBuf;
+
+/// The marker written between the regions of a file with `grep_context`.
+const REGION_MARKER: &str = "…";
+
+/// The compiled `grep` patterns of a run.
+#[derive(Debug)]
+pub(crate) struct Grep {
+    regexes: Vec<Regex>,
+    /// Whether every pattern must match (`grep_and`) rather than any.
+    all: bool,
+}
+
+impl Grep {
+    /// Compiles the `grep` patterns of a run.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<Option<Grep>>` - The patterns, `None` without `grep`, or an error if a
+    ///   pattern is not a valid regular expression.
+    pub(crate) fn for_run(config: &Config) -> Result<Option<Grep>> {
+        if config.grep.is_empty() {
+            return Ok(None);
+        }
+        let regexes = config
+            .grep
+            .iter()
+            .map(|pattern| {
+                Regex::builder()
+                    .syntax(
+                        syntax::Config::new()
+                            .case_insensitive(config.grep_ignore_case)
+                            .multi_line(true),
+                    )
+                    .build(pattern)
+                    .with_context(|| format!("Invalid --grep pattern `{}`", pattern))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Some(Grep {
+            regexes,
+            all: config.grep_and,
+        }))
+    }
+
+    /// Checks whether a file's content matches the patterns.
+    ///
+    /// # Arguments
+    /// * `content` - The decoded content of the file.
+    ///
+    /// # Returns
+    /// * `bool` - Whether any pattern matches, or every pattern with `grep_and`.
+    pub(crate) fn is_match(&self, content: &str) -> bool {
+        if self.all {
+            self.regexes.iter().all(|regex| regex.is_match(content))
+        } else {
+            self.regexes.iter().any(|regex| regex.is_match(content))
+        }
+    }
+
+    /// Selects the lines that match any pattern, each with `context` lines before and after;
+    /// overlapping and adjacent regions are merged.
+    ///
+    /// # Arguments
+    /// * `content` - The decoded content of the file.
+    /// * `context` - How many lines to keep around each matching line.
+    ///
+    /// # Returns
+    /// * `Option<(Vec<Segment>, usize)>` - The regions with a `…` marker between them, and
+    ///   the number of matching lines; `None` if no line matches.
+    pub(crate) fn regions(&self, content: &str, context: usize) -> Option<(Vec<Segment>, usize)> {
+        let total = content.lines().count();
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+        let mut matching = BTreeSet::new();
+        for regex in &self.regexes {
+            for found in regex.find_iter(content) {
+                let last = found.end().saturating_sub(1).max(found.start());
+                matching.extend((line_of(found.start())..=line_of(last)).filter(|&l| l <= total));
+            }
+        }
+        if matching.is_empty() {
+            return None;
+        }
+        let ranges = matching
+            .iter()
+            .map(|&line| {
+                (
+                    line.saturating_sub(context).max(1),
+                    (line + context).min(total),
+                )
+            })
+            .collect();
+        let mut segments = transform::select_ranges(content, &transform::merge_ranges(ranges));
+        for segment in &mut segments {
+            if let Segment::Omitted { marker, .. } = segment {
+                *marker = REGION_MARKER.to_string();
+            }
+        }
+        Some((segments, matching.len()))
+    }
+}
+
+/// The error returned for a file whose content does not match the `grep` patterns, so
+/// callers can count it separately.
+#[derive(Debug)]
+pub(crate) struct NoGrepMatch {
+    pub(crate) path: PathBuf,
+}
+
+impl fmt::Display for NoGrepMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "file not matching --grep: {}", self.path.display())
+    }
+}
+
+impl std::error::Error for NoGrepMatch {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grep(patterns: &[&str], all: bool, ignore_case: bool) -> Grep {
+        let config = Config::builder()
+            .grep(patterns.iter().copied())
+            .grep_and(all)
+            .grep_ignore_case(ignore_case)
+            .build();
+        Grep::for_run(&config).unwrap().unwrap()
+    }
+
+    #[test]
+    fn matches_any_pattern_or_all_of_them() {
+        let content = "let intent = PaymentIntent::create();\nrefund(intent);\n";
+        assert!(grep(&["PaymentIntent", "Invoice"], false, false).is_match(content));
+        assert!(!grep(&["PaymentIntent", "Invoice"], true, false).is_match(content));
+        assert!(grep(&["PaymentIntent", r"^refund\("], true, false).is_match(content));
+    }
+
+    #[test]
+    fn ignores_case_with_the_flag_or_inline() {
+        let content = "paymentintent\n";
+        assert!(!grep(&["PaymentIntent"], false, false).is_match(content));
+        assert!(grep(&["PaymentIntent"], false, true).is_match(content));
+        assert!(grep(&["(?i)PaymentIntent"], false, false).is_match(content));
+    }
+
+    #[test]
+    fn regions_merge_overlapping_context() {
+        let content = "a\nhit\nb\nc\nd\nhit\ne\nf\ng\nh\nhit\n";
+        let (segments, matches) = grep(&["hit"], false, false).regions(content, 1).unwrap();
+        assert_eq!(matches, 3);
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Lines {
+                    first_line: 1,
+                    text: "a\nhit\nb\n".to_string()
+                },
+                Segment::Omitted {
+                    lines: 1,
+                    marker: "…".to_string()
+                },
+                Segment::Lines {
+                    first_line: 5,
+                    text: "d\nhit\ne\n".to_string()
+                },
+                Segment::Omitted {
+                    lines: 2,
+                    marker: "…".to_string()
+                },
+                Segment::Lines {
+                    first_line: 10,
+                    text: "h\nhit\n".to_string()
+                },
+            ]
+        );
+        let (segments, _) = grep(&["hit"], false, false).regions(content, 2).unwrap();
+        assert_eq!(segments.len(), 1);
+        assert!(grep(&["miss"], false, false).regions(content, 2).is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let config = Config::builder().grep(["(unclosed"]).build();
+        let err = Grep::for_run(&config).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Invalid --grep pattern `(unclosed`")
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! The line-number gutter written before each line with `--line-numbers`.
+
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// The text between the line number and the line in the default template.
+pub(crate) const SEPARATOR: &str = " | ";
+
+/// The narrowest automatic gutter, matching the fixed width used before widths were computed.
+const MIN_AUTO_WIDTH: usize = 4;
+
+/// How line numbers are written, parsed from a template such as `{n} | ` or `{n:>5}→ `.
+///
+/// The template holds one `{n}` placeholder, optionally with an alignment (`>` right, the
+/// default, or `<` left) and a width. Without a width, the number column is as wide as the
+/// largest line number of the file, and at least four columns. The text around the
+/// placeholder is written as is.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct LineNumberFormat {
+    prefix: String,
+    suffix: String,
+    left_align: bool,
+    width: Option<usize>,
+}
+
+impl LineNumberFormat {
+    /// Returns the width of the number column for a file.
+    ///
+    /// # Arguments
+    /// * `last_line` - The largest line number written for the file.
+    ///
+    /// # Returns
+    /// * `usize` - The fixed width of the template, or the automatic width.
+    pub(crate) fn width(&self, last_line: usize) -> usize {
+        self.width.unwrap_or_else(|| auto_width(last_line))
+    }
+
+    /// Formats the gutter of one line.
+    ///
+    /// # Arguments
+    /// * `line` - The 1-based line number in the original file.
+    /// * `width` - The width of the number column, from [`width`](LineNumberFormat::width).
+    ///
+    /// # Returns
+    /// * `String` - The gutter, to be followed by the line.
+    pub(crate) fn gutter(&self, line: usize, width: usize) -> String {
+        if self.left_align {
+            format!("{}{:<width$}{}", self.prefix, line, self.suffix)
+        } else {
+            format!("{}{:>width$}{}", self.prefix, line, self.suffix)
+        }
+    }
+}
+
+impl Default for LineNumberFormat {
+    fn default() -> Self {
+        LineNumberFormat {
+            prefix: String::new(),
+            suffix: SEPARATOR.to_string(),
+            left_align: false,
+            width: None,
+        }
+    }
+}
+
+impl FromStr for LineNumberFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("invalid line number format `{}`: {}", s, reason);
+        let start = s
+            .find("{n")
+            .ok_or_else(|| invalid("missing the `{n}` placeholder"))?;
+        let len = s[start..]
+            .find('}')
+            .ok_or_else(|| invalid("unclosed placeholder"))?;
+        let (prefix, suffix) = (&s[..start], &s[start + len + 1..]);
+        if suffix.contains("{n") {
+            return Err(invalid("more than one `{n}` placeholder"));
+        }
+
+        let spec = &s[start + 2..start + len];
+        let spec = match spec.strip_prefix(':') {
+            Some(spec) => spec,
+            None if spec.is_empty() => spec,
+            None => return Err(invalid("expected `{n}` or `{n:>width}`")),
+        };
+        let (left_align, width) = match spec.strip_prefix('<') {
+            Some(width) => (true, width),
+            None => (false, spec.strip_prefix('>').unwrap_or(spec)),
+        };
+        let width = match width {
+            "" => None,
+            width => Some(
+                width
+                    .parse()
+                    .map_err(|_| invalid("the width must be a number"))?,
+            ),
+        };
+        Ok(LineNumberFormat {
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+            left_align,
+            width,
+        })
+    }
+}
+
+impl TryFrom<String> for LineNumberFormat {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for LineNumberFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let align = if self.left_align { "<" } else { ">" };
+        match self.width {
+            Some(width) => write!(f, "{}{{n:{}{}}}{}", self.prefix, align, width, self.suffix),
+            None if self.left_align => write!(f, "{}{{n:<}}{}", self.prefix, self.suffix),
+            None => write!(f, "{}{{n}}{}", self.prefix, self.suffix),
+        }
+    }
+}
+
+impl Serialize for LineNumberFormat {
+    /// Serializes as the string form read from configuration files.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Returns the automatic width of the number column: the digits of the largest line number,
+/// but at least four columns.
+///
+/// # Arguments
+/// * `last_line` - The largest line number written for the file.
+///
+/// # Returns
+/// * `usize` - The width in columns.
+pub(crate) fn auto_width(last_line: usize) -> usize {
+    let digits = last_line.checked_ilog10().map_or(1, |log| log as usize + 1);
+    digits.max(MIN_AUTO_WIDTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_width_grows_with_the_line_count() {
+        for (lines, width) in [
+            (9, 4),
+            (10, 4),
+            (99, 4),
+            (100, 4),
+            (9_999, 4),
+            (10_000, 5),
+            (100_000, 6),
+        ] {
+            assert_eq!(auto_width(lines), width, "{} lines", lines);
+        }
+    }
+
+    #[test]
+    fn templates_parse_alignment_and_width() {
+        let default = LineNumberFormat::default();
+        assert_eq!(default.gutter(7, default.width(12)), "   7 | ");
+        assert_eq!(default.gutter(7, default.width(12_345)), "    7 | ");
+
+        let arrow: LineNumberFormat = "{n:>5}→ ".parse().unwrap();
+        assert_eq!(arrow.gutter(42, arrow.width(100_000)), "   42→ ");
+        let left: LineNumberFormat = "L{n:<3}: ".parse().unwrap();
+        assert_eq!(left.gutter(5, left.width(9)), "L5  : ");
+
+        for template in ["{n} | ", "{n:>5}→ ", "L{n:<3}: ", "{n:<}"] {
+            let format: LineNumberFormat = template.parse().unwrap();
+            assert_eq!(format.to_string(), template);
+        }
+        assert!("| ".parse::<LineNumberFormat>().is_err());
+        assert!("{n:x}".parse::<LineNumberFormat>().is_err());
+        assert!("{n} {n}".parse::<LineNumberFormat>().is_err());
+    }
+}
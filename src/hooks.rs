@@ -0,0 +1,160 @@
+//! Callbacks that let library users observe and veto files while a bundle is written.
+
+use crate::FileEntry;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// What to do with a file that passed the filters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileAction {
+    /// Write the file as usual.
+    Include,
+    /// Leave the file out of the bundle.
+    Skip,
+    /// Write the given text in place of the file's content.
+    Replace(String),
+}
+
+/// Why a file was left out of the bundle.
+///
+/// Serialized as its [`name`](SkipReason::name), e.g. `too_large`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// The file is not valid UTF-8 text.
+    Binary,
+    /// The file is larger than `max_file_size`.
+    TooLarge,
+    /// The file is hidden, failed the include/exclude filters, or was left blank.
+    Filtered,
+    /// The file is ignored by `.gitignore`, `.ignore`, or `.rgignore` rules.
+    Ignored,
+    /// The file is ignored by a `.c2pignore` or the `ignore_file` of the configuration.
+    IgnoreFile,
+    /// The file could not be read.
+    ReadError,
+    /// The file is a lockfile, excluded unless `include_lockfiles` is set.
+    Lockfile,
+    /// The file looks like it holds secrets, such as a `.env` file or a private key,
+    /// excluded unless `include_sensitive` is set.
+    Sensitive,
+    /// The file looks minified or generated, skipped when `skip_generated` is set.
+    Generated,
+    /// The file is a test file left out by `no_tests`, or any other file with `only_tests`.
+    Tests,
+    /// The file was modified outside the `newer_than` and `older_than` window.
+    Modified,
+    /// The file is empty or whitespace-only, skipped when `skip_empty` is set.
+    Empty,
+    /// The file is smaller than `min_file_size`.
+    TooSmall,
+    /// The file was left out to fit the bundle in `fit_budget`.
+    OverBudget,
+    /// The file opted out with a `codebase-to-prompt:ignore` marker near its start.
+    OptOut,
+    /// The file is a Jupyter notebook that could not be parsed.
+    Malformed,
+    /// The file's content does not match the `grep` patterns.
+    NoMatch,
+    /// The file was left out because `max_files` files were already included.
+    OverLimit,
+    /// The file is marked `linguist-vendored` or `linguist-generated` in a `.gitattributes`,
+    /// skipped when `respect_linguist` is set.
+    Linguist,
+    /// The file or directory has the `export-ignore` attribute, skipped when
+    /// `respect_export_ignore` is set.
+    ExportIgnore,
+    /// The file is not an `entry` file, nor imported by one with `follow_imports`.
+    Unreachable,
+    /// The `filter_cmd` of the file failed, timed out, or wrote output that is not UTF-8.
+    FilterFailed,
+}
+
+impl SkipReason {
+    /// Returns the name of the reason, e.g. `lockfile`, as written in the stats CSV.
+    pub fn name(self) -> &'static str {
+        match self {
+            SkipReason::Binary => "binary",
+            SkipReason::TooLarge => "too_large",
+            SkipReason::Filtered => "filtered",
+            SkipReason::Ignored => "ignored",
+            SkipReason::IgnoreFile => "ignore_file",
+            SkipReason::ReadError => "read_error",
+            SkipReason::Lockfile => "lockfile",
+            SkipReason::Sensitive => "sensitive",
+            SkipReason::Generated => "generated",
+            SkipReason::Tests => "tests",
+            SkipReason::Modified => "modified",
+            SkipReason::OverBudget => "over_budget",
+            SkipReason::OptOut => "opt_out",
+            SkipReason::Malformed => "malformed",
+            SkipReason::NoMatch => "no_match",
+            SkipReason::OverLimit => "over_limit",
+            SkipReason::Linguist => "linguist",
+            SkipReason::ExportIgnore => "export_ignore",
+            SkipReason::Unreachable => "unreachable",
+            SkipReason::FilterFailed => "filter_failed",
+            SkipReason::Empty => "empty",
+            SkipReason::TooSmall => "too_small",
+        }
+    }
+}
+
+/// Callbacks invoked by [`run_with_hooks`](crate::run_with_hooks).
+///
+/// Every method has a default, so implementors only override what they need.
+/// Callbacks run on the thread that writes the output, in output order.
+pub trait Hooks {
+    /// Called for each file after filtering and transformations, before it is written.
+    ///
+    /// # Arguments
+    /// * `entry` - The file about to be written.
+    ///
+    /// # Returns
+    /// * `FileAction` - Whether to write, skip, or replace the file.
+    fn on_file(&mut self, entry: &FileEntry) -> FileAction {
+        let _ = entry;
+        FileAction::Include
+    }
+
+    /// Returns whether [`on_file`](Hooks::on_file) looks at the content of files. When it
+    /// does not, a large text file that is written unchanged is streamed into the output
+    /// from disk rather than read whole, and comes to `on_file` with an empty `content` and
+    /// no segments.
+    ///
+    /// # Returns
+    /// * `bool` - `true` by default.
+    fn needs_content(&self) -> bool {
+        true
+    }
+
+    /// Called for each file or directory left out of the bundle.
+    ///
+    /// # Arguments
+    /// * `path` - The path that was skipped; pruned directories are reported once.
+    /// * `reason` - Why it was skipped.
+    fn on_skipped(&mut self, path: &Path, reason: SkipReason) {
+        let _ = (path, reason);
+    }
+
+    /// Called before each file is written; returning `true` stops the run there. The files
+    /// written so far are finished as a complete bundle, and the summary is marked
+    /// [`interrupted`](crate::RunSummary::interrupted).
+    ///
+    /// # Returns
+    /// * `bool` - `true` to stop; by default, whether Ctrl-C was pressed once
+    ///   [`install_interrupt_handler`](crate::install_interrupt_handler) is installed.
+    fn cancelled(&mut self) -> bool {
+        crate::interrupt::requested()
+    }
+}
+
+/// Hooks that include every file, used by the plain entry points.
+pub(crate) struct NoHooks;
+
+impl Hooks for NoHooks {
+    fn needs_content(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,265 @@
+//! Scaffolding a starter config file (`--init`) from what a directory holds.
+
+use crate::lockfiles::DEFAULT_LOCKFILES;
+use crate::{CONFIG_FILE_NAMES, Config, LanguageCount, find_config_file, list_extensions};
+use anyhow::{Context, Result, bail};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directories that hold build output, dependencies, or caches rather than source, and why.
+const JUNK_DIRS: &[(&str, &str)] = &[
+    ("target", "Rust or Maven build output"),
+    ("node_modules", "npm dependencies"),
+    ("dist", "build output"),
+    ("build", "build output"),
+    ("vendor", "vendored dependencies"),
+    (".venv", "a Python virtual environment"),
+    ("venv", "a Python virtual environment"),
+    ("__pycache__", "Python bytecode"),
+    (".tox", "tox environments"),
+    (".next", "Next.js build output"),
+    (".gradle", "Gradle caches"),
+    (".terraform", "Terraform providers"),
+    ("coverage", "coverage reports"),
+];
+
+/// How deep the search for junk directories and lockfiles goes below the directory.
+const MAX_DEPTH: usize = 4;
+
+/// The share of the bytes in known languages below which a language is not included.
+const MIN_LANGUAGE_SHARE: f64 = 0.01;
+
+/// What `--init` found in a directory, and the config file it wrote.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct InitReport {
+    /// The config file written.
+    pub path: PathBuf,
+    /// The languages included, the largest total size first.
+    pub languages: Vec<LanguageCount>,
+    /// The languages found but left out, as too small a part of the code.
+    pub minor_languages: Vec<LanguageCount>,
+    /// The junk directory names found, and why each is left out.
+    pub exclude_dirs: Vec<(&'static str, &'static str)>,
+    /// The lockfiles found, relative to the directory.
+    pub lockfiles: Vec<PathBuf>,
+}
+
+/// Inspects a directory and writes a commented starter config file to it.
+///
+/// The file is `.c2p.toml`, or the config file the directory already has when `force` is
+/// set. It includes the extensions of the main languages found, lists the junk directories
+/// found in `exclude_dirs`, writes Markdown, and respects `.gitignore`.
+///
+/// # Arguments
+/// * `directory` - The directory to inspect.
+/// * `force` - Whether an existing config file is overwritten.
+///
+/// # Returns
+/// * `Result<InitReport>` - What was found and where it was written, or an error if the
+///   directory has a config file and `force` is not set, or it cannot be read or written.
+pub fn init_config(directory: &Path, force: bool) -> Result<InitReport> {
+    if !directory.is_dir() {
+        bail!("{} is not a directory", directory.display());
+    }
+    let existing = find_config_file(directory);
+    if let Some(existing) = existing.as_ref().filter(|_| !force) {
+        bail!(
+            "{} already exists; pass --force to overwrite",
+            existing.display()
+        );
+    }
+    let path = existing.unwrap_or_else(|| directory.join(CONFIG_FILE_NAMES[1]));
+
+    let mut exclude_dirs = Vec::new();
+    let mut lockfiles = Vec::new();
+    search(
+        directory,
+        Path::new(""),
+        0,
+        &mut exclude_dirs,
+        &mut lockfiles,
+    )?;
+    exclude_dirs.sort();
+    exclude_dirs.dedup();
+    lockfiles.sort();
+
+    let census = Config {
+        directories: vec![directory.to_path_buf()],
+        exclude_dirs: exclude_dirs
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect(),
+        // A config file from an earlier run is not part of the code.
+        exclude_paths: CONFIG_FILE_NAMES.iter().map(PathBuf::from).collect(),
+        ..Config::default()
+    };
+    let languages = list_extensions(&census)?.languages();
+    let total: u64 = languages.iter().map(|count| count.bytes).sum();
+    let (languages, minor_languages) = languages
+        .into_iter()
+        .partition(|count| count.bytes as f64 >= total as f64 * MIN_LANGUAGE_SHARE);
+
+    let report = InitReport {
+        path,
+        languages,
+        minor_languages,
+        exclude_dirs,
+        lockfiles,
+    };
+    fs::write(&report.path, report.config_toml())
+        .with_context(|| format!("Failed to write config file: {}", report.path.display()))?;
+    Ok(report)
+}
+
+/// Looks for junk directories and lockfiles below a directory, without descending into
+/// junk or hidden directories.
+fn search(
+    root: &Path,
+    relative: &Path,
+    depth: usize,
+    exclude_dirs: &mut Vec<(&'static str, &'static str)>,
+    lockfiles: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let directory = root.join(relative);
+    let entries = fs::read_dir(&directory)
+        .with_context(|| format!("Failed to read directory: {}", directory.display()))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if let Some(junk) = JUNK_DIRS.iter().find(|(junk, _)| *junk == name) {
+                exclude_dirs.push(*junk);
+            } else if !name.starts_with('.') && depth + 1 < MAX_DEPTH {
+                search(
+                    root,
+                    &relative.join(&*name),
+                    depth + 1,
+                    exclude_dirs,
+                    lockfiles,
+                )?;
+            }
+        } else if DEFAULT_LOCKFILES.contains(&&*name) {
+            lockfiles.push(relative.join(&*name));
+        }
+    }
+    Ok(())
+}
+
+/// Writes a list of strings as a TOML array.
+fn toml_array<'a>(items: impl IntoIterator<Item = &'a str>) -> String {
+    let items: Vec<String> = items
+        .into_iter()
+        .map(|item| format!("\"{}\"", item))
+        .collect();
+    format!("[{}]", items.join(", "))
+}
+
+impl InitReport {
+    /// Returns the `include` entries: the extensions of the included languages.
+    pub fn include(&self) -> Vec<&str> {
+        self.languages
+            .iter()
+            .flat_map(|count| count.extensions.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Renders the config file, with a comment on each option.
+    fn config_toml(&self) -> String {
+        let titles = |languages: &[LanguageCount]| {
+            let titles: Vec<&str> = languages.iter().map(|count| count.language.title).collect();
+            titles.join(", ")
+        };
+        let mut toml = String::from(
+            "# codebase-to-prompt configuration, written by `codebase-to-prompt --init`.\n\
+             # Every option can also be given as a flag; see `codebase-to-prompt --help`.\n\n",
+        );
+        if self.languages.is_empty() {
+            toml.push_str("# No known language was found, so every file is included.\n");
+            toml.push_str("include = []\n\n");
+        } else {
+            toml.push_str(&format!(
+                "# The extensions of the languages found: {}.\n",
+                titles(&self.languages)
+            ));
+            if !self.minor_languages.is_empty() {
+                toml.push_str(&format!(
+                    "# Also found, but too little to include: {}.\n",
+                    titles(&self.minor_languages)
+                ));
+            }
+            toml.push_str(&format!("include = {}\n\n", toml_array(self.include())));
+        }
+        if self.exclude_dirs.is_empty() {
+            toml.push_str("# No build output or dependency directories were found.\n");
+            toml.push_str("exclude_dirs = []\n\n");
+        } else {
+            toml.push_str("# Build output, dependencies, and caches found:\n");
+            for (name, why) in &self.exclude_dirs {
+                toml.push_str(&format!("#   {}: {}\n", name, why));
+            }
+            toml.push_str(&format!(
+                "exclude_dirs = {}\n\n",
+                toml_array(self.exclude_dirs.iter().map(|(name, _)| *name))
+            ));
+        }
+        toml.push_str("format = \"markdown\"\n\n");
+        toml.push_str("# Leave out the files git ignores.\n");
+        toml.push_str("respect_gitignore = true\n");
+        if !self.lockfiles.is_empty() {
+            let names: Vec<String> = self
+                .lockfiles
+                .iter()
+                .map(|path| path.to_string_lossy().replace('\\', "/"))
+                .collect();
+            toml.push_str(&format!(
+                "\n# Lockfiles ({}) are left out by default; uncomment to bundle them.\n",
+                names.join(", ")
+            ));
+            toml.push_str("# include_lockfiles = true\n");
+        }
+        toml
+    }
+}
+
+impl fmt::Display for InitReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.languages.is_empty() {
+            writeln!(f, "No known language found; including every file")?;
+        }
+        for count in &self.languages {
+            writeln!(
+                f,
+                "Including {} ({}): {} files, {} bytes",
+                count.language.title,
+                count.extensions.join(", "),
+                count.files,
+                count.bytes
+            )?;
+        }
+        for count in &self.minor_languages {
+            writeln!(
+                f,
+                "Not including {} ({}): only {} bytes",
+                count.language.title,
+                count.extensions.join(", "),
+                count.bytes
+            )?;
+        }
+        for (name, why) in &self.exclude_dirs {
+            writeln!(f, "Excluding {}/: {}", name, why)?;
+        }
+        for lockfile in &self.lockfiles {
+            writeln!(
+                f,
+                "Found lockfile {}: left out unless include_lockfiles is set",
+                lockfile.display()
+            )?;
+        }
+        writeln!(f, "Wrote {}", self.path.display())
+    }
+}
@@ -0,0 +1,45 @@
+//! Clean shutdown on Ctrl-C: the first interrupt asks the run, watch loop, or server to
+//! stop at the next file or request; a second one quits at once.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the first interrupt.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// The exit status of a process stopped by an interrupt: 128 plus `SIGINT`.
+pub const INTERRUPTED_EXIT_CODE: u8 = 130;
+
+/// Makes Ctrl-C request a clean stop instead of killing the process.
+///
+/// Runs then stop before the next file and finish the output with what was written so far
+/// (see [`RunSummary::interrupted`](crate::RunSummary::interrupted)), watch mode stops
+/// between builds, and the servers stop taking requests. A second Ctrl-C exits at once
+/// with [`INTERRUPTED_EXIT_CODE`]. Installing the handler again has no further effect.
+#[cfg(unix)]
+pub fn install_interrupt_handler() {
+    extern "C" fn handle(_: libc::c_int) {
+        if REQUESTED.swap(true, Ordering::SeqCst) {
+            // SAFETY: `_exit` is async-signal-safe.
+            unsafe { libc::_exit(i32::from(INTERRUPTED_EXIT_CODE)) };
+        }
+        let message = b"\nInterrupted; stopping (press Ctrl-C again to quit now)\n";
+        // SAFETY: `write` is async-signal-safe, and the buffer outlives the call.
+        unsafe { libc::write(libc::STDERR_FILENO, message.as_ptr().cast(), message.len()) };
+    }
+    // SAFETY: the handler only touches an atomic and calls async-signal-safe functions.
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            handle as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+/// Ctrl-C keeps its default behavior on platforms without POSIX signals.
+#[cfg(not(unix))]
+pub fn install_interrupt_handler() {}
+
+/// Checks whether an interrupt asked the process to stop.
+pub(crate) fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
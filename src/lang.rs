@@ -0,0 +1,199 @@
+//! Code-fence languages for the Markdown format, so viewers can highlight each file.
+
+use crate::languages::{LANGUAGES, Language};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Checks whether an extensionless file has a well-known name, such as `Makefile`.
+///
+/// # Arguments
+/// * `name` - The file name.
+///
+/// # Returns
+/// * `bool` - `true` if the name is in the built-in table.
+pub(crate) fn is_known_file_name(name: &str) -> bool {
+    Language::by_file_name(name).is_some()
+}
+
+/// An `<ext>=<lang>` rule that sets the code-fence language of files with an extension.
+///
+/// For files without an extension, `ext` is matched against the file name instead, e.g.
+/// `Containerfile=dockerfile`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct LangMapping {
+    extension: String,
+    language: String,
+}
+
+impl LangMapping {
+    /// Returns the language the rule maps to.
+    pub(crate) fn language(&self) -> &str {
+        &self.language
+    }
+}
+
+impl FromStr for LangMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (extension, language) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid language mapping `{}` (expected <ext>=<lang>)", s))?;
+        let extension = extension.trim().trim_start_matches('.');
+        let language = language.trim();
+        if extension.is_empty() || language.is_empty() {
+            return Err(format!(
+                "invalid language mapping `{}` (both sides must be non-empty)",
+                s
+            ));
+        }
+        Ok(LangMapping {
+            extension: extension.to_string(),
+            language: language.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for LangMapping {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for LangMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.extension, self.language)
+    }
+}
+
+impl Serialize for LangMapping {
+    /// Serializes as the string form read from configuration files.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Returns the code-fence language of a file.
+///
+/// The last matching rule in `overrides` wins, then the built-in [`LANGUAGES`] table
+/// applies, and unknown extensions are used as is.
+///
+/// # Arguments
+/// * `path` - The path of the file.
+/// * `overrides` - The `--lang-map` rules.
+///
+/// # Returns
+/// * `&str` - The language, or an empty string for unknown files without an extension.
+pub(crate) fn fence_language<'a>(path: &'a Path, overrides: &'a [LangMapping]) -> &'a str {
+    let extension = path.extension().and_then(|s| s.to_str());
+    let key = extension.or_else(|| path.file_name().and_then(|s| s.to_str()));
+    let Some(key) = key else {
+        return "";
+    };
+    if let Some(mapping) = overrides
+        .iter()
+        .rev()
+        .find(|mapping| mapping.extension.eq_ignore_ascii_case(key))
+    {
+        return &mapping.language;
+    }
+    let builtin = match extension {
+        Some(extension) => Language::by_extension(extension),
+        None => Language::by_file_name(key),
+    };
+    match builtin {
+        Some(language) => language.fence,
+        None => extension.unwrap_or(""),
+    }
+}
+
+/// Returns the name of the language section a file belongs to with `group_by` `lang`.
+///
+/// # Arguments
+/// * `path` - The path of the file.
+/// * `overrides` - The `--lang-map` rules.
+///
+/// # Returns
+/// * `String` - The title of the file's code-fence language, such as `TypeScript`, the
+///   language or extension itself if it is not a built-in one, or `Other` for unknown files
+///   without an extension.
+pub(crate) fn section_name(path: &Path, overrides: &[LangMapping]) -> String {
+    match fence_language(path, overrides) {
+        "" => "Other".to_string(),
+        fence => LANGUAGES
+            .iter()
+            .find(|language| language.fence == fence)
+            .map_or(fence, |language| language.title)
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_languages() {
+        for (path, language) in [
+            ("src/main.rs", "rust"),
+            ("ci.yml", "yaml"),
+            ("App.tsx", "typescript"),
+            ("App.jsx", "javascript"),
+            ("include/api.h", "c"),
+            ("include/api.hpp", "cpp"),
+            ("Main.kt", "kotlin"),
+            ("View.m", "objectivec"),
+            ("README.MD", "markdown"),
+            ("Dockerfile", "dockerfile"),
+            ("Makefile", "makefile"),
+            ("config.json", "json"),
+            ("notes.unknownext", "unknownext"),
+            ("LICENSE", ""),
+        ] {
+            assert_eq!(fence_language(Path::new(path), &[]), language, "{}", path);
+        }
+    }
+
+    #[test]
+    fn overrides_win_over_the_builtin_map() {
+        let overrides: Vec<LangMapping> = ["h=cpp", ".tpl=jinja", "Containerfile=dockerfile"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(fence_language(Path::new("api.h"), &overrides), "cpp");
+        assert_eq!(fence_language(Path::new("page.TPL"), &overrides), "jinja");
+        assert_eq!(
+            fence_language(Path::new("Containerfile"), &overrides),
+            "dockerfile"
+        );
+        assert!("h".parse::<LangMapping>().is_err());
+        assert!("h=".parse::<LangMapping>().is_err());
+    }
+
+    #[test]
+    fn section_names_are_language_titles() {
+        let overrides: Vec<LangMapping> = vec!["tpl=jinja".parse().unwrap()];
+        for (path, section) in [
+            ("src/main.rs", "Rust"),
+            ("web/App.tsx", "TypeScript"),
+            ("include/api.h", "C"),
+            ("Cargo.toml", "TOML"),
+            ("deploy/Dockerfile", "Docker"),
+            ("page.tpl", "jinja"),
+            ("styles.scss", "scss"),
+            ("LICENSE", "Other"),
+        ] {
+            assert_eq!(
+                section_name(Path::new(path), &overrides),
+                section,
+                "{}",
+                path
+            );
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Per-language line counts of the included files (`lang_stats`), like `tokei`: files,
+//! blank lines, comment lines, and code lines, for the metadata header.
+
+use crate::FileEntry;
+use crate::comments::{self, Syntax, Token};
+use crate::lang::{LangMapping, section_name};
+use crate::languages::LANGUAGES;
+use crate::transform::Segment;
+use serde::{Deserialize, Serialize};
+
+/// The group of files whose language is not a built-in or `lang_map` one.
+const OTHER: &str = "Other";
+
+/// The line counts of one language, in [`BundleMetadata::languages`](crate::BundleMetadata::languages).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct LanguageStats {
+    /// The language, e.g. `Rust`, or `Other` for unknown languages.
+    pub language: String,
+    /// Number of files.
+    pub files: usize,
+    /// Number of blank lines.
+    pub blank: usize,
+    /// Number of lines with only comments.
+    pub comment: usize,
+    /// Number of lines with code.
+    pub code: usize,
+}
+
+impl LanguageStats {
+    /// Returns the row of a table, in the order of [`TABLE_HEADER`].
+    pub(crate) fn row(&self) -> [String; 5] {
+        [
+            self.language.clone(),
+            self.files.to_string(),
+            self.blank.to_string(),
+            self.comment.to_string(),
+            self.code.to_string(),
+        ]
+    }
+}
+
+/// The column names of the language table.
+pub(crate) const TABLE_HEADER: [&str; 5] = ["Language", "Files", "Blank", "Comment", "Code"];
+
+/// Returns the rows of the language table: one per language, then the total.
+///
+/// # Arguments
+/// * `stats` - The counts of each language.
+///
+/// # Returns
+/// * `Vec<[String; 5]>` - The rows, without the header.
+pub(crate) fn table_rows(stats: &[LanguageStats]) -> Vec<[String; 5]> {
+    let total = stats.iter().fold(
+        LanguageStats {
+            language: "Total".to_string(),
+            ..LanguageStats::default()
+        },
+        |total, stats| LanguageStats {
+            files: total.files + stats.files,
+            blank: total.blank + stats.blank,
+            comment: total.comment + stats.comment,
+            code: total.code + stats.code,
+            ..total
+        },
+    );
+    stats
+        .iter()
+        .chain([&total])
+        .map(LanguageStats::row)
+        .collect()
+}
+
+/// Adds up the lines of the files of a run by language.
+#[derive(Debug, Default)]
+pub(crate) struct LanguageCounter {
+    stats: Vec<LanguageStats>,
+}
+
+impl LanguageCounter {
+    /// Counts the lines of a file as written: its selected lines, after transformations.
+    ///
+    /// # Arguments
+    /// * `entry` - The prepared file.
+    /// * `overrides` - The `--lang-map` rules.
+    pub(crate) fn add(&mut self, entry: &FileEntry, overrides: &[LangMapping]) {
+        let path = entry.language_path();
+        let name = section_name(&path, overrides);
+        let known = LANGUAGES.iter().any(|language| language.title == name)
+            || overrides.iter().any(|mapping| mapping.language() == name);
+        let name = if known { name } else { OTHER.to_string() };
+        let index = match self.stats.iter().position(|stats| stats.language == name) {
+            Some(index) => index,
+            None => {
+                self.stats.push(LanguageStats {
+                    language: name,
+                    ..LanguageStats::default()
+                });
+                self.stats.len() - 1
+            }
+        };
+        let stats = &mut self.stats[index];
+        stats.files += 1;
+        if entry.is_base64() {
+            return;
+        }
+        let syntax = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Syntax::from_extension);
+        for segment in entry.segments() {
+            if let Segment::Lines { text, .. } = segment {
+                let (blank, comment, code) = count_lines(text, syntax);
+                stats.blank += blank;
+                stats.comment += comment;
+                stats.code += code;
+            }
+        }
+    }
+
+    /// Returns the counts by language, most code first, with `Other` last.
+    pub(crate) fn finish(mut self) -> Vec<LanguageStats> {
+        self.stats.sort_by(|a, b| {
+            (a.language == OTHER)
+                .cmp(&(b.language == OTHER))
+                .then(b.code.cmp(&a.code))
+                .then_with(|| a.language.cmp(&b.language))
+        });
+        self.stats
+    }
+}
+
+/// Sorts the lines of a text into blank lines, lines with only comments, and lines with
+/// code, with the comment rules of `strip_comments`.
+///
+/// # Arguments
+/// * `text` - The lines.
+/// * `syntax` - The comment syntax, or `None` if the language has no known comments.
+///
+/// # Returns
+/// * `(usize, usize, usize)` - The blank, comment, and code lines.
+fn count_lines(text: &str, syntax: Option<Syntax>) -> (usize, usize, usize) {
+    let comments: Vec<_> = syntax
+        .map(|syntax| comments::tokens(text, syntax))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, token)| *token == Token::Comment)
+        .map(|(range, _)| range)
+        .collect();
+    let (mut blank, mut comment, mut code) = (0, 0, 0);
+    let mut next_comment = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        if line.trim().is_empty() {
+            blank += 1;
+            continue;
+        }
+        let has_code = line
+            .char_indices()
+            .filter(|(_, c)| !c.is_whitespace())
+            .any(|(i, _)| {
+                let at = start + i;
+                while comments
+                    .get(next_comment)
+                    .is_some_and(|range| range.end <= at)
+                {
+                    next_comment += 1;
+                }
+                comments
+                    .get(next_comment)
+                    .is_none_or(|range| range.start > at)
+            });
+        if has_code {
+            code += 1;
+        } else {
+            comment += 1;
+        }
+    }
+    (blank, comment, code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_blank_comment_or_code() {
+        let rust = "//! Crate docs.\n\nfn main() {\n    /* a\n       b */\n    let s = \"// no\"; // yes\n}\n";
+        assert_eq!(count_lines(rust, Some(Syntax::Rust)), (1, 3, 3));
+        let python = "# comment\nx = 1  # trailing\n\n";
+        assert_eq!(count_lines(python, Some(Syntax::Hash)), (1, 1, 1));
+        assert_eq!(count_lines("# not a comment\n", None), (0, 0, 1));
+    }
+
+    #[test]
+    fn tables_end_with_the_total() {
+        let stats = [
+            LanguageStats {
+                language: "Rust".to_string(),
+                files: 2,
+                blank: 3,
+                comment: 4,
+                code: 50,
+            },
+            LanguageStats {
+                language: OTHER.to_string(),
+                files: 1,
+                blank: 0,
+                comment: 0,
+                code: 7,
+            },
+        ];
+        let rows = table_rows(&stats);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2], ["Total", "3", "3", "4", "57"].map(String::from));
+    }
+}
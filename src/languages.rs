@@ -0,0 +1,348 @@
+//! Well-known languages and the files written in them, for `--lang` and for code-fence
+//! languages.
+
+use anyhow::{Result, bail};
+
+/// A language: its names, its code-fence language, and the files written in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Language {
+    /// The name accepted by `--lang` and listed in errors, e.g. `typescript`.
+    pub name: &'static str,
+    /// The name shown to readers, e.g. `TypeScript`.
+    pub title: &'static str,
+    /// Other names accepted by `--lang`, e.g. `ts`.
+    pub aliases: &'static [&'static str],
+    /// The code-fence language of its files in the Markdown format.
+    pub fence: &'static str,
+    /// Its lowercase file extensions, without the dot.
+    pub extensions: &'static [&'static str],
+    /// The names of its files without an extension, e.g. `Dockerfile`.
+    pub file_names: &'static [&'static str],
+}
+
+/// Shorthand for the entries of [`LANGUAGES`].
+const fn language(
+    name: &'static str,
+    title: &'static str,
+    aliases: &'static [&'static str],
+    fence: &'static str,
+    extensions: &'static [&'static str],
+    file_names: &'static [&'static str],
+) -> Language {
+    Language {
+        name,
+        title,
+        aliases,
+        fence,
+        extensions,
+        file_names,
+    }
+}
+
+/// The built-in languages, sorted by name.
+///
+/// An extension may belong to several languages, such as `h` to C and C++; the first one
+/// listed gives its code-fence language.
+pub const LANGUAGES: &[Language] = &[
+    language(
+        "bash",
+        "Bash",
+        &["shell", "sh", "zsh"],
+        "bash",
+        &["sh", "bash", "zsh"],
+        &[],
+    ),
+    language(
+        "batch",
+        "Batch",
+        &["bat", "cmd"],
+        "batch",
+        &["bat", "cmd"],
+        &[],
+    ),
+    language("c", "C", &[], "c", &["c", "h"], &[]),
+    language("c#", "C#", &["csharp", "cs"], "csharp", &["cs"], &[]),
+    language(
+        "c++",
+        "C++",
+        &["cpp", "cxx"],
+        "cpp",
+        &["cpp", "cxx", "cc", "hpp", "hxx", "hh", "h"],
+        &[],
+    ),
+    language(
+        "clojure",
+        "Clojure",
+        &["clj"],
+        "clojure",
+        &["clj", "cljs", "cljc", "edn"],
+        &[],
+    ),
+    language("css", "CSS", &[], "css", &["css"], &[]),
+    language("dart", "Dart", &[], "dart", &["dart"], &[]),
+    language(
+        "docker",
+        "Docker",
+        &["dockerfile"],
+        "dockerfile",
+        &["dockerfile"],
+        &["Dockerfile"],
+    ),
+    language("elixir", "Elixir", &["ex"], "elixir", &["ex", "exs"], &[]),
+    language("erlang", "Erlang", &["erl"], "erlang", &["erl", "hrl"], &[]),
+    language(
+        "f#",
+        "F#",
+        &["fsharp"],
+        "fsharp",
+        &["fs", "fsi", "fsx"],
+        &[],
+    ),
+    language("go", "Go", &["golang"], "go", &["go"], &[]),
+    language(
+        "graphql",
+        "GraphQL",
+        &["gql"],
+        "graphql",
+        &["graphql", "gql"],
+        &[],
+    ),
+    language(
+        "groovy",
+        "Groovy",
+        &["gradle"],
+        "groovy",
+        &["groovy", "gradle"],
+        &["Jenkinsfile"],
+    ),
+    language("haskell", "Haskell", &["hs"], "haskell", &["hs"], &[]),
+    language("html", "HTML", &[], "html", &["html", "htm"], &[]),
+    language("ini", "INI", &[], "ini", &["ini", "cfg"], &[]),
+    language("java", "Java", &[], "java", &["java"], &[]),
+    language(
+        "javascript",
+        "JavaScript",
+        &["js"],
+        "javascript",
+        &["js", "jsx", "mjs", "cjs"],
+        &[],
+    ),
+    language("json", "JSON", &[], "json", &["json"], &[]),
+    language("julia", "Julia", &["jl"], "julia", &["jl"], &[]),
+    language(
+        "jupyter",
+        "Jupyter Notebook",
+        &["ipynb", "notebook"],
+        "markdown",
+        &["ipynb"],
+        &[],
+    ),
+    language("kotlin", "Kotlin", &["kt"], "kotlin", &["kt", "kts"], &[]),
+    language("latex", "LaTeX", &["tex"], "latex", &["tex"], &[]),
+    language("lua", "Lua", &[], "lua", &["lua"], &[]),
+    language(
+        "make",
+        "Make",
+        &["makefile"],
+        "makefile",
+        &["mk"],
+        &["Makefile", "GNUmakefile"],
+    ),
+    language(
+        "markdown",
+        "Markdown",
+        &["md"],
+        "markdown",
+        &["md", "markdown"],
+        &[],
+    ),
+    language(
+        "objective-c",
+        "Objective-C",
+        &["objc", "objectivec"],
+        "objectivec",
+        &["m", "mm"],
+        &[],
+    ),
+    language("ocaml", "OCaml", &["ml"], "ocaml", &["ml", "mli"], &[]),
+    language("perl", "Perl", &["pl"], "perl", &["pl", "pm"], &[]),
+    language("php", "PHP", &[], "php", &["php"], &[]),
+    language(
+        "powershell",
+        "PowerShell",
+        &["ps", "pwsh"],
+        "powershell",
+        &["ps1", "psm1"],
+        &[],
+    ),
+    language(
+        "protobuf",
+        "Protocol Buffers",
+        &["proto"],
+        "protobuf",
+        &["proto"],
+        &[],
+    ),
+    language("python", "Python", &["py"], "python", &["py", "pyi"], &[]),
+    language(
+        "ruby",
+        "Ruby",
+        &["rb"],
+        "ruby",
+        &["rb"],
+        &["Gemfile", "Rakefile"],
+    ),
+    language("rust", "Rust", &["rs"], "rust", &["rs"], &[]),
+    language("scala", "Scala", &[], "scala", &["scala"], &[]),
+    language("sql", "SQL", &[], "sql", &["sql"], &[]),
+    language("swift", "Swift", &[], "swift", &["swift"], &[]),
+    language(
+        "terraform",
+        "Terraform",
+        &["tf", "hcl"],
+        "hcl",
+        &["tf", "hcl"],
+        &[],
+    ),
+    language("text", "Text", &["txt"], "text", &["txt"], &[]),
+    language("toml", "TOML", &[], "toml", &["toml"], &[]),
+    language(
+        "typescript",
+        "TypeScript",
+        &["ts"],
+        "typescript",
+        &["ts", "tsx", "mts", "cts"],
+        &[],
+    ),
+    language("xml", "XML", &[], "xml", &["xml", "svg"], &[]),
+    language("yaml", "YAML", &["yml"], "yaml", &["yml", "yaml"], &[]),
+    language("zig", "Zig", &[], "zig", &["zig"], &[]),
+];
+
+impl Language {
+    /// Finds a language by its name or an alias, ignoring case.
+    ///
+    /// # Arguments
+    /// * `name` - The name, e.g. `TypeScript`, `ts`, or `C++`.
+    ///
+    /// # Returns
+    /// * `Option<&'static Language>` - The language, or `None` if the name is unknown.
+    pub fn find(name: &str) -> Option<&'static Language> {
+        let name = name.trim();
+        LANGUAGES.iter().find(|language| {
+            language.name.eq_ignore_ascii_case(name)
+                || language
+                    .aliases
+                    .iter()
+                    .any(|alias| alias.eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// Finds the first language with a file extension, ignoring case.
+    pub(crate) fn by_extension(extension: &str) -> Option<&'static Language> {
+        LANGUAGES.iter().find(|language| {
+            language
+                .extensions
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(extension))
+        })
+    }
+
+    /// Finds the language of an extensionless file by its exact name, e.g. `Makefile`.
+    pub(crate) fn by_file_name(name: &str) -> Option<&'static Language> {
+        LANGUAGES
+            .iter()
+            .find(|language| language.file_names.contains(&name))
+    }
+
+    /// Returns the `include` entries that select the language's files: its extensions, and
+    /// a `**/<name>` glob for each of its file names.
+    pub fn include_patterns(&self) -> impl Iterator<Item = String> + '_ {
+        self.extensions
+            .iter()
+            .map(|extension| extension.to_string())
+            .chain(self.file_names.iter().map(|name| format!("**/{}", name)))
+    }
+}
+
+/// Expands language names into `include` entries.
+///
+/// # Arguments
+/// * `names` - The language names, e.g. from `--lang rust,toml`.
+///
+/// # Returns
+/// * `Result<Vec<String>>` - The extensions and file-name globs of the languages, or an error
+///   listing the known languages if a name is not one of them.
+pub(crate) fn include_patterns(names: &[String]) -> Result<Vec<String>> {
+    let mut patterns = Vec::new();
+    for name in names.iter().filter(|name| !name.trim().is_empty()) {
+        let Some(language) = Language::find(name) else {
+            let known: Vec<&str> = LANGUAGES.iter().map(|language| language.name).collect();
+            bail!(
+                "Unknown language `{}`; known languages: {}",
+                name.trim(),
+                known.join(", ")
+            );
+        };
+        patterns.extend(language.include_patterns());
+    }
+    Ok(patterns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_case_insensitive_and_aliases_work() {
+        for (name, language) in [
+            ("TypeScript", "typescript"),
+            ("ts", "typescript"),
+            ("C++", "c++"),
+            ("CPP", "c++"),
+            ("Rust", "rust"),
+            ("Dockerfile", "docker"),
+        ] {
+            assert_eq!(
+                Language::find(name).map(|l| l.name),
+                Some(language),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(Language::find("cobol"), None);
+    }
+
+    #[test]
+    fn expands_names_into_include_entries() {
+        let names = ["rust".to_string(), " Docker ".to_string()];
+        assert_eq!(
+            include_patterns(&names).unwrap(),
+            ["rs", "dockerfile", "**/Dockerfile"]
+        );
+        let err = include_patterns(&["rust".to_string(), "cobol".to_string()]).unwrap_err();
+        let message = err.to_string();
+        assert!(message.starts_with("Unknown language `cobol`; known languages: bash, batch"));
+        assert!(message.contains("typescript"));
+    }
+
+    #[test]
+    fn table_is_sorted_lowercase_and_unambiguous() {
+        assert!(LANGUAGES.windows(2).all(|pair| pair[0].name < pair[1].name));
+        let mut names = Vec::new();
+        for language in LANGUAGES {
+            assert!(
+                language
+                    .extensions
+                    .iter()
+                    .all(|ext| *ext == ext.to_ascii_lowercase())
+            );
+            names.push(language.name);
+            names.extend(language.aliases);
+        }
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), count, "a name or alias is used twice");
+    }
+}
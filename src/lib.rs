@@ -1,51 +1,901 @@
+mod also_output;
+mod archive;
+mod base64;
+mod between;
+mod blake3;
+mod budget;
+mod bundle_metadata;
+mod cache;
+mod check;
+mod churn;
+mod clipboard;
+mod color;
+pub mod comments;
+mod compress;
+mod config_builder;
+mod config_file;
+mod content_hash;
+mod effective_config;
+mod error;
+mod estimate;
+mod expand;
+mod extensions;
+mod file_filter;
+mod filter_cmd;
+mod filter_rules;
+mod follow;
+mod formatter;
+mod frontmatter;
+mod generated;
+mod git;
+mod gitattributes;
+mod github;
+mod grep;
+mod gutter;
+mod hooks;
+mod init;
+mod interrupt;
+mod json;
+pub mod lang;
+mod lang_stats;
+pub mod languages;
+mod license;
+mod limit;
+pub mod lockfiles;
+mod manifest;
+mod markers;
+mod mcp;
+mod meta;
+mod model;
+mod nested_config;
+mod notebook;
+mod output_dir;
+mod output_template;
+mod overwrite;
+mod pager;
+mod parallel;
+mod path_overrides;
+mod picker;
+pub mod progress;
+mod relative_to;
+mod remote;
+pub mod rename;
+mod report;
+mod reproducible;
+mod rust_modules;
+pub mod sensitive;
+mod server;
+mod sha256;
+mod shebang;
+mod signatures;
+mod source;
+mod stream;
+mod summary;
+pub mod test_files;
+mod time_bound;
+mod tokenizer;
+mod toml;
+pub mod transform;
+mod walk;
+mod watch;
+mod workspace;
+
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs::{self, File};
-use std::io::{self, BufWriter, Write};
-use std::path::{Path, PathBuf};
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::ops::ControlFlow;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::{Context, Result};
-use chrono::Local;
+use also_output::{AlsoOutput, FanOut};
+use anyhow::{Context, Result, bail};
+use budget::Prepared;
+use cache::Cache;
+use churn::Churn;
 use clap::ValueEnum;
-use git2::Repository;
-use ignore::gitignore::Gitignore;
-use tracing::{error, info, warn};
-use walkdir::{DirEntry, WalkDir};
+use estimate::EstimateHooks;
+use filter_cmd::{FilterFailed, Filters};
+use follow::Reach;
+use generated::GeneratedFile;
+use git::GitRepo;
+use gitattributes::Attributes;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use grep::{Grep, NoGrepMatch};
+use hooks::NoHooks;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use lang::{LangMapping, section_name};
+use lang_stats::LanguageCounter;
+use languages::Language;
+use license::LicenseHeaders;
+use limit::{LimitedWriter, OutputFailed};
+use markers::{Marker, OptedOutFile};
+use nested_config::NestedConfigs;
+use notebook::MalformedNotebook;
+use output_dir::MirrorTree;
+use overwrite::Overwrite;
+use pager::Pager;
+use progress::Progress;
+use rename::{PathRename, PathRenamer, slash_path};
+use serde::{Deserialize, Deserializer, Serialize};
+use stream::{STREAM_LEN, Scan, StreamedFile};
+use tracing::{debug, error, info, warn};
+use transform::{FileCap, LineEnding, LineRange, Segment, Truncation};
+use workspace::Workspace;
+
+pub use archive::{ArchiveSource, is_archive};
+pub use budget::{BudgetSelection, BudgetedFile, FitStrategy};
+pub use bundle_metadata::BundleMetadata;
+pub use cache::DEFAULT_CACHE_FILE;
+pub use check::{CheckOutcome, check_output};
+pub use churn::ChurnFile;
+pub use color::ColorChoice;
+pub use compress::Compression;
+pub use config_builder::ConfigBuilder;
+pub use config_file::{
+    CONFIG_FILE_NAMES, find_config_file, load_config_file, load_config_file_with_fields,
+    load_discovered_config_file, resolve_text,
+};
+pub use content_hash::HashAlgorithm;
+pub use effective_config::{ConfigSource, config_to_toml};
+pub use error::Error;
+pub use estimate::{CONTEXT_WINDOWS, Estimate, FileEstimate};
+pub use expand::{expand_config_paths, expand_path};
+pub use extensions::{ExtensionCount, ExtensionListing, LanguageCount};
+pub use file_filter::{FileFilter, FilterDecision};
+pub use filter_cmd::{DEFAULT_FILTER_TIMEOUT, FilterCommand};
+pub use formatter::{
+    AsciidocFormatter, ChatJsonFormatter, ClaudeFormatter, ConsoleFormatter, FileGroup, Formatter,
+    MarkdownFormatter, RunContext, TarFormatter, TextFormatter,
+};
+pub use github::{fetch_github, is_github_input};
+pub use gutter::LineNumberFormat;
+pub use hooks::{FileAction, Hooks, SkipReason};
+pub use init::{InitReport, init_config};
+pub use interrupt::{INTERRUPTED_EXIT_CODE, install_interrupt_handler};
+pub use lang_stats::LanguageStats;
+pub use limit::{ByteSize, OutputLimitExceeded};
+pub use markers::{IGNORE_MARKER, INCLUDE_MARKER};
+pub use mcp::serve_mcp;
+pub use meta::{FileMeta, FileMetaField};
+pub use model::Model;
+pub use relative_to::RelativeTo;
+pub use remote::{RemoteCheckout, clone_remote, is_git_url};
+pub use report::RunReport;
+#[cfg(feature = "server")]
+pub use server::Server;
+pub use server::serve;
+pub use source::{FileSource, MemorySource, SourceFile};
+pub use summary::{
+    ExtensionStats, FileStats, LargestFile, NoFilesMatched, RunSummary, SkippedFile,
+    estimate_tokens,
+};
+pub use time_bound::TimeBound;
+pub use tokenizer::Tokenizer;
+pub use watch::watch;
+pub use workspace::WorkspaceMember;
 
 /// Represents the output format for the bundled files.
 ///
 /// - `Markdown`: Outputs files in Markdown format with code blocks.
 /// - `Text`: Outputs files as plain text.
 /// - `Console`: Outputs files formatted for console display (default).
-#[derive(Debug, Clone, ValueEnum, Default)]
+/// - `ClaudeXml`: Outputs files as numbered `<document>` blocks for Claude (`claude-xml`).
+/// - `ChatJson`: Outputs the `messages` of an OpenAI Chat Completions request as JSON
+///   (`chat-json`).
+/// - `Asciidoc`: Outputs files as AsciiDoc sections with `[source]` listing blocks.
+/// - `Tar`: Outputs a tar archive with an entry per file and a `MANIFEST.json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Format {
     Markdown,
     Text,
     #[default]
     Console,
+    #[serde(rename = "claude-xml")]
+    ClaudeXml,
+    #[serde(rename = "chat-json")]
+    ChatJson,
+    Asciidoc,
+    Tar,
+}
+
+impl Format {
+    /// Returns the usual file name extension of this format, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Text | Format::Console => "txt",
+            Format::ClaudeXml => "xml",
+            Format::ChatJson => "json",
+            Format::Asciidoc => "adoc",
+            Format::Tar => "tar",
+        }
+    }
+
+    /// Infers the format of an output file from its extension: `md` or `markdown` for
+    /// Markdown, `txt` or `text` for plain text, `xml` for Claude XML, `json` for chat
+    /// messages, `adoc` or `asciidoc` for AsciiDoc, and `tar` for a tar archive.
+    ///
+    /// # Arguments
+    /// * `path` - The output file.
+    ///
+    /// # Returns
+    /// * `Option<Format>` - The format, or `None` for any other extension.
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "md" | "markdown" => Some(Format::Markdown),
+            "txt" | "text" => Some(Format::Text),
+            "xml" => Some(Format::ClaudeXml),
+            "json" => Some(Format::ChatJson),
+            "adoc" | "asciidoc" => Some(Format::Asciidoc),
+            "tar" => Some(Format::Tar),
+            _ => None,
+        }
+    }
+
+    /// Returns the built-in formatter for this format.
+    pub fn formatter(&self) -> Box<dyn Formatter> {
+        match self {
+            Format::Markdown => Box::new(MarkdownFormatter),
+            Format::Text => Box::new(TextFormatter),
+            Format::Console => Box::<ConsoleFormatter>::default(),
+            Format::ClaudeXml => Box::<ClaudeFormatter>::default(),
+            Format::ChatJson => Box::<ChatJsonFormatter>::default(),
+            Format::Asciidoc => Box::<AsciidocFormatter>::default(),
+            Format::Tar => Box::<TarFormatter>::default(),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    /// Writes the name used by `--format` and configuration files, e.g. `claude-xml`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Markdown => "markdown",
+            Format::Text => "text",
+            Format::Console => "console",
+            Format::ClaudeXml => "claude-xml",
+            Format::ChatJson => "chat-json",
+            Format::Asciidoc => "asciidoc",
+            Format::Tar => "tar",
+        })
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    /// Parses a format name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Format as ValueEnum>::from_str(s, true).map_err(|_| {
+            format!(
+                "unknown format `{}` (expected markdown, text, console, claude-xml, chat-json, asciidoc, or tar)",
+                s
+            )
+        })
+    }
+}
+
+/// Patterns prioritized by `--priority-defaults`: project overviews, manifests, and entry points.
+pub const DEFAULT_PRIORITY_PATTERNS: [&str; 12] = [
+    "README*",
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "go.mod",
+    "pom.xml",
+    "build.gradle*",
+    "src/main.rs",
+    "src/lib.rs",
+    "main.py",
+    "src/index.*",
+    "main.go",
+];
+
+/// Hidden names kept by `--keep-hidden-defaults`: CI configuration.
+pub const DEFAULT_KEEP_HIDDEN: [&str; 2] = [".github", ".gitlab-ci.yml"];
+
+/// Version control metadata directories, never walked unless `include_vcs_dir` is set.
+pub const VCS_DIRECTORIES: [&str; 3] = [".git", ".hg", ".svn"];
+
+/// Tool-agnostic ignore files honored by `respect_ignore_files`, as by ripgrep and fd. In one
+/// directory, `.rgignore` takes precedence over `.ignore`, which takes precedence over `.gitignore`.
+pub const TOOL_IGNORE_FILE_NAMES: [&str; 2] = [".ignore", ".rgignore"];
+
+/// Files in a target directory with gitignore-syntax rules that only apply to bundling.
+pub const IGNORE_FILE_NAMES: [&str; 2] = [".codebase-to-promptignore", ".c2pignore"];
+
+/// The order in which files are written to the output.
+///
+/// Every order falls back to the relative path for ties, so output is reproducible across
+/// machines and filesystems.
+#[derive(Debug, Clone, Copy, ValueEnum, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Lexicographic by relative path (default).
+    #[default]
+    Path,
+    /// By file size, smallest first.
+    Size,
+    /// By modification time, oldest first.
+    Mtime,
+    /// By file extension, then path.
+    Extension,
+    /// By the number of commits touching the file within the churn window, most first.
+    Churn,
+    /// Rust crate roots, then their modules in a depth-first walk of the `mod` declarations;
+    /// files outside the module trees follow by path.
+    #[serde(rename = "rust-modules")]
+    RustModules,
+}
+
+/// How files are split into sections with subtotals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// By directory, in the order of each directory's first file.
+    Dir,
+    /// By code-fence language, falling back to the extension.
+    Lang,
+    /// By Cargo workspace member, each section headed by the package name and version.
+    Member,
+}
+
+/// The order of language sections with [`GroupBy::Lang`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupOrder {
+    /// By estimated tokens, most first, so the dominant language leads (default).
+    #[default]
+    Tokens,
+    /// Alphabetically by language name.
+    Alpha,
 }
 
 /// Configuration options for the file bundling process.
-#[derive(Debug)]
+///
+/// Can be serialized, e.g. into the run report, and deserialized from a configuration
+/// file; missing options take their default values. Options are named as the fields are,
+/// and configuration files also accept the spelling of the CLI flags (`exclude-dirs`).
+/// A single input directory can be given as `directory`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
 pub struct Config {
-    /// The directory to process.
-    pub directory: PathBuf,
+    /// The directories to process, walked in turn.
+    #[serde(alias = "directory", deserialize_with = "one_or_more_paths")]
+    pub directories: Vec<PathBuf>,
     /// The optional output file path. If not provided, output is written to stdout.
     pub output: Option<PathBuf>,
-    /// File extensions to include in the output.
+    /// More files to write the same bundle to in the same run, each in the format its
+    /// extension names (see [`Format::from_extension`]).
+    pub also_output: Vec<PathBuf>,
+    /// A directory to write one output file per source file to instead of a single bundle,
+    /// mirroring the directory structure (e.g. `src/lib.rs.md`), with an `index.md` linking
+    /// to each.
+    pub output_dir: Option<PathBuf>,
+    /// Whether output files that already exist may be overwritten: the output, the
+    /// `also_output`, statistics, manifest, and report files, and the files of `output_dir`.
+    /// Without it (or `backup`) the run fails before writing anything.
+    pub force: bool,
+    /// Whether output files that already exist are renamed to `<name>.bak` (or
+    /// `<name>.bak.N` if that is taken) before they are written.
+    pub backup: bool,
+    /// Whether the missing parent directories of the output files are created, rather than
+    /// failing the run.
+    pub create_dirs: bool,
+    /// A CSV file that receives one row per included file and skipped entry after the run.
+    pub stats_output: Option<PathBuf>,
+    /// How many of the included files with the most tokens are listed in
+    /// [`RunSummary::largest_files`] after the run.
+    pub top: Option<usize>,
+    /// A JSON file that receives a manifest of the bundle after the run: the included files
+    /// with their sizes, hashes, and token counts, and the run's configuration.
+    pub manifest: Option<PathBuf>,
+    /// A JSON file, or `-` for stdout, that receives a [`RunReport`] after the run.
+    pub report_json: Option<PathBuf>,
+    /// File extensions such as `rs` or globs such as `src/**` to include in the output; `!`
+    /// negates an entry, and the last matching entry wins, as in `.gitignore`.
     pub include: Vec<String>,
-    /// File extensions to exclude from the output.
+    /// Well-known language names such as `rust` or `docker`, matched case-insensitively,
+    /// whose extensions and file names are included before the entries of `include` (see
+    /// [`languages::LANGUAGES`]).
+    pub languages: Vec<String>,
+    /// File extensions or globs to exclude from the output, with the same syntax as `include`.
     pub exclude: Vec<String>,
+    /// A file of `include` entries, one per line, with blank lines and `#` comments ignored;
+    /// they come before the entries of `include`.
+    pub include_from: Option<PathBuf>,
+    /// A file of `exclude` entries, one per line, read like `include_from`.
+    pub exclude_from: Option<PathBuf>,
+    /// Whether configuration files in subdirectories of the input directory apply their
+    /// filters and transformations to the files under them, overriding this configuration.
+    /// Off by default, as a subdirectory may hold a separate project with its own file.
+    pub nested_configs: bool,
+    /// Directories to leave out without entering them: names such as `node_modules`, or globs
+    /// of paths relative to the input directory such as `src/generated` (see [`exclude_dir_set`]).
+    pub exclude_dirs: Vec<String>,
+    /// Exact paths relative to the input directory of files to bundle even if the other
+    /// filters would leave them out; only binary files are still skipped.
+    pub include_paths: Vec<PathBuf>,
+    /// Exact paths relative to the input directory of files never to bundle; this wins over
+    /// every other rule, including `include_paths`.
+    pub exclude_paths: Vec<PathBuf>,
     /// The format of the output (Markdown, Text, or Console).
     pub format: Format,
     /// Whether to append the current date to the output file name.
     pub append_date: bool,
     /// Whether to append the current Git hash to the output file name.
     pub append_git_hash: bool,
+    /// Whether to add `-dirty` to the Git hash when tracked files have uncommitted changes.
+    pub dirty_suffix: bool,
+    /// A template for the output file name, with `{name}`, `{ext}`, `{dir}`, `{date}`,
+    /// `{hash}`, `{branch}`, `{label}`, `{host}`, and `{n}` placeholders; it replaces the
+    /// `append_*` suffixes and `name_suffix`.
+    pub output_template: Option<String>,
+    /// A template appended to the output file name before its extension, with the
+    /// placeholders of `output_template`, e.g. `-{branch}-{label}`.
+    pub name_suffix: Option<String>,
+    /// The value of `{label}` in `output_template` and `name_suffix`.
+    pub label: Option<String>,
+    /// Whether placeholders without a value, such as `{branch}` outside a git repository,
+    /// are left empty instead of failing the run.
+    pub lenient_suffix: bool,
+    /// Whether to bundle the outputs of earlier runs, which are otherwise left out when the
+    /// output name changes per run with `append_date`, `append_git_hash`, or a template.
+    pub include_previous_outputs: bool,
+    /// The strftime format of `{date}` and of the `append_date` suffix.
+    pub date_format: String,
     /// Whether to include line numbers in the output.
     pub line_numbers: bool,
     /// Whether to ignore hidden files and directories.
     pub ignore_hidden: bool,
+    /// Names or globs of hidden files and directories that `ignore_hidden` keeps.
+    pub keep_hidden: Vec<String>,
+    /// Whether to append [`DEFAULT_KEEP_HIDDEN`] to the `keep_hidden` patterns.
+    pub keep_hidden_defaults: bool,
     /// Whether to respect `.gitignore` rules.
     pub respect_gitignore: bool,
+    /// Whether to respect `.ignore` and `.rgignore` files like `.gitignore`.
+    pub respect_ignore_files: bool,
+    /// Whether to leave out files and directories with the `export-ignore` attribute, looked
+    /// up in the enclosing git repository as `git archive` does.
+    pub respect_export_ignore: bool,
+    /// Whether to walk [`VCS_DIRECTORIES`] such as `.git`, which are otherwise always skipped.
+    pub include_vcs_dir: bool,
+    /// A file of gitignore-syntax rules applied in addition to the `.c2pignore` of each
+    /// directory, with patterns relative to the directory.
+    pub ignore_file: Option<PathBuf>,
+    /// Whether to traverse initialized git submodules with their own `.gitignore` rules.
+    pub include_submodules: bool,
+    /// Whether to copy the output to the system clipboard.
+    pub clipboard: bool,
+    /// Whether to only list the files that would be included instead of their contents.
+    pub dry_run: bool,
+    /// Whether dry-run listings include each file's size and token estimate.
+    pub dry_run_sizes: bool,
+    /// Whether each file is written as its header alone: path, size, lines, tokens, and
+    /// first line, without its content.
+    pub headers_only: bool,
+    /// Whether to show a progress spinner on stderr (only drawn when stderr is a terminal).
+    pub progress: bool,
+    /// Whether console output to a terminal is shown in a pager: `$PAGER`, or `less -R`.
+    pub pager: bool,
+    /// Whether to remove comments from source files with a known comment syntax.
+    pub strip_comments: bool,
+    /// Whether documentation comments survive `strip_comments`.
+    pub keep_doc_comments: bool,
+    /// Whether to collapse blank-line runs and strip trailing whitespace.
+    pub compact: bool,
+    /// Whether function bodies are elided in Rust, Go, JavaScript, TypeScript, and Python
+    /// files, leaving signatures, type definitions, imports, and doc comments.
+    pub signatures_only: bool,
+    /// Whether to strip trailing spaces and tabs from each line and blank lines at the end.
+    pub trim_trailing_whitespace: bool,
+    /// Whether to remove ANSI escape sequences, such as terminal colors, from file content.
+    pub strip_ansi: bool,
+    /// Whether to remove a leading YAML or TOML front matter block from Markdown files.
+    pub strip_frontmatter: bool,
+    /// Whether to remove the comment blocks before any code that carry a license, such as an
+    /// Apache-2.0 header repeated in every file.
+    pub strip_license_headers: bool,
+    /// The regular expression a comment block must match to be removed by
+    /// `strip_license_headers`, instead of `Licensed under`, `SPDX-License-Identifier`, or
+    /// `Copyright (c)`.
+    pub license_pattern: Option<String>,
+    /// Whether the text outputs of Jupyter notebook cells are written after their code.
+    pub notebook_outputs: bool,
+    /// Whether files left out as binary or larger than `max_file_size` are listed as
+    /// one-line placeholders in their place.
+    pub binary_placeholders: bool,
+    /// Binary files up to this size are written base64-encoded instead of being skipped.
+    pub embed_binary: Option<ByteSize>,
+    /// Regular expressions of which a file's content must match one to be bundled; empty
+    /// to bundle files whatever their content.
+    pub grep: Vec<String>,
+    /// Whether a file's content must match every `grep` pattern rather than any.
+    pub grep_and: bool,
+    /// Whether the `grep` patterns ignore case.
+    pub grep_ignore_case: bool,
+    /// The most files to include; later files in output order are left out.
+    pub max_files: Option<usize>,
+    /// With `grep`, write only the matching lines of each file with this many lines around
+    /// them, numbered as in the file, instead of whole files.
+    pub grep_context: Option<usize>,
+    /// The most characters a line keeps; longer lines are cut and end with a marker.
+    pub max_line_length: Option<usize>,
+    /// The line endings file content is converted to before any other transformation.
+    pub normalize_eol: LineEnding,
+    /// The distance between tab stops when replacing tabs with spaces; `None` or `0` keeps tabs.
+    pub expand_tabs: Option<usize>,
+    /// A shell command each included file is piped through before the other
+    /// transformations: it gets the content on stdin, with `C2P_PATH` and `C2P_EXT` set, and
+    /// its stdout is bundled instead.
+    pub filter_cmd: Option<String>,
+    /// Rules that pipe the files with an extension through their own command instead of
+    /// `filter_cmd`.
+    pub filter_cmd_ext: Vec<FilterCommand>,
+    /// The seconds a filter command may run on one file before it is killed and the file
+    /// fails; `0` for no limit.
+    pub filter_timeout: u64,
+    /// The most filter commands running at once; `None` or `0` for one per job.
+    pub filter_jobs: Option<usize>,
+    /// The maximum number of leading lines to keep from each file.
+    pub head: Option<usize>,
+    /// The maximum number of trailing lines to keep from each file.
+    pub tail: Option<usize>,
+    /// The most lines of each file to keep, after `head` and `tail`, cut as `truncate` says
+    /// and marked.
+    pub max_lines_per_file: Option<usize>,
+    /// The most tokens of each file to keep, cut at a line boundary and marked; unlike
+    /// `fit_budget`, this caps files one at a time.
+    pub max_tokens_per_file: Option<usize>,
+    /// Which lines of a file over `max_lines_per_file` or `max_tokens_per_file` are kept:
+    /// its start, its end, or both ends around a marker.
+    pub truncate: Truncation,
+    /// Line ranges to restrict the bundle to; when non-empty, only the listed files are included.
+    pub only: Vec<LineRange>,
+    /// Rules that rewrite the paths shown in the output, without touching the filesystem.
+    pub rename_paths: Vec<PathRename>,
+    /// The directory displayed paths are relative to, instead of the scanned directory, e.g.
+    /// the repository root; files outside it are shown relative to the scanned directory.
+    pub relative_to: Option<RelativeTo>,
+    /// Whether displayed paths keep the platform separator instead of always using `/`.
+    pub native_paths: bool,
+    /// The order in which files are written.
+    pub sort: SortOrder,
+    /// Whether to reverse the sort order.
+    pub reverse: bool,
+    /// How far back commits count for [`SortOrder::Churn`] and [`FitStrategy::Churn`]; read
+    /// from configuration files with [`TimeBound::parse_window`], so `6m` is six months.
+    #[serde(deserialize_with = "time_bound::deserialize_window")]
+    pub churn_window: TimeBound,
+    /// Glob patterns of relative paths to emit first, in the order given.
+    pub priority: Vec<String>,
+    /// Whether to append [`DEFAULT_PRIORITY_PATTERNS`] to the priority patterns.
+    pub priority_defaults: bool,
+    /// A file listing relative paths or globs, one per line, in the order to emit them.
+    pub order_file: Option<PathBuf>,
+    /// Whether to exclude files not listed in the order file.
+    pub order_strict: bool,
+    /// A file listing the paths to bundle instead of walking the directories (`-` for stdin).
+    pub files_from: Option<PathBuf>,
+    /// Whether the paths in `files_from` are NUL-delimited instead of newline-delimited.
+    pub files_from_nul: bool,
+    /// Relative paths of entry files: only they are bundled, with the files they import
+    /// when `follow_imports` is set.
+    pub entry: Vec<PathBuf>,
+    /// Whether to also bundle the files the `entry` files import, transitively.
+    pub follow_imports: bool,
+    /// How many imports away from the `entry` files to follow; `None` for no limit.
+    pub follow_depth: Option<usize>,
+    /// Cargo workspace members, by package name or directory: only their files are bundled,
+    /// in a section per member. The input must be the workspace root.
+    pub workspace_members: Vec<String>,
+    /// Whether workspace members are read from `cargo metadata` rather than by parsing the
+    /// `Cargo.toml` files.
+    pub use_cargo_metadata: bool,
+    /// A `base..head` range of git refs: only the files that differ between them are
+    /// bundled, as they are at `head` in the repository rather than in the working tree.
+    pub between: Option<String>,
+    /// The display name of extra content read from stdin and bundled after the other files,
+    /// e.g. `panic.log`.
+    pub stdin_file: Option<PathBuf>,
+    /// Whether to follow symbolic links while walking; files reachable twice are emitted once.
+    pub follow_symlinks: bool,
+    /// The number of threads reading and transforming files; `None` or `0` uses one per CPU.
+    pub jobs: Option<usize>,
+    /// A file caching the transformed content of each file between runs, so unchanged files
+    /// are not read again; see [`DEFAULT_CACHE_FILE`].
+    pub cache: Option<PathBuf>,
+    /// The metadata fields shown in each file header.
+    pub file_meta: FileMeta,
+    /// The algorithm of the content hash shown in each file header, computed over the content
+    /// as written, and of the bundle digest written after the last file; `None` for neither.
+    pub hash: Option<HashAlgorithm>,
+    /// Whether dates and times are in UTC instead of local time: modification times in file
+    /// headers, the date in the output file name, and the time of an appended run.
+    pub utc: bool,
+    /// Whether the bundle depends only on the sources: timestamps are pinned by
+    /// `SOURCE_DATE_EPOCH` or left out, times are in UTC, and paths always use `/`.
+    pub reproducible: bool,
+    /// Whether files whose content duplicates an earlier file are written as a one-line stub.
+    pub dedup: bool,
+    /// The compression applied to the output; the output file name gets the format's extension.
+    pub compress: Option<Compression>,
+    /// Whether to append to the output file instead of overwriting it.
+    pub append: bool,
+    /// Whether output written to a file is also echoed to stdout.
+    pub tee: bool,
+    /// Whether the first file that cannot be read aborts the run.
+    pub strict: bool,
+    /// Whether a run that matches no files fails with [`NoFilesMatched`] instead of warning.
+    pub fail_if_empty: bool,
+    /// When the console format uses colors.
+    pub color: ColorChoice,
+    /// How line numbers are written with `line_numbers`.
+    pub line_number_format: LineNumberFormat,
+    /// The heading level (1–6) of each file in the Markdown format.
+    pub heading_level: u8,
+    /// A template replacing each file's Markdown heading, with `{path}`, `{ext}`, `{lines}`,
+    /// and `{tokens}` placeholders.
+    pub file_header_template: Option<String>,
+    /// How tokens are counted in the summary, estimates, and section subtotals.
+    pub tokenizer: Tokenizer,
+    /// The most tokens to bundle: files are chosen to fit, by priority and then
+    /// `fit_strategy`, and the others are listed at the end of the bundle.
+    pub fit_budget: Option<usize>,
+    /// The order in which files are considered for `fit_budget` after the priority patterns.
+    pub fit_strategy: FitStrategy,
+    /// The model the bundle is meant for: a bundle filling more than 90% of its context
+    /// window is reported, unless `fit_budget` is set.
+    pub model: Option<Model>,
+    /// Whether a bundle over 90% of the `model`'s context window fails the run instead of
+    /// warning.
+    pub strict_fit: bool,
+    /// Whether the chat-json format writes each file as its own user message.
+    pub messages_per_file: bool,
+    /// Whether each file of a Markdown bundle is wrapped in a collapsed `<details>` block,
+    /// with its path and line count, or `file_header_template`, as the `<summary>`.
+    pub collapsible: bool,
+    /// Rules that set the code-fence language of files in the Markdown format.
+    pub lang_map: Vec<LangMapping>,
+    /// Whether extensionless scripts get the language of the interpreter in their `#!` line,
+    /// both for the `include` and `exclude` lists (`bin/deploy` with `#!/usr/bin/env python3`
+    /// matches `py`) and for the code-fence language.
+    pub shebang_detect: bool,
+    /// Whether a header describing what produced the bundle (tool version, time, directory,
+    /// git commit, file count, and filters) is written before the first file.
+    pub metadata_header: bool,
+    /// Whether the metadata header has a table of the files, blank lines, comment lines,
+    /// and code lines of each language among the included files.
+    pub lang_stats: bool,
+    /// Whether a Markdown bundle starts with YAML front matter: the title, date, git branch
+    /// and commit, file count, token estimate, and filters.
+    pub frontmatter: bool,
+    /// Extra front matter fields, or replacements of the built-in ones, e.g.
+    /// `project = "billing"` in the `[frontmatter]` table of the config file.
+    pub frontmatter_fields: BTreeMap<String, String>,
+    /// Text written verbatim before the bundle.
+    pub preamble: Option<String>,
+    /// Text written verbatim after the bundle.
+    pub postamble: Option<String>,
+    /// A question written after the last file in a place of its own in each format, e.g. a
+    /// `## Question` section in Markdown or the last message in chat JSON.
+    pub question: Option<String>,
+    /// Whether lockfiles are bundled.
+    pub include_lockfiles: bool,
+    /// Whether files that look like they hold secrets, listed in
+    /// [`SENSITIVE_PATTERNS`](sensitive::SENSITIVE_PATTERNS), are bundled.
+    pub include_sensitive: bool,
+    /// Whether files marked `linguist-vendored` or `linguist-generated` in `.gitattributes`
+    /// files are left out.
+    pub respect_linguist: bool,
+    /// The file names treated as lockfiles, e.g. `Cargo.lock`; defaults to
+    /// [`DEFAULT_LOCKFILES`](lockfiles::DEFAULT_LOCKFILES).
+    pub lockfiles: Vec<String>,
+    /// Whether to leave out test files, as identified by `tests_patterns`.
+    pub no_tests: bool,
+    /// Whether to bundle only test files, as identified by `tests_patterns`.
+    pub only_tests: bool,
+    /// Globs identifying test files by relative path, e.g. `**/tests/**`; defaults to
+    /// [`DEFAULT_TEST_PATTERNS`](test_files::DEFAULT_TEST_PATTERNS).
+    pub tests_patterns: Vec<String>,
+    /// Only files modified at or after this time are bundled.
+    pub newer_than: Option<TimeBound>,
+    /// Only files modified before this time are bundled.
+    pub older_than: Option<TimeBound>,
+    /// Whether files that are empty or whitespace-only after transformations are skipped.
+    pub skip_empty: bool,
+    /// Files smaller than this size on disk are skipped.
+    pub min_file_size: Option<ByteSize>,
+    /// Files larger than this size on disk are skipped without being read. Large text files
+    /// are streamed into the output when no option needs their whole content, and are
+    /// otherwise held in memory, so this bounds the memory a single file can take.
+    pub max_file_size: Option<ByteSize>,
+    /// Whether files that look minified or generated are skipped.
+    pub skip_generated: bool,
+    /// Whether files are written in per-directory or per-language sections with subtotals.
+    pub group_by: Option<GroupBy>,
+    /// How many leading directories make up a section with [`GroupBy::Dir`].
+    pub group_depth: usize,
+    /// The order of the sections with [`GroupBy::Lang`].
+    pub group_order: GroupOrder,
+    /// The largest bundle written before the run is aborted, counted after formatting;
+    /// zero means no limit.
+    pub max_total_size: ByteSize,
+}
+
+/// Deserializes the input directories from either one path or a list of them.
+fn one_or_more_paths<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Paths {
+        One(PathBuf),
+        More(Vec<PathBuf>),
+    }
+    Ok(match Paths::deserialize(deserializer)? {
+        Paths::One(path) => vec![path],
+        Paths::More(paths) => paths,
+    })
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            directories: vec![PathBuf::from(".")],
+            output: None,
+            also_output: Vec::new(),
+            output_dir: None,
+            force: false,
+            backup: false,
+            create_dirs: true,
+            stats_output: None,
+            top: None,
+            manifest: None,
+            report_json: None,
+            include: Vec::new(),
+            languages: Vec::new(),
+            exclude: Vec::new(),
+            include_from: None,
+            exclude_from: None,
+            nested_configs: false,
+            exclude_dirs: Vec::new(),
+            include_paths: Vec::new(),
+            exclude_paths: Vec::new(),
+            format: Format::default(),
+            append_date: false,
+            append_git_hash: false,
+            dirty_suffix: false,
+            output_template: None,
+            name_suffix: None,
+            label: None,
+            lenient_suffix: false,
+            include_previous_outputs: false,
+            date_format: "%Y%m%d".to_string(),
+            line_numbers: false,
+            ignore_hidden: false,
+            keep_hidden: Vec::new(),
+            keep_hidden_defaults: false,
+            respect_gitignore: true,
+            respect_ignore_files: true,
+            respect_export_ignore: false,
+            include_vcs_dir: false,
+            ignore_file: None,
+            include_submodules: false,
+            clipboard: false,
+            dry_run: false,
+            dry_run_sizes: false,
+            headers_only: false,
+            progress: false,
+            pager: false,
+            strip_comments: false,
+            keep_doc_comments: false,
+            compact: false,
+            signatures_only: false,
+            trim_trailing_whitespace: false,
+            strip_ansi: false,
+            strip_frontmatter: false,
+            strip_license_headers: false,
+            license_pattern: None,
+            notebook_outputs: false,
+            binary_placeholders: false,
+            embed_binary: None,
+            grep: Vec::new(),
+            grep_and: false,
+            grep_ignore_case: false,
+            grep_context: None,
+            max_files: None,
+            max_line_length: None,
+            normalize_eol: LineEnding::Keep,
+            expand_tabs: None,
+            filter_cmd: None,
+            filter_cmd_ext: Vec::new(),
+            filter_timeout: DEFAULT_FILTER_TIMEOUT,
+            filter_jobs: None,
+            head: None,
+            tail: None,
+            max_lines_per_file: None,
+            max_tokens_per_file: None,
+            truncate: Truncation::Head,
+            only: Vec::new(),
+            rename_paths: Vec::new(),
+            relative_to: None,
+            native_paths: false,
+            sort: SortOrder::default(),
+            reverse: false,
+            churn_window: TimeBound::Ago(Duration::from_secs(6 * 2_630_016)),
+            priority: Vec::new(),
+            priority_defaults: false,
+            order_file: None,
+            order_strict: false,
+            files_from: None,
+            files_from_nul: false,
+            entry: Vec::new(),
+            follow_imports: false,
+            follow_depth: None,
+            workspace_members: Vec::new(),
+            use_cargo_metadata: false,
+            between: None,
+            stdin_file: None,
+            follow_symlinks: false,
+            jobs: None,
+            cache: None,
+            file_meta: FileMeta::default(),
+            hash: None,
+            utc: false,
+            reproducible: false,
+            dedup: false,
+            compress: None,
+            append: false,
+            tee: false,
+            strict: false,
+            fail_if_empty: false,
+            color: ColorChoice::default(),
+            line_number_format: LineNumberFormat::default(),
+            heading_level: 3,
+            file_header_template: None,
+            tokenizer: Tokenizer::default(),
+            fit_budget: None,
+            fit_strategy: FitStrategy::default(),
+            model: None,
+            strict_fit: false,
+            messages_per_file: false,
+            collapsible: false,
+            lang_map: Vec::new(),
+            shebang_detect: false,
+            metadata_header: true,
+            lang_stats: false,
+            frontmatter: false,
+            frontmatter_fields: BTreeMap::new(),
+            preamble: None,
+            postamble: None,
+            question: None,
+            include_lockfiles: false,
+            include_sensitive: false,
+            respect_linguist: false,
+            lockfiles: lockfiles::default_lockfiles(),
+            no_tests: false,
+            only_tests: false,
+            tests_patterns: test_files::default_test_patterns(),
+            newer_than: None,
+            older_than: None,
+            skip_empty: false,
+            min_file_size: None,
+            max_file_size: None,
+            skip_generated: false,
+            max_total_size: ByteSize::DEFAULT_MAX_TOTAL,
+            group_by: None,
+            group_depth: 1,
+            group_order: GroupOrder::default(),
+        }
+    }
 }
 
 /// Runs the file bundling process based on the provided configuration.
@@ -54,256 +904,3563 @@ pub struct Config {
 /// * `config` - The configuration options for the bundling process.
 ///
 /// # Returns
-/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the process fails.
-pub fn run(config: Config) -> Result<()> {
-    let mut output_path = config.output.clone();
-
-    if config.append_date || config.append_git_hash {
-        append_date_and_git_hash(&mut output_path, &config)?;
-    }
+/// * `Result<RunSummary, Error>` - Statistics about the run if successful, or an error if the process fails.
+pub fn run(config: Config) -> Result<RunSummary, Error> {
+    let mut formatter = config.format.formatter();
+    Ok(run_bundle(config, &mut NoHooks, formatter.as_mut())?)
+}
 
-    let writer = determine_output_writer(&output_path)?;
+/// Runs the file bundling process, letting `hooks` observe and veto each file.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+/// * `hooks` - The callbacks invoked for written and skipped files.
+///
+/// # Returns
+/// * `Result<RunSummary, Error>` - Statistics about the run if successful, or an error if the process fails.
+pub fn run_with_hooks(config: Config, hooks: &mut dyn Hooks) -> Result<RunSummary, Error> {
+    let mut formatter = config.format.formatter();
+    Ok(run_bundle(config, hooks, formatter.as_mut())?)
+}
 
-    process_directory(&config, writer)
+/// Runs the file bundling process, writing the bundle with a custom formatter.
+///
+/// `config.format` is ignored in favor of `formatter`.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+/// * `formatter` - The formatter that writes the bundle.
+///
+/// # Returns
+/// * `Result<RunSummary, Error>` - Statistics about the run if successful, or an error if the process fails.
+pub fn run_with_formatter(
+    config: Config,
+    formatter: &mut dyn Formatter,
+) -> Result<RunSummary, Error> {
+    Ok(run_bundle(config, &mut NoHooks, formatter)?)
 }
 
-/// Appends the current date and/or Git hash to the output file name if required.
+/// Writes the bundle to the configured destination (file, stdout, and/or clipboard).
 ///
 /// # Arguments
-/// * `output_path` - The optional output file path to modify.
 /// * `config` - The configuration options for the bundling process.
+/// * `hooks` - The callbacks invoked for written and skipped files.
+/// * `formatter` - The formatter that writes the bundle.
 ///
 /// # Returns
-/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
-fn append_date_and_git_hash(output_path: &mut Option<PathBuf>, config: &Config) -> Result<()> {
-    if let Some(path) = output_path {
-        let mut new_filename = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or_default()
-            .to_string();
+/// * `Result<RunSummary>` - Statistics about the run if successful, or an error if the process fails.
+fn run_bundle(
+    config: Config,
+    hooks: &mut dyn Hooks,
+    formatter: &mut dyn Formatter,
+) -> Result<RunSummary> {
+    let started = Instant::now();
+    check_git_support(&config)?;
+    if config.report_json.as_deref() == Some(Path::new("-"))
+        && (config.output.is_none() && !config.clipboard || config.tee)
+    {
+        bail!("--report-json - needs --output or --clipboard, since the bundle goes to stdout");
+    }
+    if let Some(compression) = config.compress {
+        if config.clipboard {
+            bail!("--compress cannot be combined with --clipboard");
+        }
+        compress::check_support(compression)?;
+    }
+    if config.clipboard {
+        clipboard::clipboard_command()?;
+    }
+    if config.append
+        && matches!(
+            config.format,
+            Format::ChatJson | Format::Asciidoc | Format::Tar
+        )
+    {
+        bail!(
+            "--append cannot be combined with --format {}",
+            config.format
+        );
+    }
+    if config.frontmatter {
+        if !matches!(config.format, Format::Markdown) {
+            bail!("--frontmatter needs --format markdown");
+        }
+        // Front matter only counts at the very top of a document.
+        if config.append {
+            bail!("--frontmatter cannot be combined with --append");
+        }
+    }
+    if config.output_dir.is_some() {
+        let conflicts = [
+            ("--output", config.output.is_some()),
+            ("--also-output", !config.also_output.is_empty()),
+            ("--append", config.append),
+            ("--clipboard", config.clipboard),
+            ("--compress", config.compress.is_some()),
+            ("--frontmatter", config.frontmatter),
+        ];
+        if let Some((flag, _)) = conflicts.iter().find(|(_, set)| *set) {
+            bail!("--output-dir cannot be combined with {}", flag);
+        }
+        if matches!(config.format, Format::ChatJson | Format::Tar) {
+            bail!(
+                "--output-dir cannot be combined with --format {}",
+                config.format
+            );
+        }
+    }
+    if config.backup {
+        if config.force {
+            bail!("--backup cannot be combined with --force");
+        }
+        if config.append {
+            bail!("--backup cannot be combined with --append");
+        }
+    }
+    if !config.also_output.is_empty() {
+        if config.compress.is_some() {
+            bail!("--also-output cannot be combined with --compress");
+        }
+        if config.append {
+            bail!("--also-output cannot be combined with --append");
+        }
+    }
+
+    reproducible::check(&config)?;
+    output_template::validate(&config)?;
+    let mut output_path = output_template::output_path(config.output.as_deref(), &config)?;
+    if config.tee && output_path.is_none() {
+        warn!("--tee has no effect without --output");
+    }
+    if config.collapsible && !matches!(config.format, Format::Markdown) {
+        warn!("--collapsible only applies to the Markdown format; ignoring it");
+    }
+    if let (Some(compression), Some(path)) = (config.compress, &mut output_path) {
+        *path = compression.output_path(path);
+    }
 
-        if config.append_date {
-            new_filename.push('_');
-            new_filename.push_str(&Local::now().format("%Y%m%d").to_string());
-            info!("Appending date to filename.");
+    let also_targets = if config.dry_run {
+        if !config.also_output.is_empty() {
+            warn!("--also-output has no effect with --dry-run");
+        }
+        Vec::new()
+    } else {
+        AlsoOutput::resolve_all(&config)?
+    };
+    // Every file the run writes is checked before any is touched, on its final name; the
+    // file appended to is meant to exist.
+    let overwrite = Overwrite::of(&config);
+    let targets: Vec<&Path> = output_path
+        .iter()
+        .filter(|_| !config.append)
+        .chain(also_targets.iter().map(|(path, _)| path))
+        .chain(&config.stats_output)
+        .chain(&config.manifest)
+        .chain(&config.report_json)
+        .map(PathBuf::as_path)
+        .collect();
+    for target in &targets {
+        overwrite.check(target)?;
+    }
+    if config.create_dirs {
+        let appended = output_path.as_deref().filter(|_| config.append);
+        for target in appended.into_iter().chain(targets.iter().copied()) {
+            create_parent_dirs(target)?;
+        }
+    }
+    for target in &targets {
+        if let Some(backup) = overwrite.prepare(target)? {
+            info!("Moved {} to {}", target.display(), backup.display());
         }
+    }
 
-        if config.append_git_hash {
-            match Repository::open(&config.directory) {
-                Ok(repo) => {
-                    let head = repo.head().context("Failed to get repository HEAD")?;
-                    if let Some(oid) = head.target() {
-                        new_filename.push('_');
-                        new_filename.push_str(&oid.to_string()[..7]);
-                        info!("Appending git hash to filename.");
-                    }
+    // Created before the main output, so a path that cannot be written leaves it untouched.
+    let mut also_outputs = AlsoOutput::create_all(also_targets)?;
+    let also_paths: Vec<PathBuf> = also_outputs
+        .iter()
+        .map(|output| output.path().to_path_buf())
+        .collect();
+
+    let mut clipboard_buffer = Vec::new();
+    let mut summary;
+    {
+        // Checked before opening, since opening creates the file.
+        let appending = config.append
+            && output_path
+                .as_deref()
+                .and_then(|path| fs::metadata(path).ok())
+                .is_some_and(|metadata| metadata.len() > 0);
+        let mut writer = determine_output_writer(&output_path, &config, &mut clipboard_buffer)?;
+        let destination = match &output_path {
+            Some(path) => format!("output file: {}", path.display()),
+            None if config.clipboard => "the clipboard buffer".to_string(),
+            None => "stdout".to_string(),
+        };
+        let flush_error = || format!("Failed to write {}", destination);
+        if appending && !config.dry_run {
+            write_run_separator(&mut writer, &config).context("Failed to write output")?;
+        }
+        let outputs = output_path.iter().chain(&also_paths).map(PathBuf::as_path);
+        let input = Input::open(&config, outputs.collect())?;
+        let source = input.source();
+        // Files, the clipboard, and compressed streams never get color escapes.
+        let color = output_path.is_none()
+            && !config.clipboard
+            && config.compress.is_none()
+            && config.color.enabled(io::stdout().is_terminal());
+        match (config.compress, config.output_dir.as_deref()) {
+            (Some(compression), _) => {
+                // The encoder is finished explicitly, even when the run fails, so what was
+                // written is a complete archive.
+                let mut encoder = compress::Encoder::new(compression, writer)?;
+                let result =
+                    process_directory(&config, source, &mut encoder, color, hooks, formatter)
+                        .map_err(|err| name_output(err, &destination));
+                let (_, uncompressed_bytes, compressed_bytes) =
+                    encoder.finish().with_context(flush_error)?;
+                summary = result?;
+                summary.uncompressed_bytes = uncompressed_bytes;
+                summary.compressed_bytes = compressed_bytes;
+            }
+            (None, Some(directory)) => {
+                let mut tree = MirrorTree::new(formatter, directory, &config)?;
+                summary = process_directory(&config, source, &mut writer, color, hooks, &mut tree)
+                    .map_err(|err| name_output(err, &destination))?;
+                writer.flush().with_context(flush_error)?;
+            }
+            (None, None) if !also_outputs.is_empty() => {
+                let mut fan_out = FanOut::new(formatter, &mut also_outputs, &config);
+                summary =
+                    process_directory(&config, source, &mut writer, color, hooks, &mut fan_out)
+                        .map_err(|err| name_output(err, &destination))?;
+                writer.flush().with_context(flush_error)?;
+                for output in &mut also_outputs {
+                    output.flush()?;
                 }
-                Err(_) => warn!("Not a git repository, cannot append git hash."),
+            }
+            (None, None) => {
+                summary = process_directory(&config, source, &mut writer, color, hooks, formatter)
+                    .map_err(|err| name_output(err, &destination))?;
+                writer.flush().with_context(flush_error)?;
             }
         }
+    }
 
-        if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
-            new_filename.push('.');
-            new_filename.push_str(ext);
-        }
-        path.set_file_name(new_filename);
+    if !config.dry_run {
+        model::check_fit(&config, &summary)?;
     }
-    Ok(())
+    if config.clipboard {
+        clipboard::copy_to_clipboard(&clipboard_buffer)?;
+    }
+    if let Some(path) = &config.stats_output {
+        write_stats_output(path, &summary, &config)?;
+    }
+    if let Some(path) = &config.manifest {
+        manifest::write_manifest(path, &config, &summary, output_path.as_deref())?;
+    }
+    if let Some(path) = &config.report_json {
+        let mut report =
+            RunReport::new(&config, &summary, output_path.as_deref(), started.elapsed());
+        let at = usize::from(output_path.is_some());
+        report.outputs.splice(at..at, also_paths);
+        report::write_report(path, &report)?;
+    }
+
+    Ok(summary)
 }
 
-/// Determines the output writer (file or stdout) based on the configuration.
+/// Writes the per-file statistics of a run to a CSV file.
 ///
 /// # Arguments
-/// * `output_path` - The optional output file path.
+/// * `path` - The CSV file to create or overwrite.
+/// * `summary` - The statistics of the run.
+/// * `config` - The configuration options for the bundling process.
 ///
 /// # Returns
-/// * `Result<Box<dyn Write>>` - Returns a writer for the output.
-fn determine_output_writer(output_path: &Option<PathBuf>) -> Result<Box<dyn Write>> {
-    if let Some(path) = output_path {
-        info!("Output will be written to: {}", path.display());
-        let file = File::create(path)
-            .with_context(|| format!("Failed to create output file: {}", path.display()))?;
-        Ok(Box::new(BufWriter::new(file)))
-    } else {
-        info!("Output will be written to stdout.");
-        Ok(Box::new(BufWriter::new(io::stdout())))
-    }
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the file cannot be written.
+fn write_stats_output(path: &Path, summary: &RunSummary, config: &Config) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create stats file: {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    summary
+        .write_csv(&mut writer, &config.directories)
+        .and_then(|()| writer.flush())
+        .with_context(|| format!("Failed to write stats file: {}", path.display()))
 }
 
-/// Processes the specified directory and writes the bundled content to the writer.
+/// Runs the file bundling process and writes the bundle to the given writer.
+///
+/// `output`, `also_output`, `stats_output`, `manifest`, `report_json`, `clipboard`, `compress`, and the file name suffix
+/// options are ignored; the bundle only goes to `writer`. If `output` is set, that file is still never
+/// bundled into itself.
+/// The console format is colored only with `color` set to `always`.
 ///
 /// # Arguments
 /// * `config` - The configuration options for the bundling process.
 /// * `writer` - The writer to output the bundled content.
 ///
 /// # Returns
-/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the process fails.
-fn process_directory(config: &Config, mut writer: Box<dyn Write>) -> Result<()> {
-    let (gitignore, _) = Gitignore::new(config.directory.join(".gitignore"));
-
-    let walker = WalkDir::new(&config.directory)
-        .into_iter()
-        .filter_entry(|e| should_include_entry(e, &gitignore, config));
-
-    for result in walker {
-        let entry = match result {
-            Ok(entry) => entry,
-            Err(err) => {
-                error!("Failed to access entry: {}", err);
-                continue;
-            }
-        };
+/// * `Result<RunSummary, Error>` - Statistics about the run if successful, or an error if the process fails.
+pub fn run_to_writer(config: &Config, writer: &mut dyn Write) -> Result<RunSummary, Error> {
+    let mut formatter = config.format.formatter();
+    let input = Input::open(config, config.output.iter().map(PathBuf::as_path).collect())?;
+    let summary = process_directory(
+        config,
+        input.source(),
+        writer,
+        config.color.enabled(false),
+        &mut NoHooks,
+        formatter.as_mut(),
+    )?;
+    writer.flush().context("Failed to flush output")?;
+    Ok(summary)
+}
 
-        if let Err(err) = process_file_entry(&entry, &mut writer, config) {
-            error!("{}", err);
-        }
+/// Estimates the size of the bundle without writing it.
+///
+/// The files go through the same filters and transformations as in [`run`], so the estimate
+/// matches what a run would write; tokens are approximated as four bytes each. The output
+/// options are ignored.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<Estimate>` - The size of each file and the totals, or an error if the files
+///   cannot be listed.
+pub fn estimate(config: &Config) -> Result<Estimate> {
+    let config = Config {
+        dry_run: true,
+        ..config.clone()
+    };
+    let mut hooks = EstimateHooks::default();
+    hooks.estimate.tokenizer = config.tokenizer;
+    if config.headers_only {
+        hooks.headers = Some(config.clone());
     }
+    let mut formatter = config.format.formatter();
+    let input = Input::open(
+        &config,
+        config.output.iter().map(PathBuf::as_path).collect(),
+    )?;
+    process_directory(
+        &config,
+        input.source(),
+        &mut io::sink(),
+        false,
+        &mut hooks,
+        formatter.as_mut(),
+    )?;
 
-    info!("File bundling complete.");
-    Ok(())
+    let mut estimate = hooks.estimate;
+    for text in [&config.preamble, &config.postamble, &config.question]
+        .into_iter()
+        .flatten()
+    {
+        estimate.record_framing_text(text);
+    }
+    Ok(estimate)
 }
 
-/// Determines if a directory entry should be included based on the configuration.
+/// Counts the files, lines, bytes, and estimated tokens of the bundle without writing it.
+///
+/// The files go through the same filters and transformations as in [`run`], so the counts
+/// match the content a run would write; the preamble, postamble, and question add to the
+/// bytes and tokens. The output options are ignored.
 ///
 /// # Arguments
-/// * `entry` - The directory entry to check.
-/// * `gitignore` - The `.gitignore` rules to respect.
 /// * `config` - The configuration options for the bundling process.
 ///
 /// # Returns
-/// * `bool` - Returns `true` if the entry should be included, `false` otherwise.
-fn should_include_entry(entry: &DirEntry, gitignore: &Gitignore, config: &Config) -> bool {
-    !is_hidden(entry, config) && !is_ignored(entry, gitignore, config)
+/// * `Result<RunSummary>` - The statistics of the bundle, or an error if the files cannot be
+///   listed.
+pub fn count(config: &Config) -> Result<RunSummary> {
+    let config = Config {
+        dry_run: true,
+        ..config.clone()
+    };
+    let mut formatter = config.format.formatter();
+    let input = Input::open(
+        &config,
+        config.output.iter().map(PathBuf::as_path).collect(),
+    )?;
+    let mut summary = process_directory(
+        &config,
+        input.source(),
+        &mut io::sink(),
+        false,
+        &mut NoHooks,
+        formatter.as_mut(),
+    )?;
+    for text in [&config.preamble, &config.postamble, &config.question]
+        .into_iter()
+        .flatten()
+    {
+        summary.record_framing_text(text);
+    }
+    Ok(summary)
 }
 
-/// Processes a single file entry and writes its content to the writer.
+/// Lists the files that are empty or whitespace-only after transformations.
+///
+/// The files go through the same filters and transformations as in [`run`], with
+/// `skip_empty` set and `min_file_size` unset; the output options are ignored.
 ///
 /// # Arguments
-/// * `entry` - The file entry to process.
-/// * `writer` - The writer to output the file content.
 /// * `config` - The configuration options for the bundling process.
 ///
 /// # Returns
-/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the process fails.
-fn process_file_entry(entry: &DirEntry, writer: &mut dyn Write, config: &Config) -> Result<()> {
-    let path = entry.path();
-    if !path.is_file() {
-        return Ok(());
-    }
-
-    let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+/// * `Result<Vec<PathBuf>>` - The paths of the empty files, in bundle order, or an error if
+///   the files cannot be listed.
+pub fn list_empty(config: &Config) -> Result<Vec<PathBuf>> {
+    let config = Config {
+        skip_empty: true,
+        min_file_size: None,
+        fail_if_empty: false,
+        ..config.clone()
+    };
+    let summary = count(&config)?;
+    Ok(summary
+        .files
+        .into_iter()
+        .filter(|file| file.skipped == Some(SkipReason::Empty))
+        .map(|file| file.path)
+        .collect())
+}
 
-    let apply_include_filter =
-        !(config.include.is_empty() || config.include.len() == 1 && config.include[0].is_empty());
+/// Lists the extensions of the files in the configured directories.
+///
+/// Files are walked as in [`run`], so gitignored, hidden, and lockfiles are left out in the
+/// same way, but the include and exclude rules are not applied.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<ExtensionListing>` - The file count and size of each extension, or an error if
+///   the files cannot be listed.
+pub fn list_extensions(config: &Config) -> Result<ExtensionListing> {
+    let input = Input::open(config, config.output.iter().map(PathBuf::as_path).collect())?;
+    let source = input.source();
+    let unfiltered = Config {
+        include: Vec::new(),
+        languages: Vec::new(),
+        exclude: Vec::new(),
+        ..config.clone()
+    };
+    let files = gather_files(&unfiltered, source, None, None, None, &mut |_, _| {})?;
+    let files = files.iter().map(|file| {
+        let binary = match &input {
+            Input::Archive(archive) => archive.looks_binary(file),
+            Input::Between(tree) => tree.looks_binary(file),
+            Input::Fs(_) => extensions::looks_binary(&file.path).unwrap_or_else(|err| {
+                warn!("Failed to read {}: {}", file.path.display(), err);
+                false
+            }),
+        };
+        let bytes = source.len(file).unwrap_or(0);
+        (&file.relative_path, bytes, binary)
+    });
+    Ok(ExtensionListing::new(files))
+}
 
-    if apply_include_filter && !config.include.contains(&extension.to_string()) {
-        return Ok(());
-    }
+/// Lists the members of the Cargo workspace in the input directory.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process; `use_cargo_metadata`
+///   chooses how the members are read.
+///
+/// # Returns
+/// * `Result<Vec<WorkspaceMember>>` - The members ordered by path, or an error if the input
+///   is not a single directory that is the root of a Cargo workspace.
+pub fn list_workspace_members(config: &Config) -> Result<Vec<WorkspaceMember>> {
+    workspace::members(config)
+}
 
-    if config.exclude.contains(&extension.to_string()) {
-        return Ok(());
+/// Lets the user choose the files to bundle in an interactive terminal picker.
+///
+/// The candidates are the files that pass the filters, shown as a tree with checkboxes and
+/// their estimated tokens and size. The picker is drawn on the controlling terminal, so it
+/// works while stdout is redirected.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<Option<Vec<PathBuf>>>` - The chosen paths relative to the bundled directory, in
+///   the form read by `files_from`, `None` if the user cancelled, or an error if no terminal
+///   is available or no file passes the filters.
+pub fn pick_files(config: &Config) -> Result<Option<Vec<PathBuf>>> {
+    // Fail before walking the directory when there is no terminal to show the picker on.
+    picker::require_terminal()?;
+    let estimate = estimate(&Config {
+        fail_if_empty: true,
+        ..config.clone()
+    })?;
+    if estimate.files.is_empty() {
+        bail!("No file could be read, so there is nothing to pick from");
     }
+    picker::pick(estimate.files)
+}
 
-    let relative_path = path.strip_prefix(&config.directory).unwrap_or(path);
-    let content = match fs::read_to_string(path) {
-        Ok(content) => content,
-        Err(_) => {
-            warn!("Skipping non-UTF-8 file: {}", path.display());
-            return Ok(()); // Skip non-text files
-        }
-    };
+/// Runs the file bundling process and returns the bundle as a string.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<String, Error>` - The bundled content if successful, or an error if the process fails.
+pub fn run_to_string(config: &Config) -> Result<String, Error> {
+    let mut buffer = Vec::new();
+    run_to_writer(config, &mut buffer)?;
+    Ok(String::from_utf8(buffer).context("The bundle is not valid UTF-8")?)
+}
 
-    write_file_content(writer, relative_path, &content, extension, config)
-        .with_context(|| format!("Failed to write file content for {}", path.display()))
+/// Bundles the files of any [`FileSource`], such as a [`MemorySource`], into a string.
+///
+/// The directory, walk, and output options are ignored; everything else applies as in [`run`].
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+/// * `source` - Where the files come from.
+///
+/// # Returns
+/// * `Result<String, Error>` - The bundle, or an error if the process fails.
+pub fn run_source_to_string(config: &Config, source: &dyn FileSource) -> Result<String, Error> {
+    let mut buffer = Vec::new();
+    let mut formatter = config.format.formatter();
+    process_directory(
+        config,
+        source,
+        &mut buffer,
+        config.color.enabled(false),
+        &mut NoHooks,
+        formatter.as_mut(),
+    )?;
+    Ok(String::from_utf8(buffer).context("The bundle is not valid UTF-8")?)
 }
 
-/// Writes the content of a single file to the writer based on the specified format.
+/// Fails if the configuration needs git but the crate was built without the `git` feature.
 ///
 /// # Arguments
-/// * `writer` - The writer to output the file content.
-/// * `path` - The relative path of the file.
-/// * `content` - The content of the file.
-/// * `extension` - The file extension.
 /// * `config` - The configuration options for the bundling process.
 ///
 /// # Returns
-/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
-fn write_file_content(
-    writer: &mut dyn Write,
-    path: &Path,
-    content: &str,
-    extension: &str,
-    config: &Config,
-) -> Result<()> {
-    match config.format {
-        Format::Markdown => {
-            writeln!(writer, "### `{}`\n", path.display())?;
-            writeln!(writer, "```{}", extension)?;
-            write_content_lines(writer, content, config.line_numbers)?;
-            writeln!(writer, "```\n")?;
+/// * `Result<()>` - Returns `Ok(())` if every requested option is available, or an error otherwise.
+fn check_git_support(config: &Config) -> Result<()> {
+    if cfg!(not(feature = "git")) {
+        if config.append_git_hash {
+            bail!("Cannot append the git hash: built without git support");
         }
-        Format::Text | Format::Console => {
-            // In Console mode, we could add colors or other specific formatting later
-            writeln!(writer, "./{}\n---", path.display())?;
-            write_content_lines(writer, content, config.line_numbers)?;
-            writeln!(writer, "---")?;
+        if config.include_submodules {
+            bail!("Cannot include submodules: built without git support");
+        }
+        if let Some(template) = &config.output_template
+            && !config.lenient_suffix
+            && (template.contains("{hash}") || template.contains("{branch}"))
+        {
+            bail!(
+                "Cannot use {{hash}} or {{branch}} in the output template: built without git support"
+            );
+        }
+        if let Some(suffix) = &config.name_suffix
+            && !config.lenient_suffix
+            && (suffix.contains("{hash}") || suffix.contains("{branch}"))
+        {
+            bail!(
+                "Cannot use {{hash}} or {{branch}} in the name suffix: built without git support"
+            );
         }
     }
     Ok(())
 }
 
-/// Writes content line by line to the writer, optionally including line numbers.
+/// Writes a heading that starts a new run when appending to an existing bundle.
 ///
 /// # Arguments
-/// * `writer` - The writer to output the content.
-/// * `content` - The content to write.
-/// * `line_numbers` - Whether to include line numbers.
+/// * `writer` - The writer to output the heading.
+/// * `config` - The configuration options for the bundling process.
 ///
 /// # Returns
-/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
-fn write_content_lines(writer: &mut dyn Write, content: &str, line_numbers: bool) -> Result<()> {
-    if line_numbers {
-        for (i, line) in content.lines().enumerate() {
-            writeln!(writer, "{:4} | {}", i + 1, line)?;
+/// * `io::Result<()>` - Returns `Ok(())` if successful, or an error if writing fails.
+fn write_run_separator(writer: &mut dyn Write, config: &Config) -> io::Result<()> {
+    let directories = config
+        .directories
+        .iter()
+        .map(|directory| directory.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let at = reproducible::run_time(config)
+        .map(|time| format!(" at {}", meta::format_time(time, reproducible::utc(config))))
+        .unwrap_or_default();
+    match config.format {
+        Format::Markdown => writeln!(writer, "---\n\n## Run: `{}`{}\n", directories, at),
+        Format::Text | Format::Console => {
+            writeln!(writer, "=== Run: {}{} ===", directories, at)
         }
-    } else {
-        writeln!(writer, "{}", content)?;
+        Format::ClaudeXml => writeln!(
+            writer,
+            "<!-- Run: {}{} -->",
+            directories.replace("--", "- -"),
+            at
+        ),
+        // Rejected before the run: a second JSON document, a second AsciiDoc document
+        // header, or a second archive would make the file invalid.
+        Format::ChatJson | Format::Asciidoc | Format::Tar => Ok(()),
     }
-    Ok(())
 }
 
-/// Checks if a directory entry is hidden based on the configuration.
+/// Determines the output writer (file, stdout, or clipboard buffer) based on the configuration.
+///
+/// When copying to the clipboard, the output is collected in `clipboard_buffer`: instead
+/// of stdout when no output file is set, or in addition to the output file otherwise.
+/// With `tee`, output written to a file is echoed to stdout as well. With `pager`, console
+/// output for a terminal goes through the pager instead of straight to stdout.
 ///
 /// # Arguments
-/// * `entry` - The directory entry to check.
+/// * `output_path` - The optional output file path.
 /// * `config` - The configuration options for the bundling process.
+/// * `clipboard_buffer` - The buffer receiving the clipboard copy of the output.
+///
+/// # Returns
+/// * `Result<Box<dyn Write>>` - Returns a writer for the output.
+fn determine_output_writer<'a>(
+    output_path: &Option<PathBuf>,
+    config: &Config,
+    clipboard_buffer: &'a mut Vec<u8>,
+) -> Result<Box<dyn Write + 'a>> {
+    match (output_path, config.clipboard) {
+        (Some(path), clipboard) => {
+            debug!("Output will be written to: {}", path.display());
+            let file = if config.append {
+                File::options().append(true).create(true).open(path)
+            } else {
+                File::create(path)
+            }
+            .map_err(|source| Error::OutputCreate {
+                path: path.clone(),
+                source,
+            })?;
+            let file = BufWriter::new(SyncedFile(file));
+            let writer: Box<dyn Write + 'a> = if config.tee {
+                Box::new(TeeWriter::new(
+                    (file, "the output file"),
+                    (BufWriter::new(io::stdout()), "stdout"),
+                ))
+            } else {
+                Box::new(file)
+            };
+            if clipboard {
+                Ok(Box::new(TeeWriter::new(
+                    (writer, "the output"),
+                    (clipboard_buffer, "the clipboard buffer"),
+                )))
+            } else {
+                Ok(writer)
+            }
+        }
+        (None, true) => {
+            debug!("Output will be copied to the clipboard.");
+            Ok(Box::new(clipboard_buffer))
+        }
+        (None, false) => {
+            if config.pager
+                && matches!(config.format, Format::Console)
+                && io::stdout().is_terminal()
+                && let Some(pager) = Pager::spawn()
+            {
+                return Ok(Box::new(pager));
+            }
+            debug!("Output will be written to stdout.");
+            Ok(Box::new(BufWriter::new(io::stdout())))
+        }
+    }
+}
+
+/// Creates the missing parent directories of an output file.
+///
+/// # Arguments
+/// * `path` - The output file.
 ///
 /// # Returns
-/// * `bool` - Returns `true` if the entry is hidden, `false` otherwise.
-fn is_hidden(entry: &DirEntry, config: &Config) -> bool {
-    config.ignore_hidden
-        && entry
-            .file_name()
-            .to_str()
-            .map(|s| s.starts_with('.'))
-            .unwrap_or(false)
+/// * `Result<()>` - Returns `Ok(())` if the parent directory exists, or an error naming the
+///   first directory that could not be created.
+fn create_parent_dirs(path: &Path) -> Result<()> {
+    let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    else {
+        return Ok(());
+    };
+    if parent.is_dir() {
+        return Ok(());
+    }
+    if let Err(err) = fs::create_dir_all(parent) {
+        // The outermost directory still missing is the one that could not be made.
+        let missing = parent
+            .ancestors()
+            .take_while(|directory| !directory.exists())
+            .last()
+            .unwrap_or(parent);
+        return Err(err).with_context(|| {
+            format!(
+                "Failed to create directory {} for output file {}",
+                missing.display(),
+                path.display()
+            )
+        });
+    }
+    debug!("Created directory {}", parent.display());
+    Ok(())
+}
+
+/// An output file whose `flush` also waits for its data to reach the disk, so a full disk
+/// or a failing device is reported by the run rather than lost when the file is closed.
+struct SyncedFile(File);
+
+impl Write for SyncedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        sync_file(&self.0)
+    }
+}
+
+/// Waits for the data written to a file to reach the disk.
+///
+/// Devices and pipes such as `/dev/null` cannot be synced, which is not a failure.
+pub(crate) fn sync_file(file: &File) -> io::Result<()> {
+    match file.sync_all() {
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::InvalidInput | io::ErrorKind::Unsupported
+            ) =>
+        {
+            Ok(())
+        }
+        synced => synced,
+    }
+}
+
+/// A writer that duplicates everything written to it into two underlying writers.
+///
+/// Errors name the sink that failed.
+struct TeeWriter<A: Write, B: Write> {
+    first: (A, &'static str),
+    second: (B, &'static str),
+}
+
+impl<A: Write, B: Write> TeeWriter<A, B> {
+    /// Creates a tee over two writers, each paired with the name used in its errors.
+    fn new(first: (A, &'static str), second: (B, &'static str)) -> Self {
+        TeeWriter { first, second }
+    }
 }
 
-/// Checks if a directory entry is ignored by `.gitignore` rules.
+/// Adds the name of the sink that failed to an I/O error.
+fn sink_error(name: &str, err: io::Error) -> io::Error {
+    io::Error::new(err.kind(), format!("failed to write to {}: {}", name, err))
+}
+
+impl<A: Write, B: Write> Write for TeeWriter<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (first, first_name) = &mut self.first;
+        first
+            .write_all(buf)
+            .map_err(|err| sink_error(first_name, err))?;
+        let (second, second_name) = &mut self.second;
+        second
+            .write_all(buf)
+            .map_err(|err| sink_error(second_name, err))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let (first, first_name) = &mut self.first;
+        first.flush().map_err(|err| sink_error(first_name, err))?;
+        let (second, second_name) = &mut self.second;
+        second.flush().map_err(|err| sink_error(second_name, err))
+    }
+}
+
+/// Processes the specified directory and writes the bundled content to the writer.
 ///
 /// # Arguments
-/// * `entry` - The directory entry to check.
-/// * `gitignore` - The `.gitignore` rules to respect.
 /// * `config` - The configuration options for the bundling process.
+/// * `source` - Where the files come from.
+/// * `writer` - The writer to output the bundled content.
+/// * `color` - Whether the output should be colored.
+/// * `hooks` - The callbacks invoked for written and skipped files.
+/// * `formatter` - The formatter that writes the bundle.
 ///
 /// # Returns
-/// * `bool` - Returns `true` if the entry is ignored, `false` otherwise.
-fn is_ignored(entry: &DirEntry, gitignore: &Gitignore, config: &Config) -> bool {
-    if !config.respect_gitignore {
-        return false;
+/// * `Result<RunSummary>` - Statistics about the processed files, or an error if the process fails.
+fn process_directory(
+    config: &Config,
+    source: &dyn FileSource,
+    writer: &mut dyn Write,
+    color: bool,
+    hooks: &mut dyn Hooks,
+    formatter: &mut dyn Formatter,
+) -> Result<RunSummary> {
+    reproducible::check(config)?;
+    let mut writer = LimitedWriter::new(writer, config.max_total_size);
+    let mut truncated_at = None;
+    // With `group_by`, the files of a section are held back until the section is complete,
+    // so its header can show subtotals. Language sections are only complete at the end,
+    // and are then ordered by their totals.
+    let grouping = group_by(config).filter(|_| !config.dry_run);
+    let mut group: Option<(String, Vec<PendingFile>)> = None;
+    let mut languages: Vec<(String, Vec<PendingFile>)> = Vec::new();
+    let mut summary = RunSummary {
+        tokenizer: config.tokenizer,
+        ..RunSummary::default()
+    };
+    let mut first_by_hash: HashMap<String, PathBuf> = HashMap::new();
+    // Files admitted to the bundle so far, counted against `max_files` across all sections.
+    let mut admitted = 0;
+    let mut failure = None;
+    let to_stdout = match config.output {
+        Some(_) => config.tee,
+        None => !config.clipboard,
+    };
+    let mut progress = Progress::new(config.progress, to_stdout);
+    let churn = Churn::for_run(config)?;
+    let mut reach = Reach::for_run(config)?;
+    let workspace = Workspace::for_run(config)?;
+    let mut entries = gather_files(
+        config,
+        source,
+        churn.as_ref(),
+        reach.as_mut(),
+        workspace.as_ref(),
+        &mut |path, reason| {
+            summary.record_skip(path, reason);
+            hooks.on_skipped(path, reason);
+        },
+    )?;
+    if let Some(churn) = &churn {
+        summary.churn = churn.top(&entries);
+    }
+    summary.reached = reach.as_ref().map(Reach::counts);
+    let piped = config
+        .stdin_file
+        .as_deref()
+        .map(|name| PipedFile::read(name, config))
+        .transpose()?;
+    if let Some(piped) = &piped {
+        entries.push(piped.file.clone());
+    }
+    let cached = config
+        .cache
+        .as_deref()
+        .map(|path| Cache::load(path, config));
+    let grep = Grep::for_run(config)?;
+    let license = LicenseHeaders::for_run(config)?;
+    let filters = Filters::for_run(config);
+    let readers = Readers {
+        cache: cached.as_ref(),
+        grep: grep.as_ref(),
+        filters: filters.as_ref(),
+        license: &license,
+        stream: !hooks.needs_content() && streams(config, color),
+    };
+    let prepare = |file: &SourceFile| match &piped {
+        Some(piped) if file.path == piped.file.path => {
+            let readers = Readers {
+                cache: None,
+                ..readers
+            };
+            prepare_file(file, &piped.config, &piped.source, readers)
+        }
+        _ => prepare_file(file, config, source, readers),
+    };
+    // With `fit_budget`, `lang_stats`, or `frontmatter`, the files are read ahead of the
+    // header or selection that needs their tokens or lines, and each is then written from
+    // that one read.
+    let mut read_ahead = None;
+    if let Some(budget) = config.fit_budget {
+        let (selection, prepared) = budget::fit(
+            &mut entries,
+            config,
+            budget,
+            churn.as_ref(),
+            prepare,
+            &mut |path| {
+                summary.record_skip(path, SkipReason::OverBudget);
+                hooks.on_skipped(path, SkipReason::OverBudget);
+            },
+        )?;
+        if !selection.omitted.is_empty() {
+            warn!(
+                "Left out {} files (~{} tokens) to fit the budget of {} tokens",
+                selection.omitted.len(),
+                selection.omitted_tokens(),
+                budget
+            );
+        }
+        summary.budget = Some(selection);
+        read_ahead = Some(prepared);
+    }
+    if read_ahead.is_none() && (config.lang_stats || (config.frontmatter && !config.dry_run)) {
+        let mut prepared = Prepared::with_capacity(entries.len());
+        parallel::for_each_ordered(
+            &entries,
+            parallel::job_count(config.jobs),
+            prepare,
+            |file, result| {
+                prepared.insert(file.path.clone(), result);
+                ControlFlow::Continue(())
+            },
+        );
+        read_ahead = Some(prepared);
+    }
+    if let Some(prepared) = read_ahead.as_ref().filter(|_| config.lang_stats) {
+        let mut counter = LanguageCounter::default();
+        let read = entries
+            .iter()
+            .filter_map(|file| match prepared.get(&file.path) {
+                Some(Ok(Some(entry))) => Some(entry),
+                _ => None,
+            });
+        for entry in read.take(config.max_files.unwrap_or(usize::MAX)) {
+            counter.add(entry, &config.lang_map);
+        }
+        summary.languages = counter.finish();
+    }
+    let frontmatter_tokens = read_ahead
+        .as_ref()
+        .filter(|_| config.frontmatter)
+        .map(|prepared| frontmatter_tokens(&entries, config, prepared));
+    let read_ahead = read_ahead.map(Mutex::new);
+    let prepare = |file: &SourceFile| {
+        let ahead = read_ahead
+            .as_ref()
+            .and_then(|prepared| prepared.lock().unwrap().remove(&file.path));
+        ahead.unwrap_or_else(|| prepare(file))
+    };
+
+    if !config.dry_run {
+        let files: Vec<PathBuf> = entries.iter().map(|f| f.display_path.clone()).collect();
+        let metadata = (config.metadata_header || config.frontmatter).then(|| BundleMetadata {
+            languages: summary.languages.clone(),
+            ..BundleMetadata::collect(config, entries.len())
+        });
+        let frontmatter = metadata
+            .as_ref()
+            .filter(|_| config.frontmatter)
+            .map(|metadata| {
+                frontmatter::render(config, metadata, frontmatter_tokens.unwrap_or_default())
+            });
+        let context = RunContext {
+            config,
+            files: &files,
+            color,
+            metadata: metadata.as_ref().filter(|_| config.metadata_header),
+            frontmatter: frontmatter.as_deref(),
+        };
+        let begun = (|| -> Result<()> {
+            if let Some(frontmatter) = &frontmatter {
+                writer.write_all(frontmatter.as_bytes())?;
+            }
+            if let Some(preamble) = &config.preamble {
+                summary.record_framing_text(preamble);
+                if !formatter.embeds_framing_text() {
+                    write_framing_text(&mut writer, preamble)?;
+                    writeln!(writer)?;
+                }
+            }
+            formatter.begin(&mut writer, &context)
+        })();
+        if let Err(err) = begun {
+            return Err(write_error(err, &mut writer, config));
+        }
+    }
+
+    let mut cache = cached.as_ref().map(Cache::successor);
+    parallel::for_each_ordered(
+        &entries,
+        parallel::job_count(config.jobs),
+        prepare,
+        |file, prepared| {
+            if hooks.cancelled() {
+                summary.interrupted = true;
+                return ControlFlow::Break(());
+            }
+            progress.inc(&file.path);
+            if let (Some(cache), Ok(Some(entry))) = (&mut cache, &prepared) {
+                cache.insert(entry);
+            }
+            let entry = match prepared {
+                Ok(Some(entry)) => entry,
+                Ok(None) => {
+                    summary.record_skip(&file.path, SkipReason::Filtered);
+                    hooks.on_skipped(&file.path, SkipReason::Filtered);
+                    return ControlFlow::Continue(());
+                }
+                Err(err)
+                    if config.binary_placeholders
+                        && !config.dry_run
+                        && matches!(
+                            skip_reason(&err),
+                            SkipReason::Binary | SkipReason::TooLarge
+                        ) =>
+                {
+                    let reason = skip_reason(&err);
+                    debug!("Listing a placeholder: {:#}", err);
+                    hooks.on_skipped(&file.path, reason);
+                    FileEntry::placeholder(file, source.len(file), reason)
+                }
+                Err(err) => {
+                    let reason = skip_reason(&err);
+                    summary.record_skip(&file.path, reason);
+                    hooks.on_skipped(&file.path, reason);
+                    match reason {
+                        SkipReason::ReadError => return file_failure(err, config, &mut failure),
+                        SkipReason::FilterFailed if config.strict => {
+                            return file_failure(err, config, &mut failure);
+                        }
+                        SkipReason::Generated | SkipReason::OptOut => info!("Skipping {:#}", err),
+                        SkipReason::NoMatch => debug!("Skipping {:#}", err),
+                        SkipReason::TooLarge => info!("Skipping {:#}", err),
+                        SkipReason::Empty | SkipReason::TooSmall => {
+                            debug!("Skipping {:#}", err);
+                            if config.dry_run
+                                && let Err(err) =
+                                    write_dry_run_skip_line(&mut writer, &file.display_path, reason)
+                            {
+                                summary.errors += 1;
+                                return file_failure(err, config, &mut failure);
+                            }
+                        }
+                        _ => warn!("Skipping {:#}", err),
+                    }
+                    return ControlFlow::Continue(());
+                }
+            };
+            if entry.placeholder.is_none() && config.max_files.is_some_and(|max| admitted >= max) {
+                summary.record_skip(&file.path, SkipReason::OverLimit);
+                hooks.on_skipped(&file.path, SkipReason::OverLimit);
+                return ControlFlow::Continue(());
+            }
+            let entry = match hooks.on_file(&entry) {
+                _ if entry.placeholder.is_some() => entry,
+                FileAction::Include => entry,
+                FileAction::Skip => {
+                    summary.record_skip(&file.path, SkipReason::Filtered);
+                    return ControlFlow::Continue(());
+                }
+                FileAction::Replace(content) => entry.with_content(content, config),
+            };
+            if entry.placeholder.is_none() {
+                admitted += 1;
+            }
+            let original = match &entry.sha256 {
+                Some(hash) if config.dedup => {
+                    let original = first_by_hash.get(hash).cloned();
+                    if original.is_none() {
+                        first_by_hash.insert(hash.clone(), entry.display_path.clone());
+                    }
+                    original
+                }
+                _ => None,
+            };
+            let pending = match grouping {
+                Some(GroupBy::Dir | GroupBy::Member) => {
+                    // Directory and member sections are contiguous, see `gather_files`.
+                    let name = match &workspace {
+                        Some(workspace) if grouping == Some(GroupBy::Member) => {
+                            workspace.section(&entry.relative_path)
+                        }
+                        _ => {
+                            let directory =
+                                group_directory(&entry.display_path, config.group_depth);
+                            format!("{}/", directory.display())
+                        }
+                    };
+                    match &mut group {
+                        Some((current, entries)) if *current == name => {
+                            entries.push((entry, original));
+                            return ControlFlow::Continue(());
+                        }
+                        _ => group.replace((name, vec![(entry, original)])),
+                    }
+                }
+                Some(GroupBy::Lang) => {
+                    let name = section_name(&entry.language_path(), &config.lang_map);
+                    match languages.iter_mut().find(|(current, _)| *current == name) {
+                        Some((_, entries)) => entries.push((entry, original)),
+                        None => languages.push((name, vec![(entry, original)])),
+                    }
+                    return ControlFlow::Continue(());
+                }
+                None => Some((String::new(), vec![(entry, original)])),
+            };
+            let Some((name, entries)) = pending else {
+                return ControlFlow::Continue(());
+            };
+            let section = grouping.map(|_| FileGroup::new(name, &entries));
+            write_entries(
+                section.as_ref(),
+                entries,
+                &mut writer,
+                config,
+                &mut summary,
+                formatter,
+                &mut failure,
+            )
+            .map_break(|path| truncated_at = path)
+        },
+    );
+    let mut sections: Vec<(FileGroup, Vec<PendingFile>)> = group
+        .take()
+        .into_iter()
+        .chain(languages)
+        .map(|(name, entries)| (FileGroup::new(name, &entries), entries))
+        .collect();
+    match config.group_order {
+        GroupOrder::Tokens => sections
+            .sort_by(|(a, _), (b, _)| b.tokens.cmp(&a.tokens).then_with(|| a.name.cmp(&b.name))),
+        GroupOrder::Alpha => {
+            sections.sort_by_cached_key(|(section, _)| section.name.to_lowercase())
+        }
+    }
+    for (section, entries) in sections {
+        if failure.is_some() || writer.exceeded() {
+            break;
+        }
+        if hooks.cancelled() {
+            summary.interrupted = true;
+            break;
+        }
+        if let ControlFlow::Break(path) = write_entries(
+            Some(&section),
+            entries,
+            &mut writer,
+            config,
+            &mut summary,
+            formatter,
+            &mut failure,
+        ) {
+            truncated_at = path;
+            break;
+        }
+    }
+    progress.finish();
+    if let Some(cache) = &cache {
+        cache.save();
+    }
+    if let Some(err) = failure {
+        return Err(match writer.take_failure() {
+            Some(failed) => failed.into(),
+            None => err.context("Aborting: a file failed in strict mode"),
+        });
+    }
+    if writer.exceeded() {
+        return Err(truncate_output(&mut writer, config, truncated_at));
+    }
+    if let Some(max) = config.max_files
+        && summary.skipped_count(SkipReason::OverLimit) > 0
+    {
+        warn!(
+            "Stopped after {} files (--max-files); left out {} more",
+            max,
+            summary.skipped_count(SkipReason::OverLimit)
+        );
+    }
+    if summary.skipped_count(SkipReason::Sensitive) > 0 {
+        warn!(
+            "Left out {} files that may hold secrets, such as .env files and private keys; use --include-sensitive to bundle them",
+            summary.skipped_count(SkipReason::Sensitive)
+        );
+    }
+    if summary.interrupted {
+        warn!(
+            "Interrupted: wrote {} of {} files; finishing the output with those",
+            summary.files_included,
+            entries.len()
+        );
+    } else if summary.files_included == 0 && summary.errors == 0 {
+        let empty = NoFilesMatched::new(config, &summary);
+        if config.fail_if_empty {
+            return Err(empty.into());
+        }
+        warn!("{}", empty);
+    }
+
+    if !config.dry_run {
+        for text in [&config.postamble, &config.question].into_iter().flatten() {
+            summary.record_framing_text(text);
+        }
+        let finished = (|| -> Result<()> {
+            if config.between.is_some() {
+                let filter = FileFilter::new(config)?;
+                let deleted: Vec<PathBuf> = source
+                    .deleted()
+                    .into_iter()
+                    .filter(|path| filter.matches_file(path).is_included())
+                    .collect();
+                if !deleted.is_empty() {
+                    formatter.deleted(&mut writer, &deleted, config)?;
+                }
+            }
+            if let Some(selection) = summary.budget.as_ref().filter(|s| !s.omitted.is_empty()) {
+                formatter.omitted(&mut writer, selection, config)?;
+            }
+            if let Some(max) = config.max_files
+                && summary.skipped_count(SkipReason::OverLimit) > 0
+            {
+                formatter.limited(
+                    &mut writer,
+                    max,
+                    summary.skipped_count(SkipReason::OverLimit),
+                    config,
+                )?;
+            }
+            if let Some(algorithm) = config.hash {
+                let digest = algorithm.bundle_digest(&summary.content_hashes);
+                formatter.digest(&mut writer, &digest, config)?;
+                summary.bundle_digest = Some(digest);
+            }
+            if let Some(question) = &config.question {
+                formatter.question(&mut writer, question, config)?;
+            }
+            formatter.finish(&mut writer, &summary)?;
+            if let Some(postamble) = config
+                .postamble
+                .as_ref()
+                .filter(|_| !formatter.embeds_framing_text())
+            {
+                write_framing_text(&mut writer, postamble)?;
+            }
+            Ok(())
+        })();
+        if let Err(err) = finished {
+            return Err(write_error(err, &mut writer, config));
+        }
+    }
+
+    if let Some(top) = config.top {
+        summary.record_largest_files(top);
+    }
+    debug!("File bundling complete.");
+    Ok(summary)
+}
+
+/// Writes a preamble or postamble verbatim, ending it with a newline.
+///
+/// # Arguments
+/// * `writer` - The writer to output the bundled content.
+/// * `text` - The text to write.
+///
+/// # Returns
+/// * `io::Result<()>` - Returns `Ok(())` if successful, or an error if the write fails.
+fn write_framing_text(writer: &mut dyn Write, text: &str) -> io::Result<()> {
+    write!(writer, "{}", text)?;
+    if !text.ends_with('\n') {
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Handles a file that could not be read or written.
+///
+/// The error is logged and the run continues, unless `strict` is set, in which case the
+/// error is kept in `failure` and the run stops. A closed pipe always stops the run, see
+/// [`output_failure`].
+///
+/// # Arguments
+/// * `err` - The error for the file.
+/// * `config` - The configuration options for the bundling process.
+/// * `failure` - Receives the error in strict mode.
+///
+/// # Returns
+/// * `ControlFlow<()>` - Whether to continue with the next file.
+fn file_failure(
+    err: anyhow::Error,
+    config: &Config,
+    failure: &mut Option<anyhow::Error>,
+) -> ControlFlow<()> {
+    if config.strict {
+        *failure = Some(err);
+        ControlFlow::Break(())
+    } else {
+        error!("{:#}", err);
+        ControlFlow::Continue(())
+    }
+}
+
+/// Stops the run when the output itself failed, e.g. because its reader went away or the
+/// disk is full, since nothing more can be written to it.
+///
+/// # Arguments
+/// * `err` - The error writing a file.
+/// * `writer` - The size-limited writer the error came from.
+/// * `failure` - Receives the error if the output failed.
+///
+/// # Returns
+/// * `Option<anyhow::Error>` - The error back if the output is still usable.
+fn output_failure(
+    err: anyhow::Error,
+    writer: &LimitedWriter,
+    failure: &mut Option<anyhow::Error>,
+) -> Option<anyhow::Error> {
+    if writer.failed() {
+        *failure = Some(err);
+        None
+    } else {
+        Some(err)
+    }
+}
+
+/// A prepared file waiting to be written, with the path of the earlier file it duplicates.
+pub(crate) type PendingFile = (FileEntry, Option<PathBuf>);
+
+/// Writes prepared files, after the header of their directory when grouping.
+///
+/// # Arguments
+/// * `group` - The section the files belong to, if grouping.
+/// * `entries` - The files, each with the path of the earlier file it duplicates, if any.
+/// * `writer` - The size-limited writer to output the files.
+/// * `config` - The configuration options for the bundling process.
+/// * `summary` - The run statistics to update.
+/// * `formatter` - The formatter that writes the files.
+/// * `failure` - Set to the error that aborts the run: the output failing, or a file
+///   failing in strict mode.
+///
+/// # Returns
+/// * `ControlFlow<Option<PathBuf>>` - `Break` to stop the run, with the file being written
+///   if the size limit was reached.
+fn write_entries(
+    group: Option<&FileGroup>,
+    entries: Vec<PendingFile>,
+    writer: &mut LimitedWriter,
+    config: &Config,
+    summary: &mut RunSummary,
+    formatter: &mut dyn Formatter,
+    failure: &mut Option<anyhow::Error>,
+) -> ControlFlow<Option<PathBuf>> {
+    if let Some(group) = group
+        && let Err(err) = formatter.group(writer, group, config)
+    {
+        if writer.exceeded() {
+            return ControlFlow::Break(
+                entries
+                    .into_iter()
+                    .next()
+                    .map(|(entry, _)| entry.display_path),
+            );
+        }
+        let Some(err) = output_failure(err, writer, failure) else {
+            return ControlFlow::Break(None);
+        };
+        file_failure(err, config, failure).map_break(|()| None)?;
+    }
+    for (entry, original) in entries {
+        let written = match original {
+            Some(original) => {
+                write_duplicate(&entry, &original, writer, config, summary, formatter)
+            }
+            None if entry.placeholder.is_some() => {
+                write_placeholder(&entry, writer, config, summary, formatter)
+            }
+            None => write_file_entry(&entry, writer, config, summary, formatter),
+        };
+        if let Err(err) = written {
+            if writer.exceeded() {
+                return ControlFlow::Break(Some(entry.display_path));
+            }
+            let Some(err) = output_failure(err, writer, failure) else {
+                return ControlFlow::Break(None);
+            };
+            summary.errors += 1;
+            file_failure(err, config, failure).map_break(|()| None)?;
+        }
+    }
+    ControlFlow::Continue(())
+}
+
+/// Names the output in the error of a run whose output failed.
+///
+/// # Arguments
+/// * `err` - The error of the run.
+/// * `destination` - The output, e.g. `stdout`.
+///
+/// # Returns
+/// * `anyhow::Error` - `err`, naming `destination` if it is an [`OutputFailed`].
+fn name_output(err: anyhow::Error, destination: &str) -> anyhow::Error {
+    match err.downcast::<OutputFailed>() {
+        Ok(failed) => OutputFailed {
+            output: Some(destination.to_string()),
+            ..failed
+        }
+        .into(),
+        Err(err) => err,
+    }
+}
+
+/// Converts an error writing the bundle's framing, which may have been caused by the size limit.
+///
+/// # Arguments
+/// * `err` - The write error.
+/// * `writer` - The size-limited writer the error came from.
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `anyhow::Error` - An [`OutputLimitExceeded`] if the limit was reached, otherwise `err`
+///   with context.
+fn write_error(err: anyhow::Error, writer: &mut LimitedWriter, config: &Config) -> anyhow::Error {
+    if writer.exceeded() {
+        truncate_output(writer, config, None)
+    } else if let Some(failed) = writer.take_failure() {
+        failed.into()
+    } else {
+        err.context("Failed to write output")
+    }
+}
+
+/// Ends a bundle that reached `max_total_size` with a truncation notice.
+///
+/// The notice is written past the limit, and the output is flushed so that everything
+/// written so far is kept.
+///
+/// # Arguments
+/// * `writer` - The size-limited writer that reached its limit.
+/// * `config` - The configuration options for the bundling process.
+/// * `path` - The file being written when the limit was reached, if any.
+///
+/// # Returns
+/// * `anyhow::Error` - The [`OutputLimitExceeded`] error to return, or the error that
+///   prevented writing the notice.
+fn truncate_output(
+    writer: &mut LimitedWriter,
+    config: &Config,
+    path: Option<PathBuf>,
+) -> anyhow::Error {
+    let exceeded = OutputLimitExceeded {
+        limit: config.max_total_size,
+        path,
+    };
+    let inner = writer.inner();
+    // A note would be taken for part of the last archive entry; the run fails either way.
+    let notice = match config.format {
+        Format::Tar => inner.flush(),
+        _ => writeln!(inner, "\n\n[Output truncated: {}]", exceeded).and_then(|()| inner.flush()),
+    };
+    match notice {
+        Ok(()) => exceeded.into(),
+        Err(err) => anyhow::Error::new(err).context("Failed to write output"),
+    }
+}
+
+/// Lists the files of a source and puts them in output order.
+///
+/// Display paths are renamed, and the files are sorted, prioritized, and ordered by the
+/// order file.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+/// * `source` - Where the files come from.
+/// * `churn` - The commit counts for [`SortOrder::Churn`], if the run loaded them.
+/// * `reach` - The entry files, whose imports are followed before any other filter.
+/// * `workspace` - The Cargo workspace, whose unselected members are left out.
+/// * `on_skipped` - Called for each path left out along the way.
+///
+/// # Returns
+/// * `Result<Vec<SourceFile>>` - The files in output order, or an error if they cannot be listed.
+fn gather_files(
+    config: &Config,
+    source: &dyn FileSource,
+    churn: Option<&Churn>,
+    reach: Option<&mut Reach>,
+    workspace: Option<&Workspace>,
+    on_skipped: &mut dyn FnMut(&Path, SkipReason),
+) -> Result<Vec<SourceFile>> {
+    let filter = FileFilter::new(config)?;
+    let mut entries = source.list(config, on_skipped)?;
+    if let Some(reach) = reach {
+        reach.retain(&mut entries, source, on_skipped)?;
+    }
+    if let Some(workspace) = workspace {
+        entries.retain(|file| {
+            let keep = workspace.includes(&file.relative_path);
+            if !keep {
+                on_skipped(&file.path, SkipReason::Filtered);
+            }
+            keep
+        });
+    }
+    // Exact paths come first: excluded files are dropped, and included ones are marked
+    // `forced` so the filters below keep them.
+    filter
+        .overrides()
+        .apply(&mut entries, source, config, on_skipped);
+    let mut nested = NestedConfigs::new(config);
+    for file in &mut entries {
+        file.scope = nested.scope(file)?;
+    }
+    entries.retain(|file| {
+        if !sensitive::is_sensitive(&file.relative_path) {
+            return true;
+        }
+        if file.config(config).include_sensitive {
+            warn!(
+                "Including {}, which may hold secrets (--include-sensitive)",
+                file.relative_path.display()
+            );
+            return true;
+        }
+        // Only files the include and exclude lists would keep are counted as secrets, so
+        // the warning does not name files that were never wanted.
+        let wanted = file.forced || file.filter(&filter).rules_allow(&file.relative_path);
+        let reason = if wanted {
+            SkipReason::Sensitive
+        } else {
+            SkipReason::Filtered
+        };
+        on_skipped(&file.path, reason);
+        false
+    });
+    entries.retain(|file| {
+        let lockfile = !file.forced && file.filter(&filter).is_lockfile(&file.relative_path);
+        if lockfile {
+            on_skipped(&file.path, SkipReason::Lockfile);
+        }
+        !lockfile
+    });
+    if config.respect_linguist {
+        let mut attributes = Attributes::new(source, config);
+        entries.retain(|file| {
+            if file.forced {
+                return true;
+            }
+            let Some((name, rule)) = attributes.linguist(&file.relative_path) else {
+                return true;
+            };
+            let attribution = format!(
+                "Skipping {} ({} by `{}` in {})",
+                file.path.display(),
+                name,
+                rule.line,
+                rule.from.display()
+            );
+            // A dry run is how patterns get debugged, so its attributions are shown by default.
+            if config.dry_run {
+                info!("{}", attribution);
+            } else {
+                debug!("{}", attribution);
+            }
+            on_skipped(&file.path, SkipReason::Linguist);
+            false
+        });
+    }
+    entries.retain(|file| {
+        let keep = file.forced || !file.filter(&filter).is_test_excluded(&file.relative_path);
+        if !keep {
+            on_skipped(&file.path, SkipReason::Tests);
+        }
+        keep
+    });
+    if config.newer_than.is_some() || config.older_than.is_some() {
+        let now = SystemTime::now();
+        let newer_than = config.newer_than.map(|bound| bound.resolve(now));
+        let older_than = config.older_than.map(|bound| bound.resolve(now));
+        entries.retain(|file| {
+            if file.forced {
+                return true;
+            }
+            let Some(modified) = source.modified(file) else {
+                warn!(
+                    "Including {}: its modification time cannot be read",
+                    file.path.display()
+                );
+                return true;
+            };
+            let keep = newer_than.is_none_or(|bound| modified >= bound)
+                && older_than.is_none_or(|bound| modified < bound);
+            if !keep {
+                on_skipped(&file.path, SkipReason::Modified);
+            }
+            keep
+        });
+    }
+    let prefix = match &config.relative_to {
+        Some(relative_to) => relative_to.prefix(&resolve_roots(&config.directories)?.1)?,
+        None => PathBuf::new(),
+    };
+    let mut renamer = PathRenamer::new(&config.rename_paths);
+    for entry in &mut entries {
+        entry.display_path = renamer.display_path(&prefix.join(&entry.relative_path));
+        if !config.native_paths || config.reproducible {
+            entry.display_path = slash_path(&entry.display_path);
+        }
+    }
+    sort_entries(&mut entries, config, source, churn);
+    prioritize_entries(&mut entries, config)?;
+    // Applied after the order file, which reports listed files these rules leave out.
+    if let Some(order_file) = &config.order_file {
+        apply_order_file(order_file, &mut entries, config, &filter, on_skipped)?;
+    }
+    entries.retain(|file| {
+        let keep = file.forced || passes_rules(file, source, file.filter(&filter), config);
+        if !keep {
+            on_skipped(&file.path, SkipReason::Filtered);
+        }
+        keep
+    });
+    match (group_by(config), workspace) {
+        (Some(GroupBy::Dir), _) => group_entries(&mut entries, |entry| {
+            group_directory(&entry.display_path, config.group_depth)
+        }),
+        (Some(GroupBy::Member), Some(workspace)) => group_entries(&mut entries, |entry| {
+            workspace.section(&entry.relative_path)
+        }),
+        _ => {}
+    }
+
+    Ok(entries)
+}
+
+/// Where a run reads its files: the configured directories, an archive given as the only
+/// input, or the files that differ between two git refs.
+enum Input<'a> {
+    Fs(FsSource<'a>),
+    Archive(ArchiveSource),
+    Between(between::TreeSource),
+}
+
+impl<'a> Input<'a> {
+    /// Chooses the source for the configured inputs, reading an archive into memory.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    /// * `outputs` - The output files, which are never bundled into themselves.
+    ///
+    /// # Returns
+    /// * `Result<Input>` - The source, or an error if an archive cannot be read or is combined
+    ///   with other inputs, or the `between` refs cannot be diffed.
+    fn open(config: &Config, outputs: Vec<&'a Path>) -> Result<Self> {
+        if let Some(range) = &config.between {
+            if config.directories.iter().any(|path| is_archive(path)) {
+                bail!("--between cannot be combined with an archive input");
+            }
+            return Ok(Input::Between(between::TreeSource::open(config, range)?));
+        }
+        match config.directories.as_slice() {
+            [path] if is_archive(path) => {
+                if config.files_from.is_some() {
+                    bail!("--files-from cannot be combined with an archive input");
+                }
+                Ok(Input::Archive(ArchiveSource::open(path, config)?))
+            }
+            directories if directories.iter().any(|path| is_archive(path)) => {
+                bail!("An archive must be the only input")
+            }
+            _ => Ok(Input::Fs(FsSource { outputs })),
+        }
+    }
+
+    /// Returns the source to list and read files from.
+    fn source(&self) -> &dyn FileSource {
+        match self {
+            Input::Fs(source) => source,
+            Input::Archive(source) => source,
+            Input::Between(source) => source,
+        }
+    }
+}
+
+/// The files in the configured directories on disk.
+struct FsSource<'a> {
+    /// The output files, which are never bundled into themselves.
+    outputs: Vec<&'a Path>,
+}
+
+impl FileSource for FsSource<'_> {
+    /// Walks the directories (or reads the file list), dropping duplicates and the files a
+    /// run writes: the output, statistics, manifest, report, and cache files.
+    fn list(
+        &self,
+        config: &Config,
+        on_skipped: &mut dyn FnMut(&Path, SkipReason),
+    ) -> Result<Vec<SourceFile>> {
+        check_git_support(config)?;
+        let (roots, base) = resolve_roots(&config.directories)?;
+        let mut submodule_roots = Vec::new();
+        if config.include_submodules {
+            for root in &roots {
+                collect_submodule_roots(root, &mut submodule_roots);
+            }
+        }
+        for range in &config.only {
+            if !base.join(&range.path).is_file() {
+                warn!("No file matches --only {}", range);
+            }
+        }
+
+        let mut files = match &config.files_from {
+            Some(list) => read_file_list(list, config.files_from_nul, &base)?,
+            None => {
+                let mut files = Vec::new();
+                for root in roots.iter().chain(&submodule_roots) {
+                    let entries = collect_entries(root, &submodule_roots, config, on_skipped)?;
+                    files.extend(entries);
+                }
+                files
+            }
+        };
+
+        // Sorting first makes the kept path deterministic when duplicates are dropped.
+        files.sort();
+        let written = [
+            config.stats_output.as_deref(),
+            config.manifest.as_deref(),
+            config.report_json.as_deref(),
+            config.cache.as_deref(),
+        ];
+        for own in self
+            .outputs
+            .iter()
+            .copied()
+            .chain(written.into_iter().flatten())
+        {
+            let Ok(own) = own.canonicalize() else {
+                continue;
+            };
+            files.retain(|path| {
+                path.file_name() != own.file_name()
+                    || path.canonicalize().is_ok_and(|path| path != own)
+            });
+        }
+        // The files of an earlier `output_dir` run are sections of this one.
+        if let Some(Ok(dir)) = config.output_dir.as_deref().map(Path::canonicalize) {
+            files.retain(|path| !path.canonicalize().is_ok_and(|path| path.starts_with(&dir)));
+        }
+        // Outputs named per run would otherwise pile up in the next bundle, each run
+        // containing the previous ones.
+        let also = config.also_output.iter().map(|path| Some(path.as_path()));
+        let named = std::iter::once(config.output.as_deref()).chain(also);
+        for output in named.filter(|_| !config.include_previous_outputs) {
+            let Some((dir, matcher)) = output_template::previous_outputs(output, config)? else {
+                continue;
+            };
+            let Ok(dir) = Path::new(".").join(dir).canonicalize() else {
+                continue;
+            };
+            files.retain(|path| {
+                let previous = path.file_name().is_some_and(|name| matcher.is_match(name))
+                    && path
+                        .parent()
+                        .and_then(|parent| parent.canonicalize().ok())
+                        .is_some_and(|parent| parent == dir);
+                if previous {
+                    info!(
+                        "Skipping {}: the output of an earlier run (see --include-previous-outputs)",
+                        path.display()
+                    );
+                }
+                !previous
+            });
+        }
+        let mut entries = Vec::new();
+        let mut seen = HashSet::new();
+        for path in files {
+            let relative_path = path.strip_prefix(&base).unwrap_or(&path).to_path_buf();
+            // With symlinks followed, the same file can be reachable through several paths.
+            let key = if config.follow_symlinks {
+                path.canonicalize().unwrap_or_else(|_| path.clone())
+            } else {
+                relative_path.clone()
+            };
+            if seen.insert(key) {
+                entries.push(SourceFile::new(path, relative_path));
+            }
+        }
+        Ok(entries)
+    }
+
+    fn find(&self, config: &Config, relative_path: &Path) -> Option<SourceFile> {
+        let (_, base) = resolve_roots(&config.directories).ok()?;
+        let path = base.join(relative_path);
+        path.is_file().then(|| SourceFile::new(path, relative_path))
+    }
+
+    fn read(&self, file: &SourceFile) -> io::Result<String> {
+        read_text_file(&file.path)
+    }
+
+    fn read_bytes(&self, file: &SourceFile) -> io::Result<Vec<u8>> {
+        fs::read(&file.path)
+    }
+
+    fn read_head(&self, file: &SourceFile, len: usize) -> io::Result<Vec<u8>> {
+        let mut head = Vec::with_capacity(len);
+        File::open(&file.path)?
+            .take(len as u64)
+            .read_to_end(&mut head)?;
+        Ok(head)
+    }
+
+    fn len(&self, file: &SourceFile) -> Option<u64> {
+        fs::metadata(&file.path).map(|m| m.len()).ok()
+    }
+
+    fn modified(&self, file: &SourceFile) -> Option<SystemTime> {
+        fs::metadata(&file.path).and_then(|m| m.modified()).ok()
+    }
+
+    fn on_disk(&self) -> bool {
+        true
+    }
+}
+
+/// How much of a file is read to tell text from binary before the rest is read.
+const SNIFF_LEN: usize = 8 * 1024;
+
+/// Reads a text file, rejecting binary files after their first block.
+///
+/// A file whose first [`SNIFF_LEN`] bytes contain a NUL byte or invalid UTF-8 is not read
+/// further, so a large binary file costs one block rather than its whole size. Text files
+/// that can be are streamed instead (see [`streams`]); this reads the rest.
+///
+/// # Arguments
+/// * `path` - The file to read.
+///
+/// # Returns
+/// * `io::Result<String>` - The content, or an `InvalidData` error if it is not UTF-8 text.
+fn read_text_file(path: &Path) -> io::Result<String> {
+    let binary = |reason| io::Error::new(io::ErrorKind::InvalidData, reason);
+    let mut file = File::open(path)?;
+    let mut content = Vec::with_capacity(SNIFF_LEN);
+    (&mut file)
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut content)?;
+    if content.contains(&0) {
+        return Err(binary("binary content (NUL byte)"));
+    }
+    if let Err(err) = std::str::from_utf8(&content)
+        // A character cut at the end of the block is completed by the rest of the file.
+        && err.error_len().is_some()
+    {
+        return Err(binary("stream did not contain valid UTF-8"));
+    }
+    let len = file.metadata().map(|m| m.len() as usize).unwrap_or(0);
+    content.reserve(len.saturating_sub(content.len()));
+    file.read_to_end(&mut content)?;
+    String::from_utf8(content).map_err(|_| binary("stream did not contain valid UTF-8"))
+}
+
+/// Lists the files selected by the configuration, reading each one lazily.
+///
+/// Files are yielded in output order, after the same filtering and transformations as
+/// [`run`], but nothing is written. Files skipped by the filters are left out, and a file
+/// that cannot be read comes through as an `Err` item without ending the iteration.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<impl Iterator<Item = Result<FileEntry, Error>>, Error>` - The selected files,
+///   or an error if they cannot be listed.
+pub fn collect_files(
+    config: &Config,
+) -> Result<impl Iterator<Item = Result<FileEntry, Error>>, Error> {
+    let input = Input::open(config, config.output.iter().map(PathBuf::as_path).collect())?;
+    let churn = Churn::for_run(config)?;
+    let grep = Grep::for_run(config)?;
+    let license = LicenseHeaders::for_run(config)?;
+    let filters = Filters::for_run(config);
+    let mut reach = Reach::for_run(config)?;
+    let workspace = Workspace::for_run(config)?;
+    let files = gather_files(
+        config,
+        input.source(),
+        churn.as_ref(),
+        reach.as_mut(),
+        workspace.as_ref(),
+        &mut |_, _| {},
+    )?;
+    Ok(files.into_iter().filter_map(move |file| {
+        let readers = Readers {
+            cache: None,
+            grep: grep.as_ref(),
+            filters: filters.as_ref(),
+            license: &license,
+            stream: false,
+        };
+        prepare_file(&file, config, input.source(), readers)
+            .map_err(|err| Error::reading(err, &file.path))
+            .transpose()
+    }))
+}
+
+/// Walks a single root directory and collects every file that passes the walk filters.
+///
+/// Ignore files are applied as git and ripgrep do (see [`walk::Walk`]), and any directory
+/// listed in `skip_roots` (other than the root itself) is pruned so submodules are only
+/// visited once.
+///
+/// # Arguments
+/// * `root` - The directory to walk.
+/// * `skip_roots` - Directories walked separately that must not be descended into.
+/// * `config` - The configuration options for the bundling process.
+/// * `on_skipped` - Called for each pruned entry.
+///
+/// # Returns
+/// * `Result<Vec<PathBuf>>` - The files found under the root, or an error if an entry cannot
+///   be accessed in strict mode.
+fn collect_entries(
+    root: &Path,
+    skip_roots: &[PathBuf],
+    config: &Config,
+    on_skipped: &mut dyn FnMut(&Path, SkipReason),
+) -> Result<Vec<PathBuf>> {
+    let mut walk = walk::Walk::new(root, skip_roots, config)?;
+    let mut files = Vec::new();
+    let mut directories = Vec::new();
+    let mut failed = HashSet::new();
+    let mut reported_loops = HashSet::new();
+    let mut unreadable = Vec::new();
+    for result in walk.entries.by_ref() {
+        match result {
+            Ok(entry) if entry.path().is_file() => files.push(entry.into_path()),
+            Ok(entry) if entry.path().is_dir() => directories.push(entry.into_path()),
+            Ok(entry) if entry.path_is_symlink() && !entry.path().exists() => {
+                warn!("Skipping broken symlink: {}", entry.path().display());
+            }
+            Ok(_) => {}
+            Err(err) if err.is_partial() => warn!("Invalid ignore rule: {}", err),
+            Err(err) => {
+                let (path, loop_ancestor) = walk::error_parts(&err);
+                failed.extend(path.map(Path::to_path_buf));
+                if let Some(ancestor) = loop_ancestor {
+                    if reported_loops.insert(ancestor.to_path_buf()) {
+                        warn!("Skipping symlink loop back to {}", ancestor.display());
+                    }
+                } else if err.io_error().map(io::Error::kind) == Some(io::ErrorKind::NotFound)
+                    && let Some(path) = path
+                {
+                    warn!("Skipping broken symlink: {}", path.display());
+                } else {
+                    unreadable.extend(path.map(Path::to_path_buf));
+                    if config.strict {
+                        return Err(err).context("Aborting: an entry failed in strict mode");
+                    }
+                    error!("Failed to access entry: {}", err);
+                }
+            }
+        }
+    }
+
+    let filtered = walk.take_filtered();
+    for (path, reason) in &filtered.skipped {
+        on_skipped(path, *reason);
+    }
+    // The walk drops entries matched by ignore files before the filter sees them, so they are
+    // the children of walked directories that neither the filter nor an error accounts for.
+    for directory in directories {
+        let Ok(children) = fs::read_dir(&directory) else {
+            continue;
+        };
+        for child in children.flatten() {
+            let path = child.path();
+            if !filtered.seen.contains(&path) && !failed.contains(&path) {
+                on_skipped(&path, SkipReason::Ignored);
+            }
+        }
+    }
+    for path in unreadable {
+        on_skipped(&path, SkipReason::ReadError);
+    }
+    Ok(files)
+}
+
+/// The path that stands for the content read with `stdin_file`, never a walked path.
+const STDIN_PATH: &str = "<stdin>";
+
+/// The content read from stdin with `stdin_file`, bundled as one more file.
+struct PipedFile {
+    /// The file, under the display name given by `stdin_file`.
+    file: SourceFile,
+    /// A source holding only the piped content.
+    source: MemorySource,
+    /// The configuration without the filters, which do not apply to the piped file.
+    config: Config,
+}
+
+impl PipedFile {
+    /// Reads stdin to its end.
+    ///
+    /// # Arguments
+    /// * `name` - The display name of the content, whose extension picks its language.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<PipedFile>` - The piped file, or an error if stdin is a terminal, is already
+    ///   used by `files_from`, or cannot be read.
+    fn read(name: &Path, config: &Config) -> Result<PipedFile> {
+        if config.files_from.as_deref() == Some(Path::new("-")) {
+            bail!("--stdin-file cannot be combined with --files-from -, which also reads stdin");
+        }
+        if io::stdin().is_terminal() {
+            bail!(
+                "--stdin-file needs content piped to stdin, e.g. \
+                 `cargo test 2>&1 | codebase-to-prompt . --stdin-file test.log`"
+            );
+        }
+        let mut content = Vec::new();
+        io::stdin()
+            .read_to_end(&mut content)
+            .context("Failed to read stdin")?;
+        let content =
+            String::from_utf8(content).context("The content piped to stdin is not valid UTF-8")?;
+        Ok(PipedFile {
+            file: SourceFile::new(STDIN_PATH, name),
+            source: [(STDIN_PATH, content)].into_iter().collect(),
+            config: Config {
+                include: Vec::new(),
+                languages: Vec::new(),
+                exclude: Vec::new(),
+                include_from: None,
+                exclude_from: None,
+                only: Vec::new(),
+                ..config.clone()
+            },
+        })
+    }
+}
+
+/// Reads the paths to bundle from a file list instead of walking the directories.
+///
+/// Paths are separated by newlines, or by NUL bytes when `nul_delimited` is set, and are
+/// resolved against `base`. An absolute path, or one with `..`, is taken relative to `base`
+/// when it leads inside it, so it is shown like the others. Paths that are not files or
+/// lead outside `base` are skipped with a warning.
+///
+/// # Arguments
+/// * `list` - The path of the file list, or `-` to read from stdin.
+/// * `nul_delimited` - Whether the paths are NUL-delimited (as printed by `git ls-files -z`).
+/// * `base` - The directory relative paths are resolved against.
+///
+/// # Returns
+/// * `Result<Vec<PathBuf>>` - The listed files, or an error if the list is unreadable or empty.
+fn read_file_list(list: &Path, nul_delimited: bool, base: &Path) -> Result<Vec<PathBuf>> {
+    let source = if list == Path::new("-") {
+        let mut source = Vec::new();
+        io::stdin()
+            .read_to_end(&mut source)
+            .context("Failed to read the file list from stdin")?;
+        source
+    } else {
+        fs::read(list).with_context(|| format!("Failed to read file list: {}", list.display()))?
+    };
+    let source = String::from_utf8(source).context("The file list is not valid UTF-8")?;
+
+    let separator = if nul_delimited { '\0' } else { '\n' };
+    let mut files = Vec::new();
+    for line in source.split(separator) {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let path = base.join(line);
+        if !path.is_file() {
+            warn!("Skipping listed path that is not a file: {}", line);
+            continue;
+        }
+        let plain = Path::new(line)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if plain {
+            files.push(path);
+            continue;
+        }
+        let inside = fs::canonicalize(base).and_then(|base| {
+            let path = fs::canonicalize(&path)?;
+            Ok(path.strip_prefix(&base).map(Path::to_path_buf).ok())
+        });
+        match inside {
+            Ok(Some(relative)) => files.push(base.join(relative)),
+            _ => warn!(
+                "Skipping listed path outside the target directory {}: {}",
+                base.display(),
+                line
+            ),
+        }
+    }
+
+    if files.is_empty() {
+        bail!("No files matched: the file list contains no existing files");
+    }
+    Ok(files)
+}
+
+/// Determines the roots to walk and the base directory that displayed paths are relative to.
+///
+/// A single directory is used as-is and is its own base, and a single file is shown
+/// relative to its parent, i.e. by its name. With several inputs, each is canonicalized
+/// and the base is their deepest common ancestor directory, so files from different roots
+/// keep distinct relative paths.
+///
+/// # Arguments
+/// * `directories` - The input directories or files.
+///
+/// # Returns
+/// * `Result<(Vec<PathBuf>, PathBuf)>` - The roots to walk and their common base, or an
+///   error naming an input that does not exist.
+fn resolve_roots(directories: &[PathBuf]) -> Result<(Vec<PathBuf>, PathBuf)> {
+    if let Some(missing) = directories
+        .iter()
+        .find(|directory| fs::metadata(directory).is_err())
+    {
+        return Err(Error::DirectoryNotFound(missing.clone()).into());
+    }
+    match directories {
+        [] => Ok((vec![PathBuf::from(".")], PathBuf::from("."))),
+        [file] if file.is_file() => {
+            let parent = file
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            Ok((vec![file.clone()], parent.to_path_buf()))
+        }
+        [directory] => Ok((vec![directory.clone()], directory.clone())),
+        _ => {
+            let roots = directories
+                .iter()
+                .map(|directory| {
+                    directory
+                        .canonicalize()
+                        .with_context(|| format!("Failed to resolve {}", directory.display()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let mut base = roots[0].clone();
+            for root in &roots[1..] {
+                while !root.starts_with(&base) {
+                    if !base.pop() {
+                        break;
+                    }
+                }
+            }
+            // The same file given twice is its own common ancestor.
+            if base.is_file() {
+                base.pop();
+            }
+            Ok((roots, base))
+        }
+    }
+}
+
+/// Checks whether a file passes the include and exclude lists, or opts in with an
+/// [`INCLUDE_MARKER`].
+///
+/// The first block of the file is read at most once: up front for an extensionless file
+/// with `shebang_detect`, which the lists then see named after its `#!` interpreter, e.g.
+/// `bin/deploy.py`, and otherwise only to look for the marker in a file the lists leave out.
+/// A file left out is logged with the entry that decided it.
+///
+/// # Arguments
+/// * `file` - The file to check.
+/// * `source` - The source the file is read from.
+/// * `filter` - The path filters of the run.
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `bool` - `true` if the file is kept.
+fn passes_rules(
+    file: &SourceFile,
+    source: &dyn FileSource,
+    filter: &FileFilter,
+    config: &Config,
+) -> bool {
+    let mut head = None;
+    let mut path = Cow::Borrowed(file.relative_path.as_path());
+    if config.shebang_detect && path.extension().is_none() {
+        let text = text_head(file, source);
+        if let Some(language) = text.as_deref().and_then(shebang::language) {
+            path = shebang::language_path(&file.relative_path, language);
+        }
+        head = Some(text);
+    }
+    if filter.rules_allow(&path) {
+        return true;
+    }
+    let head = head.unwrap_or_else(|| text_head(file, source));
+    if head.is_some_and(|head| opts_in(file, &head)) {
+        return true;
+    }
+    if let Some(exclusion) = filter.rules_exclusion(&path) {
+        let attribution = format!("Skipping {} ({})", file.path.display(), exclusion);
+        // As with the linguist rules, a dry run shows which entry of a long list decided.
+        if config.dry_run {
+            info!("{}", attribution);
+        } else {
+            debug!("{}", attribution);
+        }
+    }
+    false
+}
+
+/// Reads the first block of a file, as checked for binary content.
+///
+/// # Returns
+/// * `Option<String>` - The block, or `None` if the file cannot be read or looks binary.
+fn text_head(file: &SourceFile, source: &dyn FileSource) -> Option<String> {
+    let head = source.read_head(file, SNIFF_LEN).ok()?;
+    if extensions::is_binary(&head) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&head).into_owned())
+}
+
+/// Checks whether a file the include and exclude lists leave out opts in with an
+/// [`INCLUDE_MARKER`].
+///
+/// # Arguments
+/// * `file` - The file left out by the lists.
+/// * `head` - The first block of the file.
+///
+/// # Returns
+/// * `bool` - `true` if the marker is near the start of the file.
+fn opts_in(file: &SourceFile, head: &str) -> bool {
+    let opted_in = markers::find(head) == Some(Marker::Include);
+    if opted_in {
+        debug!(
+            "Including {}: it has an include marker",
+            file.path.display()
+        );
+    }
+    opted_in
+}
+
+/// Sorts the collected entries by the configured order, breaking ties by path.
+///
+/// # Arguments
+/// * `entries` - The file entries to sort.
+/// * `config` - The configuration options for the bundling process.
+/// * `source` - The source the entries come from, which provides sizes and modification times.
+/// * `churn` - The commit counts for [`SortOrder::Churn`]; without them, files keep path order.
+fn sort_entries(
+    entries: &mut [SourceFile],
+    config: &Config,
+    source: &dyn FileSource,
+    churn: Option<&Churn>,
+) {
+    match config.sort {
+        SortOrder::Path => entries.sort_by(|a, b| a.relative_path.cmp(&b.relative_path)),
+        SortOrder::Size => entries.sort_by_cached_key(|e| (source.len(e), e.relative_path.clone())),
+        SortOrder::Mtime => {
+            entries.sort_by_cached_key(|e| (source.modified(e), e.relative_path.clone()))
+        }
+        SortOrder::Extension => entries.sort_by_cached_key(|e| {
+            (
+                e.relative_path.extension().map(|ext| ext.to_os_string()),
+                e.relative_path.clone(),
+            )
+        }),
+        SortOrder::Churn => entries.sort_by_cached_key(|e| {
+            (
+                Reverse(churn.map_or(0, |churn| churn.commits(e))),
+                e.relative_path.clone(),
+            )
+        }),
+        SortOrder::RustModules => {
+            let ranks = rust_modules::module_ranks(entries, source);
+            entries.sort_by_cached_key(|e| {
+                (
+                    ranks.get(&e.relative_path).copied().unwrap_or(usize::MAX),
+                    e.relative_path.clone(),
+                )
+            })
+        }
+    }
+    if config.reverse {
+        entries.reverse();
+    }
+}
+
+/// Recursively collects the working directories of initialized git submodules.
+///
+/// The repository is found as git finds it, so `directory` may be below its root; only the
+/// submodules below `directory` are collected. Uninitialized submodules are reported with a
+/// warning and skipped.
+///
+/// # Arguments
+/// * `directory` - The directory of a working tree to inspect.
+/// * `roots` - The list the submodule directories are appended to.
+fn collect_submodule_roots(directory: &Path, roots: &mut Vec<PathBuf>) {
+    let repo = match GitRepo::discover(&[directory.to_path_buf()]) {
+        Ok(Some(repo)) => repo,
+        Ok(None) => {
+            warn!("Not a git repository, cannot include submodules.");
+            return;
+        }
+        Err(err) => {
+            warn!("{:#}", err);
+            return;
+        }
+    };
+
+    let submodules = match repo.submodules(directory) {
+        Ok(submodules) => submodules,
+        Err(err) => {
+            warn!("Failed to list submodules: {:#}", err);
+            return;
+        }
+    };
+
+    for (root, initialized) in submodules {
+        if !initialized {
+            warn!("Skipping uninitialized submodule: {}", root.display());
+            continue;
+        }
+        info!("Including submodule: {}", root.display());
+        roots.push(root.clone());
+        collect_submodule_roots(&root, roots);
+    }
+}
+
+/// Returns how a run splits files into sections: `group_by`, or by member when
+/// `workspace_members` selects some.
+pub(crate) fn group_by(config: &Config) -> Option<GroupBy> {
+    config
+        .group_by
+        .or_else(|| (!config.workspace_members.is_empty()).then_some(GroupBy::Member))
+}
+
+/// Returns the directory section a file belongs to with [`GroupBy::Dir`].
+///
+/// # Arguments
+/// * `path` - The displayed path of the file.
+/// * `depth` - How many leading directories make up a section.
+///
+/// # Returns
+/// * `PathBuf` - The first `depth` directories of the path, or `.` for top-level files.
+fn group_directory(path: &Path, depth: usize) -> PathBuf {
+    let directory: PathBuf = path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .take(depth.max(1))
+        .collect();
+    if directory.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        directory
+    }
+}
+
+/// Makes the files of each directory or member section contiguous, keeping their order
+/// within a section; sections are ordered by their first file.
+///
+/// # Arguments
+/// * `entries` - The file entries in output order.
+/// * `section` - Returns the section of a file.
+fn group_entries<K: Eq + std::hash::Hash>(
+    entries: &mut Vec<SourceFile>,
+    section: impl Fn(&SourceFile) -> K,
+) {
+    let mut first_seen: HashMap<K, usize> = HashMap::new();
+    let mut keyed: Vec<(usize, SourceFile)> = entries
+        .drain(..)
+        .map(|entry| {
+            let next = first_seen.len();
+            (*first_seen.entry(section(&entry)).or_insert(next), entry)
+        })
+        .collect();
+    keyed.sort_by_key(|(section, _)| *section);
+    entries.extend(keyed.into_iter().map(|(_, entry)| entry));
+}
+
+/// Moves entries matching the priority patterns to the front, earlier patterns first.
+///
+/// The sort is stable, so entries within a priority bucket (and the remaining entries)
+/// keep the configured sort order.
+///
+/// # Arguments
+/// * `entries` - The sorted file entries.
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if a pattern is invalid.
+fn prioritize_entries(entries: &mut [SourceFile], config: &Config) -> Result<()> {
+    let mut patterns: Vec<&str> = config.priority.iter().map(String::as_str).collect();
+    if config.priority_defaults {
+        patterns.extend(DEFAULT_PRIORITY_PATTERNS);
+    }
+    if patterns.is_empty() {
+        return Ok(());
+    }
+
+    let priorities = build_glob_set(&patterns).context("Invalid --priority pattern")?;
+    entries.sort_by_cached_key(|e| {
+        priorities
+            .matches(&e.relative_path)
+            .into_iter()
+            .min()
+            .unwrap_or(patterns.len())
+    });
+    Ok(())
+}
+
+/// Reorders entries to follow an order file, listed patterns first in file order.
+///
+/// Blank lines and `#` comments are ignored. Patterns that match no file are reported with
+/// their line number, as are listed files rejected by the include and exclude rules. Unlisted files
+/// keep their relative order at the end, or are dropped when `order_strict` is set.
+///
+/// # Arguments
+/// * `order_file` - The path of the order file.
+/// * `entries` - The sorted file entries.
+/// * `config` - The configuration options for the bundling process.
+/// * `filter` - The path filters, whose include and exclude rules are checked.
+/// * `on_skipped` - Called for each dropped entry.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the file cannot be read.
+fn apply_order_file(
+    order_file: &Path,
+    entries: &mut Vec<SourceFile>,
+    config: &Config,
+    filter: &FileFilter,
+    on_skipped: &mut dyn FnMut(&Path, SkipReason),
+) -> Result<()> {
+    let source = fs::read_to_string(order_file)
+        .with_context(|| format!("Failed to read order file: {}", order_file.display()))?;
+    let lines: Vec<(usize, &str)> = source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| (number, line.strip_prefix("./").unwrap_or(line)))
+        .collect();
+    let patterns: Vec<&str> = lines.iter().map(|(_, pattern)| *pattern).collect();
+    let order = build_glob_set(&patterns)
+        .with_context(|| format!("Invalid order file: {}", order_file.display()))?;
+
+    let mut matched = vec![false; patterns.len()];
+    let mut ranked: Vec<(usize, SourceFile)> = Vec::with_capacity(entries.len());
+    for file in entries.drain(..) {
+        let matches = order.matches(&file.relative_path);
+        for &index in &matches {
+            matched[index] = true;
+        }
+        match matches.into_iter().min() {
+            Some(index) => {
+                if !file.forced && !filter.rules_allow(&file.relative_path) {
+                    warn!(
+                        "{}:{}: skipping {}, which is excluded by the include/exclude filters",
+                        order_file.display(),
+                        lines[index].0,
+                        file.relative_path.display()
+                    );
+                }
+                ranked.push((index, file));
+            }
+            None if config.order_strict && !file.forced => {
+                on_skipped(&file.path, SkipReason::Filtered)
+            }
+            None => ranked.push((patterns.len(), file)),
+        }
+    }
+
+    for ((number, pattern), matched) in lines.iter().zip(matched) {
+        if !matched {
+            warn!(
+                "{}:{}: `{}` does not match any file",
+                order_file.display(),
+                number,
+                pattern
+            );
+        }
+    }
+
+    ranked.sort_by_key(|(index, _)| *index);
+    entries.extend(ranked.into_iter().map(|(_, file)| file));
+    Ok(())
+}
+
+/// Compiles glob patterns matched against relative paths; `*` does not cross `/`.
+///
+/// # Arguments
+/// * `patterns` - The glob patterns to compile.
+///
+/// # Returns
+/// * `Result<GlobSet>` - The compiled patterns, indexed in the order given.
+fn build_glob_set(patterns: &[&str]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob pattern `{}`", pattern))?;
+        builder.add(glob);
+    }
+    Ok(builder.build()?)
+}
+
+/// A selected file, read and transformed.
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    /// The path relative to the common base of the input directories.
+    pub relative_path: PathBuf,
+    /// The absolute path of the file on disk.
+    pub absolute_path: PathBuf,
+    /// The file extension, or an empty string if there is none.
+    pub extension: String,
+    /// The content of the file after transformations such as comment stripping; empty for a
+    /// large file streamed into the output (see [`Hooks::needs_content`]).
+    pub content: String,
+    /// The size of the file in bytes, before transformations.
+    pub size: u64,
+    /// The last-modified time of the file, if the source knows it.
+    pub modified: Option<SystemTime>,
+    /// The SHA-256 of the file's content before transformations (or of content replaced by
+    /// a hook), as hex; only computed when `file_meta` includes the hash, `dedup` is set, or a
+    /// manifest is written.
+    pub sha256: Option<String>,
+    /// The hash of the content as written, after transformations and line selection, with
+    /// its algorithm, e.g. `blake3:<hex>`; only computed with `hash`, and `None` for a
+    /// placeholder.
+    pub content_hash: Option<String>,
+    /// The path shown in the output, after `rename_paths` is applied, with `/` separators
+    /// unless `native_paths` is set.
+    display_path: PathBuf,
+    /// The selected lines of the content and any omission markers.
+    segments: Vec<Segment>,
+    /// The estimated token count of the selected lines.
+    tokens: usize,
+    /// Why the content was left out, for a placeholder listed with `binary_placeholders`;
+    /// `None` for a file written in full.
+    placeholder: Option<SkipReason>,
+    /// Whether the content is a binary file encoded as base64, with `embed_binary`.
+    base64: bool,
+    /// The number of lines that match `grep`, when only the regions around them are
+    /// written (`grep_context`).
+    grep_matches: Option<usize>,
+    /// The language of the `#!` line of an extensionless script, with `shebang_detect`.
+    shebang: Option<&'static Language>,
+    /// The cap the selected lines were cut at, with `max_lines_per_file` or
+    /// `max_tokens_per_file`.
+    truncated_at: Option<FileCap>,
+    /// The number of lines of license headers removed, with `strip_license_headers`.
+    license_lines: usize,
+    /// The file on disk for a large file that is streamed into the output rather than
+    /// held in `content` and `segments`.
+    streamed: Option<StreamedFile>,
+}
+
+impl FileEntry {
+    /// Returns the path shown in the output, after `rename_paths` is applied.
+    pub fn display_path(&self) -> &Path {
+        &self.display_path
+    }
+
+    /// Returns the selected lines of the content and any omission markers, in output order.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Returns whether the content is a binary file encoded as base64 (`embed_binary`)
+    /// rather than text.
+    pub fn is_base64(&self) -> bool {
+        self.base64
+    }
+
+    /// Returns the path that names the language of the file: its own, or with the extension
+    /// of its `#!` interpreter for an extensionless script with `shebang_detect`.
+    pub(crate) fn language_path(&self) -> Cow<'_, Path> {
+        match self.shebang {
+            Some(language) => shebang::language_path(&self.relative_path, language),
+            None => Cow::Borrowed(&self.relative_path),
+        }
+    }
+
+    /// Replaces the content, keeping the path; the new content is written in full.
+    fn with_content(self, content: String, config: &Config) -> FileEntry {
+        let segments = vec![Segment::Lines {
+            first_line: 1,
+            text: content.clone(),
+        }];
+        FileEntry {
+            tokens: config.tokenizer.count(&content),
+            sha256: self
+                .sha256
+                .as_ref()
+                .map(|_| sha256::sha256_hex(content.as_bytes())),
+            content_hash: config
+                .hash
+                .map(|algorithm| algorithm.content_hash(&segments)),
+            segments,
+            content,
+            base64: false,
+            grep_matches: None,
+            truncated_at: None,
+            streamed: None,
+            ..self
+        }
+    }
+
+    /// Creates the placeholder of a file left out as binary or too large.
+    ///
+    /// # Arguments
+    /// * `file` - The file left out.
+    /// * `size` - The size of the file in bytes, if the source knows it.
+    /// * `reason` - Why the file was left out.
+    ///
+    /// # Returns
+    /// * `FileEntry` - An entry without content that is written as a one-line stub.
+    fn placeholder(file: &SourceFile, size: Option<u64>, reason: SkipReason) -> FileEntry {
+        let path = file.path.as_path();
+        FileEntry {
+            relative_path: file.relative_path.clone(),
+            absolute_path: std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+            extension: path
+                .extension()
+                .and_then(|s| s.to_str())
+                .unwrap_or("")
+                .to_string(),
+            content: String::new(),
+            size: size.unwrap_or(0),
+            modified: None,
+            sha256: None,
+            content_hash: None,
+            display_path: file.display_path.clone(),
+            segments: Vec::new(),
+            tokens: 0,
+            placeholder: Some(reason),
+            base64: false,
+            grep_matches: None,
+            shebang: None,
+            truncated_at: None,
+            license_lines: 0,
+            streamed: None,
+        }
+    }
+
+    /// Returns the number of lines of the content, whether held or streamed.
+    pub(crate) fn line_count(&self) -> usize {
+        match &self.streamed {
+            Some(streamed) => streamed.lines,
+            None => self.content.lines().count(),
+        }
+    }
+}
+
+/// Classifies a file read error as binary content or another read failure.
+///
+/// # Arguments
+/// * `err` - The error returned while preparing a file.
+///
+/// # Returns
+/// * `SkipReason` - `Binary` for invalid UTF-8, `Generated` for generated files, `Empty`,
+///   `TooSmall`, or `TooLarge` for files skipped by size, `FilterFailed` for files whose
+///   filter command failed, `ReadError` otherwise.
+fn skip_reason(err: &anyhow::Error) -> SkipReason {
+    if err.is::<GeneratedFile>() {
+        return SkipReason::Generated;
+    }
+    if err.is::<OptedOutFile>() {
+        return SkipReason::OptOut;
+    }
+    if err.is::<MalformedNotebook>() {
+        return SkipReason::Malformed;
+    }
+    if err.is::<NoGrepMatch>() {
+        return SkipReason::NoMatch;
+    }
+    if err.is::<FilterFailed>() {
+        return SkipReason::FilterFailed;
+    }
+    if let Some(small) = err.downcast_ref::<SizedOutFile>() {
+        return small.reason;
+    }
+    match err.downcast_ref::<io::Error>() {
+        Some(err) if err.kind() == io::ErrorKind::InvalidData => SkipReason::Binary,
+        _ => SkipReason::ReadError,
+    }
+}
+
+/// A file skipped by `skip_empty`, `min_file_size`, or `max_file_size`, returned as an
+/// error by [`prepare_file`] so it is counted under its own reason.
+#[derive(Debug)]
+struct SizedOutFile {
+    path: PathBuf,
+    reason: SkipReason,
+}
+
+impl fmt::Display for SizedOutFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            SkipReason::Empty => write!(f, "empty file: {}", self.path.display()),
+            SkipReason::TooLarge => {
+                write!(f, "file above the maximum size: {}", self.path.display())
+            }
+            _ => write!(f, "file below the minimum size: {}", self.path.display()),
+        }
+    }
+}
+
+impl std::error::Error for SizedOutFile {}
+
+/// Checks whether the options use the SHA-256 of each file's content; a tar archive's
+/// `MANIFEST.json` lists it like the manifest sidecar.
+pub(crate) fn needs_sha256(config: &Config) -> bool {
+    config.file_meta.hash
+        || config.dedup
+        || config.manifest.is_some()
+        || matches!(config.format, Format::Tar)
+}
+
+/// Checks whether the options rewrite file content, in which case control characters other
+/// than tabs and line endings are stripped as well.
+fn transforms_text(config: &Config) -> bool {
+    config.strip_ansi
+        || config.normalize_eol != LineEnding::Keep
+        || config.strip_frontmatter
+        || config.strip_license_headers
+        || config.strip_comments
+        || config.signatures_only
+        || config.expand_tabs.is_some()
+        || config.trim_trailing_whitespace
+        || config.compact
+        || config.max_line_length.is_some()
+}
+
+/// Checks whether large text files can be streamed into the output line by line rather
+/// than read whole: the options write each file unchanged and in full, in a format that
+/// writes it as is, and need nothing else from its content.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+/// * `color` - Whether the output is colored, which highlights the whole content.
+///
+/// # Returns
+/// * `bool` - Whether files above [`STREAM_LEN`] may be streamed.
+fn streams(config: &Config, color: bool) -> bool {
+    let format = match config.format {
+        Format::Markdown | Format::Text => true,
+        Format::Console => !color,
+        Format::ClaudeXml | Format::ChatJson | Format::Asciidoc | Format::Tar => false,
+    };
+    format
+        && !transforms_text(config)
+        && !needs_sha256(config)
+        && config.hash.is_none()
+        && config.grep.is_empty()
+        && config.filter_cmd.is_none()
+        && config.filter_cmd_ext.is_empty()
+        && config.cache.is_none()
+        && config.only.is_empty()
+        && config.head.is_none()
+        && config.tail.is_none()
+        && config.max_lines_per_file.is_none()
+        && config.max_tokens_per_file.is_none()
+        && config.also_output.is_empty()
+        && !config.lang_stats
+        && !config.dry_run
+        && !config.headers_only
+        && config.tokenizer.count_len(0).is_some()
+}
+
+/// The share of a file's lines that, once cut by `max_line_length`, suggests the file is
+/// generated.
+const TRUNCATED_LINES_GENERATED_SHARE: f64 = 0.5;
+
+/// Estimates the tokens of a bundle for its front matter: the files and the framing text.
+///
+/// # Arguments
+/// * `entries` - The files selected for the bundle.
+/// * `config` - The configuration options for the bundling process.
+/// * `prepared` - The files read ahead of writing them.
+///
+/// # Returns
+/// * `usize` - The estimated tokens.
+fn frontmatter_tokens(entries: &[SourceFile], config: &Config, prepared: &Prepared) -> usize {
+    let files: usize = entries
+        .iter()
+        .filter_map(|file| match prepared.get(&file.path) {
+            Some(Ok(Some(entry))) => Some(entry.tokens),
+            _ => None,
+        })
+        .sum();
+    let framing: usize = [&config.preamble, &config.postamble, &config.question]
+        .into_iter()
+        .flatten()
+        .map(|text| config.tokenizer.count(text))
+        .sum();
+    files + framing
+}
+
+/// What [`prepare_file`] reads files with, the same for every file of a run.
+#[derive(Clone, Copy)]
+struct Readers<'a> {
+    /// The cache of an earlier run, whose content is reused if the file is unchanged.
+    cache: Option<&'a Cache>,
+    /// The `grep` patterns of the run, if any.
+    grep: Option<&'a Grep>,
+    /// The filter commands of the run, if any.
+    filters: Option<&'a Filters>,
+    /// The license marker of the run, used with `strip_license_headers`.
+    license: &'a LicenseHeaders,
+    /// Whether a text file above [`STREAM_LEN`] may be streamed into the output rather than
+    /// read whole, see [`streams`].
+    stream: bool,
+}
+
+/// Reads a single file and applies the filters and transformations to it.
+///
+/// This does not touch the output, so files can be prepared on worker threads.
+///
+/// # Arguments
+/// * `file` - The file to prepare.
+/// * `config` - The configuration options for the bundling process.
+/// * `source` - The source the file is read from.
+/// * `readers` - The cache, `grep` patterns, filter commands, and license marker of the run.
+///
+/// # Returns
+/// * `Result<Option<FileEntry>>` - The prepared file, `None` if it was filtered out, or an
+///   error if it cannot be read as UTF-8 text, is skipped as generated, or fails its filter
+///   command.
+fn prepare_file(
+    file: &SourceFile,
+    config: &Config,
+    source: &dyn FileSource,
+    readers: Readers,
+) -> Result<Option<FileEntry>> {
+    let Readers {
+        cache,
+        grep,
+        filters,
+        license,
+        stream,
+    } = readers;
+    let path = file.path.as_path();
+    // Files in `include_paths` pass every filter here; only reading them can still fail.
+    let filtered = !file.forced;
+    // A nested configuration brings its own transformations, which the cache does not track.
+    let config = file.config(config);
+    let cache = cache.filter(|_| file.scope.is_none());
+
+    let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+    let relative_path = file.relative_path.as_path();
+    let line_ranges: Vec<(usize, usize)> = config
+        .only
+        .iter()
+        .filter(|range| range.path == relative_path)
+        .map(|range| (range.start, range.end))
+        .collect();
+    if filtered && !config.only.is_empty() && line_ranges.is_empty() {
+        return Ok(None);
+    }
+
+    let generated = |reason| GeneratedFile {
+        path: path.to_path_buf(),
+        reason,
+    };
+    if filtered
+        && config.skip_generated
+        && let Some(reason) = generated::check_name(path)
+    {
+        return Err(generated(reason).into());
+    }
+    let sized_out = |reason| SizedOutFile {
+        path: path.to_path_buf(),
+        reason,
+    };
+    // Sizes on disk are checked before reading, so most small files are never read.
+    let len = source.len(file).filter(|_| filtered);
+    if config.skip_empty && len == Some(0) {
+        return Err(sized_out(SkipReason::Empty).into());
+    }
+    if let (Some(min), Some(len)) = (config.min_file_size, len)
+        && len < min.0
+    {
+        return Err(sized_out(SkipReason::TooSmall).into());
+    }
+    if let (Some(max), Some(len)) = (config.max_file_size, len)
+        && len > max.0
+    {
+        return Err(sized_out(SkipReason::TooLarge).into());
+    }
+
+    // Taken before reading, so a file changed while it is read is stale in the cache.
+    let modified = source.modified(file);
+    let mut base64 = false;
+    // Streamed files are read from disk as they are written, so they cannot come from a
+    // source in memory or take the transformations of a nested configuration.
+    let streamed = stream
+        && source.on_disk()
+        && file.scope.is_none()
+        && line_ranges.is_empty()
+        && !extension.eq_ignore_ascii_case(notebook::EXTENSION)
+        && source.len(file).is_some_and(|len| len > STREAM_LEN);
+    let transformed = match cache.and_then(|cache| cache.get(file, source)) {
+        Some(cached) => TransformedFile {
+            content: cached.content.clone(),
+            size: cached.size,
+            sha256: cached.sha256.clone(),
+            license_lines: cached.license_lines,
+        },
+        None if streamed => match stream::scan(path)
+            .with_context(|| format!("non-UTF-8 or unreadable file: {}", path.display()))
+        {
+            Ok(scan) => return streamed_entry(file, config, scan, modified),
+            Err(err) => {
+                base64 = true;
+                embed_binary(file, config, source, err)?
+            }
+        },
+        None => match transform_file(file, extension, config, source, filters, license) {
+            Ok(Some(transformed)) => transformed,
+            Ok(None) if filtered && config.skip_empty => {
+                return Err(sized_out(SkipReason::Empty).into());
+            }
+            Ok(None) => return Ok(None),
+            Err(err) => {
+                base64 = true;
+                embed_binary(file, config, source, err)?
+            }
+        },
+    };
+    let TransformedFile {
+        content,
+        size,
+        sha256,
+        license_lines,
+    } = transformed;
+    if filtered && config.min_file_size.is_some_and(|min| size < min.0) {
+        return Err(sized_out(SkipReason::TooSmall).into());
+    }
+    if filtered && config.max_file_size.is_some_and(|max| size > max.0) {
+        return Err(sized_out(SkipReason::TooLarge).into());
+    }
+    if filtered && config.skip_empty && content.trim().is_empty() {
+        return Err(sized_out(SkipReason::Empty).into());
+    }
+    // Embedded binary files are never searched.
+    if filtered && grep.is_some_and(|grep| base64 || !grep.is_match(&content)) {
+        return Err(NoGrepMatch {
+            path: path.to_path_buf(),
+        }
+        .into());
+    }
+
+    let regions = grep
+        .zip(config.grep_context)
+        .filter(|_| !base64 && line_ranges.is_empty())
+        .and_then(|(grep, context)| grep.regions(&content, context));
+    let grep_matches = regions.as_ref().map(|(_, matches)| *matches);
+    let segments = if let Some((segments, _)) = regions {
+        segments
+    } else if base64 {
+        // Cutting lines out would leave the encoding undecodable.
+        transform::excerpt(&content, None, None)
+    } else if line_ranges.is_empty() {
+        transform::excerpt(&content, config.head, config.tail)
+    } else {
+        let line_ranges = clamp_line_ranges(relative_path, &content, line_ranges);
+        if line_ranges.is_empty() {
+            return Ok(None);
+        }
+        transform::select_ranges(&content, &line_ranges)
+    };
+    let caps = [
+        config.max_lines_per_file.map(FileCap::Lines),
+        config
+            .max_tokens_per_file
+            .map(|max| FileCap::Tokens(max, config.tokenizer)),
+    ];
+    let mut segments = segments;
+    let mut truncated_at = None;
+    for cap in caps.into_iter().flatten().filter(|_| !base64) {
+        let truncated;
+        (segments, truncated) = transform::truncate(segments, cap, config.truncate);
+        if truncated {
+            truncated_at = Some(cap);
+        }
+    }
+
+    let tokens = segments
+        .iter()
+        .map(|segment| config.tokenizer.count(segment.text()))
+        .sum();
+    let content_hash = config
+        .hash
+        .map(|algorithm| algorithm.content_hash(&segments));
+    let shebang = (config.shebang_detect && extension.is_empty() && !base64)
+        .then(|| shebang::language(&content))
+        .flatten();
+    Ok(Some(FileEntry {
+        relative_path: relative_path.to_path_buf(),
+        absolute_path: std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+        extension: extension.to_string(),
+        content,
+        size,
+        modified,
+        sha256,
+        content_hash,
+        display_path: file.display_path.clone(),
+        segments,
+        tokens,
+        placeholder: None,
+        base64,
+        grep_matches,
+        shebang,
+        truncated_at,
+        license_lines,
+        streamed: None,
+    }))
+}
+
+/// Prepares a large text file to be streamed into the output, making the checks that
+/// [`transform_file`] and [`prepare_file`] make on content in memory.
+///
+/// # Arguments
+/// * `file` - The file to prepare.
+/// * `config` - The configuration options for the bundling process.
+/// * `scan` - What a first pass over the file found.
+/// * `modified` - The last-modified time of the file, if known.
+///
+/// # Returns
+/// * `Result<Option<FileEntry>>` - The file, with neither content nor segments, or an error
+///   if it opted out, is skipped as generated, or is blank with `skip_empty`.
+fn streamed_entry(
+    file: &SourceFile,
+    config: &Config,
+    scan: Scan,
+    modified: Option<SystemTime>,
+) -> Result<Option<FileEntry>> {
+    let path = file.path.as_path();
+    if !file.forced && markers::find(&scan.head) == Some(Marker::Ignore) {
+        return Err(OptedOutFile {
+            path: path.to_path_buf(),
+        }
+        .into());
+    }
+    if !file.forced
+        && config.skip_generated
+        && let Some(reason) = scan.generated
+    {
+        return Err(GeneratedFile {
+            path: path.to_path_buf(),
+            reason,
+        }
+        .into());
+    }
+    if !file.forced && config.skip_empty && scan.blank {
+        return Err(SizedOutFile {
+            path: path.to_path_buf(),
+            reason: SkipReason::Empty,
+        }
+        .into());
+    }
+    let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+    let shebang = (config.shebang_detect && extension.is_empty())
+        .then(|| shebang::language(&scan.head))
+        .flatten();
+    Ok(Some(FileEntry {
+        relative_path: file.relative_path.clone(),
+        absolute_path: std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+        extension: extension.to_string(),
+        content: String::new(),
+        size: scan.file.len as u64,
+        modified,
+        sha256: None,
+        content_hash: None,
+        display_path: file.display_path.clone(),
+        segments: Vec::new(),
+        tokens: config.tokenizer.count_len(scan.file.len).unwrap_or(0),
+        placeholder: None,
+        base64: false,
+        grep_matches: None,
+        shebang,
+        truncated_at: None,
+        license_lines: 0,
+        streamed: Some(scan.file),
+    }))
+}
+
+/// A file read and transformed by [`transform_file`], before lines are selected.
+struct TransformedFile {
+    content: String,
+    /// The size of the file in bytes before transformations.
+    size: u64,
+    /// The SHA-256 of the content before transformations, if the run needs it.
+    sha256: Option<String>,
+    /// The number of lines of license headers removed.
+    license_lines: usize,
+}
+
+/// Encodes a binary file as base64 if it is no larger than `embed_binary`.
+///
+/// # Arguments
+/// * `file` - The file that failed to read as text.
+/// * `config` - The configuration options for the bundling process.
+/// * `source` - The source the file is read from.
+/// * `err` - Why the file failed to read as text.
+///
+/// # Returns
+/// * `Result<TransformedFile>` - The encoded content, the size of the file, and the
+///   SHA-256 of its bytes if needed; or `err` if the file is not binary, is too large to
+///   embed, or `embed_binary` is not set.
+fn embed_binary(
+    file: &SourceFile,
+    config: &Config,
+    source: &dyn FileSource,
+    err: anyhow::Error,
+) -> Result<TransformedFile> {
+    let Some(max) = config
+        .embed_binary
+        .filter(|_| skip_reason(&err) == SkipReason::Binary)
+    else {
+        return Err(err);
+    };
+    if source.len(file).is_some_and(|len| len > max.0) {
+        return Err(err);
+    }
+    let bytes = source
+        .read_bytes(file)
+        .with_context(|| format!("Failed to read file: {}", file.path.display()))?;
+    if bytes.len() as u64 > max.0 {
+        return Err(err);
+    }
+    let sha256 = needs_sha256(config).then(|| sha256::sha256_hex(&bytes));
+    Ok(TransformedFile {
+        content: base64::encode_wrapped(&bytes),
+        size: bytes.len() as u64,
+        sha256,
+        license_lines: 0,
+    })
+}
+
+/// Reads a file and applies the content transformations, the part of preparing a file that
+/// the cache saves.
+///
+/// # Arguments
+/// * `file` - The file to read.
+/// * `extension` - The file extension, or an empty string if there is none.
+/// * `config` - The configuration options for the bundling process.
+/// * `source` - The source the file is read from.
+/// * `filters` - The filter commands of the run, if any.
+/// * `license` - The license marker of the run, used with `strip_license_headers`.
+///
+/// # Returns
+/// * `Result<Option<TransformedFile>>` - The transformed content, the size of the file, and
+///   the SHA-256 of its content if needed; `None` if the file is blank after compaction, or
+///   an error if it cannot be read as UTF-8 text, is skipped as generated, or fails its
+///   filter command.
+fn transform_file(
+    file: &SourceFile,
+    extension: &str,
+    config: &Config,
+    source: &dyn FileSource,
+    filters: Option<&Filters>,
+    license: &LicenseHeaders,
+) -> Result<Option<TransformedFile>> {
+    let path = file.path.as_path();
+    let mut content = source
+        .read(file)
+        .with_context(|| format!("non-UTF-8 or unreadable file: {}", path.display()))?;
+    let size = content.len() as u64;
+    let sha256 = needs_sha256(config).then(|| sha256::sha256_hex(content.as_bytes()));
+    if extension.eq_ignore_ascii_case(notebook::EXTENSION) {
+        content = notebook::render(&content, config.notebook_outputs).map_err(|reason| {
+            MalformedNotebook {
+                path: path.to_path_buf(),
+                reason,
+            }
+        })?;
+    }
+    if !file.forced && markers::find(&content) == Some(Marker::Ignore) {
+        return Err(OptedOutFile {
+            path: path.to_path_buf(),
+        }
+        .into());
+    }
+    if !file.forced
+        && config.skip_generated
+        && let Some(reason) = generated::check_content(&content)
+    {
+        return Err(GeneratedFile {
+            path: path.to_path_buf(),
+            reason,
+        }
+        .into());
+    }
+    if let Some(filters) = filters {
+        content = filters.apply(file, content)?;
+    }
+
+    if config.strip_ansi {
+        content = transform::strip_ansi(&content);
+    }
+    if transforms_text(config) {
+        content = transform::strip_control_chars(&content);
+    }
+    content = transform::normalize_line_endings(content, config.normalize_eol);
+    if config.strip_frontmatter
+        && transform::FRONTMATTER_EXTENSIONS
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(extension))
+    {
+        content = transform::strip_frontmatter(content);
+    }
+    let mut license_lines = 0;
+    if config.strip_license_headers {
+        (content, license_lines) = license.strip(&content, extension);
+    }
+    if config.strip_comments {
+        content = comments::strip_comments(&content, extension, config.keep_doc_comments);
+    }
+    if config.signatures_only {
+        content = signatures::signatures_only(&content, extension);
+    }
+    if let Some(width) = config.expand_tabs {
+        content = transform::expand_tabs(content, width);
+    }
+    if config.trim_trailing_whitespace {
+        content = transform::trim_trailing_whitespace(&content);
+    }
+    if config.compact {
+        content = transform::compact(&content);
+        if content.trim().is_empty() {
+            debug!("Skipping blank file: {}", path.display());
+            return Ok(None);
+        }
+    }
+
+    if let Some(max) = config.max_line_length {
+        let truncated;
+        (content, truncated) = transform::truncate_long_lines(&content, max);
+        let lines = content.lines().count();
+        if truncated > 0 && truncated as f64 > lines as f64 * TRUNCATED_LINES_GENERATED_SHARE {
+            warn!(
+                "{}: {} of {} lines are longer than {} characters; the file is probably \
+                 generated and could be excluded",
+                path.display(),
+                truncated,
+                lines,
+                max
+            );
+        }
+    }
+
+    Ok(Some(TransformedFile {
+        content,
+        size,
+        sha256,
+        license_lines,
+    }))
+}
+
+/// Writes a prepared file to the writer and records it in the summary.
+///
+/// # Arguments
+/// * `entry` - The prepared file.
+/// * `writer` - The writer to output the file content.
+/// * `config` - The configuration options for the bundling process.
+/// * `summary` - The run statistics to update.
+/// * `formatter` - The formatter that writes the file.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if writing fails.
+fn write_file_entry(
+    entry: &FileEntry,
+    writer: &mut dyn Write,
+    config: &Config,
+    summary: &mut RunSummary,
+    formatter: &mut dyn Formatter,
+) -> Result<()> {
+    if config.dry_run {
+        write_dry_run_line(writer, &entry.display_path, &entry.content, config)
+    } else if config.headers_only {
+        formatter.header(writer, entry, config)
+    } else {
+        formatter.file(writer, entry, config)
+    }
+    .with_context(|| {
+        format!(
+            "Failed to write file content for {}",
+            entry.relative_path.display()
+        )
+    })?;
+
+    if config.headers_only {
+        summary.record_header(entry, &formatter::header_text(entry, config));
+    } else {
+        summary.record_file(entry);
+    }
+    Ok(())
+}
+
+/// Writes the placeholder of a file left out as binary or too large and records it.
+///
+/// # Arguments
+/// * `entry` - The placeholder, from [`FileEntry::placeholder`].
+/// * `writer` - The writer to output the stub.
+/// * `config` - The configuration options for the bundling process.
+/// * `summary` - The run statistics to update.
+/// * `formatter` - The formatter that writes the stub.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if writing fails.
+fn write_placeholder(
+    entry: &FileEntry,
+    writer: &mut dyn Write,
+    config: &Config,
+    summary: &mut RunSummary,
+    formatter: &mut dyn Formatter,
+) -> Result<()> {
+    let reason = entry.placeholder.unwrap_or(SkipReason::Binary);
+    formatter
+        .placeholder(writer, entry, reason, config)
+        .with_context(|| {
+            format!(
+                "Failed to write placeholder for {}",
+                entry.relative_path.display()
+            )
+        })?;
+    summary.record_placeholder(entry, reason);
+    Ok(())
+}
+
+/// Writes the stub of a file whose content duplicates an earlier file and records the savings.
+///
+/// # Arguments
+/// * `entry` - The duplicate file.
+/// * `original` - The displayed path of the first file with the same content.
+/// * `writer` - The writer to output the stub.
+/// * `config` - The configuration options for the bundling process.
+/// * `summary` - The run statistics to update.
+/// * `formatter` - The formatter that writes the stub.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if writing fails.
+fn write_duplicate(
+    entry: &FileEntry,
+    original: &Path,
+    writer: &mut dyn Write,
+    config: &Config,
+    summary: &mut RunSummary,
+    formatter: &mut dyn Formatter,
+) -> Result<()> {
+    if config.dry_run {
+        writeln!(
+            writer,
+            "{} (identical to {})",
+            entry.display_path.display(),
+            original.display()
+        )
+        .map_err(anyhow::Error::from)
+    } else {
+        formatter.duplicate(writer, entry, original, config)
+    }
+    .with_context(|| {
+        format!(
+            "Failed to write duplicate stub for {}",
+            entry.relative_path.display()
+        )
+    })?;
+
+    summary.record_content_hash(entry);
+    summary.duplicates_collapsed += 1;
+    summary.duplicate_bytes_saved += entry.segments.iter().map(|s| s.text().len()).sum::<usize>();
+    summary.duplicate_tokens_saved += entry.tokens;
+    Ok(())
+}
+
+/// Clamps the requested line ranges of a file to its length and merges them.
+///
+/// Ranges that end past the last line are shortened, and ranges that start past it are
+/// dropped; both cases are reported as warnings.
+///
+/// # Arguments
+/// * `path` - The relative path of the file, used in warnings.
+/// * `content` - The content of the file.
+/// * `ranges` - The requested `(start, end)` pairs.
+///
+/// # Returns
+/// * `Vec<(usize, usize)>` - The sorted, merged ranges that lie within the file.
+fn clamp_line_ranges(
+    path: &Path,
+    content: &str,
+    ranges: Vec<(usize, usize)>,
+) -> Vec<(usize, usize)> {
+    let total = content.lines().count();
+    let mut clamped = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        if start > total {
+            warn!(
+                "Ignoring lines {}-{} of {}: the file has only {} lines",
+                start,
+                end,
+                path.display(),
+                total
+            );
+        } else if end > total {
+            warn!(
+                "Clamping lines {}-{} of {} to {}-{}",
+                start,
+                end,
+                path.display(),
+                start,
+                total
+            );
+            clamped.push((start, total));
+        } else {
+            clamped.push((start, end));
+        }
+    }
+    transform::merge_ranges(clamped)
+}
+
+/// Writes a dry-run listing line for a file that would be included.
+///
+/// # Arguments
+/// * `writer` - The writer to output the listing.
+/// * `path` - The relative path of the file.
+/// * `content` - The content of the file, used only for the size and token estimate.
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+fn write_dry_run_line(
+    writer: &mut dyn Write,
+    path: &Path,
+    content: &str,
+    config: &Config,
+) -> Result<()> {
+    if config.dry_run_sizes {
+        writeln!(
+            writer,
+            "{}\t{} bytes\t~{} tokens",
+            path.display(),
+            content.len(),
+            config.tokenizer.count(content)
+        )?;
+    } else {
+        writeln!(writer, "{}", path.display())?;
+    }
+    Ok(())
+}
+
+/// Writes a dry-run listing line marking a file skipped as empty or too small.
+///
+/// # Arguments
+/// * `writer` - The writer to output the listing.
+/// * `path` - The relative path of the file.
+/// * `reason` - Why the file is skipped.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the operation fails.
+fn write_dry_run_skip_line(writer: &mut dyn Write, path: &Path, reason: SkipReason) -> Result<()> {
+    let mark = match reason {
+        SkipReason::Empty => "empty",
+        _ => "too small",
+    };
+    writeln!(writer, "{}\t(skipped: {})", path.display(), mark)?;
+    Ok(())
+}
+
+/// Checks if a file or directory name is hidden and not kept by `keep_hidden`.
+pub(crate) fn is_hidden_name(name: &str, keep_hidden: &GlobSet) -> bool {
+    name.starts_with('.') && !keep_hidden.is_match(name)
+}
+
+/// Compiles the `exclude_dirs` patterns, matched against paths relative to the input directory.
+///
+/// As in `.gitignore`, a pattern without a `/` matches a directory of that name at any depth,
+/// a leading `/` anchors the pattern at the input directory, and a trailing `/` is optional.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<GlobSet>` - The compiled patterns, or an error if a pattern is invalid.
+pub(crate) fn exclude_dir_set(config: &Config) -> Result<GlobSet> {
+    let patterns: Vec<String> = config
+        .exclude_dirs
+        .iter()
+        .map(|pattern| {
+            let pattern = pattern.trim_end_matches('/');
+            match pattern.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if pattern.contains('/') => pattern.to_string(),
+                None => format!("**/{}", pattern),
+            }
+        })
+        .collect();
+    let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+    build_glob_set(&patterns).context("Invalid --exclude-dir pattern")
+}
+
+/// Compiles the `keep_hidden` patterns, with [`DEFAULT_KEEP_HIDDEN`] when enabled.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<GlobSet>` - The patterns, matched against file and directory names, or an error
+///   if a pattern is invalid.
+pub(crate) fn keep_hidden_set(config: &Config) -> Result<GlobSet> {
+    let mut patterns: Vec<&str> = config.keep_hidden.iter().map(String::as_str).collect();
+    if config.keep_hidden_defaults {
+        patterns.extend(DEFAULT_KEEP_HIDDEN);
+    }
+    build_glob_set(&patterns).context("Invalid --keep-hidden pattern")
+}
+
+/// Returns the names of the respected gitignore-syntax files, from lowest to highest precedence.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Vec<&'static str>` - `.gitignore` if `respect_gitignore` is set, then
+///   [`TOOL_IGNORE_FILE_NAMES`] if `respect_ignore_files` is set.
+pub(crate) fn ignore_file_names(config: &Config) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    if config.respect_gitignore {
+        names.push(".gitignore");
+    }
+    if config.respect_ignore_files {
+        names.extend(TOOL_IGNORE_FILE_NAMES);
+    }
+    names
+}
+
+/// Loads the bundling-specific ignore rules of a directory: its [`IGNORE_FILE_NAMES`] files,
+/// then `ignore_file`, with later rules taking precedence as in a single `.gitignore`.
+///
+/// # Arguments
+/// * `root` - The directory the patterns are relative to.
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<Gitignore>` - The rules, or an error if `ignore_file` is missing or a file has an
+///   invalid pattern.
+pub(crate) fn load_ignore_files(root: &Path, config: &Config) -> Result<Gitignore> {
+    let mut files: Vec<PathBuf> = IGNORE_FILE_NAMES
+        .iter()
+        .map(|name| root.join(name))
+        .filter(|path| path.is_file())
+        .collect();
+    if let Some(path) = &config.ignore_file {
+        if !path.is_file() {
+            bail!("Ignore file not found: {}", path.display());
+        }
+        files.push(path.clone());
+    }
+
+    let mut builder = GitignoreBuilder::new(root);
+    for path in &files {
+        if let Some(err) = builder.add(path) {
+            return Err(err).with_context(|| format!("Invalid ignore file {}", path.display()));
+        }
     }
-    gitignore
-        .matched(entry.path(), entry.file_type().is_dir())
-        .is_ignore()
+    Ok(builder.build()?)
 }
@@ -0,0 +1,285 @@
+//! Removal of license headers (`strip_license_headers`): the comment block before any code
+//! that carries a project's license, repeated in every file.
+
+use crate::Config;
+use crate::comments::Syntax;
+use anyhow::{Context, Result};
+use regex_automata::meta::Regex;
+
+/// The markers of a license header, unless `license_pattern` replaces them.
+const DEFAULT_PATTERN: &str = r"(?i)licensed under|SPDX-License-Identifier|copyright \(c\)";
+
+/// The compiled license marker of a run.
+#[derive(Debug)]
+pub(crate) struct LicenseHeaders {
+    regex: Regex,
+}
+
+impl LicenseHeaders {
+    /// Compiles the `license_pattern` of a run, or the default markers: `Licensed under`,
+    /// `SPDX-License-Identifier`, and `Copyright (c)`.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<LicenseHeaders>` - The marker, or an error if the pattern is not a valid
+    ///   regular expression.
+    pub(crate) fn for_run(config: &Config) -> Result<LicenseHeaders> {
+        let pattern = config.license_pattern.as_deref().unwrap_or(DEFAULT_PATTERN);
+        let regex = Regex::new(pattern)
+            .with_context(|| format!("Invalid --license-pattern `{}`", pattern))?;
+        Ok(LicenseHeaders { regex })
+    }
+
+    /// Removes the license headers at the top of a file.
+    ///
+    /// The comment blocks before the first line of code are checked one at a time, blank
+    /// lines separating them; each that matches the marker is removed with the blank lines
+    /// after it. A `#!` line (or an XML declaration) stays first, and documentation comments
+    /// (`///`, `//!`, `/** */`, `/*! */`) end the search, as does any code. Content with an
+    /// unknown extension is returned unchanged.
+    ///
+    /// # Arguments
+    /// * `content` - The file content.
+    /// * `extension` - The file extension, used to select the comment syntax.
+    ///
+    /// # Returns
+    /// * `(String, usize)` - The content without license headers, and the number of lines
+    ///   removed.
+    pub(crate) fn strip(&self, content: &str, extension: &str) -> (String, usize) {
+        let Some(syntax) = Syntax::from_extension(extension) else {
+            return (content.to_string(), 0);
+        };
+        let delimiters = Delimiters::of(syntax);
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let is_blank = |line: &str| line.trim().is_empty();
+        let prologue = lines.first().is_some_and(|first| {
+            (first.starts_with("#!") && !first.starts_with("#!["))
+                || (syntax == Syntax::Markup && first.starts_with("<?xml"))
+        });
+
+        let mut kept = vec![true; lines.len()];
+        let mut start = usize::from(prologue);
+        loop {
+            while start < lines.len() && is_blank(lines[start]) {
+                start += 1;
+            }
+            let end = delimiters.block_end(&lines, start);
+            if end == start {
+                break;
+            }
+            let mut next = end;
+            if self.regex.is_match(lines[start..end].concat().as_str()) {
+                while next < lines.len() && is_blank(lines[next]) {
+                    next += 1;
+                }
+                kept[start..next].fill(false);
+            }
+            start = next;
+        }
+
+        let removed = kept.iter().filter(|kept| !**kept).count();
+        if removed == 0 {
+            return (content.to_string(), 0);
+        }
+        let stripped = lines
+            .iter()
+            .zip(&kept)
+            .filter(|(_, kept)| **kept)
+            .map(|(line, _)| *line)
+            .collect();
+        (stripped, removed)
+    }
+}
+
+/// The comment delimiters of a syntax family.
+struct Delimiters {
+    line: &'static [&'static str],
+    block: Option<(&'static str, &'static str)>,
+    /// The openings of documentation comments, which are never part of a header.
+    doc: &'static [&'static str],
+}
+
+impl Delimiters {
+    fn of(syntax: Syntax) -> Delimiters {
+        const C_DOC: &[&str] = &["///", "//!", "/**", "/*!"];
+        match syntax {
+            Syntax::Rust | Syntax::CLike => Delimiters {
+                line: &["//"],
+                block: Some(("/*", "*/")),
+                doc: C_DOC,
+            },
+            Syntax::Css => Delimiters {
+                line: &[],
+                block: Some(("/*", "*/")),
+                doc: C_DOC,
+            },
+            Syntax::Hash | Syntax::HashSpaced => Delimiters {
+                line: &["#"],
+                block: None,
+                doc: &[],
+            },
+            Syntax::Markup => Delimiters {
+                line: &[],
+                block: Some(("<!--", "-->")),
+                doc: &[],
+            },
+            Syntax::Sql => Delimiters {
+                line: &["--"],
+                block: Some(("/*", "*/")),
+                doc: &[],
+            },
+            Syntax::Lua => Delimiters {
+                line: &["--"],
+                block: Some(("--[[", "]]")),
+                doc: &[],
+            },
+        }
+    }
+
+    /// Checks whether a comment opening is a documentation comment: `///` but not
+    /// `////`, and `/**` but not `/**/` or a `/***` banner.
+    fn is_doc(&self, line: &str) -> bool {
+        self.doc.iter().any(|doc| {
+            line.strip_prefix(doc)
+                .is_some_and(|rest| !rest.starts_with(['/', '*']))
+        })
+    }
+
+    /// Finds the end of the run of comment lines that starts at `start`, without blank
+    /// lines; `start` itself if that line is not a comment, or a documentation comment.
+    fn block_end(&self, lines: &[&str], start: usize) -> usize {
+        let mut end = start;
+        while let Some(line) = lines.get(end).map(|line| line.trim()) {
+            if line.is_empty() || self.is_doc(line) {
+                break;
+            }
+            if let Some((open, close)) = self.block.filter(|(open, _)| line.starts_with(open)) {
+                // The block comment must close at the end of a line, with no code after it.
+                fn after<'a>(text: &'a str, close: &str) -> Option<&'a str> {
+                    text.find(close).map(|at| &text[at + close.len()..])
+                }
+                let mut last = end;
+                let mut rest = after(&line[open.len()..], close);
+                while rest.is_none() && last + 1 < lines.len() {
+                    last += 1;
+                    rest = after(lines[last], close);
+                }
+                match rest {
+                    Some(rest) if rest.trim().is_empty() => end = last + 1,
+                    _ => break,
+                }
+            } else if self.line.iter().any(|prefix| line.starts_with(prefix)) {
+                end += 1;
+            } else {
+                break;
+            }
+        }
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(content: &str, extension: &str) -> (String, usize) {
+        LicenseHeaders::for_run(&Config::default())
+            .unwrap()
+            .strip(content, extension)
+    }
+
+    #[test]
+    fn line_comment_headers_are_removed() {
+        let content = "\
+// Copyright (c) 2024 Example Corp.
+//
+// Licensed under the Apache License, Version 2.0 (the \"License\");
+// you may not use this file except in compliance with the License.
+
+use std::fs;
+";
+        assert_eq!(strip(content, "rs"), ("use std::fs;\n".to_string(), 5));
+
+        let content = "\
+# Copyright (c) 2024 Example Corp.
+# Licensed under the Apache License, Version 2.0.
+
+import os
+";
+        assert_eq!(strip(content, "py"), ("import os\n".to_string(), 3));
+
+        let content = "-- SPDX-License-Identifier: MIT\nSELECT 1;\n";
+        assert_eq!(strip(content, "sql"), ("SELECT 1;\n".to_string(), 1));
+    }
+
+    #[test]
+    fn block_comment_headers_are_removed() {
+        let content = "\
+/*
+ * Copyright (c) 2024 Example Corp.
+ *
+ * Licensed under the Apache License, Version 2.0.
+ */
+
+package main
+";
+        assert_eq!(strip(content, "go"), ("package main\n".to_string(), 6));
+
+        let content = "<!-- SPDX-License-Identifier: Apache-2.0 -->\n<html></html>\n";
+        assert_eq!(strip(content, "html"), ("<html></html>\n".to_string(), 1));
+
+        let content = "/* Copyright (C) 2024 */ body {}\n";
+        assert_eq!(strip(content, "css"), (content.to_string(), 0));
+    }
+
+    #[test]
+    fn shebangs_stay_and_doc_comments_end_the_header() {
+        let content = "#!/bin/sh\n# SPDX-License-Identifier: MIT\n\necho hi\n";
+        assert_eq!(
+            strip(content, "sh"),
+            ("#!/bin/sh\necho hi\n".to_string(), 2)
+        );
+
+        let content = "// SPDX-License-Identifier: MIT\n//! Licensed under MIT.\nfn main() {}\n";
+        assert_eq!(
+            strip(content, "rs"),
+            ("//! Licensed under MIT.\nfn main() {}\n".to_string(), 1)
+        );
+        let content = "#![deny(unsafe_code)]\n// Licensed under MIT.\n";
+        assert_eq!(strip(content, "rs"), (content.to_string(), 0));
+        let content = "/** Licensed under MIT. */\nclass A {}\n";
+        assert_eq!(strip(content, "java"), (content.to_string(), 0));
+    }
+
+    #[test]
+    fn only_matching_blocks_before_code_are_removed() {
+        let content = "// Helpers for parsing.\n\n// Licensed under MIT.\n\nfn a() {}\n// Licensed under MIT.\n";
+        assert_eq!(
+            strip(content, "rs"),
+            (
+                "// Helpers for parsing.\n\nfn a() {}\n// Licensed under MIT.\n".to_string(),
+                2
+            )
+        );
+        assert_eq!(strip("# Licensed under MIT.\n", "txt").1, 0);
+    }
+
+    #[test]
+    fn patterns_replace_the_default_markers() {
+        let config = Config::builder()
+            .license_pattern("Proprietary and confidential")
+            .build();
+        let headers = LicenseHeaders::for_run(&config).unwrap();
+        let content = "// Proprietary and confidential.\n// Licensed under MIT.\nfn a() {}\n";
+        assert_eq!(headers.strip(content, "rs"), ("fn a() {}\n".to_string(), 2));
+
+        let config = Config::builder().license_pattern("(unclosed").build();
+        let err = LicenseHeaders::for_run(&config).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Invalid --license-pattern `(unclosed`")
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! The `max_total_size` guard: human-readable sizes and a writer that stops at the limit.
+
+use crate::meta::format_size;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// A number of bytes, parsed from `1048576`, `500MB` (decimal), or `1.5GiB` (binary).
+///
+/// Units are case-insensitive; `K`, `M`, `G`, and `T` without a `B` are binary, like `KiB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    /// The default `max_total_size`: 500 MiB.
+    pub const DEFAULT_MAX_TOTAL: ByteSize = ByteSize(500 * 1024 * 1024);
+}
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid size `{}` (expected e.g. `1048576`, `500MB`, `2GiB`)",
+                s
+            )
+        };
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "kb" => 1_000,
+            "mb" => 1_000_000,
+            "gb" => 1_000_000_000,
+            "tb" => 1_000_000_000_000,
+            "k" | "kib" => 1 << 10,
+            "m" | "mib" => 1 << 20,
+            "g" | "gib" => 1 << 30,
+            "t" | "tib" => 1 << 40,
+            _ => return Err(invalid()),
+        };
+        if let Ok(number) = number.parse::<u64>() {
+            return number
+                .checked_mul(multiplier)
+                .map(ByteSize)
+                .ok_or_else(invalid);
+        }
+        let number: f64 = number.parse().map_err(|_| invalid())?;
+        Ok(ByteSize((number * multiplier as f64) as u64))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_size(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ByteSizeVisitor;
+
+        impl Visitor<'_> for ByteSizeVisitor {
+            type Value = ByteSize;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a number of bytes or a size such as \"500MB\"")
+            }
+
+            fn visit_u64<E: de::Error>(self, bytes: u64) -> Result<ByteSize, E> {
+                Ok(ByteSize(bytes))
+            }
+
+            fn visit_i64<E: de::Error>(self, bytes: i64) -> Result<ByteSize, E> {
+                u64::try_from(bytes)
+                    .map(ByteSize)
+                    .map_err(|_| E::custom("a size cannot be negative"))
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<ByteSize, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(ByteSizeVisitor)
+    }
+}
+
+impl Serialize for ByteSize {
+    /// Serializes as a number of bytes, which reads back exactly.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+/// The error returned when the bundle grows past `max_total_size`.
+#[derive(Debug)]
+pub struct OutputLimitExceeded {
+    /// The limit that was exceeded.
+    pub limit: ByteSize,
+    /// The display path of the file being written when the limit was reached, if any.
+    pub path: Option<PathBuf>,
+}
+
+impl fmt::Display for OutputLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The output exceeded --max-total-size of {}", self.limit)?;
+        if let Some(path) = &self.path {
+            write!(f, " while writing {}", path.display())?;
+        }
+        write!(f, "; raise the limit to bundle everything")
+    }
+}
+
+impl std::error::Error for OutputLimitExceeded {}
+
+/// The error that stops a run when its output cannot be written, e.g. because the disk is
+/// full, since no later file could be written either.
+#[derive(Debug)]
+pub(crate) struct OutputFailed {
+    /// The output, e.g. `stdout`, once the run has named it.
+    pub(crate) output: Option<String>,
+    /// The error of the underlying writer.
+    pub(crate) source: io::Error,
+}
+
+impl fmt::Display for OutputFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.output {
+            Some(output) => write!(f, "Failed to write {}", output),
+            None => write!(f, "Failed to write output"),
+        }
+    }
+}
+
+impl std::error::Error for OutputFailed {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// A writer that counts the bytes written and refuses to go past a limit.
+///
+/// Once a write would cross the limit, the bytes up to the limit are written and every
+/// further write fails.
+pub(crate) struct LimitedWriter<'a> {
+    inner: &'a mut dyn Write,
+    written: u64,
+    limit: Option<u64>,
+    exceeded: bool,
+    failure: Option<io::Error>,
+}
+
+impl<'a> LimitedWriter<'a> {
+    /// Wraps a writer.
+    ///
+    /// # Arguments
+    /// * `inner` - The writer to forward to.
+    /// * `limit` - The maximum number of bytes, or zero for no limit.
+    ///
+    /// # Returns
+    /// * `LimitedWriter` - The wrapped writer.
+    pub(crate) fn new(inner: &'a mut dyn Write, limit: ByteSize) -> Self {
+        LimitedWriter {
+            inner,
+            written: 0,
+            limit: (limit.0 > 0).then_some(limit.0),
+            exceeded: false,
+            failure: None,
+        }
+    }
+
+    /// Returns whether a write was cut short by the limit.
+    pub(crate) fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+
+    /// Returns whether the underlying writer failed, e.g. because the reader of the output
+    /// went away or the disk is full, after which nothing more can be written.
+    pub(crate) fn failed(&self) -> bool {
+        self.failure.is_some()
+    }
+
+    /// Takes the first error of the underlying writer, as the error that stops the run.
+    pub(crate) fn take_failure(&mut self) -> Option<OutputFailed> {
+        self.failure.take().map(|source| OutputFailed {
+            output: None,
+            source,
+        })
+    }
+
+    /// Keeps a copy of the first error of the underlying writer; the original goes to the
+    /// caller.
+    fn check(&mut self, err: &io::Error) {
+        if self.failure.is_none() && err.kind() != io::ErrorKind::Interrupted {
+            self.failure = Some(match err.raw_os_error() {
+                Some(code) => io::Error::from_raw_os_error(code),
+                None => io::Error::new(err.kind(), err.to_string()),
+            });
+        }
+    }
+
+    /// Returns the underlying writer, bypassing the limit.
+    pub(crate) fn inner(&mut self) -> &mut dyn Write {
+        self.inner
+    }
+}
+
+impl Write for LimitedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let remaining = match self.limit {
+            Some(limit) => limit.saturating_sub(self.written),
+            None => u64::MAX,
+        };
+        if buf.len() as u64 > remaining {
+            self.exceeded = true;
+            if remaining == 0 {
+                return Err(io::Error::other("the output size limit was reached"));
+            }
+        }
+        let allowed = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let written = self
+            .inner
+            .write(&buf[..allowed])
+            .inspect_err(|err| self.check(err))?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().inspect_err(|err| self.check(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_parse_decimal_and_binary_units() {
+        for (text, bytes) in [
+            ("1048576", 1_048_576),
+            ("500MB", 500_000_000),
+            ("500 mb", 500_000_000),
+            ("2GiB", 2 << 30),
+            ("1.5K", 1536),
+            ("64k", 65_536),
+            ("0", 0),
+        ] {
+            assert_eq!(text.parse::<ByteSize>(), Ok(ByteSize(bytes)), "{}", text);
+        }
+        for text in ["", "MB", "12XB", "-5", "1.2.3G"] {
+            assert!(text.parse::<ByteSize>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn writer_stops_at_the_limit() {
+        let mut buffer = Vec::new();
+        let mut writer = LimitedWriter::new(&mut buffer, ByteSize(10));
+        writer.write_all(b"12345").unwrap();
+        assert!(!writer.exceeded());
+        assert!(writer.write_all(b"6789abcdef").is_err());
+        assert!(writer.exceeded());
+        writer.inner().write_all(b"!").unwrap();
+        assert_eq!(buffer, b"123456789a!");
+
+        let mut buffer = Vec::new();
+        let mut writer = LimitedWriter::new(&mut buffer, ByteSize(0));
+        writer.write_all(&[b'x'; 100]).unwrap();
+        assert!(!writer.exceeded());
+    }
+
+    #[test]
+    fn writer_keeps_the_first_error_of_the_underlying_writer() {
+        let mut full = io::BufWriter::with_capacity(0, FullDisk);
+        let mut writer = LimitedWriter::new(&mut full, ByteSize(0));
+        assert!(writer.write_all(b"a").is_err());
+        assert!(writer.write_all(b"b").is_err());
+        assert!(writer.failed());
+        assert!(!writer.exceeded());
+
+        let failed = writer.take_failure().unwrap();
+        assert_eq!(failed.source.raw_os_error(), Some(28));
+        assert_eq!(failed.to_string(), "Failed to write output");
+    }
+
+    /// A writer that fails like a full disk.
+    struct FullDisk;
+
+    impl Write for FullDisk {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from_raw_os_error(28))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
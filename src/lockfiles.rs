@@ -0,0 +1,88 @@
+//! Lockfiles and similar generated dependency manifests, left out of bundles by default.
+
+use std::path::Path;
+
+/// File names excluded by default: they are large, generated, and of little use to a model.
+///
+/// Matched against the whole file name, since extensions like `.lock` or `.json` are too
+/// broad. `include_lockfiles` keeps them; the `lockfiles` option replaces this list.
+pub const DEFAULT_LOCKFILES: &[&str] = &[
+    // Rust
+    "Cargo.lock",
+    // JavaScript
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    // Python
+    "poetry.lock",
+    "Pipfile.lock",
+    "pdm.lock",
+    "uv.lock",
+    // Go
+    "go.sum",
+    // PHP, Ruby, Elixir, Dart
+    "composer.lock",
+    "Gemfile.lock",
+    "mix.lock",
+    "pubspec.lock",
+    // Apple platforms
+    "Podfile.lock",
+    "Package.resolved",
+    // .NET, Gradle, Nix
+    "packages.lock.json",
+    "gradle.lockfile",
+    "flake.lock",
+];
+
+/// Returns the default `lockfiles` list as owned strings.
+pub(crate) fn default_lockfiles() -> Vec<String> {
+    DEFAULT_LOCKFILES
+        .iter()
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Checks whether a file is one of the listed lockfiles.
+///
+/// # Arguments
+/// * `path` - The path of the file.
+/// * `lockfiles` - The file names to exclude.
+///
+/// # Returns
+/// * `bool` - `true` if the file name is in the list.
+pub(crate) fn is_lockfile(path: &Path, lockfiles: &[String]) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| lockfiles.iter().any(|lockfile| lockfile == name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_default_entry_is_excluded_in_any_directory() {
+        let lockfiles = default_lockfiles();
+        for name in DEFAULT_LOCKFILES {
+            assert!(is_lockfile(Path::new(name), &lockfiles), "{}", name);
+            let nested = Path::new("packages/web").join(name);
+            assert!(is_lockfile(&nested, &lockfiles), "{}", nested.display());
+        }
+    }
+
+    #[test]
+    fn matches_whole_file_names_only() {
+        let lockfiles = default_lockfiles();
+        for path in [
+            "package.json",
+            "other.lock",
+            "go.mod",
+            "my-Cargo.lock",
+            "Cargo.lock.bak",
+        ] {
+            assert!(!is_lockfile(Path::new(path), &lockfiles), "{}", path);
+        }
+    }
+}
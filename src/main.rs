@@ -1,77 +1,2243 @@
-use anyhow::Result;
-use clap::Parser;
-use codebase_to_prompt::Format;
-use std::path::PathBuf;
-use tracing::{debug, level_filters::LevelFilter};
+use anyhow::{Context, Result, bail};
+use chrono::Local;
+use clap::builder::BoolishValueParser;
+use clap::parser::ValueSource;
+use clap::{
+    ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum, ValueHint,
+};
+use codebase_to_prompt::lang::LangMapping;
+use codebase_to_prompt::progress::LogWriter;
+use codebase_to_prompt::rename::PathRename;
+use codebase_to_prompt::transform::{LineEnding, LineRange, Truncation};
+use codebase_to_prompt::{
+    ByteSize, CheckOutcome, ColorChoice, Compression, Config, ConfigSource, Error, FileMetaField,
+    FilterCommand, FitStrategy, Format, GroupBy, GroupOrder, HashAlgorithm, LineNumberFormat,
+    Model, NoFilesMatched, RelativeTo, SkipReason, SortOrder, TimeBound, Tokenizer, clone_remote,
+    expand_config_paths, expand_path, fetch_github, is_git_url, is_github_input, resolve_text,
+};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tracing::{debug, level_filters::LevelFilter, warn};
 use tracing_subscriber::FmtSubscriber;
+use tracing_subscriber::filter::{ParseError, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+
+mod completions;
+
+use completions::Shell;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    after_help = "Every option can also be set through a `C2P_`-prefixed environment variable \
+(e.g. `C2P_FORMAT=markdown`, `C2P_EXCLUDE=lock,json`). Precedence: CLI flags > environment \
+variables > config file > built-in defaults. The on/off options also have a `--no-` form, e.g. \
+`--no-dedup`, that turns off an option the config file turns on.
+
+Exit codes:
+  0  The bundle was written.
+  1  The run failed, or some files could not be read or written (the rest were bundled).
+  2  No files matched the filters (a warning, or an error with --fail-if-empty)."
+)]
 struct Args {
-    #[arg(default_value = ".")]
-    directory: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The directories or files to bundle (default: the current directory); a single file
+    /// is shown by its name. A git URL such as `https://github.com/user/repo` or
+    /// `git@github.com:user/repo.git` is cloned first, and `gh:owner/repo@ref` is downloaded
+    /// as a tarball from GitHub.
+    #[arg(value_hint = ValueHint::AnyPath, env = "C2P_DIRECTORY")]
+    directories: Vec<PathBuf>,
+
+    /// The branch or tag to check out when the input is a git URL or `gh:` repository (default: its default branch).
+    #[arg(long = "ref", value_name = "REF", env = "C2P_REF")]
+    git_ref: Option<String>,
+
+    /// Path to a TOML config file. Defaults to `codebase-to-prompt.toml` or `.c2p.toml` in the target directory.
+    #[arg(short, long, value_hint = ValueHint::FilePath, env = "C2P_CONFIG")]
+    config: Option<PathBuf>,
 
-    #[arg(short, long)]
+    /// Name of a `[profile.<name>]` section in the config file to apply.
+    #[arg(short, long, env = "C2P_PROFILE")]
+    profile: Option<String>,
+
+    #[arg(short, long, value_hint = ValueHint::FilePath, env = "C2P_OUTPUT")]
     output: Option<PathBuf>,
 
-    #[arg(short, long, use_value_delimiter = true, default_value = "")]
-    include: Vec<String>,
+    /// Also write the bundle to this file (repeatable), in the format its extension names:
+    /// `.md` or `.markdown` for Markdown, `.txt` or `.text` for text, `.xml` for Claude XML,
+    /// `.json` for chat-json, `.adoc` or `.asciidoc` for AsciiDoc, and `.tar` for a tar
+    /// archive. The files are read once.
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, env = "C2P_ALSO_OUTPUT")]
+    also_output: Option<Vec<PathBuf>>,
+
+    /// Write one file per source file into this directory, mirroring the tree (e.g.
+    /// `out/src/lib.rs.md`), with an `index.md` linking to each, instead of one bundle.
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, conflicts_with = "output", env = "C2P_OUTPUT_DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// Overwrite output files that already exist (the output, --also-output, --stats-output,
+    /// --manifest, --report-json, and --output-dir files); without it the run refuses.
+    #[arg(long, env = "C2P_FORCE")]
+    force: bool,
+
+    /// Turn off --force.
+    #[arg(long, hide = true)]
+    no_force: bool,
+
+    /// Rename output files that already exist to `<name>.bak` (or `<name>.bak.N`) before
+    /// writing them.
+    #[arg(long, conflicts_with_all = ["force", "append"], env = "C2P_BACKUP")]
+    backup: bool,
+
+    /// Turn off --backup.
+    #[arg(long, hide = true)]
+    no_backup: bool,
 
-    #[arg(short, long, use_value_delimiter = true, default_value = "")]
-    exclude: Vec<String>,
+    /// Fail when the parent directory of an output file is missing, instead of creating it.
+    #[arg(long, env = "C2P_NO_CREATE_DIRS")]
+    no_create_dirs: bool,
 
-    #[arg(long, value_enum, default_value_t = Format::Console)]
-    format: Format,
+    /// Create the missing parent directories of output files (the default), overriding
+    /// `create_dirs = false` in the config file.
+    #[arg(long)]
+    create_dirs: bool,
 
-    #[arg(short = 'd', long)]
+    /// Extensions such as `rs`, dotted names such as `Cargo.lock`, or globs such as `src/**` or
+    /// `*.test.ts`, of the files to bundle (comma-separated, repeatable); `!` negates an entry,
+    /// and the last match wins.
+    #[arg(short, long, use_value_delimiter = true, env = "C2P_INCLUDE")]
+    include: Option<Vec<String>>,
+
+    /// Bundle the files of well-known languages, e.g. `rust,toml` or `docker` (comma-separated,
+    /// repeatable, case-insensitive), in addition to the `--include` entries.
+    #[arg(
+        long,
+        value_name = "NAME",
+        use_value_delimiter = true,
+        env = "C2P_LANG"
+    )]
+    lang: Option<Vec<String>>,
+
+    /// Extensions, dotted names, or globs of the files to leave out, e.g. `min.js` or
+    /// `**/fixtures/**,!**/README.md`; `!` takes files back, and the last match wins.
+    #[arg(short, long, use_value_delimiter = true, env = "C2P_EXCLUDE")]
+    exclude: Option<Vec<String>>,
+
+    /// Read --include entries from a file, one per line; blank lines and `#` comments are
+    /// ignored, and the --include entries apply after them.
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, env = "C2P_INCLUDE_FROM")]
+    include_from: Option<PathBuf>,
+
+    /// Read --exclude entries from a file, like --include-from.
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, env = "C2P_EXCLUDE_FROM")]
+    exclude_from: Option<PathBuf>,
+
+    /// Let config files in subdirectories set the filters and transformations of the files
+    /// under them.
+    #[arg(long, env = "C2P_NESTED_CONFIGS")]
+    nested_configs: bool,
+
+    /// Turn off --nested-configs.
+    #[arg(long, hide = true)]
+    no_nested_configs: bool,
+
+    /// Leave out directories without entering them (repeatable): a name such as `node_modules`
+    /// matches at any depth, a path glob such as `src/generated` is relative to the directory.
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        use_value_delimiter = true,
+        env = "C2P_EXCLUDE_DIR"
+    )]
+    exclude_dir: Option<Vec<String>>,
+
+    /// Always bundle this file, given by its exact path relative to the directory (repeatable),
+    /// even if the extension, glob, ignore-file, or other filters would leave it out.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        use_value_delimiter = true,
+        env = "C2P_INCLUDE_PATH"
+    )]
+    include_path: Option<Vec<PathBuf>>,
+
+    /// Never bundle this file, given by its exact path relative to the directory (repeatable);
+    /// wins over every other rule, including --include-path.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        use_value_delimiter = true,
+        env = "C2P_EXCLUDE_PATH"
+    )]
+    exclude_path: Option<Vec<PathBuf>>,
+
+    #[arg(long, value_enum, env = "C2P_FORMAT")]
+    format: Option<Format>,
+
+    #[arg(short = 'd', long, env = "C2P_APPEND_DATE")]
     append_date: bool,
 
-    #[arg(short = 'g', long)]
+    /// Turn off --append-date.
+    #[arg(long, hide = true)]
+    no_append_date: bool,
+
+    #[arg(short = 'g', long, env = "C2P_APPEND_GIT_HASH")]
     append_git_hash: bool,
 
-    #[arg(short = 'l', long)]
+    /// Turn off --append-git-hash.
+    #[arg(long, hide = true)]
+    no_append_git_hash: bool,
+
+    /// Add `-dirty` to the Git hash when tracked files have uncommitted changes.
+    #[arg(long, env = "C2P_DIRTY_SUFFIX")]
+    dirty_suffix: bool,
+
+    /// Turn off --dirty-suffix.
+    #[arg(long, hide = true)]
+    no_dirty_suffix: bool,
+
+    /// Name the output file with a template: `{name}` and `{ext}` (of --output), `{dir}` (the
+    /// target directory's name), `{date}`, `{hash}`, `{branch}`, `{label}`, `{host}`, and `n` in
+    /// braces (a counter that picks a file name not taken yet). Replaces --append-date,
+    /// --append-git-hash, and --name-suffix.
+    #[arg(long, value_name = "TEMPLATE", env = "C2P_OUTPUT_TEMPLATE")]
+    output_template: Option<String>,
+
+    /// Append a template to the output file name before its extension, e.g.
+    /// `-{branch}-{label}`, with the placeholders of --output-template.
+    #[arg(long, value_name = "TEMPLATE", env = "C2P_NAME_SUFFIX")]
+    name_suffix: Option<String>,
+
+    /// The value of `{label}` in --name-suffix and --output-template.
+    #[arg(long, env = "C2P_LABEL")]
+    label: Option<String>,
+
+    /// Leave placeholders without a value empty, e.g. `{branch}` outside a git repository,
+    /// instead of failing.
+    #[arg(long, env = "C2P_LENIENT_SUFFIX")]
+    lenient_suffix: bool,
+
+    /// Turn off --lenient-suffix.
+    #[arg(long, hide = true)]
+    no_lenient_suffix: bool,
+
+    /// Bundle the outputs of earlier runs, which are left out when --append-date,
+    /// --append-git-hash, or --output-template names each run's output differently.
+    #[arg(long, env = "C2P_INCLUDE_PREVIOUS_OUTPUTS")]
+    include_previous_outputs: bool,
+
+    /// Turn off --include-previous-outputs.
+    #[arg(long, hide = true)]
+    no_include_previous_outputs: bool,
+
+    /// The strftime format of `{date}` and --append-date (default: `%Y%m%d`).
+    #[arg(long, value_name = "FORMAT", env = "C2P_DATE_FORMAT")]
+    date_format: Option<String>,
+
+    #[arg(short = 'l', long, env = "C2P_LINE_NUMBERS")]
     line_numbers: bool,
 
-    #[arg(short = 'H', long)]
+    /// Turn off --line-numbers.
+    #[arg(long, hide = true)]
+    no_line_numbers: bool,
+
+    /// The heading level (1-6) of each file in the Markdown format.
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=6), env = "C2P_HEADING_LEVEL")]
+    heading_level: Option<u8>,
+
+    /// Replace each file's Markdown heading with a template using `{path}`, `{ext}`, `{lines}`,
+    /// and `{tokens}` (e.g. `**File: {path}**`).
+    #[arg(long, value_name = "TEMPLATE", env = "C2P_FILE_HEADER_TEMPLATE")]
+    file_header_template: Option<String>,
+
+    /// How tokens are counted: `approx`, a heuristic of four bytes per token.
+    #[arg(long, value_name = "NAME", env = "C2P_TOKENIZER")]
+    tokenizer: Option<Tokenizer>,
+
+    /// Bundle at most this many tokens, choosing which files fit: priority files first, then
+    /// by --fit-strategy; the files left out are listed at the end of the bundle.
+    #[arg(long, value_name = "TOKENS", env = "C2P_FIT_BUDGET")]
+    fit_budget: Option<usize>,
+
+    /// The model the bundle is meant for, e.g. `gpt-4o` or `claude-sonnet`: warns when the
+    /// bundle fills over 90% of its context window.
+    #[arg(long, value_name = "NAME", env = "C2P_MODEL")]
+    model: Option<Model>,
+
+    /// Fail instead of warning when the bundle fills over 90% of the --model's window.
+    #[arg(long, env = "C2P_STRICT_FIT")]
+    strict_fit: bool,
+
+    /// Turn off --strict-fit.
+    #[arg(long, hide = true)]
+    no_strict_fit: bool,
+
+    /// With --format chat-json, write each file as its own user message.
+    #[arg(long, env = "C2P_MESSAGES_PER_FILE")]
+    messages_per_file: bool,
+
+    /// Turn off --messages-per-file.
+    #[arg(long, hide = true)]
+    no_messages_per_file: bool,
+
+    /// The order in which files are considered for --fit-budget after the priority files.
+    #[arg(long, value_enum, env = "C2P_FIT_STRATEGY")]
+    fit_strategy: Option<FitStrategy>,
+
+    /// Wrap each file of a Markdown bundle in a collapsed `<details>` block, e.g. for pasting
+    /// into a GitHub issue; ignored with a warning for other formats.
+    #[arg(long, env = "C2P_COLLAPSIBLE")]
+    collapsible: bool,
+
+    /// Turn off --collapsible.
+    #[arg(long, hide = true)]
+    no_collapsible: bool,
+
+    /// The line-number gutter, as a template that holds `n` in braces once, optionally aligned
+    /// and padded (e.g. `{n:>5}→ `).
+    #[arg(long, value_name = "TEMPLATE", env = "C2P_LINE_NUMBER_FORMAT")]
+    line_number_format: Option<LineNumberFormat>,
+
+    #[arg(short = 'H', long, env = "C2P_IGNORE_HIDDEN")]
     ignore_hidden: bool,
 
-    #[arg(short = 'R', long, default_value_t = true)]
+    /// Turn off --ignore-hidden.
+    #[arg(long, hide = true)]
+    no_ignore_hidden: bool,
+
+    /// Keep hidden files and directories with these names or globs despite --ignore-hidden
+    /// (repeatable), e.g. `.github` or `.env.*`.
+    #[arg(
+        long,
+        value_name = "NAME",
+        use_value_delimiter = true,
+        env = "C2P_KEEP_HIDDEN"
+    )]
+    keep_hidden: Option<Vec<String>>,
+
+    /// Also keep CI configuration (`.github`, `.gitlab-ci.yml`) despite --ignore-hidden.
+    #[arg(long, env = "C2P_KEEP_HIDDEN_DEFAULTS")]
+    keep_hidden_defaults: bool,
+
+    /// Turn off --keep-hidden-defaults.
+    #[arg(long, hide = true)]
+    no_keep_hidden_defaults: bool,
+
+    /// Respect `.gitignore` rules (the default), overriding `respect_gitignore = false` in the
+    /// config file.
+    #[arg(short = 'R', long, env = "C2P_RESPECT_GITIGNORE")]
     respect_gitignore: bool,
+
+    /// Bundle files that only `.gitignore` rules exclude.
+    #[arg(long)]
+    no_respect_gitignore: bool,
+
+    /// Respect `.ignore` and `.rgignore` files like `.gitignore` (the default), overriding
+    /// `respect_ignore_files = false` in the config file.
+    #[arg(long, env = "C2P_RESPECT_IGNORE_FILES")]
+    respect_ignore_files: bool,
+
+    /// Bundle files that only `.ignore` and `.rgignore` files exclude.
+    #[arg(long)]
+    no_respect_ignore_files: bool,
+
+    /// Leave out files and directories with the `export-ignore` attribute in `.gitattributes`,
+    /// as `git archive` does.
+    #[arg(long, env = "C2P_RESPECT_EXPORT_IGNORE")]
+    respect_export_ignore: bool,
+
+    /// Turn off --respect-export-ignore.
+    #[arg(long, hide = true)]
+    no_respect_export_ignore: bool,
+
+    /// Walk version control directories (`.git`, `.hg`, `.svn`), which are always skipped
+    /// otherwise.
+    #[arg(long, env = "C2P_INCLUDE_VCS_DIR")]
+    include_vcs_dir: bool,
+
+    /// Turn off --include-vcs-dir.
+    #[arg(long, hide = true)]
+    no_include_vcs_dir: bool,
+
+    /// Also skip files matching this gitignore-syntax file, on top of `.c2pignore` and
+    /// `.codebase-to-promptignore` in the target directory.
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, env = "C2P_IGNORE_FILE")]
+    ignore_file: Option<PathBuf>,
+
+    /// Also bundle initialized git submodules, each filtered by its own `.gitignore`. The
+    /// target directory may be below the repository's root; the submodules under it are
+    /// bundled.
+    #[arg(long, env = "C2P_INCLUDE_SUBMODULES")]
+    include_submodules: bool,
+
+    /// Turn off --include-submodules.
+    #[arg(long, hide = true)]
+    no_include_submodules: bool,
+
+    /// Only log warnings and errors, and do not show the progress bar or run summary.
+    #[arg(short, long, env = "C2P_QUIET")]
+    quiet: bool,
+
+    /// Write console output straight to the terminal instead of through `$PAGER`
+    /// (`less -R` by default).
+    #[arg(long, env = "C2P_NO_PAGER")]
+    no_pager: bool,
+
+    /// Print the files, lines, bytes, and estimated tokens of each extension to stderr,
+    /// even with --quiet.
+    #[arg(long, env = "C2P_STATS")]
+    stats: bool,
+
+    /// Write one CSV row per included file (path, extension, bytes, lines, tokens) and per
+    /// skipped entry (path and reason) to this file.
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, env = "C2P_STATS_OUTPUT")]
+    stats_output: Option<PathBuf>,
+
+    /// Print the N included files with the most estimated tokens to stderr after the run,
+    /// with their sizes and share of the total, and the flags that would leave them out.
+    #[arg(long, value_name = "N", env = "C2P_TOP")]
+    top: Option<usize>,
+
+    /// Write a JSON manifest of the bundle to this file: each included file's path, size,
+    /// SHA-256, and token count, with the tool version, git HEAD, time, and effective config.
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, env = "C2P_MANIFEST")]
+    manifest: Option<PathBuf>,
+
+    /// Write a JSON report of the run to this file, or to stdout with `-` when the bundle goes
+    /// to a file or the clipboard: counts, skipped files with reasons, per-extension totals,
+    /// the files written, the duration, and the effective config.
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, env = "C2P_REPORT_JSON")]
+    report_json: Option<PathBuf>,
+
+    /// Log debug messages and list the skipped files after the summary; repeat (`-vv`) for
+    /// trace messages. `RUST_LOG` takes precedence.
+    #[arg(short, long, action = ArgAction::Count, conflicts_with = "quiet", env = "C2P_VERBOSE")]
+    verbose: u8,
+
+    /// List the files that would be included (one path per line) without their contents.
+    #[arg(long, env = "C2P_DRY_RUN")]
+    dry_run: bool,
+
+    /// Turn off --dry-run.
+    #[arg(long, hide = true)]
+    no_dry_run: bool,
+
+    /// Include each file's byte size and token estimate in --dry-run listings.
+    #[arg(long, requires = "dry_run", env = "C2P_DRY_RUN_SIZES")]
+    dry_run_sizes: bool,
+
+    /// Turn off --dry-run-sizes.
+    #[arg(long, hide = true)]
+    no_dry_run_sizes: bool,
+
+    /// Write each file as its header alone (path, size, lines, tokens, and first line),
+    /// without its content, in the chosen format.
+    #[arg(long, conflicts_with = "dry_run", env = "C2P_HEADERS_ONLY")]
+    headers_only: bool,
+
+    /// Turn off --headers-only.
+    #[arg(long, hide = true)]
+    no_headers_only: bool,
+
+    /// Print each extension found with its file count and total size, largest first, and exit
+    /// without writing output. Gitignored and hidden files are left out as in a normal run.
+    #[arg(long, conflicts_with_all = ["dry_run", "watch", "estimate"], env = "C2P_LIST_EXTENSIONS")]
+    list_extensions: bool,
+
+    /// Choose the files to bundle in a terminal picker that shows the candidates as a tree with
+    /// their estimated tokens, then bundle the selection.
+    #[arg(
+        long,
+        conflicts_with_all = ["watch", "estimate", "list_extensions"],
+        env = "C2P_INTERACTIVE"
+    )]
+    interactive: bool,
+
+    /// Save the paths chosen with --interactive to this file, to reuse with --files-from.
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        requires = "interactive",
+        env = "C2P_SAVE_SELECTION"
+    )]
+    save_selection: Option<PathBuf>,
+
+    /// Print the estimated tokens of the files that pass the filters, with the largest files,
+    /// and exit without writing output. Warns when the total exceeds 128k, 200k, or 1M tokens.
+    #[arg(long, conflicts_with_all = ["dry_run", "watch"], env = "C2P_ESTIMATE")]
+    estimate: bool,
+
+    /// Print only the totals of the files that pass the filters, after transformations, as
+    /// `files=N lines=N bytes=N tokens=N` (or a JSON object with `--count-only=json`), and
+    /// exit without writing output.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "line",
+        conflicts_with_all = ["dry_run", "watch", "estimate", "list_extensions", "interactive"],
+        env = "C2P_COUNT_ONLY"
+    )]
+    count_only: Option<CountFormat>,
+
+    /// Regenerate the bundle in memory as with --reproducible and compare it with the
+    /// --output file without writing anything: exit 0 if it is up to date, 1 if it differs
+    /// (naming the files whose sections differ), and 2 if the file is missing.
+    #[arg(
+        long,
+        requires = "output",
+        conflicts_with_all = ["dry_run", "watch", "estimate", "list_extensions", "interactive", "count_only"],
+        env = "C2P_CHECK"
+    )]
+    check: bool,
+
+    /// Print the files that are empty or whitespace-only after transformations, one per line,
+    /// and exit without writing output.
+    #[arg(
+        long,
+        conflicts_with_all = ["dry_run", "watch", "estimate", "list_extensions", "interactive", "count_only"],
+        env = "C2P_LIST_EMPTY"
+    )]
+    list_empty: bool,
+
+    /// Print the effective configuration, after the config file, environment variables, and
+    /// flags, as TOML that works as a config file, and exit without bundling.
+    /// `--print-config=verbose` notes where each option came from: default, config, env,
+    /// cli, or inferred.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "DETAIL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "plain",
+        env = "C2P_PRINT_CONFIG"
+    )]
+    print_config: Option<PrintConfig>,
+
+    /// Inspect the directory and write a commented starter `.c2p.toml` to it: the extensions
+    /// of the languages found, the build output and dependency directories found, Markdown
+    /// output, and `.gitignore` respected. Prints what it found, and refuses to overwrite a
+    /// config file without --force.
+    #[arg(
+        long,
+        conflicts_with_all = ["dry_run", "watch", "estimate", "list_extensions", "interactive", "count_only", "list_empty", "print_config"]
+    )]
+    init: bool,
+
+    /// Remove comments from source files (language-aware; unknown extensions are left untouched).
+    #[arg(long, env = "C2P_STRIP_COMMENTS")]
+    strip_comments: bool,
+
+    /// Turn off --strip-comments.
+    #[arg(long, hide = true)]
+    no_strip_comments: bool,
+
+    /// Keep documentation comments (`///`, `//!`, `/** */`) when stripping comments.
+    #[arg(long, env = "C2P_KEEP_DOC_COMMENTS")]
+    keep_doc_comments: bool,
+
+    /// Turn off --keep-doc-comments.
+    #[arg(long, hide = true)]
+    no_keep_doc_comments: bool,
+
+    /// Collapse runs of blank lines and strip trailing whitespace; skips files left blank.
+    #[arg(long, env = "C2P_COMPACT")]
+    compact: bool,
+
+    /// Turn off --compact.
+    #[arg(long, hide = true)]
+    no_compact: bool,
+
+    /// Replace function bodies with `{ … }` (`...` in Python) in Rust, Go, JavaScript,
+    /// TypeScript, and Python files, keeping signatures, types, imports, and doc comments.
+    #[arg(long, env = "C2P_SIGNATURES_ONLY")]
+    signatures_only: bool,
+
+    /// Turn off --signatures-only.
+    #[arg(long, hide = true)]
+    no_signatures_only: bool,
+
+    /// Strip trailing spaces and tabs from each line and blank lines at the end of each file.
+    /// Markdown hard line breaks (two trailing spaces) are lost.
+    #[arg(long, env = "C2P_TRIM_TRAILING_WHITESPACE")]
+    trim_trailing_whitespace: bool,
+
+    /// Turn off --trim-trailing-whitespace.
+    #[arg(long, hide = true)]
+    no_trim_trailing_whitespace: bool,
+
+    /// Remove ANSI escape sequences (colors, cursor movement, hyperlinks) from file content, e.g.
+    /// of captured logs. Textual: `\x1b` spelled out in source code is kept.
+    #[arg(long, env = "C2P_STRIP_ANSI")]
+    strip_ansi: bool,
+
+    /// Turn off --strip-ansi.
+    #[arg(long, hide = true)]
+    no_strip_ansi: bool,
+
+    /// Remove a leading front matter block (YAML between `---` or TOML between `+++`) from
+    /// Markdown and MDX files.
+    #[arg(long, env = "C2P_STRIP_FRONTMATTER")]
+    strip_frontmatter: bool,
+
+    /// Turn off --strip-frontmatter.
+    #[arg(long, hide = true)]
+    no_strip_frontmatter: bool,
+
+    /// Remove the comment blocks before any code that carry a license (`Licensed under`,
+    /// `SPDX-License-Identifier`, `Copyright (c)`), keeping `#!` lines and doc comments.
+    #[arg(long, env = "C2P_STRIP_LICENSE_HEADERS")]
+    strip_license_headers: bool,
+
+    /// Turn off --strip-license-headers.
+    #[arg(long, hide = true)]
+    no_strip_license_headers: bool,
+
+    /// The regular expression that marks a comment block as a license header for
+    /// --strip-license-headers, instead of the usual markers.
+    #[arg(long, value_name = "REGEX", env = "C2P_LICENSE_PATTERN")]
+    license_pattern: Option<String>,
+
+    /// Write the text outputs of Jupyter notebook cells after their code; images and other
+    /// rich outputs are always left out.
+    #[arg(long, env = "C2P_NOTEBOOK_OUTPUTS")]
+    notebook_outputs: bool,
+
+    /// Turn off --notebook-outputs.
+    #[arg(long, hide = true)]
+    no_notebook_outputs: bool,
+
+    /// Cut lines longer than N characters, ending them with a marker with the number of
+    /// characters removed.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        env = "C2P_MAX_LINE_LENGTH"
+    )]
+    max_line_length: Option<usize>,
+
+    /// Convert line endings to LF or CRLF before formatting, line numbering, and token counting.
+    #[arg(long, value_enum, value_name = "EOL", env = "C2P_NORMALIZE_EOL")]
+    normalize_eol: Option<LineEnding>,
+
+    /// Replace tabs with spaces up to the next multiple of N columns (textually, strings included).
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=64),
+        env = "C2P_EXPAND_TABS"
+    )]
+    expand_tabs: Option<usize>,
+
+    /// Pipe each included file through a shell command before the other transformations,
+    /// bundling its stdout; the command gets C2P_PATH and C2P_EXT in its environment.
+    #[arg(long, value_name = "COMMAND", env = "C2P_FILTER_CMD")]
+    filter_cmd: Option<String>,
+
+    /// Pipe the files with an extension through their own command instead of --filter-cmd
+    /// (repeatable).
+    #[arg(long, value_name = "EXT=COMMAND", env = "C2P_FILTER_CMD_EXT")]
+    filter_cmd_ext: Option<Vec<FilterCommand>>,
+
+    /// Kill a filter command that runs longer than this on one file, failing the file
+    /// (default: 30; 0 for no limit).
+    #[arg(long, value_name = "SECONDS", env = "C2P_FILTER_TIMEOUT")]
+    filter_timeout: Option<u64>,
+
+    /// Run at most N filter commands at once (default: one per job).
+    #[arg(long, value_name = "N", env = "C2P_FILTER_JOBS")]
+    filter_jobs: Option<usize>,
+
+    /// Keep at most the first N lines of each file, followed by a truncation marker.
+    #[arg(long, value_name = "N", env = "C2P_HEAD")]
+    head: Option<usize>,
+
+    /// Keep at most the last N lines of each file, preceded by a truncation marker.
+    #[arg(long, value_name = "N", env = "C2P_TAIL")]
+    tail: Option<usize>,
+
+    /// Keep at most N lines of each file, cut as --truncate says and marked.
+    #[arg(long, value_name = "N", env = "C2P_MAX_LINES_PER_FILE")]
+    max_lines_per_file: Option<usize>,
+
+    /// Keep at most N tokens of each file, cut at a line boundary and marked.
+    #[arg(long, value_name = "N", env = "C2P_MAX_TOKENS_PER_FILE")]
+    max_tokens_per_file: Option<usize>,
+
+    /// Which lines of a file over --max-lines-per-file or --max-tokens-per-file to keep:
+    /// its start, its end, or both ends around an `… [1,234 lines omitted] …` marker.
+    #[arg(long, value_enum, value_name = "STRATEGY", env = "C2P_TRUNCATE")]
+    truncate: Option<Truncation>,
+
+    /// Only include the given 1-based inclusive line range of a file (repeatable), e.g. `src/lib.rs:120-240`.
+    #[arg(
+        long,
+        value_name = "PATH:START-END",
+        use_value_delimiter = true,
+        env = "C2P_ONLY"
+    )]
+    only: Option<Vec<LineRange>>,
+
+    /// Rewrite displayed paths (repeatable), e.g. `internal-billing-service=service-a`; `<from>` may be a glob.
+    #[arg(
+        long,
+        value_name = "FROM=TO",
+        use_value_delimiter = true,
+        env = "C2P_RENAME_PATH"
+    )]
+    rename_path: Option<Vec<PathRename>>,
+
+    /// Show paths relative to this directory instead of the scanned one, e.g. the repository
+    /// root; `git-root` finds the top level of the enclosing git repository.
+    #[arg(long, value_name = "PATH", env = "C2P_RELATIVE_TO")]
+    relative_to: Option<RelativeTo>,
+
+    /// Show paths with the platform separator (`\` on Windows) instead of always using `/`.
+    #[arg(long, env = "C2P_NATIVE_PATHS")]
+    native_paths: bool,
+
+    /// Turn off --native-paths.
+    #[arg(long, hide = true)]
+    no_native_paths: bool,
+
+    /// Bundle lockfiles such as `Cargo.lock` and `package-lock.json`, which are skipped by default.
+    #[arg(long, env = "C2P_INCLUDE_LOCKFILES")]
+    include_lockfiles: bool,
+
+    /// Turn off --include-lockfiles.
+    #[arg(long, hide = true)]
+    no_include_lockfiles: bool,
+
+    /// Bundle files that may hold secrets (`.env`, private keys, `.npmrc`, `credentials.json`, ...), which are skipped by default.
+    #[arg(long, env = "C2P_INCLUDE_SENSITIVE")]
+    include_sensitive: bool,
+
+    /// Turn off --include-sensitive.
+    #[arg(long, hide = true)]
+    no_include_sensitive: bool,
+
+    /// Leave out files marked `linguist-vendored` or `linguist-generated` in `.gitattributes`
+    /// files, e.g. `third_party/** linguist-vendored`.
+    #[arg(long, env = "C2P_RESPECT_LINGUIST")]
+    respect_linguist: bool,
+
+    /// Turn off --respect-linguist.
+    #[arg(long, hide = true)]
+    no_respect_linguist: bool,
+
+    /// Leave out test files: `tests/`, `test/`, `__tests__/`, and `spec/` directories,
+    /// `*_test.go`, `*.test.ts`, `*.spec.js`, `test_*.py`, `*_test.py`, and similar.
+    #[arg(long, conflicts_with = "only_tests", env = "C2P_NO_TESTS")]
+    no_tests: bool,
+
+    /// Bundle test files, overriding `no_tests = true` in the config file.
+    #[arg(long)]
+    include_tests: bool,
+
+    /// Bundle only the test files that --no-tests leaves out.
+    #[arg(long, env = "C2P_ONLY_TESTS")]
+    only_tests: bool,
+
+    /// Turn off --only-tests.
+    #[arg(long, hide = true)]
+    no_only_tests: bool,
+
+    /// Only bundle files modified at or after TIME: a date (`2024-05-01`), an RFC 3339 time,
+    /// or an age such as `7d` or `12h`. Modification times are unreliable after a fresh clone
+    /// or checkout, which sets them all to the time of the checkout.
+    #[arg(long, value_name = "TIME", env = "C2P_NEWER_THAN")]
+    newer_than: Option<TimeBound>,
+
+    /// Only bundle files modified before TIME, in the same forms as --newer-than.
+    #[arg(long, value_name = "TIME", env = "C2P_OLDER_THAN")]
+    older_than: Option<TimeBound>,
+
+    /// Skip files that are empty or contain only whitespace after transformations, such as
+    /// `__init__.py` placeholders and `.gitkeep` files.
+    #[arg(long, env = "C2P_SKIP_EMPTY")]
+    skip_empty: bool,
+
+    /// Turn off --skip-empty.
+    #[arg(long, hide = true)]
+    no_skip_empty: bool,
+
+    /// Skip files smaller than SIZE on disk, e.g. `16` or `1KB`.
+    #[arg(long, value_name = "SIZE", env = "C2P_MIN_FILE_SIZE")]
+    min_file_size: Option<ByteSize>,
+
+    /// Skip files larger than SIZE on disk without reading them, e.g. `1MB` to keep stray
+    /// logs and data dumps out of the bundle.
+    #[arg(long, value_name = "SIZE", env = "C2P_MAX_FILE_SIZE")]
+    max_file_size: Option<ByteSize>,
+
+    /// List binary files and files over --max-file-size as a one-line stub with their size,
+    /// e.g. `./assets/logo.png [binary, 48.0 KiB]`, instead of leaving them out silently.
+    #[arg(long, env = "C2P_BINARY_PLACEHOLDERS")]
+    binary_placeholders: bool,
+
+    /// Turn off --binary-placeholders.
+    #[arg(long, hide = true)]
+    no_binary_placeholders: bool,
+
+    /// Write binary files up to SIZE base64-encoded, in lines of 76 characters, instead of
+    /// skipping them, e.g. `16KiB` for icons and short sound clips.
+    #[arg(long, value_name = "SIZE", env = "C2P_EMBED_BINARY")]
+    embed_binary: Option<ByteSize>,
+
+    /// Bundle only files whose content matches this regular expression (repeatable; a file
+    /// matching any of them is bundled), e.g. `PaymentIntent`. `(?i)` ignores case.
+    #[arg(long, value_name = "REGEX", env = "C2P_GREP")]
+    grep: Option<Vec<String>>,
+
+    /// Bundle only files that match every --grep pattern rather than any.
+    #[arg(long, requires = "grep", env = "C2P_GREP_AND")]
+    grep_and: bool,
+
+    /// Turn off --grep-and.
+    #[arg(long, hide = true)]
+    no_grep_and: bool,
+
+    /// Make the --grep patterns ignore case.
+    #[arg(long, requires = "grep", env = "C2P_GREP_IGNORE_CASE")]
+    grep_ignore_case: bool,
+
+    /// Turn off --grep-ignore-case.
+    #[arg(long, hide = true)]
+    no_grep_ignore_case: bool,
+
+    /// Write only the lines matching --grep with N lines around them, numbered as in the
+    /// file, instead of whole files.
+    #[arg(long, value_name = "N", requires = "grep", env = "C2P_GREP_CONTEXT")]
+    grep_context: Option<usize>,
+
+    /// Include at most N files, the first in output order, and note how many more were left
+    /// out. Skipped files do not count, and with --group-by the limit is for the whole bundle.
+    #[arg(long, value_name = "N", env = "C2P_MAX_FILES")]
+    max_files: Option<usize>,
+
+    /// Skip minified and generated files, detected by name (`*.min.*`, `*.map`, `*_pb2.py`,
+    /// `*.generated.*`) and content (very long lines, `@generated` or `DO NOT EDIT` markers).
+    #[arg(long, env = "C2P_SKIP_GENERATED")]
+    skip_generated: bool,
+
+    /// Bundle minified and generated files, overriding `skip_generated` in the config file.
+    #[arg(long)]
+    no_skip_generated: bool,
+
+    /// Write a header with the tool version, generation time, directory, git branch and
+    /// commit, file count, and filters before the first file (the default), overriding
+    /// `metadata_header = false` in the config file.
+    #[arg(long, env = "C2P_METADATA_HEADER")]
+    metadata_header: bool,
+
+    /// Leave out the metadata header.
+    #[arg(long)]
+    no_metadata_header: bool,
+
+    /// Add a table of the files, blank lines, comment lines, and code lines of each language
+    /// among the included files to the metadata header.
+    #[arg(long, env = "C2P_LANG_STATS")]
+    lang_stats: bool,
+
+    /// Turn off --lang-stats.
+    #[arg(long, hide = true)]
+    no_lang_stats: bool,
+
+    /// Start the Markdown bundle with YAML front matter: title, date, git branch and commit,
+    /// file count, token estimate, and filters. Add fields in the `[frontmatter]` table of
+    /// the config file.
+    #[arg(long, env = "C2P_FRONTMATTER")]
+    frontmatter: bool,
+
+    /// Turn off --frontmatter.
+    #[arg(long, hide = true)]
+    no_frontmatter: bool,
+
+    /// Text written before the bundle, or `@path` to read it from a file.
+    #[arg(long, value_name = "TEXT|@FILE", env = "C2P_PREAMBLE")]
+    preamble: Option<String>,
+
+    /// Text written after the bundle, or `@path` to read it from a file.
+    #[arg(long, value_name = "TEXT|@FILE", env = "C2P_POSTAMBLE")]
+    postamble: Option<String>,
+
+    /// A question written after the last file, or `@path` to read it from a file: a
+    /// `## Question` section in Markdown, a `=== QUESTION ===` banner in text, and the last
+    /// message in chat JSON. Repeat it to ask several, separated by blank lines.
+    #[arg(long, value_name = "TEXT|@FILE", env = "C2P_QUESTION")]
+    question: Option<Vec<String>>,
+
+    /// Set the code-fence language of an extension in the Markdown format (repeatable).
+    #[arg(
+        long,
+        value_name = "EXT=LANG",
+        use_value_delimiter = true,
+        env = "C2P_LANG_MAP"
+    )]
+    lang_map: Option<Vec<LangMapping>>,
+
+    /// Give extensionless scripts the language of the interpreter in their `#!` line
+    /// (python, bash/sh/zsh, node, ruby, perl; plain text otherwise), for --include, --exclude,
+    /// and --lang (`bin/deploy` with `#!/usr/bin/env python3` matches `py`) and for the
+    /// code-fence language.
+    #[arg(long, env = "C2P_SHEBANG_DETECT")]
+    shebang_detect: bool,
+
+    /// Turn off --shebang-detect.
+    #[arg(long, hide = true)]
+    no_shebang_detect: bool,
+
+    /// The order in which files are written (ties are broken by path).
+    #[arg(long, value_enum, env = "C2P_SORT")]
+    sort: Option<SortOrder>,
+
+    /// Reverse the sort order.
+    #[arg(long, env = "C2P_REVERSE")]
+    reverse: bool,
+
+    /// Turn off --reverse.
+    #[arg(long, hide = true)]
+    no_reverse: bool,
+
+    /// How far back commits count for `--sort churn` and `--fit-strategy churn`: an age such
+    /// as `6m` (months; minutes are `min`) or `26w`, or a date [default: 6m].
+    #[arg(
+        long,
+        value_name = "AGE",
+        value_parser = TimeBound::parse_window,
+        env = "C2P_CHURN_WINDOW"
+    )]
+    churn_window: Option<TimeBound>,
+
+    /// Emit files matching these globs first, in the order given (repeatable), e.g. `README*`.
+    #[arg(
+        long,
+        value_name = "GLOB",
+        use_value_delimiter = true,
+        env = "C2P_PRIORITY"
+    )]
+    priority: Option<Vec<String>>,
+
+    /// Also prioritize READMEs, language manifests, and common entry points.
+    #[arg(long, env = "C2P_PRIORITY_DEFAULTS")]
+    priority_defaults: bool,
+
+    /// Turn off --priority-defaults.
+    #[arg(long, hide = true)]
+    no_priority_defaults: bool,
+
+    /// Emit files in the order listed in this file (one relative path or glob per line).
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, env = "C2P_ORDER_FILE")]
+    order_file: Option<PathBuf>,
+
+    /// Exclude files not listed in the --order-file.
+    #[arg(long, requires = "order_file", env = "C2P_ORDER_STRICT")]
+    order_strict: bool,
+
+    /// Turn off --order-strict.
+    #[arg(long, hide = true)]
+    no_order_strict: bool,
+
+    /// Bundle exactly the paths listed in this file (one per line, `-` for stdin) instead of walking.
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, env = "C2P_FILES_FROM")]
+    files_from: Option<PathBuf>,
+
+    /// Like --files-from, but with NUL-delimited paths (e.g. from `git ls-files -z`).
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        conflicts_with = "files_from",
+        env = "C2P_FILES_FROM0"
+    )]
+    files_from0: Option<PathBuf>,
+
+    /// Bundle only this file, given by its path relative to the directory (repeatable), and
+    /// with --follow-imports the files it imports.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        use_value_delimiter = true,
+        env = "C2P_ENTRY"
+    )]
+    entry: Option<Vec<PathBuf>>,
+
+    /// Also bundle the files the --entry files import, transitively: Rust `mod` and
+    /// `crate::` paths, Python imports, and relative JavaScript/TypeScript imports.
+    #[arg(long, env = "C2P_FOLLOW_IMPORTS")]
+    follow_imports: bool,
+
+    /// Turn off --follow-imports.
+    #[arg(long, hide = true)]
+    no_follow_imports: bool,
+
+    /// Follow imports at most N steps away from the --entry files.
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "follow_imports",
+        env = "C2P_FOLLOW_DEPTH"
+    )]
+    follow_depth: Option<usize>,
+
+    /// Bundle only these members of the Cargo workspace in the input directory, by package
+    /// name or directory (comma-separated, repeatable), each in a section headed by its name
+    /// and version. Members come from the `[workspace]` globs of the root Cargo.toml.
+    #[arg(
+        long,
+        value_name = "NAME",
+        use_value_delimiter = true,
+        env = "C2P_WORKSPACE_MEMBER"
+    )]
+    workspace_member: Option<Vec<String>>,
+
+    /// Read the workspace members from `cargo metadata` instead of parsing Cargo.toml files,
+    /// for workspaces the light parse gets wrong.
+    #[arg(long, env = "C2P_USE_CARGO_METADATA")]
+    use_cargo_metadata: bool,
+
+    /// Turn off --use-cargo-metadata.
+    #[arg(long, hide = true)]
+    no_use_cargo_metadata: bool,
+
+    /// Print the members of the Cargo workspace in the input directory, with their versions
+    /// and directories, and exit without writing output.
+    #[arg(
+        long,
+        conflicts_with_all = ["dry_run", "watch", "estimate", "list_extensions", "interactive", "count_only", "list_empty"],
+        env = "C2P_LIST_MEMBERS"
+    )]
+    list_members: bool,
+
+    /// Bundle only the files that differ between two git refs, as they are at HEAD_REF, e.g. `main..release/2.4`.
+    #[arg(long, value_name = "BASE..HEAD_REF", env = "C2P_BETWEEN")]
+    between: Option<String>,
+
+    /// Read stdin to its end and bundle it after the other files under NAME, whose extension
+    /// picks the language, e.g. `cargo test 2>&1 | codebase-to-prompt . --stdin-file test.log`.
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = ["watch", "interactive"],
+        env = "C2P_STDIN_FILE"
+    )]
+    stdin_file: Option<PathBuf>,
+
+    /// Follow symbolic links while walking (loops are detected and skipped).
+    #[arg(long, env = "C2P_FOLLOW_SYMLINKS")]
+    follow_symlinks: bool,
+
+    /// Turn off --follow-symlinks.
+    #[arg(long, hide = true)]
+    no_follow_symlinks: bool,
+
+    /// Number of threads reading and transforming files (default: one per CPU).
+    #[arg(short, long, value_name = "N", env = "C2P_JOBS")]
+    jobs: Option<usize>,
+
+    /// Reuse the transformed content of files unchanged since the last run (same size and
+    /// modification time), kept in FILE; defaults to `.c2p-cache` next to the output file.
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        num_args = 0..=1,
+        require_equals = true,
+        env = "C2P_CACHE"
+    )]
+    cache: Option<Option<PathBuf>>,
+
+    /// Show metadata in each file header: a comma-separated subset of `size,mtime,hash`.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FIELDS",
+        use_value_delimiter = true,
+        env = "C2P_FILE_META"
+    )]
+    file_meta: Option<Vec<FileMetaField>>,
+
+    /// Append a content hash of each file as written to its header, e.g. `(blake3:1f2a…)`,
+    /// and end the bundle with a digest over them.
+    #[arg(long, value_enum, value_name = "ALGORITHM", env = "C2P_HASH")]
+    hash: Option<HashAlgorithm>,
+
+    /// Use UTC instead of local time for modification times in file headers, the date in the
+    /// output file name, and the time of an --append run.
+    #[arg(long, env = "C2P_UTC")]
+    utc: bool,
+
+    /// Turn off --utc.
+    #[arg(long, hide = true)]
+    no_utc: bool,
+
+    /// Make the bundle depend only on the sources: timestamps are pinned by SOURCE_DATE_EPOCH
+    /// or left out, times are in UTC, and paths always use `/`.
+    #[arg(long, conflicts_with = "append_date", env = "C2P_REPRODUCIBLE")]
+    reproducible: bool,
+
+    /// Turn off --reproducible.
+    #[arg(long, hide = true)]
+    no_reproducible: bool,
+
+    /// Write files whose content duplicates an earlier file as a one-line stub.
+    #[arg(long, env = "C2P_DEDUP")]
+    dedup: bool,
+
+    /// Turn off --dedup.
+    #[arg(long, hide = true)]
+    no_dedup: bool,
+
+    /// Append to the output file instead of overwriting it, starting with a heading for the run.
+    #[arg(long, env = "C2P_APPEND")]
+    append: bool,
+
+    /// Turn off --append.
+    #[arg(long, hide = true)]
+    no_append: bool,
+
+    /// Abort on the first file or directory that cannot be read. An output that cannot be written always stops the run.
+    #[arg(long, env = "C2P_STRICT")]
+    strict: bool,
+
+    /// Turn off --strict.
+    #[arg(long, hide = true)]
+    no_strict: bool,
+
+    /// Fail instead of warning when no files match the filters.
+    #[arg(long, env = "C2P_FAIL_IF_EMPTY")]
+    fail_if_empty: bool,
+
+    /// Turn off --fail-if-empty.
+    #[arg(long, hide = true)]
+    no_fail_if_empty: bool,
+
+    /// Also echo the output written to --output to stdout.
+    #[arg(long, env = "C2P_TEE")]
+    tee: bool,
+
+    /// Turn off --tee.
+    #[arg(long, hide = true)]
+    no_tee: bool,
+
+    /// Write files in sections per directory, language, or Cargo workspace member, each
+    /// headed by its file, line, and token counts (files keep their sort order within
+    /// sections).
+    #[arg(long, value_enum, value_name = "BY", env = "C2P_GROUP_BY")]
+    group_by: Option<GroupBy>,
+
+    /// Shorthand for --group-by dir.
+    #[arg(long, conflicts_with = "group_by", env = "C2P_GROUP_BY_DIR")]
+    group_by_dir: bool,
+
+    /// How many leading directories make up a section with --group-by dir (default: 1).
+    #[arg(long, value_name = "N", env = "C2P_GROUP_DEPTH")]
+    group_depth: Option<usize>,
+
+    /// The order of the sections with --group-by lang: most tokens first (default), or
+    /// alphabetical.
+    #[arg(long, value_enum, env = "C2P_GROUP_ORDER")]
+    group_order: Option<GroupOrder>,
+
+    /// Abort once the bundle grows past this size, e.g. `2GiB` (default 500 MiB, 0 for no limit).
+    #[arg(long, value_name = "SIZE", env = "C2P_MAX_TOTAL_SIZE")]
+    max_total_size: Option<ByteSize>,
+
+    /// Compress the output (`.gz` is appended to the output file name).
+    #[arg(long, value_enum, conflicts_with = "clipboard", env = "C2P_COMPRESS")]
+    compress: Option<Compression>,
+
+    /// When to color the console format; colors never go to files or the clipboard.
+    #[arg(long, value_enum, env = "C2P_COLOR")]
+    color: Option<ColorChoice>,
+
+    /// Keep running and regenerate the output file whenever a bundled file changes.
+    #[arg(long, env = "C2P_WATCH")]
+    watch: bool,
+
+    /// Copy the output to the system clipboard (instead of stdout when no output file is set).
+    #[arg(long, env = "C2P_CLIPBOARD")]
+    clipboard: bool,
+
+    /// Turn off --clipboard.
+    #[arg(long, hide = true)]
+    no_clipboard: bool,
+}
+
+/// How `--count-only` prints the totals.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CountFormat {
+    /// `files=N lines=N bytes=N tokens=N` on one line.
+    Line,
+    /// A JSON object with the same keys.
+    Json,
+}
+
+/// How much `--print-config` prints.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PrintConfig {
+    /// The options alone.
+    Plain,
+    /// Each option with a note of where it came from.
+    Verbose,
 }
 
-fn main() -> Result<()> {
+/// Commands other than bundling, which runs when none is given.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print a completion script for a shell.
+    ///
+    /// For example, `codebase-to-prompt completions bash >
+    /// ~/.local/share/bash-completion/completions/codebase-to-prompt`.
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Run a Model Context Protocol server over stdio, offering the `bundle_codebase` and
+    /// `list_files` tools to MCP clients.
+    ServeMcp,
+    /// Serve bundles over HTTP: `GET /bundle?dir=NAME` bundles a directory below --root.
+    ///
+    /// Needs a build with the `server` feature.
+    Serve {
+        /// The address to listen on.
+        #[arg(long, default_value = "127.0.0.1:7878", env = "C2P_ADDR")]
+        addr: String,
+        /// The directory whose subdirectories can be bundled.
+        #[arg(long, value_hint = ValueHint::DirPath, env = "C2P_ROOT")]
+        root: PathBuf,
+    },
+}
+
+fn main() -> Result<ExitCode> {
+    let matches = cli().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if let Some(Command::Completions { shell }) = args.command {
+        completions::generate(shell, &mut cli(), &mut std::io::stdout().lock())
+            .context("Failed to write the completion script")?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let rust_log = env::var("RUST_LOG").ok().filter(|value| !value.is_empty());
+    let (filter, invalid_rust_log) = log_filter(rust_log.as_deref(), args.quiet, args.verbose);
     let subscriber = FmtSubscriber::builder()
-        .with_max_level(LevelFilter::INFO)
-        .finish();
+        .with_max_level(LevelFilter::TRACE)
+        .with_writer(LogWriter::make)
+        .with_ansi(LogWriter::ansi())
+        .finish()
+        .with(filter);
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+    if let Some(err) = invalid_rust_log {
+        warn!("Ignoring invalid RUST_LOG: {}", err);
+    }
+    match &args.command {
+        Some(Command::ServeMcp) => {
+            codebase_to_prompt::install_interrupt_handler();
+            codebase_to_prompt::serve_mcp(std::io::stdin().lock(), std::io::stdout().lock())?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Serve { addr, root }) => {
+            codebase_to_prompt::serve(addr, root)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => {}
+    }
 
-    let args = Args::parse();
+    if args.init {
+        let directory = args
+            .directories
+            .first()
+            .map(|directory| expand_path(directory))
+            .unwrap_or_else(|| PathBuf::from("."));
+        let report = codebase_to_prompt::init_config(&directory, args.force)?;
+        eprint!("{}", report);
+        return Ok(ExitCode::SUCCESS);
+    }
 
-    let mut format = args.format;
-    if matches!(format, Format::Console)
-        && let Some(output_path) = &args.output
+    // Clones and downloads of remote inputs, deleted when the run ends. The printed
+    // configuration keeps the remote inputs, as the checkouts are gone once it exits.
+    let mut checkouts = Vec::new();
+    let mut remote = false;
+    for (index, directory) in args
+        .directories
+        .iter_mut()
+        .enumerate()
+        .filter(|_| args.print_config.is_none())
     {
-        match output_path.extension().and_then(|s| s.to_str()) {
-            Some("md") => format = Format::Markdown,
-            Some("txt") => format = Format::Text,
-            _ => {}
+        let checkout = match directory.to_str() {
+            Some(input) if is_github_input(input) => fetch_github(input, args.git_ref.as_deref())?,
+            Some(url) if is_git_url(url) => clone_remote(url, args.git_ref.as_deref())?,
+            _ => continue,
+        };
+        *directory = checkout.path().to_path_buf();
+        checkouts.push(checkout);
+        remote |= index == 0;
+    }
+    if args.git_ref.is_some() && checkouts.is_empty() && args.print_config.is_none() {
+        bail!("--ref only applies when the input is a git URL or gh: repository");
+    }
+
+    let quiet = args.quiet;
+    let no_pager = args.no_pager;
+    let verbose = args.verbose > 0;
+    let stats = args.stats;
+    let watch = args.watch;
+    let estimate = args.estimate;
+    let count_only = args.count_only;
+    let check = args.check;
+    let list_extensions = args.list_extensions;
+    let list_empty = args.list_empty;
+    let list_members = args.list_members;
+    let interactive = args.interactive;
+    let save_selection = args.save_selection.as_deref().map(expand_path);
+    let print_config = args.print_config;
+    let (mut config, mut sources) = resolve_config(args, &matches, remote)?;
+    config.progress = !quiet;
+    config.pager = !no_pager && !watch;
+    sources.insert("progress".to_string(), ConfigSource::Inferred);
+    sources.insert("pager".to_string(), ConfigSource::Inferred);
+    let dry_run = config.dry_run;
+
+    if let Some(detail) = print_config {
+        let sources = matches!(detail, PrintConfig::Verbose).then_some(&sources);
+        print!("{}", codebase_to_prompt::config_to_toml(&config, sources));
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    debug!("Starting codebase to prompt with config: {:?}", config);
+
+    if watch {
+        codebase_to_prompt::watch(config, |summary| {
+            if quiet {
+                return;
+            }
+            eprintln!(
+                "[{}] Rebuilt: {} files, {} bytes",
+                Local::now().format("%H:%M:%S"),
+                summary.files_included,
+                summary.total_bytes
+            );
+        })?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if list_extensions {
+        print!("{}", codebase_to_prompt::list_extensions(&config)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if list_empty {
+        for path in codebase_to_prompt::list_empty(&config)? {
+            println!("{}", path.display());
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if list_members {
+        for member in codebase_to_prompt::list_workspace_members(&config)? {
+            println!("{}", member);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if estimate {
+        let estimate = match codebase_to_prompt::estimate(&config) {
+            Err(err) if err.is::<NoFilesMatched>() => {
+                eprintln!("Error: {}", err);
+                return Ok(ExitCode::from(2));
+            }
+            result => result?,
+        };
+        print!("{}", estimate);
+        let exceeded = estimate.exceeded_windows();
+        if !exceeded.is_empty() {
+            warn!(
+                "The estimated {} tokens exceed the {} context window{}",
+                estimate.total_tokens,
+                exceeded.join(", "),
+                if exceeded.len() == 1 { "" } else { "s" }
+            );
+        }
+        return Ok(if estimate.files.is_empty() {
+            ExitCode::from(2)
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+
+    if let Some(count_format) = count_only {
+        let summary = match codebase_to_prompt::count(&config) {
+            Err(err) if err.is::<NoFilesMatched>() => {
+                eprintln!("Error: {}", err);
+                return Ok(ExitCode::from(2));
+            }
+            result => result?,
+        };
+        match count_format {
+            CountFormat::Line => println!("{}", summary.counts_line()),
+            CountFormat::Json => println!("{}", summary.counts_json()),
+        }
+        return Ok(if summary.errors > 0 {
+            ExitCode::from(1)
+        } else if summary.files_included == 0 {
+            ExitCode::from(2)
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+
+    if check {
+        return Ok(match codebase_to_prompt::check_output(&config)? {
+            CheckOutcome::UpToDate { path } => {
+                if !quiet {
+                    eprintln!("{} is up to date", path.display());
+                }
+                ExitCode::SUCCESS
+            }
+            CheckOutcome::Stale { path, changed } => {
+                eprintln!("{} is out of date; regenerate it", path.display());
+                if changed.is_empty() {
+                    eprintln!("  The header, tree, or list of files differs");
+                }
+                for file in changed {
+                    eprintln!("  {} differs", file.display());
+                }
+                ExitCode::from(1)
+            }
+            CheckOutcome::Missing { path } => {
+                eprintln!("{} does not exist; generate it", path.display());
+                ExitCode::from(2)
+            }
+            _ => ExitCode::from(1),
+        });
+    }
+
+    // Keeps the selection file of an unsaved --interactive choice alive for the run.
+    let mut _selection_file = None;
+    if interactive {
+        let selection = match codebase_to_prompt::pick_files(&config) {
+            Err(err) if err.is::<NoFilesMatched>() => {
+                eprintln!("Error: {}", err);
+                return Ok(ExitCode::from(2));
+            }
+            result => result?,
+        };
+        let Some(selection) = selection else {
+            eprintln!("Selection cancelled");
+            return Ok(ExitCode::from(1));
+        };
+        let mut list = String::new();
+        for path in &selection {
+            list.push_str(&path.to_string_lossy());
+            list.push('\n');
         }
+        let path = match save_selection {
+            Some(path) => {
+                fs::write(&path, list).with_context(|| {
+                    format!("Failed to save the selection to {}", path.display())
+                })?;
+                path
+            }
+            None => {
+                let mut file = tempfile::NamedTempFile::new()
+                    .context("Failed to create a file for the selection")?;
+                file.write_all(list.as_bytes())
+                    .context("Failed to write the selection")?;
+                let path = file.path().to_path_buf();
+                _selection_file = Some(file);
+                path
+            }
+        };
+        config.files_from = Some(path);
+        config.files_from_nul = false;
     }
 
-    let config = codebase_to_prompt::Config {
-        directory: args.directory,
-        output: args.output,
-        include: args.include,
-        exclude: args.exclude,
-        format,
-        append_date: args.append_date,
-        append_git_hash: args.append_git_hash,
-        line_numbers: args.line_numbers,
-        ignore_hidden: args.ignore_hidden,
-        respect_gitignore: args.respect_gitignore,
+    codebase_to_prompt::install_interrupt_handler();
+    let summary = match codebase_to_prompt::run(config).map_err(anyhow::Error::from) {
+        Err(err) if matches!(err.downcast_ref(), Some(Error::NoFilesMatched(_))) => {
+            eprintln!("Error: {}", err);
+            return Ok(ExitCode::from(2));
+        }
+        // The reader of stdout has what it wanted, e.g. `codebase-to-prompt | head`.
+        Err(err) if is_broken_pipe(&err) => return Ok(ExitCode::SUCCESS),
+        result => result?,
     };
+    if !quiet {
+        if dry_run {
+            eprint!("{} files matched", summary.files_included);
+            if summary.skipped_count(SkipReason::Lockfile) > 0 {
+                eprint!(
+                    " ({} lockfiles excluded; use --include-lockfiles to keep them)",
+                    summary.skipped_count(SkipReason::Lockfile)
+                );
+            }
+            if summary.skipped_count(SkipReason::Sensitive) > 0 {
+                eprint!(
+                    " ({} files that may hold secrets excluded; use --include-sensitive to keep them)",
+                    summary.skipped_count(SkipReason::Sensitive)
+                );
+            }
+            if summary.skipped_count(SkipReason::Tests) > 0 {
+                eprint!(
+                    " ({} files excluded by the test filter)",
+                    summary.skipped_count(SkipReason::Tests)
+                );
+            }
+            if summary.skipped_count(SkipReason::Modified) > 0 {
+                eprint!(
+                    " ({} files excluded by modification time)",
+                    summary.skipped_count(SkipReason::Modified)
+                );
+            }
+            if summary.skipped_count(SkipReason::Empty)
+                + summary.skipped_count(SkipReason::TooSmall)
+                > 0
+            {
+                eprint!(
+                    " ({} empty or small files skipped)",
+                    summary.skipped_count(SkipReason::Empty)
+                        + summary.skipped_count(SkipReason::TooSmall)
+                );
+            }
+            if summary.skipped_count(SkipReason::IgnoreFile) > 0 {
+                eprint!(
+                    " ({} entries excluded by .c2pignore rules)",
+                    summary.skipped_count(SkipReason::IgnoreFile)
+                );
+            }
+            eprintln!();
+        } else {
+            eprint!("{}", summary);
+            eprint!("{}", summary.skipped_report(verbose));
+        }
+    }
+    if !quiet && !dry_run {
+        eprint!("{}", summary.churn_table());
+    }
+    if stats {
+        eprint!("{}", summary.extension_table());
+    }
+    eprint!("{}", summary.largest_files_table());
 
-    debug!("Starting codebase to prompt with config: {:?}", config);
+    if summary.interrupted {
+        eprintln!(
+            "Interrupted: the output holds the {} files written before Ctrl-C",
+            summary.files_included
+        );
+        Ok(ExitCode::from(codebase_to_prompt::INTERRUPTED_EXIT_CODE))
+    } else if summary.errors > 0 {
+        Ok(ExitCode::from(1))
+    } else if summary.files_included == 0 {
+        Ok(ExitCode::from(2))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Checks whether an error comes from writing to a pipe whose reader has gone away.
+///
+/// # Arguments
+/// * `err` - The error of the run.
+///
+/// # Returns
+/// * `bool` - Whether any cause in the chain is a broken pipe.
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe)
+    })
+}
+
+/// Builds the command-line parser. On/off flags set through the environment accept `1`
+/// and `0`, `yes` and `no`, and `on` and `off`, as well as `true` and `false`.
+fn cli() -> clap::Command {
+    Args::command().mut_args(|arg| {
+        if arg.get_env().is_some() && matches!(arg.get_action(), ArgAction::SetTrue) {
+            arg.value_parser(BoolishValueParser::new())
+        } else {
+            arg
+        }
+    })
+}
+
+/// Builds the log filter: the directives in `RUST_LOG` when set and valid, otherwise the
+/// level selected by `--quiet` and `--verbose`.
+///
+/// # Arguments
+/// * `rust_log` - The value of `RUST_LOG`, if set.
+/// * `quiet` - Whether only warnings and errors are logged.
+/// * `verbose` - How many times `--verbose` was given.
+///
+/// # Returns
+/// * `(Targets, Option<ParseError>)` - The filter, and the parse error if `RUST_LOG` was invalid.
+fn log_filter(rust_log: Option<&str>, quiet: bool, verbose: u8) -> (Targets, Option<ParseError>) {
+    let mut invalid = None;
+    if let Some(directives) = rust_log {
+        match directives.parse() {
+            Ok(targets) => return (targets, None),
+            Err(err) => invalid = Some(err),
+        }
+    }
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::WARN,
+        (false, 0) => LevelFilter::INFO,
+        (false, 1) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    };
+    (Targets::new().with_default(level), invalid)
+}
 
-    codebase_to_prompt::run(config)
+/// Builds the effective configuration: CLI flags override the config file, which
+/// overrides the built-in defaults. A config file inside a downloaded or cloned
+/// repository (`remote`) is never applied.
+fn resolve_config(
+    args: Args,
+    matches: &ArgMatches,
+    remote: bool,
+) -> Result<(Config, BTreeMap<String, ConfigSource>)> {
+    let mut target = args
+        .directories
+        .first()
+        .map(|directory| expand_path(directory))
+        .unwrap_or_else(|| PathBuf::from("."));
+    // A file is bundled with the config file next to it.
+    if target.is_file() {
+        target = target
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+    }
+    let config_path = args.config.as_deref().map(expand_path).or_else(|| {
+        (!remote)
+            .then(|| codebase_to_prompt::find_config_file(&target))
+            .flatten()
+    });
+
+    let mut sources = BTreeMap::new();
+    let mut config = match config_path {
+        Some(path) => {
+            // A config file found in the target directory comes with the code, which may not
+            // be trusted to run commands or to write and read files.
+            let (config, fields) = match args.config {
+                Some(_) => codebase_to_prompt::load_config_file_with_fields(
+                    &path,
+                    args.profile.as_deref(),
+                )?,
+                None => {
+                    codebase_to_prompt::load_discovered_config_file(&path, args.profile.as_deref())?
+                }
+            };
+            sources.extend(
+                fields
+                    .into_iter()
+                    .map(|field| (field, ConfigSource::ConfigFile)),
+            );
+            config
+        }
+        None if args.profile.is_some() => {
+            bail!("--profile requires a config file, but none was found")
+        }
+        None => Config::default(),
+    };
+    for arg in matches.ids() {
+        let source = match matches.value_source(arg.as_str()) {
+            Some(ValueSource::CommandLine) => ConfigSource::Cli,
+            Some(ValueSource::EnvVariable) => ConfigSource::Env,
+            _ => continue,
+        };
+        for field in config_fields(arg.as_str()) {
+            sources.insert(field.to_string(), source);
+        }
+    }
+
+    if !args.directories.is_empty() {
+        config.directories = args.directories;
+    }
+    if let Some(output) = args.output {
+        config.output = Some(output);
+    }
+    if let Some(also_output) = args.also_output {
+        config.also_output = also_output;
+    }
+    if let Some(output_dir) = args.output_dir {
+        config.output_dir = Some(output_dir);
+    }
+    config.force = flag(matches, &mut sources, "force", "no_force").unwrap_or(config.force);
+    config.backup = flag(matches, &mut sources, "backup", "no_backup").unwrap_or(config.backup);
+    if let Some(no_create_dirs) = flag(matches, &mut sources, "no_create_dirs", "create_dirs") {
+        config.create_dirs = !no_create_dirs;
+    }
+    if let Some(stats_output) = args.stats_output {
+        config.stats_output = Some(stats_output);
+    }
+    if let Some(top) = args.top {
+        config.top = Some(top);
+    }
+    if let Some(manifest) = args.manifest {
+        config.manifest = Some(manifest);
+    }
+    if let Some(report_json) = args.report_json {
+        config.report_json = Some(report_json);
+    }
+    if let Some(include) = args.include {
+        config.include = include;
+    }
+    if let Some(languages) = args.lang {
+        config.languages = languages;
+    }
+    if let Some(exclude) = args.exclude {
+        config.exclude = exclude;
+    }
+    if let Some(include_from) = args.include_from {
+        config.include_from = Some(include_from);
+    }
+    if let Some(exclude_from) = args.exclude_from {
+        config.exclude_from = Some(exclude_from);
+    }
+    config.nested_configs = flag(matches, &mut sources, "nested_configs", "no_nested_configs")
+        .unwrap_or(config.nested_configs);
+    if let Some(exclude_dirs) = args.exclude_dir {
+        config.exclude_dirs = exclude_dirs;
+    }
+    if let Some(include_paths) = args.include_path {
+        config.include_paths = include_paths;
+    }
+    if let Some(exclude_paths) = args.exclude_path {
+        config.exclude_paths = exclude_paths;
+    }
+    if let Some(format) = args.format {
+        config.format = format;
+    }
+    config.append_date =
+        flag(matches, &mut sources, "append_date", "no_append_date").unwrap_or(config.append_date);
+    config.append_git_hash = flag(
+        matches,
+        &mut sources,
+        "append_git_hash",
+        "no_append_git_hash",
+    )
+    .unwrap_or(config.append_git_hash);
+    config.dirty_suffix = flag(matches, &mut sources, "dirty_suffix", "no_dirty_suffix")
+        .unwrap_or(config.dirty_suffix);
+    if let Some(output_template) = args.output_template {
+        config.output_template = Some(output_template);
+    }
+    if let Some(name_suffix) = args.name_suffix {
+        config.name_suffix = Some(name_suffix);
+    }
+    if let Some(label) = args.label {
+        config.label = Some(label);
+    }
+    config.lenient_suffix = flag(matches, &mut sources, "lenient_suffix", "no_lenient_suffix")
+        .unwrap_or(config.lenient_suffix);
+    config.include_previous_outputs = flag(
+        matches,
+        &mut sources,
+        "include_previous_outputs",
+        "no_include_previous_outputs",
+    )
+    .unwrap_or(config.include_previous_outputs);
+    if let Some(date_format) = args.date_format {
+        config.date_format = date_format;
+    }
+    config.line_numbers = flag(matches, &mut sources, "line_numbers", "no_line_numbers")
+        .unwrap_or(config.line_numbers);
+    if let Some(line_number_format) = args.line_number_format {
+        config.line_number_format = line_number_format;
+    }
+    if let Some(heading_level) = args.heading_level {
+        config.heading_level = heading_level;
+    }
+    if let Some(file_header_template) = args.file_header_template {
+        config.file_header_template = Some(file_header_template);
+    }
+    if let Some(tokenizer) = args.tokenizer {
+        config.tokenizer = tokenizer;
+    }
+    if let Some(fit_budget) = args.fit_budget {
+        config.fit_budget = Some(fit_budget);
+    }
+    if let Some(model) = args.model {
+        config.model = Some(model);
+    }
+    config.strict_fit =
+        flag(matches, &mut sources, "strict_fit", "no_strict_fit").unwrap_or(config.strict_fit);
+    config.messages_per_file = flag(
+        matches,
+        &mut sources,
+        "messages_per_file",
+        "no_messages_per_file",
+    )
+    .unwrap_or(config.messages_per_file);
+    if let Some(fit_strategy) = args.fit_strategy {
+        config.fit_strategy = fit_strategy;
+    }
+    config.collapsible =
+        flag(matches, &mut sources, "collapsible", "no_collapsible").unwrap_or(config.collapsible);
+    config.ignore_hidden = flag(matches, &mut sources, "ignore_hidden", "no_ignore_hidden")
+        .unwrap_or(config.ignore_hidden);
+    if let Some(keep_hidden) = args.keep_hidden {
+        config.keep_hidden = keep_hidden;
+    }
+    config.keep_hidden_defaults = flag(
+        matches,
+        &mut sources,
+        "keep_hidden_defaults",
+        "no_keep_hidden_defaults",
+    )
+    .unwrap_or(config.keep_hidden_defaults);
+    config.respect_gitignore = flag(
+        matches,
+        &mut sources,
+        "respect_gitignore",
+        "no_respect_gitignore",
+    )
+    .unwrap_or(config.respect_gitignore);
+    config.respect_ignore_files = flag(
+        matches,
+        &mut sources,
+        "respect_ignore_files",
+        "no_respect_ignore_files",
+    )
+    .unwrap_or(config.respect_ignore_files);
+    config.respect_export_ignore = flag(
+        matches,
+        &mut sources,
+        "respect_export_ignore",
+        "no_respect_export_ignore",
+    )
+    .unwrap_or(config.respect_export_ignore);
+    config.include_vcs_dir = flag(
+        matches,
+        &mut sources,
+        "include_vcs_dir",
+        "no_include_vcs_dir",
+    )
+    .unwrap_or(config.include_vcs_dir);
+    if let Some(ignore_file) = args.ignore_file {
+        config.ignore_file = Some(ignore_file);
+    }
+    config.include_submodules = flag(
+        matches,
+        &mut sources,
+        "include_submodules",
+        "no_include_submodules",
+    )
+    .unwrap_or(config.include_submodules);
+    config.clipboard =
+        flag(matches, &mut sources, "clipboard", "no_clipboard").unwrap_or(config.clipboard);
+    config.dry_run = flag(matches, &mut sources, "dry_run", "no_dry_run").unwrap_or(config.dry_run);
+    config.dry_run_sizes = flag(matches, &mut sources, "dry_run_sizes", "no_dry_run_sizes")
+        .unwrap_or(config.dry_run_sizes);
+    config.headers_only = flag(matches, &mut sources, "headers_only", "no_headers_only")
+        .unwrap_or(config.headers_only);
+    config.strip_comments = flag(matches, &mut sources, "strip_comments", "no_strip_comments")
+        .unwrap_or(config.strip_comments);
+    config.keep_doc_comments = flag(
+        matches,
+        &mut sources,
+        "keep_doc_comments",
+        "no_keep_doc_comments",
+    )
+    .unwrap_or(config.keep_doc_comments);
+    config.compact = flag(matches, &mut sources, "compact", "no_compact").unwrap_or(config.compact);
+    config.trim_trailing_whitespace = flag(
+        matches,
+        &mut sources,
+        "trim_trailing_whitespace",
+        "no_trim_trailing_whitespace",
+    )
+    .unwrap_or(config.trim_trailing_whitespace);
+    config.strip_ansi =
+        flag(matches, &mut sources, "strip_ansi", "no_strip_ansi").unwrap_or(config.strip_ansi);
+    config.strip_frontmatter = flag(
+        matches,
+        &mut sources,
+        "strip_frontmatter",
+        "no_strip_frontmatter",
+    )
+    .unwrap_or(config.strip_frontmatter);
+    config.strip_license_headers = flag(
+        matches,
+        &mut sources,
+        "strip_license_headers",
+        "no_strip_license_headers",
+    )
+    .unwrap_or(config.strip_license_headers);
+    if let Some(license_pattern) = args.license_pattern {
+        config.license_pattern = Some(license_pattern);
+    }
+    config.notebook_outputs = flag(
+        matches,
+        &mut sources,
+        "notebook_outputs",
+        "no_notebook_outputs",
+    )
+    .unwrap_or(config.notebook_outputs);
+    config.signatures_only = flag(
+        matches,
+        &mut sources,
+        "signatures_only",
+        "no_signatures_only",
+    )
+    .unwrap_or(config.signatures_only);
+    if let Some(normalize_eol) = args.normalize_eol {
+        config.normalize_eol = normalize_eol;
+    }
+    if let Some(max_line_length) = args.max_line_length {
+        config.max_line_length = Some(max_line_length);
+    }
+    if let Some(expand_tabs) = args.expand_tabs {
+        config.expand_tabs = Some(expand_tabs);
+    }
+    if let Some(filter_cmd) = args.filter_cmd {
+        config.filter_cmd = Some(filter_cmd);
+    }
+    if let Some(filter_cmd_ext) = args.filter_cmd_ext {
+        config.filter_cmd_ext = filter_cmd_ext;
+    }
+    if let Some(filter_timeout) = args.filter_timeout {
+        config.filter_timeout = filter_timeout;
+    }
+    if let Some(filter_jobs) = args.filter_jobs {
+        config.filter_jobs = Some(filter_jobs);
+    }
+    if let Some(head) = args.head {
+        config.head = Some(head);
+    }
+    if let Some(tail) = args.tail {
+        config.tail = Some(tail);
+    }
+    if let Some(max_lines_per_file) = args.max_lines_per_file {
+        config.max_lines_per_file = Some(max_lines_per_file);
+    }
+    if let Some(max_tokens_per_file) = args.max_tokens_per_file {
+        config.max_tokens_per_file = Some(max_tokens_per_file);
+    }
+    if let Some(truncate) = args.truncate {
+        config.truncate = truncate;
+    }
+    if let Some(only) = args.only {
+        config.only = only;
+    }
+    if let Some(rename_paths) = args.rename_path {
+        config.rename_paths = rename_paths;
+    }
+    if let Some(relative_to) = args.relative_to {
+        config.relative_to = Some(relative_to);
+    }
+    config.native_paths = flag(matches, &mut sources, "native_paths", "no_native_paths")
+        .unwrap_or(config.native_paths);
+    if let Some(lang_map) = args.lang_map {
+        config.lang_map = lang_map;
+    }
+    config.shebang_detect = flag(matches, &mut sources, "shebang_detect", "no_shebang_detect")
+        .unwrap_or(config.shebang_detect);
+    config.metadata_header = flag(
+        matches,
+        &mut sources,
+        "metadata_header",
+        "no_metadata_header",
+    )
+    .unwrap_or(config.metadata_header);
+    config.lang_stats =
+        flag(matches, &mut sources, "lang_stats", "no_lang_stats").unwrap_or(config.lang_stats);
+    config.frontmatter =
+        flag(matches, &mut sources, "frontmatter", "no_frontmatter").unwrap_or(config.frontmatter);
+    if let Some(preamble) = args.preamble {
+        config.preamble = Some(resolve_text(&preamble, Path::new(""))?);
+    }
+    if let Some(postamble) = args.postamble {
+        config.postamble = Some(resolve_text(&postamble, Path::new(""))?);
+    }
+    if let Some(questions) = args.question {
+        let questions = questions
+            .iter()
+            .map(|question| resolve_text(question, Path::new("")))
+            .collect::<Result<Vec<_>>>()?;
+        let questions: Vec<&str> = questions.iter().map(|q| q.trim_end()).collect();
+        config.question = Some(questions.join("\n\n"));
+    }
+    if let Some(sort) = args.sort {
+        config.sort = sort;
+    }
+    config.reverse = flag(matches, &mut sources, "reverse", "no_reverse").unwrap_or(config.reverse);
+    if let Some(churn_window) = args.churn_window {
+        config.churn_window = churn_window;
+    }
+    if let Some(priority) = args.priority {
+        config.priority = priority;
+    }
+    config.priority_defaults = flag(
+        matches,
+        &mut sources,
+        "priority_defaults",
+        "no_priority_defaults",
+    )
+    .unwrap_or(config.priority_defaults);
+    if let Some(order_file) = args.order_file {
+        config.order_file = Some(order_file);
+    }
+    config.order_strict = flag(matches, &mut sources, "order_strict", "no_order_strict")
+        .unwrap_or(config.order_strict);
+    if let Some(files_from) = args.files_from {
+        config.files_from = Some(files_from);
+        config.files_from_nul = false;
+    }
+    if let Some(entry) = args.entry {
+        config.entry = entry;
+    }
+    config.follow_imports = flag(matches, &mut sources, "follow_imports", "no_follow_imports")
+        .unwrap_or(config.follow_imports);
+    if let Some(follow_depth) = args.follow_depth {
+        config.follow_depth = Some(follow_depth);
+    }
+    if let Some(workspace_member) = args.workspace_member {
+        config.workspace_members = workspace_member;
+    }
+    config.use_cargo_metadata = flag(
+        matches,
+        &mut sources,
+        "use_cargo_metadata",
+        "no_use_cargo_metadata",
+    )
+    .unwrap_or(config.use_cargo_metadata);
+    if let Some(between) = args.between {
+        config.between = Some(between);
+    }
+    config.follow_symlinks = flag(
+        matches,
+        &mut sources,
+        "follow_symlinks",
+        "no_follow_symlinks",
+    )
+    .unwrap_or(config.follow_symlinks);
+    if let Some(jobs) = args.jobs {
+        config.jobs = Some(jobs);
+    }
+    if let Some(cache) = args.cache {
+        config.cache = Some(cache.unwrap_or_else(|| {
+            let directory = config.output.as_deref().and_then(Path::parent);
+            directory
+                .unwrap_or(Path::new(""))
+                .join(codebase_to_prompt::DEFAULT_CACHE_FILE)
+        }));
+    }
+    if let Some(file_meta) = args.file_meta {
+        config.file_meta = file_meta.into();
+    }
+    if let Some(hash) = args.hash {
+        config.hash = Some(hash);
+    }
+    config.utc = flag(matches, &mut sources, "utc", "no_utc").unwrap_or(config.utc);
+    config.reproducible = flag(matches, &mut sources, "reproducible", "no_reproducible")
+        .unwrap_or(config.reproducible);
+    config.dedup = flag(matches, &mut sources, "dedup", "no_dedup").unwrap_or(config.dedup);
+    config.append = flag(matches, &mut sources, "append", "no_append").unwrap_or(config.append);
+    config.tee = flag(matches, &mut sources, "tee", "no_tee").unwrap_or(config.tee);
+    config.strict = flag(matches, &mut sources, "strict", "no_strict").unwrap_or(config.strict);
+    config.fail_if_empty = flag(matches, &mut sources, "fail_if_empty", "no_fail_if_empty")
+        .unwrap_or(config.fail_if_empty);
+    config.include_lockfiles = flag(
+        matches,
+        &mut sources,
+        "include_lockfiles",
+        "no_include_lockfiles",
+    )
+    .unwrap_or(config.include_lockfiles);
+    config.include_sensitive = flag(
+        matches,
+        &mut sources,
+        "include_sensitive",
+        "no_include_sensitive",
+    )
+    .unwrap_or(config.include_sensitive);
+    config.respect_linguist = flag(
+        matches,
+        &mut sources,
+        "respect_linguist",
+        "no_respect_linguist",
+    )
+    .unwrap_or(config.respect_linguist);
+    config.no_tests =
+        flag(matches, &mut sources, "no_tests", "include_tests").unwrap_or(config.no_tests);
+    config.only_tests =
+        flag(matches, &mut sources, "only_tests", "no_only_tests").unwrap_or(config.only_tests);
+    if let Some(newer_than) = args.newer_than {
+        config.newer_than = Some(newer_than);
+    }
+    if let Some(older_than) = args.older_than {
+        config.older_than = Some(older_than);
+    }
+    config.skip_empty =
+        flag(matches, &mut sources, "skip_empty", "no_skip_empty").unwrap_or(config.skip_empty);
+    if let Some(min_file_size) = args.min_file_size {
+        config.min_file_size = Some(min_file_size);
+    }
+    if let Some(max_file_size) = args.max_file_size {
+        config.max_file_size = Some(max_file_size);
+    }
+    config.binary_placeholders = flag(
+        matches,
+        &mut sources,
+        "binary_placeholders",
+        "no_binary_placeholders",
+    )
+    .unwrap_or(config.binary_placeholders);
+    if let Some(embed_binary) = args.embed_binary {
+        config.embed_binary = Some(embed_binary);
+    }
+    if let Some(grep) = args.grep {
+        config.grep = grep;
+    }
+    config.grep_and =
+        flag(matches, &mut sources, "grep_and", "no_grep_and").unwrap_or(config.grep_and);
+    config.grep_ignore_case = flag(
+        matches,
+        &mut sources,
+        "grep_ignore_case",
+        "no_grep_ignore_case",
+    )
+    .unwrap_or(config.grep_ignore_case);
+    if let Some(grep_context) = args.grep_context {
+        config.grep_context = Some(grep_context);
+    }
+    if let Some(max_files) = args.max_files {
+        config.max_files = Some(max_files);
+    }
+    config.skip_generated = flag(matches, &mut sources, "skip_generated", "no_skip_generated")
+        .unwrap_or(config.skip_generated);
+    if args.group_by_dir {
+        config.group_by = Some(GroupBy::Dir);
+    }
+    if let Some(group_by) = args.group_by {
+        config.group_by = Some(group_by);
+    }
+    if let Some(group_depth) = args.group_depth {
+        config.group_depth = group_depth;
+    }
+    if let Some(group_order) = args.group_order {
+        config.group_order = group_order;
+    }
+    if let Some(max_total_size) = args.max_total_size {
+        config.max_total_size = max_total_size;
+    }
+    if let Some(compress) = args.compress {
+        config.compress = Some(compress);
+    }
+    if let Some(color) = args.color {
+        config.color = color;
+    }
+    if let Some(files_from) = args.files_from0 {
+        config.files_from = Some(files_from);
+        config.files_from_nul = true;
+    }
+    if let Some(stdin_file) = args.stdin_file {
+        config.stdin_file = Some(stdin_file);
+    }
+    expand_config_paths(&mut config);
+
+    // One document per file is for other tools to read, and front matter only fits a
+    // Markdown document, so both default to Markdown.
+    if matches!(config.format, Format::Console)
+        && (config.output_dir.is_some() || config.frontmatter)
+    {
+        config.format = Format::Markdown;
+        sources.insert("format".to_string(), ConfigSource::Inferred);
+    }
+    if matches!(config.format, Format::Console)
+        && let Some(output_path) = &config.output
+    {
+        let inferred = match output_path.extension().and_then(|s| s.to_str()) {
+            Some("md") => Some(Format::Markdown),
+            Some("txt") => Some(Format::Text),
+            _ => None,
+        };
+        if let Some(format) = inferred {
+            config.format = format;
+            sources.insert("format".to_string(), ConfigSource::Inferred);
+        }
+    }
+
+    Ok((config, sources))
+}
+
+/// Names the `Config` fields a flag sets, for `--print-config=verbose`. Most flags are
+/// named after their field; flags that are not options of the run set none.
+///
+/// # Arguments
+/// * `arg` - The id of the flag, e.g. `exclude_dir`.
+///
+/// # Returns
+/// * `Vec<&str>` - The fields, e.g. `exclude_dirs`.
+fn config_fields(arg: &str) -> Vec<&str> {
+    match arg {
+        "lang" => vec!["languages"],
+        "exclude_dir" => vec!["exclude_dirs"],
+        "include_path" => vec!["include_paths"],
+        "exclude_path" => vec!["exclude_paths"],
+        "rename_path" => vec!["rename_paths"],
+        "no_tests" | "include_tests" => vec!["no_tests"],
+        "group_by_dir" => vec!["group_by"],
+        "files_from" | "files_from0" => vec!["files_from", "files_from_nul"],
+        "workspace_member" => vec!["workspace_members"],
+        "config" | "profile" | "git_ref" | "quiet" | "verbose" | "no_pager" | "stats" | "watch"
+        | "estimate" | "count_only" | "check" | "list_extensions" | "list_empty"
+        | "list_members" | "print_config" | "init" | "interactive" | "save_selection" => vec![],
+        arg => vec![arg.strip_prefix("no_").unwrap_or(arg)],
+    }
+}
+
+/// Returns the value a flag and its `--no-` counterpart set, e.g. `--dedup` and
+/// `--no-dedup`, so a flag can turn off an option the config file turns on, and
+/// records where the value came from.
+///
+/// # Arguments
+/// * `matches` - The parsed command line.
+/// * `sources` - Where each option came from, updated for the flag's option.
+/// * `on` - The id of the flag, which may be set to `true` or `false` in the environment.
+/// * `off` - The id of its counterpart, which wins over the flag when passed.
+///
+/// # Returns
+/// * `Option<bool>` - The value to set, or `None` to keep the config file's.
+fn flag(
+    matches: &ArgMatches,
+    sources: &mut BTreeMap<String, ConfigSource>,
+    on: &str,
+    off: &str,
+) -> Option<bool> {
+    let source = |id: &str| match matches.value_source(id) {
+        Some(ValueSource::CommandLine) => Some(ConfigSource::Cli),
+        Some(ValueSource::EnvVariable) => Some(ConfigSource::Env),
+        _ => None,
+    };
+    let (value, source) = match (source(off), source(on)) {
+        (Some(source), _) if matches.get_flag(off) => (false, source),
+        (_, Some(source)) => (matches.get_flag(on), source),
+        _ => return None,
+    };
+    for field in config_fields(on) {
+        sources.insert(field.to_string(), source);
+    }
+    Some(value)
 }
@@ -0,0 +1,123 @@
+//! The manifest sidecar: a JSON description of what went into a bundle, for archiving
+//! bundles and telling later whether one is stale.
+
+use crate::git::GitRepo;
+use crate::json::{self, Value};
+use crate::reproducible;
+use crate::{Config, RunSummary, SkipReason};
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fs;
+use std::path::Path;
+
+/// Writes the manifest of a run.
+///
+/// The manifest lists every included file with its relative path, size and SHA-256 before
+/// transformations, content hash as written (with `hash`), and estimated token count, and every file listed as a placeholder
+/// (`binary_placeholders`) with its size and whether it is binary, along with the tool
+/// version, the time of the run, the input directories, the git HEAD commit if the input is
+/// in a repository, the output file (`null` for stdout or the clipboard), the bundle digest
+/// (with `hash`), and the effective configuration.
+///
+/// # Arguments
+/// * `path` - The JSON file to create or overwrite.
+/// * `config` - The configuration options for the bundling process.
+/// * `summary` - The statistics of the run.
+/// * `output` - The output file actually written, after suffixes, if any.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the file cannot be written.
+pub(crate) fn write_manifest(
+    path: &Path,
+    config: &Config,
+    summary: &RunSummary,
+    output: Option<&Path>,
+) -> Result<()> {
+    let manifest = manifest(config, summary, output)?;
+    fs::write(path, manifest.to_pretty_string() + "\n")
+        .with_context(|| format!("Failed to write manifest: {}", path.display()))
+}
+
+/// Builds the manifest of a run; see [`write_manifest`].
+pub(crate) fn manifest(
+    config: &Config,
+    summary: &RunSummary,
+    output: Option<&Path>,
+) -> Result<Value> {
+    let path_value = |path: &Path| Value::from(path.to_string_lossy().into_owned());
+    let git_head = GitRepo::discover(&config.directories)
+        .ok()
+        .flatten()
+        .and_then(|repo| repo.head_id());
+    let files = summary
+        .files
+        .iter()
+        .filter(|file| file.skipped.is_none())
+        .map(|file| {
+            let content_hash = summary
+                .content_hashes
+                .iter()
+                .find(|(path, _)| *path == file.path)
+                .map(|(_, hash)| hash.clone());
+            Value::object([
+                ("path", path_value(&file.path)),
+                ("size", Value::from(file.size)),
+                ("sha256", Value::from(file.sha256.clone())),
+                ("content_hash", Value::from(content_hash)),
+                ("tokens", Value::from(file.tokens)),
+            ])
+        })
+        .collect();
+    let placeholders = summary
+        .files
+        .iter()
+        .filter(|file| file.placeholder)
+        .map(|file| {
+            Value::object([
+                ("path", path_value(&file.path)),
+                ("size", Value::from(file.size)),
+                (
+                    "binary",
+                    Value::Bool(file.skipped == Some(SkipReason::Binary)),
+                ),
+            ])
+        })
+        .collect();
+
+    Ok(Value::object([
+        ("tool", Value::from(env!("CARGO_PKG_NAME"))),
+        ("version", Value::from(env!("CARGO_PKG_VERSION"))),
+        (
+            "generated_at",
+            Value::from(reproducible::run_time(config).map(|time| {
+                DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+            })),
+        ),
+        (
+            "directories",
+            Value::Array(config.directories.iter().map(|d| path_value(d)).collect()),
+        ),
+        ("git_head", Value::from(git_head)),
+        ("output", Value::from(output.map(path_value))),
+        (
+            "totals",
+            Value::object([
+                ("files", Value::from(summary.files_included)),
+                ("bytes", Value::from(summary.total_bytes)),
+                ("lines", Value::from(summary.total_lines)),
+                ("tokens", Value::from(summary.estimated_tokens)),
+            ]),
+        ),
+        (
+            "languages",
+            json::to_value(&summary.languages).context("Failed to describe the languages")?,
+        ),
+        ("files", Value::Array(files)),
+        ("placeholders", Value::Array(placeholders)),
+        ("bundle_digest", Value::from(summary.bundle_digest.clone())),
+        (
+            "config",
+            json::to_value(config).context("Failed to describe the configuration")?,
+        ),
+    ]))
+}
@@ -0,0 +1,90 @@
+//! Markers with which a file opts itself out of bundles, or in despite the include and
+//! exclude lists.
+
+use std::fmt;
+use std::path::PathBuf;
+
+// The markers are split so this file does not carry them itself.
+
+/// A file with this text near its start is never bundled, unless it is in `include_paths`.
+pub const IGNORE_MARKER: &str = concat!("codebase-to-prompt", ":ignore");
+
+/// A file with this text near its start is bundled even if the include and exclude lists
+/// leave it out.
+pub const INCLUDE_MARKER: &str = concat!("codebase-to-prompt", ":include");
+
+/// How many lines from the start of a file are searched for a marker.
+pub(crate) const MARKER_LINES: usize = 10;
+
+/// A marker found in a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Marker {
+    /// [`IGNORE_MARKER`]: leave the file out.
+    Ignore,
+    /// [`INCLUDE_MARKER`]: take the file in.
+    Include,
+}
+
+/// Finds a marker in the first lines of a file, in any comment syntax.
+///
+/// # Arguments
+/// * `content` - The content of the file, or its first bytes.
+///
+/// # Returns
+/// * `Option<Marker>` - The marker, [`Marker::Ignore`] if both appear, or `None`.
+pub(crate) fn find(content: &str) -> Option<Marker> {
+    let mut found = None;
+    for line in content.lines().take(MARKER_LINES) {
+        if line.contains(IGNORE_MARKER) {
+            return Some(Marker::Ignore);
+        }
+        if line.contains(INCLUDE_MARKER) {
+            found = Some(Marker::Include);
+        }
+    }
+    found
+}
+
+/// The error returned for a file that opted out with [`IGNORE_MARKER`], so callers can
+/// count it separately.
+#[derive(Debug)]
+pub(crate) struct OptedOutFile {
+    pub(crate) path: PathBuf,
+}
+
+impl fmt::Display for OptedOutFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "file with a `{}` marker: {}",
+            IGNORE_MARKER,
+            self.path.display()
+        )
+    }
+}
+
+impl std::error::Error for OptedOutFile {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_markers_in_the_first_lines_only() {
+        assert_eq!(
+            find("// codebase-to-prompt:ignore\nfn a() {}\n"),
+            Some(Marker::Ignore)
+        );
+        assert_eq!(
+            find("#!/bin/sh\n# codebase-to-prompt:include\n"),
+            Some(Marker::Include)
+        );
+        assert_eq!(
+            find("<!-- codebase-to-prompt:include -->\n/* codebase-to-prompt:ignore */\n"),
+            Some(Marker::Ignore)
+        );
+        let late = format!("{}// codebase-to-prompt:ignore\n", "x\n".repeat(10));
+        assert_eq!(find(&late), None);
+        assert_eq!(find("fn main() {}\n"), None);
+    }
+}
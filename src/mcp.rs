@@ -0,0 +1,491 @@
+//! A Model Context Protocol server over stdio (`serve-mcp`), so MCP clients can bundle a
+//! codebase without running the CLI themselves.
+//!
+//! Messages are JSON-RPC 2.0, one per line. The server offers two tools, `bundle_codebase`
+//! and `list_files`, built on the same [`Config`] and [`run_to_string`] as the CLI.
+
+use crate::config_file::{find_config_file, load_discovered_config_file};
+use crate::interrupt;
+use crate::json::{self, Value};
+use crate::{ColorChoice, Config, Format, run_to_string};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// The protocol versions the server speaks, newest first.
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// A JSON-RPC error, answered in place of a result.
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// The arguments of `bundle_codebase`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BundleArguments {
+    directory: PathBuf,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    format: Option<Format>,
+    max_tokens: Option<usize>,
+}
+
+/// The arguments of `list_files`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListArguments {
+    directory: PathBuf,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+/// Runs an MCP server until its input ends.
+///
+/// Each line of `input` is a JSON-RPC request or notification, and each response is written
+/// to `output` as one line. Logs never go to `output`, which carries only the protocol.
+///
+/// # Arguments
+/// * `input` - Where the client's messages are read from, usually stdin.
+/// * `output` - Where the responses are written, usually stdout.
+///
+/// # Returns
+/// * `Result<()>` - `Ok(())` when the client closes the input or Ctrl-C is pressed (with
+///   [`install_interrupt_handler`](crate::install_interrupt_handler)), or an error if
+///   reading or writing fails.
+pub fn serve_mcp(input: impl BufRead, mut output: impl Write) -> Result<()> {
+    info!("Serving MCP over stdio");
+    for line in input.lines() {
+        if interrupt::requested() {
+            info!("Interrupted; stopping the MCP server");
+            return Ok(());
+        }
+        let line = line.context("Failed to read an MCP message")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(response) = respond(&line) else {
+            continue;
+        };
+        writeln!(output, "{}", response.to_compact_string())
+            .and_then(|()| output.flush())
+            .context("Failed to write an MCP response")?;
+    }
+    debug!("The MCP client closed the input");
+    Ok(())
+}
+
+/// Answers one line of input.
+///
+/// # Returns
+/// * `Option<Value>` - The response, or `None` for a notification.
+fn respond(line: &str) -> Option<Value> {
+    let message = match json::parse(line) {
+        Ok(message) => message,
+        Err(err) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, format!("Parse error: {}", err)),
+            ));
+        }
+    };
+    let id = message.get("id").cloned();
+    let method = match message.get("method") {
+        Some(Value::String(method)) if message.get("jsonrpc") == Some(&"2.0".into()) => method,
+        _ => {
+            return Some(error_response(
+                id.unwrap_or(Value::Null),
+                RpcError::new(
+                    INVALID_REQUEST,
+                    "Invalid request: expected a JSON-RPC 2.0 object",
+                ),
+            ));
+        }
+    };
+    let Some(id) = id else {
+        debug!("MCP notification: {}", method);
+        return None;
+    };
+    let params = message
+        .get("params")
+        .cloned()
+        .unwrap_or(Value::Object(Vec::new()));
+    Some(match handle(method, params) {
+        Ok(result) => Value::object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)]),
+        Err(err) => error_response(id, err),
+    })
+}
+
+/// Builds the response carrying an error.
+fn error_response(id: Value, error: RpcError) -> Value {
+    Value::object([
+        ("jsonrpc", "2.0".into()),
+        ("id", id),
+        (
+            "error",
+            Value::object([
+                ("code", Value::Number(error.code.to_string())),
+                ("message", error.message.into()),
+            ]),
+        ),
+    ])
+}
+
+/// Runs a request.
+fn handle(method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        "initialize" => Ok(initialize(&params)),
+        "ping" => Ok(Value::Object(Vec::new())),
+        "tools/list" => Ok(Value::object([("tools", Value::Array(tools()))])),
+        "tools/call" => call_tool(params),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        )),
+    }
+}
+
+/// Agrees on a protocol version: the client's if the server speaks it, the newest
+/// otherwise, and announces the tools capability.
+fn initialize(params: &Value) -> Value {
+    let version = match params.get("protocolVersion") {
+        Some(Value::String(version)) if PROTOCOL_VERSIONS.contains(&version.as_str()) => {
+            version.as_str()
+        }
+        _ => PROTOCOL_VERSIONS[0],
+    };
+    Value::object([
+        ("protocolVersion", version.into()),
+        (
+            "capabilities",
+            Value::object([(
+                "tools",
+                Value::object([("listChanged", Value::Bool(false))]),
+            )]),
+        ),
+        (
+            "serverInfo",
+            Value::object([
+                ("name", env!("CARGO_PKG_NAME").into()),
+                ("version", env!("CARGO_PKG_VERSION").into()),
+            ]),
+        ),
+    ])
+}
+
+/// Describes the tools and their arguments, as JSON schemas.
+fn tools() -> Vec<Value> {
+    let string = |description: &str| {
+        Value::object([
+            ("type", "string".into()),
+            ("description", description.into()),
+        ])
+    };
+    let strings = |description: &str| {
+        Value::object([
+            ("type", "array".into()),
+            ("items", Value::object([("type", "string".into())])),
+            ("description", description.into()),
+        ])
+    };
+    let directory = string("The directory to bundle, on the machine running the server.");
+    let include = strings("Only bundle files with these extensions or matching these globs.");
+    let exclude = strings("Skip files with these extensions or matching these globs.");
+    let format = Value::object([
+        ("type", "string".into()),
+        (
+            "enum",
+            Value::Array(
+                ["markdown", "text", "claude-xml", "chat-json", "asciidoc"]
+                    .into_iter()
+                    .map(Value::from)
+                    .collect(),
+            ),
+        ),
+        (
+            "description",
+            "The output format; markdown by default.".into(),
+        ),
+    ]);
+    let max_tokens = Value::object([
+        ("type", "integer".into()),
+        ("minimum", 1usize.into()),
+        (
+            "description",
+            "Leave out files so the bundle fits in this many tokens.".into(),
+        ),
+    ]);
+    let schema = |properties: Vec<(&str, Value)>| {
+        Value::object([
+            ("type", "object".into()),
+            ("properties", Value::object(properties)),
+            ("required", Value::Array(vec!["directory".into()])),
+        ])
+    };
+    vec![
+        Value::object([
+            ("name", "bundle_codebase".into()),
+            (
+                "description",
+                "Bundle the source files of a directory into one prompt-ready text, \
+                 honoring .gitignore and the directory's codebase-to-prompt.toml."
+                    .into(),
+            ),
+            (
+                "inputSchema",
+                schema(vec![
+                    ("directory", directory.clone()),
+                    ("include", include.clone()),
+                    ("exclude", exclude.clone()),
+                    ("format", format),
+                    ("max_tokens", max_tokens),
+                ]),
+            ),
+        ]),
+        Value::object([
+            ("name", "list_files".into()),
+            (
+                "description",
+                "List the files bundle_codebase would include, one path per line.".into(),
+            ),
+            (
+                "inputSchema",
+                schema(vec![
+                    ("directory", directory),
+                    ("include", include),
+                    ("exclude", exclude),
+                ]),
+            ),
+        ]),
+    ]
+}
+
+/// Runs a tool; a failure of the run, such as a missing directory, is a result with
+/// `isError` set, so the model can read it.
+fn call_tool(params: Value) -> Result<Value, RpcError> {
+    let name = match params.get("name") {
+        Some(Value::String(name)) => name.clone(),
+        _ => return Err(RpcError::new(INVALID_PARAMS, "Missing tool name")),
+    };
+    let arguments = params
+        .get("arguments")
+        .cloned()
+        .unwrap_or(Value::Object(Vec::new()));
+    let invalid = |err: json::Error| {
+        RpcError::new(
+            INVALID_PARAMS,
+            format!("Invalid arguments for {}: {}", name, err),
+        )
+    };
+    let outcome = match name.as_str() {
+        "bundle_codebase" => bundle(json::from_value(arguments).map_err(invalid)?),
+        "list_files" => list(json::from_value(arguments).map_err(invalid)?),
+        _ => {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("Unknown tool: {}", name),
+            ));
+        }
+    };
+    let (text, is_error) = match outcome {
+        Ok(text) => (text, false),
+        Err(err) => (format!("{:#}", err), true),
+    };
+    Ok(Value::object([
+        (
+            "content",
+            Value::Array(vec![Value::object([
+                ("type", "text".into()),
+                ("text", text.into()),
+            ])]),
+        ),
+        ("isError", Value::Bool(is_error)),
+    ]))
+}
+
+/// Runs `bundle_codebase`.
+fn bundle(arguments: BundleArguments) -> Result<String> {
+    let mut config = request_config(&arguments.directory, arguments.include, arguments.exclude)?;
+    if let Some(format) = arguments.format {
+        if matches!(format, Format::Tar) {
+            bail!("A tar archive cannot be returned as text; choose another format");
+        }
+        config.format = format;
+    } else if matches!(config.format, Format::Console) {
+        config.format = Format::Markdown;
+    }
+    if arguments.max_tokens.is_some() {
+        config.fit_budget = arguments.max_tokens;
+    }
+    Ok(run_to_string(&config)?)
+}
+
+/// Runs `list_files`.
+fn list(arguments: ListArguments) -> Result<String> {
+    let mut config = request_config(&arguments.directory, arguments.include, arguments.exclude)?;
+    config.dry_run = true;
+    Ok(run_to_string(&config)?)
+}
+
+/// Builds the configuration of a request to a server, an MCP tool call or an HTTP request:
+/// the directory's configuration file, if any, with the filters of the request on top. A
+/// request that matches no file fails.
+pub(crate) fn request_config(
+    directory: &Path,
+    include: Vec<String>,
+    exclude: Vec<String>,
+) -> Result<Config> {
+    if !directory.is_dir() {
+        bail!("Directory not found: {}", directory.display());
+    }
+    // The directory is the client's choice, so its config file may not run commands or
+    // reach files outside it.
+    let mut config = match find_config_file(directory) {
+        Some(path) => load_discovered_config_file(&path, None)?.0,
+        None => Config::default(),
+    };
+    config.directories = vec![directory.to_path_buf()];
+    if !include.is_empty() {
+        config.include = include;
+    }
+    if !exclude.is_empty() {
+        config.exclude = exclude;
+    }
+    config.output = None;
+    config.color = ColorChoice::Never;
+    config.progress = false;
+    config.fail_if_empty = true;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn call(line: &str) -> Value {
+        respond(line).unwrap()
+    }
+
+    fn tool_text(response: &Value) -> (&str, bool) {
+        let result = response.get("result").unwrap();
+        let Some(Value::Array(content)) = result.get("content") else {
+            panic!("no content: {:?}", response);
+        };
+        let Some(Value::String(text)) = content[0].get("text") else {
+            panic!("no text: {:?}", response);
+        };
+        (text, result.get("isError") == Some(&Value::Bool(true)))
+    }
+
+    #[test]
+    fn negotiates_the_protocol_and_lists_the_tools() {
+        let response = call(
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05","capabilities":{},"clientInfo":{"name":"test","version":"1"}}}"#,
+        );
+        let result = response.get("result").unwrap();
+        assert_eq!(result.get("protocolVersion"), Some(&"2024-11-05".into()));
+        assert!(result.get("capabilities").unwrap().get("tools").is_some());
+        assert!(respond(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#).is_none());
+
+        let response = call(r#"{"jsonrpc":"2.0","id":"two","method":"tools/list"}"#);
+        assert_eq!(response.get("id"), Some(&"two".into()));
+        let Some(Value::Array(tools)) = response.get("result").unwrap().get("tools") else {
+            panic!("no tools: {:?}", response);
+        };
+        let names: Vec<&Value> = tools.iter().filter_map(|tool| tool.get("name")).collect();
+        assert_eq!(names, [&"bundle_codebase".into(), &"list_files".into()]);
+    }
+
+    #[test]
+    fn answers_malformed_messages_with_error_objects() {
+        let code = |response: Value| response.get("error").unwrap().get("code").cloned();
+        assert_eq!(
+            code(call("{not json")),
+            Some(Value::Number("-32700".into()))
+        );
+        assert_eq!(
+            code(call(r#"{"id":1,"method":"ping"}"#)),
+            Some(Value::Number("-32600".into()))
+        );
+        assert_eq!(
+            code(call(
+                r#"{"jsonrpc":"2.0","id":1,"method":"resources/list"}"#
+            )),
+            Some(Value::Number("-32601".into()))
+        );
+        assert_eq!(
+            code(call(
+                r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"list_files","arguments":{"dir":"."}}}"#
+            )),
+            Some(Value::Number("-32602".into()))
+        );
+    }
+
+    #[test]
+    fn tools_bundle_and_list_files_or_report_errors() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "notes\n").unwrap();
+        let directory =
+            json::Value::from(temp_dir.path().display().to_string()).to_compact_string();
+        let tool = |name: &str, arguments: &str| {
+            call(&format!(
+                r#"{{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{{"name":"{}","arguments":{}}}}}"#,
+                name, arguments
+            ))
+        };
+
+        let response = tool(
+            "bundle_codebase",
+            &format!(r#"{{"directory":{},"include":["rs"]}}"#, directory),
+        );
+        let (text, is_error) = tool_text(&response);
+        assert!(!is_error, "{}", text);
+        assert!(text.contains("```rust\nfn main() {}\n"), "{}", text);
+        assert!(!text.contains("notes"));
+
+        let response = tool("list_files", &format!(r#"{{"directory":{}}}"#, directory));
+        let (text, is_error) = tool_text(&response);
+        assert!(!is_error, "{}", text);
+        assert!(
+            text.contains("main.rs") && text.contains("notes.txt"),
+            "{}",
+            text
+        );
+
+        let response = tool(
+            "list_files",
+            &format!(r#"{{"directory":{},"include":["go"]}}"#, directory),
+        );
+        assert!(tool_text(&response).1);
+        let response = tool("bundle_codebase", r#"{"directory":"/no/such/dir"}"#);
+        assert_eq!(
+            tool_text(&response),
+            ("Directory not found: /no/such/dir", true)
+        );
+    }
+}
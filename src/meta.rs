@@ -0,0 +1,141 @@
+//! Per-file metadata shown in file headers: size, modification time, and content hash.
+
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// A metadata field that can be shown in file headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileMetaField {
+    /// The size of the file in bytes.
+    Size,
+    /// The last-modified time of the file.
+    Mtime,
+    /// The SHA-256 of the file's content.
+    Hash,
+}
+
+/// The metadata fields to show in each file header.
+///
+/// Deserializes from (and serializes to) a list of field names, e.g.
+/// `file_meta = ["size", "hash"]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "Vec<FileMetaField>", into = "Vec<FileMetaField>")]
+pub struct FileMeta {
+    /// Whether to show the size of the file.
+    pub size: bool,
+    /// Whether to show the last-modified time of the file.
+    pub mtime: bool,
+    /// Whether to show the SHA-256 of the file's content.
+    pub hash: bool,
+}
+
+impl FileMeta {
+    /// Returns `true` if no field is selected.
+    pub fn is_empty(&self) -> bool {
+        !(self.size || self.mtime || self.hash)
+    }
+}
+
+impl FromIterator<FileMetaField> for FileMeta {
+    fn from_iter<I: IntoIterator<Item = FileMetaField>>(iter: I) -> Self {
+        let mut meta = FileMeta::default();
+        for field in iter {
+            match field {
+                FileMetaField::Size => meta.size = true,
+                FileMetaField::Mtime => meta.mtime = true,
+                FileMetaField::Hash => meta.hash = true,
+            }
+        }
+        meta
+    }
+}
+
+impl From<Vec<FileMetaField>> for FileMeta {
+    fn from(fields: Vec<FileMetaField>) -> Self {
+        fields.into_iter().collect()
+    }
+}
+
+impl From<FileMeta> for Vec<FileMetaField> {
+    fn from(meta: FileMeta) -> Self {
+        [
+            (meta.size, FileMetaField::Size),
+            (meta.mtime, FileMetaField::Mtime),
+            (meta.hash, FileMetaField::Hash),
+        ]
+        .into_iter()
+        .filter_map(|(selected, field)| selected.then_some(field))
+        .collect()
+    }
+}
+
+/// Formats a byte count with binary units, e.g. `512 B` or `8.1 KiB`.
+///
+/// # Arguments
+/// * `bytes` - The size in bytes.
+///
+/// # Returns
+/// * `String` - Whole bytes below 1 KiB, otherwise one decimal in the largest fitting unit.
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Formats a time, such as a modification time, as RFC 3339 with second precision.
+///
+/// # Arguments
+/// * `time` - The time to format.
+/// * `utc` - Whether to render in UTC (`Z` suffix) instead of the local time zone.
+///
+/// # Returns
+/// * `String` - The formatted time, e.g. `2024-05-02T14:03:11+02:00`.
+pub(crate) fn format_time(time: SystemTime, utc: bool) -> String {
+    if utc {
+        DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+    } else {
+        DateTime::<Local>::from(time).to_rfc3339_opts(SecondsFormat::Secs, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn sizes_use_binary_units() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(8294), "8.1 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 + 512 * 1024), "5.5 MiB");
+        assert_eq!(format_size(3 << 30), "3.0 GiB");
+    }
+
+    #[test]
+    fn utc_mtime_is_rfc3339() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_714_658_591);
+        assert_eq!(format_time(time, true), "2024-05-02T14:03:11Z");
+    }
+
+    #[test]
+    fn fields_collect_into_flags() {
+        let meta: FileMeta = [FileMetaField::Size, FileMetaField::Hash]
+            .into_iter()
+            .collect();
+        assert!(meta.size && meta.hash && !meta.mtime);
+        assert!(FileMeta::default().is_empty());
+    }
+}
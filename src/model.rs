@@ -0,0 +1,173 @@
+//! Model presets (`--model`): the context window of popular models, so a bundle can be
+//! checked against the window it is meant for.
+
+use crate::{Config, RunSummary};
+use anyhow::{Result, bail};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use std::fmt;
+use std::str::FromStr;
+use tracing::warn;
+
+/// The share of the context window, in percent, a bundle can fill before it is reported;
+/// the rest is left for the question and the answer.
+const FIT_PERCENT: usize = 90;
+
+/// The known models; adding one is a line here.
+const MODELS: &[Model] = &[
+    Model::new("gpt-4o", 128_000),
+    Model::new("gpt-4.1", 1_047_576),
+    Model::new("o3", 200_000),
+    Model::new("claude-sonnet", 200_000),
+    Model::new("claude-opus", 200_000),
+    Model::new("gemini-1.5-pro", 2_097_152),
+    Model::new("llama-3.1-70b", 131_072),
+];
+
+/// A model the bundle is meant for, parsed from its name, e.g. `gpt-4o`; [`Model::ALL`]
+/// lists the known ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Model {
+    name: &'static str,
+    context_window: usize,
+}
+
+impl Model {
+    /// The known models.
+    pub const ALL: &[Model] = MODELS;
+
+    const fn new(name: &'static str, context_window: usize) -> Model {
+        Model {
+            name,
+            context_window,
+        }
+    }
+
+    /// Returns the name of the model, as accepted by `--model`.
+    pub fn name(self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the size of the model's context window, in tokens.
+    pub fn context_window(self) -> usize {
+        self.context_window
+    }
+
+    /// Returns the most tokens a bundle can have without being reported, a share of the
+    /// context window.
+    pub fn fit_limit(self) -> usize {
+        self.context_window * FIT_PERCENT / 100
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        Model::ALL
+            .iter()
+            .find(|model| model.name.eq_ignore_ascii_case(name))
+            .copied()
+            .ok_or_else(|| {
+                let known: Vec<&str> = Model::ALL.iter().map(|model| model.name).collect();
+                format!(
+                    "model `{}` is unknown; known models: {}",
+                    name,
+                    known.join(", ")
+                )
+            })
+    }
+}
+
+impl<'de> Deserialize<'de> for Model {
+    /// Deserializes from the name of a known model.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name)
+    }
+}
+
+impl Serialize for Model {
+    /// Serializes as the name read from configuration files.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Checks that a bundle fits the context window of the `model` of a run, warning when it
+/// fills more than 90% of it, or failing with `strict_fit`.
+///
+/// Nothing is checked without a model, or with `fit_budget`, which sets the budget itself.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+/// * `summary` - The statistics of the run.
+///
+/// # Returns
+/// * `Result<()>` - `Ok(())` if the bundle fits, or an error with the overage when it does
+///   not and `strict_fit` is set.
+pub(crate) fn check_fit(config: &Config, summary: &RunSummary) -> Result<()> {
+    let Some(model) = config.model.filter(|_| config.fit_budget.is_none()) else {
+        return Ok(());
+    };
+    let Some(message) = overage(model, summary.estimated_tokens) else {
+        return Ok(());
+    };
+    if config.strict_fit {
+        bail!("{}", message);
+    }
+    warn!("{}", message);
+    Ok(())
+}
+
+/// Describes by how much a bundle exceeds the share of a model's window it can fill.
+///
+/// # Arguments
+/// * `model` - The model the bundle is meant for.
+/// * `tokens` - The estimated tokens of the bundle.
+///
+/// # Returns
+/// * `Option<String>` - The overage, or `None` if the bundle fits.
+fn overage(model: Model, tokens: usize) -> Option<String> {
+    let limit = model.fit_limit();
+    let over = tokens.checked_sub(limit).filter(|&over| over > 0)?;
+    Some(format!(
+        "The bundle is ~{} tokens, {} over {}% of the {}-token context window of {} ({} tokens)",
+        tokens, over, FIT_PERCENT, model.context_window, model.name, limit
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip_and_errors_list_the_known_models() {
+        for model in Model::ALL {
+            assert_eq!(model.name().parse::<Model>(), Ok(*model));
+        }
+        let model: Model = "GPT-4o".parse().unwrap();
+        assert_eq!(model.context_window(), 128_000);
+        let err = "gpt-5".parse::<Model>().unwrap_err();
+        assert!(err.starts_with("model `gpt-5` is unknown; known models: gpt-4o, gpt-4.1, o3"));
+    }
+
+    #[test]
+    fn reports_the_tokens_over_the_share_of_the_window() {
+        let model: Model = "claude-sonnet".parse().unwrap();
+        assert_eq!(model.fit_limit(), 180_000);
+        assert!(overage(model, 180_000).is_none());
+        assert_eq!(
+            overage(model, 190_500).unwrap(),
+            "The bundle is ~190500 tokens, 10500 over 90% of the 200000-token context window \
+             of claude-sonnet (180000 tokens)"
+        );
+    }
+}
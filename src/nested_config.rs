@@ -0,0 +1,233 @@
+//! Configuration files in subdirectories (`nested_configs`), whose filters and
+//! transformations apply to the files under them, like nested `.gitignore` files.
+
+use crate::{Config, FileFilter, SourceFile, find_config_file, load_config_file_with_fields};
+use anyhow::{Context, Result, bail};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Declares the options a nested configuration file may set, and copies them over.
+macro_rules! nested_fields {
+    ($($field:ident),* $(,)?) => {
+        /// The options a nested configuration file may set: the filters decided per file and
+        /// the transformations of file content. The others only make sense for a whole run.
+        pub(crate) const NESTED_CONFIG_FIELDS: &[&str] = &[$(stringify!($field)),*];
+
+        /// Sets the `fields` of `config` to their values in `nested`.
+        fn overlay(config: &mut Config, nested: Config, fields: &BTreeSet<String>) {
+            $(
+                if fields.contains(stringify!($field)) {
+                    config.$field = nested.$field;
+                }
+            )*
+        }
+    };
+}
+
+nested_fields!(
+    include,
+    languages,
+    exclude,
+    include_from,
+    exclude_from,
+    include_lockfiles,
+    lockfiles,
+    include_sensitive,
+    no_tests,
+    only_tests,
+    tests_patterns,
+    skip_empty,
+    min_file_size,
+    max_file_size,
+    skip_generated,
+    strip_comments,
+    keep_doc_comments,
+    compact,
+    signatures_only,
+    trim_trailing_whitespace,
+    strip_ansi,
+    strip_frontmatter,
+    strip_license_headers,
+    notebook_outputs,
+    max_line_length,
+    normalize_eol,
+    expand_tabs,
+    head,
+    tail,
+    max_lines_per_file,
+    max_tokens_per_file,
+    truncate,
+);
+
+/// The configuration of the files under a directory with a nested configuration file.
+#[derive(Debug)]
+pub(crate) struct NestedScope {
+    /// The configuration of the run, overlaid with the nested files from the top down.
+    pub(crate) config: Config,
+    /// The path filters of `config`.
+    pub(crate) filter: FileFilter,
+}
+
+impl NestedScope {
+    /// Loads a nested configuration file over the configuration of its parent directory.
+    ///
+    /// # Arguments
+    /// * `path` - The configuration file.
+    /// * `base` - The configuration of the parent directory.
+    ///
+    /// # Returns
+    /// * `Result<NestedScope>` - The scope, or an error naming the file if it cannot be
+    ///   loaded or sets an option that only applies to a whole run.
+    fn load(path: &Path, base: &Config) -> Result<NestedScope> {
+        let (nested, fields) = load_config_file_with_fields(path, None)?;
+        if let Some(field) = fields
+            .iter()
+            .find(|field| !NESTED_CONFIG_FIELDS.contains(&field.as_str()))
+        {
+            bail!(
+                "Invalid config file: {}: `{}` can only be set in the root config file, as \
+                 nested config files only set filters and transformations",
+                path.display(),
+                field
+            );
+        }
+        let mut config = base.clone();
+        overlay(&mut config, nested, &fields);
+        let filter = FileFilter::new(&config)
+            .with_context(|| format!("Invalid config file: {}", path.display()))?;
+        Ok(NestedScope { config, filter })
+    }
+}
+
+/// Finds the nested configuration that applies to each file, loading each directory's
+/// configuration file at most once.
+pub(crate) struct NestedConfigs<'a> {
+    config: &'a Config,
+    /// The scope of each directory looked up, `None` if no nested file applies.
+    scopes: HashMap<PathBuf, Option<Arc<NestedScope>>>,
+}
+
+impl<'a> NestedConfigs<'a> {
+    /// Creates the lookup for a run.
+    pub(crate) fn new(config: &'a Config) -> Self {
+        NestedConfigs {
+            config,
+            scopes: HashMap::new(),
+        }
+    }
+
+    /// Finds the scope of a file: that of the nearest directory above it, below the input
+    /// directory, with a configuration file.
+    ///
+    /// # Arguments
+    /// * `file` - The file, read from disk.
+    ///
+    /// # Returns
+    /// * `Result<Option<Arc<NestedScope>>>` - The scope, `None` if no nested file applies or
+    ///   `nested_configs` is off, or an error if a configuration file is invalid.
+    pub(crate) fn scope(&mut self, file: &SourceFile) -> Result<Option<Arc<NestedScope>>> {
+        if !self.config.nested_configs {
+            return Ok(None);
+        }
+        let depth = file.relative_path.components().count();
+        match (
+            file.relative_path.parent(),
+            file.path.ancestors().nth(depth),
+        ) {
+            (Some(directory), Some(root)) => self.directory_scope(root, directory),
+            _ => Ok(None),
+        }
+    }
+
+    /// Finds the scope of a directory relative to the input directory.
+    fn directory_scope(
+        &mut self,
+        root: &Path,
+        directory: &Path,
+    ) -> Result<Option<Arc<NestedScope>>> {
+        // The input directory's own file is the root configuration, loaded up front.
+        let Some(parent) = directory.parent() else {
+            return Ok(None);
+        };
+        let path = root.join(directory);
+        if let Some(scope) = self.scopes.get(&path) {
+            return Ok(scope.clone());
+        }
+        let inherited = self.directory_scope(root, parent)?;
+        let scope = match find_config_file(&path) {
+            Some(file) => {
+                let base = inherited
+                    .as_ref()
+                    .map_or(self.config, |scope| &scope.config);
+                Some(Arc::new(NestedScope::load(&file, base)?))
+            }
+            None => inherited,
+        };
+        self.scopes.insert(path, scope.clone());
+        Ok(scope)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn deeper_files_override_shallower_ones() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("docs/api")).unwrap();
+        fs::write(root.join(".c2p.toml"), "include = [\"rs\"]\n").unwrap();
+        fs::write(
+            root.join("docs/.c2p.toml"),
+            "include = [\"md\"]\nstrip_frontmatter = true\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("docs/api/.c2p.toml"),
+            "exclude = [\"*.gen.md\"]\n",
+        )
+        .unwrap();
+        let config = Config::builder()
+            .include(["rs"])
+            .nested_configs(true)
+            .build();
+        let mut nested = NestedConfigs::new(&config);
+        let scope = |nested: &mut NestedConfigs, path: &str| {
+            nested
+                .scope(&SourceFile::new(root.join(path), path))
+                .unwrap()
+        };
+
+        assert!(scope(&mut nested, "main.rs").is_none());
+        assert!(scope(&mut nested, "src/main.rs").is_none());
+        let docs = scope(&mut nested, "docs/guide/intro.md").unwrap();
+        assert_eq!(docs.config.include, ["md"]);
+        assert!(docs.config.strip_frontmatter);
+        let api = scope(&mut nested, "docs/api/v1/a.gen.md").unwrap();
+        assert_eq!(api.config.include, ["md"]);
+        assert_eq!(api.config.exclude, ["*.gen.md"]);
+        assert!(!api.filter.rules_allow(Path::new("docs/api/v1/a.gen.md")));
+        assert!(Arc::ptr_eq(
+            &api,
+            &scope(&mut nested, "docs/api/b.md").unwrap()
+        ));
+    }
+
+    #[test]
+    fn run_wide_options_are_rejected() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("docs/.c2p.toml"), "output = \"docs.md\"\n").unwrap();
+        let config = Config::builder().nested_configs(true).build();
+        let err = NestedConfigs::new(&config)
+            .scope(&SourceFile::new(root.join("docs/a.md"), "docs/a.md"))
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains(".c2p.toml"), "{}", message);
+        assert!(message.contains("`output` can only be set"), "{}", message);
+    }
+}
@@ -0,0 +1,232 @@
+//! Jupyter notebooks (`.ipynb`) rewritten as readable cells instead of their JSON.
+
+use crate::json;
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+
+/// The extension of Jupyter notebooks.
+pub(crate) const EXTENSION: &str = "ipynb";
+
+/// The parts of a notebook that are written; everything else is ignored.
+#[derive(Debug, Deserialize)]
+struct Notebook {
+    cells: Vec<Cell>,
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Metadata {
+    kernelspec: Option<KernelSpec>,
+    language_info: Option<LanguageInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KernelSpec {
+    language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LanguageInfo {
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Cell {
+    cell_type: String,
+    #[serde(default)]
+    source: Text,
+    #[serde(default)]
+    outputs: Vec<Output>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Output {
+    output_type: String,
+    /// The text of a `stream` output.
+    text: Option<Text>,
+    /// The representations of an `execute_result` or `display_data` output, by MIME type.
+    data: Option<OutputData>,
+    /// The exception name and message of an `error` output.
+    ename: Option<String>,
+    evalue: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputData {
+    #[serde(rename = "text/plain")]
+    text_plain: Option<Text>,
+}
+
+/// Multi-line text, which notebooks store either whole or as a list of lines.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Lines(Vec<String>),
+    Whole(String),
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Text::Whole(String::new())
+    }
+}
+
+impl Text {
+    fn join(&self) -> String {
+        match self {
+            Text::Lines(lines) => lines.concat(),
+            Text::Whole(text) => text.clone(),
+        }
+    }
+}
+
+/// The error returned for a notebook that cannot be parsed, so callers can count it
+/// separately.
+#[derive(Debug)]
+pub(crate) struct MalformedNotebook {
+    pub(crate) path: PathBuf,
+    pub(crate) reason: String,
+}
+
+impl fmt::Display for MalformedNotebook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "malformed notebook {} ({})",
+            self.path.display(),
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for MalformedNotebook {}
+
+/// Rewrites a notebook as its cells, numbered from 1: Markdown cells as prose and code
+/// cells as code blocks in the kernel's language.
+///
+/// Raw cells are written as they are. Outputs are left out unless `outputs` is set, and
+/// then only their text is written, never images or other rich data.
+///
+/// # Arguments
+/// * `content` - The JSON of the notebook.
+/// * `outputs` - Whether to write the text outputs of code cells.
+///
+/// # Returns
+/// * `Result<String, String>` - The cells, or why the notebook cannot be parsed.
+pub(crate) fn render(content: &str, outputs: bool) -> Result<String, String> {
+    let notebook: Notebook = json::from_str(content).map_err(|err| err.to_string())?;
+    let language = notebook
+        .metadata
+        .kernelspec
+        .and_then(|kernel| kernel.language)
+        .or_else(|| notebook.metadata.language_info.and_then(|info| info.name))
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let mut rendered = String::new();
+    for (index, cell) in notebook.cells.iter().enumerate() {
+        if index > 0 {
+            rendered.push('\n');
+        }
+        rendered.push_str(&format!("# Cell {} ({})\n\n", index + 1, cell.cell_type));
+        let source = cell.source.join();
+        if cell.cell_type == "code" {
+            push_block(&mut rendered, &language, &source);
+        } else {
+            push_text(&mut rendered, &source);
+        }
+        if !outputs {
+            continue;
+        }
+        let text: String = cell.outputs.iter().filter_map(output_text).collect();
+        if !text.trim().is_empty() {
+            rendered.push_str("\nOutput:\n\n");
+            push_block(&mut rendered, "text", &text);
+        }
+    }
+    Ok(rendered)
+}
+
+/// Returns the text of an output, or `None` if it has none, such as an image.
+fn output_text(output: &Output) -> Option<String> {
+    let mut text = match output.output_type.as_str() {
+        "stream" => output.text.as_ref()?.join(),
+        "execute_result" | "display_data" => output.data.as_ref()?.text_plain.as_ref()?.join(),
+        "error" => format!(
+            "{}: {}",
+            output.ename.as_deref().unwrap_or("Error"),
+            output.evalue.as_deref().unwrap_or("")
+        ),
+        _ => return None,
+    };
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    Some(text)
+}
+
+/// Appends text, ending it with a line ending.
+fn push_text(rendered: &mut String, text: &str) {
+    rendered.push_str(text);
+    if !text.ends_with('\n') {
+        rendered.push('\n');
+    }
+}
+
+/// Appends a fenced code block longer than any run of backticks in the code.
+fn push_block(rendered: &mut String, language: &str, code: &str) {
+    let longest_run = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat((longest_run + 1).max(3));
+    rendered.push_str(&format!("{}{}\n", fence, language));
+    push_text(rendered, code);
+    rendered.push_str(&format!("{}\n", fence));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+        "cells": [
+            {"cell_type": "markdown", "metadata": {}, "source": ["# Title\n", "Some prose."]},
+            {
+                "cell_type": "code",
+                "execution_count": 1,
+                "metadata": {},
+                "source": "print(1)\n1 + 1",
+                "outputs": [
+                    {"output_type": "stream", "name": "stdout", "text": ["1\n"]},
+                    {"output_type": "execute_result", "data": {"text/plain": ["2"]}},
+                    {"output_type": "display_data", "data": {"image/png": "iVBORw0KGgo="}}
+                ]
+            }
+        ],
+        "metadata": {"kernelspec": {"name": "python3", "language": "python"}},
+        "nbformat": 4,
+        "nbformat_minor": 5
+    }"##;
+
+    #[test]
+    fn renders_cells_without_outputs_by_default() {
+        assert_eq!(
+            render(NOTEBOOK, false).unwrap(),
+            "# Cell 1 (markdown)\n\n# Title\nSome prose.\n\n\
+             # Cell 2 (code)\n\n```python\nprint(1)\n1 + 1\n```\n"
+        );
+    }
+
+    #[test]
+    fn renders_text_outputs_but_not_images() {
+        let rendered = render(NOTEBOOK, true).unwrap();
+        assert!(rendered.ends_with("```\n\nOutput:\n\n```text\n1\n2\n```\n"));
+        assert!(!rendered.contains("iVBOR"));
+    }
+
+    #[test]
+    fn rejects_malformed_notebooks() {
+        assert!(render("{\"cells\": [", false).is_err());
+        assert!(render("{\"nbformat\": 4}", false).is_err());
+    }
+}
@@ -0,0 +1,311 @@
+//! Writing one output file per source file (`output_dir`), mirroring the directory
+//! structure, with an `index.md` linking to every file written.
+
+use crate::formatter::{FileGroup, Formatter, MarkdownFormatter, RunContext};
+use crate::overwrite::Overwrite;
+use crate::{BudgetSelection, Config, FileEntry, RunSummary, SkipReason, write_framing_text};
+use anyhow::{Context, Result, bail};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// The name of the generated index at the root of the output directory.
+const INDEX_FILE_NAME: &str = "index.md";
+
+/// A formatter that writes the section of each file to its own file below `output_dir`,
+/// named after the file with the format's extension added, e.g. `out/src/lib.rs.md`.
+///
+/// Nothing is written to the main output. The preamble, the postamble, the question, and
+/// the lists of deleted and omitted files go to `index.md` instead, around a list of links
+/// to every file written.
+pub(crate) struct MirrorTree<'a> {
+    formatter: &'a mut dyn Formatter,
+    /// The output directory, resolved, so links cannot lead out of it.
+    root: PathBuf,
+    extension: &'static str,
+    overwrite: Overwrite,
+    /// The displayed path of each file written and its path relative to `root`.
+    written: Vec<(PathBuf, PathBuf)>,
+    /// The Markdown of the lists written after the last file.
+    notes: Vec<u8>,
+    preamble: Option<String>,
+    postamble: Option<String>,
+}
+
+impl<'a> MirrorTree<'a> {
+    /// Creates the output directory if needed.
+    ///
+    /// # Arguments
+    /// * `formatter` - The formatter that writes the section of each file.
+    /// * `directory` - The output directory.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<MirrorTree>` - The formatter, or an error if the directory cannot be created.
+    pub(crate) fn new(
+        formatter: &'a mut dyn Formatter,
+        directory: &Path,
+        config: &Config,
+    ) -> Result<Self> {
+        fs::create_dir_all(directory).with_context(|| {
+            format!("Failed to create output directory: {}", directory.display())
+        })?;
+        let root = directory.canonicalize().with_context(|| {
+            format!(
+                "Failed to resolve output directory: {}",
+                directory.display()
+            )
+        })?;
+        Ok(MirrorTree {
+            formatter,
+            root,
+            extension: config.format.extension(),
+            overwrite: Overwrite::of(config),
+            written: Vec::new(),
+            notes: Vec::new(),
+            preamble: None,
+            postamble: None,
+        })
+    }
+
+    /// Writes one file of the output directory, creating its parent directories.
+    fn write_file(
+        &mut self,
+        display_path: &Path,
+        write: impl FnOnce(&mut dyn Formatter, &mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        let relative = output_path(display_path, self.extension)?;
+        let path = self.create(&relative)?;
+        let mut writer = BufWriter::new(
+            File::create(&path)
+                .with_context(|| format!("Failed to create output file: {}", path.display()))?,
+        );
+        write(self.formatter, &mut writer)
+            .and_then(|()| writer.flush().map_err(Into::into))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        self.written.push((display_path.to_path_buf(), relative));
+        Ok(())
+    }
+
+    /// Resolves a path below the output directory and creates its parent directories.
+    ///
+    /// # Arguments
+    /// * `relative` - The path relative to the output directory, without `..` components.
+    ///
+    /// # Returns
+    /// * `Result<PathBuf>` - The path to write, moved out of the way with `backup`, or an
+    ///   error if it exists without `force` or `backup`, is a link, or leads out of the
+    ///   output directory through a linked directory.
+    fn create(&self, relative: &Path) -> Result<PathBuf> {
+        let path = self.root.join(relative);
+        let parent = path.parent().unwrap_or(&self.root);
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        let resolved = parent
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve directory: {}", parent.display()))?;
+        if !resolved.starts_with(&self.root) {
+            bail!(
+                "Refusing to write {}: it leads outside the output directory",
+                path.display()
+            );
+        }
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                bail!("Refusing to overwrite {}: it is a link", path.display());
+            }
+            Ok(_) => {
+                self.overwrite.prepare(&path)?;
+                Ok(path)
+            }
+            Err(_) => Ok(path),
+        }
+    }
+}
+
+/// Maps the displayed path of a file to its output file, relative to the output directory.
+///
+/// Only plain names are kept: `.` components are dropped, and a path with a `..`, root, or
+/// prefix component is refused, so no name can lead outside the output directory.
+///
+/// # Arguments
+/// * `display_path` - The path shown in the output.
+/// * `extension` - The extension of the output format, added to the file name.
+///
+/// # Returns
+/// * `Result<PathBuf>` - The relative path, e.g. `src/lib.rs.md`, or an error for a path
+///   that leads outside the directory.
+fn output_path(display_path: &Path, extension: &str) -> Result<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in display_path.components() {
+        match component {
+            Component::Normal(name) => relative.push(name),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => bail!(
+                "Refusing to write {}: it leads outside the output directory",
+                display_path.display()
+            ),
+        }
+    }
+    let Some(name) = relative.file_name() else {
+        bail!("Cannot name an output file for {}", display_path.display());
+    };
+    let mut name = name.to_os_string();
+    name.push(".");
+    name.push(extension);
+    relative.set_file_name(name);
+    Ok(relative)
+}
+
+impl Formatter for MirrorTree<'_> {
+    /// The preamble and postamble go to the index.
+    fn embeds_framing_text(&self) -> bool {
+        true
+    }
+
+    /// Checks every output file before any is written, so a name that leads outside the
+    /// directory, or a file that exists without `force` or `backup`, fails the run up front.
+    fn begin(&mut self, _writer: &mut dyn Write, context: &RunContext) -> Result<()> {
+        let mut targets = context
+            .files
+            .iter()
+            .map(|path| output_path(path, self.extension))
+            .collect::<Result<Vec<_>>>()?;
+        targets.push(PathBuf::from(INDEX_FILE_NAME));
+        for target in &targets {
+            self.overwrite.check(&self.root.join(target))?;
+        }
+        self.preamble = context.config.preamble.clone();
+        self.postamble = context.config.postamble.clone();
+        Ok(())
+    }
+
+    fn group(
+        &mut self,
+        _writer: &mut dyn Write,
+        _group: &FileGroup,
+        _config: &Config,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn file(&mut self, _writer: &mut dyn Write, entry: &FileEntry, config: &Config) -> Result<()> {
+        self.write_file(entry.display_path(), |formatter, writer| {
+            formatter.file(writer, entry, config)
+        })
+    }
+
+    fn header(
+        &mut self,
+        _writer: &mut dyn Write,
+        entry: &FileEntry,
+        config: &Config,
+    ) -> Result<()> {
+        self.write_file(entry.display_path(), |formatter, writer| {
+            formatter.header(writer, entry, config)
+        })
+    }
+
+    fn duplicate(
+        &mut self,
+        _writer: &mut dyn Write,
+        entry: &FileEntry,
+        original: &Path,
+        config: &Config,
+    ) -> Result<()> {
+        self.write_file(entry.display_path(), |formatter, writer| {
+            formatter.duplicate(writer, entry, original, config)
+        })
+    }
+
+    fn placeholder(
+        &mut self,
+        _writer: &mut dyn Write,
+        entry: &FileEntry,
+        reason: SkipReason,
+        config: &Config,
+    ) -> Result<()> {
+        self.write_file(entry.display_path(), |formatter, writer| {
+            formatter.placeholder(writer, entry, reason, config)
+        })
+    }
+
+    fn deleted(
+        &mut self,
+        _writer: &mut dyn Write,
+        paths: &[PathBuf],
+        config: &Config,
+    ) -> Result<()> {
+        MarkdownFormatter.deleted(&mut self.notes, paths, config)
+    }
+
+    fn omitted(
+        &mut self,
+        _writer: &mut dyn Write,
+        selection: &BudgetSelection,
+        config: &Config,
+    ) -> Result<()> {
+        MarkdownFormatter.omitted(&mut self.notes, selection, config)
+    }
+
+    fn limited(
+        &mut self,
+        _writer: &mut dyn Write,
+        limit: usize,
+        left_out: usize,
+        config: &Config,
+    ) -> Result<()> {
+        MarkdownFormatter.limited(&mut self.notes, limit, left_out, config)
+    }
+
+    fn digest(&mut self, _writer: &mut dyn Write, digest: &str, config: &Config) -> Result<()> {
+        MarkdownFormatter.digest(&mut self.notes, digest, config)
+    }
+
+    fn question(&mut self, _writer: &mut dyn Write, question: &str, config: &Config) -> Result<()> {
+        MarkdownFormatter.question(&mut self.notes, question, config)
+    }
+
+    /// Writes `index.md`: the preamble, a link to each file written, the lists of files left
+    /// out and the question, and the postamble.
+    fn finish(&mut self, _writer: &mut dyn Write, _summary: &RunSummary) -> Result<()> {
+        let path = self.create(Path::new(INDEX_FILE_NAME))?;
+        let mut index = Vec::new();
+        writeln!(index, "# Index\n")?;
+        if let Some(preamble) = &self.preamble {
+            write_framing_text(&mut index, preamble)?;
+            writeln!(index)?;
+        }
+        for (display_path, relative) in &self.written {
+            let link = relative.to_string_lossy().replace('\\', "/");
+            writeln!(index, "- [{}](<{}>)", display_path.display(), link)?;
+        }
+        writeln!(index)?;
+        index.extend_from_slice(&self.notes);
+        if let Some(postamble) = &self.postamble {
+            write_framing_text(&mut index, postamble)?;
+        }
+        fs::write(&path, index)
+            .with_context(|| format!("Failed to write index: {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_paths_stay_inside_the_directory() {
+        assert_eq!(
+            output_path(Path::new("src/lib.rs"), "md").unwrap(),
+            Path::new("src/lib.rs.md")
+        );
+        assert_eq!(
+            output_path(Path::new("./a/./b c.txt"), "txt").unwrap(),
+            Path::new("a/b c.txt.txt")
+        );
+        for path in ["../secret.rs", "src/../../x.rs", "/etc/passwd", ".", ""] {
+            assert!(output_path(Path::new(path), "md").is_err(), "{}", path);
+        }
+    }
+}
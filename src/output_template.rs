@@ -0,0 +1,649 @@
+//! Output file names built from a template such as `{dir}_{date}_{hash}.{ext}`, or from a
+//! suffix template such as `_{branch}-{label}` added before the extension.
+
+use crate::git::GitRepo;
+use crate::reproducible;
+use crate::{Config, is_archive};
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Utc};
+use globset::{GlobBuilder, GlobMatcher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+/// The placeholders an output template can use.
+const PLACEHOLDERS: &[&str] = &[
+    "name", "ext", "dir", "date", "hash", "branch", "label", "host", "n",
+];
+
+/// Characters a date must not produce when it is part of a file name.
+#[cfg(windows)]
+const INVALID_FILE_NAME_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+#[cfg(not(windows))]
+const INVALID_FILE_NAME_CHARS: &[char] = &['/'];
+
+/// Characters replaced by `-` in placeholder values, as no file system allows them or some
+/// do not: the path separators and the characters Windows reserves.
+const UNPORTABLE_FILE_NAME_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// The most files `{n}` counts through before giving up.
+const MAX_COUNTER: usize = 10_000;
+
+/// A piece of a parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(&'static str),
+}
+
+/// Splits a template into literal text and placeholders.
+///
+/// # Arguments
+/// * `template` - The template, e.g. `{dir}_{date}.{ext}`.
+///
+/// # Returns
+/// * `Result<Vec<Part>>` - The parts, or an error naming an unknown or unclosed placeholder.
+fn parse(template: &str) -> Result<Vec<Part>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            parts.push(Part::Literal(rest[..start].to_string()));
+        }
+        let Some(len) = rest[start..].find('}') else {
+            bail!("Unclosed `{{` in the template `{}`", template);
+        };
+        let name = &rest[start + 1..start + len];
+        let Some(placeholder) = PLACEHOLDERS.iter().find(|p| **p == name) else {
+            bail!(
+                "Unknown placeholder `{{{}}}` in the template `{}` (expected one of {})",
+                name,
+                template,
+                PLACEHOLDERS
+                    .iter()
+                    .map(|p| format!("{{{}}}", p))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        };
+        parts.push(Part::Placeholder(placeholder));
+        rest = &rest[start + len + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Literal(rest.to_string()));
+    }
+    Ok(parts)
+}
+
+/// Checks the output template, name suffix, and date format of a configuration before a run.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<()>` - An error if a template has an unknown placeholder, the name suffix is
+///   combined with a template or contains a path separator, or the date format is invalid or
+///   produces characters that cannot appear in the output file name.
+pub(crate) fn validate(config: &Config) -> Result<()> {
+    if let Some(template) = &config.output_template {
+        parse(template)?;
+    }
+    if let Some(suffix) = &config.name_suffix {
+        if config.output_template.is_some() {
+            bail!("--name-suffix cannot be combined with --output-template");
+        }
+        if suffix.contains(['/', '\\']) {
+            bail!(
+                "The name suffix `{}` cannot contain a path separator",
+                suffix
+            );
+        }
+        parse(suffix)?;
+    }
+    if StrftimeItems::new(&config.date_format).any(|item| item == Item::Error) {
+        bail!("Invalid date format `{}`", config.date_format);
+    }
+    let date_in_file_name = match &config.output_template {
+        Some(template) => template.contains("{date}"),
+        None => {
+            config.output.is_some()
+                && (config.append_date
+                    || config
+                        .name_suffix
+                        .as_deref()
+                        .is_some_and(|suffix| suffix.contains("{date}")))
+        }
+    };
+    if date_in_file_name
+        && let Some(invalid) = current_date(config)
+            .chars()
+            .find(|c| INVALID_FILE_NAME_CHARS.contains(c))
+    {
+        bail!(
+            "The date format `{}` produces `{}`, which cannot appear in a file name",
+            config.date_format,
+            invalid
+        );
+    }
+    Ok(())
+}
+
+/// Formats the date of the run with `date_format`, in UTC if `utc` or `reproducible` is set.
+fn current_date(config: &Config) -> String {
+    let time = reproducible::run_time(config).unwrap_or(SystemTime::UNIX_EPOCH);
+    if reproducible::utc(config) {
+        DateTime::<Utc>::from(time)
+            .format(&config.date_format)
+            .to_string()
+    } else {
+        DateTime::<Local>::from(time)
+            .format(&config.date_format)
+            .to_string()
+    }
+}
+
+/// Replaces the characters some file systems do not allow in names, and control
+/// characters, with `-`.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_control() || UNPORTABLE_FILE_NAME_CHARS.contains(&c) {
+                '-'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Returns the name of this machine, or `None` if it cannot be found.
+fn host_name() -> Option<String> {
+    #[cfg(unix)]
+    {
+        let mut buffer = [0u8; 256];
+        // SAFETY: the buffer is valid for its whole length, and one byte is kept back so the
+        // name is NUL-terminated even if it was cut short.
+        let status = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len() - 1) };
+        if status == 0 {
+            let len = buffer.iter().position(|&byte| byte == 0).unwrap_or(0);
+            let name = String::from_utf8_lossy(&buffer[..len]).into_owned();
+            if !name.is_empty() {
+                return Some(name);
+            }
+        }
+    }
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .ok()
+        .filter(|name| !name.is_empty())
+}
+
+/// The values of the placeholders, resolved once per run.
+struct Values {
+    name: String,
+    ext: String,
+    dir: String,
+    date: String,
+    hash: Option<String>,
+    branch: Option<String>,
+    label: Option<String>,
+    host: Option<String>,
+}
+
+impl Values {
+    /// Resolves the placeholders that do not need git; every value is made safe to use in a
+    /// file name.
+    fn new(
+        output: Option<&Path>,
+        config: &Config,
+        hash: Option<String>,
+        branch: Option<String>,
+        host: Option<String>,
+    ) -> Values {
+        Values {
+            name: output
+                .and_then(Path::file_stem)
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| input_name(config)),
+            ext: match output {
+                Some(output) => output
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                None => config.format.extension().to_string(),
+            },
+            dir: sanitize(&input_name(config)),
+            date: current_date(config),
+            hash: hash.as_deref().map(sanitize),
+            branch: branch.as_deref().map(sanitize),
+            label: config.label.as_deref().map(sanitize),
+            host: host.as_deref().map(sanitize),
+        }
+    }
+
+    fn get(&self, placeholder: &str, n: usize) -> String {
+        match placeholder {
+            "name" => self.name.clone(),
+            "ext" => self.ext.clone(),
+            "dir" => self.dir.clone(),
+            "date" => self.date.clone(),
+            "hash" => self.hash.clone().unwrap_or_default(),
+            "branch" => self.branch.clone().unwrap_or_default(),
+            "label" => self.label.clone().unwrap_or_default(),
+            "host" => self.host.clone().unwrap_or_default(),
+            "n" => n.to_string(),
+            _ => unreachable!("placeholders are checked when parsing"),
+        }
+    }
+
+    /// Returns the first placeholder of `parts` with no value, other than the git ones,
+    /// which are checked as the repository is read.
+    fn unresolved(&self, parts: &[Part]) -> Option<&'static str> {
+        parts.iter().find_map(|part| match part {
+            Part::Placeholder("label") if self.label.is_none() => Some("label"),
+            Part::Placeholder("host") if self.host.is_none() => Some("host"),
+            _ => None,
+        })
+    }
+}
+
+/// Renders parsed template parts with the given counter value.
+fn render(parts: &[Part], values: &Values, n: usize) -> String {
+    parts
+        .iter()
+        .map(|part| match part {
+            Part::Literal(text) => text.clone(),
+            Part::Placeholder(placeholder) => values.get(placeholder, n),
+        })
+        .collect()
+}
+
+/// Builds the template of the `append_date`, `append_git_hash`, and `name_suffix` suffixes:
+/// `{name}`, then `_{date}`, `_{hash}`, and the name suffix, then `.{ext}`.
+///
+/// The hash of `append_git_hash` is left out when it is unavailable, e.g. outside a git
+/// repository or before the first commit.
+fn suffix_template(config: &Config, values: &Values) -> String {
+    let mut template = "{name}".to_string();
+    if config.append_date {
+        template.push_str("_{date}");
+        info!("Appending date to filename.");
+    }
+    if config.append_git_hash && values.hash.is_some() {
+        template.push_str("_{hash}");
+        info!("Appending git hash to filename.");
+    }
+    if let Some(suffix) = &config.name_suffix {
+        template.push_str(suffix);
+    }
+    if !values.ext.is_empty() {
+        template.push_str(".{ext}");
+    }
+    template
+}
+
+/// Returns the base name of the first input, without an archive extension.
+fn input_name(config: &Config) -> String {
+    let input = config
+        .directories
+        .first()
+        .map(PathBuf::as_path)
+        .unwrap_or(Path::new("."));
+    let resolved = input.canonicalize().unwrap_or_else(|_| input.to_path_buf());
+    let name = resolved
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if is_archive(input) {
+        let lower = name.to_ascii_lowercase();
+        for suffix in [".tar.gz", ".tgz", ".tar", ".zip"] {
+            if lower.ends_with(suffix) {
+                return name[..name.len() - suffix.len()].to_string();
+            }
+        }
+    }
+    name
+}
+
+/// Computes the output path from `output_template`, or from the `append_date`,
+/// `append_git_hash`, and `name_suffix` suffixes when no template is set.
+///
+/// A template names a file next to `output`, or in the current directory without one. With
+/// `{n}`, the smallest counter from 1 that names a file that does not exist yet is used.
+///
+/// # Arguments
+/// * `output` - The output path, if any.
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<Option<PathBuf>>` - The output path, or an error if a placeholder written in
+///   the template or name suffix has no value, e.g. `{branch}` outside a git repository,
+///   unless `lenient_suffix` is set.
+pub(crate) fn output_path(output: Option<&Path>, config: &Config) -> Result<Option<PathBuf>> {
+    // The placeholders the user wrote, which must resolve unless `lenient_suffix` is set.
+    let (written, what) = match (&config.output_template, output) {
+        (Some(template), _) => (parse(template)?, "The output template"),
+        (None, Some(_)) if has_suffix(config) => (
+            parse(config.name_suffix.as_deref().unwrap_or_default())?,
+            "The name suffix",
+        ),
+        (None, _) => return Ok(output.map(Path::to_path_buf)),
+    };
+    let uses_written = |name: &str| {
+        written
+            .iter()
+            .any(|part| matches!(part, Part::Placeholder(p) if *p == name))
+    };
+    let uses = |name: &str| uses_written(name) || (name == "hash" && config.append_git_hash);
+    // Reports a placeholder without a value, or leaves it empty with `lenient_suffix`.
+    let unresolved = |err: anyhow::Error| -> Result<()> {
+        if config.lenient_suffix {
+            warn!("{:#}; leaving it empty (--lenient-suffix)", err);
+            Ok(())
+        } else {
+            Err(err)
+        }
+    };
+    let directory = config
+        .directories
+        .first()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_else(|| ".".to_string());
+
+    // The repository is discovered once and shared by the git placeholders.
+    let repo = if uses("hash") || uses("branch") {
+        match GitRepo::discover(&config.directories) {
+            Ok(repo) => repo,
+            Err(err) if uses_written("hash") || uses_written("branch") => {
+                unresolved(err)?;
+                None
+            }
+            Err(err) => return Err(err),
+        }
+    } else {
+        None
+    };
+    if repo.is_none() && (uses_written("hash") || uses_written("branch")) {
+        unresolved(anyhow!(
+            "{} uses git placeholders, but {} is not in a git repository",
+            what,
+            directory
+        ))?;
+    }
+    let hash = match &repo {
+        Some(repo) if uses("hash") => match repo.short_hash(config.dirty_suffix) {
+            Ok(hash) => Some(hash),
+            Err(err) if uses_written("hash") => {
+                unresolved(err.context(format!(
+                    "{} uses {{hash}}, but the hash of {} is unavailable",
+                    what, directory
+                )))?;
+                None
+            }
+            Err(err) => {
+                warn!("Cannot append the git hash: {:#}", err);
+                None
+            }
+        },
+        _ => None,
+    };
+    let branch = match &repo {
+        Some(repo) if uses("branch") => repo.branch()?,
+        _ => None,
+    };
+    let host = uses("host").then(host_name).flatten();
+    let values = Values::new(output, config, hash, branch, host);
+    if let Some(placeholder) = values.unresolved(&written) {
+        let reason = match placeholder {
+            "label" => "no --label is set",
+            _ => "the name of this machine is unknown",
+        };
+        unresolved(anyhow!("{} uses {{{}}}, but {}", what, placeholder, reason))?;
+    }
+    let parts = match &config.output_template {
+        Some(_) => written,
+        None => parse(&suffix_template(config, &values))?,
+    };
+
+    let parent = output.and_then(Path::parent).unwrap_or(Path::new(""));
+    if !parts.contains(&Part::Placeholder("n")) {
+        return Ok(Some(parent.join(render(&parts, &values, 1))));
+    }
+    (1..=MAX_COUNTER)
+        .map(|n| parent.join(render(&parts, &values, n)))
+        .find(|path| !path.exists())
+        .map(Some)
+        .with_context(|| {
+            format!(
+                "Every output file name up to {{n}} = {} is taken",
+                MAX_COUNTER
+            )
+        })
+}
+
+/// Checks whether the output file name gets a suffix from `append_date`,
+/// `append_git_hash`, or `name_suffix`.
+fn has_suffix(config: &Config) -> bool {
+    config.append_date || config.append_git_hash || config.name_suffix.is_some()
+}
+
+/// The placeholders whose value changes from one run to the next.
+const PER_RUN_PLACEHOLDERS: &[&str] = &["date", "hash", "branch", "label", "n"];
+
+/// Builds a glob matching the names earlier runs gave their output, with each placeholder
+/// that changes between runs (`{date}`, `{hash}`, `{branch}`, `{label}`, `{n}`) as a
+/// wildcard.
+///
+/// Without a template, the `append_date` and `append_git_hash` suffixes become one
+/// wildcard, as the hash is left out of names where it was unavailable.
+///
+/// # Arguments
+/// * `output` - The output path, if any.
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<Option<(PathBuf, GlobMatcher)>>` - The directory the outputs are written to and
+///   the glob their file names match, or `None` if every run writes the same file.
+pub(crate) fn previous_outputs(
+    output: Option<&Path>,
+    config: &Config,
+) -> Result<Option<(PathBuf, GlobMatcher)>> {
+    let parts = match (&config.output_template, output) {
+        (Some(template), _) => parse(template)?,
+        (None, Some(_)) if has_suffix(config) => {
+            let mut parts = parse("{name}")?;
+            if config.append_date || config.append_git_hash {
+                parts.extend(parse("_{date}")?);
+            }
+            if let Some(suffix) = &config.name_suffix {
+                parts.extend(parse(suffix)?);
+            }
+            if output.is_some_and(|output| output.extension().is_some()) {
+                parts.extend(parse(".{ext}")?);
+            }
+            parts
+        }
+        (None, _) => return Ok(None),
+    };
+    let per_run = |part: &Part| matches!(part, Part::Placeholder(placeholder) if PER_RUN_PLACEHOLDERS.contains(placeholder));
+    if !parts.iter().any(per_run) {
+        return Ok(None);
+    }
+    let host = parts
+        .contains(&Part::Placeholder("host"))
+        .then(host_name)
+        .flatten();
+    let values = Values::new(output, config, None, None, host);
+    let mut glob = String::new();
+    for part in &parts {
+        match part {
+            _ if per_run(part) => {
+                if !glob.ends_with('*') {
+                    glob.push('*');
+                }
+            }
+            Part::Literal(text) => glob.push_str(&globset::escape(text)),
+            Part::Placeholder(placeholder) => {
+                glob.push_str(&globset::escape(&values.get(placeholder, 1)))
+            }
+        }
+    }
+    let matcher = GlobBuilder::new(&glob)
+        .literal_separator(true)
+        .build()
+        .with_context(|| format!("Invalid output name pattern `{}`", glob))?
+        .compile_matcher();
+    let parent = output.and_then(Path::parent).unwrap_or(Path::new(""));
+    Ok(Some((parent.to_path_buf(), matcher)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Values {
+        Values {
+            name: "bundle".to_string(),
+            ext: "md".to_string(),
+            dir: "repo".to_string(),
+            date: "20250102".to_string(),
+            hash: Some("abc1234".to_string()),
+            branch: None,
+            label: Some("review".to_string()),
+            host: None,
+        }
+    }
+
+    #[test]
+    fn templates_render_placeholders() {
+        let parts = parse("{dir}_{date}_{hash}.{ext}").unwrap();
+        assert_eq!(render(&parts, &values(), 1), "repo_20250102_abc1234.md");
+        let parts = parse("{name}-{n}").unwrap();
+        assert_eq!(render(&parts, &values(), 3), "bundle-3");
+        assert_eq!(parse("").unwrap(), []);
+    }
+
+    #[test]
+    fn suffixes_come_before_the_extension() {
+        let mut config = Config {
+            name_suffix: Some("-{branch}-{label}".to_string()),
+            ..Config::default()
+        };
+        let mut values = values();
+        values.branch = Some("main".to_string());
+        let parts = parse(&suffix_template(&config, &values)).unwrap();
+        assert_eq!(render(&parts, &values, 1), "bundle-main-review.md");
+        config.append_date = true;
+        config.append_git_hash = true;
+        let parts = parse(&suffix_template(&config, &values)).unwrap();
+        assert_eq!(
+            render(&parts, &values, 1),
+            "bundle_20250102_abc1234-main-review.md"
+        );
+        values.hash = None;
+        values.ext.clear();
+        config.name_suffix = None;
+        let parts = parse(&suffix_template(&config, &values)).unwrap();
+        assert_eq!(render(&parts, &values, 1), "bundle_20250102");
+    }
+
+    #[test]
+    fn unresolved_placeholders_are_reported() {
+        let mut values = values();
+        assert_eq!(
+            values.unresolved(&parse("{label}-{host}").unwrap()),
+            Some("host")
+        );
+        values.label = None;
+        assert_eq!(
+            values.unresolved(&parse("-{label}").unwrap()),
+            Some("label")
+        );
+        assert_eq!(values.unresolved(&parse("-{branch}").unwrap()), None);
+        // Unresolved values render empty, for `lenient_suffix`.
+        assert_eq!(render(&parse("a{label}b").unwrap(), &values, 1), "ab");
+    }
+
+    #[test]
+    fn values_are_sanitized_for_file_names() {
+        assert_eq!(sanitize("feature/login"), "feature-login");
+        assert_eq!(sanitize("a:b*c?\"<d>|e\tf"), "a-b-c---d--e-f");
+        assert_eq!(sanitize("review 2"), "review 2");
+    }
+
+    #[test]
+    fn name_suffixes_are_validated() {
+        let mut config = Config {
+            name_suffix: Some("-{label}".to_string()),
+            ..Config::default()
+        };
+        assert!(validate(&config).is_ok());
+        config.name_suffix = Some("/{label}".to_string());
+        assert!(validate(&config).is_err());
+        config.name_suffix = Some("-{user}".to_string());
+        let err = validate(&config).unwrap_err().to_string();
+        assert!(err.contains("Unknown placeholder `{user}`"), "{}", err);
+        config.name_suffix = Some("-{label}".to_string());
+        config.output_template = Some("{name}.{ext}".to_string());
+        assert!(validate(&config).is_err());
+    }
+
+    #[test]
+    fn unknown_placeholders_are_rejected() {
+        let err = parse("{dir}_{user}.md").unwrap_err().to_string();
+        assert!(err.contains("Unknown placeholder `{user}`"), "{}", err);
+        assert!(parse("{dir").is_err());
+        assert!(parse("{}").is_err());
+    }
+
+    #[test]
+    fn previous_outputs_match_per_run_placeholders() {
+        let matcher = |template: Option<&str>, append_date: bool| {
+            let config = Config {
+                output_template: template.map(str::to_string),
+                append_date,
+                ..Config::default()
+            };
+            previous_outputs(Some(Path::new("out/bundle.md")), &config)
+                .unwrap()
+                .map(|(dir, matcher)| {
+                    assert_eq!(dir, Path::new("out"));
+                    matcher
+                })
+        };
+        let legacy = matcher(None, true).unwrap();
+        assert!(legacy.is_match("bundle_20250102.md"));
+        assert!(legacy.is_match("bundle_20250102_abc1234.md"));
+        assert!(!legacy.is_match("bundle.md.gz"));
+        let template = matcher(Some("[{name}]-{n}-{hash}.{ext}"), false).unwrap();
+        assert!(template.is_match("[bundle]-3-abc1234.md"));
+        assert!(!template.is_match("[bundle]-3-abc1234.txt"));
+        assert!(matcher(Some("{name}.{ext}"), false).is_none());
+        assert!(matcher(None, false).is_none());
+    }
+
+    #[test]
+    fn invalid_date_formats_are_rejected() {
+        let mut config = Config::default();
+        assert!(validate(&config).is_ok());
+        config.date_format = "%Y-%Q".to_string();
+        assert!(validate(&config).is_err());
+    }
+
+    #[test]
+    fn dates_must_fit_in_file_names() {
+        let mut config = Config {
+            date_format: "%Y/%m/%d".to_string(),
+            ..Config::default()
+        };
+        // Only a date that ends up in the file name is checked.
+        assert!(validate(&config).is_ok());
+        config.output_template = Some("{dir}_{date}.md".to_string());
+        let err = validate(&config).unwrap_err().to_string();
+        assert!(err.contains("cannot appear in a file name"), "{}", err);
+        config.date_format = "%Y%m%dT%H%MZ".to_string();
+        assert!(validate(&config).is_ok());
+    }
+}
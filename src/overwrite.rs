@@ -0,0 +1,149 @@
+//! Protection for the files a run writes (`force`, `backup`): an existing file is never
+//! silently truncated.
+
+use crate::Config;
+use anyhow::{Context, Result, bail};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a run does with an output file that already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Overwrite {
+    /// The run fails, naming the file.
+    Refuse,
+    /// The file is overwritten (`force`).
+    Force,
+    /// The file is renamed to `<name>.bak`, or `<name>.bak.N` if that is taken (`backup`).
+    Backup,
+}
+
+impl Overwrite {
+    /// Returns the policy of a run.
+    pub(crate) fn of(config: &Config) -> Self {
+        if config.backup {
+            Overwrite::Backup
+        } else if config.force {
+            Overwrite::Force
+        } else {
+            Overwrite::Refuse
+        }
+    }
+
+    /// Checks that a file may be written, without touching it.
+    ///
+    /// Only regular files are protected: writing to a device such as `/dev/null` or
+    /// `/dev/stdout`, or to a named pipe, replaces nothing.
+    ///
+    /// # Arguments
+    /// * `path` - The file to write, after every suffix is added.
+    ///
+    /// # Returns
+    /// * `Result<()>` - `Ok(())` if no regular file exists at the path or the policy allows
+    ///   replacing it, or an error naming the file otherwise.
+    pub(crate) fn check(self, path: &Path) -> Result<()> {
+        if self == Overwrite::Refuse && is_regular_file(path) {
+            bail!(
+                "{} already exists; pass --force to overwrite or --backup to keep a copy",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Makes way for a file about to be written: checks it, and renames an existing file
+    /// out of the way with [`Overwrite::Backup`].
+    ///
+    /// # Arguments
+    /// * `path` - The file to write, after every suffix is added.
+    ///
+    /// # Returns
+    /// * `Result<Option<PathBuf>>` - The backup made, if any, or an error if the file may not
+    ///   be replaced or cannot be renamed.
+    pub(crate) fn prepare(self, path: &Path) -> Result<Option<PathBuf>> {
+        self.check(path)?;
+        if self != Overwrite::Backup || !is_regular_file(path) {
+            return Ok(None);
+        }
+        let backup = backup_path(path);
+        fs::rename(path, &backup).with_context(|| {
+            format!(
+                "Failed to back up {} to {}",
+                path.display(),
+                backup.display()
+            )
+        })?;
+        Ok(Some(backup))
+    }
+}
+
+/// Returns whether a regular file, rather than nothing, a device, or a pipe, is at a path.
+fn is_regular_file(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_file())
+}
+
+/// Finds the first free backup name for a file: `out.md.bak`, then `out.md.bak.1`,
+/// `out.md.bak.2`, and so on.
+fn backup_path(path: &Path) -> PathBuf {
+    let with_suffix = |suffix: String| {
+        let mut name = OsString::from(path.as_os_str());
+        name.push(suffix);
+        PathBuf::from(name)
+    };
+    (0..)
+        .map(|n| match n {
+            0 => with_suffix(".bak".to_string()),
+            n => with_suffix(format!(".bak.{}", n)),
+        })
+        .find(|backup| fs::symlink_metadata(backup).is_err())
+        .expect("some backup name is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backups_never_replace_each_other() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("out.md");
+        for round in 0..3 {
+            fs::write(&path, format!("round {}", round)).unwrap();
+            Overwrite::Backup.prepare(&path).unwrap();
+            assert!(!path.exists());
+        }
+        let read = |name: &str| fs::read_to_string(temp_dir.path().join(name)).unwrap();
+        assert_eq!(read("out.md.bak"), "round 0");
+        assert_eq!(read("out.md.bak.1"), "round 1");
+        assert_eq!(read("out.md.bak.2"), "round 2");
+    }
+
+    #[test]
+    fn existing_files_are_refused_unless_forced() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("out.md");
+        assert!(Overwrite::Refuse.check(&path).is_ok());
+        fs::write(&path, "hand-edited").unwrap();
+        let err = Overwrite::Refuse.prepare(&path).unwrap_err();
+        assert!(err.to_string().contains("out.md already exists"));
+        assert_eq!(Overwrite::Force.prepare(&path).unwrap(), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hand-edited");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn devices_and_pipes_are_written_as_they_are() {
+        let null = Path::new("/dev/null");
+        assert!(Overwrite::Refuse.check(null).is_ok());
+        assert_eq!(Overwrite::Backup.prepare(null).unwrap(), None);
+        assert!(null.exists());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let fifo = temp_dir.path().join("pipe");
+        let name = std::ffi::CString::new(fifo.as_os_str().as_encoded_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(name.as_ptr(), 0o600) }, 0);
+        assert!(Overwrite::Refuse.check(&fifo).is_ok());
+        assert_eq!(Overwrite::Backup.prepare(&fifo).unwrap(), None);
+        assert!(!temp_dir.path().join("pipe.bak").exists());
+    }
+}
@@ -0,0 +1,141 @@
+//! Showing console output in a pager, as git does, when it goes to a terminal.
+
+use std::env;
+use std::io::{self, BufWriter, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use tracing::{debug, warn};
+
+/// The pager run when `PAGER` is not set; `-R` lets color escapes through.
+const DEFAULT_PAGER: &str = "less -R";
+
+/// A writer feeding a pager process; dropping it waits for the user to quit the pager.
+///
+/// Once the pager has quit, e.g. because the user pressed `q` before the end, the rest of
+/// the output is discarded instead of failing the run.
+pub(crate) struct Pager {
+    command: String,
+    child: Child,
+    stdin: Option<BufWriter<ChildStdin>>,
+}
+
+impl Pager {
+    /// Starts the pager named by `PAGER`, or `less -R`.
+    ///
+    /// # Returns
+    /// * `Option<Pager>` - The pager, or `None` if `PAGER` is empty or `cat`, or the pager
+    ///   cannot be started, in which case output goes straight to stdout.
+    pub(crate) fn spawn() -> Option<Pager> {
+        match env::var("PAGER") {
+            Ok(command) => Pager::spawn_command(&command, true),
+            Err(_) => Pager::spawn_command(DEFAULT_PAGER, false),
+        }
+    }
+
+    /// Starts a pager command, split on whitespace.
+    ///
+    /// # Arguments
+    /// * `command` - The program and its arguments.
+    /// * `explicit` - Whether the user chose the command, so a failure to start it is worth
+    ///   a warning.
+    ///
+    /// # Returns
+    /// * `Option<Pager>` - The pager, or `None` if there is none to start.
+    fn spawn_command(command: &str, explicit: bool) -> Option<Pager> {
+        let mut words = command.split_whitespace();
+        let program = words.next().filter(|&program| program != "cat")?;
+        let mut process = Command::new(program);
+        process.args(words).stdin(Stdio::piped());
+        // As git does: quit if the output fits on one screen, pass colors through, and
+        // leave the output on the screen afterwards.
+        if env::var_os("LESS").is_none() {
+            process.env("LESS", "FRX");
+        }
+        match process.spawn() {
+            Ok(mut child) => {
+                debug!("Output will be shown in the pager: {}", command);
+                let stdin = child.stdin.take().map(BufWriter::new);
+                Some(Pager {
+                    command: command.to_string(),
+                    child,
+                    stdin,
+                })
+            }
+            Err(err) => {
+                if explicit {
+                    warn!("Failed to run the pager `{}`: {}", command, err);
+                } else {
+                    debug!("Failed to run the pager `{}`: {}", command, err);
+                }
+                None
+            }
+        }
+    }
+
+    /// Runs a write to the pager, discarding the output once the pager has quit.
+    fn forward(
+        &mut self,
+        op: impl FnOnce(&mut BufWriter<ChildStdin>) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let Some(stdin) = &mut self.stdin else {
+            return Ok(());
+        };
+        match op(stdin) {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {
+                debug!("The pager quit before the end of the output");
+                // The buffered bytes cannot be written either; drop them without a flush.
+                if let Some(stdin) = self.stdin.take() {
+                    drop(stdin.into_parts());
+                }
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.forward(|stdin| stdin.write_all(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.forward(|stdin| stdin.flush())
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        let _ = self.forward(|stdin| stdin.flush());
+        // Closing stdin lets the pager see the end of the output.
+        self.stdin = None;
+        // The run succeeded or failed on its own; how the pager exits does not change that.
+        if let Err(err) = self.child.wait() {
+            debug!("Failed to wait for the pager `{}`: {}", self.command, err);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_after_the_pager_quits_is_discarded() {
+        let mut pager = Pager::spawn_command("true", true).unwrap();
+        pager.child.wait().unwrap();
+        for _ in 0..64 {
+            pager.write_all(&[b'x'; 16 * 1024]).unwrap();
+        }
+        pager.flush().unwrap();
+        assert!(pager.stdin.is_none());
+    }
+
+    #[test]
+    fn a_missing_or_disabled_pager_is_skipped() {
+        assert!(Pager::spawn_command("c2p-no-such-pager -R", false).is_none());
+        assert!(Pager::spawn_command("cat", true).is_none());
+        assert!(Pager::spawn_command("  ", true).is_none());
+    }
+}
@@ -0,0 +1,160 @@
+//! A small ordered work pool for processing files on several threads.
+
+use std::collections::BTreeMap;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, mpsc};
+use std::thread;
+
+/// How many results each worker may run ahead of the consumer.
+const WINDOW_PER_JOB: usize = 4;
+
+/// Returns the number of worker threads to use for a `jobs` setting.
+///
+/// # Arguments
+/// * `jobs` - The requested number of threads; `None` or `0` means one per CPU.
+///
+/// # Returns
+/// * `usize` - The number of threads, at least one.
+pub(crate) fn job_count(jobs: Option<usize>) -> usize {
+    match jobs {
+        Some(jobs) if jobs > 0 => jobs,
+        _ => thread::available_parallelism().map_or(1, |n| n.get()),
+    }
+}
+
+/// Applies `map` to every item on up to `jobs` threads and hands the results to `consume`
+/// on the calling thread, in the original item order.
+///
+/// At most `jobs * 4` results are in flight at once, so memory stays bounded no matter how
+/// many items there are. With a single job, items are processed inline.
+///
+/// # Arguments
+/// * `items` - The items to process.
+/// * `jobs` - The number of worker threads.
+/// * `map` - The work done on the worker threads.
+/// * `consume` - Called on the calling thread with each item and its result, in order;
+///   returning `ControlFlow::Break` stops the processing of the remaining items.
+pub(crate) fn for_each_ordered<T, R>(
+    items: &[T],
+    jobs: usize,
+    map: impl Fn(&T) -> R + Sync,
+    mut consume: impl FnMut(&T, R) -> ControlFlow<()>,
+) where
+    T: Sync,
+    R: Send,
+{
+    if jobs <= 1 || items.len() <= 1 {
+        for item in items {
+            if consume(item, map(item)).is_break() {
+                return;
+            }
+        }
+        return;
+    }
+
+    let window = jobs * WINDOW_PER_JOB;
+    let next = AtomicUsize::new(0);
+    let written = Mutex::new(0);
+    let advanced = Condvar::new();
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(items.len()) {
+            let sender = sender.clone();
+            let (next, written, advanced, map) = (&next, &written, &advanced, &map);
+            scope.spawn(move || {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= items.len() {
+                        break;
+                    }
+                    let mut position = written.lock().unwrap();
+                    while index >= *position + window {
+                        position = advanced.wait(position).unwrap();
+                    }
+                    drop(position);
+                    if sender.send((index, map(&items[index]))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut position = 0;
+        'receive: for (index, result) in &receiver {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&position) {
+                let flow = consume(&items[position], result);
+                position += 1;
+                *written.lock().unwrap() = position;
+                advanced.notify_all();
+                if flow.is_break() {
+                    break 'receive;
+                }
+            }
+        }
+
+        // Make workers stop at their next send, and release any worker still waiting.
+        drop(receiver);
+        *written.lock().unwrap() = items.len();
+        advanced.notify_all();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preserves_item_order() {
+        let items: Vec<u64> = (0..200).collect();
+        let mut results = Vec::new();
+        for_each_ordered(
+            &items,
+            8,
+            |&n| {
+                // Make later items finish first now and then.
+                thread::sleep(std::time::Duration::from_micros((200 - n) % 7 * 50));
+                n * 2
+            },
+            |&n, doubled| {
+                results.push((n, doubled));
+                ControlFlow::Continue(())
+            },
+        );
+        let expected: Vec<(u64, u64)> = items.iter().map(|&n| (n, n * 2)).collect();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn break_stops_consuming() {
+        let items: Vec<u64> = (0..200).collect();
+        for jobs in [1, 4] {
+            let mut consumed = Vec::new();
+            for_each_ordered(
+                &items,
+                jobs,
+                |&n| n,
+                |&n, _| {
+                    consumed.push(n);
+                    if n == 10 {
+                        ControlFlow::Break(())
+                    } else {
+                        ControlFlow::Continue(())
+                    }
+                },
+            );
+            assert_eq!(consumed, (0..=10).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn zero_jobs_means_one_per_cpu() {
+        assert!(job_count(None) >= 1);
+        assert_eq!(job_count(Some(0)), job_count(None));
+        assert_eq!(job_count(Some(3)), 3);
+    }
+}
@@ -0,0 +1,196 @@
+//! Exact-path overrides: `include_paths` and `exclude_paths`, the rules that take precedence
+//! over every other filter.
+
+use crate::source::{FileSource, SourceFile};
+use crate::{Config, SkipReason};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
+
+/// What the exact-path overrides say about a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PathRule {
+    /// Listed in `exclude_paths`: left out, whatever the other filters say.
+    Exclude,
+    /// Listed in `include_paths` and not excluded: bundled unless it is binary.
+    Include,
+    /// Not listed: the other filters decide.
+    Filters,
+}
+
+/// The exact relative paths to include and exclude.
+#[derive(Debug, Default)]
+pub(crate) struct PathOverrides {
+    include: Vec<PathBuf>,
+    exclude: HashSet<PathBuf>,
+}
+
+/// Normalizes a relative path for comparison, dropping `.` components so `./src/a.rs` and
+/// `src/a.rs` are the same path.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+impl PathOverrides {
+    /// Collects the overrides of a run.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `PathOverrides` - The normalized `include_paths` and `exclude_paths`.
+    pub(crate) fn new(config: &Config) -> PathOverrides {
+        let mut include: Vec<PathBuf> = Vec::new();
+        for path in &config.include_paths {
+            let path = normalize(path);
+            if !include.contains(&path) {
+                include.push(path);
+            }
+        }
+        PathOverrides {
+            include,
+            exclude: config
+                .exclude_paths
+                .iter()
+                .map(|path| normalize(path))
+                .collect(),
+        }
+    }
+
+    /// Decides a file by its relative path; `exclude_paths` wins over `include_paths`.
+    ///
+    /// # Arguments
+    /// * `relative_path` - The path of the file relative to the root of the source.
+    ///
+    /// # Returns
+    /// * `PathRule` - Whether the file is excluded, forced in, or left to the other filters.
+    pub(crate) fn rule(&self, relative_path: &Path) -> PathRule {
+        let path = normalize(relative_path);
+        if self.exclude.contains(&path) {
+            PathRule::Exclude
+        } else if self.include.contains(&path) {
+            PathRule::Include
+        } else {
+            PathRule::Filters
+        }
+    }
+
+    /// Applies the overrides to the files listed by a source, before the other filters.
+    ///
+    /// Excluded files are dropped. Included files are marked as forced, so later filters
+    /// keep them, and added when the source's own walk filters (ignore files, hidden files,
+    /// excluded directories) left them out. Each path that matches no file is warned about.
+    ///
+    /// # Arguments
+    /// * `entries` - The files listed by the source.
+    /// * `source` - The source, which finds included files it did not list.
+    /// * `config` - The configuration options for the bundling process.
+    /// * `on_skipped` - Called for each excluded file.
+    pub(crate) fn apply(
+        &self,
+        entries: &mut Vec<SourceFile>,
+        source: &dyn FileSource,
+        config: &Config,
+        on_skipped: &mut dyn FnMut(&Path, SkipReason),
+    ) {
+        let mut listed = HashSet::new();
+        entries.retain_mut(|file| {
+            let path = normalize(&file.relative_path);
+            let rule = self.rule(&path);
+            listed.insert(path);
+            match rule {
+                PathRule::Exclude => {
+                    on_skipped(&file.path, SkipReason::Filtered);
+                    return false;
+                }
+                PathRule::Include => file.forced = true,
+                PathRule::Filters => {}
+            }
+            true
+        });
+        for path in &self.include {
+            if listed.contains(path) {
+                continue;
+            }
+            match source.find(config, path) {
+                Some(mut file) if !self.exclude.contains(path) => {
+                    file.forced = true;
+                    entries.push(file);
+                }
+                Some(_) => {}
+                None => warn!("No file matches --include-path {}", path.display()),
+            }
+        }
+        for path in &self.exclude {
+            if !listed.contains(path) && source.find(config, path).is_none() {
+                warn!("No file matches --exclude-path {}", path.display());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemorySource;
+
+    fn overrides(include: &[&str], exclude: &[&str]) -> PathOverrides {
+        let config = Config::builder()
+            .include_paths(include)
+            .exclude_paths(exclude)
+            .build();
+        PathOverrides::new(&config)
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let overrides = overrides(&["docs/ARCH.md", "./src/a.rs"], &["src/a.rs"]);
+        assert_eq!(overrides.rule(Path::new("docs/ARCH.md")), PathRule::Include);
+        assert_eq!(overrides.rule(Path::new("src/a.rs")), PathRule::Exclude);
+        assert_eq!(overrides.rule(Path::new("./src/a.rs")), PathRule::Exclude);
+        assert_eq!(overrides.rule(Path::new("src/b.rs")), PathRule::Filters);
+        // Exact paths, not prefixes or globs.
+        assert_eq!(overrides.rule(Path::new("docs")), PathRule::Filters);
+        assert_eq!(
+            overrides.rule(Path::new("docs/ARCH.md.bak")),
+            PathRule::Filters
+        );
+    }
+
+    #[test]
+    fn apply_drops_excluded_and_adds_unlisted_included_files() {
+        let source: MemorySource = [("src/a.rs", ""), ("src/b.rs", ""), (".hidden/notes.md", "")]
+            .into_iter()
+            .collect();
+        let config = Config::builder()
+            .ignore_hidden(true)
+            .include_paths([".hidden/notes.md", "missing.rs"])
+            .exclude_paths(["src/b.rs"])
+            .build();
+        let mut skipped = Vec::new();
+        let mut entries = source
+            .list(&config, &mut |path, _| skipped.push(path.to_path_buf()))
+            .unwrap();
+        assert_eq!(skipped, [PathBuf::from(".hidden/notes.md")]);
+        skipped.clear();
+
+        PathOverrides::new(&config).apply(&mut entries, &source, &config, &mut |path, reason| {
+            assert_eq!(reason, SkipReason::Filtered);
+            skipped.push(path.to_path_buf());
+        });
+        let kept: Vec<(&Path, bool)> = entries
+            .iter()
+            .map(|file| (file.relative_path.as_path(), file.forced))
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                (Path::new("src/a.rs"), false),
+                (Path::new(".hidden/notes.md"), true)
+            ]
+        );
+        assert_eq!(skipped, [PathBuf::from("src/b.rs")]);
+    }
+}
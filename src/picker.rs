@@ -0,0 +1,576 @@
+//! The `--interactive` file picker: the candidate files as a tree with checkboxes.
+
+use crate::FileEstimate;
+use crate::meta::format_size;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// A key press understood by the picker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Enter,
+    Escape,
+    Backspace,
+    Interrupt,
+    Char(char),
+}
+
+/// How a picking session ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Confirm,
+    Cancel,
+}
+
+/// One line of the tree: a directory or a file, indented by its depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Row {
+    /// A directory, with the indexes of its visible files at any depth.
+    Directory {
+        path: PathBuf,
+        depth: usize,
+        files: Vec<usize>,
+    },
+    /// A file, by index into [`Picker::files`].
+    File { index: usize, depth: usize },
+}
+
+/// The state of the picker, independent of the terminal.
+struct Picker {
+    /// The candidate files, sorted by path.
+    files: Vec<FileEstimate>,
+    /// Whether each file is checked.
+    selected: Vec<bool>,
+    /// The row under the cursor.
+    cursor: usize,
+    /// The filter text; only files whose path contains it are shown.
+    filter: String,
+    /// Whether key presses edit the filter.
+    filtering: bool,
+}
+
+impl Picker {
+    /// Creates a picker with every file checked.
+    fn new(mut files: Vec<FileEstimate>) -> Self {
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Picker {
+            selected: vec![true; files.len()],
+            files,
+            cursor: 0,
+            filter: String::new(),
+            filtering: false,
+        }
+    }
+
+    /// Returns the visible rows: the files matching the filter, under their directories.
+    fn rows(&self) -> Vec<Row> {
+        let filter = self.filter.to_lowercase();
+        let mut rows = Vec::new();
+        // The directories enclosing the current file, with their row indexes.
+        let mut open: Vec<(&Path, usize)> = Vec::new();
+        for (index, file) in self.files.iter().enumerate() {
+            let path = file.path.to_string_lossy().to_lowercase();
+            if !path.contains(&filter) {
+                continue;
+            }
+            let parent = file.path.parent().unwrap_or(Path::new(""));
+            while open.last().is_some_and(|(dir, _)| !parent.starts_with(dir)) {
+                open.pop();
+            }
+            let mut missing: Vec<&Path> = parent
+                .ancestors()
+                .take_while(|dir| !dir.as_os_str().is_empty())
+                .take_while(|dir| open.last().is_none_or(|(open, _)| dir != open))
+                .collect();
+            while let Some(dir) = missing.pop() {
+                rows.push(Row::Directory {
+                    path: dir.to_path_buf(),
+                    depth: open.len(),
+                    files: Vec::new(),
+                });
+                open.push((dir, rows.len() - 1));
+            }
+            for (_, row) in &open {
+                if let Row::Directory { files, .. } = &mut rows[*row] {
+                    files.push(index);
+                }
+            }
+            rows.push(Row::File {
+                index,
+                depth: open.len(),
+            });
+        }
+        rows
+    }
+
+    /// Checks the given files, or unchecks them if they are all checked already.
+    fn toggle(&mut self, files: &[usize]) {
+        let check = !files.iter().all(|&index| self.selected[index]);
+        for &index in files {
+            self.selected[index] = check;
+        }
+    }
+
+    /// Returns the number of checked files and their estimated tokens and bytes.
+    fn totals(&self) -> (usize, usize, usize) {
+        self.files
+            .iter()
+            .zip(&self.selected)
+            .filter(|(_, selected)| **selected)
+            .fold((0, 0, 0), |(files, tokens, bytes), (file, _)| {
+                (files + 1, tokens + file.tokens, bytes + file.bytes)
+            })
+    }
+
+    /// Applies a key press.
+    ///
+    /// # Arguments
+    /// * `key` - The key that was pressed.
+    /// * `page` - The number of rows moved by Page Up and Page Down.
+    ///
+    /// # Returns
+    /// * `Option<Outcome>` - Whether the session ended.
+    fn handle(&mut self, key: Key, page: usize) -> Option<Outcome> {
+        let rows = self.rows();
+        if key == Key::Interrupt {
+            return Some(Outcome::Cancel);
+        }
+        if self.filtering {
+            match key {
+                Key::Char(c) => self.filter.push(c),
+                Key::Backspace => {
+                    self.filter.pop();
+                }
+                Key::Enter => self.filtering = false,
+                Key::Escape => {
+                    self.filter.clear();
+                    self.filtering = false;
+                }
+                _ => {}
+            }
+            self.cursor = 0;
+            return None;
+        }
+        let last = rows.len().saturating_sub(1);
+        match key {
+            Key::Up | Key::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.cursor = (self.cursor + 1).min(last),
+            Key::PageUp => self.cursor = self.cursor.saturating_sub(page),
+            Key::PageDown => self.cursor = (self.cursor + page).min(last),
+            Key::Char(' ') => match rows.get(self.cursor) {
+                Some(Row::Directory { files, .. }) => self.toggle(files),
+                Some(Row::File { index, .. }) => self.selected[*index] ^= true,
+                None => {}
+            },
+            Key::Char('a') => {
+                let visible: Vec<usize> = rows
+                    .iter()
+                    .filter_map(|row| match row {
+                        Row::File { index, .. } => Some(*index),
+                        Row::Directory { .. } => None,
+                    })
+                    .collect();
+                self.toggle(&visible);
+            }
+            Key::Char('/') => self.filtering = true,
+            Key::Enter => return Some(Outcome::Confirm),
+            Key::Escape | Key::Char('q') => return Some(Outcome::Cancel),
+            _ => {}
+        }
+        None
+    }
+
+    /// Renders the picker as lines of at most `width` characters.
+    ///
+    /// # Arguments
+    /// * `height` - The number of lines available.
+    /// * `width` - The number of columns available.
+    ///
+    /// # Returns
+    /// * `Vec<String>` - The lines; the row under the cursor is in reverse video.
+    fn render(&self, height: usize, width: usize) -> Vec<String> {
+        let rows = self.rows();
+        let (files, tokens, bytes) = self.totals();
+        let list_height = height.saturating_sub(3).max(1);
+        let offset = self.cursor.saturating_sub(list_height - 1);
+
+        let mut lines = vec![truncate(
+            "Space: toggle  a: toggle all  /: filter  Enter: bundle  q: cancel",
+            width,
+        )];
+        lines.push(truncate(
+            &match (self.filtering, self.filter.is_empty()) {
+                (true, _) => format!("Filter: {}_", self.filter),
+                (false, false) => format!("Filter: {}", self.filter),
+                (false, true) => String::new(),
+            },
+            width,
+        ));
+        for (i, row) in rows.iter().enumerate().skip(offset).take(list_height) {
+            let line = match row {
+                Row::Directory { path, depth, files } => {
+                    let checked = files.iter().filter(|&&index| self.selected[index]).count();
+                    let mark = match checked {
+                        0 => ' ',
+                        n if n == files.len() => 'x',
+                        _ => '-',
+                    };
+                    let name = path.file_name().unwrap_or(path.as_os_str());
+                    format!(
+                        "{}[{}] {}/",
+                        "  ".repeat(*depth),
+                        mark,
+                        name.to_string_lossy()
+                    )
+                }
+                Row::File { index, depth } => {
+                    let file = &self.files[*index];
+                    let name = file.path.file_name().unwrap_or(file.path.as_os_str());
+                    format!(
+                        "{}[{}] {}  ~{} tokens, {}",
+                        "  ".repeat(*depth),
+                        if self.selected[*index] { 'x' } else { ' ' },
+                        name.to_string_lossy(),
+                        file.tokens,
+                        format_size(file.bytes as u64)
+                    )
+                }
+            };
+            let line = truncate(&line, width);
+            if i == self.cursor {
+                lines.push(format!("\x1b[7m{}\x1b[0m", line));
+            } else {
+                lines.push(line);
+            }
+        }
+        while lines.len() < height.saturating_sub(1) {
+            lines.push(String::new());
+        }
+        lines.push(truncate(
+            &format!(
+                "{} of {} files selected, ~{} tokens, {}",
+                files,
+                self.files.len(),
+                tokens,
+                format_size(bytes as u64)
+            ),
+            width,
+        ));
+        lines
+    }
+
+    /// Returns the paths of the checked files, relative to the bundled directory.
+    fn selection(&self) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .zip(&self.selected)
+            .filter(|(_, selected)| **selected)
+            .map(|(file, _)| file.relative_path.clone())
+            .collect()
+    }
+}
+
+/// Shortens a line to `width` characters.
+fn truncate(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
+
+/// Decodes the bytes of one key press read from a terminal in raw mode.
+///
+/// # Arguments
+/// * `bytes` - The bytes returned by a single read.
+///
+/// # Returns
+/// * `Option<Key>` - The key, or `None` for keys the picker ignores.
+fn parse_key(bytes: &[u8]) -> Option<Key> {
+    match bytes {
+        [0x1b] => Some(Key::Escape),
+        [0x1b, b'[' | b'O', b'A'] => Some(Key::Up),
+        [0x1b, b'[' | b'O', b'B'] => Some(Key::Down),
+        [0x1b, b'[', b'5', b'~'] => Some(Key::PageUp),
+        [0x1b, b'[', b'6', b'~'] => Some(Key::PageDown),
+        [0x1b, ..] => None,
+        [b'\r' | b'\n'] => Some(Key::Enter),
+        [0x7f | 0x08] => Some(Key::Backspace),
+        [0x03] => Some(Key::Interrupt),
+        _ => std::str::from_utf8(bytes)
+            .ok()?
+            .chars()
+            .next()
+            .filter(|c| !c.is_control())
+            .map(Key::Char),
+    }
+}
+
+/// Fails unless a terminal is available to show the picker on.
+///
+/// # Returns
+/// * `Result<()>` - An error explaining that `--interactive` needs a terminal.
+pub(crate) fn require_terminal() -> Result<()> {
+    terminal::Terminal::check()
+}
+
+/// Lets the user choose among the candidate files in a terminal.
+///
+/// The picker is drawn on the controlling terminal, so the bundle can still go to stdout.
+///
+/// # Arguments
+/// * `files` - The files that passed the filters.
+///
+/// # Returns
+/// * `Result<Option<Vec<PathBuf>>>` - The relative paths of the chosen files, `None` if the
+///   user cancelled, or an error if no terminal is available.
+pub(crate) fn pick(files: Vec<FileEstimate>) -> Result<Option<Vec<PathBuf>>> {
+    let mut picker = Picker::new(files);
+    let mut terminal = terminal::Terminal::open()?;
+    loop {
+        let (height, width) = terminal.size();
+        terminal.draw(&picker.render(height, width))?;
+        let Some(key) = terminal.read_key()? else {
+            continue;
+        };
+        match picker.handle(key, height.saturating_sub(3).max(1)) {
+            Some(Outcome::Confirm) => return Ok(Some(picker.selection())),
+            Some(Outcome::Cancel) => return Ok(None),
+            None => {}
+        }
+    }
+}
+
+#[cfg(unix)]
+mod terminal {
+    use super::{Key, parse_key};
+    use anyhow::{Context, Result, anyhow};
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, Read, Write};
+    use std::mem::MaybeUninit;
+    use std::os::fd::AsRawFd;
+
+    /// The controlling terminal in raw mode, on the alternate screen until dropped.
+    pub(super) struct Terminal {
+        tty: File,
+        original: libc::termios,
+    }
+
+    impl Terminal {
+        /// Opens the controlling terminal, failing fast when there is none.
+        fn open_tty() -> Result<File> {
+            let no_terminal =
+                || anyhow!("--interactive needs a terminal, but this process has none attached");
+            let tty = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open("/dev/tty")
+                .map_err(|_| no_terminal())?;
+            // SAFETY: isatty only inspects the open file descriptor.
+            if unsafe { libc::isatty(tty.as_raw_fd()) } != 1 {
+                return Err(no_terminal());
+            }
+            Ok(tty)
+        }
+
+        pub(super) fn check() -> Result<()> {
+            Self::open_tty().map(drop)
+        }
+
+        pub(super) fn open() -> Result<Self> {
+            let mut tty = Self::open_tty()?;
+            let fd = tty.as_raw_fd();
+            let mut original = MaybeUninit::<libc::termios>::uninit();
+            // SAFETY: tcgetattr fills the termios struct when it returns 0.
+            let original = unsafe {
+                if libc::tcgetattr(fd, original.as_mut_ptr()) != 0 {
+                    return Err(io::Error::last_os_error())
+                        .context("Failed to read the terminal settings");
+                }
+                original.assume_init()
+            };
+            let mut raw = original;
+            // SAFETY: cfmakeraw and tcsetattr only touch the given struct and descriptor.
+            unsafe {
+                libc::cfmakeraw(&mut raw);
+                raw.c_cc[libc::VMIN] = 1;
+                raw.c_cc[libc::VTIME] = 0;
+                if libc::tcsetattr(fd, libc::TCSANOW, &raw) != 0 {
+                    return Err(io::Error::last_os_error())
+                        .context("Failed to switch the terminal to raw mode");
+                }
+            }
+            // Alternate screen, hidden cursor.
+            tty.write_all(b"\x1b[?1049h\x1b[?25l")?;
+            Ok(Terminal { tty, original })
+        }
+
+        /// Returns the terminal height and width, or 24 by 80 if it is unknown.
+        pub(super) fn size(&self) -> (usize, usize) {
+            let mut size = MaybeUninit::<libc::winsize>::zeroed();
+            // SAFETY: TIOCGWINSZ fills the winsize struct when it returns 0.
+            let size = unsafe {
+                if libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, size.as_mut_ptr()) != 0 {
+                    return (24, 80);
+                }
+                size.assume_init()
+            };
+            match (size.ws_row, size.ws_col) {
+                (0, _) | (_, 0) => (24, 80),
+                (rows, cols) => (rows.into(), cols.into()),
+            }
+        }
+
+        pub(super) fn draw(&mut self, lines: &[String]) -> Result<()> {
+            let screen = format!("\x1b[H\x1b[2J{}", lines.join("\r\n"));
+            self.tty.write_all(screen.as_bytes())?;
+            self.tty.flush()?;
+            Ok(())
+        }
+
+        pub(super) fn read_key(&mut self) -> Result<Option<Key>> {
+            let mut buffer = [0; 16];
+            let read = self
+                .tty
+                .read(&mut buffer)
+                .context("Failed to read from the terminal")?;
+            Ok(parse_key(&buffer[..read]))
+        }
+    }
+
+    impl Drop for Terminal {
+        fn drop(&mut self) {
+            let _ = self.tty.write_all(b"\x1b[?25h\x1b[?1049l");
+            let _ = self.tty.flush();
+            // SAFETY: restores the settings read in `open` on the same descriptor.
+            unsafe {
+                libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, &self.original);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod terminal {
+    use super::Key;
+    use anyhow::{Result, bail};
+
+    /// Raw terminal input is only implemented for Unix terminals.
+    pub(super) struct Terminal;
+
+    impl Terminal {
+        pub(super) fn check() -> Result<()> {
+            bail!("--interactive is only supported on Unix terminals")
+        }
+
+        pub(super) fn open() -> Result<Self> {
+            Self::check().map(|()| Terminal)
+        }
+
+        pub(super) fn size(&self) -> (usize, usize) {
+            (24, 80)
+        }
+
+        pub(super) fn draw(&mut self, _lines: &[String]) -> Result<()> {
+            Ok(())
+        }
+
+        pub(super) fn read_key(&mut self) -> Result<Option<Key>> {
+            Ok(Some(Key::Interrupt))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker(paths: &[&str]) -> Picker {
+        Picker::new(
+            paths
+                .iter()
+                .map(|path| FileEstimate {
+                    path: PathBuf::from(path),
+                    relative_path: PathBuf::from(path),
+                    bytes: 40,
+                    tokens: 10,
+                })
+                .collect(),
+        )
+    }
+
+    fn labels(picker: &Picker) -> Vec<String> {
+        picker
+            .render(20, 80)
+            .into_iter()
+            .skip(2)
+            .take_while(|line| !line.is_empty())
+            .map(|line| line.replace("\x1b[7m", "").replace("\x1b[0m", ""))
+            .map(|line| line.split("  ~").next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn files_are_shown_under_their_directories() {
+        let picker = picker(&["src/main.rs", "README.md", "src/cli/args.rs", "src/lib.rs"]);
+        assert_eq!(
+            labels(&picker),
+            [
+                "[x] README.md",
+                "[x] src/",
+                "  [x] cli/",
+                "    [x] args.rs",
+                "  [x] lib.rs",
+                "  [x] main.rs",
+            ]
+        );
+    }
+
+    #[test]
+    fn toggling_a_directory_toggles_its_files() {
+        let mut picker = picker(&["README.md", "src/cli/args.rs", "src/lib.rs"]);
+        picker.handle(Key::Down, 10);
+        picker.handle(Key::Char(' '), 10);
+        assert_eq!(picker.selection(), [PathBuf::from("README.md")]);
+        assert_eq!(picker.totals(), (1, 10, 40));
+
+        picker.handle(Key::Down, 10);
+        picker.handle(Key::Char(' '), 10);
+        assert_eq!(labels(&picker)[1], "[-] src/");
+        picker.handle(Key::Char('a'), 10);
+        assert_eq!(picker.selection().len(), 3);
+        picker.handle(Key::Char('a'), 10);
+        assert!(picker.selection().is_empty());
+    }
+
+    #[test]
+    fn filtering_hides_other_files() {
+        let mut picker = picker(&["README.md", "src/cli/args.rs", "src/lib.rs"]);
+        for key in [Key::Char('/'), Key::Char('A'), Key::Char('r'), Key::Enter] {
+            picker.handle(key, 10);
+        }
+        assert_eq!(
+            labels(&picker),
+            ["[x] src/", "  [x] cli/", "    [x] args.rs"]
+        );
+        picker.handle(Key::Char(' '), 10);
+        assert_eq!(
+            picker.selection(),
+            [PathBuf::from("README.md"), PathBuf::from("src/lib.rs")]
+        );
+        assert_eq!(picker.handle(Key::Char('q'), 10), Some(Outcome::Cancel));
+    }
+
+    #[test]
+    fn keys_are_decoded_from_raw_bytes() {
+        assert_eq!(parse_key(b"\x1b[A"), Some(Key::Up));
+        assert_eq!(parse_key(b"\x1bOB"), Some(Key::Down));
+        assert_eq!(parse_key(b"\x1b[6~"), Some(Key::PageDown));
+        assert_eq!(parse_key(b"\x1b"), Some(Key::Escape));
+        assert_eq!(parse_key(b"\r"), Some(Key::Enter));
+        assert_eq!(parse_key(b"\x7f"), Some(Key::Backspace));
+        assert_eq!(parse_key(b"\x03"), Some(Key::Interrupt));
+        assert_eq!(parse_key("é".as_bytes()), Some(Key::Char('é')));
+        assert_eq!(parse_key(b"\x1b[15~"), None);
+    }
+}
@@ -0,0 +1,142 @@
+//! A lightweight progress spinner drawn on stderr while files are processed.
+//!
+//! The spinner line is shared with [`LogWriter`], which clears it before each log line and
+//! redraws it afterwards so progress output never interleaves with tracing output.
+
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The spinner line currently shown on stderr, if any.
+static ACTIVE_LINE: Mutex<Option<String>> = Mutex::new(None);
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const REDRAW_INTERVAL: Duration = Duration::from_millis(80);
+const MAX_MESSAGE_WIDTH: usize = 60;
+
+/// A spinner reporting the number of processed files and the current path.
+pub(crate) struct Progress {
+    enabled: bool,
+    processed: usize,
+    frame: usize,
+    last_draw: Option<Instant>,
+}
+
+impl Progress {
+    /// Creates a spinner that is only drawn when `requested` is set and stderr is a terminal.
+    ///
+    /// # Arguments
+    /// * `requested` - Whether the caller wants progress output at all.
+    /// * `to_stdout` - Whether the bundle itself is written to stdout; the spinner is
+    ///   suppressed when that stdout is a terminal, since both would share the screen.
+    pub(crate) fn new(requested: bool, to_stdout: bool) -> Self {
+        let enabled =
+            requested && io::stderr().is_terminal() && !(to_stdout && io::stdout().is_terminal());
+        Self {
+            enabled,
+            processed: 0,
+            frame: 0,
+            last_draw: None,
+        }
+    }
+
+    /// Records that a file was processed and redraws the spinner if due.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the file being processed, shown as the message.
+    pub(crate) fn inc(&mut self, path: &Path) {
+        if !self.enabled {
+            return;
+        }
+        self.processed += 1;
+        if self
+            .last_draw
+            .is_some_and(|last| last.elapsed() < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+        self.frame = (self.frame + 1) % FRAMES.len();
+
+        let line = format!(
+            "{} {} files processed  {}",
+            FRAMES[self.frame],
+            self.processed,
+            truncate_left(&path.display().to_string(), MAX_MESSAGE_WIDTH)
+        );
+        let mut active = ACTIVE_LINE.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{}", line);
+        let _ = stderr.flush();
+        *active = Some(line);
+    }
+
+    /// Clears the spinner line.
+    pub(crate) fn finish(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        let mut active = ACTIVE_LINE.lock().unwrap_or_else(|e| e.into_inner());
+        if active.take().is_some() {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Keeps the last `width` characters of a message, marking the cut with an ellipsis.
+fn truncate_left(message: &str, width: usize) -> String {
+    let count = message.chars().count();
+    if count <= width {
+        return message.to_string();
+    }
+    let tail: String = message.chars().skip(count - width + 1).collect();
+    format!("…{}", tail)
+}
+
+/// A log writer that cooperates with the progress spinner.
+///
+/// Intended for `tracing_subscriber`'s `with_writer`: each write clears the spinner line,
+/// emits the log output, and redraws the spinner underneath it.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogWriter;
+
+impl LogWriter {
+    /// Returns a new log writer; usable as a `MakeWriter` function.
+    pub fn make() -> Self {
+        Self
+    }
+
+    /// Returns whether log lines should be colored: stderr is a terminal and `NO_COLOR` is unset.
+    pub fn ansi() -> bool {
+        io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let active = ACTIVE_LINE.lock().unwrap_or_else(|e| e.into_inner());
+        if active.is_some() {
+            let _ = write!(io::stderr(), "\r\x1b[2K");
+        }
+        let mut stderr = io::stderr().lock();
+        stderr.write_all(buf)?;
+        stderr.flush()?;
+        if let Some(line) = active.as_ref() {
+            let _ = write!(io::stderr(), "{}", line);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
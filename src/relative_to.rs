@@ -0,0 +1,151 @@
+//! The base displayed paths are relative to (`relative_to`), when it is not the scanned
+//! directory: e.g. the repository root, so `backend/src/handlers/auth.rs` is shown for a
+//! bundle of `backend/src`.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::warn;
+
+/// The spelling of [`RelativeTo::GitRoot`].
+const GIT_ROOT: &str = "git-root";
+
+/// The base of displayed paths, parsed from a directory or `git-root`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum RelativeTo {
+    /// The top level of the git working tree that holds the scanned directory.
+    GitRoot,
+    /// A directory that holds the scanned directory.
+    Path(PathBuf),
+}
+
+impl RelativeTo {
+    /// Finds the path from the base to the scanned directory, which goes before every
+    /// displayed path.
+    ///
+    /// # Arguments
+    /// * `scanned` - The directory displayed paths are otherwise relative to.
+    ///
+    /// # Returns
+    /// * `Result<PathBuf>` - The prefix, e.g. `backend/src`, empty if the scanned directory
+    ///   is outside the base (with a warning), or an error if the base cannot be found.
+    pub(crate) fn prefix(&self, scanned: &Path) -> Result<PathBuf> {
+        let scanned = scanned
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve {}", scanned.display()))?;
+        let base = match self {
+            RelativeTo::GitRoot => match git_root(&scanned) {
+                Some(root) => root,
+                None => bail!(
+                    "--relative-to git-root: {} is not inside a git repository",
+                    scanned.display()
+                ),
+            },
+            RelativeTo::Path(path) => path
+                .canonicalize()
+                .with_context(|| format!("Failed to resolve --relative-to {}", path.display()))?,
+        };
+        match scanned.strip_prefix(&base) {
+            Ok(prefix) => Ok(prefix.to_path_buf()),
+            Err(_) => {
+                warn!(
+                    "{} is not inside {}; showing paths relative to the scanned directory",
+                    scanned.display(),
+                    base.display()
+                );
+                Ok(PathBuf::new())
+            }
+        }
+    }
+}
+
+/// Finds the top level of the working tree that holds a directory, like
+/// `git rev-parse --show-toplevel`: the nearest ancestor with a `.git` directory or file.
+fn git_root(directory: &Path) -> Option<PathBuf> {
+    directory
+        .ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+impl FromStr for RelativeTo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("the --relative-to base cannot be empty".to_string()),
+            GIT_ROOT => Ok(RelativeTo::GitRoot),
+            path => Ok(RelativeTo::Path(PathBuf::from(path))),
+        }
+    }
+}
+
+impl TryFrom<String> for RelativeTo {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for RelativeTo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelativeTo::GitRoot => f.write_str(GIT_ROOT),
+            RelativeTo::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl Serialize for RelativeTo {
+    /// Serializes as the string form read from configuration files.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn prefixes_lead_from_the_base_to_the_scanned_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = temp_dir.path().join("repo");
+        let scanned = repo.join("backend/src");
+        fs::create_dir_all(&scanned).unwrap();
+        fs::create_dir(repo.join(".git")).unwrap();
+
+        assert_eq!(
+            RelativeTo::GitRoot.prefix(&scanned).unwrap(),
+            Path::new("backend/src")
+        );
+        assert_eq!(
+            RelativeTo::Path(repo.join("backend"))
+                .prefix(&scanned)
+                .unwrap(),
+            Path::new("src")
+        );
+        // A base that does not hold the scanned directory falls back to no prefix.
+        assert_eq!(
+            RelativeTo::Path(temp_dir.path().join("repo/backend/src"))
+                .prefix(&repo)
+                .unwrap(),
+            Path::new("")
+        );
+        assert!(RelativeTo::GitRoot.prefix(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn bases_round_trip_through_their_spelling() {
+        for spelling in ["git-root", "../repo"] {
+            let base: RelativeTo = spelling.parse().unwrap();
+            assert_eq!(base.to_string(), spelling);
+        }
+        assert!("".parse::<RelativeTo>().is_err());
+    }
+}
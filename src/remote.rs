@@ -0,0 +1,221 @@
+//! Remote git repositories as input: a shallow clone into a temporary directory.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A shallow clone of a remote repository, deleted when dropped.
+#[derive(Debug)]
+pub struct RemoteCheckout {
+    /// Keeps the temporary directory alive.
+    _dir: TempDir,
+    /// The working tree of the clone.
+    path: PathBuf,
+}
+
+impl RemoteCheckout {
+    /// Wraps a tree written into a temporary directory by another kind of download.
+    #[cfg(feature = "http")]
+    pub(crate) fn new(dir: TempDir, path: PathBuf) -> RemoteCheckout {
+        RemoteCheckout { _dir: dir, path }
+    }
+
+    /// Returns the working tree of the clone, to bundle like a local directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Checks whether an input looks like a git URL rather than a local path.
+///
+/// `https://`, `http://`, `ssh://`, `git://`, and `file://` URLs are recognized, as is the
+/// scp-like `git@host:owner/repo` form.
+///
+/// # Arguments
+/// * `input` - The input given on the command line.
+///
+/// # Returns
+/// * `bool` - `true` if the input should be cloned.
+pub fn is_git_url(input: &str) -> bool {
+    const SCHEMES: &[&str] = &["https://", "http://", "ssh://", "git://", "file://"];
+    if SCHEMES.iter().any(|scheme| input.starts_with(scheme)) {
+        return true;
+    }
+    // `user@host:path`, as long as the part before the colon is not a path itself.
+    input
+        .split_once(':')
+        .is_some_and(|(host, path)| host.contains('@') && !host.contains('/') && !path.is_empty())
+}
+
+/// Returns the directory name a clone of the URL gets, like `git clone` would choose.
+#[cfg(feature = "git")]
+fn repository_name(url: &str) -> &str {
+    let name = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or_default();
+    match name.strip_suffix(".git").unwrap_or(name) {
+        "" => "repository",
+        name => name,
+    }
+}
+
+/// Clones a remote repository with a depth of one into a temporary directory.
+///
+/// Credentials for private repositories come from the SSH agent and the configured git
+/// credential helpers.
+///
+/// # Arguments
+/// * `url` - The URL of the repository.
+/// * `reference` - The branch or tag to check out, or `None` for the default branch.
+///
+/// # Returns
+/// * `Result<RemoteCheckout>` - The clone, or the underlying git error if it fails.
+#[cfg(feature = "git")]
+pub fn clone_remote(url: &str, reference: Option<&str>) -> Result<RemoteCheckout> {
+    use anyhow::{Context, anyhow};
+    use git2::build::CheckoutBuilder;
+    use git2::{Direction, FetchOptions, Repository};
+    use tracing::info;
+
+    let dir = tempfile::Builder::new()
+        .prefix("codebase-to-prompt-")
+        .tempdir()
+        .context("Failed to create a directory for the clone")?;
+    // A named subdirectory keeps the walk root from looking hidden and reads well in headers.
+    let path = dir.path().join(repository_name(url));
+    info!("Cloning {}", url);
+
+    let repo = Repository::init(&path)
+        .with_context(|| format!("Failed to create a repository in {}", path.display()))?;
+    let mut remote = repo
+        .remote("origin", url)
+        .with_context(|| format!("Invalid repository URL {}", url))?;
+    let refname = {
+        let connection = remote
+            .connect_auth(Direction::Fetch, Some(credentials::callbacks()), None)
+            .with_context(|| format!("Failed to connect to {}", url))?;
+        match reference {
+            None => {
+                let head = connection
+                    .default_branch()
+                    .with_context(|| format!("Failed to find the default branch of {}", url))?;
+                head.as_str()
+                    .ok_or_else(|| anyhow!("The default branch of {} is not UTF-8", url))?
+                    .to_string()
+            }
+            Some(reference) => {
+                let candidates = [
+                    format!("refs/heads/{}", reference),
+                    format!("refs/tags/{}", reference),
+                    reference.to_string(),
+                ];
+                let heads = connection
+                    .list()
+                    .with_context(|| format!("Failed to list the references of {}", url))?;
+                candidates
+                    .into_iter()
+                    .find(|name| heads.iter().any(|head| head.name() == name))
+                    .ok_or_else(|| anyhow!("{} has no branch or tag named {}", url, reference))?
+            }
+        }
+    };
+
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(credentials::callbacks());
+    // The local transport cannot fetch shallowly, and a local fetch is cheap anyway.
+    if !url.starts_with("file://") {
+        options.depth(1);
+    }
+    remote
+        .fetch(&[&refname], Some(&mut options), None)
+        .with_context(|| format!("Failed to fetch {} from {}", refname, url))?;
+    let commit = repo
+        .find_reference("FETCH_HEAD")
+        .and_then(|head| head.peel_to_commit())
+        .with_context(|| format!("Failed to resolve {} of {}", refname, url))?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))
+        .with_context(|| format!("Failed to check out {}", refname))?;
+    repo.set_head_detached(commit.id())
+        .with_context(|| format!("Failed to check out {}", refname))?;
+
+    Ok(RemoteCheckout { _dir: dir, path })
+}
+
+#[cfg(not(feature = "git"))]
+pub fn clone_remote(url: &str, _reference: Option<&str>) -> Result<RemoteCheckout> {
+    anyhow::bail!("Cannot clone {}: built without git support", url)
+}
+
+#[cfg(feature = "git")]
+mod credentials {
+    use git2::{Config, Cred, CredentialType, RemoteCallbacks};
+
+    /// Callbacks that offer the SSH agent and then the git credential helpers, each once.
+    pub(super) fn callbacks<'a>() -> RemoteCallbacks<'a> {
+        let config = Config::open_default().ok();
+        let mut tried_agent = false;
+        let mut tried_helper = false;
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(move |url, username, allowed| {
+            if allowed.contains(CredentialType::SSH_KEY) && !tried_agent {
+                tried_agent = true;
+                return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+            }
+            if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !tried_helper {
+                tried_helper = true;
+                if let Some(config) = &config {
+                    return Cred::credential_helper(config, url, username);
+                }
+            }
+            if allowed.contains(CredentialType::DEFAULT) {
+                return Cred::default();
+            }
+            Err(git2::Error::from_str(&format!(
+                "no credentials were accepted for {}",
+                url
+            )))
+        });
+        callbacks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_urls_are_told_apart_from_paths() {
+        for url in [
+            "https://github.com/user/repo",
+            "http://example.com/repo.git",
+            "ssh://git@example.com/repo.git",
+            "git://example.com/repo",
+            "file:///srv/repo",
+            "git@github.com:user/repo.git",
+        ] {
+            assert!(is_git_url(url), "{}", url);
+        }
+        for path in [
+            ".",
+            "src",
+            "/home/user/repo",
+            "C:\\code\\repo",
+            "a/b@c:d",
+            "x@y:",
+        ] {
+            assert!(!is_git_url(path), "{}", path);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "git")]
+    fn repository_names() {
+        assert_eq!(repository_name("https://github.com/user/repo"), "repo");
+        assert_eq!(repository_name("https://github.com/user/repo.git/"), "repo");
+        assert_eq!(repository_name("git@github.com:user/tool.git"), "tool");
+        assert_eq!(repository_name("git@host:proj"), "proj");
+        assert_eq!(repository_name("https://"), "repository");
+    }
+}
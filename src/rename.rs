@@ -0,0 +1,244 @@
+//! Rewriting of displayed paths, so bundles can be shared without exposing internal names.
+
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Component, MAIN_SEPARATOR, Path, PathBuf};
+use std::str::FromStr;
+use tracing::warn;
+
+/// A `<from>=<to>` rule that rewrites displayed paths.
+///
+/// When `from` contains a `/`, it is matched against leading path prefixes and the longest
+/// matching prefix is replaced by `to`. Otherwise it is matched against each path component
+/// and every matching component is replaced. `from` may use glob syntax (`*`, `?`, `[...]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct PathRename {
+    from: String,
+    to: String,
+    matcher: GlobMatcher,
+}
+
+impl PathRename {
+    /// Applies the rule to a relative path.
+    ///
+    /// # Arguments
+    /// * `path` - The relative path to rewrite.
+    ///
+    /// # Returns
+    /// * `Option<PathBuf>` - The rewritten path, or `None` if the rule does not match.
+    pub fn apply(&self, path: &Path) -> Option<PathBuf> {
+        let components: Vec<&str> = path
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => part.to_str(),
+                _ => None,
+            })
+            .collect();
+
+        if self.from.contains('/') {
+            let end = (1..=components.len())
+                .rev()
+                .find(|&end| self.matcher.is_match(components[..end].join("/")))?;
+            let mut renamed = PathBuf::from(&self.to);
+            renamed.extend(&components[end..]);
+            return Some(renamed);
+        }
+
+        let mut matched = false;
+        let renamed = components
+            .iter()
+            .map(|part| {
+                if self.matcher.is_match(part) {
+                    matched = true;
+                    self.to.as_str()
+                } else {
+                    part
+                }
+            })
+            .collect();
+        matched.then_some(renamed)
+    }
+}
+
+impl FromStr for PathRename {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid path rename `{}` (expected <from>=<to>)", s))?;
+        let from = from.trim_matches('/');
+        let to = to.trim_matches('/');
+        if from.is_empty() || to.is_empty() {
+            return Err(format!(
+                "invalid path rename `{}` (both sides must be non-empty)",
+                s
+            ));
+        }
+        let matcher = GlobBuilder::new(from)
+            .literal_separator(true)
+            .build()
+            .map_err(|err| format!("invalid path rename pattern `{}`: {}", from, err))?
+            .compile_matcher();
+        Ok(PathRename {
+            from: from.to_string(),
+            to: to.to_string(),
+            matcher,
+        })
+    }
+}
+
+impl TryFrom<String> for PathRename {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for PathRename {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.from, self.to)
+    }
+}
+
+impl Serialize for PathRename {
+    /// Serializes as the string form read from configuration files.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Maps relative paths to their displayed form and warns when two paths collide.
+pub(crate) struct PathRenamer<'a> {
+    rules: &'a [PathRename],
+    seen: HashMap<PathBuf, PathBuf>,
+}
+
+impl<'a> PathRenamer<'a> {
+    /// Creates a renamer for the given rules.
+    pub(crate) fn new(rules: &'a [PathRename]) -> Self {
+        PathRenamer {
+            rules,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Returns the displayed path for a relative path; the first matching rule wins.
+    ///
+    /// # Arguments
+    /// * `path` - The relative path of a file.
+    ///
+    /// # Returns
+    /// * `PathBuf` - The path to show in the output.
+    pub(crate) fn display_path(&mut self, path: &Path) -> PathBuf {
+        if self.rules.is_empty() {
+            return path.to_path_buf();
+        }
+
+        let shown = self
+            .rules
+            .iter()
+            .find_map(|rule| rule.apply(path))
+            .unwrap_or_else(|| path.to_path_buf());
+        match self.seen.get(&shown) {
+            Some(source) if source != path => warn!(
+                "Renamed paths collide: {} and {} are both shown as {}",
+                source.display(),
+                path.display(),
+                shown.display()
+            ),
+            Some(_) => {}
+            None => {
+                self.seen.insert(shown.clone(), path.to_path_buf());
+            }
+        }
+        shown
+    }
+}
+
+/// Rewrites a relative path with `/` between its components, as shown on every platform.
+///
+/// Only the platform separator is replaced: a `\` in a Unix file name is part of the name.
+///
+/// # Arguments
+/// * `path` - The relative path to rewrite.
+///
+/// # Returns
+/// * `PathBuf` - The path with forward slashes.
+pub(crate) fn slash_path(path: &Path) -> PathBuf {
+    if MAIN_SEPARATOR == '/' {
+        return path.to_path_buf();
+    }
+    let mut shown = OsString::new();
+    for (i, component) in path.components().enumerate() {
+        if i > 0 {
+            shown.push("/");
+        }
+        shown.push(component.as_os_str());
+    }
+    PathBuf::from(shown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename(rule: &str, path: &str) -> Option<PathBuf> {
+        rule.parse::<PathRename>().unwrap().apply(Path::new(path))
+    }
+
+    #[test]
+    fn renames_matching_components() {
+        assert_eq!(
+            rename(
+                "internal-billing-service=service-a",
+                "apps/internal-billing-service/src/lib.rs"
+            ),
+            Some(PathBuf::from("apps/service-a/src/lib.rs"))
+        );
+        assert_eq!(
+            rename("internal-*=service", "internal-billing/main.rs"),
+            Some(PathBuf::from("service/main.rs"))
+        );
+        assert_eq!(rename("internal-*=service", "src/main.rs"), None);
+    }
+
+    #[test]
+    fn renames_longest_matching_prefix() {
+        assert_eq!(
+            rename("crates/*/src=lib", "crates/billing/src/api/mod.rs"),
+            Some(PathBuf::from("lib/api/mod.rs"))
+        );
+        assert_eq!(rename("crates/*/src=lib", "src/main.rs"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        assert!("no-separator".parse::<PathRename>().is_err());
+        assert!("=to".parse::<PathRename>().is_err());
+        assert!("from=".parse::<PathRename>().is_err());
+    }
+
+    #[test]
+    fn displayed_paths_use_forward_slashes() {
+        let nested = Path::new("src").join("handlers").join("auth.rs");
+        assert_eq!(slash_path(&nested), Path::new("src/handlers/auth.rs"));
+        assert_eq!(slash_path(Path::new("main.rs")), Path::new("main.rs"));
+        if cfg!(windows) {
+            assert_eq!(
+                slash_path(Path::new(r"src\handlers\auth.rs")).to_str(),
+                Some("src/handlers/auth.rs")
+            );
+        } else {
+            assert_eq!(
+                slash_path(Path::new(r"src\handlers\auth.rs")).to_str(),
+                Some(r"src\handlers\auth.rs")
+            );
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! The run report: a JSON description of what a run did, for scripts and CI pipelines that
+//! would otherwise scrape the log.
+
+use crate::json;
+use crate::summary::{ExtensionStats, LargestFile, SkippedFile};
+use crate::{Config, LanguageStats, RunSummary};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The report of a run, written with `report_json`.
+///
+/// The fields are part of the stable interface: new fields may be added, but existing ones
+/// keep their names and meaning.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RunReport {
+    /// The name of the tool, `codebase-to-prompt`.
+    pub tool: String,
+    /// The version of the tool that wrote the report.
+    pub version: String,
+    /// How long the run took, in milliseconds.
+    pub duration_ms: u64,
+    /// The files the run wrote: the bundle, after date and hash suffixes, and the statistics
+    /// and manifest files.
+    pub outputs: Vec<PathBuf>,
+    /// Whether the bundle was written to stdout.
+    pub stdout: bool,
+    /// Whether the bundle was copied to the clipboard.
+    pub clipboard: bool,
+    /// Number of files written to the bundle.
+    pub files_included: usize,
+    /// Number of entries skipped, for any reason.
+    pub files_skipped: usize,
+    /// Number of entries skipped for each reason, by [`SkipReason::name`](crate::SkipReason::name).
+    pub skipped_by_reason: BTreeMap<String, usize>,
+    /// Number of files or directories that could not be read or written.
+    pub errors: usize,
+    /// Whether the run was stopped early by Ctrl-C, with only the files included so far.
+    pub interrupted: bool,
+    /// Total number of lines across included files.
+    pub total_lines: usize,
+    /// Total number of bytes across included files, the preamble, and the postamble.
+    pub total_bytes: usize,
+    /// Estimated number of tokens across included files, the preamble, and the postamble.
+    pub estimated_tokens: usize,
+    /// The bundle digest with its algorithm, e.g. `blake3:<hex>`, with `hash`.
+    pub bundle_digest: Option<String>,
+    /// Statistics of the included files per extension, as in [`RunSummary::by_extension`].
+    pub extensions: BTreeMap<String, ExtensionStats>,
+    /// The lines of the included files per language, with `lang_stats`.
+    #[serde(default)]
+    pub languages: Vec<LanguageStats>,
+    /// The included files with the most tokens, with `top`.
+    #[serde(default)]
+    pub largest_files: Vec<LargestFile>,
+    /// Every entry skipped, with its reason.
+    pub skipped: Vec<SkippedFile>,
+    /// The effective configuration of the run.
+    pub config: Config,
+}
+
+impl RunReport {
+    /// Describes a finished run.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    /// * `summary` - The statistics of the run.
+    /// * `output` - The output file actually written, after suffixes, if any.
+    /// * `duration` - How long the run took.
+    ///
+    /// # Returns
+    /// * `RunReport` - The report.
+    pub(crate) fn new(
+        config: &Config,
+        summary: &RunSummary,
+        output: Option<&Path>,
+        duration: Duration,
+    ) -> RunReport {
+        let mut skipped_by_reason = BTreeMap::new();
+        for skipped in &summary.skipped {
+            *skipped_by_reason
+                .entry(skipped.reason.name().to_string())
+                .or_default() += 1;
+        }
+        let sidecars = [config.stats_output.as_deref(), config.manifest.as_deref()];
+        RunReport {
+            tool: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            outputs: [output]
+                .into_iter()
+                .chain(sidecars)
+                .flatten()
+                .map(Path::to_path_buf)
+                .collect(),
+            stdout: output.is_none() && !config.clipboard || config.tee,
+            clipboard: config.clipboard,
+            files_included: summary.files_included,
+            files_skipped: summary.files_skipped(),
+            skipped_by_reason,
+            errors: summary.errors,
+            interrupted: summary.interrupted,
+            total_lines: summary.total_lines,
+            total_bytes: summary.total_bytes,
+            estimated_tokens: summary.estimated_tokens,
+            bundle_digest: summary.bundle_digest.clone(),
+            extensions: summary.by_extension.clone(),
+            languages: summary.languages.clone(),
+            largest_files: summary.largest_files.clone(),
+            skipped: summary.skipped.clone(),
+            config: config.clone(),
+        }
+    }
+
+    /// Formats the report as indented JSON.
+    ///
+    /// # Returns
+    /// * `Result<String>` - The JSON text, or an error if the configuration cannot be described.
+    pub fn to_json(&self) -> Result<String> {
+        let value = json::to_value(self).context("Failed to describe the run")?;
+        Ok(value.to_pretty_string() + "\n")
+    }
+
+    /// Reads a report written by `report_json`.
+    ///
+    /// # Arguments
+    /// * `text` - The JSON text of the report.
+    ///
+    /// # Returns
+    /// * `Result<RunReport>` - The report, or an error if the text is not a valid report.
+    pub fn from_json(text: &str) -> Result<RunReport> {
+        json::from_str(text).context("Invalid run report")
+    }
+}
+
+/// Writes the report of a run.
+///
+/// # Arguments
+/// * `path` - The JSON file to create or overwrite, or `-` for stdout.
+/// * `report` - The report of the run.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` if successful, or an error if the report cannot be written.
+pub(crate) fn write_report(path: &Path, report: &RunReport) -> Result<()> {
+    let json = report.to_json()?;
+    if path == Path::new("-") {
+        print!("{}", json);
+        return Ok(());
+    }
+    fs::write(path, json).with_context(|| format!("Failed to write report: {}", path.display()))
+}
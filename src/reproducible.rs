@@ -0,0 +1,98 @@
+//! Reproducible bundles: the same sources give byte-identical output, whenever and wherever
+//! the run happens.
+
+use crate::{Config, FitStrategy, SortOrder, TimeBound};
+use anyhow::{Result, bail};
+use std::env;
+use std::time::{Duration, SystemTime};
+
+/// The environment variable that pins timestamps, as defined by
+/// <https://reproducible-builds.org/specs/source-date-epoch/>.
+const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// Reads `SOURCE_DATE_EPOCH`.
+///
+/// # Returns
+/// * `Result<Option<SystemTime>>` - The pinned time, `None` if the variable is unset or empty,
+///   or an error if it is not a number of seconds since the Unix epoch.
+fn source_date_epoch() -> Result<Option<SystemTime>> {
+    let Some(value) = env::var_os(SOURCE_DATE_EPOCH).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+    match value
+        .to_str()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+    {
+        Some(seconds) => Ok(Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))),
+        None => bail!(
+            "{} must be a number of seconds since the Unix epoch, not `{}`",
+            SOURCE_DATE_EPOCH,
+            value.to_string_lossy()
+        ),
+    }
+}
+
+/// Returns the time stamped on the bundle, its manifest, and the output file name.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Option<SystemTime>` - The current time, or with `reproducible` the time pinned by
+///   `SOURCE_DATE_EPOCH`, or `None` when it is unset and timestamps are left out.
+pub(crate) fn run_time(config: &Config) -> Option<SystemTime> {
+    if config.reproducible {
+        source_date_epoch().ok().flatten()
+    } else {
+        Some(SystemTime::now())
+    }
+}
+
+/// Returns whether times are written in UTC: with `utc`, or with `reproducible`, since the
+/// local time zone differs between machines.
+pub(crate) fn utc(config: &Config) -> bool {
+    config.utc || config.reproducible
+}
+
+/// Checks that the options of a reproducible run do not depend on when or where it happens.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<()>` - An error naming the option that cannot be reproduced, or an invalid
+///   `SOURCE_DATE_EPOCH`.
+pub(crate) fn check(config: &Config) -> Result<()> {
+    if !config.reproducible {
+        return Ok(());
+    }
+    let pinned = source_date_epoch()?;
+    if config.append_date {
+        bail!("--reproducible cannot be combined with --append-date");
+    }
+    if matches!(config.sort, SortOrder::Mtime) {
+        bail!("--reproducible cannot be combined with --sort mtime");
+    }
+    let by_churn = matches!(config.sort, SortOrder::Churn)
+        || (config.fit_budget.is_some() && config.fit_strategy == FitStrategy::Churn);
+    if by_churn && matches!(config.churn_window, TimeBound::Ago(_)) {
+        bail!("--reproducible needs a date rather than an age for --churn-window");
+    }
+    if pinned.is_none()
+        && config
+            .output_template
+            .as_deref()
+            .is_some_and(|template| template.contains("{date}"))
+    {
+        bail!("--reproducible needs SOURCE_DATE_EPOCH to fill `{{date}}` in --output-template");
+    }
+    if pinned.is_none()
+        && config
+            .name_suffix
+            .as_deref()
+            .is_some_and(|suffix| suffix.contains("{date}"))
+    {
+        bail!("--reproducible needs SOURCE_DATE_EPOCH to fill `{{date}}` in --name-suffix");
+    }
+    Ok(())
+}
@@ -0,0 +1,371 @@
+//! The module trees of Rust crates (`--sort rust-modules`): each crate root, then the files
+//! of its `mod` declarations in a depth-first walk, the order a reader would follow.
+
+use crate::comments::{self, Syntax, Token};
+use crate::source::{FileSource, SourceFile};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use tracing::debug;
+
+/// A `mod name;` declaration, whose module lives in another file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ModDecl {
+    /// The inline modules (`mod name { ... }`) the declaration is nested in, outermost first.
+    pub(crate) inline: Vec<String>,
+    /// The name of the module.
+    pub(crate) name: String,
+    /// The file named by a `#[path = "..."]` attribute, if any.
+    pub(crate) path: Option<String>,
+}
+
+/// A token of source code, with comments left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Tok<'a> {
+    Ident(&'a str),
+    Punct(char),
+    /// A string or character literal, with its quotes.
+    Literal(&'a str),
+}
+
+/// Splits source code into identifiers, punctuation, and literals, skipping comments.
+///
+/// # Arguments
+/// * `content` - The source code.
+/// * `syntax` - The comment and string syntax of its language.
+///
+/// # Returns
+/// * `Vec<Tok>` - The tokens, in order.
+pub(crate) fn lex(content: &str, syntax: Syntax) -> Vec<Tok<'_>> {
+    let mut spans = comments::tokens(content, syntax).into_iter().peekable();
+    let mut tokens = Vec::new();
+    let mut chars = content.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        if let Some((range, token)) = spans.next_if(|(range, _)| range.start == at) {
+            while chars.next_if(|(i, _)| *i < range.end).is_some() {}
+            if token == Token::Literal {
+                tokens.push(Tok::Literal(&content[range]));
+            }
+        } else if c == '_' || c.is_alphabetic() {
+            let mut end = at + c.len_utf8();
+            while let Some((i, c)) = chars.next_if(|(_, c)| *c == '_' || c.is_alphanumeric()) {
+                end = i + c.len_utf8();
+            }
+            let ident = &content[at..end];
+            // A raw identifier, e.g. `r#type`, is the name after `r#`.
+            if syntax == Syntax::Rust && ident == "r" && chars.next_if(|(_, c)| *c == '#').is_some()
+            {
+                continue;
+            }
+            tokens.push(Tok::Ident(ident));
+        } else if !c.is_whitespace() {
+            tokens.push(Tok::Punct(c));
+        }
+    }
+    tokens
+}
+
+/// Returns the text of a string literal without its quotes, e.g. `a.rs` for `r#"a.rs"#`
+/// or `'./a'`.
+pub(crate) fn unquote(literal: &str) -> String {
+    let raw = literal.trim_start_matches('r').trim_matches('#');
+    ['"', '\'', '`']
+        .iter()
+        .find_map(|quote| raw.strip_prefix(*quote)?.strip_suffix(*quote))
+        .unwrap_or(raw)
+        .to_string()
+}
+
+/// Finds the `mod name;` declarations of a Rust file, in order.
+///
+/// A token scan rather than a parser: comments and strings are skipped, braces are counted
+/// to track inline modules, and a `#[path = "..."]` attribute applies to the next
+/// declaration. Macros that expand to modules are not seen.
+///
+/// # Arguments
+/// * `content` - The Rust source.
+///
+/// # Returns
+/// * `Vec<ModDecl>` - The declarations of modules in other files.
+pub(crate) fn declarations(content: &str) -> Vec<ModDecl> {
+    let tokens = lex(content, Syntax::Rust);
+    let mut declarations = Vec::new();
+    // The open inline modules, with the brace depth outside each.
+    let mut inline: Vec<(&str, usize)> = Vec::new();
+    let mut depth = 0;
+    let mut path = None;
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i..] {
+            [Tok::Punct('#'), ..] => {
+                let open = i + 1 + usize::from(tokens.get(i + 1) == Some(&Tok::Punct('!')));
+                if tokens.get(open) == Some(&Tok::Punct('[')) {
+                    if let [
+                        Tok::Ident("path"),
+                        Tok::Punct('='),
+                        Tok::Literal(value),
+                        Tok::Punct(']'),
+                        ..,
+                    ] = tokens[open + 1..]
+                    {
+                        path = Some(unquote(value));
+                    }
+                    // Skips the attribute, keeping `path` for the item after it.
+                    let mut brackets = 0;
+                    i = open;
+                    while let Some(token) = tokens.get(i) {
+                        i += 1;
+                        match token {
+                            Tok::Punct('[') => brackets += 1,
+                            Tok::Punct(']') => {
+                                brackets -= 1;
+                                if brackets == 0 {
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
+            }
+            [Tok::Ident("mod"), Tok::Ident(name), Tok::Punct(';'), ..] => {
+                declarations.push(ModDecl {
+                    inline: inline.iter().map(|(name, _)| name.to_string()).collect(),
+                    name: name.to_string(),
+                    path: path.take(),
+                });
+                i += 3;
+                continue;
+            }
+            [Tok::Ident("mod"), Tok::Ident(name), Tok::Punct('{'), ..] => {
+                inline.push((name, depth));
+                depth += 1;
+                path = None;
+                i += 3;
+                continue;
+            }
+            [Tok::Punct('{'), ..] => depth += 1,
+            [Tok::Punct('}'), ..] => {
+                depth = depth.saturating_sub(1);
+                if inline.last().is_some_and(|(_, outside)| *outside == depth) {
+                    inline.pop();
+                }
+            }
+            _ => {}
+        }
+        if matches!(tokens[i], Tok::Punct(';' | '{' | '}')) {
+            path = None;
+        }
+        i += 1;
+    }
+    declarations
+}
+
+/// Returns the files a declaration may refer to, most likely first.
+///
+/// A crate root or `mod.rs` file owns its directory, so `mod a;` there is `a.rs` or
+/// `a/mod.rs` beside it; any other file `b.rs` keeps its modules in `b/`. A `#[path]`
+/// is relative to the directory of the file, or of the inline module it is in.
+///
+/// # Arguments
+/// * `file` - The relative path of the file with the declaration.
+/// * `is_root` - Whether the file is a crate root, such as `src/lib.rs`.
+/// * `declaration` - The declaration.
+///
+/// # Returns
+/// * `Vec<PathBuf>` - The candidate relative paths.
+pub(crate) fn candidates(file: &Path, is_root: bool, declaration: &ModDecl) -> Vec<PathBuf> {
+    let parent = file.parent().unwrap_or(Path::new(""));
+    if let Some(path) = declaration
+        .path
+        .as_ref()
+        .filter(|_| declaration.inline.is_empty())
+    {
+        return vec![normalize(&parent.join(path))];
+    }
+    let mut directory = parent.to_path_buf();
+    if !is_root && file.file_name().is_none_or(|name| name != "mod.rs") {
+        directory.extend(file.file_stem());
+    }
+    directory.extend(&declaration.inline);
+    match &declaration.path {
+        Some(path) => vec![normalize(&directory.join(path))],
+        None => vec![
+            directory.join(format!("{}.rs", declaration.name)),
+            directory.join(&declaration.name).join("mod.rs"),
+        ],
+    }
+}
+
+/// Resolves `.` and `..` in a relative path without touching the filesystem.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Checks whether a file is the root of a library or binary crate: `lib.rs` or `main.rs`
+/// in a `src` directory, or at the top of the input.
+pub(crate) fn is_crate_root(path: &Path) -> bool {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    path.file_name()
+        .is_some_and(|name| name == "lib.rs" || name == "main.rs")
+        && (parent.as_os_str().is_empty() || parent.file_name().is_some_and(|name| name == "src"))
+}
+
+/// Ranks files by a depth-first walk of the module trees of the crates among them.
+///
+/// Crate roots are taken in path order, a crate's `lib.rs` before its `main.rs`, and each
+/// module comes right after the file that declares it, in declaration order. Files that
+/// cannot be read, or declarations whose file is not among `entries`, are skipped with a
+/// debug message.
+///
+/// # Arguments
+/// * `entries` - The candidate files of the run.
+/// * `source` - The source the files are read from.
+///
+/// # Returns
+/// * `HashMap<PathBuf, usize>` - The rank of each file in a module tree, by relative path;
+///   files outside every tree have none.
+pub(crate) fn module_ranks(
+    entries: &[SourceFile],
+    source: &dyn FileSource,
+) -> HashMap<PathBuf, usize> {
+    let files: HashMap<&Path, &SourceFile> = entries
+        .iter()
+        .map(|entry| (entry.relative_path.as_path(), entry))
+        .collect();
+    let mut roots: Vec<&Path> = files
+        .keys()
+        .copied()
+        .filter(|path| is_crate_root(path))
+        .collect();
+    roots.sort_by_key(|path| {
+        (
+            path.parent(),
+            path.file_name().is_none_or(|n| n != "lib.rs"),
+        )
+    });
+    let mut ranks = HashMap::new();
+    for root in roots {
+        walk(root, true, &files, source, &mut ranks);
+    }
+    ranks
+}
+
+/// Ranks a file and then, recursively, the modules it declares.
+fn walk(
+    path: &Path,
+    is_root: bool,
+    files: &HashMap<&Path, &SourceFile>,
+    source: &dyn FileSource,
+    ranks: &mut HashMap<PathBuf, usize>,
+) {
+    if ranks.contains_key(path) {
+        return;
+    }
+    ranks.insert(path.to_path_buf(), ranks.len());
+    let content = match source.read(files[path]) {
+        Ok(content) => content,
+        Err(err) => {
+            debug!("Cannot read {} for its modules: {}", path.display(), err);
+            return;
+        }
+    };
+    for declaration in declarations(&content) {
+        let found = candidates(path, is_root, &declaration)
+            .into_iter()
+            .find(|candidate| files.contains_key(candidate.as_path()));
+        match found {
+            Some(module) => walk(&module, false, files, source, ranks),
+            None => debug!(
+                "Cannot find the file of `mod {}` in {}",
+                declaration.name,
+                path.display()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declaration(inline: &[&str], name: &str, path: Option<&str>) -> ModDecl {
+        ModDecl {
+            inline: inline.iter().map(|name| name.to_string()).collect(),
+            name: name.to_string(),
+            path: path.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn declarations_skip_comments_strings_and_bodies() {
+        let content = r#"
+//! mod docs;
+pub(crate) mod config;
+#[cfg(test)]
+mod tests;
+/* mod commented; */
+const S: &str = "mod quoted;";
+#[path = "platform/unix.rs"]
+mod sys;
+mod inline {
+    fn f() { if true {} }
+    mod nested;
+}
+mod r#async;
+fn g() { let mod_x = 1; }
+"#;
+        assert_eq!(
+            declarations(content),
+            [
+                declaration(&[], "config", None),
+                declaration(&[], "tests", None),
+                declaration(&[], "sys", Some("platform/unix.rs")),
+                declaration(&["inline"], "nested", None),
+                declaration(&[], "async", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn candidates_follow_the_module_path_rules() {
+        let plain = declaration(&[], "parser", None);
+        assert_eq!(
+            candidates(Path::new("src/lib.rs"), true, &plain),
+            [Path::new("src/parser.rs"), Path::new("src/parser/mod.rs")]
+        );
+        assert_eq!(
+            candidates(Path::new("src/net/mod.rs"), false, &plain),
+            [
+                Path::new("src/net/parser.rs"),
+                Path::new("src/net/parser/mod.rs")
+            ]
+        );
+        assert_eq!(
+            candidates(Path::new("src/net.rs"), false, &plain)[0],
+            Path::new("src/net/parser.rs")
+        );
+        let nested = declaration(&["a", "b"], "c", None);
+        assert_eq!(
+            candidates(Path::new("src/lib.rs"), true, &nested)[0],
+            Path::new("src/a/b/c.rs")
+        );
+        let attributed = declaration(&[], "sys", Some("../shared/./sys.rs"));
+        assert_eq!(
+            candidates(Path::new("src/net.rs"), false, &attributed),
+            [Path::new("shared/sys.rs")]
+        );
+    }
+}
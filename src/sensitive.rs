@@ -0,0 +1,155 @@
+//! Files that commonly hold secrets, such as `.env` files and private keys, left out of
+//! bundles unless `include_sensitive` is set.
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Globs of the files left out as sensitive, matched case-insensitively against the file
+/// name, or against the end of the path for the entries with a `/`.
+///
+/// Unlike `exclude`, the list applies whatever the ignore files say and whatever
+/// `include_paths` lists; only `include_sensitive` turns it off.
+pub const SENSITIVE_PATTERNS: &[&str] = &[
+    // Environment files
+    ".env",
+    ".env.*",
+    "*.env",
+    // Private keys and certificate stores
+    "*.pem",
+    "*.key",
+    "*.p12",
+    "*.pfx",
+    "*.jks",
+    "*.keystore",
+    "id_rsa",
+    "id_dsa",
+    "id_ecdsa",
+    "id_ed25519",
+    // Package registry and network credentials
+    ".npmrc",
+    ".pypirc",
+    ".netrc",
+    "_netrc",
+    ".git-credentials",
+    ".pgpass",
+    ".htpasswd",
+    ".aws/credentials",
+    ".docker/config.json",
+    // Credential files of cloud SDKs and tools
+    "credentials.json",
+    "service-account*.json",
+    "secrets.yml",
+    "secrets.yaml",
+    "*.tfstate",
+    "*.tfstate.backup",
+];
+
+/// Environment file templates, which hold placeholders rather than secrets and are meant to
+/// be read.
+pub const SENSITIVE_EXCEPTIONS: &[&str] = &[
+    ".env.example",
+    ".env.sample",
+    ".env.template",
+    ".env.dist",
+    "example.env",
+    "sample.env",
+];
+
+/// Compiles a list of patterns, anchoring each to a path component.
+fn glob_set(patterns: &[&str]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(&format!("**/{}", pattern))
+            .case_insensitive(true)
+            .literal_separator(true)
+            .build()
+            .expect("the built-in sensitive patterns are valid globs");
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .expect("the built-in sensitive patterns are valid globs")
+}
+
+/// Checks whether a file looks like it holds secrets.
+///
+/// # Arguments
+/// * `path` - The path of the file relative to the input directory.
+///
+/// # Returns
+/// * `bool` - `true` if the file matches [`SENSITIVE_PATTERNS`] and not
+///   [`SENSITIVE_EXCEPTIONS`].
+pub fn is_sensitive(path: &Path) -> bool {
+    static SETS: OnceLock<(GlobSet, GlobSet)> = OnceLock::new();
+    let (patterns, exceptions) =
+        SETS.get_or_init(|| (glob_set(SENSITIVE_PATTERNS), glob_set(SENSITIVE_EXCEPTIONS)));
+    patterns.is_match(path) && !exceptions.is_match(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file name matching each entry of the list, in the same order.
+    const EXAMPLES: &[&str] = &[
+        ".env",
+        ".env.local",
+        "production.env",
+        "server.pem",
+        "tls.key",
+        "client.p12",
+        "cert.pfx",
+        "release.jks",
+        "debug.keystore",
+        "id_rsa",
+        "id_dsa",
+        "id_ecdsa",
+        "id_ed25519",
+        ".npmrc",
+        ".pypirc",
+        ".netrc",
+        "_netrc",
+        ".git-credentials",
+        ".pgpass",
+        ".htpasswd",
+        ".aws/credentials",
+        ".docker/config.json",
+        "credentials.json",
+        "service-account-prod.json",
+        "secrets.yml",
+        "secrets.yaml",
+        "terraform.tfstate",
+        "terraform.tfstate.backup",
+    ];
+
+    #[test]
+    fn every_entry_is_sensitive_in_any_directory() {
+        assert_eq!(EXAMPLES.len(), SENSITIVE_PATTERNS.len());
+        for name in EXAMPLES {
+            assert!(is_sensitive(Path::new(name)), "{}", name);
+            let nested = Path::new("deploy/staging").join(name);
+            assert!(is_sensitive(&nested), "{}", nested.display());
+        }
+        assert!(is_sensitive(Path::new("config/.ENV")));
+    }
+
+    #[test]
+    fn templates_and_lookalikes_are_not_sensitive() {
+        for name in SENSITIVE_EXCEPTIONS {
+            assert!(!is_sensitive(Path::new(name)), "{}", name);
+        }
+        for path in [
+            "src/env.rs",
+            "id_rsa.pub",
+            "keys.rs",
+            "docs/credentials.md",
+            "credentials",
+            "config.json",
+            "src/key/mod.rs",
+            "environment.ts",
+        ] {
+            assert!(!is_sensitive(Path::new(path)), "{}", path);
+        }
+    }
+}
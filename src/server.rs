@@ -0,0 +1,415 @@
+//! An HTTP server for on-demand bundles (`serve`): `GET /bundle?dir=…` bundles a directory
+//! below a root and sends it back.
+//!
+//! The server is a small HTTP/1.1 implementation on the standard library, one thread and
+//! one request per connection, built with the `server` feature.
+
+#[cfg(feature = "server")]
+pub use imp::Server;
+
+use anyhow::Result;
+use std::path::Path;
+
+/// Serves bundles of the directories below `root` until Ctrl-C is pressed.
+///
+/// # Arguments
+/// * `addr` - The address to listen on, e.g. `127.0.0.1:7878`.
+/// * `root` - The directory whose subdirectories can be bundled.
+///
+/// # Returns
+/// * `Result<()>` - An error if the address cannot be bound or the root does not exist.
+#[cfg(feature = "server")]
+pub fn serve(addr: &str, root: &Path) -> Result<()> {
+    Server::bind(addr, root)?.run()
+}
+
+#[cfg(not(feature = "server"))]
+pub fn serve(addr: &str, _root: &Path) -> Result<()> {
+    anyhow::bail!("Cannot serve on {}: built without server support", addr)
+}
+
+#[cfg(feature = "server")]
+mod imp {
+    use crate::interrupt::{self, install_interrupt_handler};
+    use crate::mcp::request_config;
+    use crate::{Error, Format, run_to_writer};
+    use anyhow::{Context, Result};
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+    use std::path::{Component, Path, PathBuf};
+    use std::thread;
+    use std::time::Duration;
+    use tracing::{debug, info, warn};
+
+    /// How long a client has to send its request.
+    const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// The longest request line or header accepted, in bytes.
+    const MAX_LINE: u64 = 8 * 1024;
+
+    /// The most headers accepted in a request.
+    const MAX_HEADERS: usize = 100;
+
+    /// How long the server waits for a connection before checking for Ctrl-C again.
+    const ACCEPT_TICK: Duration = Duration::from_millis(50);
+
+    /// A bound HTTP server, ready to [`run`](Server::run).
+    #[derive(Debug)]
+    pub struct Server {
+        listener: TcpListener,
+        root: PathBuf,
+    }
+
+    impl Server {
+        /// Binds the server to an address.
+        ///
+        /// # Arguments
+        /// * `addr` - The address to listen on; port 0 picks a free port.
+        /// * `root` - The directory whose subdirectories can be bundled.
+        ///
+        /// # Returns
+        /// * `Result<Server>` - The server, or an error if the address cannot be bound or
+        ///   the root does not exist.
+        pub fn bind(addr: impl ToSocketAddrs, root: &Path) -> Result<Server> {
+            let root = root
+                .canonicalize()
+                .with_context(|| format!("Cannot serve {}", root.display()))?;
+            let listener = TcpListener::bind(addr).context("Failed to bind the server address")?;
+            Ok(Server { listener, root })
+        }
+
+        /// Returns the address the server listens on, e.g. to learn the port picked for 0.
+        pub fn local_addr(&self) -> Result<SocketAddr> {
+            self.listener
+                .local_addr()
+                .context("Failed to read the server address")
+        }
+
+        /// Answers requests until Ctrl-C is pressed, each connection on its own thread.
+        ///
+        /// Installs the [interrupt handler](crate::install_interrupt_handler); bundles being
+        /// written when it fires stop at their next file.
+        ///
+        /// # Returns
+        /// * `Result<()>` - `Ok(())` when interrupted, or an error if the address cannot be
+        ///   read; failed connections are logged and skipped.
+        pub fn run(self) -> Result<()> {
+            info!(
+                "Serving bundles of {} on http://{}",
+                self.root.display(),
+                self.local_addr()?
+            );
+            install_interrupt_handler();
+            // Accepting without blocking lets the loop notice an interrupt.
+            self.listener
+                .set_nonblocking(true)
+                .context("Failed to configure the server socket")?;
+            while !interrupt::requested() {
+                match self.listener.accept() {
+                    Ok((stream, _)) => {
+                        let root = self.root.clone();
+                        thread::spawn(move || {
+                            let handled = stream
+                                .set_nonblocking(false)
+                                .map_err(Into::into)
+                                .and_then(|()| handle_connection(stream, &root));
+                            if let Err(err) = handled {
+                                debug!("Connection failed: {:#}", err);
+                            }
+                        });
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_TICK);
+                    }
+                    Err(err) => warn!("Failed to accept a connection: {}", err),
+                }
+            }
+            info!("Interrupted; no longer taking requests");
+            Ok(())
+        }
+    }
+
+    /// A response: the status, the content type, the estimated tokens of a bundle, and the
+    /// body.
+    #[derive(Debug)]
+    struct Response {
+        status: u16,
+        content_type: &'static str,
+        tokens: Option<usize>,
+        body: Vec<u8>,
+    }
+
+    impl Response {
+        /// A plain-text error.
+        fn error(status: u16, message: impl Into<String>) -> Response {
+            let mut body = message.into().into_bytes();
+            body.push(b'\n');
+            Response {
+                status,
+                content_type: "text/plain; charset=utf-8",
+                tokens: None,
+                body,
+            }
+        }
+
+        /// Writes the response; the connection is closed after it.
+        fn write_to(&self, stream: &mut impl Write, head_only: bool) -> std::io::Result<()> {
+            write!(
+                stream,
+                "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n",
+                self.status,
+                reason_phrase(self.status),
+                self.content_type,
+                self.body.len()
+            )?;
+            if let Some(tokens) = self.tokens {
+                write!(stream, "X-C2P-Tokens: {}\r\n", tokens)?;
+            }
+            write!(stream, "Connection: close\r\n\r\n")?;
+            if !head_only {
+                stream.write_all(&self.body)?;
+            }
+            stream.flush()
+        }
+    }
+
+    /// Reads one request from a connection and answers it.
+    fn handle_connection(mut stream: TcpStream, root: &Path) -> Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let request_line = read_line(&mut reader)?;
+        for _ in 0..MAX_HEADERS {
+            if read_line(&mut reader)?.is_empty() {
+                break;
+            }
+        }
+        let mut parts = request_line.split(' ');
+        let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+        let response = match method {
+            "GET" | "HEAD" => respond(target, root),
+            _ => Response::error(405, "Only GET requests are served"),
+        };
+        info!(
+            "{} {} -> {} ({} bytes)",
+            method,
+            target,
+            response.status,
+            response.body.len()
+        );
+        response.write_to(&mut stream, method == "HEAD")?;
+        Ok(())
+    }
+
+    /// Reads a line of the request head, without its line ending.
+    fn read_line(reader: &mut impl BufRead) -> Result<String> {
+        let mut line = String::new();
+        reader.by_ref().take(MAX_LINE).read_line(&mut line)?;
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    /// Answers a request for a path and query.
+    fn respond(target: &str, root: &Path) -> Response {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        if path != "/bundle" {
+            return Response::error(404, format!("Not found: {} (try /bundle?dir=…)", path));
+        }
+        match bundle(query, root) {
+            Ok(response) | Err(response) => response,
+        }
+    }
+
+    /// Bundles the directory a `/bundle` query names.
+    fn bundle(query: &str, root: &Path) -> Result<Response, Response> {
+        let mut dir = None;
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+        let mut format = Format::Markdown;
+        let mut max_tokens = None;
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)
+                .ok_or_else(|| Response::error(400, format!("Invalid encoding in `{}`", pair)))?;
+            let list = |value: &str| -> Vec<String> {
+                value
+                    .split(',')
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            };
+            match key {
+                "dir" => dir = Some(value),
+                "include" => include.extend(list(&value)),
+                "exclude" => exclude.extend(list(&value)),
+                "format" => format = value.parse().map_err(|err| Response::error(400, err))?,
+                "max_tokens" => {
+                    max_tokens = Some(value.parse::<usize>().map_err(|_| {
+                        Response::error(400, format!("Invalid max_tokens `{}`", value))
+                    })?)
+                }
+                _ => return Err(Response::error(400, format!("Unknown parameter `{}`", key))),
+            }
+        }
+        let dir = dir.ok_or_else(|| Response::error(400, "Missing the `dir` parameter"))?;
+        let directory = resolve(root, &dir)?;
+
+        let mut config = request_config(&directory, include, exclude)
+            .map_err(|err| Response::error(500, format!("{:#}", err)))?;
+        config.format = format;
+        config.fit_budget = max_tokens;
+        let mut body = Vec::new();
+        let summary = run_to_writer(&config, &mut body).map_err(|err| {
+            let status = if matches!(err, Error::NoFilesMatched(_)) {
+                422
+            } else {
+                500
+            };
+            Response::error(status, format!("{:#}", err))
+        })?;
+        Ok(Response {
+            status: 200,
+            content_type: content_type(format),
+            tokens: Some(summary.estimated_tokens),
+            body,
+        })
+    }
+
+    /// Resolves a `dir` parameter below the root, rejecting any path that leaves it,
+    /// including through a symbolic link.
+    fn resolve(root: &Path, dir: &str) -> Result<PathBuf, Response> {
+        let relative = Path::new(dir);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(Response::error(
+                403,
+                format!("`{}` is not a directory below the served root", dir),
+            ));
+        }
+        let directory = root
+            .join(relative)
+            .canonicalize()
+            .map_err(|_| Response::error(404, format!("Directory not found: {}", dir)))?;
+        if !directory.starts_with(root) {
+            return Err(Response::error(
+                403,
+                format!("`{}` is not a directory below the served root", dir),
+            ));
+        }
+        if !directory.is_dir() {
+            return Err(Response::error(
+                404,
+                format!("Directory not found: {}", dir),
+            ));
+        }
+        Ok(directory)
+    }
+
+    /// Decodes `%XX` escapes and `+` for spaces in a query value.
+    fn percent_decode(value: &str) -> Option<String> {
+        let bytes = value.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'%' => {
+                    let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+                    decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                    i += 3;
+                }
+                b'+' => {
+                    decoded.push(b' ');
+                    i += 1;
+                }
+                byte => {
+                    decoded.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8(decoded).ok()
+    }
+
+    /// Returns the content type of a format.
+    fn content_type(format: Format) -> &'static str {
+        match format {
+            Format::Markdown => "text/markdown; charset=utf-8",
+            Format::Text | Format::Console => "text/plain; charset=utf-8",
+            Format::ClaudeXml => "application/xml; charset=utf-8",
+            Format::ChatJson => "application/json",
+            Format::Asciidoc => "text/asciidoc; charset=utf-8",
+            Format::Tar => "application/x-tar",
+        }
+    }
+
+    /// Returns the reason phrase of a status code.
+    fn reason_phrase(status: u16) -> &'static str {
+        match status {
+            200 => "OK",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            422 => "Unprocessable Content",
+            _ => "Internal Server Error",
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn decodes_query_values() {
+            assert_eq!(percent_decode("a%2Cb+c").as_deref(), Some("a,b c"));
+            assert_eq!(percent_decode("caf%C3%A9").as_deref(), Some("café"));
+            assert_eq!(percent_decode("%2"), None);
+            assert_eq!(percent_decode("%zz"), None);
+        }
+
+        #[test]
+        fn rejects_directories_outside_the_root() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let root = temp_dir.path().canonicalize().unwrap();
+            std::fs::create_dir(root.join("repo")).unwrap();
+            assert_eq!(resolve(&root, "./repo").unwrap(), root.join("repo"));
+            for dir in ["../etc", "repo/../..", "/etc"] {
+                let status = resolve(&root, dir)
+                    .map(|_| 200)
+                    .unwrap_or_else(|r| r.status);
+                assert_ne!(status, 200, "{}", dir);
+            }
+            assert_eq!(resolve(&root, "missing").unwrap_err().status, 404);
+            #[cfg(unix)]
+            {
+                std::os::unix::fs::symlink("/", root.join("escape")).unwrap();
+                assert_eq!(resolve(&root, "escape").unwrap_err().status, 403);
+            }
+        }
+
+        #[test]
+        fn config_files_cannot_reach_files_outside_the_root() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let outside = temp_dir.path().join("outside.txt");
+            std::fs::write(&outside, "private notes").unwrap();
+            let root = temp_dir.path().canonicalize().unwrap().join("root");
+            let repo = root.join("repo");
+            std::fs::create_dir_all(&repo).unwrap();
+            std::fs::write(repo.join("main.rs"), "fn main() {}\n").unwrap();
+            std::fs::write(repo.join("list.txt"), format!("{}\n", outside.display())).unwrap();
+
+            for config in [
+                format!("preamble = \"@{}\"\n", outside.display()),
+                "postamble = \"@../../outside.txt\"\n".to_string(),
+                "files_from = \"list.txt\"\n".to_string(),
+                "include_paths = [\"../outside.txt\"]\n".to_string(),
+            ] {
+                std::fs::write(repo.join(".c2p.toml"), &config).unwrap();
+                let response = bundle("dir=repo", &root).unwrap_or_else(|response| response);
+                let body = String::from_utf8_lossy(&response.body);
+                assert_ne!(response.status, 200, "{}", config);
+                assert!(!body.contains("private notes"), "{}", body);
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! The languages of extensionless scripts, read from their `#!` line (`shebang_detect`).
+
+use crate::languages::Language;
+use std::borrow::Cow;
+use std::path::Path;
+
+/// Interpreters, without a version suffix, and the languages of their scripts. Scripts for
+/// any other interpreter are plain text.
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("python", "python"),
+    ("pypy", "python"),
+    ("bash", "bash"),
+    ("sh", "bash"),
+    ("zsh", "bash"),
+    ("dash", "bash"),
+    ("ksh", "bash"),
+    ("node", "javascript"),
+    ("nodejs", "javascript"),
+    ("ruby", "ruby"),
+    ("perl", "perl"),
+];
+
+/// The language a script falls back to when its interpreter is not in [`INTERPRETERS`].
+const FALLBACK: &str = "text";
+
+/// Finds the language of a script from its `#!` line, e.g. `#!/usr/bin/env python3`.
+///
+/// `env` is looked through, with its options and variable assignments, and version
+/// suffixes such as the `3.12` of `python3.12` are dropped.
+///
+/// # Arguments
+/// * `head` - The start of the file, such as the block read to check for binary content.
+///
+/// # Returns
+/// * `Option<&'static Language>` - The language, plain text for an unknown interpreter, or
+///   `None` if the file does not start with `#!`.
+pub(crate) fn language(head: &str) -> Option<&'static Language> {
+    let line = head.lines().next()?.strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let mut program = file_name(words.next()?);
+    if program == "env" {
+        program = file_name(words.find(|word| !word.starts_with('-') && !word.contains('='))?);
+    }
+    let name = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let language = INTERPRETERS
+        .iter()
+        .find(|(interpreter, _)| *interpreter == name)
+        .map_or(FALLBACK, |(_, language)| language);
+    Language::find(language)
+}
+
+/// Returns the last component of an interpreter path, e.g. `bash` for `/bin/bash`.
+fn file_name(program: &str) -> &str {
+    program.rsplit('/').next().unwrap_or(program)
+}
+
+/// Names a script after its language: the path with the language's first extension added,
+/// e.g. `bin/deploy.py`, so rules and tables keyed by extension apply to it.
+///
+/// # Arguments
+/// * `path` - The path of the file.
+/// * `language` - The language of its `#!` line.
+///
+/// # Returns
+/// * `Cow<Path>` - The path with the extension, or the path itself if the language has none.
+pub(crate) fn language_path<'a>(path: &'a Path, language: &Language) -> Cow<'a, Path> {
+    match language.extensions.first() {
+        Some(extension) => Cow::Owned(path.with_extension(extension)),
+        None => Cow::Borrowed(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpreters_map_to_languages() {
+        for (head, name) in [
+            ("#!/usr/bin/env python3\nimport sys\n", "python"),
+            ("#!/usr/bin/python3.12 -u\n", "python"),
+            ("#!/bin/bash\nset -e\n", "bash"),
+            ("#!/bin/sh", "bash"),
+            ("#! /usr/bin/env zsh\n", "bash"),
+            ("#!/usr/bin/env -S node --no-warnings\n", "javascript"),
+            ("#!/usr/bin/env NODE_ENV=production node\n", "javascript"),
+            ("#!/usr/bin/ruby\n", "ruby"),
+            ("#!/usr/bin/perl -w\n", "perl"),
+            ("#!/usr/bin/awk -f\n", "text"),
+        ] {
+            assert_eq!(
+                language(head).map(|language| language.name),
+                Some(name),
+                "{}",
+                head
+            );
+        }
+        for head in ["", "import sys\n#!/bin/sh\n", "#!\n", "#!/usr/bin/env\n"] {
+            assert_eq!(language(head), None, "{:?}", head);
+        }
+    }
+
+    #[test]
+    fn scripts_are_named_after_their_language() {
+        let python = Language::find("python").unwrap();
+        assert_eq!(
+            language_path(Path::new("bin/deploy"), python),
+            Path::new("bin/deploy.py")
+        );
+    }
+}
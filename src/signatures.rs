@@ -0,0 +1,523 @@
+//! Elision of function bodies, so a file is reduced to its declarations.
+//!
+//! This is a heuristic on top of the comment tokenizer rather than a parser: braces, colons,
+//! and keywords inside comments and string literals are ignored, and everything else is
+//! matched by shape. In Rust, Go, JavaScript, and TypeScript, a `{ … }` block is a function
+//! body when the code before it (since the last `;`, `{`, or `}`) declares a function:
+//! `fn`, `func`, `function`, an arrow `=>`, or a method in a class body. In Python, the
+//! indented block of a `def` is replaced with `...`, after its docstring if it has one.
+//!
+//! Signatures, type definitions, imports, and doc comments are left as they are, and files
+//! in other languages pass through untouched.
+
+use crate::comments::{Syntax, Token, tokens};
+
+/// A language whose function bodies can be elided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    Rust,
+    Go,
+    JavaScript,
+    Python,
+}
+
+impl Language {
+    /// Returns the language of a file extension, if its bodies can be elided.
+    fn from_extension(extension: &str) -> Option<Self> {
+        let language = match extension.to_ascii_lowercase().as_str() {
+            "rs" => Language::Rust,
+            "go" => Language::Go,
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" => Language::JavaScript,
+            "py" | "pyi" => Language::Python,
+            _ => return None,
+        };
+        Some(language)
+    }
+
+    /// Checks whether a `{` preceded by `header` opens a function body.
+    ///
+    /// # Arguments
+    /// * `header` - The code since the last `;`, `{`, or `}`, with comments and literals
+    ///   blanked out.
+    /// * `in_class` - Whether the brace is directly inside a JavaScript class body.
+    fn opens_body(self, header: &[u8], in_class: bool) -> bool {
+        match self {
+            Language::Rust => has_word(header, "fn"),
+            Language::Go => has_word(header, "func"),
+            Language::JavaScript => {
+                has_word(header, "function")
+                    || header.trim_ascii_end().ends_with(b"=>")
+                    || (in_class && header.contains(&b'('))
+            }
+            Language::Python => false,
+        }
+    }
+}
+
+/// Checks whether a file's function bodies can be elided.
+///
+/// # Arguments
+/// * `extension` - The file extension, without the leading dot.
+///
+/// # Returns
+/// * `bool` - `true` for Rust, Go, JavaScript, TypeScript, and Python files.
+pub(crate) fn is_supported(extension: &str) -> bool {
+    Language::from_extension(extension).is_some()
+}
+
+/// Replaces the function bodies in `content` with `{ … }`, or `...` in Python.
+///
+/// Content in an unsupported language is returned unchanged.
+///
+/// # Arguments
+/// * `content` - The file content.
+/// * `extension` - The file extension, used to select the language.
+///
+/// # Returns
+/// * `String` - The content with its function bodies elided.
+pub(crate) fn signatures_only(content: &str, extension: &str) -> String {
+    match Language::from_extension(extension) {
+        Some(Language::Python) => elide_indented(content),
+        Some(language) => elide_braced(content, language),
+        None => content.to_string(),
+    }
+}
+
+/// What an open `{` belongs to, while scanning a braced language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Brace {
+    /// A JavaScript class body, whose braced members are methods.
+    Class,
+    /// Any other block at the top level of a statement.
+    Block,
+    /// A brace inside parentheses or brackets, e.g. an object literal argument.
+    Nested,
+}
+
+/// Elides function bodies in a language that delimits them with braces.
+fn elide_braced(content: &str, language: Language) -> String {
+    let syntax = if language == Language::Rust {
+        Syntax::Rust
+    } else {
+        Syntax::CLike
+    };
+    let (code, _) = blank_out(content, syntax);
+    let mut result = String::with_capacity(content.len());
+    let mut copied = 0;
+    let mut header_start = 0;
+    let mut braces = Vec::new();
+    let mut parens = 0usize;
+    let mut i = 0;
+    while i < code.len() {
+        match code[i] {
+            b'(' | b'[' => parens += 1,
+            b')' | b']' => parens = parens.saturating_sub(1),
+            b'{' if parens > 0 => braces.push(Brace::Nested),
+            b'{' => {
+                let header = &code[header_start..i];
+                let in_class = braces.last() == Some(&Brace::Class);
+                if language.opens_body(header, in_class) {
+                    let Some(close) = matching_brace(&code, i) else {
+                        break;
+                    };
+                    // An empty body has nothing to elide.
+                    if !content[i + 1..close].trim().is_empty() {
+                        result.push_str(&content[copied..=i]);
+                        result.push_str(" … }");
+                        copied = close + 1;
+                    }
+                    i = close + 1;
+                    header_start = i;
+                    continue;
+                }
+                let class = language == Language::JavaScript && has_word(header, "class");
+                braces.push(if class { Brace::Class } else { Brace::Block });
+                header_start = i + 1;
+            }
+            b'}' if braces.pop() != Some(Brace::Nested) => header_start = i + 1,
+            b';' if parens == 0 => header_start = i + 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    result.push_str(&content[copied..]);
+    result
+}
+
+/// Elides the bodies of Python `def` blocks, keeping docstrings.
+fn elide_indented(content: &str) -> String {
+    let (code, literals) = blank_out(content, Syntax::Hash);
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for line in content.split_inclusive('\n') {
+        lines.push(start..start + line.len());
+        start += line.len();
+    }
+    let line_of = |offset: usize| lines.partition_point(|line| line.end <= offset);
+
+    let mut result = String::with_capacity(content.len());
+    let mut n = 0;
+    while n < lines.len() {
+        let line = lines[n].clone();
+        let indent = indentation(&code[line.clone()]);
+        let Some(colon) = def_colon(&code, line.start + indent) else {
+            result.push_str(&content[line]);
+            n += 1;
+            continue;
+        };
+        let header_end = line_of(colon);
+        let header = &content[lines[header_end].clone()];
+        let ending = if header.ends_with("\r\n") {
+            "\r\n"
+        } else if header.ends_with('\n') {
+            "\n"
+        } else {
+            ""
+        };
+
+        // `def f(): return 1` keeps its body on the header line.
+        if !code[colon + 1..lines[header_end].end]
+            .trim_ascii()
+            .is_empty()
+        {
+            result.push_str(&content[line.start..=colon]);
+            result.push_str(" ...");
+            result.push_str(ending);
+            n = header_end + 1;
+            continue;
+        }
+        result.push_str(&content[line.start..lines[header_end].end]);
+
+        // The body runs until the first line indented no deeper than the `def`. Lines that
+        // are blank once literals are blanked out continue a multi-line string.
+        let mut body_end = header_end + 1;
+        let mut first = None;
+        for (m, body_line) in lines.iter().enumerate().skip(header_end + 1) {
+            let text = &content.as_bytes()[body_line.clone()];
+            if text.trim_ascii().is_empty() {
+                continue;
+            }
+            let continues_literal = code[body_line.clone()].trim_ascii().is_empty()
+                && text.trim_ascii_start().first() != Some(&b'#');
+            if !continues_literal && indentation(text) <= indent {
+                break;
+            }
+            first.get_or_insert(m);
+            body_end = m + 1;
+        }
+        let Some(first) = first else {
+            n = header_end + 1;
+            continue;
+        };
+
+        let first_line = lines[first].clone();
+        let body_indent = indentation(&content.as_bytes()[first_line.clone()]);
+        let docstring = literals
+            .iter()
+            .find(|literal| literal.start == first_line.start + body_indent);
+        if let Some(docstring) = docstring {
+            let docstring_end = line_of(docstring.end - 1);
+            result.push_str(&content[lines[header_end + 1].start..lines[docstring_end].end]);
+        }
+        result.push_str(&content[first_line.start..first_line.start + body_indent]);
+        result.push_str("...");
+        result.push_str(ending);
+        n = body_end;
+    }
+    result
+}
+
+/// Finds the colon that ends the header of a `def` starting at `offset`, if one does.
+fn def_colon(code: &[u8], offset: usize) -> Option<usize> {
+    let rest = &code[offset..];
+    let rest = rest
+        .strip_prefix(b"async")
+        .map_or(rest, <[u8]>::trim_ascii_start);
+    if !rest.starts_with(b"def") || !rest.get(3).is_some_and(u8::is_ascii_whitespace) {
+        return None;
+    }
+    let open = offset + code[offset..].iter().position(|b| *b == b'(')?;
+    let mut depth = 0usize;
+    for (i, byte) in code.iter().enumerate().skip(open) {
+        match byte {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth = depth.saturating_sub(1),
+            b':' if depth == 0 => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Returns the index of the `}` closing the `{` at `open`, if it is closed.
+fn matching_brace(code: &[u8], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, byte) in code.iter().enumerate().skip(open) {
+        match byte {
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Copies the content with every comment and literal byte replaced by a space, except
+/// newlines, so braces and keywords can be matched by position.
+///
+/// # Returns
+/// * `(Vec<u8>, Vec<Range<usize>>)` - The blanked-out bytes and the literal ranges.
+fn blank_out(content: &str, syntax: Syntax) -> (Vec<u8>, Vec<std::ops::Range<usize>>) {
+    let mut code = content.as_bytes().to_vec();
+    let mut literals = Vec::new();
+    for (range, token) in tokens(content, syntax) {
+        for byte in &mut code[range.clone()] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+        if token == Token::Literal {
+            literals.push(range);
+        }
+    }
+    (code, literals)
+}
+
+/// Returns the number of leading spaces and tabs.
+fn indentation(line: &[u8]) -> usize {
+    line.iter()
+        .take_while(|b| matches!(b, b' ' | b'\t'))
+        .count()
+}
+
+/// Checks whether `text` contains `word` as a whole identifier.
+fn has_word(text: &[u8], word: &str) -> bool {
+    let word = word.as_bytes();
+    let is_ident = |b: &u8| b.is_ascii_alphanumeric() || *b == b'_' || *b == b'$';
+    text.windows(word.len()).enumerate().any(|(i, window)| {
+        window == word
+            && !i
+                .checked_sub(1)
+                .is_some_and(|before| is_ident(&text[before]))
+            && !text.get(i + word.len()).is_some_and(is_ident)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_keeps_declarations_and_elides_bodies() {
+        let source = "\
+use std::fmt;
+
+/// A point.
+pub struct Point {
+    x: i32,
+}
+
+impl Point {
+    /// Creates a point.
+    pub fn new(x: i32) -> Self {
+        let s = \"}\";
+        Point { x }
+    }
+
+    fn empty(&self) {}
+}
+
+trait Shape {
+    fn area(&self) -> f64;
+    fn name(&self) -> &str {
+        \"shape\"
+    }
+}
+";
+        let expected = "\
+use std::fmt;
+
+/// A point.
+pub struct Point {
+    x: i32,
+}
+
+impl Point {
+    /// Creates a point.
+    pub fn new(x: i32) -> Self { … }
+
+    fn empty(&self) {}
+}
+
+trait Shape {
+    fn area(&self) -> f64;
+    fn name(&self) -> &str { … }
+}
+";
+        assert_eq!(signatures_only(source, "rs"), expected);
+    }
+
+    #[test]
+    fn go_elides_functions_and_methods() {
+        let source = "\
+package main
+
+import \"fmt\"
+
+type Server struct {
+\tAddr string
+}
+
+// Start runs the server.
+func (s *Server) Start(opts struct{ Debug bool }) error {
+\tfmt.Println(\"{\")
+\treturn nil
+}
+
+func main() {
+\tnew(Server).Start(struct{ Debug bool }{})
+}
+";
+        let expected = "\
+package main
+
+import \"fmt\"
+
+type Server struct {
+\tAddr string
+}
+
+// Start runs the server.
+func (s *Server) Start(opts struct{ Debug bool }) error { … }
+
+func main() { … }
+";
+        assert_eq!(signatures_only(source, "go"), expected);
+    }
+
+    #[test]
+    fn typescript_elides_functions_methods_and_arrows() {
+        let source = "\
+import { api } from './api';
+
+export interface User {
+  name: string;
+}
+
+/** Loads a user. */
+export async function load(id: string, opts: { cache: boolean }): Promise<User> {
+  return api.get(`/users/${id}`);
+}
+
+export class Store {
+  private users = new Map<string, User>();
+
+  get(id: string): User | undefined {
+    return this.users.get(id);
+  }
+
+  onChange = (user: User) => {
+    this.users.set(user.name, user);
+  };
+}
+
+export const double = (n: number) => {
+  return n * 2;
+};
+";
+        let expected = "\
+import { api } from './api';
+
+export interface User {
+  name: string;
+}
+
+/** Loads a user. */
+export async function load(id: string, opts: { cache: boolean }): Promise<User> { … }
+
+export class Store {
+  private users = new Map<string, User>();
+
+  get(id: string): User | undefined { … }
+
+  onChange = (user: User) => { … };
+}
+
+export const double = (n: number) => { … };
+";
+        assert_eq!(signatures_only(source, "ts"), expected);
+    }
+
+    #[test]
+    fn python_elides_def_bodies_and_keeps_docstrings() {
+        let source = "\
+import os
+
+
+class Loader:
+    \"\"\"Loads files.\"\"\"
+
+    def __init__(self, root: str) -> None:
+        self.root = root
+
+    @property
+    def files(self) -> list[str]:
+        \"\"\"The files under the root.
+
+        Sorted by name.
+        \"\"\"
+        text = \"\"\"
+def not_a_function():
+\"\"\"
+        return sorted(os.listdir(self.root))
+
+    def size(self): return 0
+
+
+async def fetch(
+    url: str,
+) -> bytes:
+    # Retry once.
+    return b\"\"
+";
+        let expected = "\
+import os
+
+
+class Loader:
+    \"\"\"Loads files.\"\"\"
+
+    def __init__(self, root: str) -> None:
+        ...
+
+    @property
+    def files(self) -> list[str]:
+        \"\"\"The files under the root.
+
+        Sorted by name.
+        \"\"\"
+        ...
+
+    def size(self): ...
+
+
+async def fetch(
+    url: str,
+) -> bytes:
+    ...
+";
+        assert_eq!(signatures_only(source, "py"), expected);
+    }
+
+    #[test]
+    fn other_languages_pass_through() {
+        let source = "int main() {\n  return 0;\n}\n";
+        assert!(!is_supported("c"));
+        assert_eq!(signatures_only(source, "c"), source);
+    }
+}
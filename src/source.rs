@@ -0,0 +1,373 @@
+//! Where the files to bundle come from: the filesystem walker or an in-memory map.
+
+use crate::nested_config::NestedScope;
+use crate::{Config, FileFilter, SkipReason, TOOL_IGNORE_FILE_NAMES, ignore_file_names};
+use anyhow::{Context, Result};
+use ignore::gitignore::GitignoreBuilder;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// A file offered by a [`FileSource`].
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// The path the source uses to read the file, e.g. its location on disk.
+    pub path: PathBuf,
+    /// The path relative to the root of the source, used for filtering and display.
+    pub relative_path: PathBuf,
+    /// The path shown in the output, after `rename_paths` is applied.
+    pub(crate) display_path: PathBuf,
+    /// Whether the file is listed in `include_paths`, so only binary detection can skip it.
+    pub(crate) forced: bool,
+    /// The nested configuration that applies to the file, if any.
+    pub(crate) scope: Option<Arc<NestedScope>>,
+}
+
+impl SourceFile {
+    /// Creates a source file.
+    ///
+    /// # Arguments
+    /// * `path` - The path the source uses to read the file.
+    /// * `relative_path` - The path relative to the root of the source.
+    ///
+    /// # Returns
+    /// * `SourceFile` - The file, displayed under its relative path until renamed.
+    pub fn new(path: impl Into<PathBuf>, relative_path: impl Into<PathBuf>) -> Self {
+        let relative_path = relative_path.into();
+        SourceFile {
+            path: path.into(),
+            display_path: relative_path.clone(),
+            relative_path,
+            forced: false,
+            scope: None,
+        }
+    }
+
+    /// Returns the configuration of the file: its nested configuration, or that of the run.
+    pub(crate) fn config<'a>(&'a self, config: &'a Config) -> &'a Config {
+        self.scope.as_ref().map_or(config, |scope| &scope.config)
+    }
+
+    /// Returns the path filters of the file: those of its nested configuration, or of the run.
+    pub(crate) fn filter<'a>(&'a self, filter: &'a FileFilter) -> &'a FileFilter {
+        self.scope.as_ref().map_or(filter, |scope| &scope.filter)
+    }
+}
+
+/// Supplies the candidate files of a bundle and their contents.
+///
+/// Sources apply their own walk filters (hidden files, `.gitignore` rules); extension
+/// filters, ordering, transformations, and formatting are applied on top of any source.
+pub trait FileSource: Sync {
+    /// Lists the candidate files, each relative path at most once, in any order.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    /// * `on_skipped` - Called for each path the source leaves out.
+    ///
+    /// # Returns
+    /// * `Result<Vec<SourceFile>>` - The candidate files, or an error if they cannot be listed.
+    fn list(
+        &self,
+        config: &Config,
+        on_skipped: &mut dyn FnMut(&Path, SkipReason),
+    ) -> Result<Vec<SourceFile>>;
+
+    /// Finds a file by its relative path, whether or not [`list`](FileSource::list) would
+    /// list it, for `include_paths`.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    /// * `relative_path` - The path of the file relative to the root of the source.
+    ///
+    /// # Returns
+    /// * `Option<SourceFile>` - The file, or `None` if the source has no such file.
+    fn find(&self, config: &Config, relative_path: &Path) -> Option<SourceFile> {
+        let _ = (config, relative_path);
+        None
+    }
+
+    /// Reads the content of a listed file.
+    ///
+    /// # Arguments
+    /// * `file` - A file returned by [`list`](FileSource::list).
+    ///
+    /// # Returns
+    /// * `io::Result<String>` - The content, or an `InvalidData` error if it is not UTF-8 text.
+    fn read(&self, file: &SourceFile) -> io::Result<String>;
+
+    /// Reads the raw bytes of a listed file, used to embed binary files (`embed_binary`).
+    ///
+    /// # Arguments
+    /// * `file` - A file returned by [`list`](FileSource::list).
+    ///
+    /// # Returns
+    /// * `io::Result<Vec<u8>>` - The content, whether or not it is text.
+    fn read_bytes(&self, file: &SourceFile) -> io::Result<Vec<u8>> {
+        self.read(file).map(String::into_bytes)
+    }
+
+    /// Reads the first bytes of a listed file, used to look for an include marker in files
+    /// the include and exclude lists leave out.
+    ///
+    /// # Arguments
+    /// * `file` - A file returned by [`list`](FileSource::list).
+    /// * `len` - The most bytes to read.
+    ///
+    /// # Returns
+    /// * `io::Result<Vec<u8>>` - Up to `len` bytes from the start of the file.
+    fn read_head(&self, file: &SourceFile, len: usize) -> io::Result<Vec<u8>> {
+        let mut content = self.read(file)?.into_bytes();
+        content.truncate(len);
+        Ok(content)
+    }
+
+    /// Returns the size of a file in bytes, used by `--sort size`.
+    fn len(&self, file: &SourceFile) -> Option<u64> {
+        let _ = file;
+        None
+    }
+
+    /// Returns the modification time of a file, used by `--sort mtime`.
+    fn modified(&self, file: &SourceFile) -> Option<SystemTime> {
+        let _ = file;
+        None
+    }
+
+    /// Returns the files the source knows to be deleted, listed after the last file; only
+    /// the changes of a `between` run have any.
+    fn deleted(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Returns whether each listed file is a file on disk at its `path`, so a large text
+    /// file can be streamed from there into the output.
+    fn on_disk(&self) -> bool {
+        false
+    }
+}
+
+/// A source backed by an in-memory map from relative path to content.
+///
+/// Hidden files are filtered like on disk, and `.gitignore` files in the map are respected.
+/// Paths use `/` separators.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    files: BTreeMap<PathBuf, String>,
+}
+
+impl MemorySource {
+    /// Creates an empty source.
+    pub fn new() -> Self {
+        MemorySource::default()
+    }
+
+    /// Adds a file, replacing any file with the same path.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the file relative to the root.
+    /// * `content` - The content of the file.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, content: impl Into<String>) {
+        self.files.insert(path.into(), content.into());
+    }
+}
+
+/// Lists the paths of an in-memory tree, leaving out hidden and gitignored files as on disk.
+///
+/// # Arguments
+/// * `paths` - The relative paths of the files, with `/` separators.
+/// * `gitignores` - The path and content of each ignore file in the tree; files not named in
+///   [`ignore_file_names`] are left out.
+/// * `config` - The configuration options for the bundling process.
+/// * `on_skipped` - Called for each path left out.
+///
+/// # Returns
+/// * `Result<Vec<SourceFile>>` - The files to bundle, or an error if a `.gitignore` rule is invalid.
+pub(crate) fn list_tree<'a>(
+    paths: impl IntoIterator<Item = &'a PathBuf>,
+    gitignores: impl IntoIterator<Item = (&'a Path, &'a str)>,
+    config: &Config,
+    on_skipped: &mut dyn FnMut(&Path, SkipReason),
+) -> Result<Vec<SourceFile>> {
+    // The ignore files of one directory form one matcher, in order of precedence.
+    let names = ignore_file_names(config);
+    let mut by_directory: BTreeMap<&Path, Vec<(usize, &Path, &str)>> = BTreeMap::new();
+    for (path, rules) in gitignores {
+        let Some(rank) = path
+            .file_name()
+            .and_then(|name| names.iter().position(|known| name == *known))
+        else {
+            continue;
+        };
+        let directory = path.parent().unwrap_or(Path::new(""));
+        by_directory
+            .entry(directory)
+            .or_default()
+            .push((rank, path, rules));
+    }
+    let mut matchers = Vec::new();
+    for (directory, mut files) in by_directory {
+        files.sort_by_key(|(rank, _, _)| *rank);
+        let mut builder = GitignoreBuilder::new(directory);
+        for (_, path, rules) in files {
+            for line in rules.lines() {
+                builder
+                    .add_line(None, line)
+                    .with_context(|| format!("Invalid rule `{}` in {}", line, path.display()))?;
+            }
+        }
+        matchers.push(builder.build()?);
+    }
+    // The deepest directory with an opinion wins, as on disk.
+    matchers.sort_by_key(|matcher| Reverse(matcher.path().components().count()));
+
+    let filter = FileFilter::new(config)?;
+    let mut files = Vec::new();
+    for path in paths {
+        let ignored = || {
+            matchers
+                .iter()
+                .filter(|matcher| path.starts_with(matcher.path()))
+                .map(|matcher| matcher.matched_path_or_any_parents(path, false))
+                .find(|matched| !matched.is_none())
+                .is_some_and(|matched| matched.is_ignore())
+        };
+        if let Some(reason) = filter.path_exclusion(path, false) {
+            on_skipped(path, reason);
+        } else if ignored() {
+            on_skipped(path, SkipReason::Ignored);
+        } else {
+            files.push(SourceFile::new(path, path));
+        }
+    }
+    Ok(files)
+}
+
+/// Checks whether a path names a `.gitignore`, `.ignore`, or `.rgignore` file.
+pub(crate) fn is_ignore_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name == ".gitignore" || TOOL_IGNORE_FILE_NAMES.contains(&name))
+}
+
+impl<P: Into<PathBuf>, C: Into<String>> FromIterator<(P, C)> for MemorySource {
+    fn from_iter<I: IntoIterator<Item = (P, C)>>(iter: I) -> Self {
+        let mut source = MemorySource::new();
+        for (path, content) in iter {
+            source.insert(path, content);
+        }
+        source
+    }
+}
+
+impl FileSource for MemorySource {
+    fn list(
+        &self,
+        config: &Config,
+        on_skipped: &mut dyn FnMut(&Path, SkipReason),
+    ) -> Result<Vec<SourceFile>> {
+        let gitignores = self
+            .files
+            .iter()
+            .filter(|(path, _)| is_ignore_file(path))
+            .map(|(path, rules)| (path.as_path(), rules.as_str()));
+        list_tree(self.files.keys(), gitignores, config, on_skipped)
+    }
+
+    fn find(&self, _config: &Config, relative_path: &Path) -> Option<SourceFile> {
+        self.files
+            .contains_key(relative_path)
+            .then(|| SourceFile::new(relative_path, relative_path))
+    }
+
+    fn read(&self, file: &SourceFile) -> io::Result<String> {
+        self.files.get(&file.path).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no such file: {}", file.path.display()),
+            )
+        })
+    }
+
+    fn len(&self, file: &SourceFile) -> Option<u64> {
+        self.files
+            .get(&file.path)
+            .map(|content| content.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listed(source: &MemorySource, config: &Config) -> Vec<PathBuf> {
+        let files = source.list(config, &mut |_, _| {}).unwrap();
+        files.into_iter().map(|file| file.relative_path).collect()
+    }
+
+    #[test]
+    fn memory_source_filters_hidden_and_ignored_files() {
+        let source: MemorySource = [
+            (".gitignore", "target/\n*.log\n"),
+            ("src/main.rs", "fn main() {}\n"),
+            ("target/debug/out.rs", "\n"),
+            ("build.log", "\n"),
+            (".github/ci.yml", "\n"),
+        ]
+        .into_iter()
+        .collect();
+
+        let config = Config::builder().ignore_hidden(true).build();
+        assert_eq!(listed(&source, &config), [PathBuf::from("src/main.rs")]);
+        let config = Config::builder()
+            .ignore_hidden(true)
+            .keep_hidden_defaults(true)
+            .build();
+        assert_eq!(listed(&source, &config).len(), 2);
+
+        let config = Config::builder().respect_gitignore(false).build();
+        assert_eq!(listed(&source, &config).len(), 5);
+        let config = Config::builder()
+            .respect_gitignore(false)
+            .exclude_dirs(["target", ".github"])
+            .build();
+        assert_eq!(listed(&source, &config).len(), 3);
+    }
+
+    #[test]
+    fn ignore_files_take_precedence_over_gitignore() {
+        let source: MemorySource = [
+            (".gitignore", "*.log\n"),
+            (".ignore", "vendor/\n!keep.log\n"),
+            ("app/.rgignore", "!vendor/\n"),
+            ("keep.log", "\n"),
+            ("build.log", "\n"),
+            ("vendor/lib.rs", "\n"),
+            ("app/vendor/lib.rs", "\n"),
+        ]
+        .into_iter()
+        .collect();
+        let config = Config::builder().ignore_hidden(true).build();
+        assert_eq!(
+            listed(&source, &config),
+            [
+                PathBuf::from("app/vendor/lib.rs"),
+                PathBuf::from("keep.log")
+            ]
+        );
+        let config = Config::builder()
+            .ignore_hidden(true)
+            .respect_ignore_files(false)
+            .build();
+        assert_eq!(
+            listed(&source, &config),
+            [
+                PathBuf::from("app/vendor/lib.rs"),
+                PathBuf::from("vendor/lib.rs")
+            ]
+        );
+    }
+}
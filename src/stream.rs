@@ -0,0 +1,127 @@
+//! Large text files streamed into the output line by line instead of read whole, so the
+//! memory a run takes follows its longest line rather than its largest file.
+
+use crate::generated::{self, Generated, LineCheck};
+use crate::markers::MARKER_LINES;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Text files larger than this, in bytes, are streamed when the options allow it.
+pub(crate) const STREAM_LEN: u64 = 1024 * 1024;
+
+/// How much of a file is checked for NUL bytes, as `read_text_file` checks it.
+const SNIFF_LEN: usize = 8 * 1024;
+
+/// A file written by streaming it from disk, with the counts a first pass over it found.
+#[derive(Debug, Clone)]
+pub(crate) struct StreamedFile {
+    /// The file on disk.
+    pub(crate) path: PathBuf,
+    /// The length of the file in bytes.
+    pub(crate) len: usize,
+    /// The number of lines.
+    pub(crate) lines: usize,
+    /// The longest run of backticks, which a Markdown code fence must outgrow.
+    pub(crate) longest_backtick_run: usize,
+}
+
+/// What a first pass over a file found.
+#[derive(Debug)]
+pub(crate) struct Scan {
+    /// The file, to be streamed when it is written.
+    pub(crate) file: StreamedFile,
+    /// The first lines of the file, for its markers and `#!` line.
+    pub(crate) head: String,
+    /// Whether every line is blank.
+    pub(crate) blank: bool,
+    /// Why the file looks generated, if it does.
+    pub(crate) generated: Option<Generated>,
+}
+
+/// Reads a file once, a line at a time, for what must be known before it is written.
+///
+/// # Arguments
+/// * `path` - The file to read.
+///
+/// # Returns
+/// * `io::Result<Scan>` - What the pass found, or an `InvalidData` error if the file is not
+///   UTF-8 text or has a NUL byte in its first block.
+pub(crate) fn scan(path: &Path) -> io::Result<Scan> {
+    let mut file = StreamedFile {
+        path: path.to_path_buf(),
+        len: 0,
+        lines: 0,
+        longest_backtick_run: 0,
+    };
+    let mut head = String::new();
+    let mut blank = true;
+    let mut line_check = LineCheck::default();
+    let mut long_line = None;
+    for_each_line(path, |line| {
+        if file.len < SNIFF_LEN
+            && line.as_bytes()[..line.len().min(SNIFF_LEN - file.len)].contains(&0)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "binary content (NUL byte)",
+            ));
+        }
+        file.len += line.len();
+        file.lines += 1;
+        let run = line.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+        file.longest_backtick_run = file.longest_backtick_run.max(run);
+        if file.lines <= MARKER_LINES {
+            head.push_str(line);
+        }
+        blank &= line.trim().is_empty();
+        let checked = line_check.check(line_body(line));
+        if long_line.is_none() {
+            long_line = checked;
+        }
+        Ok(())
+    })?;
+    // Markers win over line lengths, as when the whole content is checked.
+    let generated = generated::check_markers(&head)
+        .or(long_line)
+        .or_else(|| line_check.finish(file.len));
+    Ok(Scan {
+        file,
+        head,
+        blank,
+        generated,
+    })
+}
+
+/// Calls `f` with each line of a file in turn, with its line ending, holding one line in
+/// memory at a time.
+///
+/// # Arguments
+/// * `path` - The file to read.
+/// * `f` - Called with each line; an error stops the reading.
+///
+/// # Returns
+/// * `Result<(), E>` - The first error of `f`, or of reading the file, where invalid UTF-8 is
+///   an `InvalidData` error.
+pub(crate) fn for_each_line<E: From<io::Error>>(
+    path: &Path,
+    mut f: impl FnMut(&str) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        f(&line)?;
+    }
+}
+
+/// Returns a line without its line ending, as [`str::lines`] splits it.
+pub(crate) fn line_body(line: &str) -> &str {
+    match line.strip_suffix('\n') {
+        Some(line) => line.strip_suffix('\r').unwrap_or(line),
+        None => line,
+    }
+}
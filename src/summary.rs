@@ -0,0 +1,839 @@
+//! Counters describing what happened during a bundling run.
+
+use crate::json::Value;
+use crate::lang::is_known_file_name;
+use crate::transform::Segment;
+use crate::{BudgetSelection, ChurnFile, Config, FileEntry, LanguageStats, SkipReason, Tokenizer};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Statistics collected while bundling files.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RunSummary {
+    /// Number of files written to the output.
+    pub files_included: usize,
+    /// Number of files or directories that could not be read, or files that failed to be written.
+    pub errors: usize,
+    /// Total number of lines across included files.
+    pub total_lines: usize,
+    /// Total number of bytes across included files, the preamble, and the postamble.
+    pub total_bytes: usize,
+    /// Estimated number of tokens across included files, the preamble, and the postamble.
+    pub estimated_tokens: usize,
+    /// The tokenizer that counted the tokens.
+    pub tokenizer: Tokenizer,
+    /// Number of binary or too large files listed as a placeholder (`binary_placeholders`);
+    /// they are also counted as skipped.
+    pub placeholders: usize,
+    /// Number of files written as a stub because their content duplicates an earlier file.
+    pub duplicates_collapsed: usize,
+    /// Number of lines of license headers removed from included files, with
+    /// `strip_license_headers`.
+    pub license_lines_stripped: usize,
+    /// Number of bytes left out by collapsing duplicates.
+    pub duplicate_bytes_saved: usize,
+    /// Estimated number of tokens left out by collapsing duplicates.
+    pub duplicate_tokens_saved: usize,
+    /// Size of the output before compression, or zero if it was not compressed.
+    pub uncompressed_bytes: usize,
+    /// Size of the compressed output, or zero if it was not compressed.
+    pub compressed_bytes: usize,
+    /// Statistics of the included files by lowercase extension, e.g. `.rs`. Files without an
+    /// extension are listed under their name if it is well known, e.g. `Makefile`, and under
+    /// `(none)` otherwise.
+    pub by_extension: BTreeMap<String, ExtensionStats>,
+    /// Every file included and every entry skipped, in the order they were processed.
+    pub files: Vec<FileStats>,
+    /// The line counts of each language among the included files, with `lang_stats`;
+    /// empty otherwise.
+    pub languages: Vec<LanguageStats>,
+    /// Every entry skipped, with its reason, in the order they were processed.
+    pub skipped: Vec<SkippedFile>,
+    /// The `top` included files with the most tokens, most first and ties by path; empty
+    /// without `top`.
+    pub largest_files: Vec<LargestFile>,
+    /// The files chosen and left out to fit `fit_budget`, or `None` without a budget.
+    pub budget: Option<BudgetSelection>,
+    /// The run's most changed files within `churn_window`, most commits first, when files
+    /// are ranked by churn; empty otherwise.
+    pub churn: Vec<ChurnFile>,
+    /// The number of files reached from the `entry` files and the number listed, before
+    /// the other filters; `None` without `entry`.
+    pub reached: Option<(usize, usize)>,
+    /// The relative path and content hash of each file written, duplicates included, in
+    /// output order, with `hash`; empty otherwise.
+    pub content_hashes: Vec<(PathBuf, String)>,
+    /// The digest over [`content_hashes`](RunSummary::content_hashes) with its algorithm,
+    /// e.g. `blake3:<hex>`, with `hash`.
+    pub bundle_digest: Option<String>,
+    /// Whether the run was stopped early by Ctrl-C or [`Hooks::cancelled`](crate::Hooks::cancelled);
+    /// the output then holds the files included so far.
+    pub interrupted: bool,
+}
+
+/// An entry left out of a run, in [`RunSummary::skipped`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedFile {
+    /// The path of the entry as walked.
+    pub path: PathBuf,
+    /// Why the entry was left out.
+    pub reason: SkipReason,
+}
+
+/// One file of a run, in [`RunSummary::files`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStats {
+    /// The path of an included file relative to its input, or of a skipped entry as walked.
+    pub path: PathBuf,
+    /// Why the entry was left out, or `None` if the file was included.
+    pub skipped: Option<SkipReason>,
+    /// Number of bytes written; zero for a skipped entry.
+    pub bytes: usize,
+    /// Number of lines written; zero for a skipped entry.
+    pub lines: usize,
+    /// Estimated number of tokens written; zero for a skipped entry.
+    pub tokens: usize,
+    /// The size of the file in bytes before transformations; zero for a skipped entry.
+    pub size: u64,
+    /// The SHA-256 of the file's content before transformations, if the run computed it.
+    pub sha256: Option<String>,
+    /// Whether a skipped file was listed as a placeholder, with its size in `size`.
+    pub placeholder: bool,
+}
+
+/// One of the included files with the most tokens, in [`RunSummary::largest_files`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LargestFile {
+    /// The path of the file relative to its input.
+    pub path: PathBuf,
+    /// Number of bytes written.
+    pub bytes: usize,
+    /// Estimated number of tokens written.
+    pub tokens: usize,
+}
+
+/// Statistics of the included files with one extension, in [`RunSummary::by_extension`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionStats {
+    /// Number of files written.
+    pub files: usize,
+    /// Total number of lines written.
+    pub lines: usize,
+    /// Total number of bytes written.
+    pub bytes: usize,
+    /// Estimated number of tokens written.
+    pub tokens: usize,
+}
+
+impl RunSummary {
+    /// Total number of skipped entries, but for those that could not be read, which are
+    /// counted under [`errors`](RunSummary::errors).
+    pub fn files_skipped(&self) -> usize {
+        self.skipped
+            .iter()
+            .filter(|skipped| skipped.reason != SkipReason::ReadError)
+            .count()
+    }
+
+    /// Number of entries skipped for one reason.
+    ///
+    /// # Arguments
+    /// * `reason` - The reason to count.
+    ///
+    /// # Returns
+    /// * `usize` - The number of entries in [`skipped`](RunSummary::skipped) with that reason.
+    pub fn skipped_count(&self, reason: SkipReason) -> usize {
+        self.skipped
+            .iter()
+            .filter(|skipped| skipped.reason == reason)
+            .count()
+    }
+
+    /// Counts an included file, in the totals and under its extension.
+    ///
+    /// # Arguments
+    /// * `entry` - The file, as written.
+    pub(crate) fn record_file(&mut self, entry: &FileEntry) {
+        if let Some(streamed) = &entry.streamed {
+            self.record_written(entry, streamed.lines, streamed.len, entry.tokens);
+            return;
+        }
+        let (lines, bytes) = entry
+            .segments
+            .iter()
+            .map(Segment::text)
+            .fold((0, 0), |(lines, bytes), text| {
+                (lines + text.lines().count(), bytes + text.len())
+            });
+        self.record_written(entry, lines, bytes, entry.tokens);
+    }
+
+    /// Counts a file written as its header alone (`headers_only`), by the header's size.
+    ///
+    /// # Arguments
+    /// * `entry` - The file.
+    /// * `header` - The header as written, in the text layout.
+    pub(crate) fn record_header(&mut self, entry: &FileEntry, header: &str) {
+        let tokens = self.tokenizer.count(header);
+        self.record_written(entry, header.lines().count(), header.len(), tokens);
+    }
+
+    /// Counts an included file by the lines, bytes, and tokens written for it.
+    fn record_written(&mut self, entry: &FileEntry, lines: usize, bytes: usize, tokens: usize) {
+        let path = entry.relative_path.as_path();
+        let stats = self.by_extension.entry(extension_key(path)).or_default();
+        stats.files += 1;
+        stats.lines += lines;
+        stats.bytes += bytes;
+        stats.tokens += tokens;
+        self.record_content_hash(entry);
+        self.files.push(FileStats {
+            path: path.to_path_buf(),
+            skipped: None,
+            bytes,
+            lines,
+            tokens,
+            size: entry.size,
+            sha256: entry.sha256.clone(),
+            placeholder: false,
+        });
+        self.files_included += 1;
+        self.total_lines += lines;
+        self.total_bytes += bytes;
+        self.estimated_tokens += tokens;
+        self.license_lines_stripped += entry.license_lines;
+    }
+
+    /// Adds the content hash of a file written in full or as a duplicate, if it has one.
+    pub(crate) fn record_content_hash(&mut self, entry: &FileEntry) {
+        if let Some(hash) = &entry.content_hash {
+            self.content_hashes
+                .push((entry.relative_path.clone(), hash.clone()));
+        }
+    }
+
+    /// Fills [`largest_files`](RunSummary::largest_files) from the included files.
+    ///
+    /// # Arguments
+    /// * `top` - How many files to keep.
+    pub(crate) fn record_largest_files(&mut self, top: usize) {
+        let mut files: Vec<&FileStats> = self
+            .files
+            .iter()
+            .filter(|file| file.skipped.is_none())
+            .collect();
+        files.sort_by(|a, b| b.tokens.cmp(&a.tokens).then_with(|| a.path.cmp(&b.path)));
+        self.largest_files = files
+            .into_iter()
+            .take(top)
+            .map(|file| LargestFile {
+                path: file.path.clone(),
+                bytes: file.bytes,
+                tokens: file.tokens,
+            })
+            .collect();
+    }
+
+    /// Formats [`largest_files`](RunSummary::largest_files) as an aligned table with each
+    /// file's share of the estimated tokens, followed by the `--exclude-path` flags that
+    /// would leave the files out.
+    ///
+    /// # Returns
+    /// * `String` - The table and the hint, or an empty string without `top`.
+    pub fn largest_files_table(&self) -> String {
+        if self.largest_files.is_empty() {
+            return String::new();
+        }
+        let mut table = format!(
+            "{:<48}{:>12}{:>12}{:>8}\n",
+            "Largest files", "Tokens", "Bytes", "Share"
+        );
+        for file in &self.largest_files {
+            let share = file.tokens as f64 * 100.0 / self.estimated_tokens.max(1) as f64;
+            table.push_str(&format!(
+                "  {:<46}{:>12}{:>12}{:>7.1}%\n",
+                file.path.display(),
+                file.tokens,
+                file.bytes,
+                share
+            ));
+        }
+        let flags: Vec<String> = self
+            .largest_files
+            .iter()
+            .map(|file| {
+                format!(
+                    "--exclude-path {}",
+                    shell_quote(&file.path.to_string_lossy())
+                )
+            })
+            .collect();
+        table.push_str(&format!(
+            "Hint: to leave them out, add {}\n",
+            flags.join(" ")
+        ));
+        table
+    }
+
+    /// Formats [`by_extension`](RunSummary::by_extension) as an aligned table, the extensions
+    /// with the most tokens first.
+    ///
+    /// # Returns
+    /// * `String` - The table, one row per extension.
+    pub fn extension_table(&self) -> String {
+        let mut rows: Vec<(&String, &ExtensionStats)> = self.by_extension.iter().collect();
+        rows.sort_by(|a, b| b.1.tokens.cmp(&a.1.tokens).then_with(|| a.0.cmp(b.0)));
+        let mut table = format!(
+            "{:<20}{:>10}{:>12}{:>12}{:>12}\n",
+            "Extension", "Files", "Lines", "Bytes", "Tokens"
+        );
+        for (extension, stats) in rows {
+            table.push_str(&format!(
+                "  {:<18}{:>10}{:>12}{:>12}{:>12}\n",
+                extension, stats.files, stats.lines, stats.bytes, stats.tokens
+            ));
+        }
+        table
+    }
+
+    /// Formats [`churn`](RunSummary::churn) as an aligned table.
+    ///
+    /// # Returns
+    /// * `String` - The table, one row per file, or an empty string without churn.
+    pub fn churn_table(&self) -> String {
+        if self.churn.is_empty() {
+            return String::new();
+        }
+        let mut table = format!("{:<60}{:>10}\n", "Most changed files", "Commits");
+        for file in &self.churn {
+            table.push_str(&format!(
+                "  {:<58}{:>10}\n",
+                file.path.display(),
+                file.commits
+            ));
+        }
+        table
+    }
+
+    /// Writes [`files`](RunSummary::files) as CSV, one row per included file or skipped entry.
+    ///
+    /// The columns are `path`, `extension`, `bytes`, `lines`, `tokens`, and `skipped`; the
+    /// counts are empty for skipped entries and `skipped` is empty for included files.
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to output the CSV.
+    /// * `directories` - The input directories, stripped from the paths of skipped entries.
+    ///
+    /// # Returns
+    /// * `io::Result<()>` - Returns `Ok(())` if successful, or an error if the write fails.
+    pub fn write_csv(&self, writer: &mut dyn Write, directories: &[PathBuf]) -> io::Result<()> {
+        writeln!(writer, "path,extension,bytes,lines,tokens,skipped")?;
+        for file in &self.files {
+            let path = directories
+                .iter()
+                .find_map(|directory| file.path.strip_prefix(directory).ok())
+                .unwrap_or(&file.path);
+            let extension = path
+                .extension()
+                .map(|extension| extension.to_string_lossy())
+                .unwrap_or_default();
+            let row = match file.skipped {
+                None => [
+                    file.bytes.to_string(),
+                    file.lines.to_string(),
+                    file.tokens.to_string(),
+                    String::new(),
+                ],
+                Some(reason) => [
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    reason.name().to_string(),
+                ],
+            };
+            writeln!(
+                writer,
+                "{},{},{}",
+                csv_field(&path.to_string_lossy()),
+                csv_field(&extension),
+                row.join(",")
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Formats the skipped entries grouped by reason, the most common reasons first.
+    ///
+    /// # Arguments
+    /// * `paths` - Whether to list the paths of each group below the counts.
+    ///
+    /// # Returns
+    /// * `String` - e.g. `Skipped 37 files: 22 binary, 9 too large, 6 read errors`, or an
+    ///   empty string if nothing was skipped.
+    pub fn skipped_report(&self, paths: bool) -> String {
+        if self.skipped.is_empty() {
+            return String::new();
+        }
+        let mut groups: Vec<(SkipReason, Vec<&Path>)> = Vec::new();
+        for skipped in &self.skipped {
+            match groups
+                .iter_mut()
+                .find(|(reason, _)| *reason == skipped.reason)
+            {
+                Some((_, paths)) => paths.push(&skipped.path),
+                None => groups.push((skipped.reason, vec![&skipped.path])),
+            }
+        }
+        groups.sort_by_key(|(_, paths)| std::cmp::Reverse(paths.len()));
+
+        let counts: Vec<String> = groups
+            .iter()
+            .map(|(reason, paths)| format!("{} {}", paths.len(), skip_label(*reason)))
+            .collect();
+        let mut report = format!(
+            "Skipped {} {}: {}\n",
+            self.skipped.len(),
+            if self.skipped.len() == 1 {
+                "file"
+            } else {
+                "files"
+            },
+            counts.join(", ")
+        );
+        if paths {
+            for (reason, paths) in &groups {
+                report.push_str(&format!("  {}:\n", skip_label(*reason)));
+                for path in paths {
+                    report.push_str(&format!("    {}\n", path.display()));
+                }
+            }
+        }
+        report
+    }
+
+    /// Formats the totals as one line of `key=value` pairs, for scripts.
+    ///
+    /// # Returns
+    /// * `String` - e.g. `files=142 lines=51230 bytes=1843201 tokens=85210`.
+    pub fn counts_line(&self) -> String {
+        format!(
+            "files={} lines={} bytes={} tokens={}",
+            self.files_included, self.total_lines, self.total_bytes, self.estimated_tokens
+        )
+    }
+
+    /// Formats the totals as a JSON object with the keys of [`counts_line`](Self::counts_line).
+    pub fn counts_json(&self) -> String {
+        Value::object([
+            ("files", Value::from(self.files_included)),
+            ("lines", Value::from(self.total_lines)),
+            ("bytes", Value::from(self.total_bytes)),
+            ("tokens", Value::from(self.estimated_tokens)),
+        ])
+        .to_pretty_string()
+    }
+
+    /// Counts the bytes and tokens of a preamble or postamble.
+    pub(crate) fn record_framing_text(&mut self, text: &str) {
+        self.total_bytes += text.len();
+        self.estimated_tokens += self.tokenizer.count(text);
+    }
+
+    /// Records a skipped entry, counting it as an error if it could not be read.
+    pub(crate) fn record_skip(&mut self, path: &Path, reason: SkipReason) {
+        self.skipped.push(SkippedFile {
+            path: path.to_path_buf(),
+            reason,
+        });
+        self.files.push(FileStats {
+            path: path.to_path_buf(),
+            skipped: Some(reason),
+            bytes: 0,
+            lines: 0,
+            tokens: 0,
+            size: 0,
+            sha256: None,
+            placeholder: false,
+        });
+        if reason == SkipReason::ReadError {
+            self.errors += 1;
+        }
+    }
+
+    /// Counts a file listed as a placeholder: as skipped for its reason, and as a
+    /// placeholder.
+    ///
+    /// # Arguments
+    /// * `entry` - The placeholder, as written.
+    /// * `reason` - Why the content was left out.
+    pub(crate) fn record_placeholder(&mut self, entry: &FileEntry, reason: SkipReason) {
+        self.record_skip(&entry.relative_path, reason);
+        if let Some(stats) = self.files.last_mut() {
+            stats.placeholder = true;
+            stats.size = entry.size;
+        }
+        self.placeholders += 1;
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Summary")?;
+        write_row(f, "Files included", self.files_included)?;
+        write_row(f, "Files skipped", self.files_skipped())?;
+        for reason in SKIP_ROWS {
+            let count = self.skipped_count(reason);
+            if count > 0 {
+                write_row(f, &format!("  {}", skip_label(reason)), count)?;
+            }
+        }
+
+        let mut rows = vec![
+            ("Total lines", self.total_lines),
+            ("Total bytes", self.total_bytes),
+            ("Estimated tokens", self.estimated_tokens),
+        ];
+        if self.errors > 0 {
+            rows.push(("Errors", self.errors));
+        }
+        if self.placeholders > 0 {
+            rows.push(("Placeholders", self.placeholders));
+        }
+        if self.license_lines_stripped > 0 {
+            rows.push(("License lines cut", self.license_lines_stripped));
+        }
+        if self.duplicates_collapsed > 0 {
+            rows.extend([
+                ("Duplicates", self.duplicates_collapsed),
+                ("  bytes saved", self.duplicate_bytes_saved),
+                ("  tokens saved", self.duplicate_tokens_saved),
+            ]);
+        }
+        if self.compressed_bytes > 0 {
+            rows.extend([
+                ("Output bytes", self.uncompressed_bytes),
+                ("  compressed", self.compressed_bytes),
+            ]);
+        }
+
+        for (label, value) in rows {
+            write_row(f, label, value)?;
+        }
+        if let Some((reached, listed)) = self.reached {
+            write_row(f, "Files reached", format!("{} of {}", reached, listed))?;
+        }
+        write_row(f, "Tokenizer", self.tokenizer)
+    }
+}
+
+/// The skip reasons listed below "Files skipped" in the summary table, in order; read
+/// errors are listed as "Errors".
+const SKIP_ROWS: [SkipReason; 21] = [
+    SkipReason::Binary,
+    SkipReason::Filtered,
+    SkipReason::Ignored,
+    SkipReason::IgnoreFile,
+    SkipReason::Lockfile,
+    SkipReason::Sensitive,
+    SkipReason::Generated,
+    SkipReason::Tests,
+    SkipReason::Modified,
+    SkipReason::Empty,
+    SkipReason::TooSmall,
+    SkipReason::TooLarge,
+    SkipReason::OverBudget,
+    SkipReason::OptOut,
+    SkipReason::Malformed,
+    SkipReason::NoMatch,
+    SkipReason::OverLimit,
+    SkipReason::Linguist,
+    SkipReason::ExportIgnore,
+    SkipReason::Unreachable,
+    SkipReason::FilterFailed,
+];
+
+/// Writes one row of the summary table.
+fn write_row(f: &mut fmt::Formatter<'_>, label: &str, value: impl fmt::Display) -> fmt::Result {
+    writeln!(f, "  {:<18}{:>12}", label, value)
+}
+
+/// Describes a skip reason in [`RunSummary::skipped_report`], as in the summary table.
+fn skip_label(reason: SkipReason) -> &'static str {
+    match reason {
+        SkipReason::Binary => "binary",
+        SkipReason::TooLarge => "too large",
+        SkipReason::TooSmall => "too small",
+        SkipReason::Empty => "empty",
+        SkipReason::Filtered => "filtered",
+        SkipReason::Ignored => "gitignored",
+        SkipReason::IgnoreFile => ".c2pignore",
+        SkipReason::ReadError => "read errors",
+        SkipReason::Lockfile => "lockfiles",
+        SkipReason::Sensitive => "sensitive",
+        SkipReason::Generated => "generated",
+        SkipReason::Tests => "tests",
+        SkipReason::Modified => "modified time",
+        SkipReason::OverBudget => "over budget",
+        SkipReason::OptOut => "opt-out marker",
+        SkipReason::Malformed => "malformed",
+        SkipReason::NoMatch => "no grep match",
+        SkipReason::OverLimit => "over file limit",
+        SkipReason::Linguist => "linguist",
+        SkipReason::ExportIgnore => "export-ignore",
+        SkipReason::Unreachable => "not reached",
+        SkipReason::FilterFailed => "filter failed",
+    }
+}
+
+/// The error returned when `fail_if_empty` is set and no file matched the filters.
+///
+/// Its message lists the active filters and the likely causes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoFilesMatched(String);
+
+impl NoFilesMatched {
+    /// Describes an empty run.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options of the run.
+    /// * `summary` - The statistics of the run.
+    ///
+    /// # Returns
+    /// * `NoFilesMatched` - The error, with the filters and likely causes in its message.
+    pub(crate) fn new(config: &Config, summary: &RunSummary) -> Self {
+        let list = |items: &[String]| match items {
+            [] => "(none)".to_string(),
+            items => items.join(", "),
+        };
+        let directories = config
+            .directories
+            .iter()
+            .map(|dir| format!("`{}`", dir.display()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut causes = Vec::new();
+        if !config.include.is_empty()
+            || !config.languages.is_empty()
+            || config.include_from.is_some()
+        {
+            causes.push("no file has one of the included extensions".to_string());
+        }
+        if config.ignore_hidden {
+            causes.push("hidden files and directories are skipped".to_string());
+        }
+        for reason in CAUSE_ORDER {
+            let count = summary.skipped_count(reason);
+            if count > 0 {
+                causes.push(skip_cause(reason, count, config));
+            }
+        }
+        if causes.is_empty() {
+            causes.push("the directories contain no files".to_string());
+        }
+
+        let mut message = format!(
+            "No files matched the filters in {}\n  include: {}\n  exclude: {}\nLikely causes:",
+            directories,
+            list(&config.include),
+            list(&config.exclude)
+        );
+        for cause in causes {
+            message.push_str("\n  - ");
+            message.push_str(&cause);
+        }
+        NoFilesMatched(message)
+    }
+}
+
+/// The skip reasons listed as likely causes by [`NoFilesMatched`], in order.
+const CAUSE_ORDER: [SkipReason; 22] = [
+    SkipReason::Ignored,
+    SkipReason::IgnoreFile,
+    SkipReason::Filtered,
+    SkipReason::Lockfile,
+    SkipReason::Sensitive,
+    SkipReason::Linguist,
+    SkipReason::ExportIgnore,
+    SkipReason::Unreachable,
+    SkipReason::FilterFailed,
+    SkipReason::Generated,
+    SkipReason::Tests,
+    SkipReason::Modified,
+    SkipReason::Empty,
+    SkipReason::TooSmall,
+    SkipReason::TooLarge,
+    SkipReason::Binary,
+    SkipReason::NoMatch,
+    SkipReason::OptOut,
+    SkipReason::Malformed,
+    SkipReason::OverLimit,
+    SkipReason::OverBudget,
+    SkipReason::ReadError,
+];
+
+/// Describes the entries skipped for one reason as a likely cause of an empty run.
+///
+/// # Arguments
+/// * `reason` - Why the entries were skipped.
+/// * `count` - How many entries were skipped for it.
+/// * `config` - The configuration options of the run.
+///
+/// # Returns
+/// * `String` - e.g. `3 lockfiles were excluded (see --include-lockfiles)`.
+fn skip_cause(reason: SkipReason, count: usize, config: &Config) -> String {
+    match reason {
+        SkipReason::Ignored => format!(
+            "{} entries were ignored by .gitignore or .ignore rules",
+            count
+        ),
+        SkipReason::IgnoreFile => format!(
+            "{} entries were ignored by .c2pignore or --ignore-file rules",
+            count
+        ),
+        SkipReason::Filtered => format!(
+            "{} entries were left out by the extension, --exclude, or hidden-file filters",
+            count
+        ),
+        SkipReason::Lockfile => format!(
+            "{} lockfiles were excluded (see --include-lockfiles)",
+            count
+        ),
+        SkipReason::Sensitive => format!(
+            "{} files that may hold secrets were excluded (see --include-sensitive)",
+            count
+        ),
+        SkipReason::Linguist => format!(
+            "{} files were marked vendored or generated in .gitattributes (see --respect-linguist)",
+            count
+        ),
+        SkipReason::ExportIgnore => format!(
+            "{} entries were marked export-ignore in .gitattributes (see --respect-export-ignore)",
+            count
+        ),
+        SkipReason::Unreachable => format!(
+            "{} files were not reached from --entry (see --follow-imports)",
+            count
+        ),
+        SkipReason::FilterFailed => format!(
+            "{} files failed their filter command (see --filter-cmd)",
+            count
+        ),
+        SkipReason::Generated => format!(
+            "{} files were skipped as generated (see --no-skip-generated)",
+            count
+        ),
+        SkipReason::Tests => {
+            let (files, option) = if config.only_tests {
+                ("non-test files", "--only-tests")
+            } else {
+                ("test files", "--no-tests")
+            };
+            format!("{} {} were excluded by {}", count, files, option)
+        }
+        SkipReason::Modified => format!(
+            "{} files were modified outside the --newer-than/--older-than window",
+            count
+        ),
+        SkipReason::Empty => format!("{} files were skipped as empty (see --skip-empty)", count),
+        SkipReason::TooSmall => format!("{} files were smaller than --min-file-size", count),
+        SkipReason::TooLarge => format!("{} files were larger than --max-file-size", count),
+        SkipReason::Binary => format!("{} files were skipped as binary", count),
+        SkipReason::NoMatch => format!("{} files did not match --grep", count),
+        SkipReason::OptOut => format!(
+            "{} files opted out with a codebase-to-prompt:ignore marker",
+            count
+        ),
+        SkipReason::Malformed => format!("{} notebooks could not be parsed", count),
+        SkipReason::OverLimit => format!("{} files were over the --max-files limit", count),
+        SkipReason::OverBudget => format!("{} files did not fit --fit-budget", count),
+        SkipReason::ReadError => format!("{} entries could not be read", count),
+    }
+}
+
+impl fmt::Display for NoFilesMatched {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NoFilesMatched {}
+
+/// Quotes a CSV field if it contains a comma, quote, or line break, doubling its quotes.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Quotes a path for a POSIX shell if it contains anything but letters, digits, and
+/// `_./-`, so a suggested flag can be pasted as is.
+fn shell_quote(text: &str) -> String {
+    if !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_./-".contains(c))
+    {
+        text.to_string()
+    } else {
+        format!("'{}'", text.replace('\'', "'\\''"))
+    }
+}
+
+/// Returns the [`RunSummary::by_extension`] key of a file.
+///
+/// # Arguments
+/// * `path` - The path of the file.
+///
+/// # Returns
+/// * `String` - The lowercase extension with a leading dot, a well-known file name, or `(none)`.
+fn extension_key(path: &Path) -> String {
+    match path.extension().and_then(|s| s.to_str()) {
+        Some(extension) => format!(".{}", extension.to_lowercase()),
+        None => match path.file_name().and_then(|s| s.to_str()) {
+            Some(name) if is_known_file_name(name) => name.to_string(),
+            _ => "(none)".to_string(),
+        },
+    }
+}
+
+/// Estimates the number of tokens in a piece of text.
+///
+/// Uses the common approximation of four bytes per token, as [`Tokenizer::Approx`] does.
+///
+/// # Arguments
+/// * `text` - The text to estimate.
+///
+/// # Returns
+/// * `usize` - The estimated token count.
+pub fn estimate_tokens(text: &str) -> usize {
+    Tokenizer::Approx.count(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_skip_reason_is_a_likely_cause() {
+        let config = Config {
+            ignore_hidden: false,
+            ..Config::default()
+        };
+        for reason in SKIP_ROWS.into_iter().chain([SkipReason::ReadError]) {
+            let mut summary = RunSummary::default();
+            summary.record_skip(Path::new("a"), reason);
+            summary.record_skip(Path::new("b"), reason);
+            let message = NoFilesMatched::new(&config, &summary).to_string();
+            let described = message.contains("\n  - 2 ") && !message.contains("contain no files");
+            assert!(described, "{:?}: {}", reason, message);
+        }
+        let message = NoFilesMatched::new(&config, &RunSummary::default()).to_string();
+        assert!(message.contains("the directories contain no files"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Test code, left out of bundles with `no_tests` or kept alone with `only_tests`.
+
+use crate::build_glob_set;
+use anyhow::{Context, Result};
+use globset::GlobSet;
+
+/// Globs matching test files and directories by common conventions, matched against
+/// relative paths.
+///
+/// The `tests_patterns` option replaces this list.
+pub const DEFAULT_TEST_PATTERNS: &[&str] = &[
+    // Test directories
+    "**/tests/**",
+    "**/test/**",
+    "**/__tests__/**",
+    "**/spec/**",
+    // Go
+    "**/*_test.go",
+    // JavaScript and TypeScript
+    "**/*.test.{js,jsx,mjs,cjs,ts,tsx}",
+    "**/*.spec.{js,jsx,mjs,cjs,ts,tsx}",
+    // Python
+    "**/test_*.py",
+    "**/*_test.py",
+];
+
+/// Returns the default `tests_patterns` list as owned strings.
+pub(crate) fn default_test_patterns() -> Vec<String> {
+    DEFAULT_TEST_PATTERNS
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .collect()
+}
+
+/// Compiles the patterns that identify test files.
+///
+/// # Arguments
+/// * `patterns` - The glob patterns, e.g. the `tests_patterns` option.
+///
+/// # Returns
+/// * `Result<GlobSet>` - The compiled patterns, or an error naming an invalid one.
+pub(crate) fn test_set(patterns: &[String]) -> Result<GlobSet> {
+    let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+    build_glob_set(&patterns).context("Invalid `tests_patterns` pattern")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn default_patterns_match_test_conventions() {
+        let set = test_set(&default_test_patterns()).unwrap();
+        for path in [
+            "tests/integration_tests.rs",
+            "crates/core/tests/parse.rs",
+            "src/test/java/AppTest.java",
+            "web/src/__tests__/App.jsx",
+            "spec/models/user_spec.rb",
+            "pkg/server/handler_test.go",
+            "web/src/App.test.tsx",
+            "lib/util.spec.js",
+            "test_main.py",
+            "app/models_test.py",
+        ] {
+            assert!(set.is_match(Path::new(path)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn default_patterns_leave_production_code() {
+        let set = test_set(&default_test_patterns()).unwrap();
+        for path in [
+            "src/main.rs",
+            "src/testing.rs",
+            "src/contest/mod.rs",
+            "latest/main.go",
+            "pkg/server/handler.go",
+            "web/src/App.tsx",
+            "attest.py",
+        ] {
+            assert!(!set.is_match(Path::new(path)), "{}", path);
+        }
+    }
+}
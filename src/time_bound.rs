@@ -0,0 +1,241 @@
+//! Points in time for the `newer_than` and `older_than` filters: a date or an age.
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, SecondsFormat};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+/// A bound on modification times, parsed from an RFC 3339 date or time (`2024-05-01`,
+/// `2024-05-01T09:30:00+02:00`) or an age such as `7d`, `12h`, or `1w 2d`.
+///
+/// A date alone means midnight in the local time zone. An age is counted back from the
+/// start of the run. Age units are `s`, `m`, `h`, `d`, `w`, `M` (30.44 days), and `y`
+/// (365.25 days), or their long names such as `min`, `hours`, or `days`. In a window of
+/// history, parsed by [`TimeBound::parse_window`], `m` is a month instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum TimeBound {
+    /// A fixed point in time.
+    At(DateTime<FixedOffset>),
+    /// A time before the start of the run.
+    Ago(Duration),
+}
+
+impl TimeBound {
+    /// Returns the point in time the bound stands for.
+    ///
+    /// # Arguments
+    /// * `now` - The start of the run, from which ages are counted back.
+    ///
+    /// # Returns
+    /// * `SystemTime` - The bound, clamped to the earliest representable time.
+    pub fn resolve(&self, now: SystemTime) -> SystemTime {
+        match self {
+            TimeBound::At(time) => SystemTime::from(*time),
+            TimeBound::Ago(age) => now.checked_sub(*age).unwrap_or(SystemTime::UNIX_EPOCH),
+        }
+    }
+
+    /// Parses how far back a window of history reaches, such as `churn_window`, where `m`
+    /// is a month rather than a minute: `6m` is six months. Minutes are written `min`.
+    ///
+    /// # Arguments
+    /// * `s` - A date, an RFC 3339 time, or an age.
+    ///
+    /// # Returns
+    /// * `Result<TimeBound, String>` - The bound, or a message naming the invalid value.
+    pub fn parse_window(s: &str) -> Result<Self, String> {
+        parse(s, Minute::IsMonth)
+    }
+}
+
+/// What a bare `m` means in an age.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Minute {
+    /// A minute, as in `1h 30m`.
+    IsMinute,
+    /// A month, as in a churn window of `6m`.
+    IsMonth,
+}
+
+/// The length of a month, in seconds.
+const MONTH: u64 = 2_630_016;
+
+/// The names of the age units and their lengths in seconds.
+const UNITS: &[(&[&str], u64)] = &[
+    (&["seconds", "second", "secs", "sec", "s"], 1),
+    (&["minutes", "minute", "mins", "min", "m"], 60),
+    (&["hours", "hour", "hrs", "hr", "h"], 3_600),
+    (&["days", "day", "d"], 86_400),
+    (&["weeks", "week", "w"], 604_800),
+    (&["months", "month", "M"], MONTH),
+    (&["years", "year", "y"], 31_557_600),
+];
+
+/// Parses an age such as `7d` or `1h 30m` into its length.
+fn parse_age(s: &str, minute: Minute) -> Option<Duration> {
+    let mut rest = s.trim();
+    let mut seconds: u64 = 0;
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let number: u64 = rest[..digits].parse().ok()?;
+        rest = rest[digits..].trim_start();
+        let letters = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let unit = &rest[..letters];
+        let multiplier = match unit {
+            "m" if minute == Minute::IsMonth => MONTH,
+            unit => UNITS.iter().find(|(names, _)| names.contains(&unit))?.1,
+        };
+        seconds = seconds.checked_add(number.checked_mul(multiplier)?)?;
+        rest = rest[letters..].trim_start();
+    }
+    Some(Duration::from_secs(seconds))
+}
+
+/// Parses a date, an RFC 3339 time, or an age.
+fn parse(s: &str, minute: Minute) -> Result<TimeBound, String> {
+    let s = s.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(TimeBound::At(time));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        let midnight = date
+            .and_hms_opt(0, 0, 0)
+            .and_then(|time| time.and_local_timezone(Local).earliest())
+            .ok_or_else(|| format!("`{}` has no midnight in the local time zone", s))?;
+        return Ok(TimeBound::At(midnight.fixed_offset()));
+    }
+    let examples = match minute {
+        Minute::IsMinute => "`7d` or `12h`",
+        Minute::IsMonth => "`6m` or `26w`",
+    };
+    parse_age(s, minute).map(TimeBound::Ago).ok_or_else(|| {
+        format!(
+            "invalid time `{}` (expected a date such as `2024-05-01`, an RFC 3339 time, \
+             or an age such as {})",
+            s, examples
+        )
+    })
+}
+
+impl FromStr for TimeBound {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s, Minute::IsMinute)
+    }
+}
+
+impl TryFrom<String> for TimeBound {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for TimeBound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeBound::At(time) => f.write_str(&time.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            TimeBound::Ago(age) => {
+                let mut seconds = age.as_secs();
+                if seconds == 0 {
+                    return f.write_str("0s");
+                }
+                // Minutes are written `min`, which no kind of bound reads as months.
+                for (unit, length) in [("d", 86_400), ("h", 3_600), ("min", 60), ("s", 1)] {
+                    if seconds >= length {
+                        write!(f, "{}{}", seconds / length, unit)?;
+                        seconds %= length;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Serialize for TimeBound {
+    /// Serializes as the string form read from configuration files.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserializes a window of history, where `m` is a month; see [`TimeBound::parse_window`].
+pub(crate) fn deserialize_window<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<TimeBound, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    TimeBound::parse_window(&s).map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ages_and_dates() {
+        assert_eq!(
+            "7d".parse(),
+            Ok(TimeBound::Ago(Duration::from_secs(604_800)))
+        );
+        assert_eq!(
+            "1h 30min".parse(),
+            Ok(TimeBound::Ago(Duration::from_secs(5_400)))
+        );
+        assert_eq!(
+            "2 weeks".parse(),
+            Ok(TimeBound::Ago(Duration::from_secs(1_209_600)))
+        );
+        let time: TimeBound = "2024-05-01T09:30:00+02:00".parse().unwrap();
+        assert_eq!(time.to_string(), "2024-05-01T09:30:00+02:00");
+        let TimeBound::At(date) = "2024-05-01".parse().unwrap() else {
+            panic!("a date is a fixed time");
+        };
+        assert_eq!(
+            date.with_timezone(&Local).date_naive().to_string(),
+            "2024-05-01"
+        );
+        for invalid in ["", "7", "d", "7 parsecs", "2024-13-01", "-3d"] {
+            assert!(invalid.parse::<TimeBound>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn windows_read_m_as_months() {
+        let six_months = TimeBound::Ago(Duration::from_secs(6 * MONTH));
+        assert_eq!(TimeBound::parse_window("6m"), Ok(six_months));
+        assert_eq!(TimeBound::parse_window("6M"), Ok(six_months));
+        assert_eq!(
+            TimeBound::parse_window("90min"),
+            Ok(TimeBound::Ago(Duration::from_secs(5_400)))
+        );
+        assert_eq!(
+            TimeBound::parse_window(&six_months.to_string()),
+            Ok(six_months)
+        );
+        assert_eq!("6m".parse(), Ok(TimeBound::Ago(Duration::from_secs(360))));
+    }
+
+    #[test]
+    fn ages_round_trip_and_count_back_from_now() {
+        let age: TimeBound = "1w1h5s".parse().unwrap();
+        assert_eq!(age.to_string(), "7d1h5s");
+        assert_eq!(age.to_string().parse(), Ok(age));
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert_eq!(
+            age.resolve(now),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 - 608_405)
+        );
+    }
+}
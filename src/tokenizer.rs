@@ -0,0 +1,124 @@
+//! How tokens are counted: the `--tokenizer` option.
+
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Counts the tokens of the bundle, for the summary, `--estimate`, `--count-only`, and
+/// `--group-by` subtotals.
+///
+/// Parsed from its name, e.g. `approx`; [`Tokenizer::AVAILABLE`] lists the tokenizers of
+/// this build.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+#[non_exhaustive]
+pub enum Tokenizer {
+    /// A fast heuristic of four bytes per token.
+    #[default]
+    Approx,
+}
+
+impl Tokenizer {
+    /// The tokenizers compiled into this build.
+    pub const AVAILABLE: &[Tokenizer] = &[Tokenizer::Approx];
+
+    /// Returns the name of the tokenizer, as accepted by `--tokenizer`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Tokenizer::Approx => "approx",
+        }
+    }
+
+    /// Counts the tokens in a piece of text.
+    ///
+    /// # Arguments
+    /// * `text` - The text to count.
+    ///
+    /// # Returns
+    /// * `usize` - The number of tokens.
+    pub fn count(self, text: &str) -> usize {
+        match self {
+            Tokenizer::Approx => text.len().div_ceil(4),
+        }
+    }
+
+    /// Counts the tokens in text of a given length without the text itself, for a file
+    /// streamed into the output; `None` for a tokenizer that needs
+    /// the text.
+    ///
+    /// # Arguments
+    /// * `len` - The length of the text in bytes.
+    ///
+    /// # Returns
+    /// * `Option<usize>` - The number of tokens, as [`count`](Tokenizer::count) counts them.
+    pub(crate) fn count_len(self, len: usize) -> Option<usize> {
+        match self {
+            Tokenizer::Approx => Some(len.div_ceil(4)),
+        }
+    }
+}
+
+impl FromStr for Tokenizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        if let Some(tokenizer) = Tokenizer::AVAILABLE
+            .iter()
+            .find(|tokenizer| tokenizer.name().eq_ignore_ascii_case(name))
+        {
+            return Ok(*tokenizer);
+        }
+        let available: Vec<&str> = Tokenizer::AVAILABLE.iter().map(|t| t.name()).collect();
+        Err(format!(
+            "tokenizer `{}` is unknown; available tokenizers: {}",
+            name,
+            available.join(", ")
+        ))
+    }
+}
+
+impl TryFrom<String> for Tokenizer {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Tokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl Serialize for Tokenizer {
+    /// Serializes as the name read from configuration files.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip_and_errors_list_the_available_tokenizers() {
+        for tokenizer in Tokenizer::AVAILABLE {
+            assert_eq!(tokenizer.name().parse::<Tokenizer>(), Ok(*tokenizer));
+        }
+        assert_eq!("APPROX".parse::<Tokenizer>(), Ok(Tokenizer::Approx));
+        assert_eq!(
+            "gpt2".parse::<Tokenizer>(),
+            Err("tokenizer `gpt2` is unknown; available tokenizers: approx".to_string())
+        );
+    }
+
+    #[test]
+    fn approx_counts_four_bytes_per_token() {
+        assert_eq!(Tokenizer::Approx.count(""), 0);
+        assert_eq!(Tokenizer::Approx.count("abcd"), 1);
+        assert_eq!(Tokenizer::Approx.count("abcde"), 2);
+    }
+}
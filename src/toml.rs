@@ -0,0 +1,581 @@
+//! A small TOML reader covering the subset used by configuration files.
+//!
+//! Supported: comments, bare/quoted/dotted keys, `[table]` and `[[array-of-tables]]`
+//! headers, basic and literal strings (including multi-line forms), integers, floats,
+//! booleans, arrays, and inline tables. Date-times are not supported.
+//!
+//! Parsed values implement [`serde::Deserializer`], so any `Deserialize` type can be
+//! read with `T::deserialize(Value::Table(table))`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::{self, IntoDeserializer, Visitor};
+
+/// A TOML table with keys kept in sorted order.
+pub type Table = BTreeMap<String, Value>;
+
+/// A parsed TOML value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+/// An error raised while parsing or deserializing a TOML document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// Parses a TOML document into a table.
+///
+/// # Arguments
+/// * `input` - The TOML source text.
+///
+/// # Returns
+/// * `Result<Table, Error>` - The root table, or an error naming the offending line.
+pub fn parse(input: &str) -> Result<Table, Error> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser.document()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn error(&self, message: impl fmt::Display) -> Error {
+        Error(format!("line {}: {}", self.line, message))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, pattern: &str) -> bool {
+        pattern
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Error> {
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(format!("expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(format!("expected '{}', found end of input", expected))),
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    /// Skips whitespace, newlines, and comments (used between array elements and lines).
+    fn skip_trivia(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') | Some('\r') => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), Error> {
+        self.skip_spaces();
+        self.skip_comment();
+        if self.peek() == Some('\r') {
+            self.bump();
+        }
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("unexpected '{}' after value", c))),
+        }
+    }
+
+    fn document(&mut self) -> Result<Table, Error> {
+        let mut root = Table::new();
+        let mut current: Vec<String> = Vec::new();
+
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                None => break,
+                Some('[') => {
+                    self.bump();
+                    let array = self.peek() == Some('[');
+                    if array {
+                        self.bump();
+                    }
+                    self.skip_spaces();
+                    let path = self.key_path()?;
+                    self.skip_spaces();
+                    self.expect(']')?;
+                    if array {
+                        self.expect(']')?;
+                    }
+                    self.end_of_line()?;
+
+                    if array {
+                        let (last, parents) = path.split_last().expect("key path is never empty");
+                        let parent = self.table_at(&mut root, parents)?;
+                        let entry = parent
+                            .entry(last.clone())
+                            .or_insert_with(|| Value::Array(Vec::new()));
+                        match entry {
+                            Value::Array(items) => items.push(Value::Table(Table::new())),
+                            _ => return Err(self.error(format!("'{}' is not an array", last))),
+                        }
+                    } else {
+                        self.table_at(&mut root, &path)?;
+                    }
+                    current = path;
+                }
+                Some(_) => {
+                    let path = self.key_path()?;
+                    self.skip_spaces();
+                    self.expect('=')?;
+                    self.skip_spaces();
+                    let value = self.value()?;
+                    self.end_of_line()?;
+
+                    let table = self.table_at(&mut root, &current)?;
+                    self.insert(table, &path, value)?;
+                }
+            }
+        }
+
+        Ok(root)
+    }
+
+    /// Resolves (creating as needed) the table at `path`, descending into the last
+    /// element of arrays of tables.
+    fn table_at<'t>(&self, root: &'t mut Table, path: &[String]) -> Result<&'t mut Table, Error> {
+        let mut table = root;
+        for key in path {
+            let entry = table
+                .entry(key.clone())
+                .or_insert_with(|| Value::Table(Table::new()));
+            table = match entry {
+                Value::Table(inner) => inner,
+                Value::Array(items) => match items.last_mut() {
+                    Some(Value::Table(inner)) => inner,
+                    _ => return Err(self.error(format!("'{}' is not a table", key))),
+                },
+                _ => return Err(self.error(format!("'{}' is not a table", key))),
+            };
+        }
+        Ok(table)
+    }
+
+    fn insert(&self, table: &mut Table, path: &[String], value: Value) -> Result<(), Error> {
+        let (last, parents) = path.split_last().expect("key path is never empty");
+        let table = self.table_at(table, parents)?;
+        if table.contains_key(last) {
+            return Err(self.error(format!("duplicate key '{}'", last)));
+        }
+        table.insert(last.clone(), value);
+        Ok(())
+    }
+
+    fn key_path(&mut self) -> Result<Vec<String>, Error> {
+        let mut path = vec![self.key()?];
+        loop {
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                break;
+            }
+            self.bump();
+            self.skip_spaces();
+            path.push(self.key()?);
+        }
+        Ok(path)
+    }
+
+    fn key(&mut self) -> Result<String, Error> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    self.bump();
+                }
+                if self.pos == start {
+                    return Err(self.error("expected a key"));
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        match self.peek() {
+            Some('"') if self.starts_with("\"\"\"") => {
+                self.multiline_basic_string().map(Value::String)
+            }
+            Some('\'') if self.starts_with("'''") => {
+                self.multiline_literal_string().map(Value::String)
+            }
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some('t') if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(Value::Boolean(true))
+            }
+            Some('f') if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(Value::Boolean(false))
+            }
+            Some(c) if c.is_ascii_digit() || c == '+' || c == '-' => self.number(),
+            Some(c) => Err(self.error(format!("unexpected '{}' where a value was expected", c))),
+            None => Err(self.error("expected a value, found end of input")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, Error> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_'))
+        {
+            self.bump();
+        }
+        let text: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|c| **c != '_')
+            .collect();
+
+        if let Ok(integer) = text.parse::<i64>() {
+            return Ok(Value::Integer(integer));
+        }
+        if let Some(hex) = text.strip_prefix("0x")
+            && let Ok(integer) = i64::from_str_radix(hex, 16)
+        {
+            return Ok(Value::Integer(integer));
+        }
+        text.parse::<f64>()
+            .map(Value::Float)
+            .map_err(|_| self.error(format!("invalid number '{}'", text)))
+    }
+
+    fn array(&mut self) -> Result<Value, Error> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_trivia();
+            if self.peek() == Some(']') {
+                self.bump();
+                break;
+            }
+            items.push(self.value()?);
+            self.skip_trivia();
+            match self.bump() {
+                Some(',') => continue,
+                Some(']') => break,
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn inline_table(&mut self) -> Result<Value, Error> {
+        self.expect('{')?;
+        let mut table = Table::new();
+        self.skip_spaces();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(Value::Table(table));
+        }
+        loop {
+            self.skip_spaces();
+            let path = self.key_path()?;
+            self.skip_spaces();
+            self.expect('=')?;
+            self.skip_spaces();
+            let value = self.value()?;
+            self.insert(&mut table, &path, value)?;
+            self.skip_spaces();
+            match self.bump() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err(self.error("expected ',' or '}' in inline table")),
+            }
+        }
+        Ok(Value::Table(table))
+    }
+
+    fn basic_string(&mut self) -> Result<String, Error> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(out),
+                Some('\\') => out.push(self.escape()?),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn multiline_basic_string(&mut self) -> Result<String, Error> {
+        self.pos += 3;
+        self.skip_leading_newline();
+        let mut out = String::new();
+        loop {
+            if self.starts_with("\"\"\"") && !self.starts_with("\"\"\"\"") {
+                self.pos += 3;
+                return Ok(out);
+            }
+            match self.bump() {
+                None => return Err(self.error("unterminated multi-line string")),
+                Some('\\') if matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) => {
+                    // Line-ending backslash: trim all whitespace up to the next content.
+                    while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                        self.bump();
+                    }
+                }
+                Some('\\') => out.push(self.escape()?),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, Error> {
+        self.expect('\'')?;
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('\'') => return Ok(out),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn multiline_literal_string(&mut self) -> Result<String, Error> {
+        self.pos += 3;
+        self.skip_leading_newline();
+        let mut out = String::new();
+        loop {
+            if self.starts_with("'''") && !self.starts_with("''''") {
+                self.pos += 3;
+                return Ok(out);
+            }
+            match self.bump() {
+                None => return Err(self.error("unterminated multi-line string")),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn skip_leading_newline(&mut self) {
+        if self.peek() == Some('\r') && self.peek_at(1) == Some('\n') {
+            self.bump();
+        }
+        if self.peek() == Some('\n') {
+            self.bump();
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, Error> {
+        match self.bump() {
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('r') => Ok('\r'),
+            Some('b') => Ok('\u{8}'),
+            Some('f') => Ok('\u{c}'),
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('u') => self.unicode_escape(4),
+            Some('U') => self.unicode_escape(8),
+            Some(c) => Err(self.error(format!("invalid escape '\\{}'", c))),
+            None => Err(self.error("unterminated escape")),
+        }
+    }
+
+    fn unicode_escape(&mut self, len: usize) -> Result<char, Error> {
+        let digits: String = (0..len).filter_map(|_| self.bump()).collect();
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error(format!("invalid unicode escape '{}'", digits)))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::String(s) => visitor.visit_string(s),
+            Value::Integer(i) => visitor.visit_i64(i),
+            Value::Float(f) => visitor.visit_f64(f),
+            Value::Boolean(b) => visitor.visit_bool(b),
+            Value::Array(items) => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(items.into_iter()))
+            }
+            Value::Table(table) => {
+                visitor.visit_map(de::value::MapDeserializer::new(table.into_iter()))
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Value::String(s) => visitor.visit_enum(s.into_deserializer()),
+            other => other.deserialize_any(visitor).map_err(|_: Error| {
+                de::Error::custom(format!(
+                    "invalid value for {}, expected one of: {}",
+                    name,
+                    variants.join(", ")
+                ))
+            }),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scalars_arrays_and_tables() {
+        let table = parse(
+            r#"
+# comment
+name = "bundle" # trailing comment
+count = 1_000
+ratio = 0.5
+enabled = true
+list = [
+    "a",
+    'b', # inline comment
+]
+
+[nested.table]
+key = { inner = 1 }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(table["name"], Value::String("bundle".into()));
+        assert_eq!(table["count"], Value::Integer(1000));
+        assert_eq!(table["ratio"], Value::Float(0.5));
+        assert_eq!(table["enabled"], Value::Boolean(true));
+        assert_eq!(
+            table["list"],
+            Value::Array(vec![Value::String("a".into()), Value::String("b".into())])
+        );
+        let Value::Table(nested) = &table["nested"] else {
+            panic!("expected table");
+        };
+        let Value::Table(inner) = &nested["table"] else {
+            panic!("expected table");
+        };
+        assert!(matches!(&inner["key"], Value::Table(t) if t["inner"] == Value::Integer(1)));
+    }
+
+    #[test]
+    fn parses_multiline_strings_and_escapes() {
+        let table =
+            parse("a = \"\"\"\nline one\nline \\\"two\\\"\"\"\"\nb = '''\nraw \\n'''\n").unwrap();
+        assert_eq!(table["a"], Value::String("line one\nline \"two\"".into()));
+        assert_eq!(table["b"], Value::String("raw \\n".into()));
+    }
+
+    #[test]
+    fn reports_line_numbers_on_errors() {
+        let err = parse("a = 1\nb = \n").unwrap_err();
+        assert!(err.to_string().starts_with("line 2:"), "{}", err);
+
+        let err = parse("a = 1\na = 2\n").unwrap_err();
+        assert!(err.to_string().contains("duplicate key 'a'"));
+    }
+}
@@ -0,0 +1,958 @@
+//! Content transformations applied between reading a file and writing it to the output.
+
+use crate::Tokenizer;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The line endings file content is converted to before it is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    /// `\n`.
+    Lf,
+    /// `\r\n`.
+    Crlf,
+    /// Whatever each file uses (default).
+    #[default]
+    Keep,
+}
+
+/// Converts every line ending (`\n` or `\r\n`) to the given style.
+///
+/// A `\r` that is not followed by `\n` is content, not a line ending, and is left alone.
+///
+/// # Arguments
+/// * `content` - The content to convert.
+/// * `ending` - The line ending to use.
+///
+/// # Returns
+/// * `String` - The converted content; with [`LineEnding::Keep`], the content unchanged.
+pub fn normalize_line_endings(content: String, ending: LineEnding) -> String {
+    let ending = match ending {
+        LineEnding::Lf => "\n",
+        LineEnding::Crlf => "\r\n",
+        LineEnding::Keep => return content,
+    };
+    let mut result = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        let (body, line_ending) = split_line_ending(line);
+        result.push_str(body);
+        if !line_ending.is_empty() {
+            result.push_str(ending);
+        }
+    }
+    result
+}
+
+/// The extensions of Markdown files, whose front matter `strip_frontmatter` removes.
+pub const FRONTMATTER_EXTENSIONS: &[&str] = &["md", "markdown", "mdx"];
+
+/// Removes a leading front matter block, as used by Jekyll and Hugo, and the blank lines
+/// after it.
+///
+/// The block starts on the first line with `---` (YAML, closed by `---` or `...`) or `+++`
+/// (TOML, closed by `+++`). Content without a closing delimiter is left unchanged, so a
+/// stray horizontal rule never swallows a file.
+///
+/// # Arguments
+/// * `content` - The content of a Markdown file.
+///
+/// # Returns
+/// * `String` - The content after the front matter, or the content unchanged.
+pub fn strip_frontmatter(content: String) -> String {
+    let mut lines = content.split_inclusive('\n');
+    let Some(first) = lines.next() else {
+        return content;
+    };
+    let closers: &[&str] = match split_line_ending(first).0.trim_end() {
+        "---" => &["---", "..."],
+        "+++" => &["+++"],
+        _ => return content,
+    };
+    let mut offset = first.len();
+    let closed = lines.any(|line| {
+        offset += line.len();
+        closers.contains(&split_line_ending(line).0.trim_end())
+    });
+    if !closed {
+        return content;
+    }
+    let rest = &content[offset..];
+    let blank: usize = rest
+        .split_inclusive('\n')
+        .take_while(|line| line.trim().is_empty())
+        .map(str::len)
+        .sum();
+    rest[blank..].to_string()
+}
+
+/// Replaces tabs with spaces up to the next tab stop, so columns line up as in an editor.
+///
+/// The expansion is textual: a tab inside a string literal is expanded too. Columns are
+/// counted in characters, not display width.
+///
+/// # Arguments
+/// * `content` - The content to expand.
+/// * `width` - The distance between tab stops; `0` leaves the content unchanged.
+///
+/// # Returns
+/// * `String` - The content without tabs.
+pub fn expand_tabs(content: String, width: usize) -> String {
+    if width == 0 || !content.contains('\t') {
+        return content;
+    }
+    let mut result = String::with_capacity(content.len());
+    let mut column = 0;
+    for c in content.chars() {
+        match c {
+            '\t' => {
+                let spaces = width - column % width;
+                result.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\n' => {
+                result.push(c);
+                column = 0;
+            }
+            _ => {
+                result.push(c);
+                column += 1;
+            }
+        }
+    }
+    result
+}
+
+/// Strips trailing spaces and tabs from every line, and blank lines from the end.
+///
+/// Line endings are preserved, and content that ended with a line ending still does.
+///
+/// # Arguments
+/// * `content` - The content to trim.
+///
+/// # Returns
+/// * `String` - The trimmed content.
+pub fn trim_trailing_whitespace(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    // The end of the last line with content, so trailing blank lines can be cut off.
+    let mut end = 0;
+    for line in content.split_inclusive('\n') {
+        let (body, ending) = split_line_ending(line);
+        let body = body.trim_end_matches([' ', '\t']);
+        result.push_str(body);
+        if !body.is_empty() {
+            end = result.len() + ending.len();
+        }
+        result.push_str(ending);
+    }
+    result.truncate(end);
+    result
+}
+
+/// Removes ANSI escape sequences, such as the colors of recorded terminal output.
+///
+/// CSI sequences (`ESC [ … m`), OSC sequences ended by BEL or by ST (`ESC \`), the DCS,
+/// SOS, PM, and APC strings, and the short escapes such as `ESC ( B` or `ESC 7` are removed
+/// whole; an escape that starts none of them is removed alone. A string sequence without
+/// its terminator ends at the end of its line, so a stray escape cannot swallow the rest of
+/// the file.
+///
+/// The transformation is textual: it removes the escape character itself, so source code
+/// that spells an escape as `\x1b` or `\033` in a string literal is left unchanged.
+///
+/// # Arguments
+/// * `content` - The content to clean.
+///
+/// # Returns
+/// * `String` - The content without escape sequences.
+pub fn strip_ansi(content: &str) -> String {
+    if !content.contains('\x1b') {
+        return content.to_string();
+    }
+    let mut result = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            result.push(c);
+            continue;
+        }
+        match chars.peek() {
+            // CSI: parameter and intermediate bytes, then one final byte.
+            Some('[') => {
+                chars.next();
+                while let Some(&c) = chars.peek() {
+                    match c {
+                        '\x20'..='\x3f' => {
+                            chars.next();
+                        }
+                        '\x40'..='\x7e' => {
+                            chars.next();
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+            }
+            // OSC, DCS, SOS, PM, and APC: a string ended by BEL or ST.
+            Some(']' | 'P' | 'X' | '^' | '_') => {
+                chars.next();
+                while let Some(&c) = chars.peek() {
+                    match c {
+                        '\x07' => {
+                            chars.next();
+                            break;
+                        }
+                        '\x1b' => {
+                            chars.next();
+                            if chars.peek() == Some(&'\\') {
+                                chars.next();
+                            }
+                            break;
+                        }
+                        '\n' => break,
+                        _ => {
+                            chars.next();
+                        }
+                    }
+                }
+            }
+            // nF escapes: intermediate bytes, then one final byte, e.g. `ESC ( B`.
+            Some('\x20'..='\x2f') => {
+                while chars.next_if(|c| matches!(c, '\x20'..='\x2f')).is_some() {}
+                chars.next_if(|c| matches!(c, '\x30'..='\x7e'));
+            }
+            // Fp, Fe, and Fs escapes: one byte, e.g. `ESC 7` or `ESC =`.
+            Some('\x30'..='\x7e') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Removes C0 control characters other than tab and the line endings, such as NUL,
+/// backspace, and form feed.
+///
+/// The escape character is kept, so escape sequences are either removed whole by
+/// [`strip_ansi`] or left intact rather than cut in half.
+///
+/// # Arguments
+/// * `content` - The content to clean.
+///
+/// # Returns
+/// * `String` - The content without control characters.
+pub fn strip_control_chars(content: &str) -> String {
+    let is_stripped =
+        |c: char| c.is_ascii_control() && c != '\x7f' && !matches!(c, '\t' | '\n' | '\r' | '\x1b');
+    if !content.contains(is_stripped) {
+        return content.to_string();
+    }
+    content.chars().filter(|&c| !is_stripped(c)).collect()
+}
+
+/// Cuts every line longer than `max` characters down to `max`, followed by a marker with
+/// the number of characters removed.
+///
+/// Lengths are counted in characters, so a line is never cut inside a UTF-8 code point.
+///
+/// # Arguments
+/// * `content` - The content to truncate.
+/// * `max` - The most characters a line keeps.
+///
+/// # Returns
+/// * `(String, usize)` - The truncated content and the number of lines that were cut.
+pub fn truncate_long_lines(content: &str, max: usize) -> (String, usize) {
+    let mut result = String::with_capacity(content.len());
+    let mut truncated = 0;
+    for line in content.split_inclusive('\n') {
+        let (body, ending) = split_line_ending(line);
+        match body.char_indices().nth(max) {
+            Some((cut, _)) => {
+                let removed = body[cut..].chars().count();
+                result.push_str(&body[..cut]);
+                result.push_str(&format!(
+                    "… [truncated, {} more chars]",
+                    group_digits(removed)
+                ));
+                truncated += 1;
+            }
+            None => result.push_str(body),
+        }
+        result.push_str(ending);
+    }
+    (result, truncated)
+}
+
+/// Formats a number with `,` between groups of three digits, e.g. `98,231`.
+fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Collapses runs of blank lines into a single blank line and strips trailing whitespace.
+///
+/// Line endings (`\n` or `\r\n`) are preserved; a `\r` before the newline is not treated
+/// as content, so CRLF files compact the same way as LF files.
+///
+/// # Arguments
+/// * `content` - The content to compact.
+///
+/// # Returns
+/// * `String` - The compacted content.
+pub fn compact(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut previous_blank = false;
+
+    for line in content.split_inclusive('\n') {
+        let (body, ending) = split_line_ending(line);
+        let body = body.trim_end();
+        let blank = body.is_empty();
+        if blank && previous_blank {
+            continue;
+        }
+        previous_blank = blank;
+        result.push_str(body);
+        result.push_str(ending);
+    }
+
+    result
+}
+
+/// A piece of a file selected for output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// A contiguous run of lines from the file, keeping their line endings.
+    Lines {
+        /// The 1-based line number of the first line in the original file.
+        first_line: usize,
+        /// The text of the lines.
+        text: String,
+    },
+    /// A marker standing in for lines that were left out.
+    Omitted {
+        /// The number of lines left out.
+        lines: usize,
+        /// The marker text shown in their place.
+        marker: String,
+    },
+}
+
+impl Segment {
+    /// Returns the text of a `Lines` segment, or an empty string for markers.
+    pub fn text(&self) -> &str {
+        match self {
+            Segment::Lines { text, .. } => text,
+            Segment::Omitted { .. } => "",
+        }
+    }
+}
+
+/// Selects the first `head` and/or last `tail` lines of the content.
+///
+/// Lines that are left out are replaced by an [`Segment::Omitted`] marker: after the head,
+/// before the tail, or between them when both limits are set.
+///
+/// # Arguments
+/// * `content` - The file content.
+/// * `head` - The optional number of leading lines to keep.
+/// * `tail` - The optional number of trailing lines to keep.
+///
+/// # Returns
+/// * `Vec<Segment>` - The selected lines and omission markers, in output order.
+pub fn excerpt(content: &str, head: Option<usize>, tail: Option<usize>) -> Vec<Segment> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let total = lines.len();
+    let keep_head = head.unwrap_or(if tail.is_some() { 0 } else { total });
+    let keep_tail = tail.unwrap_or(0);
+
+    if keep_head + keep_tail >= total {
+        return vec![Segment::Lines {
+            first_line: 1,
+            text: content.to_string(),
+        }];
+    }
+
+    let omitted = total - keep_head - keep_tail;
+    let marker = match (keep_head, keep_tail) {
+        (_, 0) => format!("... ({} more lines truncated)", omitted),
+        (0, _) => format!("... ({} earlier lines truncated)", omitted),
+        _ => format!("... ({} lines truncated)", omitted),
+    };
+
+    let mut segments = Vec::with_capacity(3);
+    if keep_head > 0 {
+        segments.push(Segment::Lines {
+            first_line: 1,
+            text: lines[..keep_head].concat(),
+        });
+    }
+    segments.push(Segment::Omitted {
+        lines: omitted,
+        marker,
+    });
+    if keep_tail > 0 {
+        segments.push(Segment::Lines {
+            first_line: total - keep_tail + 1,
+            text: lines[total - keep_tail..].concat(),
+        });
+    }
+    segments
+}
+
+/// Which lines of a file over a per-file cap are kept (`truncate`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Truncation {
+    /// The start of the file (default).
+    #[default]
+    Head,
+    /// The end of the file.
+    Tail,
+    /// About 60% of the allowance from the start and the rest from the end, where many
+    /// languages put their exports or `main`.
+    Middle,
+}
+
+/// A limit on how much of each file is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileCap {
+    /// At most this many lines (`max_lines_per_file`).
+    Lines(usize),
+    /// At most this many tokens, counted by the tokenizer (`max_tokens_per_file`).
+    Tokens(usize, Tokenizer),
+}
+
+impl fmt::Display for FileCap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileCap::Lines(max) => write!(f, "{} lines", max),
+            FileCap::Tokens(max, _) => write!(f, "{} tokens", max),
+        }
+    }
+}
+
+/// One line of a file, or a run of lines already left out, while a cap is applied.
+enum Piece<'a> {
+    Line(usize, &'a str),
+    Gap(&'a Segment),
+}
+
+/// Cuts the selected lines of a file down to a cap, on line boundaries.
+///
+/// Each line is measured once, so a large file is tokenized once. The lines cut, including
+/// any already left out among them, are replaced by one marker: with [`Truncation::Head`],
+/// `[truncated at 4000 tokens, 1200 lines omitted]` at the end; with [`Truncation::Tail`],
+/// `[truncated at 4000 tokens, 1200 earlier lines omitted]` at the start; and with
+/// [`Truncation::Middle`], `… [1,200 lines omitted] …` between the kept ends, whose line
+/// numbers jump across it.
+///
+/// # Arguments
+/// * `segments` - The selected lines of the file and any omission markers.
+/// * `cap` - The cap.
+/// * `strategy` - Which lines to keep.
+///
+/// # Returns
+/// * `(Vec<Segment>, bool)` - The segments, and whether they were cut.
+pub fn truncate(
+    segments: Vec<Segment>,
+    cap: FileCap,
+    strategy: Truncation,
+) -> (Vec<Segment>, bool) {
+    let (allowance, cost) = match cap {
+        FileCap::Lines(max) => (max, None),
+        FileCap::Tokens(max, tokenizer) => (max, Some(tokenizer)),
+    };
+    let mut pieces = Vec::new();
+    let mut costs = Vec::new();
+    for segment in &segments {
+        match segment {
+            Segment::Lines { first_line, text } => {
+                for (i, line) in text.split_inclusive('\n').enumerate() {
+                    pieces.push(Piece::Line(first_line + i, line));
+                    costs.push(cost.map_or(1, |tokenizer| tokenizer.count(line)));
+                }
+            }
+            Segment::Omitted { .. } => {
+                pieces.push(Piece::Gap(segment));
+                costs.push(0);
+            }
+        }
+    }
+    if costs.iter().sum::<usize>() <= allowance {
+        return (segments, false);
+    }
+
+    // The number of pieces that fit an allowance, counting from one end; gaps at the edge
+    // are left to the marker.
+    let fit = |allowance: usize, costs: &mut dyn Iterator<Item = (usize, &Piece)>| {
+        let (mut used, mut taken, mut lines) = (0, 0, 0);
+        for (index, (cost, piece)) in costs.enumerate() {
+            if used + cost > allowance {
+                break;
+            }
+            used += cost;
+            if let Piece::Line(..) = piece {
+                (taken, lines) = (index + 1, used);
+            }
+        }
+        (taken, lines)
+    };
+    let forward = || costs.iter().copied().zip(&pieces);
+    let (head, tail) = match strategy {
+        Truncation::Head => (fit(allowance, &mut forward()).0, 0),
+        Truncation::Tail => (0, fit(allowance, &mut forward().rev()).0),
+        Truncation::Middle => {
+            let (head, used) = fit(allowance * 3 / 5, &mut forward());
+            let tail = fit(allowance - used, &mut forward().skip(head).rev()).0;
+            (head, tail)
+        }
+    };
+
+    let cut = &pieces[head..pieces.len() - tail];
+    let omitted = cut
+        .iter()
+        .map(|piece| match piece {
+            Piece::Line(..) => 1,
+            Piece::Gap(segment) => match segment {
+                Segment::Omitted { lines, .. } => *lines,
+                Segment::Lines { .. } => 0,
+            },
+        })
+        .sum();
+    let marker = match strategy {
+        Truncation::Head => format!("[truncated at {}, {} lines omitted]", cap, omitted),
+        Truncation::Tail => format!("[truncated at {}, {} earlier lines omitted]", cap, omitted),
+        Truncation::Middle => format!("… [{} lines omitted] …", group_digits(omitted)),
+    };
+    let mut truncated = Vec::new();
+    push_pieces(&mut truncated, &pieces[..head]);
+    truncated.push(Segment::Omitted {
+        lines: omitted,
+        marker,
+    });
+    push_pieces(&mut truncated, &pieces[pieces.len() - tail..]);
+    (truncated, true)
+}
+
+/// Appends pieces as segments, joining consecutive lines into one [`Segment::Lines`].
+fn push_pieces(segments: &mut Vec<Segment>, pieces: &[Piece]) {
+    for piece in pieces {
+        match (piece, segments.last_mut()) {
+            (Piece::Line(number, line), Some(Segment::Lines { first_line, text }))
+                if *first_line + text.split_inclusive('\n').count() == *number =>
+            {
+                text.push_str(line);
+            }
+            (Piece::Line(number, line), _) => segments.push(Segment::Lines {
+                first_line: *number,
+                text: line.to_string(),
+            }),
+            (Piece::Gap(segment), _) => segments.push((*segment).clone()),
+        }
+    }
+}
+
+/// A 1-based, inclusive line range of a single file, written as `<path>:<start>-<end>`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct LineRange {
+    /// The path of the file, relative to the bundled directory.
+    pub path: PathBuf,
+    /// The first line of the range.
+    pub start: usize,
+    /// The last line of the range.
+    pub end: usize,
+}
+
+impl FromStr for LineRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid line range `{}` (expected <path>:<start>-<end>)", s);
+        let (path, range) = s.rsplit_once(':').ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let start: usize = start.trim().parse().map_err(|_| invalid())?;
+        let end: usize = end.trim().parse().map_err(|_| invalid())?;
+        if path.is_empty() || start == 0 || start > end {
+            return Err(invalid());
+        }
+        let path = path.strip_prefix("./").unwrap_or(path);
+        Ok(LineRange {
+            path: PathBuf::from(path),
+            start,
+            end,
+        })
+    }
+}
+
+impl TryFrom<String> for LineRange {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for LineRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.path.display(), self.start, self.end)
+    }
+}
+
+impl Serialize for LineRange {
+    /// Serializes as the string form read from configuration files.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Sorts line ranges and merges the ones that overlap or touch.
+///
+/// # Arguments
+/// * `ranges` - The `(start, end)` pairs to merge, 1-based and inclusive.
+///
+/// # Returns
+/// * `Vec<(usize, usize)>` - The merged ranges in ascending order.
+pub fn merge_ranges(mut ranges: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Selects the given line ranges of the content, with an ellipsis marker between gaps.
+///
+/// # Arguments
+/// * `content` - The file content.
+/// * `ranges` - Sorted, non-overlapping `(start, end)` pairs within the content's line count.
+///
+/// # Returns
+/// * `Vec<Segment>` - The selected lines and omission markers, in output order.
+pub fn select_ranges(content: &str, ranges: &[(usize, usize)]) -> Vec<Segment> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut segments = Vec::with_capacity(ranges.len() * 2);
+    let mut previous_end = None;
+    for &(start, end) in ranges {
+        if let Some(previous_end) = previous_end {
+            segments.push(Segment::Omitted {
+                lines: start - previous_end - 1,
+                marker: "...".to_string(),
+            });
+        }
+        segments.push(Segment::Lines {
+            first_line: start,
+            text: lines[start - 1..end].concat(),
+        });
+        previous_end = Some(end);
+    }
+    segments
+}
+
+/// Splits a line into its body and its line ending (`"\r\n"`, `"\n"`, or `""`).
+fn split_line_ending(line: &str) -> (&str, &str) {
+    if let Some(body) = line.strip_suffix("\r\n") {
+        (body, "\r\n")
+    } else if let Some(body) = line.strip_suffix('\n') {
+        (body, "\n")
+    } else {
+        (line, "")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_escape_sequences_from_captured_terminal_output() {
+        let captured = include_str!("../tests/ansi/cargo_output.log");
+        assert_eq!(
+            strip_control_chars(&strip_ansi(captured)),
+            "   Compiling codebase-to-prompt v1.0.0 (/src)\n\
+             warning: unused variable: `x`\n \
+             --> src/main.rs:2:9\n\
+             docs and done\n\
+             saved\tcolumn\n\
+             test result: ok. 3 passed\n"
+        );
+    }
+
+    #[test]
+    fn strips_bare_and_unterminated_escapes_without_touching_source_escapes() {
+        assert_eq!(strip_ansi("a\x1b"), "a");
+        assert_eq!(strip_ansi("a\x1b\x1b\nb"), "a\nb");
+        assert_eq!(strip_ansi("a\x1b[31"), "a");
+        assert_eq!(strip_ansi("\x1b]0;unterminated\nnext\n"), "\nnext\n");
+        let source = "println!(\"\\x1b[31mred\\x1b[0m \\033[1m\");\n";
+        assert_eq!(strip_ansi(source), source);
+        assert_eq!(strip_control_chars(source), source);
+        assert_eq!(strip_control_chars("a\0b\r\n\x1b[1m"), "ab\r\n\x1b[1m");
+    }
+
+    #[test]
+    fn collapses_blank_runs_and_trims_trailing_whitespace() {
+        assert_eq!(compact("a  \n\n\n   \nb\t\n\n"), "a\n\nb\n\n");
+    }
+
+    #[test]
+    fn treats_carriage_returns_as_line_endings() {
+        assert_eq!(compact("a \r\n\r\n \r\n\r\nb\r\n"), "a\r\n\r\nb\r\n");
+    }
+
+    #[test]
+    fn normalizes_mixed_line_endings() {
+        let mixed = "a\r\nb\nc\rd\r\n\r\ne".to_string();
+        assert_eq!(
+            normalize_line_endings(mixed.clone(), LineEnding::Lf),
+            "a\nb\nc\rd\n\ne"
+        );
+        assert_eq!(
+            normalize_line_endings(mixed.clone(), LineEnding::Crlf),
+            "a\r\nb\r\nc\rd\r\n\r\ne"
+        );
+        assert_eq!(
+            normalize_line_endings(mixed.clone(), LineEnding::Keep),
+            mixed
+        );
+    }
+
+    #[test]
+    fn strips_yaml_and_toml_front_matter() {
+        let strip = |content: &str| strip_frontmatter(content.to_string());
+        assert_eq!(strip("---\ntitle: A\ntags: [x]\n---\n\n# A\n"), "# A\n");
+        assert_eq!(strip("---\ntitle: A\n...\nBody\n"), "Body\n");
+        assert_eq!(strip("+++\ntitle = \"A\"\n+++\r\nBody\r\n"), "Body\r\n");
+        assert_eq!(strip("---\n---\nBody"), "Body");
+        // A closing delimiter of the other style does not end the block.
+        assert_eq!(strip("+++\na = 1\n---\nBody\n"), "+++\na = 1\n---\nBody\n");
+    }
+
+    #[test]
+    fn leaves_unclosed_or_late_front_matter_alone() {
+        let strip = |content: &str| strip_frontmatter(content.to_string());
+        assert_eq!(strip("---\ntitle: A\n# A\n"), "---\ntitle: A\n# A\n");
+        assert_eq!(strip("---"), "---");
+        assert_eq!(strip("\n---\na: 1\n---\n"), "\n---\na: 1\n---\n");
+        assert_eq!(strip("# A\n---\nB\n---\n"), "# A\n---\nB\n---\n");
+    }
+
+    #[test]
+    fn expands_tabs_to_tab_stops() {
+        let expand = |content: &str, width| expand_tabs(content.to_string(), width);
+        assert_eq!(expand("\tx\n\t\ty\n", 4), "    x\n        y\n");
+        assert_eq!(expand("ab\tc\n", 4), "ab  c\n");
+        assert_eq!(expand("abcd\te", 4), "abcd    e");
+        assert_eq!(expand("a\tb\tc", 8), "a       b       c");
+        assert_eq!(expand("a\tb", 0), "a\tb");
+    }
+
+    #[test]
+    fn trims_trailing_whitespace_and_blank_lines() {
+        let fixture = "fn a() { \t\n    let x = 1;  \r\n  \n}\t\n \n\t\n";
+        assert_eq!(
+            trim_trailing_whitespace(fixture),
+            "fn a() {\n    let x = 1;\r\n\n}\n"
+        );
+        assert_eq!(trim_trailing_whitespace("end  "), "end");
+        assert_eq!(trim_trailing_whitespace("  \n\n"), "");
+    }
+
+    #[test]
+    fn truncates_long_lines_on_char_boundaries() {
+        let (content, truncated) = truncate_long_lines("short\néééééé\r\nabc", 4);
+        assert_eq!(
+            content,
+            "shor… [truncated, 1 more chars]\néééé… [truncated, 2 more chars]\r\nabc"
+        );
+        assert_eq!(truncated, 2);
+
+        let long = "x".repeat(98_241);
+        let (content, truncated) = truncate_long_lines(&long, 10);
+        assert_eq!(content, "xxxxxxxxxx… [truncated, 98,231 more chars]");
+        assert_eq!(truncated, 1);
+        assert_eq!(truncate_long_lines("ok\n", 2), ("ok\n".to_string(), 0));
+    }
+
+    #[test]
+    fn groups_digits_in_threes() {
+        assert_eq!(group_digits(0), "0");
+        assert_eq!(group_digits(999), "999");
+        assert_eq!(group_digits(1_000), "1,000");
+        assert_eq!(group_digits(1_234_567), "1,234,567");
+    }
+
+    fn lines(first_line: usize, text: &str) -> Segment {
+        Segment::Lines {
+            first_line,
+            text: text.to_string(),
+        }
+    }
+
+    fn omitted(lines: usize, marker: &str) -> Segment {
+        Segment::Omitted {
+            lines,
+            marker: marker.to_string(),
+        }
+    }
+
+    #[test]
+    fn excerpt_keeps_short_content_whole() {
+        assert_eq!(excerpt("a\nb\n", Some(2), None), vec![lines(1, "a\nb\n")]);
+        assert_eq!(
+            excerpt("a\nb\n", Some(1), Some(1)),
+            vec![lines(1, "a\nb\n")]
+        );
+    }
+
+    #[test]
+    fn excerpt_head_tail_and_both() {
+        let content = "1\n2\n3\n4\n5\n";
+        assert_eq!(
+            excerpt(content, Some(2), None),
+            vec![
+                lines(1, "1\n2\n"),
+                omitted(3, "... (3 more lines truncated)")
+            ]
+        );
+        assert_eq!(
+            excerpt(content, None, Some(2)),
+            vec![
+                omitted(3, "... (3 earlier lines truncated)"),
+                lines(4, "4\n5\n")
+            ]
+        );
+        assert_eq!(
+            excerpt(content, Some(1), Some(1)),
+            vec![
+                lines(1, "1\n"),
+                omitted(3, "... (3 lines truncated)"),
+                lines(5, "5\n")
+            ]
+        );
+    }
+
+    #[test]
+    fn token_caps_cut_at_line_boundaries() {
+        let tokens = |max| FileCap::Tokens(max, Tokenizer::Approx);
+        // Each line of eight bytes is two tokens with the approximate tokenizer.
+        let content = "line 01\nline 02\nline 03\nline 04\nline 05\n";
+        let (segments, truncated) =
+            truncate(excerpt(content, None, None), tokens(5), Truncation::Head);
+        assert!(truncated);
+        assert_eq!(
+            segments,
+            vec![
+                lines(1, "line 01\nline 02\n"),
+                omitted(3, "[truncated at 5 tokens, 3 lines omitted]")
+            ]
+        );
+
+        // Lines already left out after the cut are counted as omitted too.
+        let (segments, _) = truncate(
+            excerpt(content, Some(2), Some(1)),
+            tokens(3),
+            Truncation::Head,
+        );
+        assert_eq!(
+            segments,
+            vec![
+                lines(1, "line 01\n"),
+                omitted(4, "[truncated at 3 tokens, 4 lines omitted]")
+            ]
+        );
+
+        // A first line over the cap leaves only the marker.
+        let (segments, _) = truncate(excerpt(content, None, None), tokens(1), Truncation::Head);
+        assert_eq!(
+            segments,
+            vec![omitted(5, "[truncated at 1 tokens, 5 lines omitted]")]
+        );
+
+        let whole = excerpt(content, None, None);
+        assert_eq!(
+            truncate(whole.clone(), tokens(10), Truncation::Middle),
+            (whole, false)
+        );
+    }
+
+    #[test]
+    fn truncation_keeps_the_head_the_tail_or_both_ends() {
+        let content: String = (1..=10).map(|n| format!("line {}\n", n)).collect();
+        let cut = |strategy| truncate(excerpt(&content, None, None), FileCap::Lines(5), strategy).0;
+        assert_eq!(
+            cut(Truncation::Tail),
+            vec![
+                omitted(5, "[truncated at 5 lines, 5 earlier lines omitted]"),
+                lines(6, "line 6\nline 7\nline 8\nline 9\nline 10\n")
+            ]
+        );
+        assert_eq!(
+            cut(Truncation::Middle),
+            vec![
+                lines(1, "line 1\nline 2\nline 3\n"),
+                omitted(5, "… [5 lines omitted] …"),
+                lines(9, "line 9\nline 10\n")
+            ]
+        );
+
+        // Lines already left out in the cut are counted as omitted.
+        let segments = excerpt(&content, Some(4), Some(4));
+        assert_eq!(
+            truncate(segments, FileCap::Lines(5), Truncation::Middle).0,
+            vec![
+                lines(1, "line 1\nline 2\nline 3\n"),
+                omitted(5, "… [5 lines omitted] …"),
+                lines(9, "line 9\nline 10\n")
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_line_ranges() {
+        let range: LineRange = "./src/lib.rs:120-240".parse().unwrap();
+        assert_eq!(range.path, PathBuf::from("src/lib.rs"));
+        assert_eq!((range.start, range.end), (120, 240));
+
+        let single: LineRange = "C:/code/main.rs:7".parse().unwrap();
+        assert_eq!(single.path, PathBuf::from("C:/code/main.rs"));
+        assert_eq!((single.start, single.end), (7, 7));
+
+        assert!("src/lib.rs".parse::<LineRange>().is_err());
+        assert!("src/lib.rs:0-4".parse::<LineRange>().is_err());
+        assert!("src/lib.rs:9-4".parse::<LineRange>().is_err());
+    }
+
+    #[test]
+    fn merges_and_selects_ranges() {
+        let ranges = merge_ranges(vec![(6, 7), (1, 2), (2, 3)]);
+        assert_eq!(ranges, vec![(1, 3), (6, 7)]);
+
+        let content = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        assert_eq!(
+            select_ranges(content, &ranges),
+            vec![lines(1, "1\n2\n3\n"), omitted(2, "..."), lines(6, "6\n7\n")]
+        );
+    }
+
+    #[test]
+    fn keeps_single_blank_lines_and_leading_indentation() {
+        let source = "fn f() {\n    x();\n\n    y();\n}";
+        assert_eq!(compact(source), source);
+    }
+}
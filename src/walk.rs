@@ -0,0 +1,317 @@
+//! Directory traversal on top of `ignore::WalkBuilder`.
+//!
+//! The builder applies `.gitignore` (nested, global, and `.git/info/exclude`), `.ignore`, and
+//! `.rgignore` rules the way git and ripgrep do. Hidden entries, `.c2pignore` rules, and the
+//! `export-ignore` attribute are filtered here instead, so `keep_hidden` exceptions work and
+//! skips can be attributed.
+
+use crate::git::GitRepo;
+use crate::{Config, FileFilter, SkipReason, load_ignore_files};
+use anyhow::{Context, Result};
+use ignore::gitignore::Gitignore;
+use ignore::{DirEntry, Match, WalkBuilder};
+use std::collections::HashSet;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info, warn};
+
+/// What the entry filter saw during a walk.
+#[derive(Debug, Default)]
+pub(crate) struct Filtered {
+    /// Every entry the ignore rules let through to the filter, kept or not.
+    pub(crate) seen: HashSet<PathBuf>,
+    /// The entries the filter left out, in walk order.
+    pub(crate) skipped: Vec<(PathBuf, SkipReason)>,
+}
+
+/// A walk over one root, with the record of its filter.
+pub(crate) struct Walk {
+    /// The entries that pass the ignore rules and the filter, sorted by name per directory.
+    pub(crate) entries: ignore::Walk,
+    filtered: Arc<Mutex<Filtered>>,
+}
+
+impl Walk {
+    /// Builds the walk over a root directory.
+    ///
+    /// `respect_gitignore` maps to the builder's git ignore, global, and exclude options, and
+    /// `respect_ignore_files` to `.ignore` and `.rgignore`. Directories in `skip_roots` (other
+    /// than the root) are pruned, so submodules walked separately are only visited once.
+    ///
+    /// # Arguments
+    /// * `root` - The directory to walk.
+    /// * `skip_roots` - Directories walked separately that must not be descended into.
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<Walk>` - The walk, or an error if a `keep_hidden` pattern or ignore file is
+    ///   invalid.
+    pub(crate) fn new(root: &Path, skip_roots: &[PathBuf], config: &Config) -> Result<Walk> {
+        let filter = EntryFilter {
+            root: root.to_path_buf(),
+            skip_roots: skip_roots
+                .iter()
+                .filter(|path| *path != root)
+                .cloned()
+                .collect(),
+            ignore_file: load_ignore_files(root, config)?,
+            filter: FileFilter::new(config)?,
+            export_ignore: match config.respect_export_ignore {
+                true => ExportIgnore::for_root(root)?,
+                false => None,
+            },
+            dry_run: config.dry_run,
+        };
+        let filtered = Arc::new(Mutex::new(Filtered::default()));
+
+        let mut builder = WalkBuilder::new(root);
+        builder
+            .standard_filters(false)
+            .git_ignore(config.respect_gitignore)
+            .git_global(config.respect_gitignore)
+            .git_exclude(config.respect_gitignore)
+            .ignore(config.respect_ignore_files)
+            .parents(true)
+            // A `.gitignore` counts outside a git repository too, as it always has here.
+            .require_git(false)
+            .follow_links(config.follow_symlinks)
+            .sort_by_file_name(|a, b| a.cmp(b));
+        if config.respect_ignore_files {
+            builder.add_custom_ignore_filename(".rgignore");
+        }
+        let record = Arc::clone(&filtered);
+        builder.filter_entry(move |entry| {
+            let mut filtered = record.lock().unwrap_or_else(|e| e.into_inner());
+            filtered.seen.insert(entry.path().to_path_buf());
+            if filter.skip_roots.iter().any(|root| root == entry.path()) {
+                return false;
+            }
+            let reason = filter.exclusion(entry);
+            if let Some(reason) = reason {
+                filtered.skipped.push((entry.path().to_path_buf(), reason));
+            }
+            reason.is_none()
+        });
+
+        Ok(Walk {
+            entries: builder.build(),
+            filtered,
+        })
+    }
+
+    /// Takes what the filter has seen so far.
+    pub(crate) fn take_filtered(&self) -> Filtered {
+        mem::take(&mut *self.filtered.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// The filtering the walk builder cannot do: version control and excluded directories, hidden
+/// entries with exceptions, `.c2pignore` rules, the `export-ignore` attribute, and separately
+/// walked roots, which are pruned without being reported.
+///
+/// Everything here runs before a directory is entered, so a rejected directory costs one check
+/// rather than a visit to each file below it. File-level filters such as extensions run later.
+struct EntryFilter {
+    root: PathBuf,
+    skip_roots: Vec<PathBuf>,
+    ignore_file: Gitignore,
+    /// The name and path rules shared with the other sources.
+    filter: FileFilter,
+    export_ignore: Option<ExportIgnore>,
+    dry_run: bool,
+}
+
+impl EntryFilter {
+    /// Determines why an entry is left out, if it is.
+    ///
+    /// # Arguments
+    /// * `entry` - The entry to check.
+    ///
+    /// # Returns
+    /// * `Option<SkipReason>` - The reason the entry is skipped, or `None` if it is included.
+    fn exclusion(&self, entry: &DirEntry) -> Option<SkipReason> {
+        let path = entry.path();
+        let is_dir = entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_dir());
+        let relative_path = path.strip_prefix(&self.root).unwrap_or(path);
+        if let Some(reason) = self.filter.entry_exclusion(relative_path, is_dir) {
+            return Some(reason);
+        }
+        if let Match::Ignore(rule) = self.ignore_file.matched(path, is_dir) {
+            self.attribute(format!(
+                "Skipping {} (`{}` in {})",
+                path.display(),
+                rule.original(),
+                rule.from().unwrap_or(Path::new("--ignore-file")).display()
+            ));
+            return Some(SkipReason::IgnoreFile);
+        }
+        // The root was asked for by name, so only what is below it can be export-ignored.
+        if let Some(export_ignore) = &self.export_ignore
+            && entry.depth() > 0
+            && export_ignore.matches(relative_path)
+        {
+            self.attribute(format!(
+                "Skipping {} (export-ignore in .gitattributes)",
+                path.display()
+            ));
+            return Some(SkipReason::ExportIgnore);
+        }
+        None
+    }
+
+    /// Logs why an entry is skipped.
+    fn attribute(&self, attribution: String) {
+        // A dry run is how patterns get debugged, so its attributions are shown by default.
+        if self.dry_run {
+            info!("{}", attribution);
+        } else {
+            debug!("{}", attribution);
+        }
+    }
+}
+
+/// The `export-ignore` attribute of the repository enclosing a walked root.
+struct ExportIgnore {
+    /// The repository, locked as the walk filter must be shareable between threads.
+    repo: Mutex<GitRepo>,
+    /// The walked root relative to the working tree.
+    prefix: PathBuf,
+}
+
+impl ExportIgnore {
+    /// Opens the repository enclosing a root for `respect_export_ignore`.
+    ///
+    /// # Arguments
+    /// * `root` - The directory to walk.
+    ///
+    /// # Returns
+    /// * `Result<Option<ExportIgnore>>` - The attribute lookup, `None` with a warning if the
+    ///   root is not in a working tree, or an error if this build has no git support.
+    fn for_root(root: &Path) -> Result<Option<ExportIgnore>> {
+        let repo = GitRepo::discover(&[root.to_path_buf()])?;
+        let Some(workdir) = repo.as_ref().and_then(GitRepo::workdir) else {
+            warn!(
+                "Ignoring --respect-export-ignore for {}: it is not inside a git working tree",
+                root.display()
+            );
+            return Ok(None);
+        };
+        let workdir = workdir
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve {}", workdir.display()))?;
+        let prefix = root
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve {}", root.display()))?
+            .strip_prefix(&workdir)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Ok(repo.map(|repo| ExportIgnore {
+            repo: Mutex::new(repo),
+            prefix,
+        }))
+    }
+
+    /// Checks whether an entry carries the attribute.
+    ///
+    /// # Arguments
+    /// * `relative_path` - The path of the entry relative to the walked root.
+    ///
+    /// # Returns
+    /// * `bool` - Whether the entry is left out of source archives.
+    fn matches(&self, relative_path: &Path) -> bool {
+        let repo = self.repo.lock().unwrap_or_else(|e| e.into_inner());
+        repo.is_export_ignored(&self.prefix.join(relative_path))
+    }
+}
+
+/// Finds the path of a walk error and, for a symlink loop, the ancestor it loops back to.
+///
+/// # Arguments
+/// * `err` - The error, possibly wrapped with a path or depth.
+///
+/// # Returns
+/// * `(Option<&Path>, Option<&Path>)` - The path of the failed entry and the loop ancestor.
+pub(crate) fn error_parts(err: &ignore::Error) -> (Option<&Path>, Option<&Path>) {
+    match err {
+        ignore::Error::WithPath { path, err } => (Some(path), error_parts(err).1),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_parts(err)
+        }
+        ignore::Error::Loop { ancestor, child } => (Some(child), Some(ancestor)),
+        _ => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Walks a root and returns what the filter was shown, relative to the root.
+    fn visited(root: &Path, config: &Config) -> Vec<String> {
+        let mut walk = Walk::new(root, &[], config).unwrap();
+        walk.entries.by_ref().for_each(drop);
+        let mut seen: Vec<String> = walk
+            .take_filtered()
+            .seen
+            .iter()
+            .map(|path| {
+                let relative = path.strip_prefix(root).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect();
+        seen.sort();
+        seen
+    }
+
+    #[test]
+    fn excluded_directories_are_not_entered() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().join("repo");
+        for dir in [
+            "target/debug/deps",
+            "node_modules/pkg",
+            "src/generated",
+            "src/gitignored",
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(root.join(dir).join("file.rs"), "\n").unwrap();
+        }
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join(".gitignore"), "gitignored/\n").unwrap();
+
+        let config = Config::builder()
+            .exclude_dirs(["target/", "node_modules", "/src/generated"])
+            .build();
+        assert_eq!(
+            visited(&root, &config),
+            [
+                ".gitignore",
+                "node_modules",
+                "src",
+                "src/generated",
+                "src/main.rs",
+                "target"
+            ]
+        );
+    }
+
+    #[test]
+    fn exclude_dir_patterns_only_match_directories() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().join("repo");
+        fs::create_dir_all(root.join("crates/a/fixtures")).unwrap();
+        fs::write(root.join("crates/a/fixtures/data.txt"), "\n").unwrap();
+        fs::write(root.join("crates/a/build"), "\n").unwrap();
+
+        let config = Config::builder()
+            .exclude_dirs(["crates/*/fixtures", "build"])
+            .build();
+        let visited = visited(&root, &config);
+        assert!(visited.contains(&"crates/a/build".to_string()));
+        assert!(!visited.contains(&"crates/a/fixtures/data.txt".to_string()));
+    }
+}
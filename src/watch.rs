@@ -0,0 +1,249 @@
+//! Watch mode: regenerates the output whenever a bundled file changes.
+//!
+//! Changes are detected by polling the modification time and size of every file that
+//! passes the filters, so no platform-specific notification backend is needed.
+
+use crate::filter_rules::FilterRules;
+use crate::interrupt::{self, install_interrupt_handler};
+use crate::output_template;
+use crate::walk::Walk;
+use crate::{Config, RunSummary, run};
+use anyhow::{Result, bail};
+use globset::GlobMatcher;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+
+/// How long the tree must stay unchanged before a rebuild starts.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How often the stop flag is checked while waiting.
+const TICK: Duration = Duration::from_millis(50);
+
+/// The modification time and size of each watched file.
+type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+/// Builds the output, then rebuilds it whenever a file that passes the filters changes.
+///
+/// Rebuilds are debounced, and the files a build writes (the output, cache, statistics,
+/// manifest, and report files) are never watched. A rebuild that fails is logged, and the
+/// next change rebuilds again. Returns when the process receives an interrupt (Ctrl-C); a
+/// build in progress stops at the next file.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process; an output file is required.
+/// * `on_build` - Called with the summary of every successful build, including the initial
+///   one.
+///
+/// # Returns
+/// * `Result<()>` - Returns `Ok(())` when interrupted, or an error if the initial build fails.
+pub fn watch(config: Config, mut on_build: impl FnMut(&RunSummary)) -> Result<()> {
+    let Some(output) = &config.output else {
+        bail!("--watch requires an output file (--output)");
+    };
+    if config
+        .files_from
+        .as_deref()
+        .is_some_and(|list| list == Path::new("-"))
+    {
+        bail!("--watch cannot read the file list from stdin");
+    }
+    let written = Written::new(&config, output)?;
+    install_interrupt_handler();
+
+    let mut built = snapshot(&config, &written);
+    on_build(&run(config.clone())?);
+    // Rebuilds replace what the first build wrote.
+    let config = Config {
+        force: true,
+        backup: false,
+        ..config
+    };
+    info!("Watching for changes (press Ctrl-C to stop)...");
+
+    while wait(DEBOUNCE) {
+        let mut current = snapshot(&config, &written);
+        if current == built {
+            continue;
+        }
+        // Wait for the tree to settle so a burst of saves triggers a single rebuild.
+        loop {
+            if !wait(DEBOUNCE) {
+                return Ok(());
+            }
+            let next = snapshot(&config, &written);
+            if next == current {
+                break;
+            }
+            current = next;
+        }
+        match run(config.clone()) {
+            Ok(summary) => on_build(&summary),
+            Err(err) => error!("Rebuild failed: {:#}", anyhow::Error::from(err)),
+        }
+        built = current;
+    }
+    Ok(())
+}
+
+/// The files a build writes, which are never watched.
+struct Written {
+    /// The canonical paths of the output, also-output, cache, statistics, manifest, and
+    /// report files.
+    paths: HashSet<PathBuf>,
+    /// The canonical directory and file name pattern of outputs named per run, e.g. with
+    /// `append_date` or `{n}`.
+    previous: Vec<(PathBuf, GlobMatcher)>,
+    /// The extension `compress` adds to output file names.
+    compressed: Option<&'static str>,
+}
+
+impl Written {
+    /// Resolves the files a build writes, once before watching starts.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    /// * `output` - The output file, before its name template is applied.
+    ///
+    /// # Returns
+    /// * `Result<Written>` - The files, or an error if the output name template is invalid.
+    fn new(config: &Config, output: &Path) -> Result<Self> {
+        let compressed = config.compress.map(|compression| compression.extension());
+        let mut outputs = vec![output.to_path_buf()];
+        outputs.extend(config.also_output.iter().cloned());
+        let mut paths = HashSet::new();
+        let mut previous = Vec::new();
+        for output in &outputs {
+            let resolved = output_template::output_path(Some(output), config)?
+                .unwrap_or_else(|| output.clone());
+            let resolved = match config.compress {
+                Some(compression) => compression.output_path(&resolved),
+                None => resolved,
+            };
+            paths.insert(canonical(&resolved));
+            if let Some((dir, matcher)) = output_template::previous_outputs(Some(output), config)? {
+                previous.push((canonical(&Path::new(".").join(dir)), matcher));
+            }
+        }
+        // The cache, statistics, manifest, and report files are rewritten by every build, and
+        // may not exist yet.
+        paths.extend(
+            [
+                config.cache.as_deref(),
+                config.stats_output.as_deref(),
+                config.manifest.as_deref(),
+                config.report_json.as_deref(),
+            ]
+            .into_iter()
+            .flatten()
+            .map(canonical),
+        );
+        Ok(Written {
+            paths,
+            previous,
+            compressed,
+        })
+    }
+
+    /// Returns whether a file is written by a build.
+    ///
+    /// # Arguments
+    /// * `path` - The canonical path of the file.
+    fn contains(&self, path: &Path) -> bool {
+        if self.paths.contains(path) {
+            return true;
+        }
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return false;
+        };
+        // A compressed output is matched by its name without the compression extension.
+        let uncompressed = self
+            .compressed
+            .and_then(|extension| {
+                let name = name.to_str()?;
+                name.strip_suffix(extension)?.strip_suffix('.')
+            })
+            .map(OsStr::new);
+        self.previous.iter().any(|(dir, matcher)| {
+            parent == dir
+                && (matcher.is_match(name)
+                    || uncompressed.is_some_and(|name| matcher.is_match(name)))
+        })
+    }
+}
+
+/// Canonicalizes a path that may not exist yet, through its parent directory.
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match (parent.canonicalize(), path.file_name()) {
+        (Ok(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Records the modification time and size of every file that passes the filters.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+/// * `written` - The files a build writes, which are left out.
+///
+/// # Returns
+/// * `Snapshot` - The state of the watched files.
+fn snapshot(config: &Config, written: &Written) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    let rules = FilterRules::new(config).unwrap_or_default();
+    for directory in &config.directories {
+        // An invalid pattern or ignore file has already failed the first run.
+        let Ok(walk) = Walk::new(directory, &[], config) else {
+            continue;
+        };
+        // Walked paths are joined to the canonical directory rather than canonicalized one
+        // by one on every poll.
+        let root = directory
+            .canonicalize()
+            .unwrap_or_else(|_| directory.clone());
+        for entry in walk.entries.flatten() {
+            let path = entry.path();
+            let relative = path.strip_prefix(directory).unwrap_or(path);
+            if !path.is_file() || !rules.allows(relative) {
+                continue;
+            }
+            if written.contains(&root.join(relative)) {
+                continue;
+            }
+            if let Ok(metadata) = fs::metadata(path) {
+                snapshot.insert(
+                    path.to_path_buf(),
+                    (metadata.modified().ok(), metadata.len()),
+                );
+            }
+        }
+    }
+    snapshot
+}
+
+/// Sleeps for the given duration unless an interrupt arrives first.
+///
+/// # Returns
+/// * `bool` - `true` if the full duration elapsed, `false` if watching should stop.
+fn wait(duration: Duration) -> bool {
+    let mut waited = Duration::ZERO;
+    while waited < duration {
+        if interrupt::requested() {
+            return false;
+        }
+        thread::sleep(TICK);
+        waited += TICK;
+    }
+    !interrupt::requested()
+}
@@ -0,0 +1,481 @@
+//! Cargo workspaces (`workspace_members`, `--group-by member`): the members listed in the
+//! root `Cargo.toml`, read with a light TOML parse or, with `use_cargo_metadata`, from
+//! `cargo metadata`.
+
+use crate::Config;
+use crate::json;
+use crate::rust_modules::normalize;
+use crate::toml::{self, Table, Value};
+use anyhow::{Context, Result, bail};
+use globset::GlobBuilder;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, warn};
+
+/// The section of files outside every member with `--group-by member`.
+const OUTSIDE_MEMBERS: &str = ".";
+
+/// A package of a Cargo workspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WorkspaceMember {
+    /// The package name, e.g. `api`.
+    pub name: String,
+    /// The package version, if its `Cargo.toml` sets one.
+    pub version: Option<String>,
+    /// The directory of the package relative to the workspace root; empty for a root package.
+    pub path: PathBuf,
+}
+
+impl WorkspaceMember {
+    /// Returns the heading of the member's section: its name and version, e.g. `api 0.3.1`.
+    pub fn heading(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{} {}", self.name, version),
+            None => self.name.clone(),
+        }
+    }
+}
+
+impl fmt::Display for WorkspaceMember {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &self.path
+        };
+        write!(f, "{}\t{}", self.heading(), path.display())
+    }
+}
+
+/// The members of the workspace a run reads, and those it keeps.
+#[derive(Debug)]
+pub(crate) struct Workspace {
+    members: Vec<WorkspaceMember>,
+    /// The indexes of the members chosen with `workspace_members`; `None` keeps every file.
+    selected: Option<HashSet<usize>>,
+}
+
+impl Workspace {
+    /// Loads the workspace of a run that selects members or groups files by member.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration options for the bundling process.
+    ///
+    /// # Returns
+    /// * `Result<Option<Workspace>>` - The workspace, `None` if the run needs none, or an
+    ///   error if the input is not a single workspace root or a named member does not exist.
+    pub(crate) fn for_run(config: &Config) -> Result<Option<Workspace>> {
+        if config.workspace_members.is_empty() && config.group_by != Some(crate::GroupBy::Member) {
+            return Ok(None);
+        }
+        let members = members(config)?;
+        let selected = match config.workspace_members.as_slice() {
+            [] => None,
+            names => Some(select(&members, names)?),
+        };
+        Ok(Some(Workspace { members, selected }))
+    }
+
+    /// Finds the member a file belongs to: the one with the deepest directory above it.
+    fn member_index(&self, path: &Path) -> Option<usize> {
+        self.members
+            .iter()
+            .enumerate()
+            .filter(|(_, member)| path.starts_with(&member.path))
+            .max_by_key(|(_, member)| member.path.components().count())
+            .map(|(index, _)| index)
+    }
+
+    /// Checks whether a file belongs to a selected member, or any file without a selection.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the file relative to the workspace root.
+    pub(crate) fn includes(&self, path: &Path) -> bool {
+        match &self.selected {
+            Some(selected) => self
+                .member_index(path)
+                .is_some_and(|index| selected.contains(&index)),
+            None => true,
+        }
+    }
+
+    /// Returns the section a file belongs to: its member's heading, or `.` outside every
+    /// member.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the file relative to the workspace root.
+    pub(crate) fn section(&self, path: &Path) -> String {
+        self.member_index(path).map_or_else(
+            || OUTSIDE_MEMBERS.to_string(),
+            |index| self.members[index].heading(),
+        )
+    }
+}
+
+/// Resolves the members of the workspace a run reads.
+///
+/// # Arguments
+/// * `config` - The configuration options for the bundling process.
+///
+/// # Returns
+/// * `Result<Vec<WorkspaceMember>>` - The members ordered by path, or an error if the input
+///   is not a single directory that is the root of a Cargo workspace.
+pub(crate) fn members(config: &Config) -> Result<Vec<WorkspaceMember>> {
+    let [root] = config.directories.as_slice() else {
+        bail!("Workspace members need a single input directory, the workspace root");
+    };
+    let mut members = if config.use_cargo_metadata {
+        cargo_metadata(root)?
+    } else {
+        read_manifests(root)?
+    };
+    members.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(members)
+}
+
+/// Finds the members named by `workspace_members`, by package name or directory.
+fn select(members: &[WorkspaceMember], names: &[String]) -> Result<HashSet<usize>> {
+    names
+        .iter()
+        .map(|name| {
+            let path = normalize(Path::new(name));
+            members
+                .iter()
+                .position(|member| member.name == *name || member.path == path)
+                .with_context(|| {
+                    let known: Vec<&str> = members.iter().map(|m| m.name.as_str()).collect();
+                    format!(
+                        "No workspace member named `{}`; the members are: {}",
+                        name,
+                        known.join(", ")
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Reads the members from the `[workspace]` table of the root `Cargo.toml` and the
+/// `[package]` table of each member's.
+///
+/// Member globs are expanded one path component at a time, `**` matching any number of
+/// directories, and directories without a `Cargo.toml` are left out as Cargo warns about
+/// them. Members under a `workspace.exclude` path are dropped, and a root package is a
+/// member too.
+fn read_manifests(root: &Path) -> Result<Vec<WorkspaceMember>> {
+    let manifest = read_manifest(root)
+        .with_context(|| format!("{} is not a Cargo workspace", root.display()))?;
+    let Some(Value::Table(workspace)) = manifest.get("workspace") else {
+        bail!(
+            "{} is not a Cargo workspace: its Cargo.toml has no [workspace] table",
+            root.display()
+        );
+    };
+    let exclude: Vec<PathBuf> = strings(workspace.get("exclude"))
+        .map(|path| normalize(Path::new(path)))
+        .collect();
+    let inherited = match lookup(workspace, &["package", "version"]) {
+        Some(Value::String(version)) => Some(version.as_str()),
+        _ => None,
+    };
+
+    let mut directories = Vec::new();
+    for pattern in strings(workspace.get("members")) {
+        let pattern = normalize(Path::new(pattern));
+        let components: Vec<&str> = pattern
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => name.to_str(),
+                _ => None,
+            })
+            .collect();
+        expand(root, PathBuf::new(), &components, &mut directories)
+            .with_context(|| format!("Invalid workspace member `{}`", pattern.display()))?;
+    }
+    if manifest.contains_key("package") {
+        directories.push(PathBuf::new());
+    }
+    directories.sort();
+    directories.dedup();
+
+    let mut members = Vec::new();
+    for directory in directories {
+        if exclude
+            .iter()
+            .any(|excluded| directory.starts_with(excluded))
+        {
+            debug!("{} is excluded from the workspace", directory.display());
+            continue;
+        }
+        let manifest = if directory.as_os_str().is_empty() {
+            manifest.clone()
+        } else {
+            match read_manifest(&root.join(&directory)) {
+                Ok(manifest) => manifest,
+                Err(err) => {
+                    warn!(
+                        "Skipping workspace member {}: {:#}",
+                        directory.display(),
+                        err
+                    );
+                    continue;
+                }
+            }
+        };
+        let name = match lookup(&manifest, &["package", "name"]) {
+            Some(Value::String(name)) => name.clone(),
+            _ => directory
+                .file_name()
+                .unwrap_or(root.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+        };
+        let version = match lookup(&manifest, &["package", "version"]) {
+            Some(Value::String(version)) => Some(version.clone()),
+            Some(Value::Table(_)) => inherited.map(str::to_string),
+            _ => None,
+        };
+        members.push(WorkspaceMember {
+            name,
+            version,
+            path: directory,
+        });
+    }
+    Ok(members)
+}
+
+/// Reads and parses the `Cargo.toml` of a directory.
+fn read_manifest(directory: &Path) -> Result<Table> {
+    let path = directory.join("Cargo.toml");
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::parse(&text).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Looks up a value by its dotted key, e.g. `["package", "version"]`.
+fn lookup<'a>(table: &'a Table, keys: &[&str]) -> Option<&'a Value> {
+    let (last, tables) = keys.split_last()?;
+    let mut table = table;
+    for key in tables {
+        match table.get(*key)? {
+            Value::Table(inner) => table = inner,
+            _ => return None,
+        }
+    }
+    table.get(*last)
+}
+
+/// Returns the strings of an array value, or none if it is missing or not an array.
+fn strings(value: Option<&Value>) -> impl Iterator<Item = &str> {
+    let values = match value {
+        Some(Value::Array(values)) => values.as_slice(),
+        _ => &[],
+    };
+    values.iter().filter_map(|value| match value {
+        Value::String(text) => Some(text.as_str()),
+        _ => None,
+    })
+}
+
+/// Adds the directories under `root` matching a member pattern to `found`, relative to
+/// `root`.
+///
+/// # Arguments
+/// * `root` - The workspace root.
+/// * `directory` - The directory matched so far, relative to `root`.
+/// * `components` - The rest of the pattern, one glob per path component.
+/// * `found` - The matching directories that have a `Cargo.toml`.
+///
+/// # Returns
+/// * `Result<()>` - `Ok(())`, or an error if a component is not a valid glob.
+fn expand(
+    root: &Path,
+    directory: PathBuf,
+    components: &[&str],
+    found: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let Some((component, rest)) = components.split_first() else {
+        if root.join(&directory).join("Cargo.toml").is_file() {
+            found.push(directory);
+        } else {
+            debug!("{} has no Cargo.toml", directory.display());
+        }
+        return Ok(());
+    };
+    if *component == "**" {
+        expand(root, directory.clone(), rest, found)?;
+        for child in subdirectories(&root.join(&directory)) {
+            expand(root, directory.join(child), components, found)?;
+        }
+        return Ok(());
+    }
+    if !component.contains(['*', '?', '[', '{']) {
+        return expand(root, directory.join(component), rest, found);
+    }
+    let glob = GlobBuilder::new(component)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher();
+    for child in subdirectories(&root.join(&directory)) {
+        if glob.is_match(&child) {
+            expand(root, directory.join(child), rest, found)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the names of the visible subdirectories of a directory, sorted.
+fn subdirectories(directory: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(directory)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    names.sort();
+    names
+}
+
+/// The part of `cargo metadata --no-deps` output that names the members.
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    workspace_root: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+    version: String,
+    manifest_path: PathBuf,
+}
+
+/// Reads the members from `cargo metadata`, for workspaces the light parse gets wrong.
+fn cargo_metadata(root: &Path) -> Result<Vec<WorkspaceMember>> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .arg("--manifest-path")
+        .arg(root.join("Cargo.toml"))
+        .output()
+        .context("Failed to run cargo metadata")?;
+    if !output.status.success() {
+        bail!(
+            "{} is not a Cargo workspace: {}",
+            root.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let metadata: Metadata = json::from_str(&String::from_utf8_lossy(&output.stdout))
+        .context("Failed to parse the output of cargo metadata")?;
+    Ok(metadata
+        .packages
+        .into_iter()
+        .map(|package| {
+            let directory = package.manifest_path.parent().unwrap_or(Path::new(""));
+            WorkspaceMember {
+                name: package.name,
+                version: Some(package.version),
+                path: directory
+                    .strip_prefix(&metadata.workspace_root)
+                    .unwrap_or(directory)
+                    .to_path_buf(),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn members_expand_globs_and_honour_excludes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        write(
+            root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\", \"tools/**\"]\nexclude = [\"crates/legacy\"]\n\
+             [workspace.package]\nversion = \"2.0.0\"\n",
+        );
+        write(
+            root,
+            "crates/api/Cargo.toml",
+            "[package]\nname = \"api\"\nversion = \"0.3.1\"\n",
+        );
+        write(
+            root,
+            "crates/shared/Cargo.toml",
+            "[package]\nname = \"shared-types\"\nversion.workspace = true\n",
+        );
+        write(
+            root,
+            "crates/legacy/Cargo.toml",
+            "[package]\nname = \"legacy\"\n",
+        );
+        write(root, "crates/notes/README.md", "not a crate\n");
+        write(
+            root,
+            "tools/gen/xtask/Cargo.toml",
+            "[package]\nname = \"xtask\"\n",
+        );
+
+        let config = Config::builder().directories([root]).build();
+        let members = members(&config).unwrap();
+        let headings: Vec<String> = members.iter().map(WorkspaceMember::heading).collect();
+        assert_eq!(headings, ["api 0.3.1", "shared-types 2.0.0", "xtask"]);
+        assert_eq!(members[2].path, Path::new("tools/gen/xtask"));
+
+        let workspace = Workspace {
+            selected: Some(select(&members, &["crates/api".to_string()]).unwrap()),
+            members,
+        };
+        assert!(workspace.includes(Path::new("crates/api/src/lib.rs")));
+        assert!(!workspace.includes(Path::new("crates/shared/src/lib.rs")));
+        assert!(!workspace.includes(Path::new("README.md")));
+        assert_eq!(
+            workspace.section(Path::new("tools/gen/xtask/main.rs")),
+            "xtask"
+        );
+        assert_eq!(workspace.section(Path::new("README.md")), ".");
+    }
+
+    #[test]
+    fn roots_that_are_not_workspaces_are_rejected() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let config = Config::builder().directories([root]).build();
+        let err = members(&config).unwrap_err();
+        assert!(format!("{:#}", err).contains("is not a Cargo workspace"));
+
+        write(root, "Cargo.toml", "[package]\nname = \"solo\"\n");
+        let err = members(&config).unwrap_err();
+        assert!(err.to_string().contains("has no [workspace] table"));
+
+        write(
+            root,
+            "Cargo.toml",
+            "[package]\nname = \"app\"\n[workspace]\nmembers = [\"cli\"]\n",
+        );
+        write(root, "cli/Cargo.toml", "[package]\nname = \"app-cli\"\n");
+        let members = members(&config).unwrap();
+        assert_eq!(members[0].path, Path::new(""));
+        let err = select(&members, &["web".to_string()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No workspace member named `web`; the members are: app, app-cli"
+        );
+    }
+}